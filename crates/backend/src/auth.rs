@@ -0,0 +1,162 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use kuma_core::config::{ApiKeyConfig, ApiScope};
+use tracing::warn;
+
+use crate::AppState;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Scopes of the configured API keys, no keys means the API is open to reads and closed to admin
+/// requests.
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeys(Arc<HashMap<String, ApiScope>>);
+
+impl ApiKeys {
+    pub fn new(keys: &[ApiKeyConfig]) -> Self {
+        Self(Arc::new(
+            keys.iter()
+                .map(|key| (key.key.clone(), key.scope))
+                .collect(),
+        ))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether `key` is one of the configured keys, whatever its scope.
+    pub fn contains(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+
+    /// Whether the request's key grants `required`.
+    fn authorize(&self, headers: &HeaderMap, required: ApiScope) -> Result<(), StatusCode> {
+        self.authorize_key(
+            headers
+                .get(API_KEY_HEADER)
+                .and_then(|key| key.to_str().ok()),
+            required,
+        )
+    }
+
+    /// Whether `key` grants `required`.
+    pub fn authorize_key(&self, key: Option<&str>, required: ApiScope) -> Result<(), StatusCode> {
+        if self.is_empty() {
+            // admin routes change what kumad does, so they're never open
+            return match required {
+                ApiScope::Read => Ok(()),
+                ApiScope::Admin => Err(StatusCode::FORBIDDEN),
+            };
+        }
+
+        let key = key.ok_or(StatusCode::UNAUTHORIZED)?;
+        match self.0.get(key) {
+            Some(scope) if *scope >= required => Ok(()),
+            Some(_) => Err(StatusCode::FORBIDDEN),
+            None => Err(StatusCode::UNAUTHORIZED),
+        }
+    }
+}
+
+pub async fn require_read(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    require(&state.api_keys, ApiScope::Read, request, next).await
+}
+
+pub async fn require_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    require(&state.api_keys, ApiScope::Admin, request, next).await
+}
+
+async fn require(keys: &ApiKeys, scope: ApiScope, request: Request, next: Next) -> Response {
+    match keys.authorize(request.headers(), scope) {
+        Ok(()) => next.run(request).await,
+        Err(status) => {
+            warn!(path = %request.uri().path(), ?scope, %status, "Rejected API request");
+            (
+                status,
+                Json(serde_json::json!({
+                    "error": status.canonical_reason().unwrap_or("Unauthorized"),
+                    "message": format!("a {API_KEY_HEADER} header with {scope:?} scope is required")
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> ApiKeys {
+        ApiKeys::new(&[
+            ApiKeyConfig {
+                key: "reader".to_string(),
+                scope: ApiScope::Read,
+            },
+            ApiKeyConfig {
+                key: "operator".to_string(),
+                scope: ApiScope::Admin,
+            },
+        ])
+    }
+
+    fn headers(key: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(API_KEY_HEADER, key.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_no_keys_allows_reads_only() {
+        let keys = ApiKeys::default();
+        assert_eq!(keys.authorize(&HeaderMap::new(), ApiScope::Read), Ok(()));
+        assert_eq!(
+            keys.authorize(&HeaderMap::new(), ApiScope::Admin),
+            Err(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            keys.authorize(&headers("operator"), ApiScope::Admin),
+            Err(StatusCode::FORBIDDEN)
+        );
+    }
+
+    #[test]
+    fn test_scopes() {
+        let keys = keys();
+        assert_eq!(keys.authorize(&headers("reader"), ApiScope::Read), Ok(()));
+        assert_eq!(
+            keys.authorize(&headers("reader"), ApiScope::Admin),
+            Err(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(keys.authorize(&headers("operator"), ApiScope::Read), Ok(()));
+        assert_eq!(
+            keys.authorize(&headers("operator"), ApiScope::Admin),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_missing_or_unknown_key() {
+        let keys = keys();
+        assert_eq!(
+            keys.authorize(&HeaderMap::new(), ApiScope::Read),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            keys.authorize(&headers("guess"), ApiScope::Read),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+}
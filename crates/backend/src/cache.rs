@@ -0,0 +1,110 @@
+use std::{
+    hash::{DefaultHasher, Hash as _, Hasher as _},
+    time::Duration,
+};
+
+use axum::{
+    body::{self, Body},
+    extract::Request,
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::error;
+
+/// Time after which data recorded for a past range isn't expected to change anymore, covering
+/// late blocks and the candle aggregation lag
+pub const SETTLE_TIME: Duration = Duration::from_secs(60 * 60);
+
+/// Cache policy of responses for ranges that ended more than [`SETTLE_TIME`] ago
+pub const IMMUTABLE: &str = "private, max-age=31536000, immutable";
+/// Cache policy of everything else, which clients may keep but must revalidate
+pub const REVALIDATE: &str = "no-cache";
+
+/// Whether a range ending at `end` (unix seconds) has settled by `now`.
+pub fn settled(end: u64, now: u64) -> bool {
+    end.saturating_add(SETTLE_TIME.as_secs()) <= now
+}
+
+/// Tags successful `GET` responses with a weak ETag of their body and answers `304 Not Modified`
+/// when the client already holds it. Responses without a cache policy must be revalidated.
+pub async fn etag(request: Request, next: Next) -> Response {
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+    let method = request.method().clone();
+
+    let response = next.run(request).await;
+    if method != Method::GET || response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!(error = %e, "Failed to buffer response body");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    let etag = format!("W/\"{:016x}\"", hasher.finish());
+
+    parts
+        .headers
+        .entry(header::CACHE_CONTROL)
+        .or_insert(HeaderValue::from_static(REVALIDATE));
+    parts.headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag).expect("hex etag is a valid header value"),
+    );
+
+    let not_modified = if_none_match
+        .as_ref()
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| etag_matches(value, &etag));
+    if not_modified {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_LENGTH);
+        return Response::from_parts(parts, Body::empty());
+    }
+
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Weak comparison of an `If-None-Match` header against `etag`.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_owned();
+
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_etag_matches() {
+        let etag = "W/\"00000000deadbeef\"";
+
+        assert!(etag_matches(etag, etag));
+        assert!(etag_matches("\"00000000deadbeef\"", etag));
+        assert!(etag_matches(
+            "\"0000000000000000\", W/\"00000000deadbeef\"",
+            etag
+        ));
+        assert!(etag_matches("*", etag));
+        assert!(!etag_matches("W/\"0000000000000000\"", etag));
+    }
+
+    #[test]
+    fn test_settled() {
+        let now = 1_718_000_000;
+
+        assert!(settled(now - SETTLE_TIME.as_secs(), now));
+        assert!(!settled(now - 60, now));
+        assert!(!settled(u64::MAX, now));
+    }
+}
@@ -0,0 +1,277 @@
+//! gRPC flavour of the signal and spot price routes, see `proto/kuma.proto`.
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    pin::Pin,
+    time::Duration,
+};
+
+use axum::http::StatusCode;
+use color_eyre::eyre::{self, eyre};
+use kuma_core::{
+    config::ApiScope,
+    database::{SignalFilter, SignalSort, SortOrder},
+    signals::CrossChainSingleHop,
+    spot_prices::SpotPrices,
+    strategy::Swap,
+};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt as _};
+use tonic::{transport::Server, Request, Response, Status};
+use tracing::{debug, info};
+
+use crate::{
+    auth::API_KEY_HEADER,
+    models::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE},
+    pair::parse_pair,
+    AppState,
+};
+
+pub mod proto {
+    tonic::include_proto!("kuma.v1");
+}
+
+use proto::kuma_server::{Kuma, KumaServer};
+
+/// Serves the gRPC API on `addr` until the server fails or `state.shutdown` is cancelled.
+pub async fn serve(addr: SocketAddr, state: AppState) -> eyre::Result<()> {
+    info!("🚀 Kuma gRPC server running at {addr}");
+
+    let api_keys = state.api_keys.clone();
+    let rate_limiter = state.rate_limiter.clone();
+    let shutdown = state.shutdown.clone();
+    let service =
+        KumaServer::with_interceptor(KumaService { state }, move |request: Request<()>| {
+            let key = request
+                .metadata()
+                .get(API_KEY_HEADER)
+                .and_then(|key| key.to_str().ok());
+            let ip = request
+                .remote_addr()
+                .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |addr| addr.ip());
+            if let Err((label, retry_after)) = rate_limiter.check_request(&api_keys, key, ip) {
+                debug!(client = label, ?retry_after, "Throttled gRPC request");
+                metrics::counter!("kuma_grpc_throttled_requests_total", "limit" => label)
+                    .increment(1);
+                return Err(Status::resource_exhausted(format!(
+                    "rate limit exceeded, retry in {}s",
+                    retry_after.as_secs().max(1)
+                )));
+            }
+            match api_keys.authorize_key(key, ApiScope::Read) {
+                Ok(()) => Ok(request),
+                Err(StatusCode::FORBIDDEN) => Err(Status::permission_denied("insufficient scope")),
+                Err(_) => Err(Status::unauthenticated(format!(
+                    "a valid {API_KEY_HEADER} is required"
+                ))),
+            }
+        });
+
+    Server::builder()
+        .add_service(service)
+        .serve_with_shutdown(addr, shutdown.cancelled_owned())
+        .await
+        .map_err(|e| eyre!("grpc server failed: {e:}"))
+}
+
+struct KumaService {
+    state: AppState,
+}
+
+type SignalStream = Pin<Box<dyn Stream<Item = Result<proto::Signal, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Kuma for KumaService {
+    async fn get_signals(
+        &self,
+        request: Request<proto::GetSignalsRequest>,
+    ) -> Result<Response<proto::GetSignalsResponse>, Status> {
+        let request = request.into_inner();
+        let time =
+            |secs: Option<u64>| secs.map(|secs| std::time::UNIX_EPOCH + Duration::from_secs(secs));
+        let chain = |name: Option<&str>| {
+            name.map(|name| self.state.db.chain(&name.to_lowercase()))
+                .transpose()
+                .map_err(|e| Status::invalid_argument(format!("invalid chain: {e}")))
+        };
+
+        let filter = SignalFilter {
+            slow_chain: chain(request.slow_chain.as_deref())?,
+            fast_chain: chain(request.fast_chain.as_deref())?,
+            pair: request.pair.as_deref().map(pair_symbols).transpose()?,
+            since: time(request.from),
+            until: time(request.to),
+            ..Default::default()
+        };
+        let (limit, offset) = (page_limit(request.limit), request.offset);
+
+        let repo = self.state.db.signal_repository();
+        let (total, signals) = tokio::join!(
+            repo.count_filtered(&filter),
+            repo.get_filtered(
+                &filter,
+                SignalSort::CreatedAt,
+                SortOrder::Desc,
+                limit,
+                offset
+            )
+        );
+
+        Ok(Response::new(proto::GetSignalsResponse {
+            signals: signals
+                .map_err(database_error)?
+                .iter()
+                .map(Into::into)
+                .collect(),
+            total: total.map_err(database_error)?,
+        }))
+    }
+
+    type StreamSignalsStream = SignalStream;
+
+    async fn stream_signals(
+        &self,
+        request: Request<proto::StreamSignalsRequest>,
+    ) -> Result<Response<Self::StreamSignalsStream>, Status> {
+        let pair = request
+            .into_inner()
+            .pair
+            .as_deref()
+            .map(pair_symbols)
+            .transpose()?;
+
+        let signals =
+            BroadcastStream::new(self.state.signals_tx.subscribe()).filter_map(move |signal| {
+                match signal {
+                    Ok(signal) if pair.as_ref().is_none_or(|pair| trades_pair(&signal, pair)) => {
+                        Some(Ok(proto::Signal::from(&signal)))
+                    }
+                    Ok(_) => None,
+                    Err(e) => Some(Err(Status::data_loss(e.to_string()))),
+                }
+            });
+        // end the stream on shutdown so the server doesn't wait on it
+        let signals =
+            futures::StreamExt::take_until(signals, self.state.shutdown.clone().cancelled_owned());
+
+        Ok(Response::new(Box::pin(signals)))
+    }
+
+    async fn get_spot_prices(
+        &self,
+        request: Request<proto::GetSpotPricesRequest>,
+    ) -> Result<Response<proto::GetSpotPricesResponse>, Status> {
+        let request = request.into_inner();
+        let (token_a, token_b) = pair_symbols(&request.pair)?;
+        let limit = page_limit(request.limit);
+
+        let repo = self.state.db.spot_price_repository();
+        let (total, spot_prices) = tokio::join!(
+            repo.count_by_symbols(&token_a, &token_b),
+            repo.get_by_symbols(&token_a, &token_b, limit, request.offset)
+        );
+
+        Ok(Response::new(proto::GetSpotPricesResponse {
+            spot_prices: spot_prices
+                .map_err(database_error)?
+                .iter()
+                .map(Into::into)
+                .collect(),
+            total: total.map_err(database_error)?,
+        }))
+    }
+}
+
+fn pair_symbols(pair: &str) -> Result<(String, String), Status> {
+    parse_pair(&pair.to_uppercase())
+        .map_err(|e| Status::invalid_argument(format!("invalid pair '{pair}': {e}")))
+}
+
+fn page_limit(limit: u32) -> u32 {
+    match limit {
+        0 => DEFAULT_PAGE_SIZE,
+        limit => limit.min(MAX_PAGE_SIZE),
+    }
+}
+
+fn database_error(e: eyre::Report) -> Status {
+    tracing::error!("gRPC database query failed: {}", e);
+    Status::internal("database error")
+}
+
+/// Whether the signal trades the pair's symbols, in either order
+fn trades_pair(signal: &CrossChainSingleHop, (token_a, token_b): &(String, String)) -> bool {
+    let swap = &signal.slow_swap_sim;
+    (swap.token_in.symbol == *token_a && swap.token_out.symbol == *token_b)
+        || (swap.token_in.symbol == *token_b && swap.token_out.symbol == *token_a)
+}
+
+impl From<&CrossChainSingleHop> for proto::Signal {
+    fn from(signal: &CrossChainSingleHop) -> Self {
+        Self {
+            slow_chain: signal.slow_chain.name.to_string(),
+            slow_height: signal.slow_height,
+            slow_pool_id: signal.slow_pool_id.to_string(),
+            slow_swap: Some((&signal.slow_swap_sim).into()),
+            fast_chain: signal.fast_chain.name.to_string(),
+            fast_height: signal.fast_height,
+            fast_pool_id: signal.fast_pool_id.to_string(),
+            fast_swap: Some((&signal.fast_swap_sim).into()),
+            surplus_a: signal.surplus.0.to_string(),
+            surplus_b: signal.surplus.1.to_string(),
+            expected_profit_a: signal.expected_profit.0.to_string(),
+            expected_profit_b: signal.expected_profit.1.to_string(),
+            max_slippage_bps: signal.max_slippage_bps,
+            congestion_risk_discount_bps: signal.congestion_risk_discount_bps,
+        }
+    }
+}
+
+impl From<&Swap> for proto::Swap {
+    fn from(swap: &Swap) -> Self {
+        Self {
+            token_in: swap.token_in.symbol.clone(),
+            amount_in: swap.amount_in.to_string(),
+            token_out: swap.token_out.symbol.clone(),
+            amount_out: swap.amount_out.to_string(),
+            gas_cost: swap.gas_cost.to_string(),
+        }
+    }
+}
+
+impl From<&SpotPrices> for proto::SpotPrice {
+    fn from(spot_prices: &SpotPrices) -> Self {
+        Self {
+            chain: spot_prices.chain.name.to_string(),
+            token_a: spot_prices.pair.token_a().symbol.clone(),
+            token_b: spot_prices.pair.token_b().symbol.clone(),
+            block_height: spot_prices.block_height,
+            min_price: spot_prices.min_price,
+            max_price: spot_prices.max_price,
+            min_pool_id: spot_prices.min_pool_id.to_string(),
+            max_pool_id: spot_prices.max_pool_id.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_limit() {
+        assert_eq!(page_limit(0), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_limit(5), 5);
+        assert_eq!(page_limit(10_000), MAX_PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_pair_symbols() {
+        assert_eq!(
+            pair_symbols("weth-usdc").unwrap(),
+            ("WETH".to_string(), "USDC".to_string())
+        );
+        assert_eq!(
+            pair_symbols("WETH").unwrap_err().code(),
+            tonic::Code::InvalidArgument
+        );
+    }
+}
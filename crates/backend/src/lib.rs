@@ -1,47 +1,233 @@
+mod auth;
+mod cache;
+pub mod grpc;
 pub mod models;
 pub mod pair;
+mod rate_limit;
+mod request_id;
 mod routes;
+mod telemetry;
 
-use axum::Router;
+use axum::{
+    http::{HeaderName, HeaderValue, Method},
+    middleware,
+    routing::get,
+    Router,
+};
 use color_eyre::eyre::{self, eyre};
 use routes::spot_prices;
-use tower_http::cors::CorsLayer;
-use tracing::info;
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{AllowOrigin, CorsLayer},
+};
+use tracing::{info, warn};
 
-use std::sync::Arc;
+use std::{net::SocketAddr, path::Path, sync::Arc};
 
 use kuma_core::{
-    config::Config,
+    config::{Config, CorsConfig, StrategyConfig},
     database::{self, Handle},
+    signals::CrossChainSingleHop,
 };
+use metrics_exporter_prometheus::PrometheusHandle;
+
+use auth::ApiKeys;
+use rate_limit::RateLimiter;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Handle,
+    /// Directory Parquet exports are written under
+    pub export_dir: Arc<Path>,
+    /// Signals inserted into the database, streamed to `/signals/stream` clients
+    pub signals_tx: broadcast::Sender<CrossChainSingleHop>,
+    /// Renders the process' metrics for `/metrics`
+    pub metrics: PrometheusHandle,
+    pub api_keys: ApiKeys,
+    pub rate_limiter: RateLimiter,
+    /// Configured strategies, which can be paused through `/admin`
+    pub strategies: Arc<[StrategyConfig]>,
+    /// kumad's admin socket, behind `/admin/daemon`
+    pub admin_socket: Option<Arc<Path>>,
+    /// Cancelled when the server shuts down, ending long-lived streams
+    pub shutdown: CancellationToken,
 }
 
-pub async fn spawn(config: Config) -> eyre::Result<()> {
+/// Serves the API until `shutdown` is cancelled, then waits for in-flight requests to finish and
+/// closes the database connections.
+pub async fn spawn(config: Config, shutdown: CancellationToken) -> eyre::Result<()> {
     let (token_configs, _) = config
         .build_addrs_and_inventory()
         .map_err(|e| eyre!("failed to parse chain assets: {}", e))?;
 
+    let metrics = kuma_core::telemetry::install_prometheus_recorder()?;
+
     let db_handle =
-        database::Handle::from_config(config.database.clone(), Arc::new(token_configs.clone()))?;
-    let state = AppState { db: db_handle };
-    let cors = CorsLayer::permissive();
+        database::Handle::from_config(config.database.clone(), Arc::new(token_configs.clone()))
+            .await?;
+    let state = AppState {
+        signals_tx: routes::signals::spawn_feed(db_handle.clone(), shutdown.clone()),
+        db: db_handle,
+        export_dir: Arc::from(config.server.export_dir.as_path()),
+        metrics,
+        api_keys: ApiKeys::new(&config.server.api_keys),
+        rate_limiter: RateLimiter::new(config.server.rate_limit.as_ref()),
+        strategies: Arc::from(config.strategies.as_slice()),
+        admin_socket: config.admin_socket.as_deref().map(Arc::from),
+        shutdown: shutdown.clone(),
+    };
+    if state.api_keys.is_empty() {
+        warn!("No API keys configured, the API is open to anyone");
+    }
+    let grpc_server = config
+        .server
+        .grpc_port
+        .map(|port| {
+            let addr = format!("{}:{}", config.server.host, port)
+                .parse::<SocketAddr>()
+                .map_err(|e| eyre!("invalid grpc address: {e:}"))?;
+            eyre::Ok((addr, state.clone()))
+        })
+        .transpose()?;
+    let cors = cors_layer(&config.server.cors)?;
 
-    let app = Router::new()
+    let read = Router::new()
         .nest("/spot_prices", spot_prices::routes())
         .nest("/signals", routes::signals::routes())
+        .nest("/executions", routes::executions::routes())
+        .nest("/pnl", routes::pnl::routes())
+        .nest("/inventory", routes::inventory::routes())
+        .nest("/meta", routes::meta::routes())
+        .nest("/spreads", routes::spreads::routes())
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_read,
+        ));
+    let admin = Router::new()
+        .nest("/admin", routes::admin::routes())
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_admin,
+        ));
+
+    let app = Router::new()
+        .merge(read)
+        .merge(admin)
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::limit,
+        ))
+        .route_layer(middleware::from_fn(telemetry::track_requests))
+        .route("/metrics", get(telemetry::get_metrics))
+        .layer(CompressionLayer::new())
         .layer(cors)
-        .with_state(state);
+        .layer(middleware::from_fn(request_id::propagate))
+        .with_state(state.clone());
 
     let bind_addr = format!("{}:{}", config.server.host, config.server.port);
     info!("🚀 Kuma API server running at http://{bind_addr}");
 
     let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
 
-    axum::serve(listener, app)
+    let rest = async {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown.clone().cancelled_owned())
         .await
         .map_err(|e| eyre!("axum server failed: {e:}"))
+    };
+    let grpc = async {
+        match grpc_server {
+            Some((addr, state)) => grpc::serve(addr, state).await,
+            None => Ok(()),
+        }
+    };
+    let served = tokio::try_join!(rest, grpc);
+
+    // a failed server takes the other one down with it
+    shutdown.cancel();
+    info!("Closing database connections");
+    state.db.close().await;
+
+    served.map(|_| ())
+}
+
+/// Builds the CORS policy from `config`, failing on origins, methods or headers that don't parse.
+fn cors_layer(config: &CorsConfig) -> eyre::Result<CorsLayer> {
+    if config.permissive {
+        warn!("CORS is permissive, any website can call the API");
+        return Ok(CorsLayer::permissive());
+    }
+
+    let origins = config
+        .allowed_origins
+        .iter()
+        .map(|origin| {
+            origin
+                .parse::<HeaderValue>()
+                .map_err(|e| eyre!("invalid cors origin {origin}: {e:}"))
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    let methods = if config.allowed_methods.is_empty() {
+        vec![Method::GET, Method::POST]
+    } else {
+        config
+            .allowed_methods
+            .iter()
+            .map(|method| {
+                method
+                    .to_uppercase()
+                    .parse::<Method>()
+                    .map_err(|e| eyre!("invalid cors method {method}: {e:}"))
+            })
+            .collect::<eyre::Result<Vec<_>>>()?
+    };
+    let headers = config
+        .allowed_headers
+        .iter()
+        .map(|header| {
+            header
+                .parse::<HeaderName>()
+                .map_err(|e| eyre!("invalid cors header {header}: {e:}"))
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    Ok(CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods(methods)
+        .allow_headers(headers)
+        .expose_headers([request_id::REQUEST_ID_HEADER]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cors_layer_rejects_invalid_config() {
+        let valid = CorsConfig {
+            allowed_origins: vec!["https://dashboard.example.com".to_string()],
+            allowed_methods: vec!["get".to_string()],
+            allowed_headers: vec![auth::API_KEY_HEADER.to_string()],
+            permissive: false,
+        };
+        assert!(cors_layer(&valid).is_ok());
+        assert!(cors_layer(&CorsConfig::default()).is_ok());
+
+        let invalid_header = CorsConfig {
+            allowed_headers: vec!["not a header".to_string()],
+            ..valid.clone()
+        };
+        assert!(cors_layer(&invalid_header).is_err());
+
+        let invalid_method = CorsConfig {
+            allowed_methods: vec!["GET POST".to_string()],
+            ..valid
+        };
+        assert!(cors_layer(&invalid_method).is_err());
+    }
 }
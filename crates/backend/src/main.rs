@@ -1,15 +1,34 @@
 use color_eyre::eyre;
 use kuma_backend::spawn;
-use kuma_core::config::Config;
+use kuma_core::{
+    config::Config,
+    telemetry::{self, TelemetrySettings},
+};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
     color_eyre::install()?;
-    tracing_subscriber::fmt::init();
 
     let config = Config::load()?;
+    config.validate()?;
+    // kept alive to flush pending spans on exit
+    let _otlp = telemetry::init_logging(&TelemetrySettings::from_config("kuma-backend", &config))?;
 
-    let res = spawn(config).await?;
+    let shutdown = CancellationToken::new();
+    let mut sigterm = signal(SignalKind::terminate())?;
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            tokio::select! {
+                _ = sigterm.recv() => info!("received SIGTERM, shutting down"),
+                _ = tokio::signal::ctrl_c() => info!("received SIGINT, shutting down"),
+            }
+            shutdown.cancel();
+        }
+    });
 
-    Ok(res)
+    spawn(config, shutdown).await
 }
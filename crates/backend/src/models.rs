@@ -1,3 +1,5 @@
+use std::{fmt::Display, str::FromStr};
+
 use serde::{Deserialize, Serialize};
 
 pub const DEFAULT_PAGE_SIZE: u32 = 20;
@@ -21,15 +23,19 @@ pub struct PaginationInfo {
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct PaginationQuery {
-    #[serde(deserialize_with = "deserialize_optional_u32", default)]
+    #[serde(deserialize_with = "deserialize_optional", default)]
     pub page: Option<u32>,
-    #[serde(deserialize_with = "deserialize_optional_u32", default)]
+    #[serde(deserialize_with = "deserialize_optional", default)]
     pub page_size: Option<u32>,
 }
 
-fn deserialize_optional_u32<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+/// Parses an optional query parameter from its string form. Needed for anything but strings in
+/// queries that are `#[serde(flatten)]`ed, which hands every value over as a string.
+pub(crate) fn deserialize_optional<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
 where
     D: serde::Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
 {
     use serde::de::Error;
     match Option::<String>::deserialize(deserializer)? {
@@ -0,0 +1,262 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use kuma_core::config::{RateLimit, RateLimitConfig};
+use tracing::debug;
+
+use crate::{
+    auth::{ApiKeys, API_KEY_HEADER},
+    AppState,
+};
+
+/// Clients tracked before buckets that refilled completely are forgotten
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Who a request is counted against.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Client {
+    Ip(IpAddr),
+    Key(String),
+}
+
+impl Client {
+    /// The request's API key if it's a valid one, its IP otherwise
+    fn of(api_keys: &ApiKeys, key: Option<&str>, ip: IpAddr) -> Self {
+        match key {
+            Some(key) if api_keys.contains(key) => Client::Key(key.to_owned()),
+            _ => Client::Ip(ip),
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Client::Ip(_) => "ip",
+            Client::Key(_) => "key",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn full(limit: &RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.burst as f64,
+            refilled_at: now,
+        }
+    }
+
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.requests_per_second).min(limit.burst as f64);
+        self.refilled_at = now;
+    }
+
+    /// Takes a token, or returns how long until one is available.
+    fn take(&mut self, limit: &RateLimit, now: Instant) -> Result<(), Duration> {
+        self.refill(limit, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            // config validation rejects limits that never refill, they'd wait forever
+            Err(
+                Duration::try_from_secs_f64((1.0 - self.tokens) / limit.requests_per_second)
+                    .unwrap_or(Duration::MAX),
+            )
+        }
+    }
+}
+
+/// Per-IP and per-key token buckets, no limits means nobody is throttled.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    per_ip: Option<RateLimit>,
+    per_key: Option<RateLimit>,
+    buckets: Arc<Mutex<HashMap<Client, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: Option<&RateLimitConfig>) -> Self {
+        Self {
+            per_ip: config.and_then(|config| config.per_ip),
+            per_key: config.and_then(|config| config.per_key),
+            buckets: Arc::default(),
+        }
+    }
+
+    fn limit(&self, client: &Client) -> Option<RateLimit> {
+        match client {
+            Client::Ip(_) => self.per_ip,
+            Client::Key(_) => self.per_key,
+        }
+    }
+
+    /// Counts a request against `client`, returning how long it has to back off if it's over
+    /// its limit.
+    fn check(&self, client: Client, now: Instant) -> Result<(), Duration> {
+        let Some(limit) = self.limit(&client) else {
+            return Ok(());
+        };
+
+        let mut buckets = self
+            .buckets
+            .lock()
+            .expect("rate limit buckets lock poisoned");
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&client) {
+            buckets.retain(|client, bucket| {
+                let limit = self
+                    .limit(client)
+                    .expect("only limited clients are tracked");
+                bucket.refill(&limit, now);
+                bucket.tokens < limit.burst as f64
+            });
+        }
+
+        buckets
+            .entry(client)
+            .or_insert_with(|| Bucket::full(&limit, now))
+            .take(&limit, now)
+    }
+
+    /// Counts a request from `ip` carrying `key`, returning the limit it's over, `ip` or `key`, and
+    /// how long it has to back off.
+    pub fn check_request(
+        &self,
+        api_keys: &ApiKeys,
+        key: Option<&str>,
+        ip: IpAddr,
+    ) -> Result<(), (&'static str, Duration)> {
+        let client = Client::of(api_keys, key, ip);
+        let label = client.label();
+        self.check(client, Instant::now())
+            .map_err(|retry_after| (label, retry_after))
+    }
+}
+
+/// Throttles requests once their API key, or their IP if they don't carry a valid key, runs out
+/// of tokens.
+pub async fn limit(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let key = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|key| key.to_str().ok());
+
+    match state
+        .rate_limiter
+        .check_request(&state.api_keys, key, addr.ip())
+    {
+        Ok(()) => next.run(request).await,
+        Err((label, retry_after)) => {
+            debug!(path = %request.uri().path(), client = label, ?retry_after, "Throttled request");
+            metrics::counter!("kuma_http_throttled_requests_total", "limit" => label).increment(1);
+            let retry_secs = retry_after.as_secs().max(1);
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_secs.to_string())],
+                Json(serde_json::json!({
+                    "error": "Too Many Requests",
+                    "message": format!("rate limit exceeded, retry in {retry_secs}s")
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kuma_core::config::{ApiKeyConfig, ApiScope};
+
+    use super::*;
+
+    const LIMIT: RateLimit = RateLimit {
+        requests_per_second: 1.0,
+        burst: 2,
+    };
+
+    fn ip(last: u8) -> Client {
+        Client::Ip(IpAddr::from([10, 0, 0, last]))
+    }
+
+    #[test]
+    fn test_unlimited_by_default() {
+        let limiter = RateLimiter::default();
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(limiter.check(ip(1), now), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_burst_then_refill() {
+        let limiter = RateLimiter::new(Some(&RateLimitConfig {
+            per_ip: Some(LIMIT),
+            per_key: None,
+        }));
+        let now = Instant::now();
+
+        assert_eq!(limiter.check(ip(1), now), Ok(()));
+        assert_eq!(limiter.check(ip(1), now), Ok(()));
+        assert_eq!(limiter.check(ip(1), now), Err(Duration::from_secs(1)));
+        // other clients have their own bucket
+        assert_eq!(limiter.check(ip(2), now), Ok(()));
+        // keys aren't limited
+        assert_eq!(
+            limiter.check(Client::Key("reader".to_string()), now),
+            Ok(())
+        );
+
+        assert_eq!(limiter.check(ip(1), now + Duration::from_secs(1)), Ok(()));
+    }
+
+    #[test]
+    fn test_limit_without_refill_does_not_panic() {
+        let limit = RateLimit {
+            requests_per_second: 0.0,
+            burst: 0,
+        };
+        let now = Instant::now();
+        let mut bucket = Bucket::full(&limit, now);
+
+        assert_eq!(bucket.take(&limit, now), Err(Duration::MAX));
+    }
+
+    #[test]
+    fn test_only_valid_keys_count_against_the_key_limit() {
+        let api_keys = ApiKeys::new(&[ApiKeyConfig {
+            key: "reader".to_string(),
+            scope: ApiScope::Read,
+        }]);
+        let ip = IpAddr::from([10, 0, 0, 1]);
+
+        assert_eq!(
+            Client::of(&api_keys, Some("reader"), ip),
+            Client::Key("reader".to_string())
+        );
+        assert_eq!(Client::of(&api_keys, Some("guess"), ip), Client::Ip(ip));
+        assert_eq!(Client::of(&api_keys, None, ip), Client::Ip(ip));
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    hash::{BuildHasher as _, RandomState},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock,
+    },
+};
+
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{info_span, Instrument as _};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest request id accepted from clients, longer ones are replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Id correlating a request across services, available to handlers as an extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub HeaderValue);
+
+impl RequestId {
+    /// The client's id if it sent a usable one, a new one otherwise.
+    fn from_header(value: Option<&HeaderValue>) -> Self {
+        match value {
+            Some(value) if is_valid(value) => Self(value.clone()),
+            _ => Self::generate(),
+        }
+    }
+
+    fn generate() -> Self {
+        // keyed randomly per process, so ids don't repeat across restarts
+        static HASHER: LazyLock<RandomState> = LazyLock::new(RandomState::new);
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let id = HASHER.hash_one(COUNTER.fetch_add(1, Ordering::Relaxed));
+        Self(HeaderValue::from_str(&format!("{id:016x}")).expect("hex is a valid header value"))
+    }
+
+    fn as_str(&self) -> &str {
+        self.0.to_str().expect("request ids are visible ascii")
+    }
+}
+
+fn is_valid(value: &HeaderValue) -> bool {
+    let bytes = value.as_bytes();
+    !bytes.is_empty()
+        && bytes.len() <= MAX_REQUEST_ID_LEN
+        && bytes.iter().all(|byte| byte.is_ascii_graphic())
+}
+
+/// Takes the request's `x-request-id` or assigns one, records everything logged while handling it
+/// under a span carrying the id and returns the id in the response.
+pub async fn propagate(mut request: Request, next: Next) -> Response {
+    let request_id = RequestId::from_header(request.headers().get(&REQUEST_ID_HEADER));
+    request
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, request_id.0.clone());
+    request.extensions_mut().insert(request_id.clone());
+
+    let span = info_span!(
+        "request",
+        request_id = request_id.as_str(),
+        method = %request.method(),
+        path = %request.uri().path(),
+    );
+    let mut response = next.run(request).instrument(span).await;
+
+    response
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, request_id.0);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_id_from_header() {
+        let client = HeaderValue::from_static("3f2b9c1e-trace");
+        assert_eq!(RequestId::from_header(Some(&client)).0, client);
+
+        for invalid in ["", "has spaces", &"a".repeat(MAX_REQUEST_ID_LEN + 1)] {
+            let invalid = HeaderValue::from_str(invalid).unwrap();
+            assert_ne!(RequestId::from_header(Some(&invalid)).0, invalid);
+        }
+
+        let generated = RequestId::from_header(None);
+        assert_eq!(generated.as_str().len(), 16);
+        assert_ne!(generated, RequestId::from_header(None));
+    }
+}
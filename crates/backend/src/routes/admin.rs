@@ -0,0 +1,269 @@
+use std::path::PathBuf;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use kuma_core::{
+    admin::{self, DaemonStatus, Request},
+    database::{Control, ControlTarget, Dataset, ExportRequest, ExportSummary, WorkerHeartbeat},
+};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct ExportBody {
+    /// Start of the time range, in seconds since the unix epoch
+    pub from: u64,
+    /// Inclusive end of the time range, in seconds since the unix epoch
+    pub to: u64,
+    /// Datasets to export, all of them if omitted
+    #[serde(default)]
+    pub datasets: Option<Vec<Dataset>>,
+}
+
+/// Exports a time range of stored data to Parquet files under the server's export directory
+pub async fn export(
+    State(state): State<AppState>,
+    Json(body): Json<ExportBody>,
+) -> Result<Json<ExportSummary>, Response> {
+    info!(from = body.from, to = body.to, datasets = ?body.datasets, "Exporting datasets");
+
+    let request = ExportRequest {
+        datasets: body.datasets.unwrap_or_else(|| Dataset::ALL.to_vec()),
+        from: body.from,
+        to: body.to,
+        dir: PathBuf::from(state.export_dir.as_ref()),
+    };
+
+    match state.db.export_parquet(&request).await {
+        Ok(summary) => Ok(Json(summary)),
+        Err(e) => {
+            tracing::error!("Failed to export datasets: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Export failed",
+                    "message": e.to_string()
+                })),
+            )
+                .into_response())
+        }
+    }
+}
+
+/// Every control that was set, paused or not
+pub async fn get_controls(State(state): State<AppState>) -> Result<Json<Vec<Control>>, Response> {
+    match state.db.control_repository().get_all().await {
+        Ok(controls) => Ok(Json(controls)),
+        Err(e) => {
+            tracing::error!("Failed to fetch controls: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database error",
+                    "message": "Failed to fetch controls"
+                })),
+            )
+                .into_response())
+        }
+    }
+}
+
+/// When each of kumad's workers last made progress, as last reported by kumad
+pub async fn get_status(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<WorkerHeartbeat>>, Response> {
+    match state.db.heartbeat_repository().get_all().await {
+        Ok(heartbeats) => Ok(Json(heartbeats)),
+        Err(e) => {
+            tracing::error!("Failed to fetch worker heartbeats: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database error",
+                    "message": "Failed to fetch worker heartbeats"
+                })),
+            )
+                .into_response())
+        }
+    }
+}
+
+/// Stops the strategy from generating signals until it's resumed
+pub async fn pause_strategy(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Control>, Response> {
+    set_strategy_paused(&state, id, true).await
+}
+
+pub async fn resume_strategy(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Control>, Response> {
+    set_strategy_paused(&state, id, false).await
+}
+
+/// Stops every strategy from emitting signals until it's resumed. Signals are still generated
+/// and stored.
+pub async fn pause_execution(State(state): State<AppState>) -> Result<Json<Control>, Response> {
+    set_paused(&state, ControlTarget::Execution, true).await
+}
+
+pub async fn resume_execution(State(state): State<AppState>) -> Result<Json<Control>, Response> {
+    set_paused(&state, ControlTarget::Execution, false).await
+}
+
+async fn set_strategy_paused(
+    state: &AppState,
+    id: String,
+    paused: bool,
+) -> Result<Json<Control>, Response> {
+    if !state.strategies.iter().any(|strategy| strategy.id() == id) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "error": "Unknown strategy",
+                "message": format!("No strategy '{}' is configured", id)
+            })),
+        )
+            .into_response());
+    }
+
+    set_paused(state, ControlTarget::Strategy(id), paused).await
+}
+
+async fn set_paused(
+    state: &AppState,
+    target: ControlTarget,
+    paused: bool,
+) -> Result<Json<Control>, Response> {
+    info!(%target, paused, "Setting control");
+
+    match state
+        .db
+        .control_repository()
+        .set_paused(&target, paused)
+        .await
+    {
+        Ok(control) => Ok(Json(control)),
+        Err(e) => {
+            tracing::error!("Failed to set control {}: {}", target, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database error",
+                    "message": format!("Failed to set control {}", target)
+                })),
+            )
+                .into_response())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MinProfitBody {
+    /// Least expected profit, in bps of the slow swap's amount in, `null` to emit every signal
+    pub min_profit_bps: Option<u64>,
+}
+
+/// What the running kumad reports through its admin socket: its workers' progress as of now, and
+/// the controls it applies
+pub async fn get_daemon_status(
+    State(state): State<AppState>,
+) -> Result<Json<DaemonStatus>, Response> {
+    match daemon_request(&state, &Request::Status).await? {
+        admin::Response::Status(status) => Ok(Json(status)),
+        response => Err(unexpected_response(&response)),
+    }
+}
+
+/// Sets the least expected profit the strategy's signals need to be emitted, until kumad restarts
+pub async fn set_min_profit(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(body): Json<MinProfitBody>,
+) -> Result<StatusCode, Response> {
+    info!(strategy = %id, min_profit_bps = ?body.min_profit_bps, "Setting min profit");
+
+    let request = Request::SetMinProfit {
+        strategy: id,
+        min_profit_bps: body.min_profit_bps,
+    };
+    match daemon_request(&state, &request).await? {
+        admin::Response::Done => Ok(StatusCode::NO_CONTENT),
+        response => Err(unexpected_response(&response)),
+    }
+}
+
+async fn daemon_request(state: &AppState, request: &Request) -> Result<admin::Response, Response> {
+    let Some(socket) = state.admin_socket.as_deref() else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "error": "Admin socket disabled",
+                "message": "No admin_socket is configured"
+            })),
+        )
+            .into_response());
+    };
+
+    admin::request(socket, request).await.map_err(|e| {
+        tracing::error!("kumad admin request failed: {:#}", e);
+        (
+            StatusCode::BAD_GATEWAY,
+            Json(serde_json::json!({
+                "error": "kumad error",
+                "message": format!("{e:#}")
+            })),
+        )
+            .into_response()
+    })
+}
+
+fn unexpected_response(response: &admin::Response) -> Response {
+    tracing::error!("Unexpected kumad admin response: {:?}", response);
+    (
+        StatusCode::BAD_GATEWAY,
+        Json(serde_json::json!({
+            "error": "kumad error",
+            "message": "Unexpected response from kumad"
+        })),
+    )
+        .into_response()
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/export", post(export))
+        .route("/controls", get(get_controls))
+        .route("/status", get(get_status))
+        .route("/strategies/:id/pause", post(pause_strategy))
+        .route("/strategies/:id/resume", post(resume_strategy))
+        .route("/execution/pause", post(pause_execution))
+        .route("/execution/resume", post(resume_execution))
+        .route("/daemon/status", get(get_daemon_status))
+        .route("/daemon/strategies/:id/min_profit", post(set_min_profit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_body_deserialization() {
+        let body: ExportBody =
+            serde_json::from_str(r#"{"from": 10, "to": 20, "datasets": ["signals"]}"#).unwrap();
+        assert_eq!((body.from, body.to), (10, 20));
+        assert_eq!(body.datasets, Some(vec![Dataset::Signals]));
+
+        let body: ExportBody = serde_json::from_str(r#"{"from": 10, "to": 20}"#).unwrap();
+        assert_eq!(body.datasets, None);
+    }
+}
@@ -0,0 +1,183 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use color_eyre::eyre::{self, WrapErr as _};
+use std::time::{Duration, UNIX_EPOCH};
+
+use kuma_core::database::{ExecutionFilter, ExecutionRecord, ExecutionStatus, Handle};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{
+    models::{deserialize_optional, PaginatedResponse, PaginationQuery},
+    AppState,
+};
+
+#[derive(Deserialize)]
+pub struct ExecutionQuery {
+    pub chain: Option<String>,
+    #[serde(deserialize_with = "deserialize_optional", default)]
+    pub status: Option<ExecutionStatus>,
+    /// Inclusive lower bound on when executions were recorded, in unix seconds
+    #[serde(deserialize_with = "deserialize_optional", default)]
+    pub from: Option<u64>,
+    /// Exclusive upper bound on when executions were recorded, in unix seconds
+    #[serde(deserialize_with = "deserialize_optional", default)]
+    pub to: Option<u64>,
+    #[serde(flatten)]
+    pub pagination: PaginationQuery,
+}
+
+impl ExecutionQuery {
+    fn to_filter(&self, db: &Handle) -> eyre::Result<ExecutionFilter> {
+        let time = |secs: Option<u64>| secs.map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+
+        Ok(ExecutionFilter {
+            chain: self
+                .chain
+                .as_deref()
+                .map(|name| db.chain(&name.to_lowercase()))
+                .transpose()
+                .wrap_err("invalid chain")?,
+            status: self.status,
+            since: time(self.from),
+            until: time(self.to),
+        })
+    }
+}
+
+/// Transactions broadcast for signals' legs, newest first
+pub async fn get_executions(
+    State(state): State<AppState>,
+    Query(params): Query<ExecutionQuery>,
+) -> Result<Json<PaginatedResponse<ExecutionRecord>>, Response> {
+    let (page, page_size) = params.pagination.sanitize();
+    let (offset, limit) = params.pagination.to_offset_limit();
+
+    info!(
+        chain = ?params.chain,
+        status = ?params.status,
+        page = %page,
+        page_size = %page_size,
+        "Fetching executions"
+    );
+
+    let filter = match params.to_filter(&state.db) {
+        Ok(filter) => filter,
+        Err(e) => {
+            tracing::error!("Invalid execution filter: {:#}", e);
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": "Invalid filter",
+                    "message": format!("{e:#}")
+                })),
+            )
+                .into_response());
+        }
+    };
+
+    let repo = state.db.execution_repository();
+    let (count_result, data_result) = tokio::join!(
+        repo.count_filtered(&filter),
+        repo.get_filtered(&filter, limit, offset)
+    );
+
+    match (count_result, data_result) {
+        (Ok(total_count), Ok(executions)) => Ok(Json(PaginatedResponse::new(
+            executions,
+            page,
+            page_size,
+            Some(total_count),
+        ))),
+        (Err(e), _) | (_, Err(e)) => {
+            tracing::error!("Failed to fetch executions: {}", e);
+            Err(database_error("Failed to fetch executions"))
+        }
+    }
+}
+
+pub async fn get_execution(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<ExecutionRecord>, Response> {
+    info!(id, "Fetching execution");
+
+    match state.db.execution_repository().get_by_id(id).await {
+        Ok(Some(execution)) => Ok(Json(execution)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "error": "Unknown execution",
+                "message": format!("No execution with id {id}")
+            })),
+        )
+            .into_response()),
+        Err(e) => {
+            tracing::error!("Failed to fetch execution {}: {}", id, e);
+            Err(database_error("Failed to fetch execution"))
+        }
+    }
+}
+
+/// Executions of a signal's legs, slow leg first. Empty if the signal wasn't executed.
+pub async fn get_signal_executions(
+    State(state): State<AppState>,
+    Path(signal_id): Path<i64>,
+) -> Result<Json<Vec<ExecutionRecord>>, Response> {
+    info!(signal_id, "Fetching signal executions");
+
+    match state
+        .db
+        .execution_repository()
+        .get_by_signal_id(signal_id)
+        .await
+    {
+        Ok(executions) => Ok(Json(executions)),
+        Err(e) => {
+            tracing::error!("Failed to fetch executions of signal {}: {}", signal_id, e);
+            Err(database_error("Failed to fetch signal executions"))
+        }
+    }
+}
+
+fn database_error(message: &str) -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({
+            "error": "Database error",
+            "message": message
+        })),
+    )
+        .into_response()
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_executions))
+        .route("/:id", get(get_execution))
+        .route("/signal/:signal_id", get(get_signal_executions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execution_query_deserialization() {
+        let parsed: ExecutionQuery =
+            serde_urlencoded::from_str("chain=base&status=reverted&from=1718000000&page_size=5")
+                .unwrap();
+        assert_eq!(parsed.chain, Some("base".to_string()));
+        assert_eq!(parsed.status, Some(ExecutionStatus::Reverted));
+        assert_eq!(parsed.from, Some(1_718_000_000));
+        assert_eq!(parsed.to, None);
+        assert_eq!(parsed.pagination.page_size, Some(5));
+
+        assert!(serde_urlencoded::from_str::<ExecutionQuery>("status=dropped").is_err());
+    }
+}
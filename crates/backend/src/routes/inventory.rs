@@ -0,0 +1,328 @@
+//! Balances of the tracked account per chain, as of the latest block the collector recorded
+//! them at, valued in USD where a price against a stablecoin is available.
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use color_eyre::eyre;
+use kuma_core::database::{BlockRecord, Handle, PoolSpotPrice};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive as _;
+use serde::Serialize;
+use tracing::{debug, info};
+
+use crate::AppState;
+
+/// Tokens valued at one dollar
+const STABLECOINS: &[&str] = &["USDC", "USDT", "DAI"];
+/// Token other tokens are priced against
+const USD_QUOTE: &str = "USDC";
+/// Decimals of every supported chain's native token
+const NATIVE_DECIMALS: u32 = 18;
+
+#[derive(Debug, Serialize)]
+pub struct InventoryResponse {
+    pub chains: Vec<ChainInventory>,
+    /// Sum of every chain's valued balances, `None` if nothing could be valued
+    pub total_usd: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChainInventory {
+    pub chain: String,
+    /// Block the balances were read at
+    pub block_height: u64,
+    pub block_timestamp: u64,
+    pub native: Option<TokenBalance>,
+    pub tokens: Vec<TokenBalance>,
+    /// Sum of the valued balances, `None` if nothing could be valued
+    pub total_usd: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenBalance {
+    pub symbol: String,
+    /// Token contract, `None` for the native token
+    pub address: Option<String>,
+    /// Balance in the token's smallest unit
+    pub balance: String,
+    pub decimals: u32,
+    /// Balance in whole tokens
+    pub amount: f64,
+    pub usd_price: Option<f64>,
+    pub usd_value: Option<f64>,
+}
+
+/// Balances of every chain
+pub async fn get_inventory(
+    State(state): State<AppState>,
+) -> Result<Json<InventoryResponse>, Response> {
+    info!("Fetching inventory");
+
+    let blocks = latest_balances(&state.db).await?;
+    let chains = value_blocks(&state.db, blocks).await?;
+
+    Ok(Json(InventoryResponse {
+        total_usd: sum_known(chains.iter().map(|chain| chain.total_usd)),
+        chains,
+    }))
+}
+
+/// Balances of a single chain
+pub async fn get_chain_inventory(
+    State(state): State<AppState>,
+    Path(chain): Path<String>,
+) -> Result<Json<ChainInventory>, Response> {
+    info!(chain = %chain, "Fetching chain inventory");
+
+    let chain = state.db.chain(&chain).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": "Invalid chain",
+                "message": format!("Unknown chain '{}': {}", chain, e)
+            })),
+        )
+            .into_response()
+    })?;
+
+    let blocks = latest_balances(&state.db)
+        .await?
+        .into_iter()
+        .filter(|block| block.chain == chain)
+        .collect();
+
+    value_blocks(&state.db, blocks)
+        .await?
+        .pop()
+        .map(Json)
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({
+                    "error": "Not found",
+                    "message": format!("No balances recorded on {}", chain.name)
+                })),
+            )
+                .into_response()
+        })
+}
+
+async fn latest_balances(db: &Handle) -> Result<Vec<BlockRecord>, Response> {
+    db.block_repository().latest_balances().await.map_err(|e| {
+        tracing::error!("Failed to fetch balances: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "error": "Database error",
+                "message": "Failed to fetch balances"
+            })),
+        )
+            .into_response()
+    })
+}
+
+async fn value_blocks(
+    db: &Handle,
+    blocks: Vec<BlockRecord>,
+) -> Result<Vec<ChainInventory>, Response> {
+    let mut prices = UsdPrices::new(db);
+    let mut chains = Vec::with_capacity(blocks.len());
+
+    for block in blocks {
+        let chain_name = block.chain.name.to_string();
+        let configured = db.tokens(&block.chain);
+
+        let mut native = None;
+        if let Some(balance) = &block.native_balance {
+            // natives are priced through their wrapped token, e.g. ETH through WETH
+            let symbol = block.chain.native_token().to_string();
+            let usd_price = prices
+                .get(&format!("W{symbol}"), &chain_name)
+                .await
+                .map_err(price_error)?;
+            native = Some(token_balance(
+                symbol,
+                None,
+                balance,
+                NATIVE_DECIMALS,
+                usd_price,
+            ));
+        }
+
+        let mut tokens = Vec::new();
+        for (address, balance) in block.token_balances.iter().flat_map(|b| b.iter()) {
+            let Some(token) = configured
+                .iter()
+                .find(|token| token.address.as_ref() == address.as_slice())
+            else {
+                debug!(chain = %chain_name, %address, "Skipping balance of unconfigured token");
+                continue;
+            };
+            let usd_price = prices
+                .get(&token.symbol, &chain_name)
+                .await
+                .map_err(price_error)?;
+            tokens.push(token_balance(
+                token.symbol.clone(),
+                Some(token.address.to_string()),
+                balance,
+                token.decimals,
+                usd_price,
+            ));
+        }
+        tokens.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+
+        chains.push(ChainInventory {
+            total_usd: sum_known(
+                native
+                    .iter()
+                    .chain(tokens.iter())
+                    .map(|balance| balance.usd_value),
+            ),
+            chain: chain_name,
+            block_height: block.header.height,
+            block_timestamp: block.header.timestamp,
+            native,
+            tokens,
+        });
+    }
+
+    Ok(chains)
+}
+
+fn price_error(e: eyre::Report) -> Response {
+    tracing::error!("Failed to fetch prices: {}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({
+            "error": "Database error",
+            "message": "Failed to fetch prices"
+        })),
+    )
+        .into_response()
+}
+
+fn token_balance(
+    symbol: String,
+    address: Option<String>,
+    balance: &BigUint,
+    decimals: u32,
+    usd_price: Option<f64>,
+) -> TokenBalance {
+    let amount = balance.to_f64().unwrap_or(f64::INFINITY) / 10f64.powi(decimals as i32);
+    TokenBalance {
+        symbol,
+        address,
+        balance: balance.to_string(),
+        decimals,
+        amount,
+        usd_price,
+        usd_value: usd_price.map(|price| price * amount),
+    }
+}
+
+fn sum_known(values: impl Iterator<Item = Option<f64>>) -> Option<f64> {
+    values
+        .flatten()
+        .fold(None, |sum, value| Some(sum.unwrap_or(0.0) + value))
+}
+
+/// USD prices of tokens by symbol, looked up once per request from the latest pool prices
+struct UsdPrices<'a> {
+    db: &'a Handle,
+    pools: HashMap<String, Vec<PoolSpotPrice>>,
+}
+
+impl<'a> UsdPrices<'a> {
+    fn new(db: &'a Handle) -> Self {
+        Self {
+            db,
+            pools: HashMap::new(),
+        }
+    }
+
+    /// Price of `symbol` in USD, preferring pools on `chain`
+    async fn get(&mut self, symbol: &str, chain: &str) -> eyre::Result<Option<f64>> {
+        if STABLECOINS.contains(&symbol) {
+            return Ok(Some(1.0));
+        }
+
+        if !self.pools.contains_key(symbol) {
+            let pools = self
+                .db
+                .spot_price_repository()
+                .latest_pool_prices(symbol, USD_QUOTE)
+                .await?;
+            self.pools.insert(symbol.to_string(), pools);
+        }
+
+        Ok(usd_price(symbol, chain, &self.pools[symbol]))
+    }
+}
+
+/// Median price of `symbol` quoted against USD by `pools`, only considering other chains if no
+/// pool on `chain` quotes it
+fn usd_price(symbol: &str, chain: &str, pools: &[PoolSpotPrice]) -> Option<f64> {
+    let quotes = |same_chain: bool| {
+        let mut prices: Vec<f64> = pools
+            .iter()
+            .filter(|pool| (pool.chain.name.to_string() == chain) == same_chain)
+            .filter_map(|pool| {
+                // pools quote token a in token b
+                if pool.pair.token_a().symbol == symbol {
+                    Some(pool.price)
+                } else {
+                    (pool.price > 0.0).then(|| 1.0 / pool.price)
+                }
+            })
+            .filter(|price| price.is_finite())
+            .collect();
+        prices.sort_by(f64::total_cmp);
+        prices.get(prices.len() / 2).copied()
+    };
+
+    quotes(true).or_else(|| quotes(false))
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_inventory))
+        .route("/:chain", get(get_chain_inventory))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_balance_valuation() {
+        let balance = token_balance(
+            "USDC".to_string(),
+            None,
+            &BigUint::from(2_500_000u64),
+            6,
+            Some(1.0),
+        );
+        assert_eq!(balance.balance, "2500000");
+        assert_eq!(balance.amount, 2.5);
+        assert_eq!(balance.usd_value, Some(2.5));
+
+        let unpriced = token_balance("FOO".to_string(), None, &BigUint::from(1u64), 0, None);
+        assert_eq!(unpriced.usd_value, None);
+    }
+
+    #[test]
+    fn test_sum_known_skips_unvalued() {
+        assert_eq!(sum_known([None, None].into_iter()), None);
+        assert_eq!(
+            sum_known([Some(1.5), None, Some(2.0)].into_iter()),
+            Some(3.5)
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! The configured chains, tokens and strategies, for the UI's selectors.
+use axum::{extract::State, routing::get, Json, Router};
+use kuma_core::chain::GasToken;
+use serde::Serialize;
+
+use crate::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct ChainMeta {
+    pub name: String,
+    pub chain_id: u64,
+    /// Symbol of the token gas is paid in
+    pub native_token: String,
+    pub gas_token: GasToken,
+    pub block_time_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenMeta {
+    pub chain: String,
+    pub symbol: String,
+    pub address: String,
+    pub decimals: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StrategyMeta {
+    /// Identifies the strategy in `/admin/strategies` routes
+    pub id: String,
+    pub token_a: String,
+    pub token_b: String,
+    pub slow_chain: String,
+    pub fast_chain: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokensResponse {
+    pub tokens: Vec<TokenMeta>,
+    /// Pairs traded by each strategy
+    pub strategies: Vec<StrategyMeta>,
+}
+
+/// Configured chains, by name
+pub async fn get_chains(State(state): State<AppState>) -> Json<Vec<ChainMeta>> {
+    let mut chains: Vec<ChainMeta> = state
+        .db
+        .chains()
+        .into_iter()
+        .map(|chain| ChainMeta {
+            name: chain.name.to_string(),
+            chain_id: chain.chain_id(),
+            native_token: chain.native_token().to_string(),
+            gas_token: chain.gas_token().clone(),
+            block_time_ms: chain.block_time().as_millis() as u64,
+        })
+        .collect();
+    chains.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Json(chains)
+}
+
+/// Configured tokens on every chain, by chain and symbol, along with the strategies' pairs
+pub async fn get_tokens(State(state): State<AppState>) -> Json<TokensResponse> {
+    let mut tokens: Vec<TokenMeta> = state
+        .db
+        .chains()
+        .into_iter()
+        .flat_map(|chain| {
+            state
+                .db
+                .tokens(&chain)
+                .into_iter()
+                .map(move |token| TokenMeta {
+                    chain: chain.name.to_string(),
+                    symbol: token.symbol,
+                    address: token.address.to_string(),
+                    decimals: token.decimals,
+                })
+        })
+        .collect();
+    tokens.sort_by(|a, b| (&a.chain, &a.symbol).cmp(&(&b.chain, &b.symbol)));
+
+    let strategies = state
+        .strategies
+        .iter()
+        .map(|strategy| StrategyMeta {
+            id: strategy.id(),
+            token_a: strategy.token_a.clone(),
+            token_b: strategy.token_b.clone(),
+            slow_chain: strategy.slow_chain.clone(),
+            fast_chain: strategy.fast_chain.clone(),
+        })
+        .collect();
+
+    Json(TokensResponse { tokens, strategies })
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/chains", get(get_chains))
+        .route("/tokens", get(get_tokens))
+}
@@ -1,2 +1,8 @@
+pub mod admin;
+pub mod executions;
+pub mod inventory;
+pub mod meta;
+pub mod pnl;
 pub mod signals;
 pub mod spot_prices;
+pub mod spreads;
@@ -0,0 +1,224 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use kuma_core::{config::StrategyConfig, database::PnlEntry};
+use tracing::info;
+
+use crate::{models::deserialize_optional, AppState};
+
+/// Width of `/pnl/timeseries` buckets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PnlBucket {
+    Hour,
+    #[default]
+    Day,
+    Week,
+}
+
+impl PnlBucket {
+    pub fn duration(self) -> Duration {
+        match self {
+            PnlBucket::Hour => Duration::from_secs(60 * 60),
+            PnlBucket::Day => Duration::from_secs(24 * 60 * 60),
+            PnlBucket::Week => Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PnlQuery {
+    /// Inclusive lower bound on when executions were recorded, in unix seconds
+    #[serde(deserialize_with = "deserialize_optional", default)]
+    pub from: Option<u64>,
+    /// Exclusive upper bound on when executions were recorded, in unix seconds
+    #[serde(deserialize_with = "deserialize_optional", default)]
+    pub to: Option<u64>,
+    /// Only read by `/pnl/timeseries`
+    pub bucket: Option<PnlBucket>,
+}
+
+impl PnlQuery {
+    fn range(&self) -> (Option<SystemTime>, Option<SystemTime>) {
+        let time = |secs: Option<u64>| secs.map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+        (time(self.from), time(self.to))
+    }
+}
+
+/// A [`PnlEntry`] attributed to the strategy that executed it
+#[derive(Debug, Serialize)]
+pub struct StrategyPnl {
+    /// Id of the configured strategy, see [`StrategyConfig::id`]
+    pub strategy: String,
+    #[serde(flatten)]
+    pub pnl: PnlEntry,
+}
+
+impl StrategyPnl {
+    fn new(pnl: PnlEntry, strategies: &[StrategyConfig]) -> Self {
+        Self {
+            strategy: strategy_id(&pnl, strategies),
+            pnl,
+        }
+    }
+}
+
+/// Id of the strategy trading `pnl`'s pair across its chains, derived like a configured one's if
+/// it isn't configured anymore.
+fn strategy_id(pnl: &PnlEntry, strategies: &[StrategyConfig]) -> String {
+    let is_pair = |a: &str, b: &str| {
+        a.eq_ignore_ascii_case(&pnl.token_a) && b.eq_ignore_ascii_case(&pnl.token_b)
+    };
+    strategies
+        .iter()
+        .find(|strategy| {
+            strategy.slow_chain.eq_ignore_ascii_case(&pnl.slow_chain)
+                && strategy.fast_chain.eq_ignore_ascii_case(&pnl.fast_chain)
+                && (is_pair(&strategy.token_a, &strategy.token_b)
+                    || is_pair(&strategy.token_b, &strategy.token_a))
+        })
+        .map(StrategyConfig::id)
+        .unwrap_or_else(|| {
+            format!(
+                "{}-{}-{}-{}",
+                pnl.token_a, pnl.token_b, pnl.slow_chain, pnl.fast_chain
+            )
+            .to_lowercase()
+        })
+}
+
+/// Realized PnL of executions per strategy and chain of their legs
+pub async fn get_summary(
+    State(state): State<AppState>,
+    Query(params): Query<PnlQuery>,
+) -> Result<Json<Vec<StrategyPnl>>, Response> {
+    info!(from = ?params.from, to = ?params.to, "Fetching PnL summary");
+
+    let (since, until) = params.range();
+    match state
+        .db
+        .execution_repository()
+        .pnl_summary(since, until)
+        .await
+    {
+        Ok(entries) => Ok(Json(
+            entries
+                .into_iter()
+                .map(|pnl| StrategyPnl::new(pnl, &state.strategies))
+                .collect(),
+        )),
+        Err(e) => {
+            tracing::error!("Failed to fetch PnL summary: {}", e);
+            Err(database_error("Failed to fetch PnL summary"))
+        }
+    }
+}
+
+/// [`get_summary`] per time bucket, oldest first
+pub async fn get_timeseries(
+    State(state): State<AppState>,
+    Query(params): Query<PnlQuery>,
+) -> Result<Json<Vec<StrategyPnl>>, Response> {
+    let bucket = params.bucket.unwrap_or_default();
+    info!(from = ?params.from, to = ?params.to, ?bucket, "Fetching PnL timeseries");
+
+    let (since, until) = params.range();
+    match state
+        .db
+        .execution_repository()
+        .pnl_timeseries(bucket.duration(), since, until)
+        .await
+    {
+        Ok(entries) => Ok(Json(
+            entries
+                .into_iter()
+                .map(|pnl| StrategyPnl::new(pnl, &state.strategies))
+                .collect(),
+        )),
+        Err(e) => {
+            tracing::error!("Failed to fetch PnL timeseries: {}", e);
+            Err(database_error("Failed to fetch PnL timeseries"))
+        }
+    }
+}
+
+fn database_error(message: &str) -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({
+            "error": "Database error",
+            "message": message
+        })),
+    )
+        .into_response()
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/summary", get(get_summary))
+        .route("/timeseries", get(get_timeseries))
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigUint;
+
+    use super::*;
+
+    fn pnl(token_a: &str, token_b: &str) -> PnlEntry {
+        PnlEntry {
+            slow_chain: "ethereum".to_string(),
+            fast_chain: "base".to_string(),
+            token_a: token_a.to_string(),
+            token_b: token_b.to_string(),
+            chain: "base".to_string(),
+            bucket_start: None,
+            mined_legs: 2,
+            reverted_legs: 0,
+            pending_legs: 0,
+            gas_cost: BigUint::from(1_000u32),
+            tokens: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_pnl_query_deserialization() {
+        let parsed: PnlQuery = serde_urlencoded::from_str("from=1718000000&bucket=hour").unwrap();
+        assert_eq!(parsed.from, Some(1_718_000_000));
+        assert_eq!(parsed.to, None);
+        assert_eq!(parsed.bucket, Some(PnlBucket::Hour));
+
+        let parsed: PnlQuery = serde_urlencoded::from_str("").unwrap();
+        assert_eq!(parsed.bucket.unwrap_or_default(), PnlBucket::Day);
+
+        assert!(serde_urlencoded::from_str::<PnlQuery>("bucket=minute").is_err());
+    }
+
+    #[test]
+    fn test_pnl_is_attributed_to_the_configured_strategy() {
+        let strategies: Vec<StrategyConfig> = serde_json::from_value(serde_json::json!([{
+            "token_a": "WETH",
+            "token_b": "USDC",
+            "slow_chain": "ethereum",
+            "fast_chain": "base"
+        }]))
+        .unwrap();
+
+        // pnl pairs are in alphabetical order, strategies in configured order
+        assert_eq!(
+            strategy_id(&pnl("USDC", "WETH"), &strategies),
+            "weth-usdc-ethereum-base"
+        );
+        assert_eq!(
+            strategy_id(&pnl("DAI", "USDC"), &strategies),
+            "dai-usdc-ethereum-base"
+        );
+    }
+}
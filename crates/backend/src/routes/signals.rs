@@ -1,62 +1,134 @@
 use axum::{
     extract::{Query, State},
     http::StatusCode,
-    response::{IntoResponse, Response},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     routing::get,
     Json, Router,
 };
-use kuma_core::signals::CrossChainSingleHop;
+use color_eyre::eyre::{self, WrapErr as _};
+use num_bigint::BigUint;
+use std::{
+    convert::Infallible,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use kuma_core::{
+    database::{
+        Handle, LeaderboardEntry, LeaderboardGroup, LeaderboardRank, SignalBucket, SignalFilter,
+        SignalSort, SortOrder,
+    },
+    signals::CrossChainSingleHop,
+};
 use serde::Deserialize;
-use tracing::info;
+use tokio::sync::broadcast;
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    Stream, StreamExt as _,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 
 use crate::{
-    models::{PaginatedResponse, PaginationQuery},
+    models::{deserialize_optional, PaginatedResponse, PaginationQuery},
     pair::parse_pair,
     AppState,
 };
 
 #[derive(Deserialize)]
 pub struct SignalQuery {
-    pub pair: String,
+    /// Pair traded across the chains, as `A-B` in either order
+    pub pair: Option<String>,
+    pub slow_chain: Option<String>,
+    pub fast_chain: Option<String>,
+    /// Minimum expected profit in the slow swap's input token
+    #[serde(deserialize_with = "deserialize_optional", default)]
+    pub min_profit_a: Option<BigUint>,
+    /// Minimum expected profit in the slow swap's output token
+    #[serde(deserialize_with = "deserialize_optional", default)]
+    pub min_profit_b: Option<BigUint>,
+    /// Inclusive lower bound on when signals were recorded, in unix seconds
+    #[serde(deserialize_with = "deserialize_optional", default)]
+    pub from: Option<u64>,
+    /// Exclusive upper bound on when signals were recorded, in unix seconds
+    #[serde(deserialize_with = "deserialize_optional", default)]
+    pub to: Option<u64>,
+    pub sort: Option<SignalSort>,
+    pub order: Option<SortOrder>,
     #[serde(flatten)]
     pub pagination: PaginationQuery,
 }
 
-pub async fn get_signals_by_pair(
+impl SignalQuery {
+    fn to_filter(&self, db: &Handle) -> eyre::Result<SignalFilter> {
+        let chain = |name: &Option<String>| {
+            name.as_deref()
+                .map(|name| db.chain(&name.to_lowercase()))
+                .transpose()
+        };
+        let time = |secs: Option<u64>| secs.map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+
+        Ok(SignalFilter {
+            slow_chain: chain(&self.slow_chain).wrap_err("invalid slow chain")?,
+            fast_chain: chain(&self.fast_chain).wrap_err("invalid fast chain")?,
+            pair: self
+                .pair
+                .as_deref()
+                .map(|pair| parse_pair(&pair.to_uppercase()))
+                .transpose()
+                .wrap_err("invalid pair")?,
+            direction: None,
+            min_expected_profit_a: self.min_profit_a.clone(),
+            min_expected_profit_b: self.min_profit_b.clone(),
+            since: time(self.from),
+            until: time(self.to),
+        })
+    }
+}
+
+pub async fn get_signals(
     State(state): State<AppState>,
     Query(params): Query<SignalQuery>,
 ) -> Result<Json<PaginatedResponse<CrossChainSingleHop>>, Response> {
     let (page, page_size) = params.pagination.sanitize();
     let (offset, limit) = params.pagination.to_offset_limit();
+    let sort = params.sort.unwrap_or_default();
+    let order = params.order.unwrap_or_default();
 
     info!(
-        pair = %params.pair,
+        pair = ?params.pair,
+        slow_chain = ?params.slow_chain,
+        fast_chain = ?params.fast_chain,
+        ?sort,
+        ?order,
         page = %page,
         page_size = %page_size,
         "Fetching arbitrage signals"
     );
 
-    let repo = state.db.signal_repository();
-
-    let (token_a_symbol, token_b_symbol) = match parse_pair(&params.pair.to_uppercase()) {
-        Ok(pair) => pair,
+    let filter = match params.to_filter(&state.db) {
+        Ok(filter) => filter,
         Err(e) => {
-            tracing::error!("Failed to parse pair: {}", e);
+            tracing::error!("Invalid signal filter: {:#}", e);
             return Err((
                 StatusCode::BAD_REQUEST,
                 Json(serde_json::json!({
-                    "error": "Invalid pair format",
-                    "message": format!("Failed to parse pair '{}': {}", params.pair, e)
+                    "error": "Invalid filter",
+                    "message": format!("{e:#}")
                 })),
             )
                 .into_response());
         }
     };
 
+    let repo = state.db.signal_repository();
+
     // Get total count and data in parallel
     let (count_result, data_result) = tokio::join!(
-        repo.count_by_symbols(&token_a_symbol, &token_b_symbol),
-        repo.get_by_symbols(&token_a_symbol, &token_b_symbol, limit, offset)
+        repo.count_filtered(&filter),
+        repo.get_filtered(&filter, sort, order, limit, offset)
     );
 
     match (count_result, data_result) {
@@ -80,8 +152,182 @@ pub async fn get_signals_by_pair(
     }
 }
 
+/// Hours of signal counts returned when no limit is given, one week
+const DEFAULT_HOURLY_LIMIT: u32 = 7 * 24;
+
+#[derive(Deserialize)]
+pub struct HourlySignalsQuery {
+    pub pair: String,
+    pub limit: Option<u32>,
+}
+
+/// Hourly signal counts per chain route, read from the `hourly_signal_stats` view
+pub async fn get_hourly_signal_counts(
+    State(state): State<AppState>,
+    Query(params): Query<HourlySignalsQuery>,
+) -> Result<Json<Vec<SignalBucket>>, Response> {
+    let limit = params.limit.unwrap_or(DEFAULT_HOURLY_LIMIT);
+    info!(pair = %params.pair, limit, "Fetching hourly signal counts");
+
+    let (token_a_symbol, token_b_symbol) = match parse_pair(&params.pair.to_uppercase()) {
+        Ok(pair) => pair,
+        Err(e) => {
+            tracing::error!("Failed to parse pair: {}", e);
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": "Invalid pair format",
+                    "message": format!("Failed to parse pair '{}': {}", params.pair, e)
+                })),
+            )
+                .into_response());
+        }
+    };
+
+    match state
+        .db
+        .signal_repository()
+        .hourly_counts(&token_a_symbol, &token_b_symbol, limit)
+        .await
+    {
+        Ok(counts) => Ok(Json(counts)),
+        Err(e) => {
+            tracing::error!("Failed to fetch hourly signal counts: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database error",
+                    "message": "Failed to fetch hourly signal counts"
+                })),
+            )
+                .into_response())
+        }
+    }
+}
+
+/// Window leaderboards cover when none is given, one day
+const DEFAULT_LEADERBOARD_WINDOW_SECS: u64 = 24 * 60 * 60;
+const DEFAULT_LEADERBOARD_LIMIT: u32 = 10;
+const MAX_LEADERBOARD_LIMIT: u32 = 100;
+
+#[derive(Deserialize)]
+pub struct LeaderboardQuery {
+    pub group_by: LeaderboardGroup,
+    pub rank_by: Option<LeaderboardRank>,
+    pub window_secs: Option<u64>,
+    pub limit: Option<u32>,
+}
+
+/// Pairs, pools or chains producing the most profitable signals over a recent window
+pub async fn get_leaderboard(
+    State(state): State<AppState>,
+    Query(params): Query<LeaderboardQuery>,
+) -> Result<Json<Vec<LeaderboardEntry>>, Response> {
+    let rank_by = params.rank_by.unwrap_or(LeaderboardRank::Signals);
+    let window = Duration::from_secs(
+        params
+            .window_secs
+            .unwrap_or(DEFAULT_LEADERBOARD_WINDOW_SECS),
+    );
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_LEADERBOARD_LIMIT)
+        .clamp(1, MAX_LEADERBOARD_LIMIT);
+    info!(group_by = ?params.group_by, ?rank_by, ?window, limit, "Fetching signal leaderboard");
+
+    match state
+        .db
+        .signal_repository()
+        .leaderboard(params.group_by, rank_by, window, limit)
+        .await
+    {
+        Ok(entries) => Ok(Json(entries)),
+        Err(e) => {
+            tracing::error!("Failed to fetch signal leaderboard: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database error",
+                    "message": "Failed to fetch signal leaderboard"
+                })),
+            )
+                .into_response())
+        }
+    }
+}
+
+/// Signals buffered per stream client before it starts missing them
+const FEED_CAPACITY: usize = 256;
+const FEED_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Forwards signals announced by the database to the returned channel, reconnecting whenever
+/// the listener fails, until `shutdown` is cancelled.
+pub(crate) fn spawn_feed(
+    db: Handle,
+    shutdown: CancellationToken,
+) -> broadcast::Sender<CrossChainSingleHop> {
+    let (tx, _) = broadcast::channel(FEED_CAPACITY);
+    let feed = tx.clone();
+
+    tokio::spawn(shutdown.run_until_cancelled_owned(async move {
+        let mut listener = loop {
+            match db.listen_signals().await {
+                Ok(listener) => break listener,
+                Err(e) => {
+                    warn!(error = %e, "Failed to listen for new signals, retrying");
+                    tokio::time::sleep(FEED_RETRY_DELAY).await;
+                }
+            }
+        };
+        info!("Listening for new signals");
+
+        loop {
+            match listener.recv().await {
+                Ok(signal) => {
+                    // not having any clients is fine
+                    let _ = feed.send(signal);
+                }
+                Err(e) => {
+                    warn!(error = %e, "Failed to receive new signal");
+                    tokio::time::sleep(FEED_RETRY_DELAY).await;
+                }
+            }
+        }
+    }));
+
+    tx
+}
+
+/// New signals as server-sent `signal` events. A `lagged` event carries the number of signals
+/// skipped when the client falls behind. The stream ends when the server shuts down.
+pub async fn stream_signals(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    info!("Streaming new signals");
+
+    let events = BroadcastStream::new(state.signals_tx.subscribe()).map(|signal| {
+        let event = match signal {
+            Ok(signal) => Event::default()
+                .event("signal")
+                .json_data(&signal)
+                .unwrap_or_else(|e| Event::default().event("error").data(e.to_string())),
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                Event::default().event("lagged").data(skipped.to_string())
+            }
+        };
+        Ok(event)
+    });
+    let events = futures::StreamExt::take_until(events, state.shutdown.cancelled_owned());
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
 pub fn routes() -> Router<AppState> {
-    Router::new().route("/", get(get_signals_by_pair))
+    Router::new()
+        .route("/", get(get_signals))
+        .route("/hourly", get(get_hourly_signal_counts))
+        .route("/leaderboard", get(get_leaderboard))
+        .route("/stream", get(stream_signals))
 }
 
 #[cfg(test)]
@@ -93,9 +339,64 @@ mod tests {
         let query = "pair=PEPE-WETH&page=3&page_size=15";
         let parsed: SignalQuery = serde_urlencoded::from_str(query).unwrap();
 
-        assert_eq!(parsed.pair, "PEPE-WETH".to_string());
+        assert_eq!(parsed.pair, Some("PEPE-WETH".to_string()));
         assert_eq!(parsed.pagination.page, Some(3));
         assert_eq!(parsed.pagination.page_size, Some(15));
+        assert_eq!(parsed.sort, None);
+        assert_eq!(parsed.from, None);
+    }
+
+    #[test]
+    fn test_signal_query_filter_deserialization() {
+        let query = "slow_chain=ethereum&fast_chain=base&min_profit_a=1000000&from=1718000000\
+            &to=1718600000&sort=expected_profit_a&order=asc&page_size=50";
+        let parsed: SignalQuery = serde_urlencoded::from_str(query).unwrap();
+
+        assert_eq!(parsed.pair, None);
+        assert_eq!(parsed.slow_chain, Some("ethereum".to_string()));
+        assert_eq!(parsed.fast_chain, Some("base".to_string()));
+        assert_eq!(parsed.min_profit_a, Some(BigUint::from(1_000_000u64)));
+        assert_eq!(parsed.min_profit_b, None);
+        assert_eq!(parsed.from, Some(1_718_000_000));
+        assert_eq!(parsed.to, Some(1_718_600_000));
+        assert_eq!(parsed.sort, Some(SignalSort::ExpectedProfitA));
+        assert_eq!(parsed.order, Some(SortOrder::Asc));
+        assert_eq!(parsed.pagination.page_size, Some(50));
+
+        assert!(serde_urlencoded::from_str::<SignalQuery>("min_profit_a=-5").is_err());
+    }
+
+    #[test]
+    fn test_hourly_signals_query_deserialization() {
+        let parsed: HourlySignalsQuery =
+            serde_urlencoded::from_str("pair=WETH-USDC&limit=24").unwrap();
+        assert_eq!(parsed.pair, "WETH-USDC".to_string());
+        assert_eq!(parsed.limit, Some(24));
+
+        let parsed: HourlySignalsQuery = serde_urlencoded::from_str("pair=WETH-USDC").unwrap();
+        assert_eq!(parsed.limit, None);
+    }
+
+    #[test]
+    fn test_leaderboard_query_deserialization() {
+        let parsed: LeaderboardQuery = serde_urlencoded::from_str(
+            "group_by=pool&rank_by=expected_profit&window_secs=3600&limit=5",
+        )
+        .unwrap();
+        assert_eq!(parsed.group_by, LeaderboardGroup::Pool);
+        assert_eq!(parsed.rank_by, Some(LeaderboardRank::ExpectedProfit));
+        assert_eq!(parsed.window_secs, Some(3600));
+        assert_eq!(parsed.limit, Some(5));
+
+        let parsed: LeaderboardQuery = serde_urlencoded::from_str("group_by=chain").unwrap();
+        assert_eq!(parsed.group_by, LeaderboardGroup::Chain);
+        assert_eq!(parsed.rank_by, None);
+
+        let parsed: LeaderboardQuery =
+            serde_urlencoded::from_str("group_by=pair&rank_by=hit_rate").unwrap();
+        assert_eq!(parsed.rank_by, Some(LeaderboardRank::HitRate));
+
+        assert!(serde_urlencoded::from_str::<LeaderboardQuery>("group_by=dex").is_err());
     }
 
     #[test]
@@ -1,15 +1,25 @@
 use axum::{
     extract::{Query, State},
-    http::StatusCode,
+    http::{header, HeaderName, StatusCode},
+    middleware,
     response::{IntoResponse, Response},
     routing::get,
     Json, Router,
 };
-use kuma_core::spot_prices::SpotPrices;
+use kuma_core::{
+    chain::Chain,
+    database::{CandleInterval, CandleRange, PoolSpotPrice, SpotPriceCandle},
+    spot_prices::SpotPrices,
+};
 use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
 use tracing::info;
 
 use crate::{
+    cache,
     models::{PaginatedResponse, PaginationQuery},
     pair::parse_pair,
     AppState,
@@ -80,8 +90,208 @@ pub async fn get_spot_prices_by_pair(
     }
 }
 
+#[derive(Deserialize)]
+pub struct LatestPoolPricesQuery {
+    pub pair: String,
+}
+
+/// Latest price of every pool quoting the pair, read from the `latest_pool_spot_prices` view
+pub async fn get_latest_pool_prices(
+    State(state): State<AppState>,
+    Query(params): Query<LatestPoolPricesQuery>,
+) -> Result<Json<Vec<PoolSpotPrice>>, Response> {
+    info!(pair = ?params.pair, "Fetching latest pool spot prices");
+
+    let (token_a_symbol, token_b_symbol) = match parse_pair(&params.pair.to_uppercase()) {
+        Ok(pair) => pair,
+        Err(e) => {
+            tracing::error!("Failed to parse pair: {}", e);
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": "Invalid pair format",
+                    "message": format!("Failed to parse pair '{}': {}", params.pair, e)
+                })),
+            )
+                .into_response());
+        }
+    };
+
+    match state
+        .db
+        .spot_price_repository()
+        .latest_pool_prices(&token_a_symbol, &token_b_symbol)
+        .await
+    {
+        Ok(prices) => Ok(Json(prices)),
+        Err(e) => {
+            tracing::error!("Failed to fetch latest pool spot prices: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database error",
+                    "message": "Failed to fetch latest pool spot prices"
+                })),
+            )
+                .into_response())
+        }
+    }
+}
+
+/// Candles returned when the range isn't given
+const DEFAULT_CANDLES: u64 = 300;
+/// Most candles a single request may span
+const MAX_CANDLES: u64 = 2_000;
+
+#[derive(Deserialize)]
+pub struct CandlesQuery {
+    pub pair: String,
+    /// Candles of every chain if unset
+    pub chain: Option<String>,
+    pub interval: CandleInterval,
+    /// Earliest candle start in unix seconds, `DEFAULT_CANDLES` intervals before `to` by default
+    pub from: Option<u64>,
+    /// Latest candle start in unix seconds, now by default
+    pub to: Option<u64>,
+    /// Carry the previous close over intervals without blocks, on by default
+    pub fill_gaps: Option<bool>,
+}
+
+/// OHLC candles of a pair, oldest first, read from the `spot_price_candles` aggregates. Ranges
+/// that settled are cached as immutable.
+pub async fn get_candles(
+    State(state): State<AppState>,
+    Query(params): Query<CandlesQuery>,
+) -> Result<([(HeaderName, &'static str); 1], Json<Vec<SpotPriceCandle>>), Response> {
+    let bad_request = |message: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": "Invalid candle query",
+                "message": message
+            })),
+        )
+            .into_response()
+    };
+
+    let (token_a_symbol, token_b_symbol) = parse_pair(&params.pair.to_uppercase())
+        .map_err(|e| bad_request(format!("Failed to parse pair '{}': {}", params.pair, e)))?;
+    let chain = params
+        .chain
+        .as_deref()
+        .map(|chain| state.db.chain(chain))
+        .transpose()
+        .map_err(|e| bad_request(format!("Unknown chain: {e}")))?;
+
+    let interval_secs = params.interval.duration().as_secs();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let to = params.to.unwrap_or(now);
+    let from = params
+        .from
+        .unwrap_or_else(|| to.saturating_sub(DEFAULT_CANDLES * interval_secs));
+    if from > to {
+        return Err(bad_request(format!("from ({from}) is after to ({to})")));
+    }
+    if (to - from) / interval_secs >= MAX_CANDLES {
+        return Err(bad_request(format!(
+            "the range spans more than {MAX_CANDLES} candles, narrow it or use a wider interval"
+        )));
+    }
+
+    info!(
+        pair = %params.pair,
+        chain = ?params.chain,
+        interval = ?params.interval,
+        from,
+        to,
+        "Fetching spot price candles"
+    );
+
+    let range = CandleRange {
+        token_a_symbol,
+        token_b_symbol,
+        chain,
+        interval: params.interval,
+        from,
+        to,
+    };
+    // every chain's candles count against the limit when none is given
+    let chains = if range.chain.is_some() {
+        1
+    } else {
+        state.db.chains().len().max(1)
+    };
+    // the last candle covers `to` until the end of its interval
+    let cache_control = if cache::settled(to.saturating_add(interval_secs), now) {
+        cache::IMMUTABLE
+    } else {
+        cache::REVALIDATE
+    };
+    let cache_control = [(header::CACHE_CONTROL, cache_control)];
+
+    match state
+        .db
+        .candle_repository()
+        .get_range(&range, MAX_CANDLES as u32 * chains as u32)
+        .await
+    {
+        Ok(candles) if params.fill_gaps.unwrap_or(true) => {
+            Ok((cache_control, Json(fill_gaps(candles))))
+        }
+        Ok(candles) => Ok((cache_control, Json(candles))),
+        Err(e) => {
+            tracing::error!("Failed to fetch spot price candles: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database error",
+                    "message": "Failed to fetch spot price candles"
+                })),
+            )
+                .into_response())
+        }
+    }
+}
+
+/// Inserts a flat candle at the previous close, without blocks, for every interval missing
+/// between two candles of the same chain. Nothing is filled before a chain's first candle or after
+/// its last.
+fn fill_gaps(candles: Vec<SpotPriceCandle>) -> Vec<SpotPriceCandle> {
+    let mut filled = Vec::with_capacity(candles.len());
+    let mut previous_by_chain: HashMap<Chain, SpotPriceCandle> = HashMap::new();
+
+    for candle in candles {
+        let step = candle.interval.duration().as_secs();
+        if let Some(previous) = previous_by_chain.insert(candle.chain.clone(), candle.clone()) {
+            let mut bucket_start = previous.bucket_start + step;
+            while bucket_start < candle.bucket_start {
+                filled.push(SpotPriceCandle {
+                    bucket_start,
+                    open: previous.close,
+                    high: previous.close,
+                    low: previous.close,
+                    blocks: 0,
+                    ..previous.clone()
+                });
+                bucket_start += step;
+            }
+        }
+        filled.push(candle);
+    }
+
+    filled.sort_by_key(|candle| candle.bucket_start);
+    filled
+}
+
 pub fn routes() -> Router<AppState> {
-    Router::new().route("/", get(get_spot_prices_by_pair))
+    Router::new()
+        .route("/", get(get_spot_prices_by_pair))
+        .route("/latest", get(get_latest_pool_prices))
+        .route("/candles", get(get_candles))
+        .route_layer(middleware::from_fn(cache::etag))
 }
 
 #[cfg(test)]
@@ -98,6 +308,19 @@ mod tests {
         assert_eq!(parsed.pagination.page_size, Some(50));
     }
 
+    #[test]
+    fn test_candles_query_deserialization() {
+        let query = "pair=WETH-USDC&interval=5m&from=1718000000";
+        let parsed: CandlesQuery = serde_urlencoded::from_str(query).unwrap();
+
+        assert_eq!(parsed.interval, CandleInterval::FiveMinutes);
+        assert_eq!(parsed.from, Some(1718000000));
+        assert_eq!(parsed.to, None);
+        assert_eq!(parsed.fill_gaps, None);
+
+        assert!(serde_urlencoded::from_str::<CandlesQuery>("pair=WETH-USDC&interval=2m").is_err());
+    }
+
     #[test]
     fn test_pagination_sanitization() {
         use crate::models::PaginationQuery;
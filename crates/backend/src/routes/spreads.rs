@@ -0,0 +1,136 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use kuma_core::database::Spread;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{pair::parse_pair, AppState};
+
+/// Spreads covered when the range isn't given
+const DEFAULT_WINDOW_SECS: u64 = 24 * 60 * 60;
+const DEFAULT_SPREADS_LIMIT: u32 = 500;
+const MAX_SPREADS_LIMIT: u32 = 5_000;
+
+#[derive(Deserialize)]
+pub struct SpreadsQuery {
+    /// Pair as `A-B`, prices are of `A` in `B`
+    pub pair: String,
+    pub slow_chain: String,
+    pub fast_chain: String,
+    /// Inclusive lower bound on when slow blocks were recorded in unix seconds, a day before `to`
+    /// by default
+    pub from: Option<u64>,
+    /// Exclusive upper bound on when slow blocks were recorded in unix seconds, now by default
+    pub to: Option<u64>,
+    pub limit: Option<u32>,
+}
+
+/// Best cross-chain spread of a pair per slow chain block, newest first, from the stored spot
+/// prices
+pub async fn get_spreads(
+    State(state): State<AppState>,
+    Query(params): Query<SpreadsQuery>,
+) -> Result<Json<Vec<Spread>>, Response> {
+    let bad_request = |message: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": "Invalid spread query",
+                "message": message
+            })),
+        )
+            .into_response()
+    };
+
+    let (token_a_symbol, token_b_symbol) = parse_pair(&params.pair.to_uppercase())
+        .map_err(|e| bad_request(format!("Failed to parse pair '{}': {}", params.pair, e)))?;
+    let slow_chain = state
+        .db
+        .chain(&params.slow_chain)
+        .map_err(|e| bad_request(format!("Unknown slow chain: {e}")))?;
+    let fast_chain = state
+        .db
+        .chain(&params.fast_chain)
+        .map_err(|e| bad_request(format!("Unknown fast chain: {e}")))?;
+
+    let to = params.to.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    });
+    let from = params
+        .from
+        .unwrap_or_else(|| to.saturating_sub(DEFAULT_WINDOW_SECS));
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_SPREADS_LIMIT)
+        .clamp(1, MAX_SPREADS_LIMIT);
+
+    info!(
+        pair = %params.pair,
+        slow_chain = %params.slow_chain,
+        fast_chain = %params.fast_chain,
+        from,
+        to,
+        limit,
+        "Fetching spreads"
+    );
+
+    match state
+        .db
+        .spot_price_repository()
+        .spreads(
+            &token_a_symbol,
+            &token_b_symbol,
+            &slow_chain,
+            &fast_chain,
+            from..to,
+            limit,
+        )
+        .await
+    {
+        Ok(spreads) => Ok(Json(spreads)),
+        Err(e) => {
+            tracing::error!("Failed to fetch spreads: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database error",
+                    "message": "Failed to fetch spreads"
+                })),
+            )
+                .into_response())
+        }
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/", get(get_spreads))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spreads_query_deserialization() {
+        let query = "pair=WETH-USDC&slow_chain=ethereum&fast_chain=base&limit=50";
+        let parsed: SpreadsQuery = serde_urlencoded::from_str(query).unwrap();
+
+        assert_eq!(parsed.pair, "WETH-USDC");
+        assert_eq!(parsed.slow_chain, "ethereum");
+        assert_eq!(parsed.fast_chain, "base");
+        assert_eq!(parsed.from, None);
+        assert_eq!(parsed.limit, Some(50));
+
+        assert!(serde_urlencoded::from_str::<SpreadsQuery>("pair=WETH-USDC").is_err());
+    }
+}
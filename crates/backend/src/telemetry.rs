@@ -0,0 +1,46 @@
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::AppState;
+
+/// Records `kuma_http_requests_total` and `kuma_http_request_duration_seconds`, labelled with the
+/// route's path template so ids and query parameters don't blow up their cardinality.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| request.uri().path().to_owned());
+
+    let response = next.run(request).await;
+
+    let status = response.status().as_u16().to_string();
+    metrics::counter!(
+        "kuma_http_requests_total",
+        "method" => method.clone(),
+        "route" => route.clone(),
+        "status" => status
+    )
+    .increment(1);
+    metrics::histogram!(
+        "kuma_http_request_duration_seconds",
+        "method" => method,
+        "route" => route
+    )
+    .record(started.elapsed().as_secs_f64());
+
+    response
+}
+
+/// Everything recorded in this process in the Prometheus text format, including the `kuma_db_*`
+/// metrics of the database handle
+pub async fn get_metrics(State(state): State<AppState>) -> String {
+    state.metrics.render()
+}
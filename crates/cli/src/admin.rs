@@ -0,0 +1,125 @@
+use core::{
+    admin::{self, DaemonStatus, Request, Response},
+    config::Config,
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::Subcommand;
+use color_eyre::eyre::{self, Context as _, OptionExt as _, eyre};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct AdminCommand {
+    #[command(subcommand)]
+    command: AdminSubcommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum AdminSubcommand {
+    /// Print the running kumad's workers and the controls it applies
+    Status,
+
+    /// Stop a strategy from generating signals
+    Pause {
+        /// Strategy id, e.g. weth-usdc-ethereum-base
+        strategy: String,
+    },
+
+    /// Let a paused strategy generate signals again
+    Resume {
+        /// Strategy id, e.g. weth-usdc-ethereum-base
+        strategy: String,
+    },
+
+    /// Stop every strategy from emitting signals, which are still generated and stored
+    PauseExecution,
+
+    /// Emit signals again
+    ResumeExecution,
+
+    /// Drop a strategy's signals expected to profit less than a threshold, until kumad restarts
+    MinProfit {
+        /// Strategy id, e.g. weth-usdc-ethereum-base
+        strategy: String,
+
+        /// Least expected profit, in bps of the slow swap's amount in. Omit to emit every signal.
+        bps: Option<u64>,
+    },
+}
+
+impl AdminCommand {
+    pub(crate) async fn run(&self, config: Config) -> eyre::Result<()> {
+        let request = match &self.command {
+            AdminSubcommand::Status => Request::Status,
+            AdminSubcommand::Pause { strategy } | AdminSubcommand::Resume { strategy } => {
+                Request::SetStrategyPaused {
+                    strategy: strategy.clone(),
+                    paused: matches!(self.command, AdminSubcommand::Pause { .. }),
+                }
+            }
+            AdminSubcommand::PauseExecution => Request::SetExecutionPaused { paused: true },
+            AdminSubcommand::ResumeExecution => Request::SetExecutionPaused { paused: false },
+            AdminSubcommand::MinProfit { strategy, bps } => Request::SetMinProfit {
+                strategy: strategy.clone(),
+                min_profit_bps: *bps,
+            },
+        };
+
+        match request_daemon(&config, &request).await? {
+            Response::Status(status) => print_status(&status)?,
+            Response::Done => println!("Done"),
+            response => return Err(eyre!("unexpected response from kumad: {response:?}")),
+        }
+
+        Ok(())
+    }
+}
+
+/// Sends `request` to kumad over the configured admin socket.
+pub(crate) async fn request_daemon(config: &Config, request: &Request) -> eyre::Result<Response> {
+    let socket = config
+        .admin_socket
+        .as_deref()
+        .ok_or_eyre("set admin_socket to reach kumad")?;
+
+    admin::request(socket, request).await
+}
+
+/// Prints the controls kumad applies and its workers' progress.
+pub(crate) fn print_status(status: &DaemonStatus) -> eyre::Result<()> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .wrap_err("system clock is before the unix epoch")?
+        .as_secs();
+
+    println!(
+        "Execution: {}",
+        if status.execution_paused {
+            "paused, signals are stored but not emitted"
+        } else {
+            "running"
+        }
+    );
+
+    println!("\n{:<40}  {:<8}  min_profit", "strategy", "state");
+    for strategy in &status.strategies {
+        println!(
+            "{:<40}  {:<8}  {}",
+            strategy.id,
+            if strategy.paused { "paused" } else { "running" },
+            strategy
+                .min_profit_bps
+                .map_or_else(|| "-".to_string(), |bps| format!("{bps} bps"))
+        );
+    }
+
+    println!("\n{:<40}  last_progress", "worker");
+    for worker in &status.workers {
+        let progress = match worker.last_progress_at {
+            Some(at) => format!("{}s ago", now.saturating_sub(at)),
+            None => "never".to_string(),
+        };
+        println!("{:<40}  {progress}", worker.worker);
+    }
+
+    Ok(())
+}
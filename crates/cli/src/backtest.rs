@@ -0,0 +1,459 @@
+//! Replays stored history of a strategy to see how it would have fared with other parameters.
+//!
+//! Pool states aren't stored, only each block's spot price range and the signals found, so the
+//! replay works on those: slow blocks are paired with the fast block recorded before them, as the
+//! strategy does live, and count as crossed when the best spread between the chains survives
+//! slippage on both legs. Stored signals are repriced with [`calculate_expected_profits`] from
+//! their simulated swaps. The fast leg was sized with the slippage the signal was found with, so
+//! repricing it with another one is an approximation.
+use core::{
+    config::Config,
+    database::{self, SignalFilter, SignalSort, SortOrder, Spread, SpreadSide},
+    signals::{CrossChainSingleHop, calculate_expected_profits},
+};
+use std::{
+    collections::BTreeMap,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::eyre::{self, Context as _, OptionExt as _, eyre};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive as _;
+
+/// 100%, the most slippage or discount that can be compared
+pub(crate) const MAX_BPS: u64 = 10_000;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Backtest {
+    /// Start of the range to replay, in seconds since the unix epoch or as `block:<height>` of
+    /// the slow chain
+    #[arg(long)]
+    pub from: Bound,
+
+    /// Inclusive end of the range, like `--from`. Defaults to now
+    #[arg(long)]
+    pub to: Option<Bound>,
+
+    /// Id of the configured strategy to replay, e.g. `weth-usdc-ethereum-base`
+    #[arg(long)]
+    pub strategy: String,
+
+    /// Comma separated max slippages to compare, in bps. Defaults to half, once and twice the
+    /// strategy's
+    #[arg(long, value_delimiter = ',')]
+    pub slippage_bps: Vec<u64>,
+
+    /// Comma separated congestion risk discounts to compare, in bps. Defaults to half, once and
+    /// twice the strategy's
+    #[arg(long, value_delimiter = ',')]
+    pub discount_bps: Vec<u64>,
+}
+
+/// End of a backtest range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Bound {
+    /// Seconds since the unix epoch
+    Time(u64),
+    /// Height of a slow chain block
+    Block(u64),
+}
+
+impl FromStr for Bound {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("block:") {
+            Some(height) => height
+                .parse()
+                .map(Bound::Block)
+                .map_err(|e| format!("invalid block height {height}: {e}")),
+            None => s
+                .parse()
+                .map(Bound::Time)
+                .map_err(|e| format!("invalid unix timestamp {s}: {e}")),
+        }
+    }
+}
+
+impl Backtest {
+    pub(crate) async fn run(&self, config: Config) -> eyre::Result<()> {
+        let strategy = config
+            .strategies
+            .iter()
+            .find(|strategy| strategy.id() == self.strategy.to_lowercase())
+            .ok_or_else(|| eyre!("no strategy {} in the config", self.strategy))?;
+        let params = config.strategy_params(strategy);
+        if let Some(bps) = self
+            .slippage_bps
+            .iter()
+            .chain(&self.discount_bps)
+            .find(|bps| **bps > MAX_BPS)
+        {
+            eyre::bail!("{bps} bps is more than 100%");
+        }
+
+        let (token_configs, _) = config
+            .build_addrs_and_inventory()
+            .map_err(|e| eyre!("failed to parse chain assets: {e}"))?;
+        let db = database::Handle::from_config(config.database.clone(), Arc::new(token_configs))
+            .await
+            .wrap_err("failed to connect to the database")?;
+        let slow_chain = db.chain(&strategy.slow_chain)?;
+        let fast_chain = db.chain(&strategy.fast_chain)?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .wrap_err("system clock is before the unix epoch")?
+            .as_secs();
+        let to = self.to.unwrap_or(Bound::Time(now));
+        // block bounds are applied to the rows of the widest time range they could fall in
+        let (since, until) = match (self.from, to) {
+            (Bound::Time(from), Bound::Time(to)) => (from, to),
+            (Bound::Time(from), Bound::Block(_)) => (from, now),
+            (Bound::Block(_), Bound::Time(to)) => (0, to),
+            (Bound::Block(_), Bound::Block(_)) => (0, now),
+        };
+        let in_range = |slow_height: u64| {
+            let after_from = match self.from {
+                Bound::Block(from) => slow_height >= from,
+                Bound::Time(_) => true,
+            };
+            let before_to = match to {
+                Bound::Block(to) => slow_height <= to,
+                Bound::Time(_) => true,
+            };
+            after_from && before_to
+        };
+
+        let mut spreads = db
+            .spot_price_repository()
+            .spreads(
+                &strategy.token_a,
+                &strategy.token_b,
+                &slow_chain,
+                &fast_chain,
+                since..until + 1,
+                u32::MAX,
+            )
+            .await?;
+        spreads.retain(|spread| in_range(spread.slow_height));
+        spreads.reverse();
+
+        let filter = SignalFilter {
+            slow_chain: Some(slow_chain),
+            fast_chain: Some(fast_chain),
+            pair: Some((strategy.token_a.clone(), strategy.token_b.clone())),
+            since: Some(UNIX_EPOCH + Duration::from_secs(since)),
+            until: Some(UNIX_EPOCH + Duration::from_secs(until + 1)),
+            ..Default::default()
+        };
+        let mut signals = db
+            .signal_repository()
+            .get_filtered(&filter, SignalSort::CreatedAt, SortOrder::Asc, u32::MAX, 0)
+            .await?;
+        signals.retain(|signal| in_range(signal.slow_height));
+
+        let (first, last) = spreads
+            .first()
+            .zip(spreads.last())
+            .ok_or_eyre("no spot prices of the strategy's pair stored in the range")?;
+
+        println!(
+            "Replayed {} slow blocks of {} ({}..={})",
+            spreads.len(),
+            strategy.id(),
+            first.slow_height,
+            last.slow_height
+        );
+        let crossed = |side| {
+            spreads
+                .iter()
+                .filter(|spread| spread.buy_on == side && spread.spread_bps > 0.0)
+                .count()
+        };
+        println!(
+            "Crossed before slippage: {} buying on {}, {} buying on {}",
+            crossed(SpreadSide::Slow),
+            strategy.slow_chain,
+            crossed(SpreadSide::Fast),
+            strategy.fast_chain
+        );
+        println!(
+            "Stored signals: {}, expected to profit {}",
+            signals.len(),
+            format_profits(&stored_profits(&signals))
+        );
+
+        println!();
+        println!("slippage_bps  discount_bps  crossed_blocks  signals  expected_profit");
+        for slippage_bps in grid(&self.slippage_bps, params.max_slippage_bps) {
+            let crossed_blocks = spreads
+                .iter()
+                .filter(|spread| survives_slippage(spread, slippage_bps))
+                .count();
+            for discount_bps in grid(&self.discount_bps, params.congestion_risk_discount_bps) {
+                let repriced = reprice(&signals, slippage_bps, discount_bps);
+                let marker = if (slippage_bps, discount_bps)
+                    == (params.max_slippage_bps, params.congestion_risk_discount_bps)
+                {
+                    " (configured)"
+                } else {
+                    ""
+                };
+                println!(
+                    "{slippage_bps:>12}  {discount_bps:>12}  {crossed_blocks:>14}  {:>7}  {}{}",
+                    repriced.signals,
+                    format_profits(&repriced.profits),
+                    marker
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Expected profits summed per token symbol, with the token's decimals
+pub(crate) type Profits = BTreeMap<String, (BigUint, u32)>;
+
+struct Repriced {
+    /// Signals still expected to profit
+    signals: usize,
+    profits: Profits,
+}
+
+pub(crate) fn add_profits(
+    profits: &mut Profits,
+    signal: &CrossChainSingleHop,
+    expected: &(BigUint, BigUint),
+) {
+    // profits are in the slow swap's input and output tokens
+    for (token, amount) in [
+        (&signal.slow_swap_sim.token_in, &expected.0),
+        (&signal.slow_swap_sim.token_out, &expected.1),
+    ] {
+        profits
+            .entry(token.symbol.clone())
+            .or_insert_with(|| (BigUint::ZERO, token.decimals))
+            .0 += amount;
+    }
+}
+
+fn stored_profits(signals: &[CrossChainSingleHop]) -> Profits {
+    let mut profits = Profits::new();
+    for signal in signals {
+        add_profits(&mut profits, signal, &signal.expected_profit);
+    }
+    profits
+}
+
+/// Expected profits of `signals` had they been found with `slippage_bps` and `discount_bps`,
+/// dropping the ones that wouldn't profit at all.
+fn reprice(signals: &[CrossChainSingleHop], slippage_bps: u64, discount_bps: u64) -> Repriced {
+    let mut repriced = Repriced {
+        signals: 0,
+        profits: Profits::new(),
+    };
+    for signal in signals {
+        let Ok(expected) = calculate_expected_profits(
+            &signal.slow_swap_sim,
+            &signal.fast_swap_sim,
+            slippage_bps,
+            discount_bps,
+        ) else {
+            continue;
+        };
+        repriced.signals += 1;
+        add_profits(&mut repriced.profits, signal, &expected);
+    }
+    repriced
+}
+
+/// Whether buying and selling at the spread's prices still gains after losing `slippage_bps` on
+/// both legs.
+fn survives_slippage(spread: &Spread, slippage_bps: u64) -> bool {
+    let kept = 1.0 - slippage_bps as f64 / 10_000.0;
+    (1.0 + spread.spread_bps / 10_000.0) * kept * kept > 1.0
+}
+
+/// The values to compare, or ones around the `configured` value if none were given.
+pub(crate) fn grid(values: &[u64], configured: u64) -> Vec<u64> {
+    let mut grid = if values.is_empty() {
+        vec![configured / 2, configured, (configured * 2).min(MAX_BPS)]
+    } else {
+        values.to_vec()
+    };
+    grid.sort_unstable();
+    grid.dedup();
+    grid
+}
+
+fn format_profits(profits: &Profits) -> String {
+    if profits.is_empty() {
+        return "nothing".to_string();
+    }
+    profits
+        .iter()
+        .map(|(symbol, (amount, decimals))| {
+            let amount = amount.to_f64().unwrap_or(f64::INFINITY) / 10f64.powi(*decimals as i32);
+            format!("{amount} {symbol}")
+        })
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{chain::Chain, state::pair::Pair, strategy::Swap};
+
+    use tycho_common::{Bytes, models::token::Token};
+
+    use super::*;
+
+    fn token(address: &str, symbol: &str, decimals: u32) -> Token {
+        Token::new(
+            &Bytes::from_str(address).unwrap(),
+            symbol,
+            decimals,
+            0,
+            &[Some(0)],
+            tycho_common::models::Chain::Ethereum,
+            100,
+        )
+    }
+
+    fn weth() -> Token {
+        token("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", "WETH", 18)
+    }
+
+    fn usdc() -> Token {
+        token("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48", "USDC", 6)
+    }
+
+    fn swap(token_in: Token, amount_in: u64, token_out: Token, amount_out: u64) -> Swap {
+        Swap {
+            token_in,
+            amount_in: BigUint::from(amount_in),
+            token_out,
+            amount_out: BigUint::from(amount_out),
+            gas_cost: BigUint::ZERO,
+        }
+    }
+
+    /// Sells `slow_in` for `slow_out` on the slow chain and buys `fast_out` back with `fast_in`
+    /// on the fast one, expecting the surpluses as profit.
+    fn signal(
+        (token_in, slow_in, fast_out): (Token, u64, u64),
+        (token_out, slow_out, fast_in): (Token, u64, u64),
+    ) -> CrossChainSingleHop {
+        let pair = Pair::new(token_in.clone(), token_out.clone());
+        CrossChainSingleHop {
+            slow_chain: Chain::eth_mainnet(),
+            slow_pair: pair.clone(),
+            slow_protocol_component: None,
+            slow_pool_id: "0x01".into(),
+            slow_swap_sim: swap(token_in.clone(), slow_in, token_out.clone(), slow_out),
+            slow_height: 100,
+            fast_chain: Chain::base_mainnet(),
+            fast_pair: pair,
+            fast_protocol_component: None,
+            fast_pool_id: "0x02".into(),
+            fast_swap_sim: swap(token_out, fast_in, token_in, fast_out),
+            fast_height: 200,
+            max_slippage_bps: 0,
+            congestion_risk_discount_bps: 0,
+            surplus: (
+                BigUint::from(fast_out - slow_in),
+                BigUint::from(slow_out - fast_in),
+            ),
+            expected_profit: (
+                BigUint::from(fast_out - slow_in),
+                BigUint::from(slow_out - fast_in),
+            ),
+            block_skew_ms: None,
+        }
+    }
+
+    #[test]
+    fn profits_are_summed_per_token_across_directions() {
+        let signals = [
+            // 1 WETH for 3000 USDC, 2990 USDC back for 1.01 WETH
+            signal(
+                (weth(), 1_000_000_000_000_000_000, 1_010_000_000_000_000_000),
+                (usdc(), 3_000_000_000, 2_990_000_000),
+            ),
+            // 3000 USDC for 1.01 WETH, 1 WETH back for 3005 USDC
+            signal(
+                (usdc(), 3_000_000_000, 3_005_000_000),
+                (weth(), 1_010_000_000_000_000_000, 1_000_000_000_000_000_000),
+            ),
+        ];
+
+        let profits = stored_profits(&signals);
+        assert_eq!(
+            profits,
+            Profits::from([
+                ("USDC".to_string(), (BigUint::from(15_000_000u64), 6)),
+                (
+                    "WETH".to_string(),
+                    (BigUint::from(20_000_000_000_000_000u64), 18)
+                ),
+            ])
+        );
+        assert_eq!(format_profits(&profits), "15 USDC + 0.02 WETH");
+        assert_eq!(format_profits(&Profits::new()), "nothing");
+    }
+
+    #[test]
+    fn repricing_drops_signals_that_no_longer_profit() {
+        let signals = [signal(
+            (weth(), 1_000_000_000_000_000_000, 1_010_000_000_000_000_000),
+            (usdc(), 3_000_000_000, 2_990_000_000),
+        )];
+
+        let repriced = reprice(&signals, 0, 5_000);
+        assert_eq!(repriced.signals, 1);
+        assert_eq!(format_profits(&repriced.profits), "5 USDC + 0.005 WETH");
+
+        // 1% slippage on 1.01 WETH doesn't cover the 1 WETH sold
+        let repriced = reprice(&signals, 100, 0);
+        assert_eq!(repriced.signals, 0);
+        assert!(repriced.profits.is_empty());
+    }
+
+    #[test]
+    fn grid_defaults_to_around_the_configured_value() {
+        assert_eq!(grid(&[], 50), [25, 50, 100]);
+        assert_eq!(grid(&[], 8_000), [4_000, 8_000, MAX_BPS]);
+        assert_eq!(grid(&[30, 10, 30], 50), [10, 30]);
+    }
+
+    #[test]
+    fn spreads_survive_slippage_on_both_legs() {
+        let spread = |spread_bps| Spread {
+            slow_height: 100,
+            fast_height: 200,
+            timestamp: 1_718_000_000,
+            slow_min_price: 3_000.0,
+            slow_max_price: 3_000.0,
+            fast_min_price: 3_000.0,
+            fast_max_price: 3_000.0,
+            buy_on: SpreadSide::Slow,
+            spread_bps,
+        };
+
+        assert!(survives_slippage(&spread(50.0), 20));
+        assert!(!survives_slippage(&spread(50.0), 30));
+        assert!(!survives_slippage(&spread(-10.0), 0));
+    }
+
+    #[test]
+    fn bounds_parse_as_times_or_slow_blocks() {
+        assert_eq!("1718000000".parse(), Ok(Bound::Time(1_718_000_000)));
+        assert_eq!("block:19000000".parse(), Ok(Bound::Block(19_000_000)));
+        assert!("block:latest".parse::<Bound>().is_err());
+        assert!("yesterday".parse::<Bound>().is_err());
+    }
+}
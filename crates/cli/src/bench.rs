@@ -0,0 +1,234 @@
+//! Measures how long the strategy's hot path takes on synthetic pools, to catch regressions in
+//! precompute and signal search before deploying.
+//!
+//! Both chains get `--pools` Uniswap V2 pools of a made-up pair with the same reserves, priced
+//! around 1 with up to `--spread-bps` of noise, so the fast chain's pools are usually mispriced
+//! against some of the slow chain's and the search runs the whole way.
+use core::{
+    chain::Chain,
+    config::Config,
+    state::{PoolId, pair::Pair, pair::PairState, pool_meta::PoolMeta},
+    strategy::CrossChainSingleHop,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr as _,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use alloy::primitives::U256;
+use color_eyre::eyre;
+use num_bigint::BigUint;
+use rand::{Rng as _, SeedableRng as _, rngs::StdRng};
+use tycho_common::{Bytes, models::token::Token, simulation::protocol_sim::ProtocolSim};
+use tycho_simulation::{
+    evm::protocol::uniswap_v2::state::UniswapV2State, protocol::models::ProtocolComponent,
+};
+
+/// Decimals of both synthetic tokens
+const DECIMALS: u32 = 18;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Bench {
+    /// Pools of the pair on each chain
+    #[arg(long, default_value_t = 50)]
+    pub pools: usize,
+
+    /// Reserve of each token in every pool, in whole tokens
+    #[arg(long, default_value_t = 1_000)]
+    pub reserve: u64,
+
+    /// Inventory of each token on each chain, in whole tokens
+    #[arg(long, default_value_t = 100)]
+    pub inventory: u64,
+
+    /// Most a pool's price deviates from 1, in basis points
+    #[arg(long, default_value_t = 50)]
+    pub spread_bps: u64,
+
+    /// Times precompute and signal search are each measured
+    #[arg(long, default_value_t = 200)]
+    pub iterations: usize,
+
+    /// Seed of the pools' prices, so runs are comparable
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Fail when the 99th percentile of either step exceeds this many milliseconds
+    #[arg(long)]
+    pub max_p99_ms: Option<f64>,
+}
+
+impl Bench {
+    pub(crate) async fn run(&self, config: Config) -> eyre::Result<()> {
+        if self.iterations == 0 {
+            eyre::bail!("--iterations must be at least 1");
+        }
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let slow_chain = Chain::eth_mainnet();
+        let fast_chain = Chain::base_mainnet();
+        let slow_pair = make_pair(slow_chain.name);
+        let fast_pair = make_pair(fast_chain.name);
+        let mut slow_state = self.make_pair_state(&mut rng, &slow_pair, slow_chain.name);
+        let mut fast_state = self.make_pair_state(&mut rng, &fast_pair, fast_chain.name);
+
+        let inventory = whole_tokens(self.inventory);
+        let strategy = CrossChainSingleHop {
+            slow_pair,
+            slow_chain,
+            fast_pair,
+            fast_chain,
+            slow_inventory: (inventory.clone(), inventory.clone()),
+            fast_inventory: (inventory.clone(), inventory),
+            binary_search_steps: config.binary_search_steps,
+            max_slippage_bps: config.max_slippage_bps,
+            congestion_risk_discount_bps: config.congestion_risk_discount_bps,
+            slow_spot_prices: Default::default(),
+            fast_spot_prices: Default::default(),
+        };
+
+        let mut precompute_times = Vec::with_capacity(self.iterations);
+        let mut signal_times = Vec::with_capacity(self.iterations);
+        let mut signals = 0;
+        for _ in 0..self.iterations {
+            // received again, so the strategy doesn't reuse the last iteration's spot prices
+            slow_state.received_at = Instant::now();
+            fast_state.received_at = Instant::now();
+
+            let start = Instant::now();
+            let precompute = strategy.precompute(&slow_state);
+            precompute_times.push(start.elapsed());
+
+            let start = Instant::now();
+            let signal = strategy.generate_signal(&precompute, &fast_state);
+            signal_times.push(start.elapsed());
+            signals += usize::from(signal.is_ok());
+        }
+
+        println!(
+            "{} pools per chain, {} binary search steps, {} iterations, signal found in {signals}",
+            self.pools, strategy.binary_search_steps, self.iterations
+        );
+        println!(
+            "\n{:<16}  {:>10}  {:>10}  {:>10}  {:>10}",
+            "step", "p50_ms", "p90_ms", "p99_ms", "max_ms"
+        );
+        let mut slowest = Vec::new();
+        for (step, times) in [
+            ("precompute", &mut precompute_times),
+            ("generate_signal", &mut signal_times),
+        ] {
+            times.sort();
+            let [p50, p90, p99, max] = [0.5, 0.9, 0.99, 1.0].map(|q| millis(percentile(times, q)));
+            println!("{step:<16}  {p50:>10.3}  {p90:>10.3}  {p99:>10.3}  {max:>10.3}");
+            if self.max_p99_ms.is_some_and(|limit| p99 > limit) {
+                slowest.push(format!("{step} {p99:.3}ms"));
+            }
+        }
+
+        if !slowest.is_empty() {
+            eyre::bail!(
+                "p99 over {}ms: {}",
+                self.max_p99_ms.expect("only exceeded when set"),
+                slowest.join(", ")
+            );
+        }
+
+        Ok(())
+    }
+
+    /// A state of `pair` on `chain` with the configured number of pools, each priced at 1 plus or
+    /// minus up to `--spread-bps`
+    fn make_pair_state(
+        &self,
+        rng: &mut StdRng,
+        pair: &Pair,
+        chain: tycho_common::models::Chain,
+    ) -> PairState {
+        let mut states: HashMap<PoolId, Arc<dyn ProtocolSim>> = HashMap::with_capacity(self.pools);
+        let mut metadata = HashMap::with_capacity(self.pools);
+        for i in 0..self.pools {
+            let address =
+                Bytes::from_str(&format!("0x{:040x}", 0x1000 + i)).expect("well-formed address");
+            let id = PoolId::from(address.to_string());
+            let deviation = rng.gen_range(-1.0..=1.0) * self.spread_bps as f64 / 10_000.0;
+            let reserve = self.reserve as f64 * 10f64.powi(DECIMALS as i32);
+
+            states.insert(
+                id.clone(),
+                Arc::new(UniswapV2State::new(
+                    U256::from(reserve as u128),
+                    U256::from((reserve * (1.0 + deviation)) as u128),
+                )),
+            );
+            metadata.insert(
+                id,
+                Arc::new(ProtocolComponent::new(
+                    address.clone(),
+                    "uniswap_v2".to_string(),
+                    "uniswap_v2_pool".to_string(),
+                    chain,
+                    vec![pair.token_a().clone(), pair.token_b().clone()],
+                    vec![address],
+                    HashMap::new(),
+                    Bytes::default(),
+                    Default::default(),
+                )),
+            );
+        }
+
+        let pools: HashSet<_> = states.keys().cloned().collect();
+        let pool_meta = metadata
+            .iter()
+            .map(|(id, component)| (id.clone(), PoolMeta::from_component(component)))
+            .collect();
+        PairState {
+            block_height: 1,
+            states,
+            modified_pools: Arc::new(pools),
+            unmodified_pools: Arc::new(HashSet::new()),
+            removed_pools: Arc::new(HashSet::new()),
+            metadata,
+            pool_meta,
+            native_balance: None,
+            finality: Default::default(),
+            timestamp: None,
+            base_fee_per_gas: None,
+            received_at: Instant::now(),
+        }
+    }
+}
+
+/// The synthetic pair on `chain`
+fn make_pair(chain: tycho_common::models::Chain) -> Pair {
+    let token = |address: &str, symbol: &str| {
+        Token::new(
+            &Bytes::from_str(address).expect("well-formed address"),
+            symbol,
+            DECIMALS,
+            0,
+            &[Some(0)],
+            chain,
+            100,
+        )
+    };
+    Pair::new(
+        token("0x0000000000000000000000000000000000000001", "AAA"),
+        token("0x0000000000000000000000000000000000000002", "BBB"),
+    )
+}
+
+fn whole_tokens(amount: u64) -> BigUint {
+    BigUint::from(amount) * BigUint::from(10u64).pow(DECIMALS)
+}
+
+/// The `q` quantile of the sorted `times`
+fn percentile(times: &[Duration], q: f64) -> Duration {
+    let idx = ((times.len() as f64 * q).ceil() as usize).clamp(1, times.len()) - 1;
+    times[idx]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
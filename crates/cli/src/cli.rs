@@ -1,13 +1,19 @@
 use core::config::Config;
+use std::{
+    fmt::{self, Display},
+    path::PathBuf,
+    process::ExitCode,
+    str::FromStr,
+};
 
 use clap::{Parser, Subcommand, command};
-use color_eyre::eyre::{self, eyre};
+use color_eyre::eyre::{self, Context as _, eyre};
 use tokio_util::sync::CancellationToken;
-use tracing::info;
 
 use crate::{
-    kuma::{self},
-    permit, tokens,
+    admin::AdminCommand, backtest, bench, config::ConfigCommand, db::DbCommand, dry_run, execute,
+    export, export_curves, generate_signals, init, inventory, keys::KeysCommand, permit, pools,
+    quote, replay, score, status, sweep, tokens,
 };
 
 #[derive(Parser)]
@@ -36,17 +42,114 @@ pub(crate) struct StrategyArgs {
     pub(crate) fast_chain: String,
 }
 
+/// Pairs to generate signals for, all between the same two chains.
+#[derive(clap::Args, Debug, Clone)]
+pub(crate) struct PairsArgs {
+    /// First token of a single pair, like `--pair`
+    #[arg(long, requires = "token_b")]
+    pub(crate) token_a: Option<String>,
+
+    /// Second token of a single pair
+    #[arg(long, requires = "token_a")]
+    pub(crate) token_b: Option<String>,
+
+    /// Token symbols of a pair, e.g. `WETH/USDC`. Repeat it for several pairs
+    #[arg(long = "pair")]
+    pub(crate) pairs: Vec<PairArg>,
+
+    /// File listing one `TOKEN_A/TOKEN_B` pair per line, `#` starting a comment
+    #[arg(long)]
+    pub(crate) pairs_file: Option<PathBuf>,
+
+    /// Slow blockchain for the arbitrage
+    #[arg(long)]
+    pub(crate) slow_chain: String,
+
+    /// Fast blockchain for the arbitrage
+    #[arg(long)]
+    pub(crate) fast_chain: String,
+}
+
+impl PairsArgs {
+    /// Every pair given through the flags and the file, without duplicates
+    pub(crate) fn pairs(&self) -> eyre::Result<Vec<PairArg>> {
+        let mut pairs = Vec::new();
+        if let (Some(token_a), Some(token_b)) = (&self.token_a, &self.token_b) {
+            pairs.push(PairArg {
+                token_a: token_a.clone(),
+                token_b: token_b.clone(),
+            });
+        }
+        pairs.extend(self.pairs.iter().cloned());
+        if let Some(path) = &self.pairs_file {
+            let file = std::fs::read_to_string(path)
+                .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+            for (i, line) in file.lines().enumerate() {
+                let line = line.split('#').next().unwrap_or_default().trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let pair = line
+                    .parse()
+                    .wrap_err_with(|| format!("{}:{}", path.display(), i + 1))?;
+                pairs.push(pair);
+            }
+        }
+
+        let mut unique: Vec<PairArg> = Vec::with_capacity(pairs.len());
+        for pair in pairs {
+            if !unique.contains(&pair) {
+                unique.push(pair);
+            }
+        }
+        if unique.is_empty() {
+            eyre::bail!("give a pair with --pair, --pairs-file or --token-a and --token-b");
+        }
+
+        Ok(unique)
+    }
+}
+
+/// Token symbols of a pair, parsed from `TOKEN_A/TOKEN_B`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PairArg {
+    pub(crate) token_a: String,
+    pub(crate) token_b: String,
+}
+
+impl FromStr for PairArg {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().split_once('/') {
+            Some((token_a, token_b)) if !token_a.is_empty() && !token_b.is_empty() => Ok(Self {
+                token_a: token_a.trim().to_owned(),
+                token_b: token_b.trim().to_owned(),
+            }),
+            _ => Err(eyre!(
+                "pair must be two token symbols like WETH/USDC, got `{s}`"
+            )),
+        }
+    }
+}
+
+impl Display for PairArg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.token_a, self.token_b)
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
-    /// Calculate potential arbitrage profit
+    /// Calculate potential arbitrage profit on one or more pairs
     #[command(name = "generate-signals")]
-    GenerateSignals(StrategyArgs),
+    GenerateSignals(generate_signals::GenerateSignals),
 
     /// Perform a dry run (simulated transaction without execution)
-    DryRun(StrategyArgs),
+    DryRun(dry_run::DryRun),
 
     /// Execute arbitrage transaction
-    Execute(StrategyArgs),
+    Execute(execute::Execute),
 
     /// Get all tokens from tycho api
     Tokens(tokens::Tokens),
@@ -54,33 +157,95 @@ enum Commands {
     /// sign permit2 for a token
     #[command(name = "init-permit2")]
     SignPermit2(permit::Permit2),
+
+    /// Export stored spot prices and signals to Parquet files
+    Export(export::Export),
+
+    /// Precompute recorded slow blocks and export their curves to Parquet files
+    ExportCurves(export_curves::ExportCurves),
+
+    /// Replay a strategy over stored spot prices and signals
+    Backtest(backtest::Backtest),
+
+    /// Replay recorded blocks with a grid of strategy parameters and write a CSV of what each found
+    Sweep(sweep::Sweep),
+
+    /// Run a strategy over the Tycho messages recorded by kumad's collectors
+    Replay(replay::Replay),
+
+    /// Score a strategy on a pair's recorded slow and fast state, offline
+    Score(score::Score),
+
+    /// Time precompute and signal search on synthetic pools
+    Bench(bench::Bench),
+
+    /// Compare the account's balances on every chain against the configured inventory
+    Inventory(inventory::Inventory),
+
+    /// Simulate a swap on every pool of a pair
+    Quote(quote::Quote),
+
+    /// List the pools tracked for a pair
+    Pools(pools::Pools),
+
+    /// Show kumad's workers, controls, latest blocks and balances, and latest signal
+    Status(status::Status),
+
+    /// Pause and resume strategies or execution, or set min-profit thresholds, on the running
+    /// kumad through its admin socket
+    Admin(AdminCommand),
+
+    /// Create, import and inspect the encrypted keystores signing on each chain
+    Keys(KeysCommand),
+
+    /// Apply and inspect schema migrations, or run the candle retention job
+    Db(DbCommand),
+
+    /// Inspect the config file format
+    Config(ConfigCommand),
+
+    /// Write a first config file from answers to a few questions
+    Init(init::Init),
 }
 
 impl Cli {
+    /// Runs commands that don't need the config, `None` for the ones that do
+    pub(crate) async fn run_without_config(&self) -> Option<eyre::Result<()>> {
+        match &self.command {
+            Commands::Config(cmd) => Some(cmd.run()),
+            Commands::Init(cmd) => Some(cmd.run().await),
+            _ => None,
+        }
+    }
+
     pub(crate) async fn run(
         self,
         config: Config,
         shutdown_token: CancellationToken,
-    ) -> eyre::Result<()> {
+    ) -> eyre::Result<ExitCode> {
         match &self.command {
-            Commands::GenerateSignals(args) | Commands::DryRun(args) => {
-                let kuma = kuma::Kuma::spawn(config, args.clone(), shutdown_token.clone())
-                    .map_err(|e| eyre!("Failed to spawn Kuma: {e:}"))?;
-
-                // Run the command with the Kuma instance
-                let signal = kuma.generate_signal().await?;
-                info!(%signal, "✅ Generated signal");
-
-                if let Commands::DryRun(_) = self.command {
-                    unimplemented!()
-                };
-            }
-            Commands::Execute(_) => {
-                unimplemented!()
-            }
+            Commands::GenerateSignals(cmd) => return cmd.run(config, shutdown_token).await,
+            Commands::DryRun(cmd) => cmd.run(config, shutdown_token).await?,
+            Commands::Execute(cmd) => cmd.run(config, shutdown_token).await?,
             Commands::Tokens(cmd) => cmd.run(config).await?,
             Commands::SignPermit2(cmd) => cmd.run(config).await?,
+            Commands::Export(cmd) => cmd.run(config).await?,
+            Commands::ExportCurves(cmd) => cmd.run(config, shutdown_token).await?,
+            Commands::Backtest(cmd) => cmd.run(config).await?,
+            Commands::Replay(cmd) => cmd.run(config, shutdown_token).await?,
+            Commands::Sweep(cmd) => cmd.run(config, shutdown_token).await?,
+            Commands::Score(cmd) => cmd.run(config).await?,
+            Commands::Bench(cmd) => cmd.run(config).await?,
+            Commands::Inventory(cmd) => cmd.run(config).await?,
+            Commands::Quote(cmd) => cmd.run(config, shutdown_token).await?,
+            Commands::Pools(cmd) => cmd.run(config, shutdown_token).await?,
+            Commands::Status(cmd) => cmd.run(config).await?,
+            Commands::Admin(cmd) => cmd.run(config).await?,
+            Commands::Keys(cmd) => cmd.run(config).await?,
+            Commands::Db(cmd) => cmd.run(config).await?,
+            Commands::Config(cmd) => cmd.run()?,
+            Commands::Init(cmd) => cmd.run().await?,
         }
-        Ok(())
+        Ok(ExitCode::SUCCESS)
     }
 }
@@ -0,0 +1,30 @@
+use core::config::Config;
+
+use clap::Subcommand;
+use color_eyre::eyre::{self, Context as _};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ConfigCommand {
+    #[command(subcommand)]
+    command: ConfigSubcommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigSubcommand {
+    /// Print the JSON Schema of the config file, for editors to validate and complete it with
+    Schema,
+}
+
+impl ConfigCommand {
+    /// Runs without loading the config, which may not exist or be valid yet
+    pub(crate) fn run(&self) -> eyre::Result<()> {
+        match self.command {
+            ConfigSubcommand::Schema => {
+                let schema = serde_json::to_string_pretty(&Config::json_schema())
+                    .wrap_err("failed to serialize the config schema")?;
+                println!("{schema}");
+            }
+        }
+        Ok(())
+    }
+}
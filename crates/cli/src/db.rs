@@ -0,0 +1,124 @@
+use core::{
+    config::Config,
+    database::{self, CandleInterval, MigrationState},
+};
+use std::{sync::Arc, time::Duration};
+
+use clap::Subcommand;
+use color_eyre::eyre::{self, Context as _, eyre};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct DbCommand {
+    #[command(subcommand)]
+    command: DbSubcommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum DbSubcommand {
+    /// Apply the pending migrations embedded in the binary
+    Migrate,
+
+    /// List every migration and whether it's applied
+    Status,
+
+    /// Aggregate spot price candles and prune the raw spot prices they cover
+    Prune {
+        /// Raw spot prices older than this are deleted, `database.candles.raw_retention_days`
+        /// by default
+        #[arg(long)]
+        retention_days: Option<u64>,
+    },
+}
+
+impl DbCommand {
+    pub(crate) async fn run(&self, config: Config) -> eyre::Result<()> {
+        let (token_configs, _) = config
+            .build_addrs_and_inventory()
+            .map_err(|e| eyre!("failed to parse chain assets: {e}"))?;
+        let retention_days = config
+            .database
+            .candles
+            .as_ref()
+            .and_then(|candles| candles.raw_retention_days);
+
+        // only connect, the commands below do explicitly what the background jobs would
+        let mut db_config = config.database.clone();
+        db_config.migrate_on_start = false;
+        db_config.write_buffer = None;
+        db_config.materialized_views = None;
+        db_config.candles = None;
+        if !matches!(self.command, DbSubcommand::Prune { .. }) {
+            // hypertables are set up on tables the migrations may not have created yet
+            db_config.timescale = None;
+        }
+        let db = database::Handle::from_config(db_config, Arc::new(token_configs))
+            .await
+            .wrap_err("failed to connect to the database")?;
+
+        match &self.command {
+            DbSubcommand::Migrate => {
+                db.migrate().await?;
+                print_status(&db).await?;
+            }
+            DbSubcommand::Status => print_status(&db).await?,
+            DbSubcommand::Prune {
+                retention_days: flag,
+            } => {
+                let days = flag.or(retention_days).ok_or_else(|| {
+                    eyre!("pass --retention-days or set database.candles.raw_retention_days")
+                })?;
+                let candles = db.candle_repository();
+                for &interval in CandleInterval::ALL {
+                    let rows = candles
+                        .aggregate(interval)
+                        .await
+                        .wrap_err_with(|| format!("failed to aggregate {interval:?} candles"))?;
+                    println!("Aggregated {rows} {interval:?} candles");
+                }
+                let rows = candles
+                    .prune_raw(Duration::from_secs(days * 24 * 60 * 60))
+                    .await
+                    .wrap_err("failed to prune raw spot prices")?;
+                println!("Pruned {rows} raw spot prices older than {days} days");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+async fn print_status(db: &database::Handle) -> eyre::Result<()> {
+    let migrations = db
+        .migration_status()
+        .await
+        .wrap_err("failed to read the applied migrations")?;
+
+    println!(
+        "{:>8}  {:<9}  {:>12}  description",
+        "version", "state", "applied_at"
+    );
+    for migration in &migrations {
+        let state = match migration.state {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::Failed => "FAILED",
+            MigrationState::Modified => "MODIFIED",
+            MigrationState::Unknown => "unknown",
+        };
+        let applied_at = migration
+            .applied_at
+            .map(|at| at.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:>8}  {state:<9}  {applied_at:>12}  {}",
+            migration.version, migration.description
+        );
+    }
+    let pending = migrations
+        .iter()
+        .filter(|migration| migration.state == MigrationState::Pending)
+        .count();
+    println!("{} migrations, {pending} pending", migrations.len());
+
+    Ok(())
+}
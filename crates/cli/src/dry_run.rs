@@ -0,0 +1,91 @@
+use core::{
+    config::Config,
+    execution::{self, Leg},
+    rpc::RpcEndpoints,
+};
+
+use color_eyre::eyre::{self, eyre};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive as _;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::{cli::StrategyArgs, kuma::Kuma};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct DryRun {
+    #[command(flatten)]
+    pub strategy: StrategyArgs,
+}
+
+impl DryRun {
+    pub(crate) async fn run(
+        &self,
+        config: Config,
+        shutdown_token: CancellationToken,
+    ) -> eyre::Result<()> {
+        let kuma = Kuma::spawn(config.clone(), self.strategy.clone(), shutdown_token)
+            .map_err(|e| eyre!("Failed to spawn Kuma: {e:}"))?;
+        let signal = kuma.generate_signal().await?;
+        info!(%signal, "✅ Generated signal");
+
+        let (slow, fast) = execution::encode_signal(
+            &signal,
+            &config.signer(&signal.slow_chain)?,
+            &config.signer(&signal.fast_chain)?,
+        )?;
+
+        let mut reverted = 0;
+        for (name, leg) in [("slow", &slow), ("fast", &fast)] {
+            if !simulate(name, leg).await? {
+                reverted += 1;
+            }
+        }
+        if reverted > 0 {
+            eyre::bail!("{reverted} of the signal's legs would revert");
+        }
+
+        Ok(())
+    }
+}
+
+/// Simulates `leg` and prints its expected outcome, `false` if its transaction reverts.
+pub(crate) async fn simulate(name: &str, leg: &Leg) -> eyre::Result<bool> {
+    let rpc = RpcEndpoints::new(&leg.chain)?;
+    let swap = &leg.swap;
+    let (token_in, token_out) = (&swap.token_in, &swap.token_out);
+
+    println!(
+        "{name} leg on {}: sell {} {} for at least {} {} (simulated {})",
+        leg.chain.name,
+        amount(&swap.amount_in, token_in.decimals),
+        token_in.symbol,
+        amount(&leg.min_amount_out, token_out.decimals),
+        token_out.symbol,
+        amount(&swap.amount_out, token_out.decimals),
+    );
+
+    let simulation = match leg.simulate(&rpc).await {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            println!("  reverted: {e:#}");
+            return Ok(false);
+        }
+    };
+    let gas_token = &leg.chain.gas_token;
+    println!(
+        "  eth_call: {} {}, {} gas at {:.3} gwei = {} {}",
+        amount(&simulation.amount_out, token_out.decimals),
+        token_out.symbol,
+        simulation.gas,
+        simulation.gas_price as f64 / 1e9,
+        amount(&simulation.gas_cost(), gas_token.decimals),
+        gas_token.symbol,
+    );
+
+    Ok(true)
+}
+
+pub(crate) fn amount(units: &BigUint, decimals: u32) -> f64 {
+    units.to_f64().unwrap_or(f64::INFINITY) / 10f64.powi(decimals as i32)
+}
@@ -0,0 +1,274 @@
+use core::{
+    collector,
+    config::Config,
+    database::{self, ExecutionLeg, ExecutionStatus, NewExecution},
+    execution::{self, Leg},
+    rpc::RpcEndpoints,
+    state::balances::Inventory,
+};
+use std::{
+    io::{self, BufRead as _, Write as _},
+    sync::Arc,
+    time::Duration,
+};
+
+use alloy::{
+    primitives::{Address, B256},
+    signers::local::PrivateKeySigner,
+};
+use color_eyre::eyre::{self, Context as _, eyre};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::{
+    cli::StrategyArgs,
+    dry_run::{amount, simulate},
+    kuma::Kuma,
+};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Execute {
+    #[command(flatten)]
+    pub strategy: StrategyArgs,
+
+    /// Broadcast without asking for confirmation once both legs simulate successfully
+    #[arg(long)]
+    pub yes: bool,
+
+    /// How long to wait for each leg to be mined
+    #[arg(long, default_value_t = 120)]
+    pub confirmation_timeout_secs: u64,
+}
+
+impl Execute {
+    pub(crate) async fn run(
+        &self,
+        config: Config,
+        shutdown_token: CancellationToken,
+    ) -> eyre::Result<()> {
+        let (token_configs, _) = config
+            .build_addrs_and_inventory()
+            .map_err(|e| eyre!("failed to parse chain assets: {e}"))?;
+        let db = database::Handle::from_config(config.database.clone(), Arc::new(token_configs))
+            .await
+            .wrap_err("failed to connect to the database")?;
+
+        let kuma = Kuma::spawn(config.clone(), self.strategy.clone(), shutdown_token)
+            .map_err(|e| eyre!("Failed to spawn Kuma: {e:}"))?;
+        let signal = kuma.generate_signal().await?;
+        info!(%signal, "✅ Generated signal");
+
+        let slow_signer = config.signer(&signal.slow_chain)?;
+        let fast_signer = config.signer(&signal.fast_chain)?;
+        let (slow, fast) = execution::encode_signal(&signal, &slow_signer, &fast_signer)?;
+
+        let slow_rpc = RpcEndpoints::new(&slow.chain)?;
+        let fast_rpc = RpcEndpoints::new(&fast.chain)?;
+        check_inventory("slow", &slow, &slow_rpc, &slow_signer, &config).await?;
+        check_inventory("fast", &fast, &fast_rpc, &fast_signer, &config).await?;
+
+        // never broadcast a leg that would revert, it would only burn gas
+        let (slow_ok, fast_ok) = (
+            simulate("slow", &slow).await?,
+            simulate("fast", &fast).await?,
+        );
+        if !(slow_ok && fast_ok) {
+            eyre::bail!("not broadcasting, the signal's legs don't both simulate successfully");
+        }
+        if !self.yes && !confirm()? {
+            println!("Aborted, nothing was broadcast");
+            return Ok(());
+        }
+
+        // stored before anything is broadcast, so every submitted leg can be recorded against it
+        let signal_id = db
+            .signal_repository()
+            .insert(signal.clone())
+            .await
+            .wrap_err("not broadcasting, failed to record the signal")?;
+        info!(signal_id, "Recorded signal");
+
+        // both legs go out at once, a failed submission doesn't hold back the other
+        let (slow_hash, fast_hash) = tokio::join!(
+            submit("slow", &slow, &slow_rpc, &slow_signer),
+            submit("fast", &fast, &fast_rpc, &fast_signer),
+        );
+
+        let timeout = Duration::from_secs(self.confirmation_timeout_secs);
+        let (slow_execution, fast_execution) = tokio::join!(
+            report(
+                signal_id,
+                ExecutionLeg::Slow,
+                &slow,
+                &slow_rpc,
+                slow_hash,
+                &slow_signer,
+                timeout
+            ),
+            report(
+                signal_id,
+                ExecutionLeg::Fast,
+                &fast,
+                &fast_rpc,
+                fast_hash,
+                &fast_signer,
+                timeout
+            ),
+        );
+
+        let executions = db.execution_repository();
+        let mut executed = true;
+        for execution in [slow_execution, fast_execution] {
+            let Some(execution) = execution else {
+                executed = false;
+                continue;
+            };
+            executed &= execution.status == ExecutionStatus::Mined;
+            let id = executions.insert(&execution).await.wrap_err_with(|| {
+                format!("failed to record the {} leg's execution", execution.leg)
+            })?;
+            info!(id, leg = %execution.leg, tx_hash = %execution.tx_hash, "Recorded execution");
+        }
+        if !executed {
+            eyre::bail!("the signal wasn't fully executed");
+        }
+
+        Ok(())
+    }
+}
+
+/// Asks on stdin whether to broadcast both legs.
+fn confirm() -> eyre::Result<bool> {
+    print!("Broadcast both legs? [y/N] ");
+    io::stdout().flush().wrap_err("failed to flush stdout")?;
+    let mut answer = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .wrap_err("failed to read the confirmation from stdin")?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Fails when the signing account doesn't hold the leg's amount in, or the chain's configured
+/// minimum of gas, as of the latest block.
+async fn check_inventory(
+    name: &str,
+    leg: &Leg,
+    rpc: &RpcEndpoints,
+    signer: &PrivateKeySigner,
+    config: &Config,
+) -> eyre::Result<()> {
+    let token_in = &leg.swap.token_in;
+    let address = Address::try_from(token_in.address.as_ref())
+        .wrap_err_with(|| format!("invalid token address {}", token_in.address))?;
+    let balances = collector::fetch_account_balances(rpc, signer.address(), &[address])
+        .await
+        .wrap_err_with(|| format!("failed to fetch the {name} leg's balances"))?;
+
+    if let Some(min_gas_balance) = config.min_gas_balance(&leg.chain)? {
+        if balances.native < min_gas_balance {
+            eyre::bail!(
+                "not executing the {name} leg: {} {} held, less than the minimum {min_gas_balance}",
+                balances.native,
+                leg.chain.native_token()
+            );
+        }
+    }
+
+    let mut inventory = Inventory::new([token_in.clone()]);
+    inventory.update_held(balances.height, &balances.tokens);
+    leg.check_inventory(&inventory)
+        .wrap_err_with(|| format!("not executing the {name} leg"))
+}
+
+async fn submit(
+    name: &str,
+    leg: &Leg,
+    rpc: &RpcEndpoints,
+    signer: &PrivateKeySigner,
+) -> eyre::Result<B256> {
+    let tx_hash = leg
+        .submit(rpc, signer)
+        .await
+        .wrap_err_with(|| format!("failed to submit {name} leg"))?;
+    info!(leg = name, chain = %leg.chain, %tx_hash, "📤 Submitted leg");
+
+    Ok(tx_hash)
+}
+
+/// Waits for a submitted leg to be mined and prints what it realized. Returns the execution to
+/// record, `pending` if it wasn't mined in time, or `None` if the leg wasn't submitted.
+async fn report(
+    signal_id: i64,
+    name: ExecutionLeg,
+    leg: &Leg,
+    rpc: &RpcEndpoints,
+    tx_hash: eyre::Result<B256>,
+    signer: &PrivateKeySigner,
+    timeout: Duration,
+) -> Option<NewExecution> {
+    let token_out = &leg.swap.token_out;
+    let pending = |tx_hash| NewExecution {
+        signal_id,
+        leg: name,
+        chain: leg.chain.clone(),
+        tx_hash,
+        status: ExecutionStatus::Pending,
+        block_height: None,
+        token_out_symbol: token_out.symbol.clone(),
+        expected_amount_out: leg.swap.amount_out.clone(),
+        amount_out: None,
+        gas_used: None,
+        gas_price: None,
+    };
+    let tx_hash = match tx_hash {
+        Ok(tx_hash) => tx_hash,
+        Err(e) => {
+            println!("{name} leg on {}: {e:#}", leg.chain.name);
+            return None;
+        }
+    };
+    let execution = match leg.confirm(rpc, tx_hash, signer.address(), timeout).await {
+        Ok(execution) => execution,
+        Err(e) => {
+            println!("{name} leg on {}: {e:#}", leg.chain.name);
+            return Some(pending(tx_hash));
+        }
+    };
+
+    let gas_token = &leg.chain.gas_token;
+    let status = if execution.success {
+        "mined"
+    } else {
+        "REVERTED"
+    };
+    println!(
+        "{name} leg on {}: {status} in block {}, received {} {} (expected {}), gas {} = {} {}",
+        leg.chain.name,
+        execution
+            .block
+            .map(|block| block.to_string())
+            .unwrap_or_else(|| "?".to_string()),
+        amount(&execution.amount_out, token_out.decimals),
+        token_out.symbol,
+        amount(&leg.swap.amount_out, token_out.decimals),
+        execution.gas_used,
+        amount(&execution.gas_cost(), gas_token.decimals),
+        gas_token.symbol,
+    );
+    println!("  {}", execution.tx_hash);
+
+    Some(NewExecution {
+        status: if execution.success {
+            ExecutionStatus::Mined
+        } else {
+            ExecutionStatus::Reverted
+        },
+        block_height: execution.block,
+        amount_out: Some(execution.amount_out.clone()),
+        gas_used: Some(execution.gas_used),
+        gas_price: Some(execution.gas_price),
+        ..pending(execution.tx_hash)
+    })
+}
@@ -0,0 +1,73 @@
+use core::{
+    config::Config,
+    database::{self, Dataset, ExportRequest},
+};
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::eyre::{self, Context as _, eyre};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Export {
+    /// Start of the time range to export, in seconds since the unix epoch
+    #[arg(long)]
+    pub from: u64,
+
+    /// Inclusive end of the time range, in seconds since the unix epoch. Defaults to now
+    #[arg(long)]
+    pub to: Option<u64>,
+
+    /// Comma separated datasets to export (`spot_prices`, `signals`, `precompute_curves`,
+    /// `executions`). Defaults to all of them
+    #[arg(long, value_delimiter = ',')]
+    pub datasets: Vec<Dataset>,
+
+    /// Directory the Parquet files are written under
+    #[arg(long, default_value = "export")]
+    pub dir: PathBuf,
+}
+
+impl Export {
+    pub(crate) async fn run(&self, config: Config) -> eyre::Result<()> {
+        let (token_configs, _) = config
+            .build_addrs_and_inventory()
+            .map_err(|e| eyre!("failed to parse chain assets: {e}"))?;
+        let db = database::Handle::from_config(config.database.clone(), Arc::new(token_configs))
+            .await
+            .wrap_err("failed to connect to the database")?;
+
+        let to = match self.to {
+            Some(to) => to,
+            None => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .wrap_err("system clock is before the unix epoch")?
+                .as_secs(),
+        };
+        let datasets = if self.datasets.is_empty() {
+            Dataset::ALL.to_vec()
+        } else {
+            self.datasets.clone()
+        };
+
+        let summary = db
+            .export_parquet(&ExportRequest {
+                datasets,
+                from: self.from,
+                to,
+                dir: self.dir.clone(),
+            })
+            .await?;
+
+        println!(
+            "Exported {} rows to {} files under {}",
+            summary.rows,
+            summary.files.len(),
+            self.dir.display()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+//! Writes the precompute curves of recorded slow blocks to Parquet.
+//!
+//! kumad stores the curves of its live precomputes with `record_precompute_curves`; this rebuilds
+//! them offline from the collectors' recordings instead, in the same layout as
+//! `kuma export --datasets precompute_curves`.
+use core::{config::Config, database};
+use std::path::PathBuf;
+
+use color_eyre::eyre::{self, eyre};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    backtest::Bound,
+    replay::{Recordings, SLOW},
+};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ExportCurves {
+    /// Directory the collectors recorded to, i.e. kumad's `record_dir`
+    #[arg(long)]
+    pub input: PathBuf,
+
+    /// Id of the configured strategy whose precomputes are exported, e.g.
+    /// `weth-usdc-ethereum-base`
+    #[arg(long)]
+    pub strategy: String,
+
+    /// First slow block exported, by the time it was received in seconds since the unix epoch or
+    /// as `block:<height>`. Defaults to the start of the recordings
+    #[arg(long)]
+    pub from: Option<Bound>,
+
+    /// Last slow block exported, like `--from`. Defaults to the end of the recordings
+    #[arg(long)]
+    pub to: Option<Bound>,
+
+    /// Directory the Parquet files are written under
+    #[arg(long, default_value = "export")]
+    pub dir: PathBuf,
+}
+
+impl ExportCurves {
+    pub(crate) async fn run(
+        &self,
+        config: Config,
+        shutdown_token: CancellationToken,
+    ) -> eyre::Result<()> {
+        let strategy_config = config
+            .strategies
+            .iter()
+            .find(|strategy| strategy.id() == self.strategy.to_lowercase())
+            .ok_or_else(|| eyre!("no strategy {} in the config", self.strategy))?;
+        let params = config.strategy_params(strategy_config);
+
+        let mut recordings = Recordings::open(&config, &self.input, strategy_config).await?;
+        let strategy = recordings.setup.strategy(params);
+        let chain = recordings.setup.chains[SLOW].name.to_string();
+
+        let mut curves = Vec::new();
+        while let Some((idx, received_at, state)) = recordings.next().await? {
+            if shutdown_token.is_cancelled() {
+                break;
+            }
+            if idx != SLOW {
+                continue;
+            }
+            let received_at = received_at / 1000;
+            let height = state.block_height;
+            if self.past(received_at, height) {
+                break;
+            }
+            if self.before(received_at, height) {
+                continue;
+            }
+
+            let precompute = strategy.precompute(&state);
+            curves.extend(
+                precompute
+                    .curves()
+                    .into_iter()
+                    .map(|curve| (chain.clone(), received_at as i64, curve)),
+            );
+        }
+
+        let summary = database::export_curves(curves, &self.dir).await?;
+        println!(
+            "Exported {} curves to {} files under {}",
+            summary.rows,
+            summary.files.len(),
+            self.dir.display()
+        );
+
+        Ok(())
+    }
+
+    /// Whether the slow block `height`, received at `received_at` in seconds, comes before the
+    /// window
+    fn before(&self, received_at: u64, height: u64) -> bool {
+        match self.from {
+            None => false,
+            Some(Bound::Time(from)) => received_at < from,
+            Some(Bound::Block(from)) => height < from,
+        }
+    }
+
+    /// Whether the slow block `height`, received at `received_at` in seconds, comes after the
+    /// window
+    fn past(&self, received_at: u64, height: u64) -> bool {
+        match self.to {
+            None => false,
+            Some(Bound::Time(to)) => received_at > to,
+            Some(Bound::Block(to)) => height > to,
+        }
+    }
+}
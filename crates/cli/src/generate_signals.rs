@@ -0,0 +1,111 @@
+use core::{config::Config, signals};
+use std::process::ExitCode;
+
+use color_eyre::eyre::{self, Context as _, eyre};
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::{cli::PairsArgs, kuma::Kuma};
+
+/// Exit code when no pair produced a signal, telling it apart from a failure
+const NO_SIGNAL_EXIT_CODE: u8 = 2;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct GenerateSignals {
+    #[command(flatten)]
+    pub pairs: PairsArgs,
+
+    /// Evaluate only the first slow and fast blocks, reporting why a pair has no signal instead
+    /// of waiting for blocks that have one
+    #[arg(long)]
+    pub once: bool,
+
+    /// How the signals are printed to stdout
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Text,
+    /// A JSON array with an object per pair
+    Json,
+}
+
+/// What was found for a pair, as printed with `--format json`
+#[derive(Serialize)]
+struct PairResult<'a> {
+    pair: String,
+    slow_chain: &'a str,
+    fast_chain: &'a str,
+    /// `None` when the pair has no signal
+    signal: Option<signals::CrossChainSingleHop>,
+    /// Why the pair has no signal, outermost first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    reasons: Vec<String>,
+}
+
+impl GenerateSignals {
+    /// Exits successfully when any pair has a signal, with [`NO_SIGNAL_EXIT_CODE`] when none has
+    pub(crate) async fn run(
+        &self,
+        config: Config,
+        shutdown_token: CancellationToken,
+    ) -> eyre::Result<ExitCode> {
+        let pairs = self.pairs.pairs()?;
+        let kuma = Kuma::spawn_pairs(
+            config,
+            &self.pairs.slow_chain,
+            &self.pairs.fast_chain,
+            &pairs,
+            shutdown_token,
+        )
+        .map_err(|e| eyre!("Failed to spawn Kuma: {e:}"))?;
+
+        let mut results = Vec::with_capacity(pairs.len());
+        for (pair, signal) in kuma.generate_signals(self.once).await {
+            let (signal, reasons) = match signal {
+                Ok(signal) => {
+                    info!(%pair, %signal, "✅ Generated signal");
+                    (Some(signal), Vec::new())
+                }
+                Err(e) => {
+                    warn!(%pair, error = %e, "No signal");
+                    (None, e.chain().map(ToString::to_string).collect())
+                }
+            };
+            results.push(PairResult {
+                pair: pair.to_string(),
+                slow_chain: &self.pairs.slow_chain,
+                fast_chain: &self.pairs.fast_chain,
+                signal,
+                reasons,
+            });
+        }
+
+        match self.format {
+            Format::Text => {
+                for result in &results {
+                    match &result.signal {
+                        Some(signal) => println!("{}: {signal}", result.pair),
+                        None => {
+                            println!("{}: no signal, {}", result.pair, result.reasons.join(": "))
+                        }
+                    }
+                }
+            }
+            Format::Json => {
+                let json = serde_json::to_string_pretty(&results)
+                    .wrap_err("failed to serialize the signals")?;
+                println!("{json}");
+            }
+        }
+
+        Ok(if results.iter().any(|result| result.signal.is_some()) {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::from(NO_SIGNAL_EXIT_CODE)
+        })
+    }
+}
@@ -0,0 +1,537 @@
+//! Interactive setup of a first config file.
+//!
+//! Asks for the chains, tokens, strategies, Tycho API key and database, checks each chain's RPC
+//! serves the chain it's named after and each token address is an ERC20 with the given decimals,
+//! then writes a config that loads and validates like any other.
+use core::{chain::KnownChain, config::Config};
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead as _, Write as _},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use alloy::{
+    primitives::Address,
+    providers::{DynProvider, Provider as _, ProviderBuilder},
+    sol,
+};
+use color_eyre::eyre::{self, Context as _, eyre};
+use figment::{
+    Figment,
+    providers::{Format as _, Yaml},
+};
+use tycho_common::models::Chain;
+
+sol! {
+    #[sol(rpc)]
+    interface IERC20 {
+        function decimals() external view returns (uint8);
+    }
+}
+
+/// Public endpoints offered for each chain: RPC and Tycho
+const DEFAULT_ENDPOINTS: &[(Chain, &str, &str)] = &[
+    (
+        Chain::Ethereum,
+        "https://ethereum-rpc.publicnode.com",
+        "tycho-beta.propellerheads.xyz",
+    ),
+    (
+        Chain::Base,
+        "https://mainnet.base.org",
+        "tycho-base-beta.propellerheads.xyz",
+    ),
+    (
+        Chain::Unichain,
+        "https://mainnet.unichain.org",
+        "tycho-unichain-beta.propellerheads.xyz",
+    ),
+    (
+        Chain::Arbitrum,
+        "https://arb1.arbitrum.io/rpc",
+        "tycho-arbitrum-beta.propellerheads.xyz",
+    ),
+];
+
+/// Canonical Permit2 deployment, at the same address on every chain
+const PERMIT2_ADDRESS: &str = "0x000000000022d473030f116ddee9f6b43ac78ba3";
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Init {
+    /// File the config is written to, its extension must be .yaml or .yml
+    #[arg(long, default_value = "kuma.yaml")]
+    pub output: PathBuf,
+
+    /// Overwrite the output file if it exists
+    #[arg(long)]
+    pub force: bool,
+
+    /// Don't check the RPC endpoints and token addresses, e.g. when offline
+    #[arg(long)]
+    pub skip_checks: bool,
+}
+
+struct ChainAnswer {
+    name: Chain,
+    rpc_url: String,
+    tycho_url: String,
+    /// Connected to `rpc_url`, unless checks are skipped
+    provider: Option<DynProvider>,
+}
+
+struct TokenAnswer {
+    symbol: String,
+    addresses: Vec<(Chain, Address)>,
+    decimals: u8,
+    inventory: String,
+}
+
+struct StrategyAnswer {
+    token_a: String,
+    token_b: String,
+    slow_chain: Chain,
+    fast_chain: Chain,
+}
+
+struct DatabaseAnswer {
+    user: String,
+    password: String,
+    host: String,
+    port: u16,
+    dbname: String,
+}
+
+struct Answers {
+    chains: Vec<ChainAnswer>,
+    tokens: Vec<TokenAnswer>,
+    strategies: Vec<StrategyAnswer>,
+    tycho_api_key: String,
+    private_key: String,
+    /// `postgres`, `file` or `noop`
+    store: String,
+    database: DatabaseAnswer,
+}
+
+impl Init {
+    /// Runs without loading the config, which this creates
+    pub(crate) async fn run(&self) -> eyre::Result<()> {
+        if !matches!(
+            self.output
+                .extension()
+                .and_then(|extension| extension.to_str()),
+            Some("yaml" | "yml")
+        ) {
+            eyre::bail!("{} isn't a .yaml or .yml file", self.output.display());
+        }
+        if self.output.exists() && !self.force {
+            eyre::bail!(
+                "{} already exists, pass --force to overwrite it",
+                self.output.display()
+            );
+        }
+
+        println!("Answer each question or press enter to take the default in brackets.\n");
+        let chains = self.ask_chains().await?;
+        let tokens = self.ask_tokens(&chains).await?;
+        let strategies = ask_strategies(&chains, &tokens)?;
+        let tycho_api_key = ask("Tycho API key", Some("${TYCHO_API_KEY}"))?;
+        let private_key = ask(
+            "Private key signing the trades, or a reference to it",
+            Some("${KUMA_PRIVATE_KEY}"),
+        )?;
+        let store = ask_parsed(
+            "Store spot prices and signals in (postgres, file, noop)",
+            Some("postgres"),
+            |store| match store {
+                "postgres" | "file" | "noop" => Ok(store.to_owned()),
+                other => Err(eyre!("unknown store {other}")),
+            },
+        )?;
+        let database = ask_database()?;
+
+        let answers = Answers {
+            chains,
+            tokens,
+            strategies,
+            tycho_api_key,
+            private_key,
+            store,
+            database,
+        };
+        let yaml = render(&answers);
+        let config: Config = Figment::from(Yaml::string(&yaml))
+            .extract()
+            .wrap_err("the generated config doesn't parse")?;
+        if let Err(errors) = config.validate() {
+            eyre::bail!("the generated config is invalid:\n{errors}");
+        }
+
+        std::fs::write(&self.output, yaml)
+            .wrap_err_with(|| format!("failed to write {}", self.output.display()))?;
+        println!("\nWrote {}", self.output.display());
+        if answers.store == "postgres" {
+            println!("Run `kuma db migrate` to create the database's tables before starting kumad");
+        }
+
+        Ok(())
+    }
+
+    async fn ask_chains(&self) -> eyre::Result<Vec<ChainAnswer>> {
+        let known = DEFAULT_ENDPOINTS
+            .iter()
+            .map(|(name, _, _)| name.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let names = ask_parsed(
+            &format!("Chains to trade on, comma separated ({known})"),
+            Some("ethereum,base"),
+            parse_chains,
+        )?;
+
+        let mut chains = Vec::with_capacity(names.len());
+        for name in names {
+            let (_, rpc_default, tycho_default) = DEFAULT_ENDPOINTS
+                .iter()
+                .find(|(known, _, _)| *known == name)
+                .expect("parsed chains have default endpoints");
+            let known = KnownChain::find(name)?;
+
+            let (rpc_url, provider) = loop {
+                let rpc_url = ask(&format!("{name} RPC url"), Some(rpc_default))?;
+                if self.skip_checks {
+                    break (rpc_url, None);
+                }
+                match connect(&rpc_url, known).await {
+                    Ok(provider) => break (rpc_url, Some(provider)),
+                    Err(e) => println!("  {e:#}"),
+                }
+            };
+            let tycho_url = ask(&format!("{name} Tycho url"), Some(tycho_default))?;
+
+            chains.push(ChainAnswer {
+                name,
+                rpc_url,
+                tycho_url,
+                provider,
+            });
+        }
+
+        Ok(chains)
+    }
+
+    async fn ask_tokens(&self, chains: &[ChainAnswer]) -> eyre::Result<Vec<TokenAnswer>> {
+        let mut tokens: Vec<TokenAnswer> = Vec::new();
+        loop {
+            let default = match tokens.len() {
+                0 => Some("WETH"),
+                1 => Some("USDC"),
+                _ => None,
+            };
+            let symbol = ask("\nToken symbol, empty when done", default)?;
+            if symbol.is_empty() {
+                if tokens.len() < 2 {
+                    println!("  a strategy needs at least two tokens");
+                    continue;
+                }
+                return Ok(tokens);
+            }
+            if tokens.iter().any(|token| token.symbol == symbol) {
+                println!("  {symbol} is already configured");
+                continue;
+            }
+
+            let mut addresses = Vec::new();
+            let mut onchain_decimals = Vec::new();
+            for chain in chains {
+                loop {
+                    let address = ask(
+                        &format!(
+                            "{symbol} address on {}, empty if it isn't deployed",
+                            chain.name
+                        ),
+                        None,
+                    )?;
+                    if address.is_empty() {
+                        break;
+                    }
+                    let address = match Address::from_str(&address) {
+                        Ok(address) => address,
+                        Err(e) => {
+                            println!("  invalid address: {e}");
+                            continue;
+                        }
+                    };
+                    if let Some(provider) = &chain.provider {
+                        match IERC20::new(address, provider.clone())
+                            .decimals()
+                            .call()
+                            .await
+                        {
+                            Ok(decimals) => onchain_decimals.push((chain.name, decimals)),
+                            Err(e) => {
+                                println!("  {address} isn't an ERC20 on {}: {e}", chain.name);
+                                continue;
+                            }
+                        }
+                    }
+                    addresses.push((chain.name, address));
+                    break;
+                }
+            }
+            if addresses.is_empty() {
+                println!("  {symbol} has no address, skipping it");
+                continue;
+            }
+
+            let default_decimals = onchain_decimals
+                .first()
+                .map(|(_, decimals)| decimals.to_string());
+            let decimals = loop {
+                let decimals: u8 = ask_parsed(
+                    &format!("{symbol} decimals"),
+                    default_decimals.as_deref(),
+                    |decimals| decimals.parse().wrap_err("not a number of decimals"),
+                )?;
+                match onchain_decimals
+                    .iter()
+                    .find(|(_, onchain)| *onchain != decimals)
+                {
+                    Some((chain, onchain)) => {
+                        println!("  {symbol} has {onchain} decimals on {chain}");
+                    }
+                    None => break decimals,
+                }
+            };
+            let inventory = ask(&format!("{symbol} inventory, in whole tokens"), Some("0"))?;
+
+            tokens.push(TokenAnswer {
+                symbol,
+                addresses,
+                decimals,
+                inventory,
+            });
+        }
+    }
+}
+
+/// Connects to `rpc_url`, checking it serves `known`.
+async fn connect(rpc_url: &str, known: &KnownChain) -> eyre::Result<DynProvider> {
+    let provider = ProviderBuilder::new()
+        .connect(rpc_url)
+        .await
+        .wrap_err_with(|| format!("failed to connect to {rpc_url}"))?
+        .erased();
+    let chain_id = provider
+        .get_chain_id()
+        .await
+        .wrap_err_with(|| format!("failed to read the chain id from {rpc_url}"))?;
+    let expected = alloy_chains::Chain::from(known.named).id();
+    if chain_id != expected {
+        eyre::bail!(
+            "{rpc_url} serves chain id {chain_id}, not {}'s {expected}",
+            known.name
+        );
+    }
+
+    Ok(provider)
+}
+
+fn parse_chains(names: &str) -> eyre::Result<Vec<Chain>> {
+    let mut chains: Vec<Chain> = Vec::new();
+    for name in names.split(',').map(str::trim) {
+        let chain = Chain::from_str(name).map_err(|e| eyre!("unknown chain {name}: {e}"))?;
+        if !DEFAULT_ENDPOINTS
+            .iter()
+            .any(|(known, _, _)| *known == chain)
+        {
+            eyre::bail!("kuma doesn't support {chain}");
+        }
+        if chains.contains(&chain) {
+            eyre::bail!("{chain} is given twice");
+        }
+        chains.push(chain);
+    }
+    if chains.len() < 2 {
+        eyre::bail!("a strategy needs two chains");
+    }
+
+    Ok(chains)
+}
+
+fn ask_strategies(
+    chains: &[ChainAnswer],
+    tokens: &[TokenAnswer],
+) -> eyre::Result<Vec<StrategyAnswer>> {
+    let deployed = |symbol: &str, chain: Chain| {
+        tokens.iter().any(|token| {
+            token.symbol == symbol && token.addresses.iter().any(|(on, _)| *on == chain)
+        })
+    };
+    let default = format!(
+        "{}/{}:{}/{}",
+        tokens[0].symbol, tokens[1].symbol, chains[0].name, chains[1].name
+    );
+
+    ask_parsed(
+        "\nStrategies as TOKEN_A/TOKEN_B:SLOW_CHAIN/FAST_CHAIN, comma separated",
+        Some(&default),
+        |strategies| {
+            strategies
+                .split(',')
+                .map(|strategy| {
+                    let strategy = strategy.trim();
+                    let (pair, path) = strategy
+                        .split_once(':')
+                        .ok_or_else(|| eyre!("{strategy} is missing its chains"))?;
+                    let (token_a, token_b) = pair
+                        .split_once('/')
+                        .ok_or_else(|| eyre!("{pair} isn't a pair of tokens"))?;
+                    let (slow_chain, fast_chain) = path
+                        .split_once('/')
+                        .ok_or_else(|| eyre!("{path} isn't a pair of chains"))?;
+                    let slow_chain = Chain::from_str(slow_chain)
+                        .map_err(|e| eyre!("unknown chain {slow_chain}: {e}"))?;
+                    let fast_chain = Chain::from_str(fast_chain)
+                        .map_err(|e| eyre!("unknown chain {fast_chain}: {e}"))?;
+                    for token in [token_a, token_b] {
+                        for chain in [slow_chain, fast_chain] {
+                            if !deployed(token, chain) {
+                                eyre::bail!("{token} has no address on {chain}");
+                            }
+                        }
+                    }
+
+                    Ok(StrategyAnswer {
+                        token_a: token_a.to_owned(),
+                        token_b: token_b.to_owned(),
+                        slow_chain,
+                        fast_chain,
+                    })
+                })
+                .collect()
+        },
+    )
+}
+
+fn ask_database() -> eyre::Result<DatabaseAnswer> {
+    println!("\nDatabase the backend reads from, and kumad writes to with the postgres store");
+    Ok(DatabaseAnswer {
+        user: ask("Database user", Some("kuma"))?,
+        password: ask(
+            "Database password, or a reference to it",
+            Some("${KUMA_DB_PASSWORD}"),
+        )?,
+        host: ask("Database host", Some("localhost"))?,
+        port: ask_parsed("Database port", Some("5432"), |port| {
+            port.parse().wrap_err("not a port")
+        })?,
+        dbname: ask("Database name", Some("kuma"))?,
+    })
+}
+
+/// Asks `question` until the answer parses, retrying on errors
+fn ask_parsed<T>(
+    question: &str,
+    default: Option<&str>,
+    parse: impl Fn(&str) -> eyre::Result<T>,
+) -> eyre::Result<T> {
+    loop {
+        match parse(&ask(question, default)?) {
+            Ok(value) => return Ok(value),
+            Err(e) => println!("  {e:#}"),
+        }
+    }
+}
+
+/// Reads the trimmed answer to `question` from stdin, `default` when it's empty
+fn ask(question: &str, default: Option<&str>) -> eyre::Result<String> {
+    match default {
+        Some(default) => print!("{question} [{default}]: "),
+        None => print!("{question}: "),
+    }
+    io::stdout().flush().wrap_err("failed to write to stdout")?;
+
+    let mut line = String::new();
+    if io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .wrap_err("failed to read from stdin")?
+        == 0
+    {
+        eyre::bail!("stdin closed before every question was answered");
+    }
+    let answer = line.trim();
+
+    Ok(match default {
+        Some(default) if answer.is_empty() => default.to_owned(),
+        _ => answer.to_owned(),
+    })
+}
+
+/// Quotes `value` as a YAML string, JSON's double quoted strings being valid YAML
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).expect("strings serialize")
+}
+
+fn render(answers: &Answers) -> String {
+    let mut yaml = String::from(
+        "# Written by `kuma init`. Every option is described in the repository's kuma.yaml\n\n",
+    );
+    let mut line = |line: String| writeln!(yaml, "{line}").expect("writing to a string");
+
+    line("database:".to_owned());
+    let database = &answers.database;
+    line(format!("  user: {}", quote(&database.user)));
+    line(format!("  password: {}", quote(&database.password)));
+    line(format!("  host: {}", quote(&database.host)));
+    line(format!("  port: {}", database.port));
+    line(format!("  dbname: {}", quote(&database.dbname)));
+    line("  max_connections: 10".to_owned());
+    line("  connection_timeout_secs: 30".to_owned());
+    line("  idle_timeout_secs: 600".to_owned());
+    match answers.store.as_str() {
+        "postgres" => {}
+        "file" => line("store:\n  kind: file\n  dir: \".kuma/store\"".to_owned()),
+        store => line(format!("store:\n  kind: {store}")),
+    }
+    line("server:\n  host: \"0.0.0.0\"\n  port: 8080".to_owned());
+
+    line("strategies:".to_owned());
+    for strategy in &answers.strategies {
+        line(format!("  - token_a: {}", quote(&strategy.token_a)));
+        line(format!("    token_b: {}", quote(&strategy.token_b)));
+        line(format!("    slow_chain: {}", strategy.slow_chain));
+        line(format!("    fast_chain: {}", strategy.fast_chain));
+    }
+
+    line("tokens:".to_owned());
+    for token in &answers.tokens {
+        line(format!("  {}:", quote(&token.symbol)));
+        line("    addresses:".to_owned());
+        for (chain, address) in &token.addresses {
+            line(format!("      {chain}: \"{address}\""));
+        }
+        line(format!("    decimals: {}", token.decimals));
+        line("    tax: 0\n    gas: []\n    quality: 100".to_owned());
+        line(format!("    inventory: {}", quote(&token.inventory)));
+    }
+
+    line("chains:".to_owned());
+    for chain in &answers.chains {
+        line(format!("  - name: {}", chain.name));
+        line(format!("    rpc_url: {}", quote(&chain.rpc_url)));
+        line(format!("    tycho_url: {}", quote(&chain.tycho_url)));
+        line(format!("    permit2_address: \"{PERMIT2_ADDRESS}\""));
+    }
+
+    line(format!("tycho_api_key: {}", quote(&answers.tycho_api_key)));
+    line(format!("private_key: {}", quote(&answers.private_key)));
+    line(
+        "binary_search_steps: 1024\nmax_slippage_bps: 25\ncongestion_risk_discount_bps: 0\n\
+         add_tvl_threshold: 10.0\nremove_tvl_threshold: 5.0"
+            .to_owned(),
+    );
+
+    yaml
+}
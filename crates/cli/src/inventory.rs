@@ -0,0 +1,189 @@
+use core::{
+    chain::Chain,
+    collector::{AccountBalances, fetch_account_balances},
+    config::{Config, InventoryForToken},
+    rpc::RpcEndpoints,
+};
+
+use alloy::primitives::Address;
+use color_eyre::eyre::{self, Context as _, eyre};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive as _;
+use serde::Serialize;
+use tycho_common::models::token::Token;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Inventory {
+    /// Print the balances as JSON instead of a table
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Balances of the configured account on one chain.
+#[derive(Debug, Serialize)]
+struct ChainInventory {
+    chain: String,
+    /// Block the balances were read at
+    height: Option<u64>,
+    gas_token: TokenInventory,
+    tokens: Vec<TokenInventory>,
+    /// Why the balances couldn't be read
+    error: Option<String>,
+}
+
+/// Balance of a token next to its configured inventory, both in the token's smallest unit.
+#[derive(Debug, Serialize)]
+struct TokenInventory {
+    symbol: String,
+    address: String,
+    decimals: u32,
+    balance: Option<String>,
+    /// Unset for the gas token, which has no configured inventory
+    inventory: Option<String>,
+    /// Whether the balance is below the configured inventory
+    short: bool,
+}
+
+impl Inventory {
+    pub(crate) async fn run(&self, config: Config) -> eyre::Result<()> {
+        let account = config.account_address()?;
+        let (_, inventories) = config
+            .build_addrs_and_inventory()
+            .map_err(|e| eyre!("failed to parse chain assets: {e}"))?;
+
+        let mut chains: Vec<_> = inventories.iter().collect();
+        chains.sort_by_key(|(chain, _)| chain.name.to_string());
+        let chains = futures::future::join_all(
+            chains
+                .into_iter()
+                .map(|(chain, inventory)| chain_inventory(chain, account, inventory)),
+        )
+        .await;
+
+        if self.json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&chains).wrap_err("failed to serialize inventory")?
+            );
+            return Ok(());
+        }
+
+        println!("Account {account}");
+        println!(
+            "{:<10}  {:<8}  {:>20}  {:>20}  status",
+            "chain", "token", "balance", "inventory"
+        );
+        for chain in &chains {
+            if let Some(error) = &chain.error {
+                println!("{:<10}  {error}", chain.chain);
+                continue;
+            }
+            for token in std::iter::once(&chain.gas_token).chain(&chain.tokens) {
+                let amount = |units: &Option<String>| {
+                    units
+                        .as_ref()
+                        .and_then(|units| units.parse::<BigUint>().ok())
+                        .map(|units| format_amount(&units, token.decimals))
+                        .unwrap_or_else(|| "-".to_string())
+                };
+                let status = match (&token.inventory, token.short) {
+                    (None, _) => "gas",
+                    (Some(_), true) => "SHORT",
+                    (Some(_), false) => "ok",
+                };
+                println!(
+                    "{:<10}  {:<8}  {:>20}  {:>20}  {status}",
+                    chain.chain,
+                    token.symbol,
+                    amount(&token.balance),
+                    amount(&token.inventory)
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads `account`'s balances on `chain`, recording the error instead of failing so the other
+/// chains are still reported.
+async fn chain_inventory(
+    chain: &Chain,
+    account: Address,
+    inventory: &InventoryForToken,
+) -> ChainInventory {
+    let mut tokens: Vec<_> = inventory.iter().collect();
+    tokens.sort_by(|(a, _), (b, _)| a.symbol.cmp(&b.symbol));
+
+    let balances = match fetch(chain, account, &tokens).await {
+        Ok(balances) => balances,
+        Err(e) => {
+            return ChainInventory {
+                chain: chain.name.to_string(),
+                height: None,
+                gas_token: TokenInventory::gas_token(chain, None),
+                tokens: Vec::new(),
+                error: Some(format!("{e:#}")),
+            };
+        }
+    };
+
+    ChainInventory {
+        chain: chain.name.to_string(),
+        height: Some(balances.height),
+        gas_token: TokenInventory::gas_token(chain, Some(&balances.native)),
+        tokens: tokens
+            .into_iter()
+            .map(|(token, configured)| {
+                let balance = Address::try_from(token.address.as_ref())
+                    .ok()
+                    .and_then(|address| balances.tokens.get(&address));
+                TokenInventory {
+                    symbol: token.symbol.clone(),
+                    address: token.address.to_string(),
+                    decimals: token.decimals,
+                    balance: balance.map(ToString::to_string),
+                    inventory: Some(configured.to_string()),
+                    short: balance.is_some_and(|balance| balance < configured),
+                }
+            })
+            .collect(),
+        error: None,
+    }
+}
+
+async fn fetch(
+    chain: &Chain,
+    account: Address,
+    tokens: &[(&Token, &BigUint)],
+) -> eyre::Result<AccountBalances> {
+    let addresses = tokens
+        .iter()
+        .map(|(token, _)| {
+            Address::try_from(token.address.as_ref())
+                .wrap_err_with(|| format!("invalid address for {}", token.symbol))
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    let rpc = RpcEndpoints::new(chain)?;
+
+    fetch_account_balances(&rpc, account, &addresses).await
+}
+
+impl TokenInventory {
+    fn gas_token(chain: &Chain, balance: Option<&BigUint>) -> Self {
+        let gas_token = &chain.gas_token;
+        Self {
+            symbol: gas_token.symbol.clone(),
+            address: gas_token.address.to_string(),
+            decimals: gas_token.decimals,
+            balance: balance.map(ToString::to_string),
+            inventory: None,
+            short: false,
+        }
+    }
+}
+
+fn format_amount(units: &BigUint, decimals: u32) -> String {
+    let amount = units.to_f64().unwrap_or(f64::INFINITY) / 10f64.powi(decimals as i32);
+    format!("{amount:.6}")
+}
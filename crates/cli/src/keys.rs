@@ -0,0 +1,284 @@
+use core::config::{self, Config};
+use std::{
+    fs,
+    io::{self, BufRead as _},
+    path::{Path, PathBuf},
+};
+
+use alloy::signers::local::PrivateKeySigner;
+use clap::Subcommand;
+use color_eyre::eyre::{self, Context as _, OptionExt as _, eyre};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct KeysCommand {
+    #[command(subcommand)]
+    command: KeysSubcommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum KeysSubcommand {
+    /// Create a new account in an encrypted keystore for a chain
+    Generate(KeystoreArgs),
+
+    /// Encrypt an existing private key, read from stdin, into a keystore for a chain
+    Import(KeystoreArgs),
+
+    /// Print the address of the account signing on a chain
+    ShowAddress {
+        /// Chain the account signs on
+        #[arg(long)]
+        chain: String,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct KeystoreArgs {
+    /// Chain the account signs on, also the keystore's file name
+    #[arg(long)]
+    chain: String,
+
+    /// Directory the keystore is written to
+    #[arg(long, default_value = ".kuma/keys")]
+    dir: PathBuf,
+}
+
+impl KeysCommand {
+    pub(crate) async fn run(&self, config: Config) -> eyre::Result<()> {
+        let chains = config
+            .chain_registry()
+            .wrap_err("failed to parse chains from config")?;
+
+        match &self.command {
+            KeysSubcommand::Generate(args) | KeysSubcommand::Import(args) => {
+                // fail before writing a keystore nothing would be able to decrypt
+                chains.get_by_name(&args.chain)?;
+                let password = config.keystore_password.as_deref().ok_or_eyre(
+                    "set keystore_password, e.g. to ${KUMA_KEYSTORE_PASSWORD}, to encrypt the \
+                     keystore with",
+                )?;
+                let name = format!("{}.json", args.chain);
+                let path = args.dir.join(&name);
+                if path.exists() {
+                    eyre::bail!("{} already exists", path.display());
+                }
+                fs::create_dir_all(&args.dir)
+                    .wrap_err_with(|| format!("failed to create {}", args.dir.display()))?;
+
+                let mut rng = rand::thread_rng();
+                let written = match &self.command {
+                    KeysSubcommand::Import(_) => PrivateKeySigner::encrypt_keystore(
+                        &args.dir,
+                        &mut rng,
+                        read_private_key()?.to_bytes(),
+                        password,
+                        Some(&name),
+                    ),
+                    _ => PrivateKeySigner::new_keystore(&args.dir, &mut rng, password, Some(&name)),
+                };
+                let (signer, _) = written.wrap_err("failed to write keystore")?;
+                println!("Wrote keystore {} for {}", path.display(), signer.address());
+
+                record_keystore(&config::config_path(), &args.chain, &path)?;
+            }
+            KeysSubcommand::ShowAddress { chain } => {
+                let chain = chains.get_by_name(chain)?;
+                println!("{}", config.signer(chain)?.address());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads a private key from the first line of stdin, so it doesn't end up in the shell history.
+fn read_private_key() -> eyre::Result<PrivateKeySigner> {
+    eprintln!("Private key:");
+    let mut line = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .wrap_err("failed to read the private key from stdin")?;
+
+    line.trim()
+        .parse()
+        .map_err(|_| eyre!("failed to parse private key"))
+}
+
+/// Sets `keystore` on `chain`'s entry in the YAML config at `path`, editing the file in place so
+/// its comments and secret references are kept. Other formats, and YAML laid out in ways the edit
+/// can't follow, are left to edit by hand.
+fn record_keystore(path: &Path, chain: &str, keystore: &Path) -> eyre::Result<()> {
+    let keystore = keystore.display().to_string();
+    let hint = |reason: &str| {
+        println!(
+            "Add `keystore: {}` to chain {chain} in {} to sign with the keystore{reason}",
+            yaml_string(&keystore),
+            path.display()
+        );
+    };
+    if !matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("yaml" | "yml")
+    ) {
+        hint("");
+        return Ok(());
+    }
+
+    let yaml = fs::read_to_string(path)
+        .wrap_err_with(|| format!("failed to read config {}", path.display()))?;
+    let yaml = match with_keystore(&yaml, chain, &keystore) {
+        Ok(yaml) => yaml,
+        Err(e) => {
+            hint(&format!(" ({e})"));
+            return Ok(());
+        }
+    };
+    fs::write(path, yaml).wrap_err_with(|| format!("failed to write config {}", path.display()))?;
+    println!("Recorded the keystore for {chain} in {}", path.display());
+
+    Ok(())
+}
+
+/// `yaml` with `keystore` set on `chain`'s entry, replacing any it had.
+///
+/// The line is placed by indentation alone, so the result is parsed back and compared to the
+/// original with only the keystore set, failing rather than returning a config that reads
+/// differently.
+fn with_keystore(yaml: &str, chain: &str, keystore: &str) -> eyre::Result<String> {
+    let mut lines: Vec<&str> = yaml.lines().collect();
+    let indent = |line: &str| line.len() - line.trim_start().len();
+
+    let start = lines
+        .iter()
+        .position(|line| {
+            line.trim_start()
+                .strip_prefix("- name:")
+                .is_some_and(|name| name.trim().trim_matches(['"', '\'']) == chain)
+        })
+        .ok_or_else(|| eyre!("no `- name: {chain}` entry"))?;
+    let key_indent = indent(lines[start]) + 2;
+    // the entry's keys run until the next line indented less, comments and blank lines aside
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| {
+            let trimmed = line.trim_start();
+            !trimmed.is_empty() && !trimmed.starts_with('#') && indent(line) < key_indent
+        })
+        .map_or(lines.len(), |i| start + 1 + i);
+    let entry = format!(
+        "{}keystore: {}",
+        " ".repeat(key_indent),
+        yaml_string(keystore)
+    );
+
+    match (start + 1..end)
+        .find(|&i| indent(lines[i]) == key_indent && lines[i].trim_start().starts_with("keystore:"))
+    {
+        Some(i) => lines[i] = entry.as_str(),
+        None => lines.insert(start + 1, entry.as_str()),
+    }
+    let mut edited = lines.join("\n");
+    edited.push('\n');
+
+    let mut expected: serde_yaml::Value =
+        serde_yaml::from_str(yaml).wrap_err("failed to parse config")?;
+    expected
+        .get_mut("chains")
+        .and_then(serde_yaml::Value::as_sequence_mut)
+        .and_then(|chains| {
+            chains
+                .iter_mut()
+                .find(|entry| entry.get("name").and_then(serde_yaml::Value::as_str) == Some(chain))
+        })
+        .and_then(serde_yaml::Value::as_mapping_mut)
+        .ok_or_else(|| eyre!("no entry for chain {chain} under `chains`"))?
+        .insert("keystore".into(), keystore.into());
+    let parsed: serde_yaml::Value =
+        serde_yaml::from_str(&edited).wrap_err("edited config doesn't parse")?;
+    if parsed != expected {
+        eyre::bail!("couldn't place the keystore line in {chain}'s entry");
+    }
+
+    Ok(edited)
+}
+
+/// `value` as a double-quoted YAML string.
+fn yaml_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"# chains kuma trades on
+chains:
+  - name: ethereum
+    rpc_url: "https://ethereum-rpc.publicnode.com"
+    # optional: rpc endpoints to fail over to
+    rpc_fallbacks: []
+
+  - name: base
+    rpc_url: "${BASE_RPC_URL}"
+    # keystore: ".kuma/keys/old.json"
+
+    permit2_address: "0x000000000022d473030f116ddee9f6b43ac78ba3"
+
+strategies: []
+"#;
+
+    #[test]
+    fn keystore_is_added_to_the_first_chain() {
+        let yaml = with_keystore(CONFIG, "ethereum", ".kuma/keys/ethereum.json").unwrap();
+        assert_eq!(
+            yaml,
+            CONFIG.replace(
+                "  - name: ethereum\n",
+                "  - name: ethereum\n    keystore: \".kuma/keys/ethereum.json\"\n",
+            )
+        );
+    }
+
+    #[test]
+    fn keystore_is_added_to_the_last_chain_around_comments() {
+        let yaml = with_keystore(CONFIG, "base", ".kuma/keys/base.json").unwrap();
+        // the commented out keystore is kept, as are the blank line and comments after it
+        assert_eq!(
+            yaml,
+            CONFIG.replace(
+                "  - name: base\n",
+                "  - name: base\n    keystore: \".kuma/keys/base.json\"\n",
+            )
+        );
+    }
+
+    #[test]
+    fn existing_keystore_is_replaced() {
+        let config = CONFIG.replace(
+            "    # keystore: \".kuma/keys/old.json\"\n",
+            "    keystore: \".kuma/keys/old.json\"\n",
+        );
+        let yaml = with_keystore(&config, "base", ".kuma/keys/base.json").unwrap();
+        assert_eq!(yaml, config.replace("old.json", "base.json"));
+    }
+
+    #[test]
+    fn keystore_path_is_quoted_for_yaml() {
+        let keystore = r#"C:\keys\"base".json"#;
+        let yaml = with_keystore(CONFIG, "base", keystore).unwrap();
+
+        let parsed: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(parsed["chains"][1]["keystore"].as_str(), Some(keystore));
+    }
+
+    #[test]
+    fn unfollowable_layouts_are_rejected() {
+        assert!(with_keystore(CONFIG, "arbitrum", "arbitrum.json").is_err());
+        // flow style entries don't have a `- name:` line to add to
+        let flow = "chains: [{name: base, rpc_url: \"https://mainnet.base.org\"}]\n";
+        assert!(with_keystore(flow, "base", "base.json").is_err());
+        // nor do entries whose name isn't their first key
+        let reordered = "chains:\n  - rpc_url: \"https://mainnet.base.org\"\n    name: base\n";
+        assert!(with_keystore(reordered, "base", "base.json").is_err());
+    }
+}
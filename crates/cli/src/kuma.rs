@@ -1,29 +1,36 @@
-use std::{collections::HashMap, str::FromStr as _};
+use std::collections::HashMap;
 
-use color_eyre::eyre::{self, Context as _};
+use color_eyre::eyre::{self, Context as _, OptionExt as _, eyre};
 use futures::StreamExt as _;
+use tokio::select;
 use tokio_util::sync::CancellationToken;
-use tracing::{info, instrument};
+use tracing::{debug, info, instrument};
 use tycho_common::models::token::Token;
 
 use core::{
-    chain::Chain, collector, config::Config, signals, state::pair::Pair,
+    chain::{Chain, ChainRegistry},
+    collector,
+    config::Config,
+    signals,
+    state::{
+        PoolFilter,
+        pair::{Pair, PairState, PairStateStream},
+    },
     strategy::CrossChainSingleHop,
 };
 
-use crate::cli::StrategyArgs;
+use crate::cli::{PairArg, StrategyArgs};
 
 pub(crate) struct Kuma {
     #[allow(unused)]
     all_tokens: HashMap<Chain, HashMap<tycho_common::Bytes, Token>>,
-    slow_pair: Pair,
     slow_chain: Chain,
-    fast_pair: Pair,
     fast_chain: Chain,
 
     slow_collector_handle: collector::Handle,
     fast_collector_handle: collector::Handle,
-    strategy: CrossChainSingleHop,
+    /// One per pair, in the order they were given
+    strategies: Vec<CrossChainSingleHop>,
 }
 
 impl Kuma {
@@ -31,6 +38,27 @@ impl Kuma {
         cfg: Config,
         strategy_config: StrategyArgs,
         shutdown_token: CancellationToken,
+    ) -> eyre::Result<Self> {
+        let pair = PairArg {
+            token_a: strategy_config.token_a,
+            token_b: strategy_config.token_b,
+        };
+        Self::spawn_pairs(
+            cfg,
+            &strategy_config.slow_chain,
+            &strategy_config.fast_chain,
+            &[pair],
+            shutdown_token,
+        )
+    }
+
+    /// Spawns one collector per chain, shared by a strategy for each of `pairs`.
+    pub fn spawn_pairs(
+        cfg: Config,
+        slow_chain: &str,
+        fast_chain: &str,
+        pairs: &[PairArg],
+        shutdown_token: CancellationToken,
     ) -> eyre::Result<Self> {
         let (tokens_by_chain, inventory) = cfg
             .build_addrs_and_inventory()
@@ -44,43 +72,76 @@ impl Kuma {
                         "🔗 Initialized chain info from config");
         }
 
-        let pairs = Config::get_chain_pairs(
-            &strategy_config.token_a,
-            &strategy_config.token_b,
-            &inventory,
+        let chains = cfg
+            .chain_registry()
+            .wrap_err("failed to parse chains from config")?;
+        let (slow_chain, fast_chain) = get_chains_from_names(slow_chain, fast_chain, &chains)?;
+        let (slow_pool_filter, fast_pool_filter) =
+            (cfg.pool_filter(&slow_chain), cfg.pool_filter(&fast_chain));
+        let (slow_protocols, fast_protocols) =
+            (cfg.protocols(&slow_chain), cfg.protocols(&fast_chain));
+        let (slow_endpoints, fast_endpoints) = (
+            cfg.tycho_endpoints(&slow_chain),
+            cfg.tycho_endpoints(&fast_chain),
         );
 
         let Config {
-            tycho_api_key,
             add_tvl_threshold,
             remove_tvl_threshold,
             max_slippage_bps,
             congestion_risk_discount_bps,
             binary_search_steps,
+            max_tycho_stream_failures,
             ..
         } = cfg;
 
-        let (slow_chain, fast_chain) = get_chains_from_names(
-            strategy_config.slow_chain,
-            strategy_config.fast_chain,
-            &tokens_by_chain,
-        );
-        let slow_pair = pairs.get(&slow_chain).expect(&format!(
-            "could not find pair info for {:}",
-            slow_chain.name
-        ));
-        let fast_pair = pairs.get(&fast_chain).expect(&format!(
-            "could not find pair info for {:}",
-            fast_chain.name
-        ));
-
-        // set up tycho stream collectors
+        // initialize a single hop strategy per pair
+        let strategies = pairs
+            .iter()
+            .map(|pair| {
+                let pairs = Config::get_chain_pairs(&pair.token_a, &pair.token_b, &inventory);
+                let slow_pair = pairs
+                    .get(&slow_chain)
+                    .ok_or_else(|| eyre!("could not find pair {pair} on {}", slow_chain.name))?;
+                let fast_pair = pairs
+                    .get(&fast_chain)
+                    .ok_or_else(|| eyre!("could not find pair {pair} on {}", fast_chain.name))?;
+
+                let slow_inventory = (
+                    inventory[&slow_chain][slow_pair.token_a()].clone(),
+                    inventory[&slow_chain][slow_pair.token_b()].clone(),
+                );
+                let fast_inventory = (
+                    inventory[&fast_chain][fast_pair.token_a()].clone(),
+                    inventory[&fast_chain][fast_pair.token_b()].clone(),
+                );
+
+                Ok(CrossChainSingleHop {
+                    slow_pair: slow_pair.clone(),
+                    slow_chain: slow_chain.clone(),
+                    fast_pair: fast_pair.clone(),
+                    fast_chain: fast_chain.clone(),
+                    slow_inventory,
+                    fast_inventory,
+                    binary_search_steps,
+                    max_slippage_bps,
+                    congestion_risk_discount_bps,
+                    slow_spot_prices: Default::default(),
+                    fast_spot_prices: Default::default(),
+                })
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        // set up tycho stream collectors, tracking every configured token so all pairs share them
         let slow_collector_handle = make_collector(
             slow_chain.clone(),
             tokens_by_chain[&slow_chain].clone(),
-            &tycho_api_key,
+            slow_endpoints,
+            max_tycho_stream_failures,
             add_tvl_threshold,
             remove_tvl_threshold,
+            slow_pool_filter,
+            slow_protocols,
             shutdown_token.clone(),
         )
         .wrap_err("failed to start chain a collector")?;
@@ -88,106 +149,167 @@ impl Kuma {
         let fast_collector_handle = make_collector(
             fast_chain.clone(),
             tokens_by_chain[&fast_chain].clone(),
-            &tycho_api_key,
+            fast_endpoints,
+            max_tycho_stream_failures,
             add_tvl_threshold,
             remove_tvl_threshold,
+            fast_pool_filter,
+            fast_protocols,
             shutdown_token.clone(),
         )
         .wrap_err("failed to start chain a collector")?;
 
-        // initialize single hop strategy
-        let slow_inventory = (
-            inventory[&slow_chain][slow_pair.token_a()].clone(),
-            inventory[&slow_chain][slow_pair.token_b()].clone(),
-        );
-        let fast_inventory = (
-            inventory[&fast_chain][fast_pair.token_a()].clone(),
-            inventory[&fast_chain][fast_pair.token_b()].clone(),
-        );
-
-        let strategy = CrossChainSingleHop {
-            slow_pair: slow_pair.clone(),
-            slow_chain: slow_chain.clone(),
-            fast_pair: fast_pair.clone(),
-            fast_chain: fast_chain.clone(),
-            slow_inventory,
-            fast_inventory,
-            binary_search_steps,
-            max_slippage_bps,
-            congestion_risk_discount_bps,
-        };
-
         Ok(Self {
             all_tokens: tokens_by_chain,
             slow_chain,
-            slow_pair: slow_pair.clone(),
             fast_chain,
-            fast_pair: fast_pair.clone(),
             slow_collector_handle,
             fast_collector_handle,
-            strategy,
+            strategies,
         })
     }
 
-    #[instrument(skip(self))]
+    /// The signal of the first blocks of the single pair the instance was spawned for.
     pub async fn generate_signal(self) -> eyre::Result<signals::CrossChainSingleHop> {
+        let (_, signal) = self
+            .generate_signals(true)
+            .await
+            .pop()
+            .expect("a strategy is spawned for every pair");
+        signal
+    }
+
+    /// A signal for every pair, from the first blocks the shared collectors receive if `once`,
+    /// or the first blocks with a signal otherwise.
+    #[instrument(skip(self), fields(pairs = self.strategies.len()))]
+    pub async fn generate_signals(
+        self,
+        once: bool,
+    ) -> Vec<(Pair, eyre::Result<signals::CrossChainSingleHop>)> {
         let Self {
             slow_chain,
-            slow_pair,
             fast_chain,
-            fast_pair,
             slow_collector_handle,
             fast_collector_handle,
-            strategy,
+            strategies,
             ..
         } = self;
 
-        info!(command = "generating signal");
+        info!(command = "generating signals");
 
-        let mut slow_chain_states = slow_collector_handle.get_pair_state_stream(&slow_pair);
-        let mut fast_chain_states = fast_collector_handle.get_pair_state_stream(&fast_pair);
-        // read state from stream
-        let slow_state = slow_chain_states
-            .next()
-            .await
-            .expect("chain a stream should yield initial block");
-        let fast_state = fast_chain_states
-            .next()
-            .await
-            .expect("chain b stream should yield initial block");
+        let signals = strategies.into_iter().map(|strategy| {
+            let slow_states = slow_collector_handle.get_pair_state_stream(&strategy.slow_pair);
+            let fast_states = fast_collector_handle.get_pair_state_stream(&strategy.fast_pair);
+            let (slow_chain, fast_chain) = (&slow_chain, &fast_chain);
+            async move {
+                let pair = strategy.slow_pair.clone();
+                let signal = first_signal(
+                    &strategy,
+                    slow_chain,
+                    fast_chain,
+                    slow_states,
+                    fast_states,
+                    once,
+                )
+                .await;
+                (pair, signal)
+            }
+        });
+
+        futures::future::join_all(signals).await
+    }
+}
 
-        info!(block = %slow_state.block_height, chain = %slow_chain.name, "reaped initial block");
-        info!(block = %fast_state.block_height, chain = %fast_chain.name, "reaped initial block");
+/// The signal of the first slow and fast blocks, or unless `once` of the first blocks found to
+/// have one, precomputing again on every new slow block.
+#[instrument(skip_all, fields(pair = %strategy.slow_pair))]
+async fn first_signal(
+    strategy: &CrossChainSingleHop,
+    slow_chain: &Chain,
+    fast_chain: &Chain,
+    mut slow_chain_states: PairStateStream,
+    mut fast_chain_states: PairStateStream,
+    once: bool,
+) -> eyre::Result<signals::CrossChainSingleHop> {
+    // read state from stream
+    let slow_state = slow_chain_states
+        .next()
+        .await
+        .expect("chain a stream should yield initial block");
+    let mut fast_state = fast_chain_states
+        .next()
+        .await
+        .expect("chain b stream should yield initial block");
 
-        // precompute data for signal
-        let precompute = strategy.precompute(slow_state);
+    info!(block = %slow_state.block_height, chain = %slow_chain.name, "reaped initial block");
+    info!(block = %fast_state.block_height, chain = %fast_chain.name, "reaped initial block");
 
-        info!(block_height = %precompute.block_height, chain = %slow_chain.name, "✅ precomputed data");
+    // precompute data for signal
+    let mut precompute = strategy.precompute(&slow_state);
 
-        // compute arb signal
-        let signal = strategy.generate_signal(&precompute, fast_state)?;
+    info!(block_height = %precompute.block_height, chain = %slow_chain.name, "✅ precomputed data");
 
-        info!(signal = ?signal, "📊 generated signal");
+    loop {
+        // compute arb signal
+        let fast_height = fast_state.block_height;
+        match strategy.generate_signal(&precompute, &fast_state) {
+            Ok(signal) => {
+                info!(signal = ?signal, "📊 generated signal");
+                return Ok(signal);
+            }
+            Err(e) if once => {
+                return Err(e.wrap_err(format!(
+                    "no signal between {slow_chain} block {} and {fast_chain} block {fast_height}",
+                    precompute.block_height
+                )));
+            }
+            Err(e) => debug!(
+                slow.height = precompute.block_height,
+                fast.height = fast_height,
+                error = %e,
+                "No signal, waiting for the next block"
+            ),
+        }
 
-        Ok(signal)
+        select! {
+            slow_state = slow_chain_states.next() => {
+                let slow_state = slow_state.ok_or_eyre("slow chain stream ended")?;
+                precompute = strategy.precompute(&slow_state);
+                info!(block_height = %precompute.block_height, chain = %slow_chain.name, "✅ precomputed data");
+            }
+            state = fast_chain_states.next() => {
+                fast_state = state.ok_or_eyre("fast chain stream ended")?;
+            }
+        }
     }
 }
 
 pub(crate) fn make_collector(
     chain: Chain,
     tokens: HashMap<tycho_common::Bytes, Token>,
-    tycho_api_key: &str,
+    endpoints: Vec<collector::TychoEndpoint>,
+    max_stream_failures: u32,
     add_tvl_threshold: f64,
     remove_tvl_threshold: f64,
+    pool_filter: PoolFilter,
+    protocols: Vec<String>,
     shutdown_token: CancellationToken,
 ) -> eyre::Result<collector::Handle> {
     let handle = collector::Builder {
-        tycho_url: chain.tycho_url.clone(),
-        api_key: tycho_api_key.to_string(),
+        endpoints,
+        max_stream_failures,
         add_tvl_threshold,
         remove_tvl_threshold,
         tokens,
         chain,
+        account: None,
+        pool_filter,
+        pair_state_delivery: Default::default(),
+        protocols,
+        snapshot: None,
+        record: None,
+        blocks: None,
+        gas_prices: None,
         shutdown_token,
     }
     .build();
@@ -195,29 +317,64 @@ pub(crate) fn make_collector(
     handle.wrap_err("failed to start tycho collector for chain : {chain}")
 }
 
+/// State of `pair` on `chain` as of the first block a short-lived collector receives, leaving out
+/// pools rejected by `pool_filter`.
+pub(crate) async fn snapshot_pair_state(
+    cfg: &Config,
+    chain: &Chain,
+    tokens: HashMap<tycho_common::Bytes, Token>,
+    pair: &Pair,
+    pool_filter: PoolFilter,
+    shutdown_token: &CancellationToken,
+) -> eyre::Result<PairState> {
+    let mut collector = make_collector(
+        chain.clone(),
+        tokens,
+        cfg.tycho_endpoints(chain),
+        cfg.max_tycho_stream_failures,
+        cfg.add_tvl_threshold,
+        cfg.remove_tvl_threshold,
+        pool_filter,
+        cfg.protocols(chain),
+        shutdown_token.child_token(),
+    )?;
+
+    info!(chain.name = %chain.name, %pair, "Waiting for the first block");
+    let mut states = collector.get_pair_state_stream(pair);
+    let state = select! {
+        () = shutdown_token.cancelled() => None,
+        state = states.next() => state,
+    };
+    collector.shutdown().await?;
+
+    state.ok_or_eyre("collector stopped before receiving a block")
+}
+
+/// The token configured on a chain under `symbol`, case insensitively.
+pub(crate) fn find_token(
+    tokens: &HashMap<tycho_common::Bytes, Token>,
+    symbol: &str,
+) -> eyre::Result<Token> {
+    tokens
+        .values()
+        .find(|token| token.symbol.eq_ignore_ascii_case(symbol))
+        .cloned()
+        .ok_or_else(|| eyre::eyre!("token {symbol} is not configured on this chain"))
+}
+
 pub(crate) fn get_chains_from_names(
-    slow_chain: String,
-    fast_chain: String,
-    chain_tokens: &HashMap<Chain, HashMap<tycho_common::Bytes, Token>>,
-) -> (Chain, Chain) {
-    let slow_chain = chain_tokens
-        .keys()
-        .find(|chain| {
-            chain.name
-                == tycho_common::models::Chain::from_str(&slow_chain)
-                    .expect("Invalid slow chain name")
-        })
-        .expect("Chain A not configured")
+    slow_chain: &str,
+    fast_chain: &str,
+    chains: &ChainRegistry,
+) -> eyre::Result<(Chain, Chain)> {
+    let slow_chain = chains
+        .get_by_name(slow_chain)
+        .wrap_err("invalid slow chain")?
         .clone();
-    let fast_chain = chain_tokens
-        .keys()
-        .find(|chain| {
-            chain.name
-                == tycho_common::models::Chain::from_str(&fast_chain)
-                    .expect("Invalid fast chain name")
-        })
-        .expect("Chain B not configured")
+    let fast_chain = chains
+        .get_by_name(fast_chain)
+        .wrap_err("invalid fast chain")?
         .clone();
 
-    (slow_chain, fast_chain)
+    Ok((slow_chain, fast_chain))
 }
@@ -8,19 +8,51 @@ use tokio::{
 };
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info};
-use tracing_subscriber::{self, EnvFilter};
 
 // use crate::kuma::Kuma;
 
-use core::config::Config;
+use core::{
+    config::Config,
+    telemetry::{self, TelemetrySettings},
+};
 
+mod admin;
+mod backtest;
+mod bench;
 mod cli;
+mod config;
+mod db;
+mod dry_run;
+mod execute;
+mod export;
+mod export_curves;
+mod generate_signals;
+mod init;
+mod inventory;
+mod keys;
 mod kuma;
 mod permit;
+mod pools;
+mod quote;
+mod replay;
+mod score;
+mod status;
+mod sweep;
 mod tokens;
 
 #[tokio::main]
 async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if let Some(result) = cli.run_without_config().await {
+        return match result {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{e:?}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
     // Load configuration
     let config = match Config::load() {
         Ok(config) => config,
@@ -29,29 +61,24 @@ async fn main() -> ExitCode {
             return ExitCode::FAILURE;
         }
     };
+    if let Err(errors) = config.validate() {
+        eprintln!("{errors}");
+        return ExitCode::FAILURE;
+    }
 
-    eprintln!("starting with config:\n{config:?}");
-
-    // TODO: move to core
     // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::from_default_env()
-                .add_directive(
-                    "tycho_client=warn"
-                        .parse()
-                        .expect("well-formed tracing directive should parse"),
-                )
-                .add_directive(
-                    "tycho_simulation=warn"
-                        .parse()
-                        .expect("well-formed tracing directive should parse"),
-                ),
-        )
-        .with_target(false)
-        .init();
+    let telemetry_settings = TelemetrySettings {
+        stderr: true,
+        ..TelemetrySettings::from_config("kuma-cli", &config)
+    };
+    let _otlp = match telemetry::init_logging(&telemetry_settings) {
+        Ok(otlp) => otlp,
+        Err(e) => {
+            eprintln!("{e:?}");
+            return ExitCode::FAILURE;
+        }
+    };
 
-    let cli = Cli::parse();
     let shutdown_token = CancellationToken::new();
 
     let command_jh = tokio::spawn(cli.run(config, shutdown_token));
@@ -68,7 +95,7 @@ async fn main() -> ExitCode {
             // TODO: make sure this is correct
             res.and_then(|commands_result| {
                 match commands_result {
-                    Ok(_) => Ok(ExitCode::SUCCESS),
+                    Ok(code) => Ok(code),
                     Err(e) => {
                         error!(error=%e, "command failed");
                         Ok(ExitCode::FAILURE)
@@ -87,9 +114,9 @@ async fn main() -> ExitCode {
     };
 
     match result {
-        Ok(_) => {
+        Ok(code) => {
             info!("command completed");
-            ExitCode::SUCCESS
+            code
         }
         Err(e) => {
             error!(%e, "command exited unexpectedly");
@@ -3,7 +3,6 @@ use alloy::{
     primitives::{Keccak256, U256},
     providers::{Provider as _, ProviderBuilder},
     rpc::types::TransactionRequest,
-    signers::local::PrivateKeySigner,
     sol_types::SolValue as _,
 };
 
@@ -28,21 +27,16 @@ impl Permit2 {
                         "🔗 Initialized chain info from config");
         }
 
-        let signer: PrivateKeySigner = config
-            .private_key
-            .parse()
-            .wrap_err("Failed to parse private key")?;
-
-        let wallet = EthereumWallet::new(signer.clone());
         let approve_function_signature = "approve(address,uint256)";
         for (chain, tokens) in tokens_by_chain.iter() {
+            let wallet = EthereumWallet::new(config.signer(chain)?);
             let args = (
                 chain.permit2_address,
                 U256::MAX, // Approve maximum amount
             );
             let call_data = encode_input(approve_function_signature, args.abi_encode());
             let provider = ProviderBuilder::new()
-                .wallet(wallet.clone())
+                .wallet(wallet)
                 .connect_http(chain.rpc_url.parse().wrap_err("Failed to parse RPC URL")?);
 
             for (address_bytes, token) in tokens.iter() {
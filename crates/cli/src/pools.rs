@@ -0,0 +1,150 @@
+use core::{
+    chain::Chain,
+    config::Config,
+    state::{PoolFilter, PoolId, pair::Pair},
+};
+use std::collections::HashMap;
+
+use color_eyre::eyre::{self, Context as _, eyre};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+use tycho_common::dto::{ComponentTvlRequestBody, PaginationParams};
+use tycho_simulation::tycho_client::{HttpRPCClient, rpc::RPCClient as _};
+
+use crate::kuma::{find_token, snapshot_pair_state};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Pools {
+    /// Chain to list the pools of
+    #[arg(long)]
+    pub chain: String,
+
+    /// Token symbols of the pair, e.g. `WETH-USDC`
+    #[arg(long)]
+    pub pair: String,
+}
+
+impl Pools {
+    pub(crate) async fn run(
+        &self,
+        config: Config,
+        shutdown_token: CancellationToken,
+    ) -> eyre::Result<()> {
+        let (symbol_a, symbol_b) = self
+            .pair
+            .split_once('-')
+            .ok_or_else(|| eyre!("pair must be two token symbols like WETH-USDC"))?;
+        let chains = config
+            .chain_registry()
+            .wrap_err("failed to parse chains from config")?;
+        let chain = chains.get_by_name(&self.chain)?.clone();
+        let (mut tokens_by_chain, _) = config
+            .build_addrs_and_inventory()
+            .map_err(|e| eyre!("failed to parse chain assets: {e}"))?;
+        let tokens = tokens_by_chain
+            .remove(&chain)
+            .ok_or_else(|| eyre!("no tokens configured on {}", chain.name))?;
+        let pair = Pair::new(
+            find_token(&tokens, symbol_a)?,
+            find_token(&tokens, symbol_b)?,
+        );
+
+        // every tracked pool, marking the ones the configured lists filter out
+        let state = snapshot_pair_state(
+            &config,
+            &chain,
+            tokens,
+            &pair,
+            PoolFilter::default(),
+            &shutdown_token,
+        )
+        .await?;
+        let pool_filter = config.pool_filter(&chain);
+
+        let mut ids: Vec<&PoolId> = state.states.keys().collect();
+        ids.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        let tvls = match fetch_tvls(&config, &chain, &ids).await {
+            Ok(tvls) => tvls,
+            Err(e) => {
+                warn!(err = %e, "Failed to fetch pool TVLs");
+                HashMap::new()
+            }
+        };
+
+        println!(
+            "{} pools of {pair} on {} at block {}",
+            ids.len(),
+            chain.name,
+            state.block_height
+        );
+        println!(
+            "{:<66}  {:<16}  {:>8}  {:>16}  {:>12}  {:<12}  filter",
+            "pool", "protocol", "fee", "spot_price", "tvl", "tvl_bucket"
+        );
+        for id in ids {
+            let pool = &state.states[id];
+            let protocol = state
+                .pool_meta
+                .get(id)
+                .map(|meta| meta.protocol_system.as_str())
+                .unwrap_or_default();
+            let spot_price = pool
+                .spot_price(pair.token_a(), pair.token_b())
+                .map(|price| format!("{price:.6}"))
+                .unwrap_or_else(|_| "-".to_string());
+            let tvl = tvls.get(id.as_ref()).copied();
+            let bucket = match tvl {
+                None => "unknown",
+                Some(tvl) if tvl < config.remove_tvl_threshold => "below_remove",
+                Some(tvl) if tvl < config.add_tvl_threshold => "between",
+                Some(_) => "above_add",
+            };
+            let filter = if pool_filter.allows(id) {
+                "allowed"
+            } else {
+                "filtered"
+            };
+            println!(
+                "{:<66}  {protocol:<16}  {:>8.4}  {spot_price:>16}  {:>12}  {bucket:<12}  {filter}",
+                id.as_ref(),
+                pool.fee(),
+                tvl.map(|tvl| format!("{tvl:.2}"))
+                    .unwrap_or_else(|| "-".to_string()),
+            );
+        }
+        println!(
+            "TVL is in the chain's native token; pools are added above {} and removed below {}",
+            config.add_tvl_threshold, config.remove_tvl_threshold
+        );
+
+        Ok(())
+    }
+}
+
+/// TVL of each of `ids` as indexed by Tycho, keyed by pool id.
+async fn fetch_tvls(
+    config: &Config,
+    chain: &Chain,
+    ids: &[&PoolId],
+) -> eyre::Result<HashMap<String, f64>> {
+    let client = HttpRPCClient::new(
+        &format!("https://{}", chain.tycho_url),
+        Some(config.tycho_api_key.as_str()),
+    )
+    .map_err(|e| eyre!("failed to create tycho rpc client: {e}"))?;
+    let request = ComponentTvlRequestBody {
+        chain: chain.name.into(),
+        protocol_system: None,
+        component_ids: Some(ids.iter().map(ToString::to_string).collect()),
+        pagination: PaginationParams {
+            page: 0,
+            page_size: ids.len() as i64,
+        },
+    };
+    let response = client
+        .get_component_tvl(&request)
+        .await
+        .map_err(|e| eyre!("component_tvl request failed: {e}"))?;
+
+    Ok(response.tvl)
+}
@@ -0,0 +1,148 @@
+use core::{
+    config::{Config, TokenAmount},
+    state::pair::Pair,
+    strategy::Swap,
+};
+
+use color_eyre::eyre::{self, Context as _, eyre};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive as _;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use crate::kuma::{find_token, snapshot_pair_state};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Quote {
+    /// Chain to simulate the swap on
+    #[arg(long)]
+    pub chain: String,
+
+    /// Symbol of the token sold
+    #[arg(long)]
+    pub token_in: String,
+
+    /// Symbol of the token bought
+    #[arg(long)]
+    pub token_out: String,
+
+    /// Amount sold, in whole tokens, e.g. `1.5`
+    #[arg(long)]
+    pub amount: String,
+}
+
+/// A pool's simulated output for the quoted amount.
+struct PoolQuote {
+    pool: String,
+    protocol: String,
+    amount_out: BigUint,
+    /// Execution price below the pool's spot price, in bps. `None` if the pool has no spot price.
+    price_impact_bps: Option<f64>,
+}
+
+impl Quote {
+    pub(crate) async fn run(
+        &self,
+        config: Config,
+        shutdown_token: CancellationToken,
+    ) -> eyre::Result<()> {
+        let chains = config
+            .chain_registry()
+            .wrap_err("failed to parse chains from config")?;
+        let chain = chains.get_by_name(&self.chain)?.clone();
+        let (mut tokens_by_chain, _) = config
+            .build_addrs_and_inventory()
+            .map_err(|e| eyre!("failed to parse chain assets: {e}"))?;
+        let tokens = tokens_by_chain
+            .remove(&chain)
+            .ok_or_else(|| eyre!("no tokens configured on {}", chain.name))?;
+        let token_in = find_token(&tokens, &self.token_in)?;
+        let token_out = find_token(&tokens, &self.token_out)?;
+        let amount_in = TokenAmount::Decimal(self.amount.clone())
+            .to_units(token_in.decimals)
+            .map_err(|e| eyre!("invalid amount: {e}"))?;
+
+        let pair = Pair::new(token_in.clone(), token_out.clone());
+        let pool_filter = config.pool_filter(&chain);
+        let state =
+            snapshot_pair_state(&config, &chain, tokens, &pair, pool_filter, &shutdown_token)
+                .await?;
+
+        let amount = |units: &BigUint, decimals: u32| {
+            units.to_f64().unwrap_or(f64::INFINITY) / 10f64.powi(decimals as i32)
+        };
+        let mut quotes: Vec<PoolQuote> = state
+            .states
+            .iter()
+            .filter_map(|(id, pool)| {
+                let swap =
+                    match Swap::from_protocol_sim(&amount_in, &token_in, &token_out, pool.as_ref())
+                    {
+                        Ok(swap) => swap,
+                        Err(e) => {
+                            debug!(pool = %id, err = %e, "Skipping pool that failed to simulate");
+                            return None;
+                        }
+                    };
+                let execution_price = amount(&swap.amount_out, token_out.decimals)
+                    / amount(&amount_in, token_in.decimals);
+                let price_impact_bps = pool
+                    .spot_price(&token_in, &token_out)
+                    .ok()
+                    .filter(|spot| *spot > 0.0)
+                    .map(|spot| (1.0 - execution_price / spot) * 10_000.0);
+
+                Some(PoolQuote {
+                    pool: id.to_string(),
+                    protocol: state
+                        .metadata
+                        .get(id)
+                        .map(|component| component.protocol_system.clone())
+                        .unwrap_or_default(),
+                    amount_out: swap.amount_out,
+                    price_impact_bps,
+                })
+            })
+            .collect();
+        quotes.sort_by(|a, b| b.amount_out.cmp(&a.amount_out));
+
+        println!(
+            "{} {} to {} on {} at block {}, {} pools",
+            self.amount,
+            token_in.symbol,
+            token_out.symbol,
+            chain.name,
+            state.block_height,
+            quotes.len()
+        );
+        println!(
+            "{:<66}  {:<16}  {:>20}  {:>12}",
+            "pool", "protocol", "amount_out", "impact_bps"
+        );
+        for quote in &quotes {
+            let impact = quote
+                .price_impact_bps
+                .map(|bps| format!("{bps:.2}"))
+                .unwrap_or_else(|| "-".to_string());
+            println!(
+                "{:<66}  {:<16}  {:>20}  {impact:>12}",
+                quote.pool,
+                quote.protocol,
+                amount(&quote.amount_out, token_out.decimals)
+            );
+        }
+
+        match quotes.first() {
+            Some(best) => println!(
+                "Best route: {} {} through {} ({})",
+                amount(&best.amount_out, token_out.decimals),
+                token_out.symbol,
+                best.pool,
+                best.protocol
+            ),
+            None => println!("No pool could quote the swap"),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+use core::{
+    chain::Chain,
+    collector::{self, recording},
+    config::{Config, InventoriesForChain, StrategyConfig, StrategyParams, TokenAddressesForChain},
+    state::{
+        PoolFilter,
+        block::Block,
+        pair::{Pair, PairState},
+    },
+    strategy::CrossChainSingleHop,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use color_eyre::eyre::{self, Context as _, OptionExt as _, eyre};
+use tokio::select;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+use tycho_common::{Bytes, models::token::Token};
+use tycho_simulation::protocol::models::Update;
+
+use crate::kuma::get_chains_from_names;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Replay {
+    /// Directory the collectors recorded to, i.e. kumad's `record_dir`
+    #[arg(long)]
+    pub input: PathBuf,
+
+    /// Id of the configured strategy to replay, e.g. `weth-usdc-ethereum-base`
+    #[arg(long)]
+    pub strategy: String,
+
+    /// Replay speed relative to how the messages were received. `0` replays as fast as possible
+    #[arg(long, default_value_t = 1.0)]
+    pub speed: f64,
+}
+
+/// Indices of the slow and fast chain's recordings
+pub(crate) const SLOW: usize = 0;
+pub(crate) const FAST: usize = 1;
+
+impl Replay {
+    pub(crate) async fn run(
+        &self,
+        config: Config,
+        shutdown_token: CancellationToken,
+    ) -> eyre::Result<()> {
+        if !(self.speed >= 0.0 && self.speed.is_finite()) {
+            eyre::bail!("speed must be a non-negative number, got {}", self.speed);
+        }
+        let strategy_config = config
+            .strategies
+            .iter()
+            .find(|strategy| strategy.id() == self.strategy.to_lowercase())
+            .ok_or_else(|| eyre!("no strategy {} in the config", self.strategy))?;
+        let mut recordings = Recordings::open(&config, &self.input, strategy_config).await?;
+        let strategy = recordings
+            .setup
+            .strategy(config.strategy_params(strategy_config));
+
+        let mut precompute = None;
+        let (mut slow_blocks, mut fast_blocks, mut signals) = (0, 0, 0);
+        let mut last_received_at = None;
+
+        while let Some((idx, received_at, state)) = recordings.next().await? {
+            let last = last_received_at.replace(received_at);
+            if let Some(last) = last.filter(|_| self.speed > 0.0) {
+                let wait =
+                    Duration::from_millis(received_at.saturating_sub(last)).div_f64(self.speed);
+                select! {
+                    () = shutdown_token.cancelled() => break,
+                    () = tokio::time::sleep(wait) => {}
+                }
+            } else if shutdown_token.is_cancelled() {
+                break;
+            }
+
+            match idx {
+                SLOW => {
+                    slow_blocks += 1;
+                    let slow_precompute = strategy.precompute(&state);
+                    debug!(
+                        block.height = slow_precompute.block_height,
+                        "Precomputed slow block"
+                    );
+                    precompute = Some(slow_precompute);
+                }
+                _ => {
+                    fast_blocks += 1;
+                    if let Some(precompute) = &precompute {
+                        let height = state.block_height;
+                        match strategy.generate_signal(precompute, &state) {
+                            Ok(signal) => {
+                                signals += 1;
+                                info!(%signal, "📊 Replayed signal");
+                            }
+                            Err(e) => {
+                                debug!(block.height = height, err = %e, "No signal")
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        println!(
+            "Replayed {slow_blocks} {} and {fast_blocks} {} blocks: {signals} signals",
+            recordings.setup.chains[SLOW].name, recordings.setup.chains[FAST].name
+        );
+
+        Ok(())
+    }
+}
+
+/// A configured strategy's chains, pairs and inventory, as resolved from the config.
+pub(crate) struct Setup {
+    /// The slow and fast chain
+    pub(crate) chains: [Chain; 2],
+    /// The strategy's pair on the slow and fast chain
+    pub(crate) pairs: [Pair; 2],
+    pub(crate) pool_filters: [PoolFilter; 2],
+    tokens_by_chain: TokenAddressesForChain,
+    inventory: InventoriesForChain,
+}
+
+impl Setup {
+    pub(crate) fn new(config: &Config, strategy_config: &StrategyConfig) -> eyre::Result<Self> {
+        let (tokens_by_chain, inventory) = config
+            .build_addrs_and_inventory()
+            .map_err(|e| eyre!("failed to parse chain assets: {e}"))?;
+        let chains = config
+            .chain_registry()
+            .wrap_err("failed to parse chains from config")?;
+        let (slow_chain, fast_chain) = get_chains_from_names(
+            &strategy_config.slow_chain,
+            &strategy_config.fast_chain,
+            &chains,
+        )?;
+        let pairs = Config::get_chain_pairs(
+            &strategy_config.token_a,
+            &strategy_config.token_b,
+            &inventory,
+        );
+        let slow_pair = pairs
+            .get(&slow_chain)
+            .ok_or_eyre("strategy's pair isn't configured on the slow chain")?;
+        let fast_pair = pairs
+            .get(&fast_chain)
+            .ok_or_eyre("strategy's pair isn't configured on the fast chain")?;
+
+        Ok(Self {
+            pairs: [slow_pair.clone(), fast_pair.clone()],
+            pool_filters: [
+                config.pool_filter(&slow_chain),
+                config.pool_filter(&fast_chain),
+            ],
+            chains: [slow_chain, fast_chain],
+            tokens_by_chain,
+            inventory,
+        })
+    }
+
+    /// The strategy over the setup's chains, trading with `params`
+    pub(crate) fn strategy(&self, params: StrategyParams) -> CrossChainSingleHop {
+        let [slow_chain, fast_chain] = &self.chains;
+        let [slow_pair, fast_pair] = &self.pairs;
+        CrossChainSingleHop {
+            slow_pair: slow_pair.clone(),
+            slow_chain: slow_chain.clone(),
+            fast_pair: fast_pair.clone(),
+            fast_chain: fast_chain.clone(),
+            slow_inventory: (
+                self.inventory[slow_chain][slow_pair.token_a()].clone(),
+                self.inventory[slow_chain][slow_pair.token_b()].clone(),
+            ),
+            fast_inventory: (
+                self.inventory[fast_chain][fast_pair.token_a()].clone(),
+                self.inventory[fast_chain][fast_pair.token_b()].clone(),
+            ),
+            binary_search_steps: params.binary_search_steps,
+            max_slippage_bps: params.max_slippage_bps,
+            congestion_risk_discount_bps: params.congestion_risk_discount_bps,
+            slow_spot_prices: Default::default(),
+            fast_spot_prices: Default::default(),
+        }
+    }
+
+    /// Tokens configured on the slow or fast chain, which its collector tracks
+    pub(crate) fn tokens(&self, idx: usize) -> eyre::Result<HashMap<Bytes, Token>> {
+        let chain = &self.chains[idx];
+        self.tokens_by_chain
+            .get(chain)
+            .cloned()
+            .ok_or_else(|| eyre!("no tokens configured on {}", chain.name))
+    }
+}
+
+/// The recordings of a strategy's slow and fast chain, played back in the order they were
+/// received.
+pub(crate) struct Recordings {
+    pub(crate) setup: Setup,
+    replays: [recording::Replay; 2],
+    pending: [Option<(u64, Update)>; 2],
+    blocks: [Option<Block>; 2],
+}
+
+impl Recordings {
+    /// Opens the recordings in `dir` of the configured `strategy_config`'s chains.
+    pub(crate) async fn open(
+        config: &Config,
+        dir: &Path,
+        strategy_config: &StrategyConfig,
+    ) -> eyre::Result<Self> {
+        let setup = Setup::new(config, strategy_config)?;
+        let mut replays = [
+            open_recording(config, dir, &setup, SLOW).await?,
+            open_recording(config, dir, &setup, FAST).await?,
+        ];
+        let pending = [replays[SLOW].next().await?, replays[FAST].next().await?];
+
+        Ok(Self {
+            setup,
+            replays,
+            pending,
+            blocks: [None, None],
+        })
+    }
+
+    /// Applies the next recorded message of either chain, returning which one ([`SLOW`] or
+    /// [`FAST`]) it updated, when it was received in milliseconds since the unix epoch and the
+    /// pair's state on that chain. `None` once both recordings are exhausted.
+    pub(crate) async fn next(&mut self) -> eyre::Result<Option<(usize, u64, PairState)>> {
+        let idx = match (&self.pending[SLOW], &self.pending[FAST]) {
+            (None, None) => return Ok(None),
+            (Some(_), None) => SLOW,
+            (None, Some(_)) => FAST,
+            (Some((slow_at, _)), Some((fast_at, _))) => {
+                if fast_at < slow_at {
+                    FAST
+                } else {
+                    SLOW
+                }
+            }
+        };
+        let (received_at, update) = self.pending[idx].take().expect("picked a pending message");
+        self.pending[idx] = self.replays[idx].next().await?;
+
+        let block = match self.blocks[idx].take() {
+            Some(block) => block.apply_update(update),
+            None => Block::new(update),
+        };
+        let state = block.get_pair_state(&self.setup.pairs[idx], &self.setup.pool_filters[idx]);
+        self.blocks[idx] = Some(block);
+
+        Ok(Some((idx, received_at, state)))
+    }
+}
+
+/// Protocols `chain`'s collector subscribes to, and records the messages of
+pub(crate) fn recorded_protocols(config: &Config, chain: &Chain) -> eyre::Result<Vec<String>> {
+    let protocols = config.protocols(chain);
+    if !protocols.is_empty() {
+        return Ok(protocols);
+    }
+
+    Ok(collector::default_protocols_for_chain(chain)?
+        .iter()
+        .map(ToString::to_string)
+        .collect())
+}
+
+/// Opens the recording in `dir` of the setup's slow or fast chain.
+async fn open_recording(
+    config: &Config,
+    dir: &Path,
+    setup: &Setup,
+    idx: usize,
+) -> eyre::Result<recording::Replay> {
+    let chain = &setup.chains[idx];
+    recording::Replay::open(
+        recording::path_in(dir, chain.name),
+        &recorded_protocols(config, chain)?,
+        setup.tokens(idx)?,
+    )
+    .await
+}
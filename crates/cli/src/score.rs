@@ -0,0 +1,268 @@
+//! Scores a strategy against a pair's slow and fast state saved to a file, to debug offline why
+//! a block did or didn't signal.
+//!
+//! A snapshot holds both sides as [`PairStateSnapshot`]s, the pools' states as Tycho sent them,
+//! decoded again like `kuma replay` does. `--capture` takes one from kumad's recordings.
+use core::{
+    collector::recording::{self, RecordedMessage},
+    config::{Config, StrategyConfig},
+    state::{
+        block::Block,
+        pair::PairState,
+        pair_snapshot::{PairStateSnapshot, TychoStates},
+    },
+};
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{self, Context as _, OptionExt as _, eyre};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt as _, BufReader};
+use tycho_simulation::{
+    evm::decoder::TychoStreamDecoder, tycho_client::feed::BlockHeader as TychoBlockHeader,
+};
+
+use crate::replay::{FAST, SLOW, Setup, recorded_protocols};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Score {
+    /// Snapshot file to score, written first when `--capture` is given
+    #[arg(long)]
+    pub snapshot: PathBuf,
+
+    /// Directory of recordings, i.e. kumad's `record_dir`, to cut the snapshot from
+    #[arg(long, requires_all = ["strategy", "slow_block"])]
+    pub capture: Option<PathBuf>,
+
+    /// Id of the configured strategy the captured snapshot is scored with, e.g.
+    /// `weth-usdc-ethereum-base`
+    #[arg(long)]
+    pub strategy: Option<String>,
+
+    /// Height of the captured slow block
+    #[arg(long)]
+    pub slow_block: Option<u64>,
+
+    /// Height of the captured fast block. Defaults to the first one received after the slow block,
+    /// the first one live signals were searched on
+    #[arg(long)]
+    pub fast_block: Option<u64>,
+}
+
+/// A pair's slow and fast state.
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+    /// Id of the configured strategy the states are scored with
+    strategy: String,
+    slow: PairStateSnapshot,
+    fast: PairStateSnapshot,
+}
+
+impl Score {
+    pub(crate) async fn run(&self, config: Config) -> eyre::Result<()> {
+        if let Some(dir) = &self.capture {
+            let snapshot = self.capture(&config, dir).await?;
+            let json = serde_json::to_vec(&snapshot).wrap_err("failed to serialize snapshot")?;
+            tokio::fs::write(&self.snapshot, json)
+                .await
+                .wrap_err_with(|| format!("failed to write {}", self.snapshot.display()))?;
+            println!("Captured {}", self.snapshot.display());
+        }
+
+        let json = tokio::fs::read(&self.snapshot)
+            .await
+            .wrap_err_with(|| format!("failed to read {}", self.snapshot.display()))?;
+        let snapshot: Snapshot = serde_json::from_slice(&json)
+            .wrap_err_with(|| format!("failed to parse snapshot {}", self.snapshot.display()))?;
+
+        let strategy_config = find_strategy(&config, &snapshot.strategy)?;
+        let setup = Setup::new(&config, strategy_config)?;
+        let strategy = setup.strategy(config.strategy_params(strategy_config));
+        let slow_state = PairState::from_snapshot(
+            snapshot.slow,
+            &decoder(&config, &setup, SLOW).await?,
+            &setup.pairs[SLOW],
+            &setup.pool_filters[SLOW],
+        )
+        .await?;
+        let fast_state = PairState::from_snapshot(
+            snapshot.fast,
+            &decoder(&config, &setup, FAST).await?,
+            &setup.pairs[FAST],
+            &setup.pool_filters[FAST],
+        )
+        .await?;
+
+        for (idx, state) in [(SLOW, &slow_state), (FAST, &fast_state)] {
+            print_state(&setup, idx, state);
+        }
+
+        let precompute = strategy.precompute(&slow_state);
+        println!();
+        match strategy.generate_signal(&precompute, &fast_state) {
+            Ok(signal) => println!("Signal: {signal}"),
+            Err(e) => println!("No signal: {e:#}"),
+        }
+
+        Ok(())
+    }
+
+    /// Takes the pair's state at the scored blocks from the recordings in `dir`.
+    async fn capture(&self, config: &Config, dir: &Path) -> eyre::Result<Snapshot> {
+        let strategy = self
+            .strategy
+            .clone()
+            .expect("clap requires --strategy with --capture");
+        let slow_block = self
+            .slow_block
+            .expect("clap requires --slow-block with --capture");
+        let setup = Setup::new(config, find_strategy(config, &strategy)?)?;
+        let [slow_chain, fast_chain] = &setup.chains;
+
+        let slow = read_until(&recording::path_in(dir, slow_chain.name), |message| {
+            block_height(message) >= Some(slow_block)
+        })
+        .await?;
+        let slow_received_at = match slow.last() {
+            Some(message) if block_height(message) == Some(slow_block) => message.received_at_ms,
+            _ => eyre::bail!("{slow_chain} block {slow_block} isn't in the recording"),
+        };
+
+        let fast = read_until(
+            &recording::path_in(dir, fast_chain.name),
+            |message| match self.fast_block {
+                Some(fast_block) => block_height(message) >= Some(fast_block),
+                None => message.received_at_ms > slow_received_at,
+            },
+        )
+        .await?;
+        match (self.fast_block, fast.last()) {
+            (Some(fast_block), Some(message)) if block_height(message) != Some(fast_block) => {
+                eyre::bail!("{fast_chain} block {fast_block} isn't in the recording")
+            }
+            (_, None) => eyre::bail!("the {fast_chain} recording is empty"),
+            _ => {}
+        }
+
+        Ok(Snapshot {
+            strategy,
+            slow: snapshot_after(config, &setup, SLOW, slow).await?,
+            fast: snapshot_after(config, &setup, FAST, fast).await?,
+        })
+    }
+}
+
+/// The configured strategy with `id`
+fn find_strategy<'a>(config: &'a Config, id: &str) -> eyre::Result<&'a StrategyConfig> {
+    config
+        .strategies
+        .iter()
+        .find(|strategy| strategy.id() == id.to_lowercase())
+        .ok_or_else(|| eyre!("no strategy {id} in the config"))
+}
+
+/// Height of the block a message updates to
+fn block_height(message: &RecordedMessage) -> Option<u64> {
+    message
+        .message
+        .state_msgs
+        .values()
+        .map(|state| state.header.number)
+        .max()
+}
+
+/// Reads the recording at `path` up to and including the first message `last` matches, or to
+/// its end.
+async fn read_until(
+    path: &Path,
+    last: impl Fn(&RecordedMessage) -> bool,
+) -> eyre::Result<Vec<RecordedMessage>> {
+    let file = tokio::fs::File::open(path)
+        .await
+        .wrap_err_with(|| format!("failed to open recording {}", path.display()))?;
+    let mut lines = BufReader::new(file).lines();
+
+    let mut messages = Vec::new();
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .wrap_err_with(|| format!("failed to read {}", path.display()))?
+    {
+        let message: RecordedMessage = serde_json::from_str(&line)
+            .wrap_err_with(|| format!("failed to parse a message of {}", path.display()))?;
+        let done = last(&message);
+        messages.push(message);
+        if done {
+            break;
+        }
+    }
+
+    Ok(messages)
+}
+
+/// Decoder of the setup's slow or fast chain
+async fn decoder(
+    config: &Config,
+    setup: &Setup,
+    idx: usize,
+) -> eyre::Result<TychoStreamDecoder<TychoBlockHeader>> {
+    recording::decoder(
+        &recorded_protocols(config, &setup.chains[idx])?,
+        setup.tokens(idx)?,
+    )
+    .await
+}
+
+/// Snapshot of the pair's state on the setup's slow or fast chain after applying `messages`.
+async fn snapshot_after(
+    config: &Config,
+    setup: &Setup,
+    idx: usize,
+    messages: Vec<RecordedMessage>,
+) -> eyre::Result<PairStateSnapshot> {
+    let chain = &setup.chains[idx];
+    let decoder = decoder(config, setup, idx).await?;
+
+    let mut tycho_states = TychoStates::default();
+    let mut block: Option<Block> = None;
+    for message in messages {
+        tycho_states.apply(&message.message);
+        let update = decoder
+            .decode(message.message)
+            .await
+            .map_err(|e| eyre!("failed to decode a {chain} message: {e}"))?;
+        block = Some(match block {
+            Some(block) => block.apply_update(update),
+            None => Block::new(update),
+        });
+    }
+
+    block
+        .ok_or_eyre(format!("the {chain} recording has no messages"))?
+        .get_pair_state(&setup.pairs[idx], &setup.pool_filters[idx])
+        .to_snapshot(&tycho_states)
+}
+
+/// Prints the pools of a state with their spot prices, lowest first.
+fn print_state(setup: &Setup, idx: usize, state: &PairState) {
+    let pair = &setup.pairs[idx];
+    let mut spot_prices: Vec<_> = state
+        .states
+        .iter()
+        .map(|(id, pool)| (id, pool.spot_price(pair.token_a(), pair.token_b()).ok()))
+        .collect();
+    spot_prices.sort_by(|(_, a), (_, b)| {
+        a.unwrap_or(f64::INFINITY)
+            .total_cmp(&b.unwrap_or(f64::INFINITY))
+    });
+
+    println!(
+        "\n{} pools of {pair} on {} at block {}",
+        spot_prices.len(),
+        setup.chains[idx].name,
+        state.block_height
+    );
+    for (id, spot_price) in spot_prices {
+        let spot_price = spot_price.map_or_else(|| "-".to_string(), |price| format!("{price:.6}"));
+        println!("{:<66}  {spot_price:>16}", id.as_ref());
+    }
+}
@@ -0,0 +1,204 @@
+use core::{
+    admin::{Request, Response},
+    config::Config,
+    database::{self, ControlTarget, SignalFilter, SignalSort, SortOrder},
+};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alloy::primitives::Address;
+use color_eyre::eyre::{self, Context as _, eyre};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive as _;
+
+use crate::admin;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Status {}
+
+impl Status {
+    /// Reads what a running kumad records in the database: worker heartbeats, operator controls,
+    /// the latest blocks and balances, and the latest signal. Also asks kumad itself when its
+    /// admin socket is configured.
+    pub(crate) async fn run(&self, config: Config) -> eyre::Result<()> {
+        let (token_configs, _) = config
+            .build_addrs_and_inventory()
+            .map_err(|e| eyre!("failed to parse chain assets: {e}"))?;
+        let chains = config
+            .chain_registry()
+            .wrap_err("failed to parse chains from config")?;
+        let token_configs = Arc::new(token_configs);
+        let db = database::Handle::from_config(config.database.clone(), Arc::clone(&token_configs))
+            .await
+            .wrap_err("failed to connect to the database")?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .wrap_err("system clock is before the unix epoch")?
+            .as_secs();
+        let ago = |at: Option<u64>| match at {
+            Some(at) => format!("{}s ago", now.saturating_sub(at)),
+            None => "never".to_string(),
+        };
+
+        let heartbeats: HashMap<String, _> = db
+            .heartbeat_repository()
+            .get_all()
+            .await
+            .wrap_err("failed to read worker heartbeats")?
+            .into_iter()
+            .map(|heartbeat| (heartbeat.worker.clone(), heartbeat))
+            .collect();
+        let controls: HashMap<String, bool> = db
+            .control_repository()
+            .get_all()
+            .await
+            .wrap_err("failed to read controls")?
+            .into_iter()
+            .map(|control| (control.target.to_string(), control.paused))
+            .collect();
+        let reported_at = heartbeats
+            .values()
+            .map(|heartbeat| heartbeat.reported_at)
+            .max();
+        println!("kumad last reported {}", ago(reported_at));
+
+        let execution_paused = controls
+            .get(&ControlTarget::Execution.to_string())
+            .copied()
+            .unwrap_or_default();
+        println!(
+            "Execution: {}",
+            if execution_paused {
+                "paused, signals are stored but not emitted"
+            } else {
+                "running"
+            }
+        );
+
+        println!(
+            "\n{:<10}  {:>12}  {:>14}  collector",
+            "chain", "last_block", "block_time"
+        );
+        let mut chains: Vec<_> = chains.iter().collect();
+        chains.sort_by_key(|chain| chain.name.to_string());
+        let blocks = db.block_repository();
+        for chain in &chains {
+            let latest = blocks
+                .get_latest(chain, 1)
+                .await
+                .wrap_err_with(|| format!("failed to read the latest {} block", chain.name))?;
+            let (height, timestamp) = latest
+                .first()
+                .map(|block| {
+                    (
+                        block.header.height.to_string(),
+                        Some(block.header.timestamp),
+                    )
+                })
+                .unwrap_or_else(|| ("-".to_string(), None));
+            let collector = heartbeats
+                .get(&format!("collector:{}", chain.name))
+                .map(|heartbeat| format!("progressed {}", ago(heartbeat.last_progress_at)))
+                .unwrap_or_else(|| "not reported".to_string());
+            println!(
+                "{:<10}  {height:>12}  {:>14}  {collector}",
+                chain.name,
+                timestamp.map_or_else(|| "-".to_string(), |at| ago(Some(at)))
+            );
+        }
+
+        println!("\n{:<40}  {:<8}  last_progress", "strategy", "state");
+        for strategy in &config.strategies {
+            let id = strategy.id();
+            let paused = controls
+                .get(&ControlTarget::Strategy(id.clone()).to_string())
+                .copied()
+                .unwrap_or_default();
+            let progress = heartbeats
+                .get(&format!("strategy:{id}"))
+                .map(|heartbeat| ago(heartbeat.last_progress_at))
+                .unwrap_or_else(|| "not reported".to_string());
+            println!(
+                "{id:<40}  {:<8}  {progress}",
+                if paused { "paused" } else { "running" }
+            );
+        }
+
+        println!(
+            "\n{:<10}  {:>12}  {:<8}  {:>20}",
+            "chain", "block", "token", "balance"
+        );
+        let mut balances = db
+            .block_repository()
+            .latest_balances()
+            .await
+            .wrap_err("failed to read the latest balances")?;
+        balances.sort_by_key(|block| block.chain.name.to_string());
+        for block in &balances {
+            let symbols: HashMap<Address, (&str, u32)> = token_configs
+                .get(&block.chain)
+                .into_iter()
+                .flat_map(|tokens| tokens.values())
+                .filter_map(|token| {
+                    let address = Address::try_from(token.address.as_ref()).ok()?;
+                    Some((address, (token.symbol.as_str(), token.decimals)))
+                })
+                .collect();
+            let gas_token = &block.chain.gas_token;
+            let native = block
+                .native_balance
+                .iter()
+                .map(|balance| (gas_token.symbol.as_str(), gas_token.decimals, balance));
+            let tokens = block.token_balances.iter().flat_map(|balances| {
+                balances.iter().map(|(address, balance)| {
+                    let (symbol, decimals) = symbols.get(address).copied().unwrap_or(("?", 0));
+                    (symbol, decimals, balance)
+                })
+            });
+            for (symbol, decimals, balance) in native.chain(tokens) {
+                println!(
+                    "{:<10}  {:>12}  {symbol:<8}  {:>20.6}",
+                    block.chain.name,
+                    block.header.height,
+                    amount(balance, decimals)
+                );
+            }
+        }
+
+        let latest = db
+            .signal_repository()
+            .get_filtered(
+                &SignalFilter::default(),
+                SignalSort::CreatedAt,
+                SortOrder::Desc,
+                1,
+                0,
+            )
+            .await
+            .wrap_err("failed to read the latest signal")?;
+        match latest.first() {
+            Some(signal) => println!("\nLatest signal:\n{signal}"),
+            None => println!("\nNo signal stored yet"),
+        }
+
+        if config.admin_socket.is_some() {
+            match admin::request_daemon(&config, &Request::Status).await {
+                Ok(Response::Status(status)) => {
+                    println!("\nkumad reports through its admin socket:");
+                    admin::print_status(&status)?;
+                }
+                Ok(response) => println!("\nUnexpected response from kumad: {response:?}"),
+                Err(e) => println!("\nkumad's admin socket didn't answer: {e:#}"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn amount(units: &BigUint, decimals: u32) -> f64 {
+    units.to_f64().unwrap_or(f64::INFINITY) / 10f64.powi(decimals as i32)
+}
@@ -0,0 +1,417 @@
+//! Backtests a grid of strategy parameters over recorded blocks, to tune them from data.
+//!
+//! Unlike `kuma backtest`, which reprices stored signals, the sweep replays the collectors'
+//! recordings through the strategy once per combination, so `binary_search_steps` changes the
+//! trade sizes found as it would live. Slow blocks are precomputed once per number of steps and
+//! shared by the combinations using it.
+use core::{
+    config::{Config, StrategyParams},
+    strategy::{CrossChainSingleHop, Precomputes},
+};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use color_eyre::eyre::{self, Context as _, eyre};
+use num_traits::ToPrimitive as _;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+use crate::{
+    backtest::{Bound, MAX_BPS, Profits, add_profits, grid},
+    replay::{FAST, Recordings, SLOW},
+};
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct Sweep {
+    /// Directory the collectors recorded to, i.e. kumad's `record_dir`
+    #[arg(long)]
+    pub input: PathBuf,
+
+    /// Id of the configured strategy to sweep, e.g. `weth-usdc-ethereum-base`
+    #[arg(long)]
+    pub strategy: String,
+
+    /// Start of the window signals are counted in, in seconds since the unix epoch or as
+    /// `block:<height>` of the slow chain. Defaults to the start of the recordings
+    #[arg(long)]
+    pub from: Option<Bound>,
+
+    /// Inclusive end of the window, like `--from`. Defaults to the end of the recordings
+    #[arg(long)]
+    pub to: Option<Bound>,
+
+    /// Comma separated numbers of binary search steps to compare. Defaults to half, once and
+    /// twice the strategy's
+    #[arg(long, value_delimiter = ',')]
+    pub binary_search_steps: Vec<u64>,
+
+    /// Comma separated max slippages to compare, in bps. Defaults to half, once and twice the
+    /// strategy's
+    #[arg(long, value_delimiter = ',')]
+    pub slippage_bps: Vec<u64>,
+
+    /// Comma separated congestion risk discounts to compare, in bps. Defaults to half, once and
+    /// twice the strategy's
+    #[arg(long, value_delimiter = ',')]
+    pub discount_bps: Vec<u64>,
+
+    /// File the CSV is written to, stdout if omitted
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+/// What a combination of parameters found
+struct Outcome {
+    params: StrategyParams,
+    signals: usize,
+    profits: Profits,
+}
+
+/// A combination of parameters' strategy and what it found so far
+struct Run {
+    strategy: CrossChainSingleHop,
+    outcome: Outcome,
+}
+
+impl Sweep {
+    pub(crate) async fn run(
+        &self,
+        config: Config,
+        shutdown_token: CancellationToken,
+    ) -> eyre::Result<()> {
+        let strategy_config = config
+            .strategies
+            .iter()
+            .find(|strategy| strategy.id() == self.strategy.to_lowercase())
+            .ok_or_else(|| eyre!("no strategy {} in the config", self.strategy))?;
+        let params = config.strategy_params(strategy_config);
+        if let Some(bps) = self
+            .slippage_bps
+            .iter()
+            .chain(&self.discount_bps)
+            .find(|bps| **bps > MAX_BPS)
+        {
+            eyre::bail!("{bps} bps is more than 100%");
+        }
+        if self.binary_search_steps.contains(&0) {
+            eyre::bail!("binary search needs at least one step");
+        }
+
+        let mut recordings = Recordings::open(&config, &self.input, strategy_config).await?;
+        let mut runs: Vec<Run> = self
+            .combinations(params)
+            .into_iter()
+            .map(|params| Run {
+                strategy: recordings.setup.strategy(params),
+                outcome: Outcome {
+                    params,
+                    signals: 0,
+                    profits: Profits::new(),
+                },
+            })
+            .collect();
+        // combinations are ordered by number of steps first
+        let mut steps_grid: Vec<usize> = runs
+            .iter()
+            .map(|run| run.outcome.params.binary_search_steps)
+            .collect();
+        steps_grid.dedup();
+        info!(combinations = runs.len(), "Sweeping parameters");
+
+        // the latest slow block's precompute for each number of steps
+        let mut precomputes: BTreeMap<usize, Precomputes> = BTreeMap::new();
+        let (mut slow_height, mut fast_blocks) = (None, 0);
+        while let Some((idx, received_at, state)) = recordings.next().await? {
+            if shutdown_token.is_cancelled() {
+                break;
+            }
+            let received_at = received_at / 1000;
+            let height = if idx == SLOW {
+                Some(state.block_height)
+            } else {
+                slow_height
+            };
+            if self.past(received_at, height) {
+                break;
+            }
+
+            if idx == SLOW {
+                slow_height = height;
+                for &steps in &steps_grid {
+                    let run = runs
+                        .iter()
+                        .find(|run| run.strategy.binary_search_steps == steps)
+                        .expect("every number of steps has runs");
+                    precomputes.insert(steps, run.strategy.precompute(&state));
+                }
+                continue;
+            }
+            if precomputes.is_empty() || self.before(received_at, slow_height) {
+                continue;
+            }
+
+            fast_blocks += 1;
+            for run in &mut runs {
+                let precompute = &precomputes[&run.strategy.binary_search_steps];
+                match run.strategy.generate_signal(precompute, &state) {
+                    Ok(signal) => {
+                        run.outcome.signals += 1;
+                        add_profits(&mut run.outcome.profits, &signal, &signal.expected_profit);
+                    }
+                    Err(e) => debug!(block.height = state.block_height, err = %e, "No signal"),
+                }
+            }
+        }
+        info!(
+            fast_blocks,
+            chain = %recordings.setup.chains[FAST].name,
+            "Replayed fast blocks in the window"
+        );
+
+        let pair = &recordings.setup.pairs[SLOW];
+        let mut output: Box<dyn Write> = match &self.output {
+            Some(path) => Box::new(
+                File::create(path)
+                    .wrap_err_with(|| format!("failed to create {}", path.display()))?,
+            ),
+            None => Box::new(io::stdout().lock()),
+        };
+        write_csv(
+            &mut output,
+            [
+                pair.token_a().symbol.as_str(),
+                pair.token_b().symbol.as_str(),
+            ],
+            runs.iter().map(|run| &run.outcome),
+        )
+    }
+
+    /// Every combination of the parameters to compare, ordered by number of steps, then slippage,
+    /// then discount. Parameters not given default to ones around `configured`'s.
+    fn combinations(&self, configured: StrategyParams) -> Vec<StrategyParams> {
+        let steps_grid = grid(
+            &self.binary_search_steps,
+            configured.binary_search_steps as u64,
+        );
+        let slippage_grid = grid(&self.slippage_bps, configured.max_slippage_bps);
+        let discount_grid = grid(&self.discount_bps, configured.congestion_risk_discount_bps);
+
+        let mut combinations = Vec::new();
+        // half of a single step is none, which can't search
+        for &binary_search_steps in steps_grid.iter().filter(|steps| **steps > 0) {
+            for &max_slippage_bps in &slippage_grid {
+                for &congestion_risk_discount_bps in &discount_grid {
+                    combinations.push(StrategyParams {
+                        binary_search_steps: binary_search_steps as usize,
+                        max_slippage_bps,
+                        congestion_risk_discount_bps,
+                    });
+                }
+            }
+        }
+        combinations
+    }
+
+    /// Whether a block received at `received_at`, in seconds, is too early to count: before the
+    /// `--from` time, or paired with a slow block `height` below the `--from` block. Blocks
+    /// received before any slow block have no `height`, so don't count towards a `--from` block.
+    fn before(&self, received_at: u64, height: Option<u64>) -> bool {
+        match self.from {
+            None => false,
+            Some(Bound::Time(from)) => received_at < from,
+            Some(Bound::Block(from)) => height.is_none_or(|height| height < from),
+        }
+    }
+
+    /// Whether a block received at `received_at`, in seconds, ends the replay: after the `--to`
+    /// time, or paired with a slow block `height` above the inclusive `--to` block. Recordings are
+    /// replayed in order, so nothing after it counts either.
+    fn past(&self, received_at: u64, height: Option<u64>) -> bool {
+        match self.to {
+            None => false,
+            Some(Bound::Time(to)) => received_at > to,
+            Some(Bound::Block(to)) => height.is_some_and(|height| height > to),
+        }
+    }
+}
+
+/// Writes a row per combination's outcome, with its expected profits in `symbols` in whole tokens.
+fn write_csv<'a>(
+    output: &mut dyn Write,
+    symbols: [&str; 2],
+    outcomes: impl IntoIterator<Item = &'a Outcome>,
+) -> eyre::Result<()> {
+    writeln!(
+        output,
+        "binary_search_steps,max_slippage_bps,congestion_risk_discount_bps,signals,\
+         expected_profit_{},expected_profit_{}",
+        symbols[0], symbols[1]
+    )?;
+    for outcome in outcomes {
+        let profit = |symbol: &str| {
+            outcome
+                .profits
+                .get(symbol)
+                .map_or(0.0, |(amount, decimals)| {
+                    amount.to_f64().unwrap_or(f64::INFINITY) / 10f64.powi(*decimals as i32)
+                })
+        };
+        writeln!(
+            output,
+            "{},{},{},{},{},{}",
+            outcome.params.binary_search_steps,
+            outcome.params.max_slippage_bps,
+            outcome.params.congestion_risk_discount_bps,
+            outcome.signals,
+            profit(symbols[0]),
+            profit(symbols[1]),
+        )?;
+    }
+    output.flush().wrap_err("failed to write the sweep")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigUint;
+
+    use super::*;
+
+    const CONFIGURED: StrategyParams = StrategyParams {
+        binary_search_steps: 8,
+        max_slippage_bps: 50,
+        congestion_risk_discount_bps: 6_000,
+    };
+
+    fn sweep(from: Option<Bound>, to: Option<Bound>) -> Sweep {
+        Sweep {
+            input: PathBuf::new(),
+            strategy: "weth-usdc-ethereum-base".to_string(),
+            from,
+            to,
+            binary_search_steps: Vec::new(),
+            slippage_bps: Vec::new(),
+            discount_bps: Vec::new(),
+            output: None,
+        }
+    }
+
+    fn params(steps: usize, slippage_bps: u64, discount_bps: u64) -> StrategyParams {
+        StrategyParams {
+            binary_search_steps: steps,
+            max_slippage_bps: slippage_bps,
+            congestion_risk_discount_bps: discount_bps,
+        }
+    }
+
+    #[test]
+    fn combinations_default_to_around_the_configured_params() {
+        let combinations = sweep(None, None).combinations(CONFIGURED);
+
+        assert_eq!(combinations.len(), 27);
+        assert_eq!(combinations[0], params(4, 25, 3_000));
+        assert_eq!(combinations[1], params(4, 25, 6_000));
+        // twice the discount is capped at 100%
+        assert_eq!(combinations[2], params(4, 25, 10_000));
+        assert_eq!(combinations[3], params(4, 50, 3_000));
+        assert_eq!(combinations[26], params(16, 100, 10_000));
+    }
+
+    #[test]
+    fn combinations_use_the_given_params() {
+        let mut sweep = sweep(None, None);
+        sweep.binary_search_steps = vec![16, 4, 16];
+        sweep.slippage_bps = vec![30];
+
+        assert_eq!(
+            sweep.combinations(CONFIGURED),
+            [
+                params(4, 30, 3_000),
+                params(4, 30, 6_000),
+                params(4, 30, 10_000),
+                params(16, 30, 3_000),
+                params(16, 30, 6_000),
+                params(16, 30, 10_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn combinations_skip_searching_without_steps() {
+        let configured = StrategyParams {
+            binary_search_steps: 1,
+            ..CONFIGURED
+        };
+        let steps: Vec<usize> = sweep(None, None)
+            .combinations(configured)
+            .iter()
+            .map(|params| params.binary_search_steps)
+            .collect();
+
+        assert!(steps.iter().all(|steps| (1..=2).contains(steps)));
+        assert!(steps.contains(&1) && steps.contains(&2));
+    }
+
+    #[test]
+    fn csv_has_a_row_per_combination_in_whole_tokens() {
+        let found = Outcome {
+            params: CONFIGURED,
+            signals: 2,
+            profits: Profits::from([
+                (
+                    "WETH".to_string(),
+                    (BigUint::from(500_000_000_000_000_000u64), 18),
+                ),
+                ("USDC".to_string(), (BigUint::from(1_250_000_000u64), 6)),
+            ]),
+        };
+        let nothing = Outcome {
+            params: params(4, 25, 3_000),
+            signals: 0,
+            profits: Profits::new(),
+        };
+
+        let mut csv = Vec::new();
+        write_csv(&mut csv, ["WETH", "USDC"], [&found, &nothing]).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "binary_search_steps,max_slippage_bps,congestion_risk_discount_bps,signals,\
+             expected_profit_WETH,expected_profit_USDC\n\
+             8,50,6000,2,0.5,1250\n\
+             4,25,3000,0,0,0\n"
+        );
+    }
+
+    #[test]
+    fn blocks_are_paired_with_the_latest_slow_block_height() {
+        let window = sweep(Some(Bound::Block(100)), Some(Bound::Block(200)));
+
+        // a fast block received before any slow block waits for one in the window
+        assert!(window.before(0, None));
+        assert!(window.before(0, Some(99)));
+        assert!(!window.before(0, Some(100)));
+        // the end is inclusive
+        assert!(!window.past(0, Some(200)));
+        assert!(window.past(0, Some(201)));
+        assert!(!window.past(0, None));
+    }
+
+    #[test]
+    fn blocks_are_counted_by_when_they_were_received() {
+        let window = sweep(Some(Bound::Time(1_000)), Some(Bound::Time(2_000)));
+
+        assert!(window.before(999, Some(500)));
+        assert!(!window.before(1_000, None));
+        assert!(!window.past(2_000, Some(u64::MAX)));
+        assert!(window.past(2_001, Some(0)));
+
+        let unbounded = sweep(None, None);
+        assert!(!unbounded.before(0, None));
+        assert!(!unbounded.past(u64::MAX, Some(u64::MAX)));
+    }
+}
@@ -1,14 +1,11 @@
 use core::config::Config;
-use std::{collections::HashMap, str::FromStr};
+use std::collections::HashMap;
 
 use color_eyre::eyre::{self, Context, Ok};
 use tokio::fs;
 use tracing::info;
 use tycho_common::{Bytes, models::token::Token};
-use tycho_simulation::{
-    evm::tycho_models,
-    tycho_client::{HttpRPCClient, rpc::RPCClient as _},
-};
+use tycho_simulation::tycho_client::{HttpRPCClient, rpc::RPCClient as _};
 
 #[derive(clap::Args, Debug)]
 pub(crate) struct Tokens {
@@ -20,16 +17,11 @@ pub(crate) struct Tokens {
 impl Tokens {
     pub(crate) async fn run(&self, config: Config) -> eyre::Result<()> {
         let chains = config
-            .build_chains()
-            .expect("Failed to parse chains from config");
-        let chain = {
-            let chain = tycho_models::Chain::from_str(&self.chain)
-                .wrap_err("Failed to parse chain from CLI argument")?;
-
-            chains.iter().find(|c| c.name == chain).ok_or_else(|| {
-                eyre::eyre!("Chain '{}' not found in the provided chains", self.chain)
-            })?
-        };
+            .chain_registry()
+            .wrap_err("Failed to parse chains from config")?;
+        let chain = chains
+            .get_by_name(&self.chain)
+            .wrap_err("Failed to resolve chain from CLI argument")?;
 
         let no_tls = false; // Set to true if you want to use HTTP instead of HTTPS
 
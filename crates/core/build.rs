@@ -0,0 +1,4 @@
+// rebuild when migrations change, they're embedded by `sqlx::migrate!`
+fn main() {
+    println!("cargo:rerun-if-changed=../../migrations");
+}
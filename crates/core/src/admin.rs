@@ -0,0 +1,120 @@
+//! kumad's admin socket, its runtime control surface.
+//!
+//! kumad listens on the Unix socket configured as `admin_socket`. Each connection sends one
+//! [`Request`] as a line of JSON and reads back one [`Response`], so `socat` works as a client
+//! as well as [`request`].
+use std::path::Path;
+
+use color_eyre::eyre::{self, Context as _, eyre};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader},
+    net::UnixStream,
+};
+
+/// A command for the running kumad.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    /// The workers' progress and the controls in effect
+    Status,
+    /// Pauses or resumes a strategy's signal generation
+    SetStrategyPaused { strategy: String, paused: bool },
+    /// Pauses or resumes signal emission across all strategies
+    SetExecutionPaused { paused: bool },
+    /// Sets the least expected profit, in bps of the slow swap's amount in, a strategy's signals
+    /// need to be emitted. `None` emits every signal.
+    SetMinProfit {
+        strategy: String,
+        min_profit_bps: Option<u64>,
+    },
+}
+
+/// kumad's answer to a [`Request`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Response {
+    Status(DaemonStatus),
+    /// The control was applied
+    Done,
+    Error {
+        message: String,
+    },
+}
+
+/// What kumad reports about itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    /// Whether signal emission is paused across all strategies
+    pub execution_paused: bool,
+    pub strategies: Vec<StrategyStatus>,
+    pub workers: Vec<WorkerStatus>,
+}
+
+/// Controls in effect for a strategy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StrategyStatus {
+    pub id: String,
+    pub paused: bool,
+    pub min_profit_bps: Option<u64>,
+}
+
+/// When a worker last made progress, `collector:<chain>` or `strategy:<id>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkerStatus {
+    pub worker: String,
+    /// In seconds since the unix epoch, `None` until the worker's first block
+    pub last_progress_at: Option<u64>,
+}
+
+/// Sends `request` to the kumad listening on `socket` and reads its response.
+///
+/// # Errors
+/// Returns an error if kumad isn't listening on `socket`, or it answered with a
+/// [`Response::Error`].
+pub async fn request(socket: &Path, request: &Request) -> eyre::Result<Response> {
+    let stream = UnixStream::connect(socket)
+        .await
+        .wrap_err_with(|| format!("failed to connect to kumad at {}", socket.display()))?;
+    let (reader, mut writer) = stream.into_split();
+
+    let mut line = serde_json::to_string(request).wrap_err("failed to serialize request")?;
+    line.push('\n');
+    writer
+        .write_all(line.as_bytes())
+        .await
+        .wrap_err("failed to send request")?;
+
+    let mut line = String::new();
+    BufReader::new(reader)
+        .read_line(&mut line)
+        .await
+        .wrap_err("failed to read response")?;
+    match serde_json::from_str(&line).wrap_err("failed to parse response")? {
+        Response::Error { message } => Err(eyre!("kumad refused the request: {message}")),
+        response => Ok(response),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_wire_format() {
+        let request: Request = serde_json::from_str(
+            r#"{"command": "set_min_profit", "strategy": "weth-usdc-ethereum-base", "min_profit_bps": 5}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            Request::SetMinProfit {
+                strategy: "weth-usdc-ethereum-base".to_string(),
+                min_profit_bps: Some(5),
+            }
+        );
+
+        let response = serde_json::to_value(Response::Done).unwrap();
+        assert_eq!(response, serde_json::json!({"result": "done"}));
+    }
+}
@@ -1,6 +1,7 @@
 use std::{
     fmt::{self, Display},
     str::FromStr,
+    time::Duration,
 };
 
 use alloy::primitives::Address;
@@ -9,6 +10,87 @@ use color_eyre::eyre::{self, Context, eyre};
 use serde::{Deserialize, Serialize};
 use tycho_common::models as tycho_models;
 
+/// Static facts about a network kuma knows how to trade on.
+#[derive(Debug, Clone, Copy)]
+pub struct KnownChain {
+    pub name: tycho_models::Chain,
+    pub named: NamedChain,
+    pub native_token: &'static str,
+    pub block_time: Duration,
+}
+
+/// Networks supported by both Tycho and kuma.
+pub const KNOWN_CHAINS: &[KnownChain] = &[
+    KnownChain {
+        name: tycho_models::Chain::Ethereum,
+        named: NamedChain::Mainnet,
+        native_token: "ETH",
+        block_time: Duration::from_secs(12),
+    },
+    KnownChain {
+        name: tycho_models::Chain::Base,
+        named: NamedChain::Base,
+        native_token: "ETH",
+        block_time: Duration::from_secs(2),
+    },
+    KnownChain {
+        name: tycho_models::Chain::Unichain,
+        named: NamedChain::Unichain,
+        native_token: "ETH",
+        block_time: Duration::from_secs(1),
+    },
+    KnownChain {
+        name: tycho_models::Chain::Arbitrum,
+        named: NamedChain::Arbitrum,
+        native_token: "ETH",
+        block_time: Duration::from_millis(250),
+    },
+    KnownChain {
+        name: tycho_models::Chain::Optimism,
+        named: NamedChain::Optimism,
+        native_token: "ETH",
+        block_time: Duration::from_secs(2),
+    },
+];
+
+/// Token gas is paid in on a chain.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GasToken {
+    pub symbol: String,
+    /// Zero for the chain's native asset
+    pub address: Address,
+    pub decimals: u32,
+}
+
+impl GasToken {
+    /// The chain's native asset, with 18 decimals like ETH
+    pub fn native(symbol: impl Into<String>) -> Self {
+        Self {
+            symbol: symbol.into(),
+            address: Address::ZERO,
+            decimals: 18,
+        }
+    }
+}
+
+impl KnownChain {
+    pub fn find(name: tycho_models::Chain) -> eyre::Result<&'static Self> {
+        KNOWN_CHAINS
+            .iter()
+            .find(|known| known.name == name)
+            .ok_or_else(|| {
+                eyre!(
+                    "unsupported chain {}, expected one of {:?}",
+                    name,
+                    KNOWN_CHAINS
+                        .iter()
+                        .map(|known| known.name.to_string())
+                        .collect::<Vec<_>>()
+                )
+            })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct Chain {
     pub name: tycho_models::Chain,
@@ -16,10 +98,17 @@ pub struct Chain {
     pub metadata: alloy_chains::Chain,
     #[serde(skip)]
     pub rpc_url: String,
+    /// RPC endpoints to fail over to, in order of preference
+    #[serde(skip)]
+    pub rpc_fallbacks: Vec<String>,
     #[serde(skip)]
     pub tycho_url: String,
     #[serde(skip)]
     pub permit2_address: Address,
+    #[serde(skip)]
+    pub block_time: Duration,
+    #[serde(skip)]
+    pub gas_token: GasToken,
 }
 
 impl Chain {
@@ -31,12 +120,11 @@ impl Chain {
     ) -> eyre::Result<Self> {
         let name = tycho_models::Chain::from_str(name)
             .wrap_err("failed to parse chain name into tycho::models::Chain")?;
-        let metadata = match name {
-            tycho_models::Chain::Ethereum => alloy_chains::Chain::from(NamedChain::Mainnet),
-            tycho_models::Chain::Base => alloy_chains::Chain::from(NamedChain::Base),
-            tycho_models::Chain::Unichain => alloy_chains::Chain::from(NamedChain::Unichain),
-            _ => return Err(eyre!("unsupported chain {}", name)),
-        };
+        let known = KnownChain::find(name)?;
+        let metadata = alloy_chains::Chain::from(known.named);
+        let block_time = metadata
+            .average_blocktime_hint()
+            .unwrap_or(known.block_time);
 
         let permit2_address =
             Address::from_str(permit2_address).wrap_err("failed to parse address")?;
@@ -45,25 +133,74 @@ impl Chain {
             name,
             metadata,
             rpc_url: rpc_url.to_string(),
+            rpc_fallbacks: Vec::new(),
             tycho_url: tycho_url.to_string(),
             permit2_address: permit2_address,
+            block_time,
+            gas_token: GasToken::native(known.native_token),
         })
     }
 
+    /// Overrides the average block time, e.g. from configuration.
+    pub fn with_block_time(mut self, block_time: Duration) -> Self {
+        self.block_time = block_time;
+        self
+    }
+
+    /// Sets the RPC endpoints to fail over to when `rpc_url` is unreachable.
+    pub fn with_rpc_fallbacks(mut self, rpc_fallbacks: Vec<String>) -> Self {
+        self.rpc_fallbacks = rpc_fallbacks;
+        self
+    }
+
+    /// Overrides the gas token, e.g. from configuration.
+    pub fn with_gas_token(mut self, gas_token: GasToken) -> Self {
+        self.gas_token = gas_token;
+        self
+    }
+
+    /// RPC endpoints in order of preference, `rpc_url` first.
+    pub fn rpc_urls(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.rpc_url.as_str()).chain(self.rpc_fallbacks.iter().map(String::as_str))
+    }
+
     #[allow(unused)]
     pub fn chain_id(&self) -> u64 {
         self.metadata.id()
     }
 
+    /// Average block time
+    pub fn block_time(&self) -> Duration {
+        self.block_time
+    }
+
+    /// Symbol of the token gas is paid in
+    pub fn native_token(&self) -> &str {
+        &self.gas_token.symbol
+    }
+
+    /// Token gas is paid in, for valuing gas costs
+    pub fn gas_token(&self) -> &GasToken {
+        &self.gas_token
+    }
+
+    /// The name Tycho uses for this chain, as accepted in `kuma.yaml`.
+    pub fn tycho_slug(&self) -> String {
+        self.name.to_string()
+    }
+
     #[cfg(test)]
     pub fn eth_mainnet() -> Self {
         Self {
             name: tycho_models::Chain::Ethereum,
             metadata: alloy_chains::Chain::from_named(NamedChain::Mainnet),
             rpc_url: "https://mainnet.infura.io/v3/".to_string(),
+            rpc_fallbacks: Vec::new(),
             tycho_url: "tycho-beta.propellerheads.xyz".to_string(),
             permit2_address: Address::from_str("0x000000000022d473030f116ddee9f6b43ac78ba3")
                 .expect("Couldn't convert to address"),
+            block_time: Duration::from_secs(12),
+            gas_token: GasToken::native("ETH"),
         }
     }
 
@@ -73,9 +210,12 @@ impl Chain {
             name: tycho_models::Chain::Base,
             metadata: alloy_chains::Chain::from_named(NamedChain::Base),
             rpc_url: "https://base-mainnet.infura.io/v3/".to_string(),
+            rpc_fallbacks: Vec::new(),
             tycho_url: "tycho-base-beta.propellerheads.xyz".to_string(),
             permit2_address: Address::from_str("0x000000000022d473030f116ddee9f6b43ac78ba3")
                 .expect("Couldn't convert to address"),
+            block_time: Duration::from_secs(2),
+            gas_token: GasToken::native("ETH"),
         }
     }
 
@@ -86,10 +226,77 @@ impl Chain {
             name: tycho_models::Chain::Unichain,
             metadata: alloy_chains::Chain::from_named(NamedChain::Unichain),
             rpc_url: "https://unichain-mainnet.infura.io/v3/".to_string(),
+            rpc_fallbacks: Vec::new(),
             tycho_url: "tycho-unichain-beta.propellerheads.xyz".to_string(),
             permit2_address: Address::from_str("0x000000000022d473030f116ddee9f6b43ac78ba3")
                 .expect("Couldn't convert to address"),
+            block_time: Duration::from_secs(1),
+            gas_token: GasToken::native("ETH"),
+        }
+    }
+
+    #[cfg(test)]
+    #[allow(unused)]
+    pub fn arbitrum_mainnet() -> Self {
+        Self {
+            name: tycho_models::Chain::Arbitrum,
+            metadata: alloy_chains::Chain::from_named(NamedChain::Arbitrum),
+            rpc_url: "https://arbitrum-mainnet.infura.io/v3/".to_string(),
+            rpc_fallbacks: Vec::new(),
+            tycho_url: "tycho-arbitrum-beta.propellerheads.xyz".to_string(),
+            permit2_address: Address::from_str("0x000000000022d473030f116ddee9f6b43ac78ba3")
+                .expect("Couldn't convert to address"),
+            block_time: Duration::from_millis(250),
+            gas_token: GasToken::native("ETH"),
+        }
+    }
+}
+
+/// The set of chains configured in `kuma.yaml`, the single place chains are resolved from.
+#[derive(Debug, Clone, Default)]
+pub struct ChainRegistry {
+    chains: Vec<Chain>,
+}
+
+impl ChainRegistry {
+    pub fn new(chains: Vec<Chain>) -> eyre::Result<Self> {
+        for (i, chain) in chains.iter().enumerate() {
+            if chains[..i].iter().any(|other| other.name == chain.name) {
+                return Err(eyre!("chain {} is configured more than once", chain.name));
+            }
         }
+
+        Ok(Self { chains })
+    }
+
+    pub fn get(&self, name: tycho_models::Chain) -> Option<&Chain> {
+        self.chains.iter().find(|chain| chain.name == name)
+    }
+
+    pub fn get_by_id(&self, chain_id: u64) -> Option<&Chain> {
+        self.chains
+            .iter()
+            .find(|chain| chain.chain_id() == chain_id)
+    }
+
+    /// Resolves a chain from its Tycho name, e.g. `"ethereum"`.
+    pub fn get_by_name(&self, name: &str) -> eyre::Result<&Chain> {
+        let name = tycho_models::Chain::from_str(name)
+            .map_err(|e| eyre!("unknown chain name {name}: {e}"))?;
+        self.get(name)
+            .ok_or_else(|| eyre!("chain {name} is not configured"))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Chain> {
+        self.chains.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
     }
 }
 
@@ -1,97 +1,415 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
+use alloy::primitives::Address;
 use color_eyre::eyre::{self, Context as _, eyre};
-use tokio::sync::watch;
+use futures::{StreamExt as _, stream::BoxStream};
+use tokio::{select, sync::watch};
 use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 use tycho_common::{Bytes, models::token::Token};
 use tycho_simulation::{
     evm::{
+        decoder::TychoStreamDecoder,
         protocol::{
             pancakeswap_v2::state::PancakeswapV2State, uniswap_v2::state::UniswapV2State,
             uniswap_v3::state::UniswapV3State,
         },
         stream::ProtocolStreamBuilder,
     },
-    tycho_client::feed::component_tracker::ComponentFilter,
+    protocol::models::Update,
+    tycho_client::feed::{BlockHeader as TychoBlockHeader, component_tracker::ComponentFilter},
+};
+
+use super::{Worker, recording};
+use crate::{
+    chain::Chain,
+    database::{BlockRepository, GasPriceRepository},
+    rpc::RpcEndpoints,
+    state::{
+        PoolFilter,
+        balances::{Inventory, SharedInventory},
+        block::Block,
+        header::{BlockHeader, FinalityHeads},
+        pair::{PairStateDelivery, PairStateRouter},
+        pair_snapshot::TychoStates,
+        snapshot::BlockSnapshot,
+    },
 };
 
-use super::Worker;
-use crate::{chain::Chain, state::block::Block};
+pub struct SnapshotSettings {
+    pub path: PathBuf,
+    /// Number of blocks between two snapshot writes
+    pub interval_blocks: u64,
+}
+
+/// A Tycho indexer endpoint and the API key used to authenticate against it.
+#[derive(Debug, Clone)]
+pub struct TychoEndpoint {
+    pub url: String,
+    pub api_key: String,
+}
 
 pub struct Builder {
     pub chain: Chain,
-    pub tycho_url: String,
-    pub api_key: String,
+    /// Tycho endpoints in order of preference, rotated through on repeated stream failures
+    pub endpoints: Vec<TychoEndpoint>,
+    /// Consecutive stream errors tolerated before moving on to the next endpoint
+    pub max_stream_failures: u32,
     pub tokens: HashMap<Bytes, Token>,
     pub add_tvl_threshold: f64,
     pub remove_tvl_threshold: f64,
+    /// Account whose native gas balance is tracked on every block, if any
+    pub account: Option<Address>,
+    /// Pools to keep out of every `PairState` built from this collector
+    pub pool_filter: PoolFilter,
+    /// Whether pair state streams get only the latest state or queue them
+    pub pair_state_delivery: PairStateDelivery,
+    /// Tycho protocol systems to subscribe to. Empty selects the chain's defaults.
+    pub protocols: Vec<String>,
+    /// Persist the latest block periodically and resume from it on startup
+    pub snapshot: Option<SnapshotSettings>,
+    /// Append every raw Tycho message to this file, to replay them offline
+    pub record: Option<PathBuf>,
+    /// Records every block's header and the account's balances in the `blocks` table
+    pub blocks: Option<BlockRepository>,
+    /// Records base and priority fees of every block in the `gas_prices` table
+    pub gas_prices: Option<GasPriceRepository>,
     pub shutdown_token: CancellationToken,
 }
 
 impl Builder {
     pub fn build(self) -> eyre::Result<super::Handle> {
         let Self {
-            tycho_url: url,
+            endpoints,
+            max_stream_failures,
             add_tvl_threshold,
             remove_tvl_threshold,
             chain,
-            api_key,
             tokens,
+            account,
+            pool_filter,
+            pair_state_delivery,
+            protocols,
+            snapshot,
+            record,
+            blocks,
+            gas_prices,
             shutdown_token,
             ..
         } = self;
 
-        // make protocol stream
-        let protocol_stream = ProtocolStreamBuilder::new(&url, chain.name);
-        let tvl_filter = ComponentFilter::with_tvl_range(remove_tvl_threshold, add_tvl_threshold);
-        let protocol_stream = Self::add_exchanges_for_chain(&chain, protocol_stream, tvl_filter)
-            .wrap_err("failed to set exchanges for {chain.name}.")?;
+        if endpoints.is_empty() {
+            return Err(eyre!("no tycho endpoints configured for {chain}"));
+        }
+
+        let rpc = RpcEndpoints::new(&chain)?;
 
-        let protocol_stream_builder = protocol_stream
-            .auth_key(Some(api_key))
-            .skip_state_decode_failures(true)
-            .set_tokens(tokens.clone());
+        let protocols: Vec<String> = if protocols.is_empty() {
+            default_protocols_for_chain(&chain)?
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        } else {
+            protocols
+        };
+        if let Some(unsupported) = protocols
+            .iter()
+            .find(|protocol| !SUPPORTED_PROTOCOLS.contains(&protocol.as_str()))
+        {
+            return Err(eyre!(
+                "unsupported protocol {unsupported} for {}, expected one of {SUPPORTED_PROTOCOLS:?}",
+                chain.name
+            ));
+        }
+        info!(chain.name = %chain.name, ?protocols, endpoints = endpoints.len(), "Subscribing to protocols");
+
+        let stream_settings = StreamSettings {
+            endpoints,
+            protocols,
+            tvl_filter: ComponentFilter::with_tvl_range(remove_tvl_threshold, add_tvl_threshold),
+            tokens,
+            max_failures: max_stream_failures.max(1),
+            record,
+            tycho_states: snapshot.as_ref().map(|_| Arc::default()),
+        };
+
+        let token_addresses = stream_settings
+            .tokens
+            .keys()
+            .map(|address| {
+                Address::try_from(address.as_ref())
+                    .wrap_err_with(|| format!("invalid token address {address}"))
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        let inventory =
+            SharedInventory::new(Inventory::new(stream_settings.tokens.values().cloned()));
 
         let (block_tx, block_rx) = watch::channel::<Arc<Option<Block>>>(Arc::new(None));
+        let (header_tx, header_rx) = watch::channel::<Option<BlockHeader>>(None);
+        let (finality_tx, finality_rx) = watch::channel(FinalityHeads::default());
+        let (last_update_tx, last_update_rx) = watch::channel(None);
 
         let worker = Worker {
-            // TODO: do i really wanna get rid of these or keep them for reconnect?
-            // uri: Uri::from_str(&url).expect("invalid uri"),
-            // api_key: api_key.clone(),
-            protocol_stream_builder: Box::pin(protocol_stream_builder),
+            stream_settings,
             chain: chain.clone(),
+            rpc,
+            account,
+            token_addresses,
+            inventory: inventory.clone(),
             block_tx,
+            header_tx,
+            finality_tx,
+            last_update_tx,
+            snapshot,
+            blocks,
+            gas_prices,
             shutdown_token: shutdown_token.clone(),
         };
         let worker_handle = tokio::task::spawn(async { worker.run().await });
+        let (pair_states, router_handle) = PairStateRouter::spawn(
+            chain.clone(),
+            block_rx.clone(),
+            Arc::new(pool_filter),
+            pair_state_delivery,
+            shutdown_token.clone(),
+        );
 
         Ok(super::Handle {
+            staleness: super::Staleness::new(chain.clone(), last_update_rx),
             chain,
             shutdown_token,
             worker_handle: Some(worker_handle),
+            router_handle: Some(router_handle),
             block_rx,
+            header_rx,
+            finality_rx,
+            pair_states,
+            inventory,
         })
     }
 
-    fn add_exchanges_for_chain(
-        chain: &Chain,
-        protocol_stream: ProtocolStreamBuilder,
+    fn add_exchanges(
+        mut protocol_stream: ProtocolStreamBuilder,
+        protocols: &[String],
         tvl_filter: ComponentFilter,
     ) -> eyre::Result<ProtocolStreamBuilder> {
-        match chain.name {
-            tycho_common::models::Chain::Ethereum => Ok(protocol_stream
-                .exchange::<UniswapV2State>("uniswap_v2", tvl_filter.clone(), None)
-                .exchange::<UniswapV2State>("sushiswap_v2", tvl_filter.clone(), None)
-                .exchange::<PancakeswapV2State>("pancakeswap_v2", tvl_filter.clone(), None)
-                .exchange::<UniswapV3State>("uniswap_v3", tvl_filter.clone(), None)
-                .exchange::<UniswapV3State>("pancakeswap_v3", tvl_filter.clone(), None)),
-            tycho_common::models::Chain::Base => Ok(protocol_stream
-                .exchange::<UniswapV2State>("uniswap_v2", tvl_filter.clone(), None)
-                .exchange::<UniswapV3State>("uniswap_v3", tvl_filter.clone(), None)),
-            tycho_common::models::Chain::Unichain => Ok(protocol_stream
-                .exchange::<UniswapV2State>("uniswap_v2", tvl_filter.clone(), None)
-                .exchange::<UniswapV3State>("uniswap_v3", tvl_filter.clone(), None)),
-            _ => Err(eyre!("unsupported chain variant")),
+        for protocol in protocols {
+            protocol_stream = match protocol.as_str() {
+                "uniswap_v2" | "sushiswap_v2" => {
+                    protocol_stream.exchange::<UniswapV2State>(protocol, tvl_filter.clone(), None)
+                }
+                "pancakeswap_v2" => protocol_stream.exchange::<PancakeswapV2State>(
+                    protocol,
+                    tvl_filter.clone(),
+                    None,
+                ),
+                "uniswap_v3" | "pancakeswap_v3" => {
+                    protocol_stream.exchange::<UniswapV3State>(protocol, tvl_filter.clone(), None)
+                }
+                _ => {
+                    return Err(eyre!(
+                        "unsupported protocol {protocol}, expected one of {SUPPORTED_PROTOCOLS:?}"
+                    ));
+                }
+            };
         }
+
+        Ok(protocol_stream)
+    }
+}
+
+/// Block updates of a protocol stream, from Tycho or a recording.
+pub(super) type UpdateStream = BoxStream<'static, eyre::Result<Update>>;
+
+/// Wait before going round the tycho endpoints again after none accepted a stream, doubled on
+/// every further round up to [`MAX_RECONNECT_BACKOFF`]
+const MIN_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Everything needed to (re)connect a protocol stream to one of the configured endpoints.
+pub(super) struct StreamSettings {
+    pub(super) endpoints: Vec<TychoEndpoint>,
+    pub(super) protocols: Vec<String>,
+    pub(super) tvl_filter: ComponentFilter,
+    pub(super) tokens: HashMap<Bytes, Token>,
+    pub(super) max_failures: u32,
+    pub(super) record: Option<PathBuf>,
+    /// The latest state Tycho sent of every component, followed to snapshot blocks
+    pub(super) tycho_states: Option<Arc<Mutex<TychoStates>>>,
+}
+
+/// A block snapshot a collector resumes from, and the decoder seeded with its states that the
+/// first stream decodes the messages after it with.
+pub(super) struct WarmStart {
+    pub(super) snapshot: Arc<BlockSnapshot>,
+    pub(super) decoder: Arc<TychoStreamDecoder<TychoBlockHeader>>,
+}
+
+impl WarmStart {
+    /// Reads the snapshot at `path` and decodes it into the block to start from, `None` if there
+    /// is no snapshot or it's of another chain.
+    pub(super) async fn load(
+        path: &Path,
+        chain: &Chain,
+        settings: &StreamSettings,
+    ) -> eyre::Result<Option<(Self, Block)>> {
+        let Some(snapshot) = BlockSnapshot::load(path).await? else {
+            return Ok(None);
+        };
+        if snapshot.chain != chain.name {
+            warn!(path = %path.display(), snapshot.chain = %snapshot.chain, "Ignored block snapshot of another chain");
+            return Ok(None);
+        }
+
+        let decoder = recording::decoder(&settings.protocols, settings.tokens.clone()).await?;
+        let update = decoder
+            .decode(snapshot.to_message())
+            .await
+            .map_err(|e| eyre!("failed to decode block snapshot: {e}"))?;
+        let mut block = Block::new(update);
+        block.timestamp = Some(snapshot.header.timestamp);
+
+        if let Some(tycho_states) = &settings.tycho_states {
+            *tycho_states.lock().unwrap() = snapshot.to_tycho_states();
+        }
+
+        Ok(Some((
+            Self {
+                snapshot: Arc::new(snapshot),
+                decoder: Arc::new(decoder),
+            },
+            block,
+        )))
+    }
+}
+
+impl StreamSettings {
+    /// Connects to the first endpoint that accepts a stream, starting at `*endpoint_idx` and
+    /// wrapping around once. `*endpoint_idx` is left pointing at the connected endpoint.
+    ///
+    /// A stream resuming from `warm` decodes only the messages past its snapshot.
+    pub(super) async fn connect(
+        &self,
+        chain: &Chain,
+        endpoint_idx: &mut usize,
+        warm: Option<&WarmStart>,
+    ) -> eyre::Result<UpdateStream> {
+        for attempt in 0..self.endpoints.len() {
+            let idx = (*endpoint_idx + attempt) % self.endpoints.len();
+            let endpoint = &self.endpoints[idx];
+
+            match self.connect_to(chain, endpoint, warm).await {
+                Ok(stream) => {
+                    info!(chain.name = %chain.name, tycho.url = %endpoint.url, "Connected to tycho endpoint");
+                    *endpoint_idx = idx;
+                    return Ok(stream);
+                }
+                Err(e) => {
+                    warn!(chain.name = %chain.name, tycho.url = %endpoint.url, err = %e, "Failed to connect to tycho endpoint");
+                }
+            }
+        }
+
+        Err(eyre!(
+            "failed to connect to any of the {} tycho endpoints for {}",
+            self.endpoints.len(),
+            chain.name
+        ))
+    }
+
+    /// Moves on from the endpoint at `*endpoint_idx` and connects to the next one that works,
+    /// going round the endpoints again with exponential backoff while none does. `None` once
+    /// `shutdown_token` is cancelled.
+    pub(super) async fn rotate(
+        &self,
+        chain: &Chain,
+        endpoint_idx: &mut usize,
+        shutdown_token: &CancellationToken,
+    ) -> Option<UpdateStream> {
+        *endpoint_idx = (*endpoint_idx + 1) % self.endpoints.len();
+        let mut backoff = MIN_RECONNECT_BACKOFF;
+        loop {
+            let connected = select! {
+                () = shutdown_token.cancelled() => return None,
+                connected = self.connect(chain, endpoint_idx, None) => connected,
+            };
+            match connected {
+                Ok(stream) => return Some(stream),
+                Err(e) => {
+                    warn!(chain.name = %chain.name, ?backoff, err = %e, "Failed to reconnect protocol stream, retrying");
+                    metrics::counter!("kuma_collector_reconnect_failures_total", "chain" => chain.to_string())
+                        .increment(1);
+                }
+            }
+
+            select! {
+                () = shutdown_token.cancelled() => return None,
+                () = tokio::time::sleep(backoff) => {}
+            }
+            backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+        }
+    }
+
+    async fn connect_to(
+        &self,
+        chain: &Chain,
+        endpoint: &TychoEndpoint,
+        warm: Option<&WarmStart>,
+    ) -> eyre::Result<UpdateStream> {
+        // recording or following the raw messages means decoding them ourselves
+        if self.record.is_some() || self.tycho_states.is_some() {
+            return recording::connect_raw(self, chain.name, endpoint, warm).await;
+        }
+
+        let protocol_stream = ProtocolStreamBuilder::new(&endpoint.url, chain.name);
+        let protocol_stream =
+            Builder::add_exchanges(protocol_stream, &self.protocols, self.tvl_filter.clone())
+                .wrap_err_with(|| format!("failed to set exchanges for {}", chain.name))?;
+
+        let stream = protocol_stream
+            .auth_key(Some(endpoint.api_key.clone()))
+            .skip_state_decode_failures(true)
+            .set_tokens(self.tokens.clone())
+            .await
+            .build()
+            .await
+            .wrap_err("failed building protocol stream")?;
+
+        Ok(stream
+            .map(|message| message.map_err(|e| eyre!("{e}")))
+            .boxed())
+    }
+}
+
+/// Tycho protocol systems the collector knows how to decode.
+pub const SUPPORTED_PROTOCOLS: &[&str] = &[
+    "uniswap_v2",
+    "sushiswap_v2",
+    "pancakeswap_v2",
+    "uniswap_v3",
+    "pancakeswap_v3",
+];
+
+/// Protocols subscribed to when a chain doesn't configure its own list.
+pub fn default_protocols_for_chain(chain: &Chain) -> eyre::Result<&'static [&'static str]> {
+    match chain.name {
+        tycho_common::models::Chain::Ethereum => Ok(&[
+            "uniswap_v2",
+            "sushiswap_v2",
+            "pancakeswap_v2",
+            "uniswap_v3",
+            "pancakeswap_v3",
+        ]),
+        tycho_common::models::Chain::Base
+        | tycho_common::models::Chain::Unichain
+        | tycho_common::models::Chain::Arbitrum
+        | tycho_common::models::Chain::Optimism => Ok(&["uniswap_v2", "uniswap_v3"]),
+        _ => Err(eyre!("unsupported chain variant")),
     }
 }
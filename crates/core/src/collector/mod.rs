@@ -1,26 +1,53 @@
 //! Module for interacting with Tycho Simulation's ProtocolStream
-//! TODO: move this to a simulation submodule and add an execution submodule for the encoder
-//! and submission stuff?
-use std::{pin::Pin, sync::Arc};
+//! TODO: move this to a simulation submodule next to [`crate::execution`]?
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
+use alloy::{
+    eips::BlockNumberOrTag,
+    primitives::Address,
+    providers::{DynProvider, Provider as _},
+    rpc::types::Log,
+    sol,
+};
 use color_eyre::eyre;
-use color_eyre::eyre::WrapErr as _;
-use tokio::{select, sync::watch};
+use color_eyre::eyre::{OptionExt as _, WrapErr as _};
+use num_bigint::BigUint;
+use tokio::{select, sync::watch, time::Instant};
 use tokio_stream::StreamExt;
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info, instrument, trace};
-use tycho_simulation::evm::stream::ProtocolStreamBuilder;
+use tracing::{error, info, instrument, trace, warn};
 
 use crate::{
     chain::Chain,
+    database::{BlockRecord, BlockRepository, GasPrice, GasPriceRepository},
+    rpc::RpcEndpoints,
     state::{
+        balances::{SharedInventory, TokenBalances, transfer_filters, u256_to_biguint},
         block::Block,
-        pair::{Pair, PairStateStream},
+        header::{BlockHeader, FinalityHeads},
+        pair::{Pair, PairStateRouter, PairStateStream},
+        snapshot::{BlockSnapshot, SnapshotWriter},
     },
 };
 
-pub use builder::Builder;
+pub use builder::{
+    Builder, SUPPORTED_PROTOCOLS, SnapshotSettings, TychoEndpoint, default_protocols_for_chain,
+};
+use builder::{StreamSettings, WarmStart};
 mod builder;
+pub mod recording;
+
+sol! {
+    #[sol(rpc)]
+    interface IERC20 {
+        function balanceOf(address account) external view returns (uint256);
+    }
+}
 
 pub struct Handle {
     #[allow(unused)]
@@ -28,8 +55,49 @@ pub struct Handle {
     #[allow(unused)]
     shutdown_token: CancellationToken,
     worker_handle: Option<tokio::task::JoinHandle<eyre::Result<()>>>,
+    router_handle: Option<tokio::task::JoinHandle<()>>,
     // TODO: get rid of option
     block_rx: watch::Receiver<Arc<Option<Block>>>,
+    header_rx: watch::Receiver<Option<BlockHeader>>,
+    finality_rx: watch::Receiver<FinalityHeads>,
+    staleness: Staleness,
+    /// Builds the pair states of every block once for all of the collector's subscribers
+    pair_states: PairStateRouter,
+    inventory: SharedInventory,
+}
+
+/// Tracks how long ago a collector last received a block update.
+#[derive(Debug, Clone)]
+pub struct Staleness {
+    chain: Chain,
+    last_update_rx: watch::Receiver<Option<Instant>>,
+}
+
+impl Staleness {
+    /// Staleness of `chain`'s collector, which sends when it received each block update on
+    /// `last_update_rx`'s channel
+    pub fn new(chain: Chain, last_update_rx: watch::Receiver<Option<Instant>>) -> Self {
+        Self {
+            chain,
+            last_update_rx,
+        }
+    }
+
+    pub fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
+    /// Time elapsed since the last block update, `None` if no block was received yet.
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.last_update_rx.borrow().map(|at| at.elapsed())
+    }
+
+    /// Whether the collector hasn't received a block within `threshold`.
+    ///
+    /// A collector that hasn't produced its first block yet is not considered stale.
+    pub fn exceeds(&self, threshold: Duration) -> bool {
+        self.elapsed().is_some_and(|elapsed| elapsed > threshold)
+    }
 }
 
 impl Handle {
@@ -45,6 +113,11 @@ impl Handle {
             error!(chain=%self.chain, "Tycho simulation stream worker failed: {}", e);
             return Err(e.into());
         }
+        if let Some(router_handle) = self.router_handle.take() {
+            if let Err(e) = router_handle.await {
+                error!(chain=%self.chain, "Pair state router failed: {}", e);
+            }
+        }
         Ok(())
     }
 
@@ -53,9 +126,34 @@ impl Handle {
         self.block_rx.clone()
     }
 
+    /// Receiver for the header (base fee, blob fee, timestamp) of the latest collected block
+    pub fn get_header_rx(&self) -> watch::Receiver<Option<BlockHeader>> {
+        self.header_rx.clone()
+    }
+
+    /// Receiver for the chain's latest, safe and finalized heads
+    pub fn get_finality_rx(&self) -> watch::Receiver<FinalityHeads> {
+        self.finality_rx.clone()
+    }
+
+    /// Time elapsed since the collector last received a block update
+    pub fn staleness(&self) -> Option<Duration> {
+        self.staleness.elapsed()
+    }
+
+    /// A cloneable watchdog over this collector's block updates, for use by other workers
+    pub fn staleness_monitor(&self) -> Staleness {
+        self.staleness.clone()
+    }
+
+    /// Stream of `pair`'s state on every block from the next one on
     pub fn get_pair_state_stream(&self, pair: &Pair) -> PairStateStream {
-        let block_rx = self.block_rx.clone();
-        PairStateStream::from_block_rx(pair.clone(), block_rx)
+        self.pair_states.subscribe(pair)
+    }
+
+    /// The account's inventory of the collector's tokens, its balances refreshed on every block
+    pub fn inventory(&self) -> SharedInventory {
+        self.inventory.clone()
     }
 }
 
@@ -86,8 +184,19 @@ impl Future for Handle {
 
 struct Worker {
     chain: Chain,
-    protocol_stream_builder: Pin<Box<dyn Future<Output = ProtocolStreamBuilder> + Send>>,
+    stream_settings: StreamSettings,
+    rpc: RpcEndpoints,
+    account: Option<Address>,
+    /// Tokens whose balances are tracked for `account`
+    token_addresses: Vec<Address>,
+    inventory: SharedInventory,
     block_tx: watch::Sender<Arc<Option<Block>>>,
+    header_tx: watch::Sender<Option<BlockHeader>>,
+    finality_tx: watch::Sender<FinalityHeads>,
+    last_update_tx: watch::Sender<Option<Instant>>,
+    snapshot: Option<SnapshotSettings>,
+    blocks: Option<BlockRepository>,
+    gas_prices: Option<GasPriceRepository>,
     shutdown_token: CancellationToken,
 }
 
@@ -95,17 +204,68 @@ impl Worker {
     #[instrument(name = "tycho_stream_collector", skip(self), fields(chain.name = %self.chain.name))]
     pub async fn run(self) -> eyre::Result<()> {
         let Self {
-            protocol_stream_builder,
+            stream_settings,
             chain,
+            rpc,
+            account,
+            token_addresses,
+            inventory,
             block_tx,
+            header_tx,
+            finality_tx,
+            last_update_tx,
+            snapshot,
+            blocks,
+            gas_prices,
             ..
         } = self;
 
-        let mut protocol_stream = protocol_stream_builder
-            .await
-            .build()
+        let warm = match &snapshot {
+            Some(settings) => {
+                match WarmStart::load(&settings.path, &chain, &stream_settings).await {
+                    Ok(warm) => warm,
+                    Err(e) => {
+                        warn!(path = %settings.path.display(), err = %e, "Failed to load block snapshot, starting cold");
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        let mut last_snapshot_height = None;
+        if let Some((warm, block)) = &warm {
+            info!(
+                snapshot.height = warm.snapshot.height(),
+                snapshot.pools = block.states.len(),
+                "💾 Resuming from block snapshot of previous run"
+            );
+            last_snapshot_height = Some(block.height);
+        }
+        let snapshot_writer = snapshot
+            .as_ref()
+            .map(|settings| SnapshotWriter::spawn(settings.path.clone()));
+        let mut tracked_balances = TrackedBalances::default();
+
+        let mut endpoint_idx = 0;
+        let mut protocol_stream = stream_settings
+            .connect(
+                &chain,
+                &mut endpoint_idx,
+                warm.as_ref().map(|(warm, _)| warm),
+            )
             .await
             .wrap_err("Failed building protocol stream")?;
+        let mut consecutive_failures = 0;
+        // a freshly connected stream starts over from a full snapshot, unless it resumes from the
+        // block of a snapshot that its updates then apply to
+        let mut fresh_stream = true;
+        if let Some((_, block)) = warm {
+            fresh_stream = false;
+            block_tx.send_replace(Arc::new(Some(block)));
+        }
+        let mut message_rate = MessageRate::default();
+        // keeps the rate gauge falling while the stream is silent
+        let mut message_rate_ticks = tokio::time::interval(MESSAGE_RATE_REFRESH);
 
         info!(
             chain.name = ?chain.name,
@@ -120,11 +280,61 @@ impl Worker {
                     break Ok(())
                 }
 
-                Some(message_result) = protocol_stream.next() => {
-                    let block_update = match message_result {
-                        Ok(msg) => msg,
-                        Err(e) => {
-                            error!("Failed to receive message: {}", e);
+                _ = message_rate_ticks.tick() => {
+                    metrics::gauge!("kuma_collector_stream_messages_per_second", "chain" => chain.to_string())
+                        .set(message_rate.per_second(Instant::now()));
+                }
+
+                message = protocol_stream.next() => {
+                    if message.is_some() {
+                        message_rate.record(Instant::now());
+                    }
+                    let block_update = match message {
+                        Some(Ok(msg)) => {
+                            consecutive_failures = 0;
+                            msg
+                        }
+                        Some(Err(e)) => {
+                            consecutive_failures += 1;
+                            metrics::counter!("kuma_collector_stream_failures_total", "chain" => chain.to_string()).increment(1);
+                            error!(consecutive_failures, "Failed to receive message: {}", e);
+                            if consecutive_failures >= stream_settings.max_failures {
+                                warn!(
+                                    tycho.url = %stream_settings.endpoints[endpoint_idx].url,
+                                    "Too many consecutive stream failures, rotating tycho endpoint"
+                                );
+                                protocol_stream = match stream_settings
+                                    .rotate(&chain, &mut endpoint_idx, &self.shutdown_token)
+                                    .await
+                                {
+                                    Some(stream) => stream,
+                                    None => {
+                                        info!("tycho collector received shutdown signal");
+                                        break Ok(());
+                                    }
+                                };
+                                consecutive_failures = 0;
+                                fresh_stream = true;
+                            }
+                            continue;
+                        }
+                        None => {
+                            warn!(
+                                tycho.url = %stream_settings.endpoints[endpoint_idx].url,
+                                "Protocol stream ended, rotating tycho endpoint"
+                            );
+                            protocol_stream = match stream_settings
+                                .rotate(&chain, &mut endpoint_idx, &self.shutdown_token)
+                                .await
+                            {
+                                Some(stream) => stream,
+                                None => {
+                                    info!("tycho collector received shutdown signal");
+                                    break Ok(());
+                                }
+                            };
+                            consecutive_failures = 0;
+                            fresh_stream = true;
                             continue;
                         }
                     };
@@ -133,24 +343,173 @@ impl Worker {
                         block.height = ?block_update.block_number_or_timestamp,
                         "🎁 Received block update"
                     );
-                    let block = {
-                        if let Some(old_block) = block_tx.borrow().as_ref().clone() {
+                    last_update_tx.send_replace(Some(Instant::now()));
+                    metrics::counter!("kuma_collector_blocks_total", "chain" => chain.to_string()).increment(1);
+                    metrics::gauge!("kuma_collector_block_height", "chain" => chain.to_string())
+                        .set(block_update.block_number_or_timestamp as f64);
+                    let old_block = if fresh_stream {
+                        fresh_stream = false;
+                        None
+                    } else {
+                        block_tx.borrow().as_ref().clone()
+                    };
+                    let mut block = {
+                        if let Some(old_block) = old_block {
                             let new_block = old_block.apply_update(block_update);
                             trace!(
                                 block.number = new_block.height,
                                 "Applied block update from Tycho Simulation stream."
                             );
+                            record_pool_set_changes(&chain, &new_block);
 
-                            Some(new_block)
+                            new_block
                         } else {
                             trace!(
                                 block.number = block_update.block_number_or_timestamp,
                                 "Received initial block from Tycho Simulation stream."
                             );
-                            Some(Block::new(block_update))
+                            Block::new(block_update)
+                        }
+                    };
+
+                    // the block's RPC reads go out together, each bounded by the RPC call timeout
+                    let height = block.height;
+                    let (balances, header, (finality, rpc_head)) = tokio::join!(
+                        async {
+                            let account = account?;
+                            Some(tokio::join!(
+                                rpc.call(|provider| async move {
+                                    fetch_native_balance(&provider, account, height).await
+                                }),
+                                tracked_balances.update(&rpc, account, &token_addresses, height),
+                            ))
+                        },
+                        rpc.call(|provider| async move { fetch_header(&provider, height).await }),
+                        async { tokio::join!(fetch_finality(&rpc, height), fetch_rpc_head(&rpc)) },
+                    );
+
+                    if let (Some(account), Some((native_balance, token_balances))) = (account, balances) {
+                        match native_balance {
+                            Ok(balance) => {
+                                trace!(
+                                    block.number = block.height,
+                                    %account,
+                                    native_balance = %balance,
+                                    "Fetched native balance"
+                                );
+                                block.native_balance = Some(balance);
+                            }
+                            Err(e) => {
+                                // keep the previous balance around, it's refreshed on the next block
+                                warn!(
+                                    block.number = block.height,
+                                    %account,
+                                    err = %e,
+                                    "Failed to fetch native balance"
+                                );
+                            }
+                        }
+
+                        match token_balances {
+                            Ok(balances) => {
+                                inventory.update_held(height, &balances);
+                                block.token_balances = Some(balances);
+                            }
+                            Err(e) => {
+                                warn!(
+                                    block.number = block.height,
+                                    %account,
+                                    err = %e,
+                                    "Failed to fetch token balances"
+                                );
+                            }
+                        }
+                    }
+
+                    match header {
+                        Ok(header) => {
+                            trace!(
+                                block.number = header.height,
+                                base_fee_per_gas = ?header.base_fee_per_gas,
+                                blob_base_fee = ?header.blob_base_fee,
+                                "Fetched block header"
+                            );
+                            metrics::gauge!("kuma_collector_stream_lag_seconds", "chain" => chain.to_string())
+                                .set(stream_lag(&header).as_secs_f64());
+                            if let Some(repo) = &blocks {
+                                let record = BlockRecord {
+                                    chain: chain.clone(),
+                                    header: header.clone(),
+                                    native_balance: block.native_balance.clone(),
+                                    token_balances: block.token_balances.clone(),
+                                };
+                                let repo = repo.clone();
+                                tokio::spawn(async move {
+                                    if let Err(e) = repo.upsert(&record).await {
+                                        warn!(block.number = record.header.height, err = %e, "Failed to record block");
+                                    }
+                                });
+                            }
+                            if let Some(repo) = &gas_prices {
+                                let repo = repo.clone();
+                                let rpc = rpc.clone();
+                                let chain = chain.clone();
+                                let header = header.clone();
+                                tokio::spawn(async move {
+                                    if let Err(e) = record_gas_price(&repo, &rpc, chain, &header).await {
+                                        warn!(block.number = header.height, err = %e, "Failed to record gas price");
+                                    }
+                                });
+                            }
+                            block.timestamp = Some(header.timestamp);
+                            block.base_fee_per_gas = header.base_fee_per_gas;
+                            header_tx.send_replace(Some(header));
                         }
+                        Err(e) => {
+                            warn!(block.number = block.height, err = %e, "Failed to fetch block header");
+                        }
+                    }
+
+                    if let Some(rpc_head) = rpc_head {
+                        // negative while the RPC lags behind tycho
+                        metrics::gauge!("kuma_collector_rpc_head_lag_blocks", "chain" => chain.to_string())
+                            .set(rpc_head as f64 - block.height as f64);
+                    }
+                    trace!(
+                        block.number = block.height,
+                        safe = ?finality.safe,
+                        finalized = ?finality.finalized,
+                        "Fetched finality heads"
+                    );
+                    // tags the RPC failed to serve keep their last known height
+                    block.finality = FinalityHeads {
+                        latest: block.height,
+                        safe: finality.safe.or(block.finality.safe),
+                        finalized: finality.finalized.or(block.finality.finalized),
                     };
-                    let send_res = block_tx.send(Arc::new(block));
+                    finality_tx.send_replace(block.finality);
+
+                    if let (Some(settings), Some(writer), Some(tycho_states)) =
+                        (&snapshot, &snapshot_writer, &stream_settings.tycho_states)
+                    {
+                        let due = last_snapshot_height.is_none_or(|last: u64| {
+                            block.height.saturating_sub(last) >= settings.interval_blocks
+                        });
+                        if due {
+                            // the stream applied this block's messages to the states last
+                            let snapshot = BlockSnapshot::from_tycho_states(chain.name, &tycho_states.lock().unwrap())
+                                .filter(|snapshot| snapshot.height() == block.height);
+                            match snapshot {
+                                Some(snapshot) => {
+                                    last_snapshot_height = Some(block.height);
+                                    writer.write(snapshot);
+                                }
+                                None => warn!(block.height = block.height, "Tycho states aren't at the block, skipped its snapshot"),
+                            }
+                        }
+                    }
+
+                    let send_res = block_tx.send(Arc::new(Some(block)));
                     if let Err(e) = send_res {
                         // TODO: handle send_res more
                         error!(err = %e, "Failed to receive block update from Tycho Simulation stream.");
@@ -160,3 +519,357 @@ impl Worker {
         }
     }
 }
+
+/// Logs and counts the pools a block update started tracking, stopped tracking or changed the
+/// metadata of.
+fn record_pool_set_changes(chain: &Chain, block: &Block) {
+    metrics::gauge!("kuma_collector_tracked_pools", "chain" => chain.to_string())
+        .set(block.metadata.len() as f64);
+
+    let changes = [
+        ("added", block.added_pools.len()),
+        ("removed", block.removed_pools.len()),
+        ("metadata", block.updated_metadata.len()),
+    ];
+    if changes.iter().all(|(_, count)| *count == 0) {
+        return;
+    }
+    for (change, count) in changes {
+        metrics::counter!("kuma_collector_pool_set_changes_total", "chain" => chain.to_string(), "change" => change)
+            .increment(count as u64);
+    }
+
+    info!(
+        block.number = block.height,
+        pools.added = block.added_pools.len(),
+        pools.removed = block.removed_pools.len(),
+        pools.metadata_updated = block.updated_metadata.len(),
+        pools.tracked = block.metadata.len(),
+        "Tracked pool set changed"
+    );
+}
+
+/// Balances of an account as of one block.
+#[derive(Debug, Clone)]
+pub struct AccountBalances {
+    pub height: u64,
+    /// Balance of the chain's native asset
+    pub native: BigUint,
+    pub tokens: TokenBalances,
+}
+
+/// Fetches `account`'s native and `tokens` balances at the latest block of the RPC's chain.
+pub async fn fetch_account_balances(
+    rpc: &RpcEndpoints,
+    account: Address,
+    tokens: &[Address],
+) -> eyre::Result<AccountBalances> {
+    let height = rpc
+        .call(|provider| async move {
+            provider
+                .get_block_number()
+                .await
+                .wrap_err("eth_blockNumber failed")
+        })
+        .await?;
+    let (native, tokens) = tokio::try_join!(
+        rpc.call(|provider| async move { fetch_native_balance(&provider, account, height).await }),
+        rpc.call(|provider| async move {
+            fetch_token_balances(&provider, account, tokens, height).await
+        }),
+    )?;
+
+    Ok(AccountBalances {
+        height,
+        native,
+        tokens,
+    })
+}
+
+async fn fetch_native_balance(
+    provider: &DynProvider,
+    account: Address,
+    height: u64,
+) -> eyre::Result<BigUint> {
+    let balance = provider
+        .get_balance(account)
+        .number(height)
+        .await
+        .wrap_err("eth_getBalance failed")?;
+    Ok(u256_to_biguint(balance))
+}
+
+async fn record_gas_price(
+    repo: &GasPriceRepository,
+    rpc: &RpcEndpoints,
+    chain: Chain,
+    header: &BlockHeader,
+) -> eyre::Result<()> {
+    // chains without a fee market don't serve priority fee estimates
+    let priority_fee = rpc
+        .call(|provider| async move {
+            provider
+                .get_max_priority_fee_per_gas()
+                .await
+                .wrap_err("eth_maxPriorityFeePerGas failed")
+        })
+        .await;
+    let priority_fee_per_gas = match priority_fee {
+        Ok(fee) => Some(u64::try_from(fee).wrap_err("priority fee doesn't fit in a u64")?),
+        Err(e) => {
+            trace!(err = %e, "Failed to fetch priority fee estimate");
+            None
+        }
+    };
+
+    repo.upsert(&GasPrice {
+        chain,
+        height: header.height,
+        timestamp: header.timestamp,
+        base_fee_per_gas: header.base_fee_per_gas,
+        priority_fee_per_gas,
+    })
+    .await
+}
+
+/// Blocks between reads of the account's token balances, which are otherwise kept up to date from
+/// its transfers. Bounds the drift from balance changes that don't log a transfer, like rebases.
+const BALANCE_READ_INTERVAL: u64 = 100;
+
+/// The account's token balances as of the last block, replayed from its `Transfer` logs rather
+/// than read with a `balanceOf` call per token on every block.
+#[derive(Debug, Default)]
+struct TrackedBalances {
+    /// Block the balances were last read at
+    read_at: Option<u64>,
+    latest: Option<(u64, TokenBalances)>,
+}
+
+impl TrackedBalances {
+    /// Brings the balances to `height` by applying the transfers since the previous block. They're
+    /// read instead on the first block, after a reorg or a failed update, and every
+    /// [`BALANCE_READ_INTERVAL`] blocks.
+    async fn update(
+        &mut self,
+        rpc: &RpcEndpoints,
+        account: Address,
+        tokens: &[Address],
+        height: u64,
+    ) -> eyre::Result<TokenBalances> {
+        // a filter without addresses would match every contract's transfers
+        if tokens.is_empty() {
+            return Ok(TokenBalances::default());
+        }
+
+        let replayable = match (self.read_at, self.latest.take()) {
+            (Some(read_at), Some((last, balances)))
+                if last < height && height - read_at < BALANCE_READ_INTERVAL =>
+            {
+                Some((last, balances))
+            }
+            _ => None,
+        };
+
+        if let Some((last, mut balances)) = replayable {
+            let applied = rpc
+                .call(|provider| async move {
+                    fetch_transfer_logs(&provider, account, tokens, last + 1, height).await
+                })
+                .await
+                .and_then(|(incoming, outgoing)| {
+                    balances.update_token_balances(account, &incoming, &outgoing)
+                });
+            match applied {
+                Ok(()) => {
+                    self.latest = Some((height, balances.clone()));
+                    return Ok(balances);
+                }
+                Err(e) => {
+                    warn!(
+                        block.number = height,
+                        %account,
+                        err = %e,
+                        "Failed to apply transfer logs, reading token balances instead"
+                    );
+                }
+            }
+        }
+
+        let balances = rpc
+            .call(|provider| async move {
+                fetch_token_balances(&provider, account, tokens, height).await
+            })
+            .await?;
+        self.read_at = Some(height);
+        self.latest = Some((height, balances.clone()));
+        Ok(balances)
+    }
+}
+
+/// Fetches the `Transfer` logs of `tokens` into and out of `account` over blocks `from..=to`.
+async fn fetch_transfer_logs(
+    provider: &DynProvider,
+    account: Address,
+    tokens: &[Address],
+    from: u64,
+    to: u64,
+) -> eyre::Result<(Vec<Log>, Vec<Log>)> {
+    let (incoming, outgoing) = transfer_filters(account, tokens, from, to);
+    tokio::try_join!(
+        async {
+            provider
+                .get_logs(&incoming)
+                .await
+                .wrap_err("eth_getLogs failed for incoming transfers")
+        },
+        async {
+            provider
+                .get_logs(&outgoing)
+                .await
+                .wrap_err("eth_getLogs failed for outgoing transfers")
+        },
+    )
+}
+
+async fn fetch_token_balances(
+    provider: &DynProvider,
+    account: Address,
+    tokens: &[Address],
+    height: u64,
+) -> eyre::Result<TokenBalances> {
+    let balances = futures::future::try_join_all(tokens.iter().map(|&token| async move {
+        let balance = IERC20::new(token, provider)
+            .balanceOf(account)
+            .block(height.into())
+            .call()
+            .await
+            .wrap_err_with(|| format!("balanceOf failed for {token}"))?;
+        eyre::Ok((token, u256_to_biguint(balance)))
+    }))
+    .await?;
+
+    Ok(TokenBalances::new(balances.into_iter().collect()))
+}
+
+/// Safe and finalized heads, leaving out tags the RPC fails to serve.
+async fn fetch_finality(rpc: &RpcEndpoints, latest: u64) -> FinalityHeads {
+    let fetch_tag = |tag: BlockNumberOrTag| async move {
+        let block = rpc
+            .call(|provider| async move {
+                provider
+                    .get_block_by_number(tag)
+                    .await
+                    .wrap_err("eth_getBlockByNumber failed")
+            })
+            .await;
+        match block {
+            Ok(block) => block.map(|block| block.header.number),
+            Err(e) => {
+                trace!(%tag, err = %e, "Failed to fetch tagged block");
+                None
+            }
+        }
+    };
+
+    let (safe, finalized) = tokio::join!(
+        fetch_tag(BlockNumberOrTag::Safe),
+        fetch_tag(BlockNumberOrTag::Finalized)
+    );
+
+    FinalityHeads {
+        latest,
+        safe,
+        finalized,
+    }
+}
+
+/// Latest block number of the RPC, to compare the stream's height against.
+async fn fetch_rpc_head(rpc: &RpcEndpoints) -> Option<u64> {
+    let head = rpc
+        .call(|provider| async move {
+            provider
+                .get_block_number()
+                .await
+                .wrap_err("eth_blockNumber failed")
+        })
+        .await;
+    match head {
+        Ok(head) => Some(head),
+        Err(e) => {
+            trace!(err = %e, "Failed to fetch RPC head");
+            None
+        }
+    }
+}
+
+/// Window the protocol stream's message rate is averaged over
+const MESSAGE_RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Interval at which the message rate gauge is refreshed between messages
+const MESSAGE_RATE_REFRESH: Duration = Duration::from_secs(5);
+
+/// Protocol stream messages received over the last [`MESSAGE_RATE_WINDOW`].
+#[derive(Debug, Default)]
+struct MessageRate {
+    received: VecDeque<Instant>,
+}
+
+impl MessageRate {
+    fn record(&mut self, now: Instant) {
+        self.received.push_back(now);
+        self.expire(now);
+    }
+
+    /// Messages per second, averaged over the window.
+    fn per_second(&mut self, now: Instant) -> f64 {
+        self.expire(now);
+        self.received.len() as f64 / MESSAGE_RATE_WINDOW.as_secs_f64()
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while self
+            .received
+            .front()
+            .is_some_and(|&at| now.duration_since(at) > MESSAGE_RATE_WINDOW)
+        {
+            self.received.pop_front();
+        }
+    }
+}
+
+/// How far behind the wall clock `header` was received, going by its timestamp.
+fn stream_lag(header: &BlockHeader) -> Duration {
+    let block_time = UNIX_EPOCH + Duration::from_secs(header.timestamp);
+    SystemTime::now()
+        .duration_since(block_time)
+        .unwrap_or_default()
+}
+
+async fn fetch_header(provider: &DynProvider, height: u64) -> eyre::Result<BlockHeader> {
+    let block = provider
+        .get_block_by_number(height.into())
+        .await
+        .wrap_err("eth_getBlockByNumber failed")?
+        .ok_or_eyre("block not found")?;
+
+    // only query the blob base fee on chains that have blobs
+    let blob_base_fee = if block.header.excess_blob_gas.is_some() {
+        match provider.get_blob_base_fee().await {
+            Ok(fee) => Some(fee),
+            Err(e) => {
+                trace!(err = %e, "Failed to fetch blob base fee");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    Ok(BlockHeader {
+        height,
+        hash: block.header.hash,
+        timestamp: block.header.timestamp,
+        base_fee_per_gas: block.header.base_fee_per_gas,
+        blob_base_fee,
+    })
+}
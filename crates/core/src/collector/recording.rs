@@ -0,0 +1,236 @@
+//! Raw Tycho messages recorded by collectors, to replay a session offline.
+//!
+//! Simulation states are `dyn ProtocolSim` trait objects and can't be serialized, so a recording
+//! collector decodes the stream itself and appends every message as Tycho sent it, one JSON line
+//! per message, before decoding it. A [`Replay`] decodes the lines again with the same decoders,
+//! yielding the updates the collector built its blocks from.
+//!
+//! A collector taking block snapshots decodes the stream itself the same way, to follow the
+//! component states Tycho sends.
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::eyre::{self, WrapErr as _, eyre};
+use futures::{StreamExt as _, future, stream::BoxStream};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncBufReadExt as _, AsyncWriteExt as _, BufReader, Lines},
+    sync::Mutex,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::warn;
+use tycho_common::{Bytes, models as tycho_models, models::token::Token};
+use tycho_simulation::{
+    evm::{
+        decoder::TychoStreamDecoder,
+        protocol::{
+            pancakeswap_v2::state::PancakeswapV2State, uniswap_v2::state::UniswapV2State,
+            uniswap_v3::state::UniswapV3State,
+        },
+    },
+    protocol::models::Update,
+    tycho_client::{
+        feed::{BlockHeader as TychoBlockHeader, FeedMessage},
+        stream::TychoStreamBuilder,
+    },
+};
+
+use super::builder::{SUPPORTED_PROTOCOLS, StreamSettings, TychoEndpoint, WarmStart};
+use crate::state::{pair_snapshot::TychoStates, snapshot::Resume};
+
+/// A Tycho message and when the collector received it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedMessage {
+    /// Milliseconds since the unix epoch
+    pub received_at_ms: u64,
+    pub message: FeedMessage<TychoBlockHeader>,
+}
+
+/// Default location of a chain's recording inside `dir`.
+pub fn path_in(dir: &Path, chain: tycho_models::Chain) -> PathBuf {
+    dir.join(format!("{chain}.jsonl"))
+}
+
+/// Decoder for the collector's `protocols`, which must be in [`SUPPORTED_PROTOCOLS`].
+pub async fn decoder(
+    protocols: &[String],
+    tokens: HashMap<Bytes, Token>,
+) -> eyre::Result<TychoStreamDecoder<TychoBlockHeader>> {
+    let mut decoder = TychoStreamDecoder::new();
+    for protocol in protocols {
+        match protocol.as_str() {
+            "uniswap_v2" | "sushiswap_v2" => decoder.register_decoder::<UniswapV2State>(protocol),
+            "pancakeswap_v2" => decoder.register_decoder::<PancakeswapV2State>(protocol),
+            "uniswap_v3" | "pancakeswap_v3" => decoder.register_decoder::<UniswapV3State>(protocol),
+            _ => {
+                return Err(eyre!(
+                    "unsupported protocol {protocol}, expected one of {SUPPORTED_PROTOCOLS:?}"
+                ));
+            }
+        }
+    }
+    decoder.skip_state_decode_failures(true);
+    decoder.set_tokens(tokens).await;
+
+    Ok(decoder)
+}
+
+/// Appends recorded messages to a chain's recording.
+struct Recorder {
+    path: PathBuf,
+    file: File,
+}
+
+impl Recorder {
+    async fn open(path: PathBuf) -> eyre::Result<Self> {
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .wrap_err_with(|| format!("failed to create recording dir {}", dir.display()))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .wrap_err_with(|| format!("failed to open {}", path.display()))?;
+
+        Ok(Self { path, file })
+    }
+
+    async fn write(&mut self, message: &FeedMessage<TychoBlockHeader>) -> eyre::Result<()> {
+        let received_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .wrap_err("system clock is before the unix epoch")?
+            .as_millis() as u64;
+        let mut line = serde_json::to_vec(&RecordedMessage {
+            received_at_ms,
+            message: message.clone(),
+        })
+        .wrap_err("failed to serialize tycho message")?;
+        line.push(b'\n');
+
+        self.file
+            .write_all(&line)
+            .await
+            .wrap_err_with(|| format!("failed to append to {}", self.path.display()))
+    }
+}
+
+/// Connects to `endpoint` like a protocol stream does, recording every message before it's
+/// decoded and following the component states per `settings`.
+///
+/// Resuming from `warm`, the stream decodes with its seeded decoder and skips the messages its
+/// snapshot already holds.
+pub(super) async fn connect_raw(
+    settings: &StreamSettings,
+    chain: tycho_models::Chain,
+    endpoint: &TychoEndpoint,
+    warm: Option<&WarmStart>,
+) -> eyre::Result<BoxStream<'static, eyre::Result<Update>>> {
+    let TychoEndpoint { url, api_key } = endpoint;
+    let mut feed = TychoStreamBuilder::new(url, chain).auth_key(Some(api_key.clone()));
+    for protocol in &settings.protocols {
+        feed = feed.exchange(protocol, settings.tvl_filter.clone());
+    }
+    let decoder = match warm {
+        Some(warm) => Arc::clone(&warm.decoder),
+        None => Arc::new(decoder(&settings.protocols, settings.tokens.clone()).await?),
+    };
+    let recorder = match &settings.record {
+        Some(path) => Some(Arc::new(Mutex::new(Recorder::open(path.clone()).await?))),
+        None => None,
+    };
+    let mut resume = warm.map(|warm| Resume::new(Arc::clone(&warm.snapshot)));
+    let tycho_states = settings.tycho_states.clone();
+    if let (Some(tycho_states), None) = (&tycho_states, warm) {
+        // a fresh stream starts over from a full snapshot, without the components Tycho dropped
+        *tycho_states.lock().unwrap() = TychoStates::default();
+    }
+
+    let (_, messages) = feed
+        .build()
+        .await
+        .map_err(|e| eyre!("failed building tycho stream: {e}"))?;
+
+    Ok(ReceiverStream::new(messages)
+        .then(move |message| {
+            let recorder = recorder.clone();
+            async move {
+                // a message that fails to record is still used live
+                if let Some(recorder) = recorder {
+                    if let Err(e) = recorder.lock().await.write(&message).await {
+                        warn!(err = %e, "Failed to record tycho message");
+                    }
+                }
+                message
+            }
+        })
+        .filter_map(move |mut message| {
+            let admitted = resume
+                .as_mut()
+                .is_none_or(|resume| resume.admit(&mut message));
+            future::ready(admitted.then_some(message))
+        })
+        .then(move |message| {
+            let decoder = Arc::clone(&decoder);
+            if let Some(tycho_states) = &tycho_states {
+                tycho_states.lock().unwrap().apply(&message);
+            }
+            async move { decoder.decode(message).await.map_err(|e| eyre!("{e}")) }
+        })
+        .boxed())
+}
+
+/// Decodes a chain's recording message by message.
+pub struct Replay {
+    path: PathBuf,
+    lines: Lines<BufReader<File>>,
+    decoder: TychoStreamDecoder<TychoBlockHeader>,
+}
+
+impl Replay {
+    /// Opens the recording at `path`, decoding the `protocols` the chain's collector subscribed to.
+    pub async fn open(
+        path: PathBuf,
+        protocols: &[String],
+        tokens: HashMap<Bytes, Token>,
+    ) -> eyre::Result<Self> {
+        let file = File::open(&path)
+            .await
+            .wrap_err_with(|| format!("failed to open recording {}", path.display()))?;
+
+        Ok(Self {
+            lines: BufReader::new(file).lines(),
+            decoder: decoder(protocols, tokens).await?,
+            path,
+        })
+    }
+
+    /// The next update and when it was received in milliseconds since the unix epoch, `None` at
+    /// the end of the recording.
+    pub async fn next(&mut self) -> eyre::Result<Option<(u64, Update)>> {
+        let Some(line) = self
+            .lines
+            .next_line()
+            .await
+            .wrap_err_with(|| format!("failed to read {}", self.path.display()))?
+        else {
+            return Ok(None);
+        };
+        let recorded: RecordedMessage = serde_json::from_str(&line)
+            .wrap_err_with(|| format!("failed to parse a message of {}", self.path.display()))?;
+        let update = self
+            .decoder
+            .decode(recorded.message)
+            .await
+            .map_err(|e| eyre!("failed to decode a message of {}: {e}", self.path.display()))?;
+
+        Ok(Some((recorded.received_at_ms, update)))
+    }
+}
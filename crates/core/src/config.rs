@@ -1,20 +1,49 @@
-use crate::{chain::Chain, state::pair::Pair};
-use color_eyre::eyre::{self, Context as _, OptionExt as _, eyre};
+use crate::{
+    chain::{Chain, ChainRegistry, GasToken},
+    collector::{SnapshotSettings, TychoEndpoint, recording},
+    state::{
+        PoolFilter,
+        header::Confirmation,
+        pair::{Pair, PairStateDelivery},
+        snapshot::BlockSnapshot,
+    },
+};
+use alloy::{primitives::Address, signers::local::PrivateKeySigner};
+use color_eyre::eyre::{self, Context as _, eyre};
 use figment::{
     Figment,
-    providers::{Env, Format as _, Yaml},
+    providers::{Env, Format as _, Json, Toml, Yaml},
 };
 use num_bigint::BigUint;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr as _,
+    time::Duration,
+};
 use tracing::{info, warn};
 use tycho_common::{Bytes, models::token::Token};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+pub use amount::TokenAmount;
+pub use secrets::SecretsConfig;
+pub use validate::{ValidationError, ValidationErrors};
+
+mod amount;
+mod secrets;
+mod validate;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     /// Database configuration
     pub database: DatabaseConfig,
 
+    /// Where `kumad` writes spot prices and signals, the database by default
+    #[serde(default)]
+    pub store: StoreConfig,
+
     /// Server configuration
     pub server: ServerConfig,
 
@@ -36,17 +65,126 @@ pub struct Config {
     /// Threshold for removing TVL from the system
     pub remove_tvl_threshold: f64,
 
-    /// Congestion risk discount factor (0.0 - 1.0)
+    /// Congestion risk discount factor (0.0 - 1.0), unless a strategy overrides it
     pub congestion_risk_discount_bps: u64,
 
-    /// Maximum acceptable slippage percentage
+    /// Maximum acceptable slippage percentage, unless a strategy overrides it
     pub max_slippage_bps: u64,
 
-    /// Number of binary search steps
+    /// Number of binary search steps, unless a strategy overrides it
     pub binary_search_steps: usize,
 
-    /// Private key for signing transactions
+    /// Maximum time without a block update before a chain's state is considered stale
+    #[serde(default = "default_max_block_staleness_secs")]
+    pub max_block_staleness_secs: u64,
+
+    /// Warn when acting on a block takes longer than this fraction of its chain's block time:
+    /// precomputing on a slow block, or emitting a signal from a fast block
+    #[serde(default = "default_max_block_latency_fraction")]
+    pub max_block_latency_fraction: f64,
+
+    /// Skip signal generation on a fast chain state received more than this many fast chain block
+    /// times ago
+    #[serde(default = "default_max_fast_state_age_blocks")]
+    pub max_fast_state_age_blocks: u32,
+
+    /// How strategies get the states of their pairs: `latest` only, skipping the blocks a strategy
+    /// was too busy to read, or `{queue: <n>}` states in order, dropping the oldest past `n`
+    #[serde(default)]
+    pub pair_state_delivery: PairStateDelivery,
+
+    /// Reject signals whose slow and fast blocks arrived more than this many milliseconds apart.
+    /// Disabled when unset.
+    #[serde(default)]
+    pub max_block_skew_ms: Option<u64>,
+
+    /// Reject signals whose simulated spot price deviates more than this many bps from the
+    /// pool's on-chain state. Disabled when unset.
+    #[serde(default)]
+    pub max_onchain_deviation_bps: Option<u64>,
+
+    /// Drop signals expected to profit less than this many bps of the slow swap's amount in,
+    /// unless changed through the admin socket. Disabled when unset.
+    #[serde(default)]
+    pub min_profit_bps: Option<u64>,
+
+    /// Directory collectors persist their latest block snapshot to and resume from on restart.
+    /// Disabled when unset.
+    #[serde(default)]
+    pub snapshot_dir: Option<PathBuf>,
+
+    /// Number of blocks between two snapshot writes
+    #[serde(default = "default_snapshot_interval_blocks")]
+    pub snapshot_interval_blocks: u64,
+
+    /// Directory collectors append the raw Tycho messages they receive to, for `kuma replay`.
+    /// Disabled when unset.
+    #[serde(default)]
+    pub record_dir: Option<PathBuf>,
+
+    /// Store the amount in to amount out curves of every slow block's precompute, for
+    /// `kuma export --dataset precompute_curves`. Needs the postgres store.
+    #[serde(default)]
+    pub record_precompute_curves: bool,
+
+    /// Binance markets to track, disabled when unset
+    #[serde(default)]
+    pub binance: Option<CexConfig>,
+
+    /// OKX markets (and balances, with credentials) to track, disabled when unset
+    #[serde(default)]
+    pub okx: Option<OkxConfig>,
+
+    /// Coinbase Advanced Trade markets (and balances, with a CDP key) to track, disabled when unset
+    #[serde(default)]
+    pub coinbase: Option<CoinbaseConfig>,
+
+    /// Serve metrics for Prometheus to scrape, disabled when unset
+    #[serde(default)]
+    pub metrics: Option<MetricsConfig>,
+
+    /// Log output of every binary
+    #[serde(default)]
+    pub log: LogConfig,
+
+    /// Slack and Telegram alerts about signals and failures, disabled when unset
+    #[serde(default)]
+    pub alerts: Option<AlertsConfig>,
+
+    /// Report panics and error events to Sentry, disabled when unset
+    #[serde(default)]
+    pub sentry: Option<SentryConfig>,
+
+    /// Consecutive Tycho stream errors tolerated before failing over to the next endpoint
+    #[serde(default = "default_max_tycho_stream_failures")]
+    pub max_tycho_stream_failures: u32,
+
+    /// Private key for signing transactions on the chains without a `keystore`
+    #[serde(default)]
     pub private_key: String,
+
+    /// Password the chains' `keystore` files are encrypted with
+    #[serde(default)]
+    pub keystore_password: Option<String>,
+
+    /// Directory `${secret:NAME}` references in secret fields are read from
+    #[serde(default)]
+    pub secrets: Option<SecretsConfig>,
+
+    /// Unix socket `kumad` serves its admin interface on, for `kuma admin` and the backend's
+    /// `/admin/daemon` routes. Disabled when unset.
+    #[serde(default)]
+    pub admin_socket: Option<PathBuf>,
+
+    /// File `kumad` writes its process id to while it runs, removed on shutdown. Disabled when
+    /// unset.
+    #[serde(default)]
+    pub pid_file: Option<PathBuf>,
+
+    /// Tell systemd when `kumad` is ready and ping its watchdog while every worker makes progress,
+    /// disabled when unset
+    #[serde(default)]
+    pub systemd: Option<SystemdConfig>,
 }
 
 pub type AddressForToken = HashMap<tycho_common::Bytes, Token>;
@@ -57,51 +195,306 @@ pub type InventoriesForChain = HashMap<Chain, InventoryForToken>;
 
 pub type PairForChain = HashMap<Chain, Pair>;
 
+/// Environment variable holding the path of the config file, whose extension decides its format
+pub const CONFIG_PATH_ENV: &str = "KUMA_CONFIG";
+
+/// Files the configuration is looked up in when [`CONFIG_PATH_ENV`] isn't set, relative to the
+/// working directory and in order
+pub const CONFIG_FILES: &[&str] = &["kuma.yaml", "kuma.yml", "kuma.toml", "kuma.json"];
+
+/// Path of the config file: [`CONFIG_PATH_ENV`] if set, the first of [`CONFIG_FILES`] that exists
+/// otherwise.
+pub fn config_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(CONFIG_PATH_ENV) {
+        return PathBuf::from(path);
+    }
+
+    CONFIG_FILES
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from(CONFIG_FILES[0]))
+}
+
+/// Trading parameters of a strategy, its overrides applied on top of the global ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrategyParams {
+    pub binary_search_steps: usize,
+    pub max_slippage_bps: u64,
+    pub congestion_risk_discount_bps: u64,
+}
+
+/// Settings that running strategy workers pick up when the config file changes, without a
+/// restart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiveSettings {
+    pub max_slippage_bps: u64,
+    pub congestion_risk_discount_bps: u64,
+    pub max_block_staleness: Duration,
+}
+
+fn default_max_block_staleness_secs() -> u64 {
+    60
+}
+
+fn default_max_block_latency_fraction() -> f64 {
+    0.5
+}
+
+fn default_max_fast_state_age_blocks() -> u32 {
+    3
+}
+
+fn default_snapshot_interval_blocks() -> u64 {
+    100
+}
+
+fn default_max_tycho_stream_failures() -> u32 {
+    3
+}
+
 impl Config {
-    /// Load configuration from environment and optional config file
+    /// JSON Schema of the config file, for editors to validate and complete it with
+    pub fn json_schema() -> schemars::Schema {
+        schemars::schema_for!(Config)
+    }
+
+    /// Load configuration from environment and optional config file at [`config_path`]
     pub fn load() -> Result<Self, figment::Error> {
-        let config: Config = Figment::new()
-            .merge(Yaml::file("kuma.yaml"))
-            .merge(Env::prefixed("KUMA_").split("__"))
-            .extract()?;
+        Self::load_from(&config_path())
+    }
+
+    /// Load configuration from environment and optional YAML, TOML or JSON file at `path`,
+    /// resolving `${...}` references to environment variables and secrets
+    pub fn load_from(path: &Path) -> Result<Self, figment::Error> {
+        let file = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => Figment::from(Yaml::file(path)),
+            Some("toml") => Figment::from(Toml::file(path)),
+            Some("json") => Figment::from(Json::file(path)),
+            _ => {
+                return Err(figment::Error::from(format!(
+                    "unsupported config file {}, expected a .yaml, .yml, .toml or .json file",
+                    path.display()
+                )));
+            }
+        };
+        let mut config: Config = file.merge(Env::prefixed("KUMA_").split("__")).extract()?;
+        config.resolve_secrets()?;
 
         Ok(config)
     }
 
-    pub fn build_chains(&self) -> eyre::Result<Vec<Chain>> {
-        self.chains
-            .iter()
-            .map(
-                |ChainConfig {
-                     name,
-                     rpc_url,
-                     tycho_url,
-                     permit2_address,
-                 }| {
-                    Chain::new(name, rpc_url, tycho_url, permit2_address)
-                        .wrap_err("failed to parse chain info")
-                },
+    pub fn max_block_staleness(&self) -> Duration {
+        Duration::from_secs(self.max_block_staleness_secs)
+    }
+
+    pub fn max_block_skew(&self) -> Option<Duration> {
+        self.max_block_skew_ms.map(Duration::from_millis)
+    }
+
+    /// Parameters of `strategy`, falling back to the global ones it doesn't override
+    pub fn strategy_params(&self, strategy: &StrategyConfig) -> StrategyParams {
+        StrategyParams {
+            binary_search_steps: strategy
+                .binary_search_steps
+                .unwrap_or(self.binary_search_steps),
+            max_slippage_bps: strategy.max_slippage_bps.unwrap_or(self.max_slippage_bps),
+            congestion_risk_discount_bps: strategy
+                .congestion_risk_discount_bps
+                .unwrap_or(self.congestion_risk_discount_bps),
+        }
+    }
+
+    pub fn live_settings(&self, strategy: &StrategyConfig) -> LiveSettings {
+        let params = self.strategy_params(strategy);
+        LiveSettings {
+            max_slippage_bps: params.max_slippage_bps,
+            congestion_risk_discount_bps: params.congestion_risk_discount_bps,
+            max_block_staleness: self.max_block_staleness(),
+        }
+    }
+
+    /// Top-level fields that differ in `new`, ignoring the global and per-strategy ones in
+    /// [`LiveSettings`]. Changes to these only take effect after a restart.
+    pub fn restart_required(&self, new: &Config) -> eyre::Result<Vec<String>> {
+        let mut new = new.clone();
+        new.max_slippage_bps = self.max_slippage_bps;
+        new.congestion_risk_discount_bps = self.congestion_risk_discount_bps;
+        new.max_block_staleness_secs = self.max_block_staleness_secs;
+        for (new, current) in new.strategies.iter_mut().zip(&self.strategies) {
+            new.max_slippage_bps = current.max_slippage_bps;
+            new.congestion_risk_discount_bps = current.congestion_risk_discount_bps;
+        }
+
+        let (serde_json::Value::Object(current), serde_json::Value::Object(new)) = (
+            serde_json::to_value(self).wrap_err("failed to serialize current config")?,
+            serde_json::to_value(&new).wrap_err("failed to serialize new config")?,
+        ) else {
+            return Err(eyre!("config didn't serialize to a map"));
+        };
+
+        Ok(current
+            .keys()
+            .chain(new.keys())
+            .filter(|field| current.get(*field) != new.get(*field))
+            .cloned()
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect())
+    }
+
+    /// Address of the account controlled by the configured private key
+    pub fn account_address(&self) -> eyre::Result<Address> {
+        let signer: PrivateKeySigner = self
+            .private_key
+            .parse()
+            .wrap_err("failed to parse private key")?;
+        Ok(signer.address())
+    }
+
+    /// Signer for transactions on `chain`: its decrypted `keystore` if it has one, the
+    /// configured private key otherwise
+    pub fn signer(&self, chain: &Chain) -> eyre::Result<PrivateKeySigner> {
+        let Some(keystore) = self
+            .chain_config(chain)
+            .and_then(|chain_config| chain_config.keystore.as_ref())
+        else {
+            return self
+                .private_key
+                .parse()
+                .wrap_err("failed to parse private key");
+        };
+        let password = self.keystore_password.as_deref().ok_or_else(|| {
+            eyre!(
+                "{} has a keystore but keystore_password isn't set",
+                chain.name
             )
-            .collect::<eyre::Result<Vec<Chain>>>()
+        })?;
+
+        PrivateKeySigner::decrypt_keystore(keystore, password)
+            .wrap_err_with(|| format!("failed to decrypt keystore {}", keystore.display()))
+    }
+
+    /// Resolve every configured chain, applying per-chain overrides on top of the known
+    /// defaults
+    pub fn chain_registry(&self) -> eyre::Result<ChainRegistry> {
+        let chains = self
+            .chains
+            .iter()
+            .map(|chain_config| {
+                chain_config
+                    .build()
+                    .wrap_err_with(|| format!("failed to parse chain {}", chain_config.name))
+            })
+            .collect::<eyre::Result<Vec<Chain>>>()?;
+
+        ChainRegistry::new(chains)
+    }
+
+    /// Snapshot settings for `chain`'s collector, if snapshots are enabled
+    pub fn snapshot_settings(&self, chain: &Chain) -> Option<SnapshotSettings> {
+        self.snapshot_dir.as_ref().map(|dir| SnapshotSettings {
+            path: BlockSnapshot::path_in(dir, chain.name),
+            interval_blocks: self.snapshot_interval_blocks.max(1),
+        })
+    }
+
+    /// File `chain`'s collector records its Tycho messages to, if recording is enabled
+    pub fn record_path(&self, chain: &Chain) -> Option<PathBuf> {
+        self.record_dir
+            .as_ref()
+            .map(|dir| recording::path_in(dir, chain.name))
+    }
+
+    /// Tycho endpoints for `chain` in failover order: the chain's `tycho_url` first, then any
+    /// fallbacks. Endpoints without their own API key use `tycho_api_key`.
+    pub fn tycho_endpoints(&self, chain: &Chain) -> Vec<TychoEndpoint> {
+        let fallbacks = self
+            .chain_config(chain)
+            .map(|chain_config| chain_config.tycho_fallbacks.as_slice())
+            .unwrap_or_default();
+
+        std::iter::once(TychoEndpoint {
+            url: chain.tycho_url.clone(),
+            api_key: self.tycho_api_key.clone(),
+        })
+        .chain(fallbacks.iter().map(|fallback| {
+            TychoEndpoint {
+                url: fallback.url.clone(),
+                api_key: fallback
+                    .api_key
+                    .clone()
+                    .unwrap_or_else(|| self.tycho_api_key.clone()),
+            }
+        }))
+        .collect()
+    }
+
+    /// Pending transaction watcher configured for `chain`
+    pub fn mempool(&self, chain: &Chain) -> Option<&MempoolConfig> {
+        self.chain_config(chain)
+            .and_then(|chain_config| chain_config.mempool.as_ref())
+    }
+
+    /// Least gas token balance, in its smallest unit, the account must hold on `chain`, `None`
+    /// when unchecked
+    pub fn min_gas_balance(&self, chain: &Chain) -> eyre::Result<Option<BigUint>> {
+        self.chain_config(chain)
+            .and_then(|chain_config| chain_config.min_gas_balance.as_ref())
+            .map(|amount| {
+                amount
+                    .to_units(chain.gas_token().decimals)
+                    .map_err(|e| eyre!("invalid min_gas_balance for {chain}: {e}"))
+            })
+            .transpose()
+    }
+
+    /// Pool allowlist/denylist configured for `chain`
+    pub fn pool_filter(&self, chain: &Chain) -> PoolFilter {
+        self.chain_config(chain)
+            .map(|chain_config| {
+                PoolFilter::new(&chain_config.pool_allowlist, &chain_config.pool_denylist)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Protocols configured for `chain`, empty if the chain's defaults should be used
+    pub fn protocols(&self, chain: &Chain) -> Vec<String> {
+        self.chain_config(chain)
+            .map(|chain_config| chain_config.protocols.clone())
+            .unwrap_or_default()
+    }
+
+    fn chain_config(&self, chain: &Chain) -> Option<&ChainConfig> {
+        self.chains.iter().find(|chain_config| {
+            tycho_common::models::Chain::from_str(&chain_config.name)
+                .is_ok_and(|name| name == chain.name)
+        })
     }
+
     /// Parse chain assets from the config, returning tokens and their inventories by chain
     pub fn build_addrs_and_inventory(
         &self,
     ) -> eyre::Result<(TokenAddressesForChain, InventoriesForChain)> {
         let chains = self
-            .build_chains()
-            .expect("Failed to parse chains from config");
+            .chain_registry()
+            .wrap_err("failed to parse chains from config")?;
 
         let mut inventories_by_chain: HashMap<Chain, HashMap<Token, BigUint>> = HashMap::new();
 
         let mut tokens_by_chain = HashMap::new();
-        for chain in chains {
+        for chain in chains.iter().cloned() {
             let mut tokens = HashMap::new();
             for (symbol, token_config) in &self.tokens {
                 let addr = token_config
                     .addresses
                     .get(&chain.name)
-                    .ok_or_eyre("token address for {symbol} on chain {chain.name} not found")?
+                    .ok_or_else(|| {
+                        eyre!(
+                            "token address for {symbol} on chain {} not found",
+                            chain.name
+                        )
+                    })?
                     .clone();
 
                 let token = Token::new(
@@ -114,8 +507,10 @@ impl Config {
                     token_config.quality,
                 );
 
-                let token_inventory =
-                    BigUint::from(token_config.inventory) * 10u128.pow(token.decimals);
+                let token_inventory = token_config
+                    .inventory
+                    .to_units(token.decimals)
+                    .map_err(|e| eyre!("invalid inventory for {symbol}: {e}"))?;
 
                 if let Some(token_inventories) = inventories_by_chain.get_mut(&chain) {
                     match token_inventories.insert(token.clone(), token_inventory) {
@@ -170,9 +565,10 @@ impl Config {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TokenConfig {
     /// Token addresses on different chains
+    #[schemars(with = "HashMap<String, String>")]
     pub addresses: HashMap<tycho_common::models::Chain, Bytes>,
 
     /// Token decimals
@@ -187,33 +583,351 @@ pub struct TokenConfig {
     /// Quality of the token
     pub quality: u32,
 
-    /// Existing inventory for this token
-    pub inventory: u64,
+    /// Existing inventory for this token, in whole tokens, e.g. `1000` or `"1.5"`
+    pub inventory: TokenAmount,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ChainConfig {
     /// Chain name
     pub name: String,
 
-    /// RPC endpoint URL
+    /// RPC endpoint URL, http(s) or ws(s)
     pub rpc_url: String,
 
+    /// RPC endpoints to fail over to, in order of preference, when `rpc_url` is unreachable
+    #[serde(default)]
+    pub rpc_fallbacks: Vec<String>,
+
     /// RPC endpoint URL for Tycho Indexer
     pub tycho_url: String,
 
     /// Address of the Permit2 contract
     pub permit2_address: String,
+
+    /// If non-empty, only these pools are used for this chain
+    #[serde(default)]
+    pub pool_allowlist: Vec<String>,
+
+    /// Pools that are never used for this chain
+    #[serde(default)]
+    pub pool_denylist: Vec<String>,
+
+    /// Tycho protocol systems to simulate on this chain, e.g. `[uniswap_v2, uniswap_v3]`.
+    /// Defaults to every protocol supported on the chain when empty.
+    #[serde(default)]
+    pub protocols: Vec<String>,
+
+    /// Expected chain id, checked against the id known for `name`
+    #[serde(default)]
+    pub chain_id: Option<u64>,
+
+    /// Average block time override, in milliseconds
+    #[serde(default)]
+    pub block_time_ms: Option<u64>,
+
+    /// Gas token override, the chain's native asset by default
+    #[serde(default)]
+    pub gas_token: Option<GasTokenConfig>,
+
+    /// Tycho endpoints to fail over to when `tycho_url` keeps failing
+    #[serde(default)]
+    pub tycho_fallbacks: Vec<TychoEndpointConfig>,
+
+    /// Pending transaction watcher, used when this is a strategy's fast chain
+    #[serde(default)]
+    pub mempool: Option<MempoolConfig>,
+
+    /// Encrypted keystore of the account signing on this chain, used instead of `private_key`
+    #[serde(default)]
+    pub keystore: Option<PathBuf>,
+
+    /// Least gas token balance, in whole tokens, the account must hold for signals to be
+    /// generated and legs submitted on this chain. Unchecked when unset.
+    #[serde(default)]
+    pub min_gas_balance: Option<TokenAmount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GasTokenConfig {
+    pub symbol: String,
+
+    /// Token address, the zero address for the chain's native asset
+    #[serde(default)]
+    pub address: Option<String>,
+
+    #[serde(default = "default_gas_token_decimals")]
+    pub decimals: u32,
 }
-#[derive(Debug, Clone, Serialize, Deserialize)]
+
+fn default_gas_token_decimals() -> u32 {
+    18
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MempoolConfig {
+    /// Websocket RPC endpoint that streams full pending transactions
+    pub ws_url: String,
+
+    /// Smallest competing swap to flag, in units of the strategy's token A
+    #[serde(default)]
+    pub min_swap_size: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CexConfig {
+    /// Markets to track, in the venue's notation, e.g. `ETHUSDC` or `ETH-USDC`
+    pub markets: Vec<String>,
+
+    /// Websocket url, defaults to the venue's public market data stream
+    #[serde(default)]
+    pub ws_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OkxConfig {
+    #[serde(flatten)]
+    pub cex: CexConfig,
+
+    /// API credentials, balances are only tracked when all three are set
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub api_secret: Option<String>,
+    #[serde(default)]
+    pub passphrase: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CoinbaseConfig {
+    #[serde(flatten)]
+    pub cex: CexConfig,
+
+    /// CDP API key name, `organizations/<org id>/apiKeys/<key id>`. Balances are only tracked
+    /// when both it and `api_secret` are set
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// The CDP API key's PEM encoded EC private key
+    #[serde(default)]
+    pub api_secret: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TychoEndpointConfig {
+    pub url: String,
+
+    /// API key for this endpoint, defaults to `tycho_api_key`
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+impl ChainConfig {
+    fn build(&self) -> eyre::Result<Chain> {
+        let mut chain = Chain::new(
+            &self.name,
+            &self.rpc_url,
+            &self.tycho_url,
+            &self.permit2_address,
+        )?
+        .with_rpc_fallbacks(self.rpc_fallbacks.clone());
+
+        if let Some(chain_id) = self.chain_id {
+            if chain_id != chain.chain_id() {
+                return Err(eyre!(
+                    "configured chain id {chain_id} doesn't match {} for {}",
+                    chain.chain_id(),
+                    self.name
+                ));
+            }
+        }
+        if let Some(block_time_ms) = self.block_time_ms {
+            chain = chain.with_block_time(Duration::from_millis(block_time_ms));
+        }
+        if let Some(gas_token) = &self.gas_token {
+            let address = gas_token
+                .address
+                .as_deref()
+                .map(Address::from_str)
+                .transpose()
+                .wrap_err("failed to parse gas token address")?
+                .unwrap_or(Address::ZERO);
+            chain = chain.with_gas_token(GasToken {
+                symbol: gas_token.symbol.clone(),
+                address,
+                decimals: gas_token.decimals,
+            });
+        }
+
+        Ok(chain)
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct StrategyConfig {
     pub token_a: String,
     pub token_b: String,
     pub slow_chain: String,
     pub fast_chain: String,
+
+    /// How settled the slow chain block must be before a signal is emitted, e.g. `safe` or
+    /// `{ depth: 2 }`. Defaults to acting on the latest block.
+    #[serde(default)]
+    pub slow_confirmation: Confirmation,
+
+    /// Overrides the global `binary_search_steps`
+    #[serde(default)]
+    pub binary_search_steps: Option<usize>,
+
+    /// Overrides the global `max_slippage_bps`
+    #[serde(default)]
+    pub max_slippage_bps: Option<u64>,
+
+    /// Overrides the global `congestion_risk_discount_bps`
+    #[serde(default)]
+    pub congestion_risk_discount_bps: Option<u64>,
+}
+
+impl StrategyConfig {
+    /// Identifies the strategy to operators, e.g. `weth-usdc-ethereum-base`
+    pub fn id(&self) -> String {
+        format!(
+            "{}-{}-{}-{}",
+            self.token_a, self.token_b, self.slow_chain, self.fast_chain
+        )
+        .to_lowercase()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct MetricsConfig {
+    /// Address the `/metrics` endpoint listens on
+    #[serde(default = "default_metrics_listen_addr")]
+    #[schemars(with = "String")]
+    pub listen_addr: SocketAddr,
+}
+
+fn default_metrics_listen_addr() -> SocketAddr {
+    SocketAddr::from(([0, 0, 0, 0], 9100))
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct LogConfig {
+    #[serde(default)]
+    pub format: LogFormat,
+
+    /// Level of events not matched by `directives`. `RUST_LOG` replaces both when set.
+    #[serde(default = "default_log_level")]
+    pub level: String,
+
+    /// Levels for specific targets, e.g. `kuma_core::collector=debug`
+    #[serde(default)]
+    pub directives: Vec<String>,
+
+    /// OTLP/HTTP endpoint spans are exported to, e.g. `http://localhost:4318/v1/traces`.
+    /// Disabled when unset.
+    #[serde(default)]
+    pub otlp_endpoint: Option<String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::default(),
+            level: default_log_level(),
+            directives: Vec::new(),
+            otlp_endpoint: None,
+        }
+    }
+}
+
+fn default_log_level() -> String {
+    "info".to_owned()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human readable lines
+    #[default]
+    Pretty,
+    /// One JSON object per event, with the fields of its spans merged in
+    Json,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct AlertsConfig {
+    #[serde(default)]
+    pub slack: Option<SlackConfig>,
+
+    #[serde(default)]
+    pub telegram: Option<TelegramConfig>,
+
+    /// Alert on signals expected to profit at least this much of a token, by symbol, e.g.
+    /// `{ WETH: "0.05" }`
+    #[serde(default)]
+    pub min_signal_profit: HashMap<String, TokenAmount>,
+
+    /// Least time between two alerts of the same kind, later ones are counted and dropped
+    #[serde(default = "default_alert_interval_secs")]
+    pub min_interval_secs: u64,
+}
+
+fn default_alert_interval_secs() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct SlackConfig {
+    /// Incoming webhook url
+    pub webhook_url: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct TelegramConfig {
+    pub bot_token: String,
+    pub chat_id: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct SentryConfig {
+    /// Project DSN events are sent to
+    pub dsn: String,
+
+    /// Environment events are tagged with, e.g. `production`
+    #[serde(default)]
+    pub environment: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct SystemdConfig {
+    /// Stop pinging the watchdog, so systemd restarts `kumad`, once a worker made no progress for
+    /// this long
+    #[serde(default = "default_max_worker_idle_secs")]
+    pub max_worker_idle_secs: u64,
+}
+
+impl SystemdConfig {
+    pub fn max_worker_idle(&self) -> Duration {
+        Duration::from_secs(self.max_worker_idle_secs)
+    }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+fn default_max_worker_idle_secs() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StoreConfig {
+    /// The configured database, which `kumad` also records blocks and gas prices to
+    #[default]
+    Postgres,
+    /// JSON lines files under `dir`, without connecting to the database
+    File { dir: PathBuf },
+    /// Discard spot prices and signals, without connecting to the database
+    Noop,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct DatabaseConfig {
     pub user: String,
     pub password: String,
@@ -223,6 +937,127 @@ pub struct DatabaseConfig {
     pub max_connections: u32,
     pub connection_timeout_secs: u64,
     pub idle_timeout_secs: u64,
+
+    /// Interval between checks that the database is reachable
+    #[serde(default = "default_health_check_interval_secs")]
+    pub health_check_interval_secs: u64,
+
+    /// Apply pending schema migrations when connecting
+    #[serde(default)]
+    pub migrate_on_start: bool,
+
+    /// Store time-series tables as TimescaleDB hypertables, plain Postgres when unset
+    #[serde(default)]
+    pub timescale: Option<TimescaleConfig>,
+
+    /// Buffer spot price and signal writes in memory while the database is unreachable
+    #[serde(default)]
+    pub write_buffer: Option<WriteBufferConfig>,
+
+    /// Periodically refresh the materialized views behind dashboard queries
+    #[serde(default)]
+    pub materialized_views: Option<MaterializedViewsConfig>,
+
+    /// Downsample spot prices into OHLC candles and prune old raw rows
+    #[serde(default)]
+    pub candles: Option<CandlesConfig>,
+}
+
+fn default_health_check_interval_secs() -> u64 {
+    15
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct TimescaleConfig {
+    /// Age after which chunks are compressed
+    #[serde(default = "default_compress_after_hours")]
+    pub compress_after_hours: u64,
+}
+
+fn default_compress_after_hours() -> u64 {
+    7 * 24
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct WriteBufferConfig {
+    /// Writes held in memory before spilling or dropping
+    #[serde(default = "default_write_buffer_capacity")]
+    pub capacity: usize,
+    /// Upper bound on the delay between retries of a failed write
+    #[serde(default = "default_max_backoff_secs")]
+    pub max_backoff_secs: u64,
+    /// File that writes overflowing the buffer are appended to instead of being dropped
+    #[serde(default)]
+    pub spill_path: Option<PathBuf>,
+    /// Attempts at a write before giving up on it, unless it failed for the database being
+    /// unreachable. Writes the database rejects, e.g. for a constraint, are given up on at once.
+    #[serde(default = "default_max_write_attempts")]
+    pub max_attempts: u32,
+    /// File that writes given up on are appended to instead of being dropped
+    #[serde(default)]
+    pub dead_letter_path: Option<PathBuf>,
+}
+
+fn default_write_buffer_capacity() -> usize {
+    10_000
+}
+
+fn default_max_write_attempts() -> u32 {
+    10
+}
+
+fn default_max_backoff_secs() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct MaterializedViewsConfig {
+    /// Interval between refreshes of the views
+    #[serde(default = "default_view_refresh_interval_secs")]
+    pub refresh_interval_secs: u64,
+}
+
+fn default_view_refresh_interval_secs() -> u64 {
+    60
+}
+
+impl MaterializedViewsConfig {
+    pub fn refresh_interval(&self) -> Duration {
+        // a zero interval would refresh back to back
+        Duration::from_secs(self.refresh_interval_secs.max(1))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct CandlesConfig {
+    /// Interval between aggregation runs
+    #[serde(default = "default_candle_aggregate_interval_secs")]
+    pub aggregate_interval_secs: u64,
+    /// Age after which raw spot prices already covered by hourly candles are deleted, kept
+    /// forever when unset
+    #[serde(default)]
+    pub raw_retention_days: Option<u64>,
+}
+
+fn default_candle_aggregate_interval_secs() -> u64 {
+    60
+}
+
+impl CandlesConfig {
+    pub fn aggregate_interval(&self) -> Duration {
+        Duration::from_secs(self.aggregate_interval_secs.max(1))
+    }
+
+    pub fn raw_retention(&self) -> Option<Duration> {
+        self.raw_retention_days
+            .map(|days| Duration::from_secs(days * 24 * 60 * 60))
+    }
+}
+
+impl WriteBufferConfig {
+    pub fn max_backoff(&self) -> Duration {
+        Duration::from_secs(self.max_backoff_secs)
+    }
 }
 
 impl DatabaseConfig {
@@ -233,10 +1068,93 @@ impl DatabaseConfig {
     pub fn idle_timeout(&self) -> Duration {
         Duration::from_secs(self.idle_timeout_secs)
     }
+
+    pub fn health_check_interval(&self) -> Duration {
+        // a zero interval would make the checks spin
+        Duration::from_secs(self.health_check_interval_secs.max(1))
+    }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+
+    /// Directory Parquet exports requested through `/admin/export` are written under
+    #[serde(default = "default_export_dir")]
+    pub export_dir: PathBuf,
+
+    /// Port of the gRPC API, served on `host` next to the REST API. Disabled when unset.
+    #[serde(default)]
+    pub grpc_port: Option<u16>,
+
+    /// Keys accepted in the `x-api-key` header. When empty, reads are open to anyone and admin
+    /// routes are refused.
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+
+    /// Throttles clients that send too many requests. Disabled when unset.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+
+    /// Cross-origin requests browsers may make. None are allowed by default.
+    #[serde(default)]
+    pub cors: CorsConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+pub struct CorsConfig {
+    /// Origins allowed to call the API, e.g. `https://dashboard.example.com`
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// Methods allowed in cross-origin requests, `GET` and `POST` if empty
+    #[serde(default)]
+    pub allowed_methods: Vec<String>,
+    /// Request headers allowed in cross-origin requests besides the CORS-safelisted ones, e.g.
+    /// `x-api-key`
+    #[serde(default)]
+    pub allowed_headers: Vec<String>,
+    /// Allows any origin, method and header, ignoring the lists above. Meant for local
+    /// development only.
+    #[serde(default)]
+    pub permissive: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct RateLimitConfig {
+    /// Limit of each client IP, applied to requests without a valid API key
+    #[serde(default)]
+    pub per_ip: Option<RateLimit>,
+    /// Limit of each API key
+    #[serde(default)]
+    pub per_key: Option<RateLimit>,
+}
+
+/// A token bucket holding `burst` requests, refilled at `requests_per_second`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct RateLimit {
+    pub requests_per_second: f64,
+    pub burst: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct ApiKeyConfig {
+    pub key: String,
+    pub scope: ApiScope,
+}
+
+/// What an API key may access, admin keys can read too.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiScope {
+    /// Signals and spot prices
+    Read,
+    /// Admin routes such as exports
+    Admin,
+}
+
+fn default_export_dir() -> PathBuf {
+    PathBuf::from("export")
 }
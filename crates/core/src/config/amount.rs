@@ -0,0 +1,105 @@
+//! Token amounts written in whole tokens, e.g. `1000` or `"1.5"`, converted to raw units with the
+//! token's decimals.
+use std::fmt;
+
+use num_bigint::BigUint;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// An amount of tokens in human units. Quote fractional amounts, `"0.1"`, to keep them exact.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum TokenAmount {
+    Whole(u64),
+    Decimal(String),
+    Float(f64),
+}
+
+impl TokenAmount {
+    /// The amount in the token's smallest unit, i.e. scaled by `10^decimals`.
+    ///
+    /// # Errors
+    /// Returns an error if the amount isn't a non-negative decimal number or has more fractional
+    /// digits than `decimals`.
+    pub fn to_units(&self, decimals: u32) -> Result<BigUint, String> {
+        match self {
+            Self::Whole(amount) => Ok(BigUint::from(*amount) * 10u128.pow(decimals)),
+            Self::Decimal(amount) => parse_units(amount, decimals),
+            Self::Float(amount) => parse_units(&amount.to_string(), decimals),
+        }
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Whole(amount) => write!(f, "{amount}"),
+            Self::Decimal(amount) => write!(f, "{amount}"),
+            Self::Float(amount) => write!(f, "{amount}"),
+        }
+    }
+}
+
+/// Parses a decimal string like `25_000` or `1.5` into units of `10^-decimals`.
+fn parse_units(amount: &str, decimals: u32) -> Result<BigUint, String> {
+    let digits = amount.trim().replace('_', "");
+    let (integer, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+    if (integer.is_empty() && fraction.is_empty())
+        || !integer
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(format!("{amount:?} isn't a decimal amount"));
+    }
+
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > decimals as usize {
+        return Err(format!(
+            "{amount} has {} decimals, more than the token's {decimals}",
+            fraction.len()
+        ));
+    }
+
+    // the leading zero keeps amounts like `.0` from being empty
+    let units = format!("0{integer}{fraction:0<width$}", width = decimals as usize);
+    BigUint::parse_bytes(units.as_bytes(), 10)
+        .ok_or_else(|| format!("{amount:?} isn't a decimal amount"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_units() {
+        let units = |amount: TokenAmount, decimals| amount.to_units(decimals).unwrap().to_string();
+
+        assert_eq!(units(TokenAmount::Whole(1000), 6), "1000000000");
+        assert_eq!(
+            units(TokenAmount::Decimal("1.5".to_string()), 18),
+            "1500000000000000000"
+        );
+        assert_eq!(
+            units(TokenAmount::Decimal("25_000".to_string()), 6),
+            "25000000000"
+        );
+        assert_eq!(units(TokenAmount::Decimal(".25".to_string()), 2), "25");
+        assert_eq!(units(TokenAmount::Decimal("1.100".to_string()), 1), "11");
+        assert_eq!(units(TokenAmount::Float(0.1), 8), "10000000");
+
+        assert!(
+            TokenAmount::Decimal("1.0000001".to_string())
+                .to_units(6)
+                .is_err()
+        );
+        assert!(TokenAmount::Decimal("-1".to_string()).to_units(6).is_err());
+        assert!(
+            TokenAmount::Decimal("1e18".to_string())
+                .to_units(18)
+                .is_err()
+        );
+        assert!(TokenAmount::Decimal(".".to_string()).to_units(18).is_err());
+        assert!(TokenAmount::Float(-1.0).to_units(18).is_err());
+    }
+}
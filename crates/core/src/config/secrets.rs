@@ -0,0 +1,178 @@
+//! Resolves `${...}` references in the config's secret fields, so keys don't have to live in
+//! `kuma.yaml`.
+//!
+//! `${NAME}` is replaced with the environment variable `NAME` and `${secret:NAME}` with the file
+//! `NAME` in the secrets directory, as mounted by Docker or Kubernetes secrets. `$${` escapes a
+//! literal `${`.
+use std::{env, fs, path::PathBuf};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::Config;
+
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct SecretsConfig {
+    /// Directory holding one file per secret, named after the secret
+    pub dir: PathBuf,
+}
+
+impl SecretsConfig {
+    fn read(&self, name: &str) -> Result<String, String> {
+        if name.is_empty() || name.contains(['/', '\\']) || name == ".." || name == "." {
+            return Err(format!("invalid secret name {name:?}"));
+        }
+
+        let path = self.dir.join(name);
+        fs::read_to_string(&path)
+            .map(|secret| secret.trim_end_matches(['\n', '\r']).to_owned())
+            .map_err(|e| format!("failed to read secret {}: {e}", path.display()))
+    }
+}
+
+impl Config {
+    /// Replaces references in the API keys, private key, keystore password, RPC urls, alert
+    /// webhooks, Sentry DSN and database password with the environment variables or secrets they
+    /// name.
+    pub(super) fn resolve_secrets(&mut self) -> Result<(), String> {
+        let secrets = self.secrets.clone();
+        let lookup = |reference: &str| -> Result<String, String> {
+            match reference.strip_prefix("secret:") {
+                Some(name) => secrets
+                    .as_ref()
+                    .ok_or_else(|| {
+                        format!("secret {name} is referenced but secrets.dir isn't set")
+                    })?
+                    .read(name),
+                None => env::var(reference)
+                    .map_err(|e| format!("environment variable {reference} isn't usable: {e}")),
+            }
+        };
+        let resolve = |path: &str, value: &mut String| {
+            *value = interpolate(value, &lookup).map_err(|e| format!("{path}: {e}"))?;
+            Ok::<_, String>(())
+        };
+
+        resolve("tycho_api_key", &mut self.tycho_api_key)?;
+        resolve("private_key", &mut self.private_key)?;
+        if let Some(password) = &mut self.keystore_password {
+            resolve("keystore_password", password)?;
+        }
+        resolve("database.password", &mut self.database.password)?;
+
+        for (i, chain) in self.chains.iter_mut().enumerate() {
+            resolve(&format!("chains[{i}].rpc_url"), &mut chain.rpc_url)?;
+            for (j, url) in chain.rpc_fallbacks.iter_mut().enumerate() {
+                resolve(&format!("chains[{i}].rpc_fallbacks[{j}]"), url)?;
+            }
+            for (j, fallback) in chain.tycho_fallbacks.iter_mut().enumerate() {
+                if let Some(api_key) = &mut fallback.api_key {
+                    resolve(
+                        &format!("chains[{i}].tycho_fallbacks[{j}].api_key"),
+                        api_key,
+                    )?;
+                }
+            }
+            if let Some(mempool) = &mut chain.mempool {
+                resolve(&format!("chains[{i}].mempool.ws_url"), &mut mempool.ws_url)?;
+            }
+        }
+
+        if let Some(okx) = &mut self.okx {
+            for (field, value) in [
+                ("okx.api_key", &mut okx.api_key),
+                ("okx.api_secret", &mut okx.api_secret),
+                ("okx.passphrase", &mut okx.passphrase),
+            ] {
+                if let Some(value) = value {
+                    resolve(field, value)?;
+                }
+            }
+        }
+
+        if let Some(coinbase) = &mut self.coinbase {
+            for (field, value) in [
+                ("coinbase.api_key", &mut coinbase.api_key),
+                ("coinbase.api_secret", &mut coinbase.api_secret),
+            ] {
+                if let Some(value) = value {
+                    resolve(field, value)?;
+                }
+            }
+        }
+
+        if let Some(alerts) = &mut self.alerts {
+            if let Some(slack) = &mut alerts.slack {
+                resolve("alerts.slack.webhook_url", &mut slack.webhook_url)?;
+            }
+            if let Some(telegram) = &mut alerts.telegram {
+                resolve("alerts.telegram.bot_token", &mut telegram.bot_token)?;
+            }
+        }
+
+        if let Some(sentry) = &mut self.sentry {
+            resolve("sentry.dsn", &mut sentry.dsn)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Replaces every `${reference}` in `value` with what `lookup` resolves it to.
+fn interpolate(
+    value: &str,
+    lookup: impl Fn(&str) -> Result<String, String>,
+) -> Result<String, String> {
+    let mut resolved = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            resolved.push_str(&rest[..start - 1]);
+            resolved.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        resolved.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unterminated reference in {value:?}"))?;
+        resolved.push_str(&lookup(&rest[start + 2..start + end])?);
+        rest = &rest[start + end + 1..];
+    }
+    resolved.push_str(rest);
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(reference: &str) -> Result<String, String> {
+        match reference {
+            "TYCHO_API_KEY" => Ok("tycho-key".to_string()),
+            "secret:rpc" => Ok("https://rpc.example.com/key".to_string()),
+            _ => Err(format!("{reference} isn't set")),
+        }
+    }
+
+    #[test]
+    fn test_interpolate() {
+        assert_eq!(interpolate("plain", lookup).unwrap(), "plain");
+        assert_eq!(
+            interpolate("${TYCHO_API_KEY}", lookup).unwrap(),
+            "tycho-key"
+        );
+        assert_eq!(
+            interpolate("${secret:rpc}?chain=${TYCHO_API_KEY}", lookup).unwrap(),
+            "https://rpc.example.com/key?chain=tycho-key"
+        );
+        assert_eq!(interpolate("pa$${word}", lookup).unwrap(), "pa${word}");
+        assert_eq!(interpolate("pa$$word", lookup).unwrap(), "pa$$word");
+
+        assert!(interpolate("${MISSING}", lookup).is_err());
+        assert!(interpolate("${TYCHO_API_KEY", lookup).is_err());
+    }
+}
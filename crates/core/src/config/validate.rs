@@ -0,0 +1,530 @@
+//! Checks a loaded [`Config`] before anything is built from it, so mistakes are reported together
+//! and point at where they are in `kuma.yaml`.
+use std::{collections::HashMap, fmt, str::FromStr as _};
+
+use num_bigint::BigUint;
+use tracing_subscriber::filter::{Directive, LevelFilter};
+
+use super::{Config, RateLimit, TokenConfig};
+use crate::{rpc, state::pair::PairStateDelivery};
+
+/// Most decimals accepted for a token, inventories are scaled by `10^decimals` in a `u128`
+const MAX_TOKEN_DECIMALS: u32 = 36;
+
+/// Inventories of `10^max(decimals, 9)` tokens or more were most likely written in raw units
+const MIN_RAW_UNITS_DECIMALS: u32 = 9;
+
+/// A problem with the config, at a path such as `tokens.WETH.addresses.base`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Every problem [`Config::validate`] found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationErrors(pub Vec<ValidationError>);
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid config, {} problem(s) found:", self.0.len())?;
+        for error in &self.0 {
+            write!(f, "\n  - {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+#[derive(Default)]
+struct Errors(Vec<ValidationError>);
+
+impl Errors {
+    fn push(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.0.push(ValidationError {
+            path: path.into(),
+            message: message.into(),
+        });
+    }
+}
+
+impl Config {
+    /// Checks chains, tokens and strategies for mistakes that would otherwise fail deep inside
+    /// [`Config::build_addrs_and_inventory`] or when starting strategies.
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = Errors::default();
+
+        let chains = self.validate_chains(&mut errors);
+        self.validate_tokens(&chains, &mut errors);
+        self.validate_strategies(&chains, &mut errors);
+        self.validate_alerts(&mut errors);
+
+        if !(self.max_block_latency_fraction > 0.0 && self.max_block_latency_fraction.is_finite()) {
+            errors.push(
+                "max_block_latency_fraction",
+                "must be a positive fraction of the block time",
+            );
+        }
+        if self.pair_state_delivery == PairStateDelivery::Queue(0) {
+            errors.push("pair_state_delivery", "queue must hold at least one state");
+        }
+
+        if let Err(e) = self.log.level.parse::<LevelFilter>() {
+            errors.push("log.level", e.to_string());
+        }
+        for (i, directive) in self.log.directives.iter().enumerate() {
+            if let Err(e) = directive.parse::<Directive>() {
+                errors.push(format!("log.directives[{i}]"), e.to_string());
+            }
+        }
+
+        if let Some(sentry) = &self.sentry {
+            if let Err(e) = sentry.dsn.parse::<sentry::types::Dsn>() {
+                errors.push("sentry.dsn", format!("invalid dsn: {e}"));
+            }
+        }
+
+        if let Some(rate_limit) = &self.server.rate_limit {
+            for (path, limit) in [
+                ("server.rate_limit.per_ip", &rate_limit.per_ip),
+                ("server.rate_limit.per_key", &rate_limit.per_key),
+            ] {
+                if let Some(limit) = limit {
+                    validate_rate_limit(path, limit, &mut errors);
+                }
+            }
+        }
+
+        if errors.0.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationErrors(errors.0))
+        }
+    }
+
+    /// Returns the chains that parsed.
+    fn validate_chains(&self, errors: &mut Errors) -> Vec<tycho_common::models::Chain> {
+        if self.chains.is_empty() {
+            errors.push("chains", "no chains are configured");
+        }
+
+        let mut chains = Vec::new();
+        for (i, chain_config) in self.chains.iter().enumerate() {
+            let path = format!("chains[{i}]");
+            let name = match tycho_common::models::Chain::from_str(&chain_config.name) {
+                Ok(name) => name,
+                Err(e) => {
+                    errors.push(
+                        format!("{path}.name"),
+                        format!("unknown chain {}: {e}", chain_config.name),
+                    );
+                    continue;
+                }
+            };
+            if chains.contains(&name) {
+                errors.push(
+                    format!("{path}.name"),
+                    format!("chain {name} is configured more than once"),
+                );
+                continue;
+            }
+            for (field, url) in std::iter::once(("rpc_url".to_string(), &chain_config.rpc_url))
+                .chain(
+                    chain_config
+                        .rpc_fallbacks
+                        .iter()
+                        .enumerate()
+                        .map(|(j, url)| (format!("rpc_fallbacks[{j}]"), url)),
+                )
+            {
+                if !rpc::is_supported_url(url) {
+                    errors.push(format!("{path}.{field}"), "must be an http(s) or ws(s) url");
+                }
+            }
+            match chain_config.build() {
+                Ok(chain) => {
+                    if let Some(amount) = &chain_config.min_gas_balance {
+                        if let Err(e) = amount.to_units(chain.gas_token().decimals) {
+                            errors.push(format!("{path}.min_gas_balance"), e);
+                        }
+                    }
+                }
+                Err(e) => errors.push(path, format!("{e:#}")),
+            }
+            chains.push(name);
+        }
+
+        chains
+    }
+
+    fn validate_tokens(&self, chains: &[tycho_common::models::Chain], errors: &mut Errors) {
+        let mut symbols: HashMap<String, &str> = HashMap::new();
+        let mut addresses = HashMap::new();
+
+        for (symbol, token_config) in sorted(&self.tokens) {
+            let path = format!("tokens.{symbol}");
+
+            // strategies look tokens up regardless of case
+            if let Some(other) = symbols.insert(symbol.to_ascii_uppercase(), symbol) {
+                errors.push(
+                    &path,
+                    format!("symbol only differs from {other} in case, they can't be told apart"),
+                );
+            }
+            if token_config.decimals > MAX_TOKEN_DECIMALS {
+                errors.push(
+                    format!("{path}.decimals"),
+                    format!(
+                        "{} decimals is more than the {MAX_TOKEN_DECIMALS} supported",
+                        token_config.decimals
+                    ),
+                );
+            } else {
+                validate_inventory(&path, token_config, errors);
+            }
+
+            for chain in chains {
+                let Some(address) = token_config.addresses.get(chain) else {
+                    errors.push(
+                        format!("{path}.addresses"),
+                        format!("missing an address on {chain}, every configured chain needs one"),
+                    );
+                    continue;
+                };
+                let address_path = format!("{path}.addresses.{chain}");
+
+                if address.len() != 20 {
+                    errors.push(
+                        &address_path,
+                        format!("{address} is {} bytes long, not 20", address.len()),
+                    );
+                }
+                if let Some(other) = addresses.insert((*chain, address.clone()), symbol) {
+                    errors.push(address_path, format!("{address} is also {other}'s address"));
+                }
+            }
+        }
+    }
+
+    fn validate_strategies(&self, chains: &[tycho_common::models::Chain], errors: &mut Errors) {
+        if self.strategies.is_empty() {
+            errors.push("strategies", "no strategies are configured");
+        }
+
+        for (i, strategy) in self.strategies.iter().enumerate() {
+            let path = format!("strategies[{i}]");
+
+            // strategies are paused and reloaded by id
+            if self.strategies[..i]
+                .iter()
+                .any(|other| other.id() == strategy.id())
+            {
+                errors.push(
+                    &path,
+                    format!("strategy {} is configured more than once", strategy.id()),
+                );
+            }
+
+            for (field, symbol) in [
+                ("token_a", &strategy.token_a),
+                ("token_b", &strategy.token_b),
+            ] {
+                if !self
+                    .tokens
+                    .keys()
+                    .any(|token| token.eq_ignore_ascii_case(symbol))
+                {
+                    errors.push(
+                        format!("{path}.{field}"),
+                        format!("token {symbol} isn't configured under tokens"),
+                    );
+                }
+            }
+            if strategy.token_a.eq_ignore_ascii_case(&strategy.token_b) {
+                errors.push(&path, "token_a and token_b are the same token");
+            }
+
+            for (field, name) in [
+                ("slow_chain", &strategy.slow_chain),
+                ("fast_chain", &strategy.fast_chain),
+            ] {
+                match tycho_common::models::Chain::from_str(name) {
+                    Ok(chain) if chains.contains(&chain) => {}
+                    Ok(_) => errors.push(
+                        format!("{path}.{field}"),
+                        format!("chain {name} isn't configured under chains"),
+                    ),
+                    Err(e) => errors.push(
+                        format!("{path}.{field}"),
+                        format!("unknown chain {name}: {e}"),
+                    ),
+                }
+            }
+            if strategy
+                .slow_chain
+                .eq_ignore_ascii_case(&strategy.fast_chain)
+            {
+                errors.push(&path, "slow_chain and fast_chain are the same chain");
+            }
+        }
+    }
+
+    fn validate_alerts(&self, errors: &mut Errors) {
+        let Some(alerts) = &self.alerts else {
+            return;
+        };
+
+        for (symbol, amount) in sorted(&alerts.min_signal_profit) {
+            let path = format!("alerts.min_signal_profit.{symbol}");
+            match self
+                .tokens
+                .iter()
+                .find(|(token, _)| token.eq_ignore_ascii_case(symbol))
+            {
+                Some((_, token_config)) => {
+                    if let Err(e) = amount.to_units(token_config.decimals) {
+                        errors.push(path, e);
+                    }
+                }
+                None => errors.push(
+                    path,
+                    format!("token {symbol} isn't configured under tokens"),
+                ),
+            }
+        }
+    }
+}
+
+fn validate_inventory(path: &str, token_config: &TokenConfig, errors: &mut Errors) {
+    let decimals = token_config.decimals;
+    let units = match token_config.inventory.to_units(decimals) {
+        Ok(units) => units,
+        Err(e) => {
+            errors.push(format!("{path}.inventory"), e);
+            return;
+        }
+    };
+
+    let implausible = BigUint::from(10u8).pow(decimals + decimals.max(MIN_RAW_UNITS_DECIMALS));
+    if units >= implausible {
+        errors.push(
+            format!("{path}.inventory"),
+            format!(
+                "{} tokens is implausibly large, inventory is in whole tokens rather than raw units",
+                token_config.inventory
+            ),
+        );
+    }
+}
+
+fn validate_rate_limit(path: &str, limit: &RateLimit, errors: &mut Errors) {
+    if !(limit.requests_per_second > 0.0 && limit.requests_per_second.is_finite()) {
+        errors.push(
+            format!("{path}.requests_per_second"),
+            "must be a positive number of requests",
+        );
+    }
+    if limit.burst < 1 {
+        errors.push(format!("{path}.burst"), "must allow at least one request");
+    }
+}
+
+/// Entries of `map` ordered by key, so errors are reported in a stable order.
+fn sorted<V>(map: &HashMap<String, V>) -> Vec<(&String, &V)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by_key(|(key, _)| *key);
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::{
+        Figment,
+        providers::{Format as _, Yaml},
+    };
+
+    use super::*;
+    use crate::config::{RateLimitConfig, TokenAmount};
+
+    const CONFIG: &str = r#"
+database:
+  user: kuma
+  password: kuma
+  host: localhost
+  port: 5432
+  dbname: kuma
+  max_connections: 1
+  connection_timeout_secs: 1
+  idle_timeout_secs: 1
+server:
+  host: localhost
+  port: 8080
+strategies:
+  - token_a: usdc
+    token_b: WETH
+    slow_chain: ethereum
+    fast_chain: base
+tokens:
+  USDC:
+    addresses:
+      ethereum: "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"
+      base: "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913"
+    decimals: 6
+    tax: 0
+    gas: [1000]
+    quality: 100
+    inventory: 1000
+  WETH:
+    addresses:
+      ethereum: "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"
+      base: "0x4200000000000000000000000000000000000006"
+    decimals: 18
+    tax: 0
+    gas: [1000]
+    quality: 100
+    inventory: 1
+chains:
+  - name: ethereum
+    rpc_url: "https://ethereum-rpc.publicnode.com"
+    tycho_url: "tycho-beta.propellerheads.xyz"
+    permit2_address: "0x000000000022d473030f116ddee9f6b43ac78ba3"
+  - name: base
+    rpc_url: "https://mainnet.base.org"
+    tycho_url: "tycho-base-beta.propellerheads.xyz"
+    permit2_address: "0x000000000022d473030f116ddee9f6b43ac78ba3"
+tycho_api_key: key
+add_tvl_threshold: 10.0
+remove_tvl_threshold: 5.0
+congestion_risk_discount_bps: 0
+max_slippage_bps: 25
+binary_search_steps: 16
+private_key: "0x01"
+"#;
+
+    fn config() -> Config {
+        Figment::new()
+            .merge(Yaml::string(CONFIG))
+            .extract()
+            .unwrap()
+    }
+
+    fn paths(config: &Config) -> Vec<String> {
+        match config.validate() {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.0.into_iter().map(|error| error.path).collect(),
+        }
+    }
+
+    #[test]
+    fn test_valid_config() {
+        assert_eq!(config().validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_reports_every_problem_with_its_path() {
+        let mut config = config();
+        config.chains[1].permit2_address = "0x1234".to_string();
+        config.chains[1].rpc_fallbacks = vec!["mainnet.base.org".to_string()];
+        config.strategies[0].fast_chain = "arbitrum".to_string();
+        config.strategies[0].token_b = "WBTC".to_string();
+
+        let weth = config.tokens.get_mut("WETH").unwrap();
+        weth.decimals = 40;
+        config.tokens.get_mut("USDC").unwrap().inventory =
+            TokenAmount::Decimal("25000000000".to_string());
+        weth.addresses.remove(&tycho_common::models::Chain::Base);
+        let usdc_address =
+            config.tokens["USDC"].addresses[&tycho_common::models::Chain::Ethereum].clone();
+        config
+            .tokens
+            .get_mut("WETH")
+            .unwrap()
+            .addresses
+            .insert(tycho_common::models::Chain::Ethereum, usdc_address);
+
+        assert_eq!(
+            paths(&config),
+            [
+                "chains[1].rpc_fallbacks[0]",
+                "chains[1]",
+                "tokens.USDC.inventory",
+                "tokens.WETH.decimals",
+                "tokens.WETH.addresses.ethereum",
+                "tokens.WETH.addresses",
+                "strategies[0].token_b",
+                "strategies[0].fast_chain",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_empty_pair_state_queue() {
+        let mut config = config();
+        config.pair_state_delivery = PairStateDelivery::Queue(0);
+        assert_eq!(paths(&config), ["pair_state_delivery"]);
+
+        config.pair_state_delivery = PairStateDelivery::Queue(8);
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_min_gas_balance_finer_than_the_gas_token() {
+        let mut config = config();
+        config.chains[0].min_gas_balance = Some(TokenAmount::Decimal("0.05".to_string()));
+        assert_eq!(config.validate(), Ok(()));
+
+        config.chains[1].min_gas_balance =
+            Some(TokenAmount::Decimal("0.0000000000000000001".to_string()));
+        assert_eq!(paths(&config), ["chains[1].min_gas_balance"]);
+    }
+
+    #[test]
+    fn test_rejects_rate_limits_that_never_refill() {
+        let mut config = config();
+        config.server.rate_limit = Some(RateLimitConfig {
+            per_ip: Some(RateLimit {
+                requests_per_second: 0.0,
+                burst: 0,
+            }),
+            per_key: Some(RateLimit {
+                requests_per_second: 10.0,
+                burst: 20,
+            }),
+        });
+        assert_eq!(
+            paths(&config),
+            [
+                "server.rate_limit.per_ip.requests_per_second",
+                "server.rate_limit.per_ip.burst",
+            ]
+        );
+
+        config.server.rate_limit.as_mut().unwrap().per_ip = Some(RateLimit {
+            requests_per_second: -1.0,
+            burst: 1,
+        });
+        assert_eq!(
+            paths(&config),
+            ["server.rate_limit.per_ip.requests_per_second"]
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_log_filters() {
+        let mut config = config();
+        config.log.level = "loud".to_string();
+        config.log.directives = vec![
+            "kuma_core::collector=debug".to_string(),
+            "kumad=verbose".to_string(),
+        ];
+
+        assert_eq!(paths(&config), ["log.level", "log.directives[1]"]);
+    }
+}
@@ -0,0 +1,224 @@
+use std::{collections::HashMap, str::FromStr as _, sync::Arc};
+
+use alloy::primitives::{Address, B256};
+use color_eyre::eyre::{self, WrapErr as _, eyre};
+use num_bigint::BigUint;
+use sqlx::{FromRow, PgPool};
+use tracing::instrument;
+
+use crate::{
+    chain::Chain,
+    config::TokenAddressesForChain,
+    state::{balances::TokenBalances, header::BlockHeader},
+};
+
+use super::{observe::ObserveQuery as _, try_chain_from_str};
+
+/// Chain conditions of a collected block and the tracked account's balances at it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockRecord {
+    pub chain: Chain,
+    pub header: BlockHeader,
+    pub native_balance: Option<BigUint>,
+    pub token_balances: Option<TokenBalances>,
+}
+
+#[derive(Clone)]
+pub struct BlockRepository {
+    pool: Arc<PgPool>,
+    token_configs: Arc<TokenAddressesForChain>,
+}
+
+impl BlockRepository {
+    pub(super) fn new(pool: Arc<PgPool>, token_configs: Arc<TokenAddressesForChain>) -> Self {
+        Self {
+            pool,
+            token_configs,
+        }
+    }
+
+    /// Inserts `block`, replacing a previous record at the same height (e.g. after a reorg).
+    #[instrument(skip_all, fields(chain = %block.chain, height = block.header.height))]
+    pub async fn upsert(&self, block: &BlockRecord) -> eyre::Result<()> {
+        let token_balances = block.token_balances.as_ref().map(|balances| {
+            serde_json::Value::Object(
+                balances
+                    .iter()
+                    .map(|(token, balance)| (token.to_string(), balance.to_string().into()))
+                    .collect(),
+            )
+        });
+
+        sqlx::query(
+            r#"
+            INSERT INTO blocks (
+                chain, height, hash, timestamp,
+                base_fee_per_gas, blob_base_fee,
+                native_balance, token_balances
+            ) VALUES ($1, $2, $3, to_timestamp($4), $5, $6, $7, $8)
+            ON CONFLICT (chain, height) DO UPDATE SET
+                hash = EXCLUDED.hash,
+                timestamp = EXCLUDED.timestamp,
+                base_fee_per_gas = EXCLUDED.base_fee_per_gas,
+                blob_base_fee = EXCLUDED.blob_base_fee,
+                native_balance = EXCLUDED.native_balance,
+                token_balances = EXCLUDED.token_balances
+            "#,
+        )
+        .bind(block.chain.name.to_string())
+        .bind(block.header.height as i64)
+        .bind(block.header.hash.to_string())
+        .bind(block.header.timestamp as f64)
+        .bind(block.header.base_fee_per_gas.map(|fee| fee as i64))
+        .bind(block.header.blob_base_fee.map(|fee| fee.to_string()))
+        .bind(block.native_balance.as_ref().map(ToString::to_string))
+        .bind(token_balances)
+        .execute(self.pool.as_ref())
+        .observe("blocks.upsert")
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_by_height(
+        &self,
+        chain: &Chain,
+        height: u64,
+    ) -> eyre::Result<Option<BlockRecord>> {
+        let row: Option<BlockRow> = sqlx::query_as(
+            r#"
+            SELECT
+                chain, height, hash,
+                EXTRACT(EPOCH FROM timestamp)::BIGINT AS timestamp,
+                base_fee_per_gas, blob_base_fee,
+                native_balance, token_balances
+            FROM blocks
+            WHERE chain = $1 AND height = $2
+            "#,
+        )
+        .bind(chain.name.to_string())
+        .bind(height as i64)
+        .fetch_optional(self.pool.as_ref())
+        .observe("blocks.get_by_height")
+        .await?;
+
+        row.map(|row| try_block_from_row(row, &self.token_configs))
+            .transpose()
+    }
+
+    /// Most recent blocks of `chain`, newest first
+    #[instrument(skip(self))]
+    pub async fn get_latest(&self, chain: &Chain, limit: u32) -> eyre::Result<Vec<BlockRecord>> {
+        let rows: Vec<BlockRow> = sqlx::query_as(
+            r#"
+            SELECT
+                chain, height, hash,
+                EXTRACT(EPOCH FROM timestamp)::BIGINT AS timestamp,
+                base_fee_per_gas, blob_base_fee,
+                native_balance, token_balances
+            FROM blocks
+            WHERE chain = $1
+            ORDER BY height DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(chain.name.to_string())
+        .bind(limit as i64)
+        .fetch_all(self.pool.as_ref())
+        .observe("blocks.get_latest")
+        .await?;
+
+        rows.into_iter()
+            .map(|row| try_block_from_row(row, &self.token_configs))
+            .collect()
+    }
+
+    /// Most recent block with collected balances of every chain, skipping chains that aren't
+    /// configured anymore
+    #[instrument(skip(self))]
+    pub async fn latest_balances(&self) -> eyre::Result<Vec<BlockRecord>> {
+        let rows: Vec<BlockRow> = sqlx::query_as(
+            r#"
+            SELECT DISTINCT ON (chain)
+                chain, height, hash,
+                EXTRACT(EPOCH FROM timestamp)::BIGINT AS timestamp,
+                base_fee_per_gas, blob_base_fee,
+                native_balance, token_balances
+            FROM blocks
+            WHERE native_balance IS NOT NULL OR token_balances IS NOT NULL
+            ORDER BY chain, height DESC
+            "#,
+        )
+        .fetch_all(self.pool.as_ref())
+        .observe("blocks.latest_balances")
+        .await?;
+
+        rows.into_iter()
+            .filter(|row| try_chain_from_str(&row.chain, &self.token_configs).is_ok())
+            .map(|row| try_block_from_row(row, &self.token_configs))
+            .collect()
+    }
+}
+
+#[derive(FromRow)]
+struct BlockRow {
+    chain: String,
+    height: i64,
+    hash: String,
+    timestamp: i64,
+    base_fee_per_gas: Option<i64>,
+    blob_base_fee: Option<String>,
+    native_balance: Option<String>,
+    token_balances: Option<serde_json::Value>,
+}
+
+fn try_block_from_row(
+    row: BlockRow,
+    token_configs: &TokenAddressesForChain,
+) -> eyre::Result<BlockRecord> {
+    let chain = try_chain_from_str(&row.chain, token_configs)?;
+
+    let token_balances = row
+        .token_balances
+        .map(|balances| {
+            let balances: HashMap<String, String> =
+                serde_json::from_value(balances).wrap_err("invalid token balances")?;
+            balances
+                .into_iter()
+                .map(|(token, balance)| {
+                    Ok((
+                        Address::from_str(&token)
+                            .wrap_err_with(|| format!("invalid token address {token}"))?,
+                        parse_biguint(&balance)?,
+                    ))
+                })
+                .collect::<eyre::Result<HashMap<_, _>>>()
+                .map(TokenBalances::new)
+        })
+        .transpose()?;
+
+    Ok(BlockRecord {
+        chain,
+        header: BlockHeader {
+            height: row.height as u64,
+            hash: B256::from_str(&row.hash).wrap_err("invalid block hash")?,
+            timestamp: row.timestamp as u64,
+            base_fee_per_gas: row.base_fee_per_gas.map(|fee| fee as u64),
+            blob_base_fee: row
+                .blob_base_fee
+                .map(|fee| fee.parse().wrap_err("invalid blob base fee"))
+                .transpose()?,
+        },
+        native_balance: row
+            .native_balance
+            .as_deref()
+            .map(parse_biguint)
+            .transpose()?,
+        token_balances,
+    })
+}
+
+fn parse_biguint(value: &str) -> eyre::Result<BigUint> {
+    BigUint::from_str(value).map_err(|e| eyre!("invalid balance {value}: {e}"))
+}
@@ -0,0 +1,294 @@
+//! OHLC candles downsampled from per-block spot prices.
+//!
+//! A background job folds new spot prices into 1m, 5m and 1h candles and, once they're covered by
+//! the coarsest candles, deletes raw rows older than the configured retention. Each block's price
+//! is the midpoint of its min and max pool prices.
+use std::{sync::Arc, time::Duration};
+
+use color_eyre::eyre::{self, eyre};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use tokio::{select, time::MissedTickBehavior};
+use tokio_util::sync::{CancellationToken, DropGuard};
+use tracing::{debug, instrument, warn};
+
+use crate::{
+    chain::Chain,
+    config::{CandlesConfig, TokenAddressesForChain},
+    state::pair::Pair,
+};
+
+use super::{
+    observe::ObserveQuery as _, timescale::time_bucket, try_chain_from_str,
+    try_token_from_chain_symbol,
+};
+
+/// Width of a candle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CandleInterval {
+    #[serde(rename = "1m")]
+    OneMinute,
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "1h")]
+    OneHour,
+}
+
+impl CandleInterval {
+    pub const ALL: &'static [CandleInterval] = &[
+        CandleInterval::OneMinute,
+        CandleInterval::FiveMinutes,
+        CandleInterval::OneHour,
+    ];
+
+    pub fn duration(self) -> Duration {
+        match self {
+            CandleInterval::OneMinute => Duration::from_secs(60),
+            CandleInterval::FiveMinutes => Duration::from_secs(5 * 60),
+            CandleInterval::OneHour => Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// Spot price movement of a pair on a chain over one candle.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpotPriceCandle {
+    pub chain: Chain,
+    pub pair: Pair,
+    pub interval: CandleInterval,
+    /// Start of the candle, in seconds since the unix epoch
+    pub bucket_start: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// Number of blocks in the candle
+    pub blocks: u64,
+}
+
+/// Candles [`CandleRepository::get_range`] looks up.
+#[derive(Debug, Clone)]
+pub struct CandleRange {
+    /// Pair in either token order
+    pub token_a_symbol: String,
+    pub token_b_symbol: String,
+    /// Every chain's candles if unset
+    pub chain: Option<Chain>,
+    pub interval: CandleInterval,
+    /// Earliest candle start, in seconds since the unix epoch
+    pub from: u64,
+    /// Latest candle start, in seconds since the unix epoch
+    pub to: u64,
+}
+
+#[derive(Clone)]
+pub struct CandleRepository {
+    pool: Arc<PgPool>,
+    token_configs: Arc<TokenAddressesForChain>,
+    timescale: bool,
+}
+
+impl CandleRepository {
+    pub(super) fn new(
+        pool: Arc<PgPool>,
+        token_configs: Arc<TokenAddressesForChain>,
+        timescale: bool,
+    ) -> Self {
+        Self {
+            pool,
+            token_configs,
+            timescale,
+        }
+    }
+
+    /// Folds spot prices into candles of `interval`, starting over from the latest candle
+    /// since it may have been aggregated before it was complete. Returns the candles written.
+    #[instrument(skip(self))]
+    pub async fn aggregate(&self, interval: CandleInterval) -> eyre::Result<u64> {
+        let query = format!(
+            r#"
+            INSERT INTO spot_price_candles (
+                chain_id, token_a_id, token_b_id, interval_secs, bucket_start,
+                open, high, low, close, blocks
+            )
+            SELECT
+                chain_id, token_a_id, token_b_id, $2, bucket_start,
+                (ARRAY_AGG((min_price + max_price) / 2 ORDER BY block_height))[1],
+                MAX(max_price),
+                MIN(min_price),
+                (ARRAY_AGG((min_price + max_price) / 2 ORDER BY block_height DESC))[1],
+                COUNT(*)
+            FROM (
+                SELECT *, {bucket} AS bucket_start
+                FROM spot_prices
+                WHERE created_at >= COALESCE(
+                    (SELECT MAX(bucket_start) FROM spot_price_candles WHERE interval_secs = $2),
+                    '-infinity'
+                )
+            ) sp
+            GROUP BY chain_id, token_a_id, token_b_id, bucket_start
+            ON CONFLICT (chain_id, token_a_id, token_b_id, interval_secs, bucket_start)
+            DO UPDATE SET
+                open = EXCLUDED.open,
+                high = EXCLUDED.high,
+                low = EXCLUDED.low,
+                close = EXCLUDED.close,
+                blocks = EXCLUDED.blocks
+            "#,
+            bucket = time_bucket(self.timescale, "created_at", 1),
+        );
+
+        let result = sqlx::query(&query)
+            .bind(interval.duration().as_secs_f64())
+            .bind(interval.duration().as_secs() as i32)
+            .execute(self.pool.as_ref())
+            .observe("candles.aggregate")
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Deletes raw spot prices older than `retention` that are covered by hourly candles.
+    /// Returns the rows deleted.
+    #[instrument(skip(self))]
+    pub async fn prune_raw(&self, retention: Duration) -> eyre::Result<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM spot_prices
+            WHERE created_at < NOW() - make_interval(secs => $1)
+                AND created_at < (
+                    SELECT MAX(bucket_start) FROM spot_price_candles WHERE interval_secs = $2
+                )
+            "#,
+        )
+        .bind(retention.as_secs_f64())
+        .bind(CandleInterval::OneHour.duration().as_secs() as i32)
+        .execute(self.pool.as_ref())
+        .observe("candles.prune_raw")
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Up to `limit` candles in `range`, oldest first
+    pub async fn get_range(
+        &self,
+        range: &CandleRange,
+        limit: u32,
+    ) -> eyre::Result<Vec<SpotPriceCandle>> {
+        let rows: Vec<CandleRow> = sqlx::query_as(
+            r#"
+            SELECT
+                c.name AS chain,
+                ta.symbol AS token_a_symbol,
+                tb.symbol AS token_b_symbol,
+                EXTRACT(EPOCH FROM sc.bucket_start)::BIGINT AS bucket_start,
+                sc.open, sc.high, sc.low, sc.close, sc.blocks
+            FROM spot_price_candles sc
+            JOIN chains c ON c.id = sc.chain_id
+            JOIN tokens ta ON ta.id = sc.token_a_id
+            JOIN tokens tb ON tb.id = sc.token_b_id
+            WHERE ((ta.symbol = $1 AND tb.symbol = $2)
+                OR (ta.symbol = $2 AND tb.symbol = $1))
+                AND sc.interval_secs = $3
+                AND sc.bucket_start BETWEEN to_timestamp($4) AND to_timestamp($5)
+                AND ($6::TEXT IS NULL OR c.name = $6)
+            ORDER BY sc.bucket_start
+            LIMIT $7
+            "#,
+        )
+        .bind(&range.token_a_symbol)
+        .bind(&range.token_b_symbol)
+        .bind(range.interval.duration().as_secs() as i32)
+        .bind(range.from as f64)
+        .bind(range.to as f64)
+        .bind(range.chain.as_ref().map(|chain| chain.name.to_string()))
+        .bind(limit as i64)
+        .fetch_all(self.pool.as_ref())
+        .observe("candles.get_range")
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let chain = try_chain_from_str(&row.chain, &self.token_configs)?;
+                let token_a =
+                    try_token_from_chain_symbol(&row.token_a_symbol, &chain, &self.token_configs)
+                        .map_err(|e| eyre!("failed to parse token a from db: {e:}"))?;
+                let token_b =
+                    try_token_from_chain_symbol(&row.token_b_symbol, &chain, &self.token_configs)
+                        .map_err(|e| eyre!("failed to parse token b from db: {e:}"))?;
+                Ok(SpotPriceCandle {
+                    chain,
+                    pair: Pair::new(token_a, token_b),
+                    interval: range.interval,
+                    bucket_start: row.bucket_start as u64,
+                    open: row.open,
+                    high: row.high,
+                    low: row.low,
+                    close: row.close,
+                    blocks: row.blocks as u64,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(FromRow)]
+struct CandleRow {
+    chain: String,
+    token_a_symbol: String,
+    token_b_symbol: String,
+    bucket_start: i64,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    blocks: i64,
+}
+
+/// Spawns the aggregation job, which stops once the guard is dropped.
+pub(super) fn spawn_job(repository: CandleRepository, config: &CandlesConfig) -> DropGuard {
+    let cancel = CancellationToken::new();
+    let cancelled = cancel.clone();
+    let (interval, retention) = (config.aggregate_interval(), config.raw_retention());
+
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            select! {
+                () = cancelled.cancelled() => break,
+                _ = ticks.tick() => run_job(&repository, retention).await,
+            }
+        }
+    });
+
+    cancel.drop_guard()
+}
+
+async fn run_job(repository: &CandleRepository, retention: Option<Duration>) {
+    for &interval in CandleInterval::ALL {
+        match repository.aggregate(interval).await {
+            Ok(candles) => debug!(?interval, candles, "Aggregated spot price candles"),
+            Err(e) => {
+                metrics::counter!("kuma_db_candle_job_failures_total", "step" => "aggregate")
+                    .increment(1);
+                warn!(?interval, error = %e, "Failed to aggregate spot price candles");
+                // pruning could drop raw rows that never made it into a candle
+                return;
+            }
+        }
+    }
+
+    if let Some(retention) = retention {
+        match repository.prune_raw(retention).await {
+            Ok(rows) => debug!(rows, "Pruned raw spot prices"),
+            Err(e) => {
+                metrics::counter!("kuma_db_candle_job_failures_total", "step" => "prune")
+                    .increment(1);
+                warn!(error = %e, "Failed to prune raw spot prices");
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! Operator switches that pause strategies or signal emission while kumad keeps running.
+//!
+//! The backend flips them in the `controls` table, whose trigger announces the changed target on
+//! [`CONTROLS_CHANNEL`] (see `migrations/012_controls.sql`). kumad loads the switches on start and
+//! applies changes as they're announced.
+use std::{fmt, str::FromStr, sync::Arc};
+
+use color_eyre::eyre::{self, WrapErr as _, eyre};
+use serde::{Serialize, Serializer};
+use sqlx::{FromRow, PgPool, postgres::PgListener};
+use tracing::instrument;
+
+use super::observe::ObserveQuery as _;
+
+/// Channel changed control targets are announced on
+pub const CONTROLS_CHANNEL: &str = "kuma_controls";
+
+/// What a control pauses.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ControlTarget {
+    /// Signal generation of the strategy with this id, see `StrategyConfig::id`
+    Strategy(String),
+    /// Emission of every strategy's signals. Signals are still generated and stored.
+    Execution,
+}
+
+impl fmt::Display for ControlTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlTarget::Strategy(id) => write!(f, "strategy:{id}"),
+            ControlTarget::Execution => f.write_str("execution"),
+        }
+    }
+}
+
+impl FromStr for ControlTarget {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("strategy", id)) if !id.is_empty() => Ok(ControlTarget::Strategy(id.to_string())),
+            None if s == "execution" => Ok(ControlTarget::Execution),
+            _ => Err(eyre!("invalid control target {s}")),
+        }
+    }
+}
+
+impl Serialize for ControlTarget {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Control {
+    pub target: ControlTarget,
+    pub paused: bool,
+    /// When the control was last changed, in seconds since the unix epoch
+    pub updated_at: u64,
+}
+
+#[derive(Clone)]
+pub struct ControlRepository {
+    pool: Arc<PgPool>,
+}
+
+impl ControlRepository {
+    pub(super) fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+
+    #[instrument(skip(self), fields(%target))]
+    pub async fn set_paused(&self, target: &ControlTarget, paused: bool) -> eyre::Result<Control> {
+        let row: ControlRow = sqlx::query_as(
+            r#"
+            INSERT INTO controls (target, paused) VALUES ($1, $2)
+            ON CONFLICT (target) DO UPDATE SET
+                paused = EXCLUDED.paused,
+                updated_at = NOW()
+            RETURNING target, paused, EXTRACT(EPOCH FROM updated_at)::BIGINT AS updated_at
+            "#,
+        )
+        .bind(target.to_string())
+        .bind(paused)
+        .fetch_one(self.pool.as_ref())
+        .observe("controls.set_paused")
+        .await?;
+
+        row.try_into()
+    }
+
+    #[instrument(skip(self), fields(%target))]
+    pub async fn get(&self, target: &ControlTarget) -> eyre::Result<Option<Control>> {
+        let row: Option<ControlRow> = sqlx::query_as(
+            r#"
+            SELECT target, paused, EXTRACT(EPOCH FROM updated_at)::BIGINT AS updated_at
+            FROM controls
+            WHERE target = $1
+            "#,
+        )
+        .bind(target.to_string())
+        .fetch_optional(self.pool.as_ref())
+        .observe("controls.get")
+        .await?;
+
+        row.map(TryInto::try_into).transpose()
+    }
+
+    /// Every control that was ever set, paused or not
+    #[instrument(skip(self))]
+    pub async fn get_all(&self) -> eyre::Result<Vec<Control>> {
+        let rows: Vec<ControlRow> = sqlx::query_as(
+            r#"
+            SELECT target, paused, EXTRACT(EPOCH FROM updated_at)::BIGINT AS updated_at
+            FROM controls
+            ORDER BY target
+            "#,
+        )
+        .fetch_all(self.pool.as_ref())
+        .observe("controls.get_all")
+        .await?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+}
+
+#[derive(FromRow)]
+struct ControlRow {
+    target: String,
+    paused: bool,
+    updated_at: i64,
+}
+
+impl TryFrom<ControlRow> for Control {
+    type Error = eyre::Report;
+
+    fn try_from(row: ControlRow) -> Result<Self, Self::Error> {
+        Ok(Control {
+            target: row.target.parse()?,
+            paused: row.paused,
+            updated_at: row.updated_at as u64,
+        })
+    }
+}
+
+pub struct ControlListener {
+    listener: PgListener,
+    controls: ControlRepository,
+}
+
+impl ControlListener {
+    pub(super) async fn connect(pool: &PgPool, controls: ControlRepository) -> eyre::Result<Self> {
+        let mut listener = PgListener::connect_with(pool)
+            .await
+            .wrap_err("failed to connect control listener")?;
+        listener
+            .listen(CONTROLS_CHANNEL)
+            .await
+            .wrap_err("failed to listen for control changes")?;
+
+        Ok(Self { listener, controls })
+    }
+
+    /// Waits for the next change to a control.
+    ///
+    /// The connection is re-established on the next call after an error; changes made while it
+    /// was down are missed, so callers should reload every control with
+    /// [`ControlRepository::get_all`] after one.
+    pub async fn recv(&mut self) -> eyre::Result<Control> {
+        loop {
+            let notification = self.listener.recv().await?;
+            let target: ControlTarget = notification.payload().parse()?;
+
+            if let Some(control) = self.controls.get(&target).await? {
+                return Ok(control);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_targets_roundtrip() {
+        for target in [
+            ControlTarget::Strategy("weth-usdc-ethereum-base".to_string()),
+            ControlTarget::Execution,
+        ] {
+            assert_eq!(target.to_string().parse::<ControlTarget>().unwrap(), target);
+        }
+
+        assert!("strategy:".parse::<ControlTarget>().is_err());
+        assert!("executions".parse::<ControlTarget>().is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use color_eyre::eyre;
+use num_bigint::BigUint;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use tracing::instrument;
+
+use crate::{chain::Chain, strategy::PrecomputeCurve};
+
+use super::observe::ObserveQuery as _;
+
+/// Rows per multi-row `INSERT`, each curve binds 7 parameters
+const INSERT_CHUNK_SIZE: usize = 1_000;
+
+#[derive(Clone)]
+pub struct CurveRepository {
+    pool: Arc<PgPool>,
+}
+
+impl CurveRepository {
+    pub(super) fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Inserts the curves of one of `chain`'s precomputes in a single transaction, keeping the
+    /// ones already stored for the same block, pool and direction.
+    #[instrument(skip_all, fields(%chain, count = curves.len()))]
+    pub async fn insert_many(&self, chain: &Chain, curves: &[PrecomputeCurve]) -> eyre::Result<()> {
+        if curves.is_empty() {
+            return Ok(());
+        }
+
+        async {
+            let mut tx = self.pool.begin().await?;
+            for chunk in curves.chunks(INSERT_CHUNK_SIZE) {
+                let mut query = QueryBuilder::<Postgres>::new(
+                    r#"
+                    INSERT INTO precompute_curves (
+                        chain, block_height, pool_id, token_in, token_out, amounts_in, amounts_out
+                    ) "#,
+                );
+                query.push_values(chunk, |mut row, curve| {
+                    row.push_bind(chain.name.to_string())
+                        .push_bind(curve.block_height as i64)
+                        .push_bind(curve.pool_id.to_string())
+                        .push_bind(&curve.token_in)
+                        .push_bind(&curve.token_out)
+                        .push_bind(to_strings(&curve.amounts_in))
+                        .push_bind(to_strings(&curve.amounts_out));
+                });
+                query.push(" ON CONFLICT (chain, block_height, pool_id, token_in) DO NOTHING");
+                query.build().execute(&mut *tx).await?;
+            }
+            tx.commit().await?;
+            eyre::Ok(())
+        }
+        .observe("precompute_curves.insert_many")
+        .await
+    }
+}
+
+fn to_strings(amounts: &[BigUint]) -> Vec<String> {
+    amounts.iter().map(ToString::to_string).collect()
+}
@@ -0,0 +1,719 @@
+//! Transactions broadcast for signals' legs and what they realized.
+//!
+//! `kuma execute` records each leg it submits into the `executions` table (see
+//! `migrations/016_executions.sql`), the backend serves them on `/executions` and sums what they
+//! realized on `/pnl`.
+use std::{
+    collections::{BTreeMap, btree_map::Entry},
+    fmt,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use alloy::primitives::B256;
+use color_eyre::eyre::{self, WrapErr as _, eyre};
+use num_bigint::{BigInt, BigUint};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use tracing::instrument;
+
+use crate::{chain::Chain, config::TokenAddressesForChain};
+
+use super::{
+    observe::ObserveQuery as _, signals::unix_secs, timescale::time_bucket, try_chain_from_str,
+};
+
+/// Columns of [`ExecutionRow`]
+const EXECUTION_COLUMNS: &str = r#"
+    id, signal_id, leg, chain, tx_hash, status, block_height, token_out_symbol,
+    expected_amount_out, amount_out, gas_used, gas_price,
+    EXTRACT(EPOCH FROM created_at)::BIGINT AS created_at
+"#;
+
+/// Which of a signal's swaps a transaction executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionLeg {
+    Slow,
+    Fast,
+}
+
+impl fmt::Display for ExecutionLeg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecutionLeg::Slow => f.write_str("slow"),
+            ExecutionLeg::Fast => f.write_str("fast"),
+        }
+    }
+}
+
+impl FromStr for ExecutionLeg {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "slow" => Ok(ExecutionLeg::Slow),
+            "fast" => Ok(ExecutionLeg::Fast),
+            _ => Err(eyre!("unknown execution leg {s}")),
+        }
+    }
+}
+
+/// Where a broadcast transaction stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionStatus {
+    /// Not mined before `kuma execute` stopped waiting for it
+    Pending,
+    Mined,
+    Reverted,
+}
+
+impl fmt::Display for ExecutionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecutionStatus::Pending => f.write_str("pending"),
+            ExecutionStatus::Mined => f.write_str("mined"),
+            ExecutionStatus::Reverted => f.write_str("reverted"),
+        }
+    }
+}
+
+impl FromStr for ExecutionStatus {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(ExecutionStatus::Pending),
+            "mined" => Ok(ExecutionStatus::Mined),
+            "reverted" => Ok(ExecutionStatus::Reverted),
+            _ => Err(eyre!("unknown execution status {s}")),
+        }
+    }
+}
+
+/// A submitted leg's transaction, as recorded by [`ExecutionRepository::insert`].
+#[derive(Debug, Clone)]
+pub struct NewExecution {
+    pub signal_id: i64,
+    pub leg: ExecutionLeg,
+    pub chain: Chain,
+    pub tx_hash: B256,
+    pub status: ExecutionStatus,
+    pub block_height: Option<u64>,
+    pub token_out_symbol: String,
+    /// Amount out simulated when the signal was generated
+    pub expected_amount_out: BigUint,
+    /// Amount of the bought token received, unknown while pending
+    pub amount_out: Option<BigUint>,
+    pub gas_used: Option<u64>,
+    /// Effective gas price paid, in wei
+    pub gas_price: Option<u128>,
+}
+
+/// A recorded execution of one of a signal's legs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExecutionRecord {
+    pub id: i64,
+    /// Id of the executed signal in the `signals` table
+    pub signal_id: i64,
+    pub leg: ExecutionLeg,
+    pub chain: String,
+    pub tx_hash: B256,
+    pub status: ExecutionStatus,
+    pub block_height: Option<u64>,
+    pub token_out_symbol: String,
+    pub expected_amount_out: BigUint,
+    pub amount_out: Option<BigUint>,
+    pub gas_used: Option<u64>,
+    /// Gas the transaction cost, in wei of the chain's gas token
+    pub gas_cost: Option<BigUint>,
+    /// When the transaction was recorded, in seconds since the unix epoch
+    pub created_at: u64,
+}
+
+/// Criteria for [`ExecutionRepository::get_filtered`]; unset fields match every execution.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionFilter {
+    pub chain: Option<Chain>,
+    pub status: Option<ExecutionStatus>,
+    /// Inclusive lower bound on when the execution was recorded
+    pub since: Option<SystemTime>,
+    /// Exclusive upper bound on when the execution was recorded
+    pub until: Option<SystemTime>,
+}
+
+/// What a pair's executed legs realized on one chain, see [`ExecutionRepository::pnl_summary`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PnlEntry {
+    pub slow_chain: String,
+    pub fast_chain: String,
+    /// The pair's tokens, in alphabetical order
+    pub token_a: String,
+    pub token_b: String,
+    /// Chain the legs were executed on
+    pub chain: String,
+    /// Start of the time bucket in unix seconds, unset in a summary
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bucket_start: Option<u64>,
+    pub mined_legs: u64,
+    pub reverted_legs: u64,
+    /// Legs whose outcome is unknown, left out of the PnL
+    pub pending_legs: u64,
+    /// Gas mined and reverted legs paid, in wei of the chain's gas token
+    pub gas_cost: BigUint,
+    pub tokens: Vec<TokenPnl>,
+}
+
+/// Flows of one token through a chain's mined legs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TokenPnl {
+    pub symbol: String,
+    /// Amount the legs sold
+    pub amount_in: BigUint,
+    /// Amount the legs received
+    pub amount_out: BigUint,
+    /// `amount_out - amount_in`, less the gas cost for the chain's gas token or its wrapped token
+    pub realized: BigInt,
+}
+
+#[derive(Clone)]
+pub struct ExecutionRepository {
+    pool: Arc<PgPool>,
+    tokens_config: Arc<TokenAddressesForChain>,
+    timescale: bool,
+}
+
+impl ExecutionRepository {
+    pub(super) fn new(
+        pool: Arc<PgPool>,
+        tokens_config: Arc<TokenAddressesForChain>,
+        timescale: bool,
+    ) -> Self {
+        Self {
+            pool,
+            tokens_config,
+            timescale,
+        }
+    }
+
+    /// Records a submitted leg, returning its id.
+    #[instrument(skip_all, fields(signal_id = execution.signal_id, leg = %execution.leg))]
+    pub async fn insert(&self, execution: &NewExecution) -> eyre::Result<i64> {
+        let id = sqlx::query_scalar(
+            r#"
+            INSERT INTO executions (
+                signal_id, leg, chain, tx_hash, status, block_height, token_out_symbol,
+                expected_amount_out, amount_out, gas_used, gas_price
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            RETURNING id
+            "#,
+        )
+        .bind(execution.signal_id)
+        .bind(execution.leg.to_string())
+        .bind(execution.chain.name.to_string())
+        .bind(execution.tx_hash.to_string())
+        .bind(execution.status.to_string())
+        .bind(execution.block_height.map(|height| height as i64))
+        .bind(&execution.token_out_symbol)
+        .bind(execution.expected_amount_out.to_string())
+        .bind(execution.amount_out.as_ref().map(ToString::to_string))
+        .bind(execution.gas_used.map(|gas| gas as i64))
+        .bind(execution.gas_price.map(|price| price.to_string()))
+        .fetch_one(self.pool.as_ref())
+        .observe("executions.insert")
+        .await?;
+
+        Ok(id)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_by_id(&self, id: i64) -> eyre::Result<Option<ExecutionRecord>> {
+        let query = format!("SELECT {EXECUTION_COLUMNS} FROM executions WHERE id = $1");
+        let row: Option<ExecutionRow> = sqlx::query_as(&query)
+            .bind(id)
+            .fetch_optional(self.pool.as_ref())
+            .observe("executions.get_by_id")
+            .await?;
+
+        row.map(ExecutionRecord::try_from).transpose()
+    }
+
+    /// Executions of a signal's legs, slow leg first
+    #[instrument(skip(self))]
+    pub async fn get_by_signal_id(&self, signal_id: i64) -> eyre::Result<Vec<ExecutionRecord>> {
+        let query = format!(
+            r#"
+            SELECT {EXECUTION_COLUMNS}
+            FROM executions
+            WHERE signal_id = $1
+            ORDER BY leg DESC, created_at
+            "#
+        );
+        let rows: Vec<ExecutionRow> = sqlx::query_as(&query)
+            .bind(signal_id)
+            .fetch_all(self.pool.as_ref())
+            .observe("executions.get_by_signal_id")
+            .await?;
+
+        rows.into_iter().map(ExecutionRecord::try_from).collect()
+    }
+
+    #[instrument(skip(self))]
+    pub async fn count_filtered(&self, filter: &ExecutionFilter) -> eyre::Result<u64> {
+        let mut query = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM executions");
+        push_filter(&mut query, filter)?;
+
+        let count: i64 = query
+            .build_query_scalar()
+            .fetch_one(self.pool.as_ref())
+            .observe("executions.count_filtered")
+            .await?;
+
+        Ok(count as u64)
+    }
+
+    /// Executions matching `filter`, newest first
+    #[instrument(skip(self))]
+    pub async fn get_filtered(
+        &self,
+        filter: &ExecutionFilter,
+        limit: u32,
+        offset: u32,
+    ) -> eyre::Result<Vec<ExecutionRecord>> {
+        let mut query =
+            QueryBuilder::<Postgres>::new(format!("SELECT {EXECUTION_COLUMNS} FROM executions"));
+        push_filter(&mut query, filter)?;
+        query
+            .push(" ORDER BY created_at DESC, id DESC LIMIT ")
+            .push_bind(limit as i64)
+            .push(" OFFSET ")
+            .push_bind(offset as i64);
+
+        let rows: Vec<ExecutionRow> = query
+            .build_query_as()
+            .fetch_all(self.pool.as_ref())
+            .observe("executions.get_filtered")
+            .await?;
+
+        rows.into_iter().map(ExecutionRecord::try_from).collect()
+    }
+
+    /// PnL of executions recorded in `[since, until)` per pair and chain of their legs.
+    #[instrument(skip(self))]
+    pub async fn pnl_summary(
+        &self,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> eyre::Result<Vec<PnlEntry>> {
+        self.pnl(None, since, until).await
+    }
+
+    /// [`pnl_summary`](Self::pnl_summary) in `bucket` wide time buckets, oldest first.
+    #[instrument(skip(self))]
+    pub async fn pnl_timeseries(
+        &self,
+        bucket: Duration,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> eyre::Result<Vec<PnlEntry>> {
+        self.pnl(Some(bucket), since, until).await
+    }
+
+    /// Sums legs per pair, chain and `bucket` if given. Mined legs count what they received minus
+    /// what they sold, reverted ones only the gas they paid.
+    async fn pnl(
+        &self,
+        bucket: Option<Duration>,
+        since: Option<SystemTime>,
+        until: Option<SystemTime>,
+    ) -> eyre::Result<Vec<PnlEntry>> {
+        // a leg sells what its swap in the signal sold, executions only record what it received
+        let query = format!(
+            r#"
+            SELECT
+                CASE WHEN $1::FLOAT8 IS NULL THEN NULL
+                    ELSE EXTRACT(EPOCH FROM {bucket})::BIGINT
+                END AS bucket_start,
+                sc.name AS slow_chain,
+                fc.name AS fast_chain,
+                LEAST(sti.symbol, sto.symbol) AS token_a,
+                GREATEST(sti.symbol, sto.symbol) AS token_b,
+                e.chain,
+                e.status,
+                CASE e.leg WHEN 'slow' THEN sti.symbol ELSE fti.symbol END AS token_in,
+                e.token_out_symbol AS token_out,
+                COUNT(*) AS legs,
+                SUM(
+                    CASE e.leg
+                        WHEN 'slow' THEN s.slow_swap_amount_in
+                        ELSE s.fast_swap_amount_in
+                    END::NUMERIC
+                )::TEXT AS amount_in,
+                COALESCE(SUM(e.amount_out::NUMERIC), 0)::TEXT AS amount_out,
+                COALESCE(SUM(e.gas_used * e.gas_price::NUMERIC), 0)::TEXT AS gas_cost
+            FROM executions e
+            JOIN signals s ON s.id = e.signal_id
+            JOIN chains sc ON sc.id = s.slow_chain_id
+            JOIN chains fc ON fc.id = s.fast_chain_id
+            JOIN tokens sti ON sti.id = s.slow_swap_token_in_id
+            JOIN tokens sto ON sto.id = s.slow_swap_token_out_id
+            JOIN tokens fti ON fti.id = s.fast_swap_token_in_id
+            WHERE ($2::BIGINT IS NULL OR e.created_at >= to_timestamp($2))
+                AND ($3::BIGINT IS NULL OR e.created_at < to_timestamp($3))
+            GROUP BY 1, 2, 3, 4, 5, 6, 7, 8, 9
+            ORDER BY 1, 2, 3, 4, 5, 6
+            "#,
+            bucket = time_bucket(self.timescale, "e.created_at", 1),
+        );
+        let rows: Vec<PnlRow> = sqlx::query_as(&query)
+            .bind(bucket.map(|bucket| bucket.as_secs_f64()))
+            .bind(since.map(unix_secs).transpose()?)
+            .bind(until.map(unix_secs).transpose()?)
+            .fetch_all(self.pool.as_ref())
+            .observe("executions.pnl")
+            .await?;
+
+        let mut entries = BTreeMap::new();
+        for row in rows {
+            let key = (
+                row.bucket_start,
+                row.slow_chain.clone(),
+                row.fast_chain.clone(),
+                row.token_a.clone(),
+                row.token_b.clone(),
+                row.chain.clone(),
+            );
+            let entry = match entries.entry(key) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let chain = try_chain_from_str(&row.chain, &self.tokens_config)?;
+                    entry.insert(PnlAccumulator::new(&row, chain.gas_token.symbol))
+                }
+            };
+            entry.add(row)?;
+        }
+
+        Ok(entries.into_values().map(PnlAccumulator::finish).collect())
+    }
+}
+
+/// Sums the rows of one [`PnlEntry`].
+struct PnlAccumulator {
+    entry: PnlEntry,
+    gas_token: String,
+    /// Amounts in and out per token symbol
+    tokens: BTreeMap<String, (BigUint, BigUint)>,
+}
+
+impl PnlAccumulator {
+    fn new(row: &PnlRow, gas_token: String) -> Self {
+        Self {
+            entry: PnlEntry {
+                slow_chain: row.slow_chain.clone(),
+                fast_chain: row.fast_chain.clone(),
+                token_a: row.token_a.clone(),
+                token_b: row.token_b.clone(),
+                chain: row.chain.clone(),
+                bucket_start: row.bucket_start.map(|start| start as u64),
+                mined_legs: 0,
+                reverted_legs: 0,
+                pending_legs: 0,
+                gas_cost: BigUint::ZERO,
+                tokens: Vec::new(),
+            },
+            gas_token,
+            tokens: BTreeMap::new(),
+        }
+    }
+
+    fn add(&mut self, row: PnlRow) -> eyre::Result<()> {
+        let parse = |amount: &str| {
+            BigUint::from_str(amount).map_err(|e| eyre!("invalid amount {amount}: {e}"))
+        };
+        let legs = row.legs as u64;
+
+        match row.status.parse()? {
+            ExecutionStatus::Pending => {
+                self.entry.pending_legs += legs;
+                return Ok(());
+            }
+            // reverted legs kept what they sold but paid for gas
+            ExecutionStatus::Reverted => self.entry.reverted_legs += legs,
+            ExecutionStatus::Mined => {
+                self.entry.mined_legs += legs;
+                let amount_in = parse(&row.amount_in)?;
+                self.tokens.entry(row.token_in).or_default().0 += amount_in;
+                let amount_out = parse(&row.amount_out)?;
+                self.tokens.entry(row.token_out).or_default().1 += amount_out;
+            }
+        }
+        self.entry.gas_cost += parse(&row.gas_cost)?;
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> PnlEntry {
+        let wrapped_gas_token = format!("W{}", self.gas_token);
+        let gas_token = if self.tokens.contains_key(&wrapped_gas_token) {
+            wrapped_gas_token
+        } else {
+            self.gas_token
+        };
+        if self.entry.gas_cost > BigUint::ZERO {
+            self.tokens.entry(gas_token.clone()).or_default();
+        }
+
+        self.entry.tokens = self
+            .tokens
+            .into_iter()
+            .map(|(symbol, (amount_in, amount_out))| {
+                let mut realized =
+                    BigInt::from(amount_out.clone()) - BigInt::from(amount_in.clone());
+                if symbol == gas_token {
+                    realized -= BigInt::from(self.entry.gas_cost.clone());
+                }
+                TokenPnl {
+                    symbol,
+                    amount_in,
+                    amount_out,
+                    realized,
+                }
+            })
+            .collect();
+        self.entry
+    }
+}
+
+fn push_filter(
+    query: &mut QueryBuilder<'_, Postgres>,
+    filter: &ExecutionFilter,
+) -> eyre::Result<()> {
+    query.push(" WHERE TRUE");
+
+    if let Some(chain) = &filter.chain {
+        query
+            .push(" AND chain = ")
+            .push_bind(chain.name.to_string());
+    }
+    if let Some(status) = filter.status {
+        query.push(" AND status = ").push_bind(status.to_string());
+    }
+    if let Some(since) = filter.since {
+        query
+            .push(" AND created_at >= to_timestamp(")
+            .push_bind(unix_secs(since)?)
+            .push(")");
+    }
+    if let Some(until) = filter.until {
+        query
+            .push(" AND created_at < to_timestamp(")
+            .push_bind(unix_secs(until)?)
+            .push(")");
+    }
+
+    Ok(())
+}
+
+#[derive(FromRow)]
+struct ExecutionRow {
+    id: i64,
+    signal_id: i64,
+    leg: String,
+    chain: String,
+    tx_hash: String,
+    status: String,
+    block_height: Option<i64>,
+    token_out_symbol: String,
+    expected_amount_out: String,
+    amount_out: Option<String>,
+    gas_used: Option<i64>,
+    gas_price: Option<String>,
+    created_at: i64,
+}
+
+#[derive(FromRow)]
+struct PnlRow {
+    bucket_start: Option<i64>,
+    slow_chain: String,
+    fast_chain: String,
+    token_a: String,
+    token_b: String,
+    chain: String,
+    status: String,
+    token_in: String,
+    token_out: String,
+    legs: i64,
+    amount_in: String,
+    amount_out: String,
+    gas_cost: String,
+}
+
+impl TryFrom<ExecutionRow> for ExecutionRecord {
+    type Error = eyre::Report;
+
+    fn try_from(row: ExecutionRow) -> eyre::Result<Self> {
+        let parse = |amount: &str| {
+            BigUint::from_str(amount).map_err(|e| eyre!("invalid amount {amount}: {e}"))
+        };
+        let gas_used = row.gas_used.map(|gas| gas as u64);
+        let gas_price = row.gas_price.as_deref().map(parse).transpose()?;
+
+        Ok(Self {
+            id: row.id,
+            signal_id: row.signal_id,
+            leg: row.leg.parse()?,
+            chain: row.chain,
+            tx_hash: B256::from_str(&row.tx_hash).wrap_err("invalid transaction hash")?,
+            status: row.status.parse()?,
+            block_height: row.block_height.map(|height| height as u64),
+            token_out_symbol: row.token_out_symbol,
+            expected_amount_out: parse(&row.expected_amount_out)?,
+            amount_out: row.amount_out.as_deref().map(parse).transpose()?,
+            gas_used,
+            gas_cost: gas_used
+                .zip(gas_price)
+                .map(|(gas, price)| BigUint::from(gas) * price),
+            created_at: row.created_at as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row() -> ExecutionRow {
+        ExecutionRow {
+            id: 7,
+            signal_id: 42,
+            leg: "fast".to_string(),
+            chain: "base".to_string(),
+            tx_hash: B256::repeat_byte(0xab).to_string(),
+            status: "mined".to_string(),
+            block_height: Some(1_000),
+            token_out_symbol: "WETH".to_string(),
+            expected_amount_out: "1000000000000000000".to_string(),
+            amount_out: Some("999000000000000000".to_string()),
+            gas_used: Some(150_000),
+            gas_price: Some("2000000000".to_string()),
+            created_at: 1_718_000_000,
+        }
+    }
+
+    #[test]
+    fn record_from_row_computes_the_gas_cost() {
+        let record = ExecutionRecord::try_from(row()).unwrap();
+
+        assert_eq!(record.leg, ExecutionLeg::Fast);
+        assert_eq!(record.status, ExecutionStatus::Mined);
+        assert_eq!(record.tx_hash, B256::repeat_byte(0xab));
+        assert_eq!(
+            record.amount_out,
+            Some(BigUint::from(999_000_000_000_000_000u64))
+        );
+        assert_eq!(record.gas_cost, Some(BigUint::from(300_000_000_000_000u64)));
+    }
+
+    #[test]
+    fn pending_record_has_no_outcome() {
+        let record = ExecutionRecord::try_from(ExecutionRow {
+            status: "pending".to_string(),
+            block_height: None,
+            amount_out: None,
+            gas_used: None,
+            gas_price: None,
+            ..row()
+        })
+        .unwrap();
+
+        assert_eq!(record.status, ExecutionStatus::Pending);
+        assert_eq!(record.amount_out, None);
+        assert_eq!(record.gas_cost, None);
+    }
+
+    fn pnl_row(
+        status: &str,
+        leg_in: &str,
+        leg_out: &str,
+        amount_in: &str,
+        amount_out: &str,
+    ) -> PnlRow {
+        PnlRow {
+            bucket_start: None,
+            slow_chain: "ethereum".to_string(),
+            fast_chain: "base".to_string(),
+            token_a: "USDC".to_string(),
+            token_b: "WETH".to_string(),
+            chain: "base".to_string(),
+            status: status.to_string(),
+            token_in: leg_in.to_string(),
+            token_out: leg_out.to_string(),
+            legs: 2,
+            amount_in: amount_in.to_string(),
+            amount_out: amount_out.to_string(),
+            gas_cost: "1000".to_string(),
+        }
+    }
+
+    fn token<'a>(entry: &'a PnlEntry, symbol: &str) -> &'a TokenPnl {
+        entry
+            .tokens
+            .iter()
+            .find(|token| token.symbol == symbol)
+            .unwrap()
+    }
+
+    #[test]
+    fn pnl_takes_gas_off_the_wrapped_gas_token() {
+        let first = pnl_row("mined", "USDC", "WETH", "3000000000", "1000000");
+        let mut pnl = PnlAccumulator::new(&first, "ETH".to_string());
+        pnl.add(first).unwrap();
+        pnl.add(pnl_row("mined", "WETH", "USDC", "400000", "1200000000"))
+            .unwrap();
+        pnl.add(pnl_row("reverted", "USDC", "WETH", "5000000000", "0"))
+            .unwrap();
+        pnl.add(pnl_row("pending", "USDC", "WETH", "5000000000", "0"))
+            .unwrap();
+        let entry = pnl.finish();
+
+        assert_eq!(
+            (entry.mined_legs, entry.reverted_legs, entry.pending_legs),
+            (4, 2, 2)
+        );
+        assert_eq!(entry.gas_cost, BigUint::from(3_000u32));
+        assert_eq!(entry.tokens.len(), 2);
+        assert_eq!(
+            token(&entry, "USDC").realized,
+            BigInt::from(-1_800_000_000i64)
+        );
+        // 1000000 received - 400000 sold - 3000 gas
+        assert_eq!(token(&entry, "WETH").realized, BigInt::from(597_000));
+    }
+
+    #[test]
+    fn pnl_reports_gas_in_an_untraded_gas_token() {
+        let first = pnl_row("mined", "USDC", "DAI", "100", "101");
+        let mut pnl = PnlAccumulator::new(&first, "ETH".to_string());
+        pnl.add(first).unwrap();
+        let entry = pnl.finish();
+
+        assert_eq!(token(&entry, "DAI").realized, BigInt::from(101));
+        assert_eq!(token(&entry, "USDC").realized, BigInt::from(-100));
+        let gas = token(&entry, "ETH");
+        assert_eq!(gas.amount_in, BigUint::ZERO);
+        assert_eq!(gas.realized, BigInt::from(-1_000));
+    }
+
+    #[test]
+    fn unknown_status_is_rejected() {
+        assert!(
+            ExecutionRecord::try_from(ExecutionRow {
+                status: "dropped".to_string(),
+                ..row()
+            })
+            .is_err()
+        );
+    }
+}
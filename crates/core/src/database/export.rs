@@ -0,0 +1,694 @@
+//! Parquet exports of stored data for offline research.
+//!
+//! Rows inserted within a time range are written to hive-style partitions,
+//! `<dir>/<dataset>/<chain column>=<chain>/date=<yyyy-mm-dd>/part-0.parquet`, which duckdb and
+//! pandas read back as a single table. The chain a partition is keyed by is left out of its files
+//! so it doesn't clash with the partition column.
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    fs::{self, File},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use arrow::{
+    array::{
+        ArrayRef, Float64Array, Int64Array, ListBuilder, StringArray, StringBuilder,
+        TimestampSecondArray,
+    },
+    datatypes::{DataType, Field, Schema, TimeUnit},
+    record_batch::RecordBatch,
+};
+use color_eyre::eyre::{self, WrapErr as _, eyre};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use tracing::info;
+
+use crate::strategy::PrecomputeCurve;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// A table that can be exported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dataset {
+    SpotPrices,
+    Signals,
+    /// Stored when kumad runs with `record_precompute_curves`
+    PrecomputeCurves,
+    /// Legs `kuma execute` broadcast
+    Executions,
+}
+
+impl Dataset {
+    pub const ALL: &'static [Dataset] = &[
+        Dataset::SpotPrices,
+        Dataset::Signals,
+        Dataset::PrecomputeCurves,
+        Dataset::Executions,
+    ];
+
+    /// Column holding the chain the dataset is partitioned by
+    fn partition_column(self) -> &'static str {
+        match self {
+            Dataset::SpotPrices | Dataset::PrecomputeCurves | Dataset::Executions => "chain",
+            Dataset::Signals => "slow_chain",
+        }
+    }
+}
+
+impl Display for Dataset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dataset::SpotPrices => write!(f, "spot_prices"),
+            Dataset::Signals => write!(f, "signals"),
+            Dataset::PrecomputeCurves => write!(f, "precompute_curves"),
+            Dataset::Executions => write!(f, "executions"),
+        }
+    }
+}
+
+impl FromStr for Dataset {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> eyre::Result<Self> {
+        match s {
+            "spot_prices" => Ok(Dataset::SpotPrices),
+            "signals" => Ok(Dataset::Signals),
+            "precompute_curves" => Ok(Dataset::PrecomputeCurves),
+            "executions" => Ok(Dataset::Executions),
+            other => Err(eyre!(
+                "unknown dataset `{other}`, expected `spot_prices`, `signals`, \
+                 `precompute_curves` or `executions`"
+            )),
+        }
+    }
+}
+
+/// Which rows to export and where to.
+#[derive(Debug, Clone)]
+pub struct ExportRequest {
+    pub datasets: Vec<Dataset>,
+    /// Start of the range of insertion times, in seconds since the unix epoch
+    pub from: u64,
+    /// Inclusive end of the range of insertion times, in seconds since the unix epoch
+    pub to: u64,
+    pub dir: PathBuf,
+}
+
+/// Files written by an export.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExportSummary {
+    pub files: Vec<PathBuf>,
+    pub rows: u64,
+}
+
+/// Writes the rows of each requested dataset inserted within the range to Parquet files.
+pub(super) async fn export(pool: &PgPool, request: &ExportRequest) -> eyre::Result<ExportSummary> {
+    eyre::ensure!(
+        request.from <= request.to,
+        "export range starts after it ends"
+    );
+
+    let mut summary = ExportSummary::default();
+    for &dataset in &request.datasets {
+        let partitions = match dataset {
+            Dataset::SpotPrices => spot_price_partitions(pool, request).await?,
+            Dataset::Signals => signal_partitions(pool, request).await?,
+            Dataset::PrecomputeCurves => curve_partitions(pool, request).await?,
+            Dataset::Executions => execution_partitions(pool, request).await?,
+        };
+        write_partitions(&request.dir, dataset, partitions, &mut summary).await?;
+    }
+
+    Ok(summary)
+}
+
+/// Writes curves precomputed outside of kumad, e.g. from recorded blocks, the way exports of
+/// [`Dataset::PrecomputeCurves`] are. Each curve comes with the name of its chain and when its
+/// block was received, in seconds since the unix epoch.
+pub async fn export_curves(
+    curves: Vec<(String, i64, PrecomputeCurve)>,
+    dir: &Path,
+) -> eyre::Result<ExportSummary> {
+    let rows = curves
+        .into_iter()
+        .map(|(chain, created_at, curve)| CurveExportRow {
+            chain,
+            block_height: curve.block_height as i64,
+            pool_id: curve.pool_id.to_string(),
+            token_in: curve.token_in,
+            token_out: curve.token_out,
+            amounts_in: curve.amounts_in.iter().map(ToString::to_string).collect(),
+            amounts_out: curve.amounts_out.iter().map(ToString::to_string).collect(),
+            created_at,
+        })
+        .collect();
+
+    let mut summary = ExportSummary::default();
+    let partitions = group_by_partition(rows, |row| (&row.chain, row.created_at), curve_batch)?;
+    write_partitions(dir, Dataset::PrecomputeCurves, partitions, &mut summary).await?;
+
+    Ok(summary)
+}
+
+async fn write_partitions(
+    dir: &Path,
+    dataset: Dataset,
+    partitions: Partitions,
+    summary: &mut ExportSummary,
+) -> eyre::Result<()> {
+    for ((chain, day), batch) in partitions {
+        let dir = dir
+            .join(dataset.to_string())
+            .join(format!("{}={chain}", dataset.partition_column()))
+            .join(format!("date={}", format_day(day)));
+        let rows = batch.num_rows() as u64;
+        let path = tokio::task::spawn_blocking(move || write_partition(&dir, &batch))
+            .await
+            .wrap_err("parquet writer panicked")??;
+
+        info!(%dataset, path = %path.display(), rows, "Exported partition");
+        summary.rows += rows;
+        summary.files.push(path);
+    }
+
+    Ok(())
+}
+
+/// Partition of an export, by chain name and days since the unix epoch
+type Partitions = BTreeMap<(String, i64), RecordBatch>;
+
+#[derive(FromRow)]
+struct SpotPriceExportRow {
+    chain: String,
+    token_a: String,
+    token_b: String,
+    block_height: i64,
+    block_timestamp: Option<i64>,
+    min_price: f64,
+    max_price: f64,
+    min_pool_id: String,
+    max_pool_id: String,
+    created_at: i64,
+}
+
+async fn spot_price_partitions(pool: &PgPool, request: &ExportRequest) -> eyre::Result<Partitions> {
+    let rows: Vec<SpotPriceExportRow> = sqlx::query_as(
+        r#"
+        SELECT
+            c.name AS chain,
+            ta.symbol AS token_a,
+            tb.symbol AS token_b,
+            sp.block_height,
+            EXTRACT(EPOCH FROM b.timestamp)::BIGINT AS block_timestamp,
+            sp.min_price, sp.max_price, sp.min_pool_id, sp.max_pool_id,
+            EXTRACT(EPOCH FROM sp.created_at)::BIGINT AS created_at
+        FROM spot_prices sp
+        JOIN chains c ON c.id = sp.chain_id
+        JOIN tokens ta ON ta.id = sp.token_a_id
+        JOIN tokens tb ON tb.id = sp.token_b_id
+        LEFT JOIN blocks b ON b.chain = c.name AND b.height = sp.block_height
+        WHERE sp.created_at BETWEEN to_timestamp($1) AND to_timestamp($2)
+        ORDER BY c.name, sp.created_at
+        "#,
+    )
+    .bind(request.from as f64)
+    .bind(request.to as f64)
+    .fetch_all(pool)
+    .await
+    .wrap_err("failed to read spot prices")?;
+
+    group_by_partition(
+        rows,
+        |row| (&row.chain, row.created_at),
+        |rows| {
+            let schema = Schema::new(vec![
+                Field::new("token_a", DataType::Utf8, false),
+                Field::new("token_b", DataType::Utf8, false),
+                Field::new("block_height", DataType::Int64, false),
+                timestamp_field("block_timestamp", true),
+                Field::new("min_price", DataType::Float64, false),
+                Field::new("max_price", DataType::Float64, false),
+                Field::new("min_pool_id", DataType::Utf8, false),
+                Field::new("max_pool_id", DataType::Utf8, false),
+                timestamp_field("created_at", false),
+            ]);
+            let columns: Vec<ArrayRef> = vec![
+                strings(rows, |row| &row.token_a),
+                strings(rows, |row| &row.token_b),
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|row| row.block_height),
+                )),
+                timestamps(rows.iter().map(|row| row.block_timestamp)),
+                Arc::new(Float64Array::from_iter_values(
+                    rows.iter().map(|row| row.min_price),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    rows.iter().map(|row| row.max_price),
+                )),
+                strings(rows, |row| &row.min_pool_id),
+                strings(rows, |row| &row.max_pool_id),
+                timestamps(rows.iter().map(|row| Some(row.created_at))),
+            ];
+            RecordBatch::try_new(Arc::new(schema), columns).map_err(Into::into)
+        },
+    )
+}
+
+#[derive(FromRow)]
+struct SignalExportRow {
+    slow_chain: String,
+    slow_height: i64,
+    slow_block_timestamp: Option<i64>,
+    slow_pool_id: String,
+    fast_chain: String,
+    fast_height: i64,
+    fast_block_timestamp: Option<i64>,
+    fast_pool_id: String,
+    slow_swap_token_in: String,
+    slow_swap_token_out: String,
+    slow_swap_amount_in: String,
+    slow_swap_amount_out: String,
+    slow_swap_gas_cost: String,
+    fast_swap_token_in: String,
+    fast_swap_token_out: String,
+    fast_swap_amount_in: String,
+    fast_swap_amount_out: String,
+    fast_swap_gas_cost: String,
+    surplus_a: String,
+    surplus_b: String,
+    expected_profit_a: String,
+    expected_profit_b: String,
+    max_slippage_bps: i64,
+    congestion_risk_discount_bps: i64,
+    created_at: i64,
+}
+
+/// Signals are partitioned by their slow chain. Token amounts stay decimal strings since they
+/// overflow 64-bit integers.
+async fn signal_partitions(pool: &PgPool, request: &ExportRequest) -> eyre::Result<Partitions> {
+    let rows: Vec<SignalExportRow> = sqlx::query_as(
+        r#"
+        SELECT
+            sc.name AS slow_chain, s.slow_height,
+            EXTRACT(EPOCH FROM sb.timestamp)::BIGINT AS slow_block_timestamp,
+            s.slow_pool_id,
+            fc.name AS fast_chain, s.fast_height,
+            EXTRACT(EPOCH FROM fb.timestamp)::BIGINT AS fast_block_timestamp,
+            s.fast_pool_id,
+            sti.symbol AS slow_swap_token_in, sto.symbol AS slow_swap_token_out,
+            s.slow_swap_amount_in, s.slow_swap_amount_out, s.slow_swap_gas_cost,
+            fti.symbol AS fast_swap_token_in, fto.symbol AS fast_swap_token_out,
+            s.fast_swap_amount_in, s.fast_swap_amount_out, s.fast_swap_gas_cost,
+            s.surplus_a, s.surplus_b, s.expected_profit_a, s.expected_profit_b,
+            s.max_slippage_bps::BIGINT AS max_slippage_bps,
+            s.congestion_risk_discount_bps::BIGINT AS congestion_risk_discount_bps,
+            EXTRACT(EPOCH FROM s.created_at)::BIGINT AS created_at
+        FROM signals s
+        JOIN chains sc ON sc.id = s.slow_chain_id
+        JOIN chains fc ON fc.id = s.fast_chain_id
+        JOIN tokens sti ON sti.id = s.slow_swap_token_in_id
+        JOIN tokens sto ON sto.id = s.slow_swap_token_out_id
+        JOIN tokens fti ON fti.id = s.fast_swap_token_in_id
+        JOIN tokens fto ON fto.id = s.fast_swap_token_out_id
+        LEFT JOIN blocks sb ON sb.chain = sc.name AND sb.height = s.slow_height
+        LEFT JOIN blocks fb ON fb.chain = fc.name AND fb.height = s.fast_height
+        WHERE s.created_at BETWEEN to_timestamp($1) AND to_timestamp($2)
+        ORDER BY sc.name, s.created_at
+        "#,
+    )
+    .bind(request.from as f64)
+    .bind(request.to as f64)
+    .fetch_all(pool)
+    .await
+    .wrap_err("failed to read signals")?;
+
+    group_by_partition(
+        rows,
+        |row| (&row.slow_chain, row.created_at),
+        |rows| {
+            let mut fields = Vec::new();
+            let mut columns: Vec<ArrayRef> = Vec::new();
+            let mut string_column = |name: &str, value: fn(&SignalExportRow) -> &String| {
+                fields.push(Field::new(name, DataType::Utf8, false));
+                columns.push(strings(rows, value));
+            };
+            string_column("slow_pool_id", |row| &row.slow_pool_id);
+            string_column("fast_chain", |row| &row.fast_chain);
+            string_column("fast_pool_id", |row| &row.fast_pool_id);
+            string_column("slow_swap_token_in", |row| &row.slow_swap_token_in);
+            string_column("slow_swap_token_out", |row| &row.slow_swap_token_out);
+            string_column("slow_swap_amount_in", |row| &row.slow_swap_amount_in);
+            string_column("slow_swap_amount_out", |row| &row.slow_swap_amount_out);
+            string_column("slow_swap_gas_cost", |row| &row.slow_swap_gas_cost);
+            string_column("fast_swap_token_in", |row| &row.fast_swap_token_in);
+            string_column("fast_swap_token_out", |row| &row.fast_swap_token_out);
+            string_column("fast_swap_amount_in", |row| &row.fast_swap_amount_in);
+            string_column("fast_swap_amount_out", |row| &row.fast_swap_amount_out);
+            string_column("fast_swap_gas_cost", |row| &row.fast_swap_gas_cost);
+            string_column("surplus_a", |row| &row.surplus_a);
+            string_column("surplus_b", |row| &row.surplus_b);
+            string_column("expected_profit_a", |row| &row.expected_profit_a);
+            string_column("expected_profit_b", |row| &row.expected_profit_b);
+
+            let mut int_column = |name: &str, value: fn(&SignalExportRow) -> i64| {
+                fields.push(Field::new(name, DataType::Int64, false));
+                columns.push(Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(value),
+                )));
+            };
+            int_column("slow_height", |row| row.slow_height);
+            int_column("fast_height", |row| row.fast_height);
+            int_column("max_slippage_bps", |row| row.max_slippage_bps);
+            int_column("congestion_risk_discount_bps", |row| {
+                row.congestion_risk_discount_bps
+            });
+
+            let mut timestamp_column =
+                |name: &str, nullable, value: fn(&SignalExportRow) -> Option<i64>| {
+                    fields.push(timestamp_field(name, nullable));
+                    columns.push(timestamps(rows.iter().map(value)));
+                };
+            timestamp_column("slow_block_timestamp", true, |row| row.slow_block_timestamp);
+            timestamp_column("fast_block_timestamp", true, |row| row.fast_block_timestamp);
+            timestamp_column("created_at", false, |row| Some(row.created_at));
+
+            RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(Into::into)
+        },
+    )
+}
+
+#[derive(FromRow)]
+struct CurveExportRow {
+    chain: String,
+    block_height: i64,
+    pool_id: String,
+    token_in: String,
+    token_out: String,
+    amounts_in: Vec<String>,
+    amounts_out: Vec<String>,
+    created_at: i64,
+}
+
+async fn curve_partitions(pool: &PgPool, request: &ExportRequest) -> eyre::Result<Partitions> {
+    let rows: Vec<CurveExportRow> = sqlx::query_as(
+        r#"
+        SELECT
+            chain, block_height, pool_id, token_in, token_out, amounts_in, amounts_out,
+            EXTRACT(EPOCH FROM created_at)::BIGINT AS created_at
+        FROM precompute_curves
+        WHERE created_at BETWEEN to_timestamp($1) AND to_timestamp($2)
+        ORDER BY chain, created_at, pool_id, token_in
+        "#,
+    )
+    .bind(request.from as f64)
+    .bind(request.to as f64)
+    .fetch_all(pool)
+    .await
+    .wrap_err("failed to read precompute curves")?;
+
+    group_by_partition(rows, |row| (&row.chain, row.created_at), curve_batch)
+}
+
+/// Curves keep one row per block, pool and direction, their amounts as lists of decimal strings.
+fn curve_batch(rows: &[CurveExportRow]) -> eyre::Result<RecordBatch> {
+    let amounts = |value: fn(&CurveExportRow) -> &Vec<String>| -> ArrayRef {
+        let mut builder = ListBuilder::new(StringBuilder::new());
+        for row in rows {
+            for amount in value(row) {
+                builder.values().append_value(amount);
+            }
+            builder.append(true);
+        }
+        Arc::new(builder.finish())
+    };
+    let amounts_field = |name: &str| {
+        Field::new(
+            name,
+            DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+            false,
+        )
+    };
+
+    let schema = Schema::new(vec![
+        Field::new("block_height", DataType::Int64, false),
+        Field::new("pool_id", DataType::Utf8, false),
+        Field::new("token_in", DataType::Utf8, false),
+        Field::new("token_out", DataType::Utf8, false),
+        amounts_field("amounts_in"),
+        amounts_field("amounts_out"),
+        timestamp_field("created_at", false),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int64Array::from_iter_values(
+            rows.iter().map(|row| row.block_height),
+        )),
+        strings(rows, |row| &row.pool_id),
+        strings(rows, |row| &row.token_in),
+        strings(rows, |row| &row.token_out),
+        amounts(|row| &row.amounts_in),
+        amounts(|row| &row.amounts_out),
+        timestamps(rows.iter().map(|row| Some(row.created_at))),
+    ];
+    RecordBatch::try_new(Arc::new(schema), columns).map_err(Into::into)
+}
+
+#[derive(FromRow)]
+struct ExecutionExportRow {
+    id: i64,
+    signal_id: i64,
+    leg: String,
+    chain: String,
+    tx_hash: String,
+    status: String,
+    block_height: Option<i64>,
+    token_out_symbol: String,
+    expected_amount_out: String,
+    amount_out: Option<String>,
+    gas_used: Option<i64>,
+    gas_price: Option<String>,
+    created_at: i64,
+}
+
+async fn execution_partitions(pool: &PgPool, request: &ExportRequest) -> eyre::Result<Partitions> {
+    let rows: Vec<ExecutionExportRow> = sqlx::query_as(
+        r#"
+        SELECT
+            id, signal_id, leg, chain, tx_hash, status, block_height, token_out_symbol,
+            expected_amount_out, amount_out, gas_used, gas_price,
+            EXTRACT(EPOCH FROM created_at)::BIGINT AS created_at
+        FROM executions
+        WHERE created_at BETWEEN to_timestamp($1) AND to_timestamp($2)
+        ORDER BY chain, created_at, id
+        "#,
+    )
+    .bind(request.from as f64)
+    .bind(request.to as f64)
+    .fetch_all(pool)
+    .await
+    .wrap_err("failed to read executions")?;
+
+    group_by_partition(rows, |row| (&row.chain, row.created_at), execution_batch)
+}
+
+/// Executions keep one row per leg. What pending legs realized is null, amounts and the gas price
+/// are decimal strings like the signals' they're compared against.
+fn execution_batch(rows: &[ExecutionExportRow]) -> eyre::Result<RecordBatch> {
+    let optional_strings = |value: fn(&ExecutionExportRow) -> &Option<String>| -> ArrayRef {
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|row| value(row).as_deref()),
+        ))
+    };
+
+    let schema = Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("signal_id", DataType::Int64, false),
+        Field::new("leg", DataType::Utf8, false),
+        Field::new("tx_hash", DataType::Utf8, false),
+        Field::new("status", DataType::Utf8, false),
+        Field::new("block_height", DataType::Int64, true),
+        Field::new("token_out_symbol", DataType::Utf8, false),
+        Field::new("expected_amount_out", DataType::Utf8, false),
+        Field::new("amount_out", DataType::Utf8, true),
+        Field::new("gas_used", DataType::Int64, true),
+        Field::new("gas_price", DataType::Utf8, true),
+        timestamp_field("created_at", false),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int64Array::from_iter_values(rows.iter().map(|row| row.id))),
+        Arc::new(Int64Array::from_iter_values(
+            rows.iter().map(|row| row.signal_id),
+        )),
+        strings(rows, |row| &row.leg),
+        strings(rows, |row| &row.tx_hash),
+        strings(rows, |row| &row.status),
+        Arc::new(Int64Array::from_iter(
+            rows.iter().map(|row| row.block_height),
+        )),
+        strings(rows, |row| &row.token_out_symbol),
+        strings(rows, |row| &row.expected_amount_out),
+        optional_strings(|row| &row.amount_out),
+        Arc::new(Int64Array::from_iter(rows.iter().map(|row| row.gas_used))),
+        optional_strings(|row| &row.gas_price),
+        timestamps(rows.iter().map(|row| Some(row.created_at))),
+    ];
+    RecordBatch::try_new(Arc::new(schema), columns).map_err(Into::into)
+}
+
+/// Splits rows ordered by partition key into one record batch per chain and day.
+fn group_by_partition<R>(
+    rows: Vec<R>,
+    key: impl Fn(&R) -> (&String, i64),
+    to_batch: impl Fn(&[R]) -> eyre::Result<RecordBatch>,
+) -> eyre::Result<Partitions> {
+    let mut grouped: BTreeMap<(String, i64), Vec<R>> = BTreeMap::new();
+    for row in rows {
+        let (chain, created_at) = key(&row);
+        let partition = (chain.clone(), created_at.div_euclid(SECONDS_PER_DAY));
+        grouped.entry(partition).or_default().push(row);
+    }
+
+    grouped
+        .into_iter()
+        .map(|(partition, rows)| Ok((partition, to_batch(&rows)?)))
+        .collect()
+}
+
+fn write_partition(dir: &Path, batch: &RecordBatch) -> eyre::Result<PathBuf> {
+    fs::create_dir_all(dir).wrap_err_with(|| format!("failed to create {}", dir.display()))?;
+    let path = dir.join("part-0.parquet");
+    let file =
+        File::create(&path).wrap_err_with(|| format!("failed to create {}", path.display()))?;
+
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(props))?;
+    writer.write(batch)?;
+    writer.close()?;
+
+    Ok(path)
+}
+
+fn timestamp_field(name: &str, nullable: bool) -> Field {
+    Field::new(
+        name,
+        DataType::Timestamp(TimeUnit::Second, Some("UTC".into())),
+        nullable,
+    )
+}
+
+fn timestamps(values: impl Iterator<Item = Option<i64>>) -> ArrayRef {
+    Arc::new(TimestampSecondArray::from_iter(values).with_timezone("UTC"))
+}
+
+fn strings<R>(rows: &[R], value: impl Fn(&R) -> &String) -> ArrayRef {
+    Arc::new(StringArray::from_iter_values(rows.iter().map(value)))
+}
+
+/// Formats days since the unix epoch as a `yyyy-mm-dd` date.
+fn format_day(days: i64) -> String {
+    // civil_from_days from http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_day_matches_calendar_dates() {
+        assert_eq!(format_day(0), "1970-01-01");
+        assert_eq!(format_day(-1), "1969-12-31");
+        assert_eq!(format_day(11_016), "2000-02-29");
+        // 2024-03-01T12:00:00Z
+        assert_eq!(format_day(1_709_294_400 / SECONDS_PER_DAY), "2024-03-01");
+    }
+
+    #[test]
+    fn datasets_round_trip_through_their_names() {
+        for &dataset in Dataset::ALL {
+            assert_eq!(dataset.to_string().parse::<Dataset>().unwrap(), dataset);
+        }
+        assert!("blocks".parse::<Dataset>().is_err());
+    }
+
+    fn execution_row() -> ExecutionExportRow {
+        ExecutionExportRow {
+            id: 1,
+            signal_id: 42,
+            leg: "slow".to_string(),
+            chain: "ethereum".to_string(),
+            tx_hash: format!("0x{}", "ab".repeat(32)),
+            status: "mined".to_string(),
+            block_height: Some(20_000_000),
+            token_out_symbol: "WETH".to_string(),
+            expected_amount_out: "1000000000000000000".to_string(),
+            amount_out: Some("999000000000000000".to_string()),
+            gas_used: Some(150_000),
+            gas_price: Some("2000000000".to_string()),
+            created_at: 1_709_294_400,
+        }
+    }
+
+    #[test]
+    fn execution_batch_leaves_pending_outcomes_null() {
+        let pending = ExecutionExportRow {
+            id: 2,
+            leg: "fast".to_string(),
+            status: "pending".to_string(),
+            block_height: None,
+            amount_out: None,
+            gas_used: None,
+            gas_price: None,
+            ..execution_row()
+        };
+
+        let batch = execution_batch(&[execution_row(), pending]).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert!(batch.schema().field_with_name("chain").is_err());
+        let amount_out = batch.column_by_name("amount_out").unwrap();
+        assert_eq!(amount_out.null_count(), 1);
+        assert!(amount_out.is_null(1));
+    }
+
+    #[test]
+    fn curve_batch_keeps_a_row_per_curve() {
+        let row = CurveExportRow {
+            chain: "base".to_string(),
+            block_height: 42,
+            pool_id: "0xpool".to_string(),
+            token_in: "WETH".to_string(),
+            token_out: "USDC".to_string(),
+            amounts_in: vec!["1".to_string(), "2".to_string()],
+            amounts_out: vec!["3000".to_string(), "5990".to_string()],
+            created_at: 1_709_294_400,
+        };
+
+        let batch = curve_batch(&[row]).unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        assert!(batch.schema().field_with_name("chain").is_err());
+        assert!(matches!(
+            batch
+                .schema()
+                .field_with_name("amounts_out")
+                .unwrap()
+                .data_type(),
+            DataType::List(_)
+        ));
+    }
+}
@@ -0,0 +1,146 @@
+use std::sync::Arc;
+
+use color_eyre::eyre;
+use sqlx::{FromRow, PgPool};
+use tracing::instrument;
+
+use crate::{chain::Chain, config::TokenAddressesForChain};
+
+use super::{observe::ObserveQuery as _, try_chain_from_str};
+
+/// Fees paid on a chain at a given block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GasPrice {
+    pub chain: Chain,
+    pub height: u64,
+    /// Header timestamp, in seconds since the unix epoch
+    pub timestamp: u64,
+    pub base_fee_per_gas: Option<u64>,
+    /// The node's `eth_maxPriorityFeePerGas` estimate when the block was collected
+    pub priority_fee_per_gas: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct GasPriceRepository {
+    pool: Arc<PgPool>,
+    token_configs: Arc<TokenAddressesForChain>,
+}
+
+impl GasPriceRepository {
+    pub(super) fn new(pool: Arc<PgPool>, token_configs: Arc<TokenAddressesForChain>) -> Self {
+        Self {
+            pool,
+            token_configs,
+        }
+    }
+
+    #[instrument(skip_all, fields(chain = %gas_price.chain, height = gas_price.height))]
+    pub async fn upsert(&self, gas_price: &GasPrice) -> eyre::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO gas_prices (
+                chain, height, timestamp, base_fee_per_gas, priority_fee_per_gas
+            ) VALUES ($1, $2, to_timestamp($3), $4, $5)
+            ON CONFLICT (chain, height) DO UPDATE SET
+                timestamp = EXCLUDED.timestamp,
+                base_fee_per_gas = EXCLUDED.base_fee_per_gas,
+                priority_fee_per_gas = EXCLUDED.priority_fee_per_gas
+            "#,
+        )
+        .bind(gas_price.chain.name.to_string())
+        .bind(gas_price.height as i64)
+        .bind(gas_price.timestamp as f64)
+        .bind(gas_price.base_fee_per_gas.map(|fee| fee as i64))
+        .bind(gas_price.priority_fee_per_gas.map(|fee| fee as i64))
+        .execute(self.pool.as_ref())
+        .observe("gas_prices.upsert")
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gas prices of `chain` for blocks `from_height..=to_height`, in height order
+    #[instrument(skip(self))]
+    pub async fn get_by_height_range(
+        &self,
+        chain: &Chain,
+        from_height: u64,
+        to_height: u64,
+    ) -> eyre::Result<Vec<GasPrice>> {
+        let rows: Vec<GasPriceRow> = sqlx::query_as(
+            r#"
+            SELECT
+                chain, height,
+                EXTRACT(EPOCH FROM timestamp)::BIGINT AS timestamp,
+                base_fee_per_gas, priority_fee_per_gas
+            FROM gas_prices
+            WHERE chain = $1 AND height BETWEEN $2 AND $3
+            ORDER BY height
+            "#,
+        )
+        .bind(chain.name.to_string())
+        .bind(from_height as i64)
+        .bind(to_height as i64)
+        .fetch_all(self.pool.as_ref())
+        .observe("gas_prices.get_by_height_range")
+        .await?;
+
+        rows.into_iter()
+            .map(|row| try_gas_price_from_row(row, &self.token_configs))
+            .collect()
+    }
+
+    /// Gas prices of `chain` for blocks timestamped within `from..=to` (unix seconds), in height
+    /// order
+    #[instrument(skip(self))]
+    pub async fn get_by_time_range(
+        &self,
+        chain: &Chain,
+        from: u64,
+        to: u64,
+    ) -> eyre::Result<Vec<GasPrice>> {
+        let rows: Vec<GasPriceRow> = sqlx::query_as(
+            r#"
+            SELECT
+                chain, height,
+                EXTRACT(EPOCH FROM timestamp)::BIGINT AS timestamp,
+                base_fee_per_gas, priority_fee_per_gas
+            FROM gas_prices
+            WHERE chain = $1 AND timestamp BETWEEN to_timestamp($2) AND to_timestamp($3)
+            ORDER BY height
+            "#,
+        )
+        .bind(chain.name.to_string())
+        .bind(from as f64)
+        .bind(to as f64)
+        .fetch_all(self.pool.as_ref())
+        .observe("gas_prices.get_by_time_range")
+        .await?;
+
+        rows.into_iter()
+            .map(|row| try_gas_price_from_row(row, &self.token_configs))
+            .collect()
+    }
+}
+
+#[derive(FromRow)]
+struct GasPriceRow {
+    chain: String,
+    height: i64,
+    timestamp: i64,
+    base_fee_per_gas: Option<i64>,
+    priority_fee_per_gas: Option<i64>,
+}
+
+fn try_gas_price_from_row(
+    row: GasPriceRow,
+    token_configs: &TokenAddressesForChain,
+) -> eyre::Result<GasPrice> {
+    Ok(GasPrice {
+        chain: try_chain_from_str(&row.chain, token_configs)?,
+        height: row.height as u64,
+        timestamp: row.timestamp as u64,
+        base_fee_per_gas: row.base_fee_per_gas.map(|fee| fee as u64),
+        priority_fee_per_gas: row.priority_fee_per_gas.map(|fee| fee as u64),
+    })
+}
@@ -0,0 +1,86 @@
+//! Periodic database health checks.
+//!
+//! The pool connects lazily, so an unreachable database otherwise only shows up on the first
+//! failing query. A background task acquires a connection and runs `SELECT 1` on every tick,
+//! reporting the pool's status as metrics and the outcome on a watch channel.
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use sqlx::PgPool;
+use tokio::{select, sync::watch, time::MissedTickBehavior};
+
+/// Outcome of the most recent health check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Health {
+    /// No check has completed yet
+    Unknown,
+    Healthy,
+    /// The last check failed with this error
+    Unhealthy(String),
+}
+
+impl Health {
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, Health::Healthy)
+    }
+}
+
+/// Spawns the health check task, which stops once every receiver is dropped.
+pub(super) fn spawn(pool: Arc<PgPool>, interval: Duration) -> watch::Receiver<Health> {
+    let (tx, rx) = watch::channel(Health::Unknown);
+
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            select! {
+                () = tx.closed() => break,
+                _ = ticks.tick() => {
+                    let health = check(&pool).await;
+                    tx.send_if_modified(|current| {
+                        let changed = *current != health;
+                        *current = health;
+                        changed
+                    });
+                }
+            }
+        }
+    });
+
+    rx
+}
+
+async fn check(pool: &PgPool) -> Health {
+    let idle = pool.num_idle();
+    let in_use = (pool.size() as usize).saturating_sub(idle);
+    metrics::gauge!("kuma_db_pool_connections", "state" => "idle").set(idle as f64);
+    metrics::gauge!("kuma_db_pool_connections", "state" => "in_use").set(in_use as f64);
+
+    let started = Instant::now();
+    let result = match pool.acquire().await {
+        Ok(mut conn) => {
+            metrics::histogram!("kuma_db_pool_acquire_seconds")
+                .record(started.elapsed().as_secs_f64());
+            sqlx::query("SELECT 1")
+                .execute(&mut *conn)
+                .await
+                .map(|_| ())
+        }
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(()) => {
+            metrics::gauge!("kuma_db_up").set(1.0);
+            Health::Healthy
+        }
+        Err(e) => {
+            metrics::gauge!("kuma_db_up").set(0.0);
+            metrics::counter!("kuma_db_health_check_failures_total").increment(1);
+            Health::Unhealthy(e.to_string())
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Liveness of kumad's workers, stored so the backend can report it.
+//!
+//! kumad upserts when each worker last made progress into the `worker_heartbeats` table (see
+//! `migrations/013_worker_heartbeats.sql`), the backend lists them on `/admin/status`.
+use std::sync::Arc;
+
+use color_eyre::eyre;
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use tracing::instrument;
+
+use super::observe::ObserveQuery as _;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WorkerHeartbeat {
+    /// The worker, e.g. `collector:base` or `strategy:<id>`
+    pub worker: String,
+    /// When the worker last made progress, in seconds since the unix epoch. `None` if it hasn't
+    /// yet.
+    pub last_progress_at: Option<u64>,
+    /// Seconds between the last progress and now
+    pub seconds_since_progress: Option<u64>,
+    /// When kumad last reported the worker, in seconds since the unix epoch
+    pub reported_at: u64,
+}
+
+#[derive(Clone)]
+pub struct HeartbeatRepository {
+    pool: Arc<PgPool>,
+}
+
+impl HeartbeatRepository {
+    pub(super) fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Records when each worker last made progress, in seconds since the unix epoch.
+    #[instrument(skip_all, fields(workers = heartbeats.len()))]
+    pub async fn upsert_all(&self, heartbeats: &[(String, Option<u64>)]) -> eyre::Result<()> {
+        let (workers, last_progress_at): (Vec<_>, Vec<_>) = heartbeats
+            .iter()
+            .map(|(worker, at)| (worker.clone(), at.map(|at| at as i64)))
+            .unzip();
+
+        sqlx::query(
+            r#"
+            INSERT INTO worker_heartbeats (worker, last_progress_at, reported_at)
+            SELECT worker, TO_TIMESTAMP(last_progress_at), NOW()
+            FROM UNNEST($1::TEXT[], $2::BIGINT[]) AS t (worker, last_progress_at)
+            ON CONFLICT (worker) DO UPDATE SET
+                last_progress_at = EXCLUDED.last_progress_at,
+                reported_at = EXCLUDED.reported_at
+            "#,
+        )
+        .bind(workers)
+        .bind(last_progress_at)
+        .execute(self.pool.as_ref())
+        .observe("heartbeats.upsert_all")
+        .await?;
+
+        Ok(())
+    }
+
+    /// Every worker kumad ever reported, by name
+    #[instrument(skip(self))]
+    pub async fn get_all(&self) -> eyre::Result<Vec<WorkerHeartbeat>> {
+        let rows: Vec<WorkerHeartbeatRow> = sqlx::query_as(
+            r#"
+            SELECT
+                worker,
+                EXTRACT(EPOCH FROM last_progress_at)::BIGINT AS last_progress_at,
+                EXTRACT(EPOCH FROM NOW() - last_progress_at)::BIGINT AS seconds_since_progress,
+                EXTRACT(EPOCH FROM reported_at)::BIGINT AS reported_at
+            FROM worker_heartbeats
+            ORDER BY worker
+            "#,
+        )
+        .fetch_all(self.pool.as_ref())
+        .observe("heartbeats.get_all")
+        .await?;
+
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+}
+
+#[derive(FromRow)]
+struct WorkerHeartbeatRow {
+    worker: String,
+    last_progress_at: Option<i64>,
+    seconds_since_progress: Option<i64>,
+    reported_at: i64,
+}
+
+impl From<WorkerHeartbeatRow> for WorkerHeartbeat {
+    fn from(row: WorkerHeartbeatRow) -> Self {
+        Self {
+            worker: row.worker,
+            last_progress_at: row.last_progress_at.map(|at| at as u64),
+            seconds_since_progress: row.seconds_since_progress.map(|secs| secs.max(0) as u64),
+            reported_at: row.reported_at as u64,
+        }
+    }
+}
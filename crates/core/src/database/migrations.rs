@@ -0,0 +1,128 @@
+//! Schema migrations embedded from the workspace's `migrations` directory.
+use std::collections::BTreeMap;
+
+use color_eyre::eyre::{Result, WrapErr as _};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool, migrate::Migrator};
+use tracing::info;
+
+use super::observe::ObserveQuery as _;
+
+/// Where a migration stands against the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Recorded as started but didn't complete
+    Failed,
+    /// Applied from a file that has since been edited
+    Modified,
+    /// Applied but no longer embedded, e.g. the mock data seeds that used to be migrations
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+    /// When the migration was applied, in seconds since the unix epoch
+    pub applied_at: Option<u64>,
+}
+
+fn migrator() -> Migrator {
+    let mut migrator = sqlx::migrate!("../../migrations");
+    // development databases may still record the mock data seeds that used to be migrations
+    migrator.set_ignore_missing(true);
+    migrator
+}
+
+/// Applies the migrations embedded from the workspace's `migrations` directory to `pool`.
+pub async fn migrate(pool: &PgPool) -> Result<()> {
+    let migrator = migrator();
+    migrator
+        .run(pool)
+        .await
+        .wrap_err("failed to run database migrations")?;
+    info!(
+        migrations = migrator.iter().count(),
+        "Database schema is up to date"
+    );
+
+    Ok(())
+}
+
+#[derive(FromRow)]
+struct AppliedRow {
+    version: i64,
+    description: String,
+    applied_at: i64,
+    success: bool,
+    checksum: Vec<u8>,
+}
+
+pub(super) async fn status(pool: &PgPool) -> Result<Vec<MigrationStatus>> {
+    // sqlx creates its bookkeeping table on the first run
+    let (tracked,): (bool,) = sqlx::query_as("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(pool)
+        .observe("migrations.tracked")
+        .await?;
+    let applied: Vec<AppliedRow> = if tracked {
+        sqlx::query_as(
+            r#"
+            SELECT
+                version, description, success, checksum,
+                EXTRACT(EPOCH FROM installed_on)::BIGINT AS applied_at
+            FROM _sqlx_migrations
+            "#,
+        )
+        .fetch_all(pool)
+        .observe("migrations.applied")
+        .await?
+    } else {
+        Vec::new()
+    };
+    let mut applied: BTreeMap<i64, AppliedRow> =
+        applied.into_iter().map(|row| (row.version, row)).collect();
+
+    let mut statuses: BTreeMap<i64, MigrationStatus> = BTreeMap::new();
+    for migration in migrator()
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+    {
+        let row = applied.remove(&migration.version);
+        let state = match &row {
+            None => MigrationState::Pending,
+            Some(row) if !row.success => MigrationState::Failed,
+            Some(row) if row.checksum != *migration.checksum => MigrationState::Modified,
+            Some(_) => MigrationState::Applied,
+        };
+        statuses.insert(
+            migration.version,
+            MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                state,
+                applied_at: row.map(|row| row.applied_at as u64),
+            },
+        );
+    }
+    for (version, row) in applied {
+        statuses.insert(
+            version,
+            MigrationStatus {
+                version,
+                description: row.description,
+                state: if row.success {
+                    MigrationState::Unknown
+                } else {
+                    MigrationState::Failed
+                },
+                applied_at: Some(row.applied_at as u64),
+            },
+        );
+    }
+
+    Ok(statuses.into_values().collect())
+}
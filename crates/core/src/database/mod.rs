@@ -1,6 +1,8 @@
-use color_eyre::eyre::{self, OptionExt as _, Result, eyre};
+use color_eyre::eyre::{self, OptionExt as _, Result, WrapErr as _, eyre};
 use sqlx::{PgPool, postgres::PgPoolOptions};
 use std::{str::FromStr as _, sync::Arc};
+use tokio::sync::watch;
+use tokio_util::sync::DropGuard;
 use tracing::info;
 use tycho_common::models::token::Token;
 
@@ -9,20 +11,65 @@ use crate::{
     config::{DatabaseConfig, TokenAddressesForChain},
 };
 
+use write_buffer::WriteBuffer;
+
+pub use blocks::*;
+pub use candles::*;
+pub use controls::*;
+pub use curves::*;
+pub use executions::*;
+pub use export::{Dataset, ExportRequest, ExportSummary, export_curves};
+pub use gas_prices::*;
+pub use health::Health;
+pub use heartbeats::*;
+pub use migrations::{MigrationState, MigrationStatus, migrate};
+pub use notify::{SIGNALS_CHANNEL, SignalListener};
 pub use signals::*;
 pub use spot_prices::*;
+pub use write_buffer::Write;
 
+mod blocks;
+mod candles;
+mod controls;
+mod curves;
+mod executions;
+mod export;
+mod gas_prices;
+mod health;
+mod heartbeats;
+mod migrations;
+mod notify;
+mod observe;
 mod signals;
 mod spot_prices;
+mod timescale;
+mod views;
+mod write_buffer;
 
 #[derive(Debug, Clone)]
 pub struct Handle {
     pool: Arc<PgPool>,
     token_configs: Arc<TokenAddressesForChain>,
+    /// Whether time-series tables are TimescaleDB hypertables
+    timescale: bool,
+    /// Queue for writes made while the database is unreachable, if enabled
+    write_buffer: Option<Arc<WriteBuffer>>,
+    /// Outcome of the latest periodic health check
+    health_rx: watch::Receiver<Health>,
+    /// Stops refreshing the materialized views once the last handle is dropped
+    #[allow(unused)]
+    view_refresh: Option<Arc<DropGuard>>,
+    /// Stops the candle aggregation job once the last handle is dropped
+    #[allow(unused)]
+    candle_job: Option<Arc<DropGuard>>,
 }
 
 impl Handle {
-    pub fn from_config(
+    /// Connects to the database, applying pending migrations first if `migrate_on_start` is set
+    /// and setting up hypertables if `timescale` is. Refreshes the dashboard's materialized views
+    /// and aggregates spot price candles in the background if `materialized_views` and `candles`
+    /// are set.
+    pub async fn from_config(
         config: DatabaseConfig,
         token_configs: Arc<TokenAddressesForChain>,
     ) -> Result<Self> {
@@ -42,24 +89,189 @@ impl Handle {
             config.max_connections
         );
 
-        let handle = Handle {
-            pool: Arc::new(pool),
+        let pool = Arc::new(pool);
+        let health_rx = health::spawn(Arc::clone(&pool), config.health_check_interval());
+
+        let mut handle = Handle {
+            pool,
             token_configs,
+            timescale: config.timescale.is_some(),
+            write_buffer: None,
+            health_rx,
+            view_refresh: None,
+            candle_job: None,
         };
 
+        if config.migrate_on_start {
+            handle.migrate().await?;
+        }
+        if let Some(timescale) = &config.timescale {
+            timescale::setup(&handle.pool, timescale)
+                .await
+                .wrap_err("failed to set up timescaledb")?;
+        }
+        if let Some(views) = &config.materialized_views {
+            handle.view_refresh = Some(Arc::new(views::spawn_refresh(
+                Arc::clone(&handle.pool),
+                views.refresh_interval(),
+            )));
+        }
+        if let Some(candles) = &config.candles {
+            handle.candle_job = Some(Arc::new(candles::spawn_job(
+                handle.candle_repository(),
+                candles,
+            )));
+        }
+        if let Some(write_buffer) = &config.write_buffer {
+            handle.write_buffer = Some(Arc::new(WriteBuffer::spawn(
+                write_buffer,
+                handle.spot_price_repository(),
+                handle.signal_repository(),
+            )));
+        }
+
         Ok(handle)
     }
+
+    /// Writes spot prices or a signal, queueing it in the write buffer if one is configured.
+    ///
+    /// Buffered writes return once queued and are retried until the database accepts them or
+    /// rejects them for good.
+    pub async fn write(&self, write: Write) -> Result<()> {
+        if let Some(write_buffer) = &self.write_buffer {
+            return write_buffer.push(write).await;
+        }
+
+        match write {
+            Write::SpotPrices(spot_prices) => {
+                self.spot_price_repository().insert_many(&spot_prices).await
+            }
+            Write::Signal(signal) => self.signal_repository().insert(signal).await.map(|_| ()),
+            Write::SignalWithSpotPrices {
+                signal,
+                spot_prices,
+            } => {
+                self.signal_repository()
+                    .insert_with_spot_prices(&signal, &spot_prices)
+                    .await
+            }
+        }
+    }
+
+    /// Listens for signals inserted from now on, by any process writing to the database.
+    pub async fn listen_signals(&self) -> Result<SignalListener> {
+        SignalListener::connect(&self.pool, self.signal_repository()).await
+    }
+
+    /// Listens for changes to the operator controls, by any process writing to the database.
+    pub async fn listen_controls(&self) -> Result<ControlListener> {
+        ControlListener::connect(&self.pool, self.control_repository()).await
+    }
+
+    /// The configured chain called `name`.
+    pub fn chain(&self, name: &str) -> Result<Chain> {
+        try_chain_from_str(name, &self.token_configs)
+    }
+
+    /// Every configured chain.
+    pub fn chains(&self) -> Vec<Chain> {
+        self.token_configs.keys().cloned().collect()
+    }
+
+    /// Tokens configured on `chain`.
+    pub fn tokens(&self, chain: &Chain) -> Vec<Token> {
+        self.token_configs
+            .get(chain)
+            .map(|tokens| tokens.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Outcome of the latest health check.
+    pub fn health(&self) -> Health {
+        self.health_rx.borrow().clone()
+    }
+
+    /// Receiver notified whenever the database's health changes
+    pub fn subscribe_health(&self) -> watch::Receiver<Health> {
+        self.health_rx.clone()
+    }
+
+    /// Exports the requested datasets to partitioned Parquet files under `request.dir`.
+    pub async fn export_parquet(&self, request: &ExportRequest) -> Result<ExportSummary> {
+        export::export(&self.pool, request).await
+    }
+
+    /// Closes the pool, waiting for checked out connections to be returned. Queries made through
+    /// any clone of the handle fail afterwards.
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    /// Applies any pending schema migrations.
+    pub async fn migrate(&self) -> Result<()> {
+        migrate(&self.pool).await
+    }
+
+    /// Every migration embedded in the binary or recorded as applied, by version.
+    pub async fn migration_status(&self) -> Result<Vec<MigrationStatus>> {
+        migrations::status(&self.pool).await
+    }
+
     #[allow(dead_code)]
     pub fn pool(&self) -> Arc<PgPool> {
         Arc::clone(&self.pool)
     }
 
     pub fn spot_price_repository(&self) -> SpotPriceRepository {
-        SpotPriceRepository::new(Arc::clone(&self.pool), Arc::clone(&self.token_configs))
+        SpotPriceRepository::new(
+            Arc::clone(&self.pool),
+            Arc::clone(&self.token_configs),
+            self.timescale,
+        )
     }
 
     pub fn signal_repository(&self) -> SignalRepository {
-        SignalRepository::new(Arc::clone(&self.pool), Arc::clone(&self.token_configs))
+        SignalRepository::new(
+            Arc::clone(&self.pool),
+            Arc::clone(&self.token_configs),
+            self.timescale,
+        )
+    }
+
+    pub fn candle_repository(&self) -> CandleRepository {
+        CandleRepository::new(
+            Arc::clone(&self.pool),
+            Arc::clone(&self.token_configs),
+            self.timescale,
+        )
+    }
+
+    pub fn block_repository(&self) -> BlockRepository {
+        BlockRepository::new(Arc::clone(&self.pool), Arc::clone(&self.token_configs))
+    }
+
+    pub fn control_repository(&self) -> ControlRepository {
+        ControlRepository::new(Arc::clone(&self.pool))
+    }
+
+    pub fn curve_repository(&self) -> CurveRepository {
+        CurveRepository::new(Arc::clone(&self.pool))
+    }
+
+    pub fn execution_repository(&self) -> ExecutionRepository {
+        ExecutionRepository::new(
+            Arc::clone(&self.pool),
+            Arc::clone(&self.token_configs),
+            self.timescale,
+        )
+    }
+
+    pub fn heartbeat_repository(&self) -> HeartbeatRepository {
+        HeartbeatRepository::new(Arc::clone(&self.pool))
+    }
+
+    pub fn gas_price_repository(&self) -> GasPriceRepository {
+        GasPriceRepository::new(Arc::clone(&self.pool), Arc::clone(&self.token_configs))
     }
 }
 
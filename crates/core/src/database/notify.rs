@@ -0,0 +1,51 @@
+//! Notifications of newly inserted signals.
+//!
+//! Inserting a signal fires a trigger that sends its id on the [`SIGNALS_CHANNEL`] channel, see
+//! `migrations/011_signal_notify.sql`. A listener holds its own connection outside of the pool.
+use color_eyre::eyre::{self, WrapErr as _};
+use sqlx::{PgPool, postgres::PgListener};
+
+use crate::signals;
+
+use super::SignalRepository;
+
+/// Channel new signal ids are announced on
+pub const SIGNALS_CHANNEL: &str = "kuma_signals";
+
+pub struct SignalListener {
+    listener: PgListener,
+    signals: SignalRepository,
+}
+
+impl SignalListener {
+    pub(super) async fn connect(pool: &PgPool, signals: SignalRepository) -> eyre::Result<Self> {
+        let mut listener = PgListener::connect_with(pool)
+            .await
+            .wrap_err("failed to connect signal listener")?;
+        listener
+            .listen(SIGNALS_CHANNEL)
+            .await
+            .wrap_err("failed to listen for new signals")?;
+
+        Ok(Self { listener, signals })
+    }
+
+    /// Waits for the next signal to be inserted.
+    ///
+    /// The connection is re-established on the next call after an error; signals inserted while
+    /// it was down are missed.
+    pub async fn recv(&mut self) -> eyre::Result<signals::CrossChainSingleHop> {
+        loop {
+            let notification = self.listener.recv().await?;
+            let id: i64 = notification
+                .payload()
+                .parse()
+                .wrap_err("failed to parse signal id from notification")?;
+
+            // the row can be gone already, e.g. after pruning
+            if let Some(signal) = self.signals.get_by_id(id).await? {
+                return Ok(signal);
+            }
+        }
+    }
+}
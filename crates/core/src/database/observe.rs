@@ -0,0 +1,22 @@
+//! Per-query latency and error metrics.
+use std::time::Instant;
+
+use futures::FutureExt as _;
+
+pub(super) trait ObserveQuery<T, E>: Future<Output = Result<T, E>> + Sized {
+    /// Records the query's duration as `kuma_db_query_duration_seconds` and failures as
+    /// `kuma_db_query_errors_total`, both labelled with `query`.
+    fn observe(self, query: &'static str) -> impl Future<Output = Result<T, E>> {
+        let started = Instant::now();
+        self.map(move |result| {
+            metrics::histogram!("kuma_db_query_duration_seconds", "query" => query)
+                .record(started.elapsed().as_secs_f64());
+            if result.is_err() {
+                metrics::counter!("kuma_db_query_errors_total", "query" => query).increment(1);
+            }
+            result
+        })
+    }
+}
+
+impl<T, E, F: Future<Output = Result<T, E>>> ObserveQuery<T, E> for F {}
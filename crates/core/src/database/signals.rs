@@ -1,79 +1,261 @@
-use std::{str::FromStr, sync::Arc};
+use std::{
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use color_eyre::eyre::{self, Context, eyre};
 use num_bigint::BigUint;
-use sqlx::PgPool;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool, Postgres, QueryBuilder};
 use tracing::instrument;
 
 use crate::{
     chain::Chain,
     config::TokenAddressesForChain,
-    signals,
+    signals::{self, Direction},
+    spot_prices::SpotPrices,
     state::{PoolId, pair::Pair},
     strategy::Swap,
 };
 
-use super::{try_chain_from_str, try_token_from_chain_symbol};
+use super::{
+    observe::ObserveQuery as _, spot_prices::insert_spot_prices, timescale::time_bucket,
+    try_chain_from_str, try_token_from_chain_symbol,
+};
+
+/// Signals `s` joined with their slow/fast chains `sc`/`fc` and the tokens of both swaps
+const SIGNALS_WITH_SYMBOLS: &str = r#"
+    signals s
+    JOIN chains sc ON sc.id = s.slow_chain_id
+    JOIN chains fc ON fc.id = s.fast_chain_id
+    JOIN tokens sti ON sti.id = s.slow_swap_token_in_id
+    JOIN tokens sto ON sto.id = s.slow_swap_token_out_id
+    JOIN tokens fti ON fti.id = s.fast_swap_token_in_id
+    JOIN tokens fto ON fto.id = s.fast_swap_token_out_id
+"#;
+
+/// Columns of [`SignalRow`] selected from [`SIGNALS_WITH_SYMBOLS`]
+const SIGNAL_COLUMNS: &str = r#"
+    sc.name AS slow_chain, s.slow_height, s.slow_pool_id,
+    fc.name AS fast_chain, s.fast_height, s.fast_pool_id,
+    sti.symbol AS slow_swap_token_in_symbol, sto.symbol AS slow_swap_token_out_symbol,
+    s.slow_swap_amount_in, s.slow_swap_amount_out, s.slow_swap_gas_cost,
+    fti.symbol AS fast_swap_token_in_symbol, fto.symbol AS fast_swap_token_out_symbol,
+    s.fast_swap_amount_in, s.fast_swap_amount_out, s.fast_swap_gas_cost,
+    s.surplus_a, s.surplus_b, s.expected_profit_a, s.expected_profit_b,
+    s.max_slippage_bps::BIGINT AS max_slippage_bps,
+    s.congestion_risk_discount_bps::BIGINT AS congestion_risk_discount_bps
+"#;
+
+/// Signals trading `$1` and `$2` against each other across the two chains, in either direction
+const MATCHES_SYMBOLS: &str = r#"
+    (((sti.symbol = $1 AND sto.symbol = $2) AND (fti.symbol = $2 AND fto.symbol = $1))
+        OR ((sti.symbol = $2 AND sto.symbol = $1) AND (fti.symbol = $1 AND fto.symbol = $2)))
+"#;
+
+/// A signal as stored, with when it was recorded.
+#[derive(Debug, Clone, Serialize)]
+pub struct SignalRecord {
+    #[serde(flatten)]
+    pub signal: signals::CrossChainSingleHop,
+    /// When the row was inserted, in seconds since the unix epoch
+    pub created_at: u64,
+    /// Timestamp of the slow chain block, if the block was recorded
+    pub slow_block_timestamp: Option<u64>,
+    /// Timestamp of the fast chain block, if the block was recorded
+    pub fast_block_timestamp: Option<u64>,
+}
+
+/// Criteria for [`SignalRepository::get_filtered`]; unset fields match every signal.
+#[derive(Debug, Clone, Default)]
+pub struct SignalFilter {
+    pub slow_chain: Option<Chain>,
+    pub fast_chain: Option<Chain>,
+    /// Symbols of the pair traded across the chains, matched in either order unless `direction`
+    /// is set
+    pub pair: Option<(String, String)>,
+    /// Whether the slow chain swap sells the first (`AtoB`) or second (`BtoA`) token of `pair`.
+    /// Ignored without a `pair`.
+    pub direction: Option<Direction>,
+    /// Minimum expected profit in the slow swap's input token
+    pub min_expected_profit_a: Option<BigUint>,
+    /// Minimum expected profit in the slow swap's output token
+    pub min_expected_profit_b: Option<BigUint>,
+    /// Inclusive lower bound on when the signal was recorded
+    pub since: Option<SystemTime>,
+    /// Exclusive upper bound on when the signal was recorded
+    pub until: Option<SystemTime>,
+}
+
+/// Column [`SignalRepository::get_filtered`] sorts by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignalSort {
+    /// When the signal was recorded
+    #[default]
+    CreatedAt,
+    ExpectedProfitA,
+    ExpectedProfitB,
+}
+
+impl SignalSort {
+    fn column(self) -> &'static str {
+        match self {
+            SignalSort::CreatedAt => "s.created_at",
+            // matches the expression indexes on the profit columns
+            SignalSort::ExpectedProfitA => "s.expected_profit_a::NUMERIC",
+            SignalSort::ExpectedProfitB => "s.expected_profit_b::NUMERIC",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    fn keyword(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+/// Number of signals found for a slow/fast chain route over one time bucket.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SignalBucket {
+    pub slow_chain: Chain,
+    pub fast_chain: Chain,
+    /// Start of the bucket, in seconds since the unix epoch
+    pub bucket_start: u64,
+    pub signals: u64,
+}
+
+/// What [`SignalRepository::leaderboard`] entries aggregate over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardGroup {
+    /// Token pair, keyed as `A-B` with the symbols sorted
+    Pair,
+    /// Pool on either leg of the signal, keyed as `chain:pool_id`
+    Pool,
+    /// Chain on either leg of the signal, keyed by name
+    Chain,
+}
+
+/// Order of [`SignalRepository::leaderboard`] entries, highest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardRank {
+    Signals,
+    ExpectedProfit,
+    HitRate,
+}
+
+/// Aggregated signals of one pair, pool or chain.
+///
+/// Expected profits of different tokens can't be summed, so entries are split by the token
+/// profits are denominated in.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LeaderboardEntry {
+    pub key: String,
+    /// Symbol of the slow swap's input token, which expected profit is denominated in
+    pub profit_token: String,
+    pub signals: u64,
+    pub total_expected_profit: BigUint,
+    /// Share of signals whose legs `kuma execute` got mined, both legs for pairs and the leg on
+    /// the pool or chain otherwise
+    pub hit_rate: f64,
+}
 
 #[derive(Clone)]
 pub struct SignalRepository {
     pool: Arc<PgPool>,
     tokens_config: Arc<TokenAddressesForChain>,
+    timescale: bool,
 }
 
 impl SignalRepository {
-    pub(super) fn new(pool: Arc<PgPool>, tokens_config: Arc<TokenAddressesForChain>) -> Self {
+    pub(super) fn new(
+        pool: Arc<PgPool>,
+        tokens_config: Arc<TokenAddressesForChain>,
+        timescale: bool,
+    ) -> Self {
         Self {
             pool,
             tokens_config,
+            timescale,
         }
     }
 
+    /// Inserts a signal, returning its id.
     #[instrument(skip(self, signal))]
-    pub async fn insert(&self, signal: signals::CrossChainSingleHop) -> eyre::Result<()> {
-        sqlx::query!(
+    pub async fn insert(&self, signal: signals::CrossChainSingleHop) -> eyre::Result<i64> {
+        async {
+            let mut conn = self.pool.acquire().await?;
+            insert_signal(&mut conn, &signal).await
+        }
+        .observe("signals.insert")
+        .await
+    }
+
+    /// Inserts a signal together with the spot prices of the blocks it was derived from, in a
+    /// single transaction so neither is stored without the other.
+    ///
+    /// # Errors
+    /// Returns an error without writing anything if a spot price isn't from the signal's slow or
+    /// fast block.
+    #[instrument(skip_all, fields(spot_prices = spot_prices.len()))]
+    pub async fn insert_with_spot_prices(
+        &self,
+        signal: &signals::CrossChainSingleHop,
+        spot_prices: &[SpotPrices],
+    ) -> eyre::Result<()> {
+        if let Some(unrelated) = spot_prices
+            .iter()
+            .find(|spot_prices| !is_from_signal_blocks(spot_prices, signal))
+        {
+            eyre::bail!(
+                "spot prices at {} block {} are not from the signal's blocks",
+                unrelated.chain,
+                unrelated.block_height
+            );
+        }
+
+        async {
+            let mut tx = self.pool.begin().await?;
+            insert_spot_prices(&mut tx, spot_prices).await?;
+            insert_signal(&mut tx, signal).await?;
+            tx.commit().await?;
+            eyre::Ok(())
+        }
+        .observe("signals.insert_with_spot_prices")
+        .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_by_id(&self, id: i64) -> eyre::Result<Option<signals::CrossChainSingleHop>> {
+        let query = format!(
             r#"
-            INSERT INTO signals (
-                slow_chain, slow_height, slow_pool_id,
-                fast_chain, fast_height, fast_pool_id,
-                slow_swap_token_in_symbol, slow_swap_token_out_symbol,
-                slow_swap_amount_in, slow_swap_amount_out, slow_swap_gas_cost,
-                fast_swap_token_in_symbol, fast_swap_token_out_symbol,
-                fast_swap_amount_in, fast_swap_amount_out, fast_swap_gas_cost,
-                surplus_a, surplus_b, expected_profit_a, expected_profit_b,
-                max_slippage_bps, congestion_risk_discount_bps
-            ) VALUES (
-                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13,
-                $14, $15, $16, $17, $18, $19, $20, $21, $22
-            )
-            "#,
-            &signal.slow_chain.name.to_string(),
-            signal.slow_height as i64,
-            &signal.slow_pool_id.to_string(),
-            &signal.fast_chain.name.to_string(),
-            signal.fast_height as i64,
-            &signal.fast_pool_id.to_string(),
-            &signal.slow_swap_sim.token_in.symbol,
-            &signal.slow_swap_sim.token_out.symbol,
-            &signal.slow_swap_sim.amount_in.to_string(),
-            &signal.slow_swap_sim.amount_out.to_string(),
-            &signal.slow_swap_sim.gas_cost.to_string(),
-            &signal.fast_swap_sim.token_in.symbol,
-            &signal.fast_swap_sim.token_out.symbol,
-            &signal.fast_swap_sim.amount_in.to_string(),
-            &signal.fast_swap_sim.amount_out.to_string(),
-            &signal.fast_swap_sim.gas_cost.to_string(),
-            &signal.surplus.0.to_string(),
-            &signal.surplus.1.to_string(),
-            &signal.expected_profit.0.to_string(),
-            &signal.expected_profit.1.to_string(),
-            signal.max_slippage_bps as i64,
-            signal.congestion_risk_discount_bps as i64,
-        )
-        .execute(self.pool.as_ref())
-        .await?;
+            SELECT {SIGNAL_COLUMNS}
+            FROM {SIGNALS_WITH_SYMBOLS}
+            WHERE s.id = $1
+            "#
+        );
+        let row: Option<SignalRow> = sqlx::query_as(&query)
+            .bind(id)
+            .fetch_optional(self.pool.as_ref())
+            .observe("signals.get_by_id")
+            .await?;
 
-        Ok(())
+        row.map(|r| try_signal_from_row(r, &self.tokens_config))
+            .transpose()
     }
 
     #[instrument(skip(self))]
@@ -82,20 +264,19 @@ impl SignalRepository {
         token_a_symbol: &str,
         token_b_symbol: &str,
     ) -> eyre::Result<u64> {
-        let count: i64 = sqlx::query_scalar(
+        let query = format!(
             r#"
             SELECT COUNT(*) as count
-            FROM signals
-            WHERE (((slow_swap_token_in_symbol = $1 AND slow_swap_token_out_symbol = $2)
-                AND (fast_swap_token_in_symbol = $2 AND fast_swap_token_out_symbol = $1))
-                OR ((fast_swap_token_in_symbol = $1 AND fast_swap_token_out_symbol = $2)
-                AND (fast_swap_token_in_symbol = $2 AND fast_swap_token_out_symbol = $1)))
-            "#,
-        )
-        .bind(token_a_symbol)
-        .bind(token_b_symbol)
-        .fetch_one(self.pool.as_ref())
-        .await?;
+            FROM {SIGNALS_WITH_SYMBOLS}
+            WHERE {MATCHES_SYMBOLS}
+            "#
+        );
+        let count: i64 = sqlx::query_scalar(&query)
+            .bind(token_a_symbol)
+            .bind(token_b_symbol)
+            .fetch_one(self.pool.as_ref())
+            .observe("signals.count_by_symbols")
+            .await?;
 
         Ok(count as u64)
     }
@@ -107,40 +288,473 @@ impl SignalRepository {
         limit: u32,
         offset: u32,
     ) -> eyre::Result<Vec<signals::CrossChainSingleHop>> {
-        let rows = sqlx::query_as!(
-            SignalRow,
+        let query = format!(
             r#"
-            SELECT
-                slow_chain, slow_height, slow_pool_id,
-                fast_chain, fast_height, fast_pool_id,
-                slow_swap_token_in_symbol, slow_swap_token_out_symbol,
-                slow_swap_amount_in, slow_swap_amount_out, slow_swap_gas_cost,
-                fast_swap_token_in_symbol, fast_swap_token_out_symbol,
-                fast_swap_amount_in, fast_swap_amount_out, fast_swap_gas_cost,
-                surplus_a, surplus_b, expected_profit_a, expected_profit_b,
-                max_slippage_bps, congestion_risk_discount_bps
-            FROM signals
-            WHERE (((slow_swap_token_in_symbol = $1 AND slow_swap_token_out_symbol = $2)
-                AND (fast_swap_token_in_symbol = $2 AND fast_swap_token_out_symbol = $1))
-                OR ((slow_swap_token_in_symbol = $2 AND slow_swap_token_out_symbol = $1)
-                AND (fast_swap_token_in_symbol = $1 AND fast_swap_token_out_symbol = $2)))
-            ORDER BY created_at DESC
+            SELECT {SIGNAL_COLUMNS}
+            FROM {SIGNALS_WITH_SYMBOLS}
+            WHERE {MATCHES_SYMBOLS}
+            ORDER BY s.created_at DESC
             LIMIT $3 OFFSET $4
+            "#
+        );
+        let rows: Vec<SignalRow> = sqlx::query_as(&query)
+            .bind(token_a_symbol)
+            .bind(token_b_symbol)
+            .bind(limit as i64)
+            .bind(offset as i64)
+            .fetch_all(self.pool.as_ref())
+            .observe("signals.get_by_symbols")
+            .await?;
+
+        rows.into_iter()
+            .map(|r| try_signal_from_row(r, &self.tokens_config))
+            .collect()
+    }
+
+    #[instrument(skip(self))]
+    pub async fn count_filtered(&self, filter: &SignalFilter) -> eyre::Result<u64> {
+        let mut query =
+            QueryBuilder::<Postgres>::new(format!("SELECT COUNT(*) FROM {SIGNALS_WITH_SYMBOLS}"));
+        push_filter(&mut query, filter)?;
+
+        let count: i64 = query
+            .build_query_scalar()
+            .fetch_one(self.pool.as_ref())
+            .observe("signals.count_filtered")
+            .await?;
+
+        Ok(count as u64)
+    }
+
+    /// Signals matching `filter`, sorted by `sort` in `order`
+    #[instrument(skip(self))]
+    pub async fn get_filtered(
+        &self,
+        filter: &SignalFilter,
+        sort: SignalSort,
+        order: SortOrder,
+        limit: u32,
+        offset: u32,
+    ) -> eyre::Result<Vec<signals::CrossChainSingleHop>> {
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT {SIGNAL_COLUMNS} FROM {SIGNALS_WITH_SYMBOLS}"
+        ));
+        push_filter(&mut query, filter)?;
+        let (column, direction) = (sort.column(), order.keyword());
+        query
+            .push(format!(
+                " ORDER BY {column} {direction}, s.id {direction} LIMIT "
+            ))
+            .push_bind(limit as i64)
+            .push(" OFFSET ")
+            .push_bind(offset as i64);
+
+        let rows: Vec<SignalRow> = query
+            .build_query_as()
+            .fetch_all(self.pool.as_ref())
+            .observe("signals.get_filtered")
+            .await?;
+
+        rows.into_iter()
+            .map(|r| try_signal_from_row(r, &self.tokens_config))
+            .collect()
+    }
+
+    /// Signals inserted within `from..=to` (unix seconds), oldest first
+    #[instrument(skip(self))]
+    pub async fn get_by_time_range(&self, from: u64, to: u64) -> eyre::Result<Vec<SignalRecord>> {
+        let query = format!(
+            r#"
+            SELECT
+                {SIGNAL_COLUMNS},
+                EXTRACT(EPOCH FROM s.created_at)::BIGINT AS created_at,
+                EXTRACT(EPOCH FROM sb.timestamp)::BIGINT AS slow_block_timestamp,
+                EXTRACT(EPOCH FROM fb.timestamp)::BIGINT AS fast_block_timestamp
+            FROM {SIGNALS_WITH_SYMBOLS}
+            LEFT JOIN blocks sb ON sb.chain = sc.name AND sb.height = s.slow_height
+            LEFT JOIN blocks fb ON fb.chain = fc.name AND fb.height = s.fast_height
+            WHERE s.created_at BETWEEN to_timestamp($1) AND to_timestamp($2)
+            ORDER BY s.created_at
+            "#
+        );
+        let rows: Vec<SignalRecordRow> = sqlx::query_as(&query)
+            .bind(from as f64)
+            .bind(to as f64)
+            .fetch_all(self.pool.as_ref())
+            .observe("signals.get_by_time_range")
+            .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(SignalRecord {
+                    signal: try_signal_from_row(row.signal, &self.tokens_config)?,
+                    created_at: row.created_at as u64,
+                    slow_block_timestamp: row
+                        .slow_block_timestamp
+                        .map(|timestamp| timestamp as u64),
+                    fast_block_timestamp: row
+                        .fast_block_timestamp
+                        .map(|timestamp| timestamp as u64),
+                })
+            })
+            .collect()
+    }
+
+    /// Hourly signal counts per chain route for a pair traded in either direction, newest first
+    /// and as of the last refresh of the `hourly_signal_stats` view
+    #[instrument(skip(self))]
+    pub async fn hourly_counts(
+        &self,
+        token_a_symbol: &str,
+        token_b_symbol: &str,
+        limit: u32,
+    ) -> eyre::Result<Vec<SignalBucket>> {
+        let rows: Vec<SignalBucketRow> = sqlx::query_as(
+            r#"
+            SELECT
+                sc.name AS slow_chain,
+                fc.name AS fast_chain,
+                EXTRACT(EPOCH FROM h.hour)::BIGINT AS bucket_start,
+                SUM(h.signals)::BIGINT AS signals
+            FROM hourly_signal_stats h
+            JOIN chains sc ON sc.id = h.slow_chain_id
+            JOIN chains fc ON fc.id = h.fast_chain_id
+            JOIN tokens ti ON ti.id = h.slow_swap_token_in_id
+            JOIN tokens tout ON tout.id = h.slow_swap_token_out_id
+            WHERE ((ti.symbol = $1 AND tout.symbol = $2)
+                OR (ti.symbol = $2 AND tout.symbol = $1))
+            GROUP BY sc.name, fc.name, h.hour
+            ORDER BY h.hour DESC
+            LIMIT $3
             "#,
-            token_a_symbol,
-            token_b_symbol,
-            limit as i64,
-            offset as i64
         )
-        .fetch_all(&*self.pool)
+        .bind(token_a_symbol)
+        .bind(token_b_symbol)
+        .bind(limit as i64)
+        .fetch_all(self.pool.as_ref())
+        .observe("signals.hourly_counts")
         .await?;
 
         rows.into_iter()
-            .map(|r| try_signal_from_row(r, &self.tokens_config))
+            .map(|row| {
+                Ok(SignalBucket {
+                    slow_chain: try_chain_from_str(&row.slow_chain, &self.tokens_config)?,
+                    fast_chain: try_chain_from_str(&row.fast_chain, &self.tokens_config)?,
+                    bucket_start: row.bucket_start as u64,
+                    signals: row.signals as u64,
+                })
+            })
+            .collect()
+    }
+
+    /// Top `limit` pairs, pools or chains by `rank_by` over signals recorded in the last `window`.
+    /// Signals count towards the pools and chains of both legs, and are hits when the `executions`
+    /// of the legs they count for were mined.
+    #[instrument(skip(self))]
+    pub async fn leaderboard(
+        &self,
+        group: LeaderboardGroup,
+        rank_by: LeaderboardRank,
+        window: Duration,
+        limit: u32,
+    ) -> eyre::Result<Vec<LeaderboardEntry>> {
+        // executed legs each signal's key counts for
+        let legs = match group {
+            LeaderboardGroup::Pair => vec![(
+                "LEAST(sti.symbol, sto.symbol) || '-' || GREATEST(sti.symbol, sto.symbol)",
+                "'slow', 'fast'",
+            )],
+            LeaderboardGroup::Pool => vec![
+                ("sc.name || ':' || s.slow_pool_id", "'slow'"),
+                ("fc.name || ':' || s.fast_pool_id", "'fast'"),
+            ],
+            LeaderboardGroup::Chain => vec![("sc.name", "'slow'"), ("fc.name", "'fast'")],
+        };
+        let legs = legs
+            .into_iter()
+            .map(|(key, executed_legs)| {
+                let leg_count = executed_legs.split(',').count();
+                format!(
+                    r#"
+                    SELECT
+                        {key} AS key,
+                        sti.symbol AS profit_token,
+                        s.expected_profit_a::NUMERIC AS profit,
+                        (
+                            SELECT COUNT(DISTINCT e.leg)
+                            FROM executions e
+                            WHERE e.signal_id = s.id
+                                AND e.status = 'mined'
+                                AND e.leg IN ({executed_legs})
+                        ) = {leg_count} AS hit
+                    FROM {SIGNALS_WITH_SYMBOLS}
+                    WHERE s.created_at >= NOW() - make_interval(secs => $1)
+                    "#
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" UNION ALL ");
+        let order = match rank_by {
+            LeaderboardRank::Signals => "signals",
+            LeaderboardRank::ExpectedProfit => "SUM(profit)",
+            LeaderboardRank::HitRate => "hit_rate",
+        };
+
+        let query = format!(
+            r#"
+            SELECT
+                key,
+                profit_token,
+                COUNT(*) AS signals,
+                SUM(profit)::TEXT AS total_expected_profit,
+                AVG(CASE WHEN hit THEN 1 ELSE 0 END)::DOUBLE PRECISION AS hit_rate
+            FROM ({legs}) legs
+            GROUP BY key, profit_token
+            ORDER BY {order} DESC, signals DESC
+            LIMIT $2
+            "#
+        );
+        let rows: Vec<LeaderboardRow> = sqlx::query_as(&query)
+            .bind(window.as_secs_f64())
+            .bind(limit as i64)
+            .fetch_all(self.pool.as_ref())
+            .observe("signals.leaderboard")
+            .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(LeaderboardEntry {
+                    total_expected_profit: BigUint::from_str(&row.total_expected_profit)
+                        .wrap_err("failed to parse total expected profit from db")?,
+                    key: row.key,
+                    profit_token: row.profit_token,
+                    signals: row.signals as u64,
+                    hit_rate: row.hit_rate,
+                })
+            })
+            .collect()
+    }
+
+    /// Signal counts per chain route in `bucket` wide time buckets, newest first
+    #[instrument(skip(self))]
+    pub async fn count_bucketed(
+        &self,
+        bucket: Duration,
+        limit: u32,
+    ) -> eyre::Result<Vec<SignalBucket>> {
+        let query = format!(
+            r#"
+            SELECT
+                sc.name AS slow_chain,
+                fc.name AS fast_chain,
+                EXTRACT(EPOCH FROM {bucket})::BIGINT AS bucket_start,
+                COUNT(*) AS signals
+            FROM signals s
+            JOIN chains sc ON sc.id = s.slow_chain_id
+            JOIN chains fc ON fc.id = s.fast_chain_id
+            GROUP BY sc.name, fc.name, bucket_start
+            ORDER BY bucket_start DESC
+            LIMIT $2
+            "#,
+            bucket = time_bucket(self.timescale, "s.created_at", 1),
+        );
+
+        let rows: Vec<SignalBucketRow> = sqlx::query_as(&query)
+            .bind(bucket.as_secs_f64())
+            .bind(limit as i64)
+            .fetch_all(self.pool.as_ref())
+            .observe("signals.count_bucketed")
+            .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(SignalBucket {
+                    slow_chain: try_chain_from_str(&row.slow_chain, &self.tokens_config)?,
+                    fast_chain: try_chain_from_str(&row.fast_chain, &self.tokens_config)?,
+                    bucket_start: row.bucket_start as u64,
+                    signals: row.signals as u64,
+                })
+            })
             .collect()
     }
 }
 
+async fn insert_signal(
+    conn: &mut PgConnection,
+    signal: &signals::CrossChainSingleHop,
+) -> eyre::Result<i64> {
+    let (slow_swap, fast_swap) = (&signal.slow_swap_sim, &signal.fast_swap_sim);
+    let id = sqlx::query_scalar(
+        r#"
+        INSERT INTO signals (
+            slow_chain_id, slow_height, slow_pool_id,
+            fast_chain_id, fast_height, fast_pool_id,
+            slow_swap_token_in_id, slow_swap_token_out_id,
+            slow_swap_amount_in, slow_swap_amount_out, slow_swap_gas_cost,
+            fast_swap_token_in_id, fast_swap_token_out_id,
+            fast_swap_amount_in, fast_swap_amount_out, fast_swap_gas_cost,
+            surplus_a, surplus_b, expected_profit_a, expected_profit_b,
+            max_slippage_bps, congestion_risk_discount_bps
+        ) VALUES (
+            kuma_chain_id($1), $2, $3, kuma_chain_id($4), $5, $6,
+            kuma_token_id($1, $7, $8, $9), kuma_token_id($1, $10, $11, $12),
+            $13, $14, $15,
+            kuma_token_id($4, $16, $17, $18), kuma_token_id($4, $19, $20, $21),
+            $22, $23, $24, $25, $26, $27, $28, $29, $30
+        )
+        RETURNING id
+        "#,
+    )
+    .bind(signal.slow_chain.name.to_string())
+    .bind(signal.slow_height as i64)
+    .bind(signal.slow_pool_id.to_string())
+    .bind(signal.fast_chain.name.to_string())
+    .bind(signal.fast_height as i64)
+    .bind(signal.fast_pool_id.to_string())
+    .bind(&slow_swap.token_in.symbol)
+    .bind(slow_swap.token_in.address.to_string())
+    .bind(slow_swap.token_in.decimals as i32)
+    .bind(&slow_swap.token_out.symbol)
+    .bind(slow_swap.token_out.address.to_string())
+    .bind(slow_swap.token_out.decimals as i32)
+    .bind(slow_swap.amount_in.to_string())
+    .bind(slow_swap.amount_out.to_string())
+    .bind(slow_swap.gas_cost.to_string())
+    .bind(&fast_swap.token_in.symbol)
+    .bind(fast_swap.token_in.address.to_string())
+    .bind(fast_swap.token_in.decimals as i32)
+    .bind(&fast_swap.token_out.symbol)
+    .bind(fast_swap.token_out.address.to_string())
+    .bind(fast_swap.token_out.decimals as i32)
+    .bind(fast_swap.amount_in.to_string())
+    .bind(fast_swap.amount_out.to_string())
+    .bind(fast_swap.gas_cost.to_string())
+    .bind(signal.surplus.0.to_string())
+    .bind(signal.surplus.1.to_string())
+    .bind(signal.expected_profit.0.to_string())
+    .bind(signal.expected_profit.1.to_string())
+    .bind(signal.max_slippage_bps as i32)
+    .bind(signal.congestion_risk_discount_bps as i32)
+    .fetch_one(conn)
+    .await?;
+
+    Ok(id)
+}
+
+/// Whether `spot_prices` were taken at the signal's slow or fast block
+fn is_from_signal_blocks(spot_prices: &SpotPrices, signal: &signals::CrossChainSingleHop) -> bool {
+    (spot_prices.chain == signal.slow_chain && spot_prices.block_height == signal.slow_height)
+        || (spot_prices.chain == signal.fast_chain
+            && spot_prices.block_height == signal.fast_height)
+}
+
+/// Appends a `WHERE` clause for `filter` to a query over [`SIGNALS_WITH_SYMBOLS`].
+fn push_filter(query: &mut QueryBuilder<'_, Postgres>, filter: &SignalFilter) -> eyre::Result<()> {
+    query.push(" WHERE TRUE");
+
+    if let Some(chain) = &filter.slow_chain {
+        query
+            .push(" AND sc.name = ")
+            .push_bind(chain.name.to_string());
+    }
+    if let Some(chain) = &filter.fast_chain {
+        query
+            .push(" AND fc.name = ")
+            .push_bind(chain.name.to_string());
+    }
+
+    if let Some((token_a, token_b)) = &filter.pair {
+        query.push(" AND (");
+        match filter.direction {
+            Some(Direction::AtoB) => push_direction(query, token_a, token_b),
+            Some(Direction::BtoA) => push_direction(query, token_b, token_a),
+            None => {
+                push_direction(query, token_a, token_b);
+                query.push(" OR ");
+                push_direction(query, token_b, token_a);
+            }
+        }
+        query.push(")");
+    }
+
+    // matches the expression indexes on the profit columns
+    if let Some(min_profit) = &filter.min_expected_profit_a {
+        query
+            .push(" AND s.expected_profit_a::NUMERIC >= ")
+            .push_bind(min_profit.to_string())
+            .push("::NUMERIC");
+    }
+    if let Some(min_profit) = &filter.min_expected_profit_b {
+        query
+            .push(" AND s.expected_profit_b::NUMERIC >= ")
+            .push_bind(min_profit.to_string())
+            .push("::NUMERIC");
+    }
+
+    if let Some(since) = filter.since {
+        query
+            .push(" AND s.created_at >= to_timestamp(")
+            .push_bind(unix_secs(since)?)
+            .push(")");
+    }
+    if let Some(until) = filter.until {
+        query
+            .push(" AND s.created_at < to_timestamp(")
+            .push_bind(unix_secs(until)?)
+            .push(")");
+    }
+
+    Ok(())
+}
+
+/// Matches signals selling `sold` for `bought` on the slow chain and buying it back on the fast one.
+fn push_direction(query: &mut QueryBuilder<'_, Postgres>, sold: &str, bought: &str) {
+    query
+        .push("(sti.symbol = ")
+        .push_bind(sold.to_owned())
+        .push(" AND sto.symbol = ")
+        .push_bind(bought.to_owned())
+        .push(" AND fti.symbol = ")
+        .push_bind(bought.to_owned())
+        .push(" AND fto.symbol = ")
+        .push_bind(sold.to_owned())
+        .push(")");
+}
+
+pub(super) fn unix_secs(time: SystemTime) -> eyre::Result<f64> {
+    Ok(time
+        .duration_since(UNIX_EPOCH)
+        .wrap_err("time is before the unix epoch")?
+        .as_secs_f64())
+}
+
+#[derive(FromRow)]
+struct SignalBucketRow {
+    slow_chain: String,
+    fast_chain: String,
+    bucket_start: i64,
+    signals: i64,
+}
+
+#[derive(FromRow)]
+struct LeaderboardRow {
+    key: String,
+    profit_token: String,
+    signals: i64,
+    total_expected_profit: String,
+    hit_rate: f64,
+}
+
+#[derive(FromRow)]
+struct SignalRecordRow {
+    #[sqlx(flatten)]
+    signal: SignalRow,
+    created_at: i64,
+    slow_block_timestamp: Option<i64>,
+    fast_block_timestamp: Option<i64>,
+}
+
+#[derive(FromRow)]
 struct SignalRow {
     slow_chain: String,
     slow_height: i64,
@@ -244,6 +858,7 @@ fn try_signal_from_row(
         slow_swap_sim,
         fast_pool_id,
         fast_swap_sim,
+        block_skew_ms: None, // the block skew is not stored in the db
     })
 }
 
@@ -277,3 +892,112 @@ fn try_swap_from_symbols_and_amounts(
         gas_cost,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use tycho_common::{Bytes, models::token::Token};
+
+    use super::*;
+    use crate::database::migrate;
+
+    fn token(address: &str, symbol: &str, chain: &Chain) -> Token {
+        Token::new(
+            &Bytes::from_str(address).unwrap(),
+            symbol,
+            18,
+            0,
+            &[Some(0)],
+            chain.name,
+            100,
+        )
+    }
+
+    fn pair(chain: &Chain) -> Pair {
+        Pair::new(
+            token("0x0000000000000000000000000000000000000001", "AAA", chain),
+            token("0x0000000000000000000000000000000000000002", "BBB", chain),
+        )
+    }
+
+    fn swap(pair: &Pair) -> Swap {
+        Swap {
+            token_in: pair.token_a().clone(),
+            amount_in: BigUint::from(100u64),
+            token_out: pair.token_b().clone(),
+            amount_out: BigUint::from(110u64),
+            gas_cost: BigUint::default(),
+        }
+    }
+
+    fn spot_prices(chain: &Chain, block_height: u64) -> SpotPrices {
+        SpotPrices {
+            pair: pair(chain),
+            block_height,
+            min_price: 1.0,
+            max_price: 1.1,
+            min_pool_id: PoolId::from("0x1000"),
+            max_pool_id: PoolId::from("0x2000"),
+            chain: chain.clone(),
+            min_pool_meta: None,
+            max_pool_meta: None,
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at KUMA_TEST_DATABASE_URL"]
+    async fn failed_signal_insert_rolls_back_its_spot_prices() {
+        let url = std::env::var("KUMA_TEST_DATABASE_URL").unwrap();
+        let pool = Arc::new(PgPool::connect(&url).await.unwrap());
+        migrate(&pool).await.unwrap();
+        let repository = SignalRepository::new(Arc::clone(&pool), Arc::default(), false);
+
+        let (slow_chain, fast_chain) = (Chain::eth_mainnet(), Chain::base_mainnet());
+        // heights no other test writes to
+        let slow_height = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64
+            % i64::MAX as u64;
+        let fast_height = slow_height + 1;
+        let signal = signals::CrossChainSingleHop {
+            slow_chain: slow_chain.clone(),
+            slow_pair: pair(&slow_chain),
+            slow_protocol_component: None,
+            slow_pool_id: PoolId::from("0x1000"),
+            slow_swap_sim: swap(&pair(&slow_chain)),
+            slow_height,
+            fast_chain: fast_chain.clone(),
+            fast_pair: pair(&fast_chain),
+            fast_protocol_component: None,
+            fast_pool_id: PoolId::from("0x2000"),
+            fast_swap_sim: swap(&pair(&fast_chain)),
+            fast_height,
+            // outside the column's bps domain, failing the signal's insert
+            max_slippage_bps: 10_001,
+            congestion_risk_discount_bps: 0,
+            surplus: (BigUint::default(), BigUint::default()),
+            expected_profit: (BigUint::from(10u64), BigUint::default()),
+            block_skew_ms: None,
+        };
+        let spot_prices = [
+            spot_prices(&slow_chain, slow_height),
+            spot_prices(&fast_chain, fast_height),
+        ];
+
+        assert!(
+            repository
+                .insert_with_spot_prices(&signal, &spot_prices)
+                .await
+                .is_err()
+        );
+
+        let written: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM spot_prices WHERE block_height IN ($1, $2)")
+                .bind(slow_height as i64)
+                .bind(fast_height as i64)
+                .fetch_one(pool.as_ref())
+                .await
+                .unwrap();
+        assert_eq!(written, 0);
+    }
+}
@@ -1,53 +1,155 @@
-use std::sync::Arc;
+use std::{ops::Range, sync::Arc, time::Duration};
 
 use color_eyre::eyre::{self, eyre};
-use sqlx::PgPool;
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgPool, Postgres, QueryBuilder};
 
 use crate::{
+    chain::Chain,
     config::TokenAddressesForChain,
     spot_prices::SpotPrices,
-    state::{PoolId, pair::Pair},
+    state::{PoolId, pair::Pair, pool_meta::PoolMeta},
 };
 
-use super::{try_chain_from_str, try_token_from_chain_symbol};
+use super::{
+    observe::ObserveQuery as _, timescale::time_bucket, try_chain_from_str,
+    try_token_from_chain_symbol,
+};
+
+/// Rows per multi-row insert, keeping the bind count under Postgres' limit of 65535
+const INSERT_CHUNK_SIZE: usize = 1000;
+
+/// Spot prices `sp` joined with their chain `c` and tokens `ta`/`tb`
+const SPOT_PRICES_WITH_SYMBOLS: &str = r#"
+    spot_prices sp
+    JOIN chains c ON c.id = sp.chain_id
+    JOIN tokens ta ON ta.id = sp.token_a_id
+    JOIN tokens tb ON tb.id = sp.token_b_id
+"#;
+
+/// Spot price range of a pair on a chain over one time bucket.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpotPriceBucket {
+    pub chain: Chain,
+    /// Start of the bucket, in seconds since the unix epoch
+    pub bucket_start: u64,
+    pub min_price: f64,
+    pub max_price: f64,
+    /// Number of blocks in the bucket
+    pub blocks: u64,
+}
+
+/// Spot prices as stored, with when they were recorded.
+#[derive(Debug, Clone, Serialize)]
+pub struct SpotPriceRecord {
+    #[serde(flatten)]
+    pub spot_prices: SpotPrices,
+    /// When the row was inserted, in seconds since the unix epoch
+    pub created_at: u64,
+    /// Timestamp of the block the prices were taken at, if the block was recorded
+    pub block_timestamp: Option<u64>,
+}
+
+/// Latest price quoted by a pool, from the `latest_pool_spot_prices` materialized view.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PoolSpotPrice {
+    pub chain: Chain,
+    pub pair: Pair,
+    pub pool_id: PoolId,
+    pub price: f64,
+    pub block_height: u64,
+}
+
+/// Which chain a spread buys on, selling on the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpreadSide {
+    Slow,
+    Fast,
+}
+
+/// Best cross-chain spread of a pair between a slow chain block and the latest fast chain block
+/// recorded before it. Prices are of the pair's first token in its second.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Spread {
+    pub slow_height: u64,
+    pub fast_height: u64,
+    /// When the slow block's prices were recorded, in seconds since the unix epoch
+    pub timestamp: u64,
+    pub slow_min_price: f64,
+    pub slow_max_price: f64,
+    pub fast_min_price: f64,
+    pub fast_max_price: f64,
+    /// Chain of the cheapest pool to buy from
+    pub buy_on: SpreadSide,
+    /// Selling at the other chain's priciest pool over buying at the cheapest one, in bps.
+    /// Negative when no direction is profitable before fees.
+    pub spread_bps: f64,
+}
+
+impl Spread {
+    fn new(row: SpreadRow) -> Self {
+        // buy the first token where it's cheapest and sell it where it's priciest
+        let buy_slow = (row.fast_max_price / row.slow_min_price - 1.0) * 10_000.0;
+        let buy_fast = (row.slow_max_price / row.fast_min_price - 1.0) * 10_000.0;
+        let (buy_on, spread_bps) = if buy_slow >= buy_fast {
+            (SpreadSide::Slow, buy_slow)
+        } else {
+            (SpreadSide::Fast, buy_fast)
+        };
+
+        Self {
+            slow_height: row.slow_height as u64,
+            fast_height: row.fast_height as u64,
+            timestamp: row.timestamp as u64,
+            slow_min_price: row.slow_min_price,
+            slow_max_price: row.slow_max_price,
+            fast_min_price: row.fast_min_price,
+            fast_max_price: row.fast_max_price,
+            buy_on,
+            spread_bps,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct SpotPriceRepository {
     pool: Arc<PgPool>,
     token_configs: Arc<TokenAddressesForChain>,
+    timescale: bool,
 }
 
 impl SpotPriceRepository {
-    pub(super) fn new(pool: Arc<PgPool>, token_configs: Arc<TokenAddressesForChain>) -> Self {
+    pub(super) fn new(
+        pool: Arc<PgPool>,
+        token_configs: Arc<TokenAddressesForChain>,
+        timescale: bool,
+    ) -> Self {
         Self {
             pool,
             token_configs,
+            timescale,
         }
     }
 
     pub async fn insert(&self, spot_prices: SpotPrices) -> eyre::Result<()> {
-        sqlx::query!(
-            r#"
-            INSERT INTO spot_prices (
-                token_a_symbol,
-                token_b_symbol,
-                min_price, max_price, min_pool_id, max_pool_id,
-                block_height, chain
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-            "#,
-            spot_prices.pair.token_a().symbol,
-            spot_prices.pair.token_b().symbol,
-            spot_prices.min_price,
-            spot_prices.max_price,
-            spot_prices.min_pool_id.to_string(),
-            spot_prices.max_pool_id.to_string(),
-            spot_prices.block_height as i64,
-            spot_prices.chain.name.to_string(),
-        )
-        .execute(self.pool.as_ref())
-        .await?;
+        self.insert_many(std::slice::from_ref(&spot_prices)).await
+    }
+
+    /// Inserts spot prices with multi-row `INSERT`s in a single transaction.
+    pub async fn insert_many(&self, spot_prices: &[SpotPrices]) -> eyre::Result<()> {
+        if spot_prices.is_empty() {
+            return Ok(());
+        }
 
-        Ok(())
+        async {
+            let mut tx = self.pool.begin().await?;
+            insert_spot_prices(&mut tx, spot_prices).await?;
+            tx.commit().await?;
+            eyre::Ok(())
+        }
+        .observe("spot_prices.insert_many")
+        .await
     }
 
     pub async fn count_by_symbols(
@@ -55,18 +157,20 @@ impl SpotPriceRepository {
         token_a_symbol: &str,
         token_b_symbol: &str,
     ) -> eyre::Result<u64> {
-        let count: i64 = sqlx::query_scalar(
+        let query = format!(
             r#"
             SELECT COUNT(*) as count
-            FROM spot_prices
-            WHERE ((token_a_symbol = $1 AND token_b_symbol = $2)
-                OR (token_a_symbol = $2 AND token_b_symbol = $1))
-            "#,
-        )
-        .bind(token_a_symbol)
-        .bind(token_b_symbol)
-        .fetch_one(self.pool.as_ref())
-        .await?;
+            FROM {SPOT_PRICES_WITH_SYMBOLS}
+            WHERE ((ta.symbol = $1 AND tb.symbol = $2)
+                OR (ta.symbol = $2 AND tb.symbol = $1))
+            "#
+        );
+        let count: i64 = sqlx::query_scalar(&query)
+            .bind(token_a_symbol)
+            .bind(token_b_symbol)
+            .fetch_one(self.pool.as_ref())
+            .observe("spot_prices.count_by_symbols")
+            .await?;
 
         Ok(count as u64)
     }
@@ -78,33 +182,351 @@ impl SpotPriceRepository {
         limit: u32,
         offset: u32,
     ) -> eyre::Result<Vec<SpotPrices>> {
-        let rows = sqlx::query_as!(
-            SpotPriceRow,
+        let query = format!(
             r#"
             SELECT
-                token_a_symbol,
-                token_b_symbol,
-                block_height, min_price, max_price, min_pool_id, max_pool_id, chain
-            FROM spot_prices
-            WHERE ((token_a_symbol = $1 AND token_b_symbol = $2)
-                OR (token_a_symbol = $2 AND token_b_symbol = $1))
-            ORDER BY created_at DESC
+                ta.symbol AS token_a_symbol,
+                tb.symbol AS token_b_symbol,
+                sp.block_height, sp.min_price, sp.max_price, sp.min_pool_id, sp.max_pool_id,
+                sp.min_pool_protocol, sp.min_pool_fee_pips, sp.max_pool_protocol, sp.max_pool_fee_pips,
+                c.name AS chain
+            FROM {SPOT_PRICES_WITH_SYMBOLS}
+            WHERE ((ta.symbol = $1 AND tb.symbol = $2)
+                OR (ta.symbol = $2 AND tb.symbol = $1))
+            ORDER BY sp.created_at DESC
             LIMIT $3 OFFSET $4
+            "#
+        );
+        let rows: Vec<SpotPriceRow> = sqlx::query_as(&query)
+            .bind(token_a_symbol)
+            .bind(token_b_symbol)
+            .bind(limit as i64)
+            .bind(offset as i64)
+            .fetch_all(self.pool.as_ref())
+            .observe("spot_prices.get_by_symbols")
+            .await?;
+
+        rows.into_iter()
+            .map(|r| try_spot_price_from_row(r, &self.token_configs))
+            .collect()
+    }
+
+    /// Per-chain spot price ranges of a pair in `bucket` wide time buckets, newest first
+    pub async fn get_bucketed_by_symbols(
+        &self,
+        token_a_symbol: &str,
+        token_b_symbol: &str,
+        bucket: Duration,
+        limit: u32,
+    ) -> eyre::Result<Vec<SpotPriceBucket>> {
+        let query = format!(
+            r#"
+            SELECT
+                c.name AS chain,
+                EXTRACT(EPOCH FROM {bucket})::BIGINT AS bucket_start,
+                MIN(sp.min_price) AS min_price,
+                MAX(sp.max_price) AS max_price,
+                COUNT(*) AS blocks
+            FROM {SPOT_PRICES_WITH_SYMBOLS}
+            WHERE ((ta.symbol = $1 AND tb.symbol = $2)
+                OR (ta.symbol = $2 AND tb.symbol = $1))
+            GROUP BY c.name, bucket_start
+            ORDER BY bucket_start DESC
+            LIMIT $4
+            "#,
+            bucket = time_bucket(self.timescale, "sp.created_at", 3),
+        );
+
+        let rows: Vec<SpotPriceBucketRow> = sqlx::query_as(&query)
+            .bind(token_a_symbol)
+            .bind(token_b_symbol)
+            .bind(bucket.as_secs_f64())
+            .bind(limit as i64)
+            .fetch_all(self.pool.as_ref())
+            .observe("spot_prices.get_bucketed_by_symbols")
+            .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(SpotPriceBucket {
+                    chain: try_chain_from_str(&row.chain, &self.token_configs)?,
+                    bucket_start: row.bucket_start as u64,
+                    min_price: row.min_price,
+                    max_price: row.max_price,
+                    blocks: row.blocks as u64,
+                })
+            })
+            .collect()
+    }
+
+    /// Spot prices of the pair inserted within `from..=to` (unix seconds), oldest first
+    pub async fn get_by_time_range(
+        &self,
+        token_a_symbol: &str,
+        token_b_symbol: &str,
+        from: u64,
+        to: u64,
+    ) -> eyre::Result<Vec<SpotPriceRecord>> {
+        let query = format!(
+            r#"
+            SELECT
+                ta.symbol AS token_a_symbol,
+                tb.symbol AS token_b_symbol,
+                sp.block_height, sp.min_price, sp.max_price, sp.min_pool_id, sp.max_pool_id,
+                sp.min_pool_protocol, sp.min_pool_fee_pips, sp.max_pool_protocol, sp.max_pool_fee_pips,
+                c.name AS chain,
+                EXTRACT(EPOCH FROM sp.created_at)::BIGINT AS created_at,
+                EXTRACT(EPOCH FROM b.timestamp)::BIGINT AS block_timestamp
+            FROM {SPOT_PRICES_WITH_SYMBOLS}
+            LEFT JOIN blocks b ON b.chain = c.name AND b.height = sp.block_height
+            WHERE ((ta.symbol = $1 AND tb.symbol = $2)
+                OR (ta.symbol = $2 AND tb.symbol = $1))
+                AND sp.created_at BETWEEN to_timestamp($3) AND to_timestamp($4)
+            ORDER BY sp.created_at
+            "#
+        );
+        let rows: Vec<SpotPriceRecordRow> = sqlx::query_as(&query)
+            .bind(token_a_symbol)
+            .bind(token_b_symbol)
+            .bind(from as f64)
+            .bind(to as f64)
+            .fetch_all(self.pool.as_ref())
+            .observe("spot_prices.get_by_time_range")
+            .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(SpotPriceRecord {
+                    spot_prices: try_spot_price_from_row(row.spot_prices, &self.token_configs)?,
+                    created_at: row.created_at as u64,
+                    block_timestamp: row.block_timestamp.map(|timestamp| timestamp as u64),
+                })
+            })
+            .collect()
+    }
+
+    /// Latest price of every pool quoting the pair, as of the last view refresh
+    pub async fn latest_pool_prices(
+        &self,
+        token_a_symbol: &str,
+        token_b_symbol: &str,
+    ) -> eyre::Result<Vec<PoolSpotPrice>> {
+        let rows: Vec<PoolSpotPriceRow> = sqlx::query_as(
+            r#"
+            SELECT
+                c.name AS chain,
+                ta.symbol AS token_a_symbol,
+                tb.symbol AS token_b_symbol,
+                lp.pool_id, lp.price, lp.block_height
+            FROM latest_pool_spot_prices lp
+            JOIN chains c ON c.id = lp.chain_id
+            JOIN tokens ta ON ta.id = lp.token_a_id
+            JOIN tokens tb ON tb.id = lp.token_b_id
+            WHERE ((ta.symbol = $1 AND tb.symbol = $2)
+                OR (ta.symbol = $2 AND tb.symbol = $1))
+            ORDER BY c.name, lp.price
             "#,
-            token_a_symbol,
-            token_b_symbol,
-            limit as i64,
-            offset as i64,
         )
+        .bind(token_a_symbol)
+        .bind(token_b_symbol)
         .fetch_all(self.pool.as_ref())
+        .observe("spot_prices.latest_pool_prices")
         .await?;
 
         rows.into_iter()
-            .map(|r| try_spot_price_from_row(r, &self.token_configs))
+            .map(|row| {
+                let chain = try_chain_from_str(&row.chain, &self.token_configs)?;
+                let token_a =
+                    try_token_from_chain_symbol(&row.token_a_symbol, &chain, &self.token_configs)
+                        .map_err(|e| eyre!("failed to parse token a from db: {e:}"))?;
+                let token_b =
+                    try_token_from_chain_symbol(&row.token_b_symbol, &chain, &self.token_configs)
+                        .map_err(|e| eyre!("failed to parse token b from db: {e:}"))?;
+                Ok(PoolSpotPrice {
+                    chain,
+                    pair: Pair::new(token_a, token_b),
+                    pool_id: PoolId::from(row.pool_id.as_str()),
+                    price: row.price,
+                    block_height: row.block_height as u64,
+                })
+            })
             .collect()
     }
+
+    /// Up to `limit` spreads of the pair between `slow_chain` and `fast_chain` for slow blocks
+    /// recorded within `recorded` (unix seconds), newest first
+    pub async fn spreads(
+        &self,
+        token_a_symbol: &str,
+        token_b_symbol: &str,
+        slow_chain: &Chain,
+        fast_chain: &Chain,
+        recorded: Range<u64>,
+        limit: u32,
+    ) -> eyre::Result<Vec<Spread>> {
+        // prices of the pair as `$1` in `$2`, whichever order the chain stores it in
+        let oriented = |chain_param: &str| {
+            format!(
+                r#"
+                SELECT
+                    sp.block_height, sp.created_at,
+                    CASE WHEN ta.symbol = $1 THEN sp.min_price ELSE 1 / sp.max_price END AS min_price,
+                    CASE WHEN ta.symbol = $1 THEN sp.max_price ELSE 1 / sp.min_price END AS max_price
+                FROM {SPOT_PRICES_WITH_SYMBOLS}
+                WHERE c.name = {chain_param}
+                    AND ((ta.symbol = $1 AND tb.symbol = $2)
+                        OR (ta.symbol = $2 AND tb.symbol = $1))
+                    AND sp.min_price > 0 AND sp.max_price > 0
+                "#
+            )
+        };
+        let query = format!(
+            r#"
+            WITH slow AS ({slow}), fast AS ({fast})
+            SELECT
+                s.block_height AS slow_height,
+                f.block_height AS fast_height,
+                EXTRACT(EPOCH FROM s.created_at)::BIGINT AS timestamp,
+                s.min_price AS slow_min_price,
+                s.max_price AS slow_max_price,
+                f.min_price AS fast_min_price,
+                f.max_price AS fast_max_price
+            FROM slow s
+            JOIN LATERAL (
+                SELECT * FROM fast
+                WHERE fast.created_at <= s.created_at
+                ORDER BY fast.created_at DESC
+                LIMIT 1
+            ) f ON TRUE
+            WHERE s.created_at >= to_timestamp($5) AND s.created_at < to_timestamp($6)
+            ORDER BY s.created_at DESC
+            LIMIT $7
+            "#,
+            slow = oriented("$3"),
+            fast = oriented("$4"),
+        );
+
+        let rows: Vec<SpreadRow> = sqlx::query_as(&query)
+            .bind(token_a_symbol)
+            .bind(token_b_symbol)
+            .bind(slow_chain.name.to_string())
+            .bind(fast_chain.name.to_string())
+            .bind(recorded.start as f64)
+            .bind(recorded.end as f64)
+            .bind(limit as i64)
+            .fetch_all(self.pool.as_ref())
+            .observe("spot_prices.spreads")
+            .await?;
+
+        Ok(rows.into_iter().map(Spread::new).collect())
+    }
+}
+
+#[derive(FromRow)]
+struct SpreadRow {
+    slow_height: i64,
+    fast_height: i64,
+    timestamp: i64,
+    slow_min_price: f64,
+    slow_max_price: f64,
+    fast_min_price: f64,
+    fast_max_price: f64,
+}
+
+/// Inserts spot prices with multi-row `INSERT`s on `conn`, e.g. inside a caller's transaction.
+pub(super) async fn insert_spot_prices(
+    conn: &mut PgConnection,
+    spot_prices: &[SpotPrices],
+) -> eyre::Result<()> {
+    for chunk in spot_prices.chunks(INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            INSERT INTO spot_prices (
+                chain_id, token_a_id, token_b_id,
+                min_price, max_price, min_pool_id, max_pool_id,
+                min_pool_protocol, min_pool_fee_pips, max_pool_protocol, max_pool_fee_pips,
+                block_height
+            )
+            SELECT
+                kuma_chain_id(chain),
+                kuma_token_id(chain, token_a_symbol, token_a_address, token_a_decimals),
+                kuma_token_id(chain, token_b_symbol, token_b_address, token_b_decimals),
+                min_price, max_price, min_pool_id, max_pool_id,
+                min_pool_protocol, min_pool_fee_pips, max_pool_protocol, max_pool_fee_pips,
+                block_height
+            FROM ("#,
+        );
+        query.push_values(chunk, |mut row, spot_prices| {
+            let (token_a, token_b) = (spot_prices.pair.token_a(), spot_prices.pair.token_b());
+            row.push_bind(spot_prices.chain.name.to_string())
+                .push_bind(&token_a.symbol)
+                .push_bind(token_a.address.to_string())
+                .push_bind(token_a.decimals as i32)
+                .push_bind(&token_b.symbol)
+                .push_bind(token_b.address.to_string())
+                .push_bind(token_b.decimals as i32)
+                .push_bind(spot_prices.min_price)
+                .push_bind(spot_prices.max_price)
+                .push_bind(spot_prices.min_pool_id.to_string())
+                .push_bind(spot_prices.max_pool_id.to_string())
+                .push_bind(protocol(&spot_prices.min_pool_meta))
+                .push_bind(fee_pips(&spot_prices.min_pool_meta))
+                .push_bind(protocol(&spot_prices.max_pool_meta))
+                .push_bind(fee_pips(&spot_prices.max_pool_meta))
+                .push_bind(spot_prices.block_height as i64);
+        });
+        query.push(
+            r#") AS v (
+                chain,
+                token_a_symbol, token_a_address, token_a_decimals,
+                token_b_symbol, token_b_address, token_b_decimals,
+                min_price, max_price, min_pool_id, max_pool_id,
+                min_pool_protocol, min_pool_fee_pips, max_pool_protocol, max_pool_fee_pips,
+                block_height
+            )"#,
+        );
+        query.build().execute(&mut *conn).await?;
+    }
+
+    Ok(())
+}
+
+fn protocol(meta: &Option<PoolMeta>) -> Option<String> {
+    meta.as_ref().map(|meta| meta.protocol_system.clone())
+}
+
+fn fee_pips(meta: &Option<PoolMeta>) -> Option<i32> {
+    meta.as_ref()?.fee_pips.map(|fee| fee as i32)
+}
+
+#[derive(FromRow)]
+struct SpotPriceRecordRow {
+    #[sqlx(flatten)]
+    spot_prices: SpotPriceRow,
+    created_at: i64,
+    block_timestamp: Option<i64>,
+}
+
+#[derive(FromRow)]
+struct PoolSpotPriceRow {
+    chain: String,
+    token_a_symbol: String,
+    token_b_symbol: String,
+    pool_id: String,
+    price: f64,
+    block_height: i64,
+}
+
+#[derive(FromRow)]
+struct SpotPriceBucketRow {
+    chain: String,
+    bucket_start: i64,
+    min_price: f64,
+    max_price: f64,
+    blocks: i64,
 }
 
+#[derive(FromRow)]
 struct SpotPriceRow {
     chain: String,
     block_height: i64,
@@ -112,6 +534,10 @@ struct SpotPriceRow {
     max_pool_id: String,
     min_price: f64,
     max_price: f64,
+    min_pool_protocol: Option<String>,
+    min_pool_fee_pips: Option<i32>,
+    max_pool_protocol: Option<String>,
+    max_pool_fee_pips: Option<i32>,
     token_a_symbol: String,
     token_b_symbol: String,
 }
@@ -140,5 +566,11 @@ fn try_spot_price_from_row(
         min_pool_id,
         max_pool_id,
         chain,
+        min_pool_meta: row
+            .min_pool_protocol
+            .map(|protocol| PoolMeta::new(protocol, row.min_pool_fee_pips.map(|fee| fee as u32))),
+        max_pool_meta: row
+            .max_pool_protocol
+            .map(|protocol| PoolMeta::new(protocol, row.max_pool_fee_pips.map(|fee| fee as u32))),
     })
 }
@@ -0,0 +1,80 @@
+//! Opt-in TimescaleDB support.
+//!
+//! Converts the time-series tables into hypertables partitioned on `created_at` and adds
+//! compression policies. Repositories pick [`time_bucket`] over Postgres' `date_bin` when it's
+//! enabled, so the same queries run against vanilla Postgres.
+use color_eyre::eyre::{self, WrapErr as _};
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::config::TimescaleConfig;
+
+/// Hypertables and the column their compressed chunks are segmented by
+const HYPERTABLES: &[(&str, &str)] = &[("spot_prices", "chain_id"), ("signals", "slow_chain_id")];
+
+/// Creates the hypertables and compression policies that don't exist yet.
+pub(super) async fn setup(pool: &PgPool, config: &TimescaleConfig) -> eyre::Result<()> {
+    sqlx::query("CREATE EXTENSION IF NOT EXISTS timescaledb")
+        .execute(pool)
+        .await
+        .wrap_err("failed to enable the timescaledb extension")?;
+
+    for &(table, segment_by) in HYPERTABLES {
+        let is_hypertable: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM timescaledb_information.hypertables WHERE hypertable_name = $1
+            )
+            "#,
+        )
+        .bind(table)
+        .fetch_one(pool)
+        .await?;
+        if is_hypertable {
+            continue;
+        }
+
+        let mut tx = pool.begin().await?;
+        // unique constraints on a hypertable must include its time column
+        for statement in [
+            format!("ALTER TABLE {table} DROP CONSTRAINT IF EXISTS {table}_pkey"),
+            format!("ALTER TABLE {table} ALTER COLUMN created_at SET NOT NULL"),
+            format!("ALTER TABLE {table} ADD PRIMARY KEY (id, created_at)"),
+            format!("SELECT create_hypertable('{table}', 'created_at', migrate_data => TRUE)"),
+            format!(
+                "ALTER TABLE {table} SET (timescaledb.compress, timescaledb.compress_segmentby = '{segment_by}')"
+            ),
+        ] {
+            sqlx::query(&statement)
+                .execute(&mut *tx)
+                .await
+                .wrap_err_with(|| format!("failed to convert {table} to a hypertable"))?;
+        }
+        sqlx::query(
+            "SELECT add_compression_policy($1::regclass, make_interval(hours => $2), if_not_exists => TRUE)",
+        )
+        .bind(table)
+        .bind(config.compress_after_hours as i32)
+        .execute(&mut *tx)
+        .await
+        .wrap_err_with(|| format!("failed to add a compression policy to {table}"))?;
+        tx.commit().await?;
+
+        info!(
+            table,
+            compress_after_hours = config.compress_after_hours,
+            "Converted table to a hypertable"
+        );
+    }
+
+    Ok(())
+}
+
+/// SQL expression truncating `column` to buckets of `$param` seconds.
+pub(super) fn time_bucket(timescale: bool, column: &str, param: usize) -> String {
+    if timescale {
+        format!("time_bucket(make_interval(secs => ${param}), {column})")
+    } else {
+        format!("date_bin(make_interval(secs => ${param}), {column}, TIMESTAMPTZ '2000-01-01')")
+    }
+}
@@ -0,0 +1,50 @@
+//! Materialized views backing dashboard queries.
+//!
+//! Scanning `spot_prices` and `signals` for the latest price per pool or per-hour signal counts
+//! gets slow as they grow, so those are precomputed into views that a background task refreshes.
+use std::{sync::Arc, time::Duration};
+
+use sqlx::PgPool;
+use tokio::{select, time::MissedTickBehavior};
+use tokio_util::sync::{CancellationToken, DropGuard};
+use tracing::{debug, warn};
+
+/// Materialized views refreshed by [`spawn_refresh`]
+const VIEWS: &[&str] = &["latest_pool_spot_prices", "hourly_signal_stats"];
+
+/// Spawns the task refreshing the views every `interval`, which stops once the guard is dropped.
+pub(super) fn spawn_refresh(pool: Arc<PgPool>, interval: Duration) -> DropGuard {
+    let cancel = CancellationToken::new();
+    let cancelled = cancel.clone();
+
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            select! {
+                () = cancelled.cancelled() => break,
+                _ = ticks.tick() => refresh(&pool).await,
+            }
+        }
+    });
+
+    cancel.drop_guard()
+}
+
+async fn refresh(pool: &PgPool) {
+    for view in VIEWS {
+        // concurrent refreshes don't block readers of the view
+        match sqlx::query(&format!("REFRESH MATERIALIZED VIEW CONCURRENTLY {view}"))
+            .execute(pool)
+            .await
+        {
+            Ok(_) => debug!(view, "Refreshed materialized view"),
+            Err(e) => {
+                metrics::counter!("kuma_db_view_refresh_failures_total", "view" => *view)
+                    .increment(1);
+                warn!(view, error = %e, "Failed to refresh materialized view");
+            }
+        }
+    }
+}
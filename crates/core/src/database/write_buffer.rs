@@ -0,0 +1,474 @@
+//! Bounded buffer for writes made while the database is unreachable.
+//!
+//! Writes are queued and applied in order by a background task. A write failing because the
+//! database can't be reached is retried with exponential backoff until it succeeds, one the
+//! database rejects, e.g. for violating a constraint, is given up on right away and any other
+//! failure after `max_attempts`. Writes given up on are appended to the dead letter file if one is
+//! configured and dropped otherwise. When the queue is full, writes are appended to the spill
+//! file if one is configured and dropped otherwise.
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use color_eyre::eyre::{self, WrapErr as _};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::AsyncWriteExt as _,
+    sync::{Mutex, mpsc},
+};
+use tracing::{error, info, warn};
+
+use crate::{config::WriteBufferConfig, signals, spot_prices::SpotPrices};
+
+use super::{SignalRepository, SpotPriceRepository};
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// A write that can be buffered until the database accepts it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Write {
+    SpotPrices(Vec<SpotPrices>),
+    Signal(signals::CrossChainSingleHop),
+    /// A signal and the spot prices of its blocks, written in one transaction
+    SignalWithSpotPrices {
+        signal: signals::CrossChainSingleHop,
+        spot_prices: Vec<SpotPrices>,
+    },
+}
+
+impl Write {
+    fn kind(&self) -> &'static str {
+        match self {
+            Write::SpotPrices(_) => "spot_prices",
+            Write::Signal(_) => "signal",
+            Write::SignalWithSpotPrices { .. } => "signal_with_spot_prices",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct WriteBuffer {
+    tx: mpsc::Sender<Write>,
+    spill: Option<Arc<JsonLines>>,
+}
+
+impl WriteBuffer {
+    /// Spawns the task flushing buffered writes, which stops once the buffer is dropped.
+    pub(super) fn spawn(
+        config: &WriteBufferConfig,
+        spot_prices: SpotPriceRepository,
+        signals: SignalRepository,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel(config.capacity);
+        let spill = config
+            .spill_path
+            .clone()
+            .map(|path| Arc::new(JsonLines::new(path)));
+
+        let flusher = Flusher {
+            rx,
+            capacity: config.capacity,
+            spill: spill.clone(),
+            dead_letters: config.dead_letter_path.clone().map(JsonLines::new),
+            max_backoff: config.max_backoff(),
+            max_attempts: config.max_attempts.max(1),
+            spot_prices,
+            signals,
+        };
+        tokio::spawn(flusher.run());
+
+        Self { tx, spill }
+    }
+
+    /// Queues a write, spilling or dropping it if the buffer is full.
+    pub(super) async fn push(&self, write: Write) -> eyre::Result<()> {
+        let write = match self.tx.try_send(write) {
+            Ok(()) => {
+                record_depth(&self.tx);
+                return Ok(());
+            }
+            Err(mpsc::error::TrySendError::Full(write)) => write,
+            Err(mpsc::error::TrySendError::Closed(write)) => {
+                metrics::counter!("kuma_db_write_buffer_dropped_total", "kind" => write.kind())
+                    .increment(1);
+                eyre::bail!("write buffer is closed, dropped {} write", write.kind());
+            }
+        };
+
+        let kind = write.kind();
+        match &self.spill {
+            Some(spill) => match spill.append(&write).await {
+                Ok(()) => {
+                    metrics::counter!("kuma_db_write_buffer_spilled_total", "kind" => kind)
+                        .increment(1);
+                    Ok(())
+                }
+                Err(e) => {
+                    metrics::counter!("kuma_db_write_buffer_dropped_total", "kind" => kind)
+                        .increment(1);
+                    Err(e.wrap_err(format!("write buffer is full, dropped {kind} write")))
+                }
+            },
+            None => {
+                metrics::counter!("kuma_db_write_buffer_dropped_total", "kind" => kind)
+                    .increment(1);
+                eyre::bail!("write buffer is full, dropped {kind} write")
+            }
+        }
+    }
+}
+
+struct Flusher {
+    rx: mpsc::Receiver<Write>,
+    capacity: usize,
+    spill: Option<Arc<JsonLines>>,
+    dead_letters: Option<JsonLines>,
+    max_backoff: Duration,
+    max_attempts: u32,
+    spot_prices: SpotPriceRepository,
+    signals: SignalRepository,
+}
+
+impl Flusher {
+    async fn run(mut self) {
+        while let Some(write) = self.rx.recv().await {
+            self.write_with_retry(write).await;
+            metrics::gauge!("kuma_db_write_buffer_depth").set(self.rx.len() as f64);
+
+            // spilled writes are older than anything still queued in memory, but only fit back in
+            // once the queue has drained
+            if self.rx.is_empty() {
+                self.unspill().await;
+            }
+        }
+        info!("Write buffer closed, stopped flushing");
+    }
+
+    /// Applies `write`, retrying it per the kind of failure until it's written or given up on.
+    async fn write_with_retry(&self, write: Write) {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempts = 0;
+        loop {
+            let result = match &write {
+                Write::SpotPrices(spot_prices) => self.spot_prices.insert_many(spot_prices).await,
+                Write::Signal(signal) => self.signals.insert(signal.clone()).await.map(|_| ()),
+                Write::SignalWithSpotPrices {
+                    signal,
+                    spot_prices,
+                } => {
+                    self.signals
+                        .insert_with_spot_prices(signal, spot_prices)
+                        .await
+                }
+            };
+            let Err(e) = result else {
+                return;
+            };
+
+            let failure = Failure::of(&e);
+            // an unreachable database fails every write alike, so those don't count
+            if failure != Failure::Unreachable {
+                attempts += 1;
+            }
+            if failure == Failure::Rejected || attempts >= self.max_attempts {
+                error!(
+                    error = %e,
+                    kind = write.kind(),
+                    ?failure,
+                    attempts,
+                    "Buffered database write failed, giving up on it"
+                );
+                self.dead_letter(&write).await;
+                return;
+            }
+
+            warn!(
+                error = %e,
+                kind = write.kind(),
+                ?failure,
+                retry_in = ?backoff,
+                queued = self.rx.len(),
+                "Buffered database write failed, retrying"
+            );
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.max_backoff);
+        }
+    }
+
+    /// Sets aside a write given up on in the dead letter file, or drops it without one.
+    async fn dead_letter(&self, write: &Write) {
+        let kind = write.kind();
+        if let Some(dead_letters) = &self.dead_letters {
+            match dead_letters.append(write).await {
+                Ok(()) => {
+                    metrics::counter!("kuma_db_write_buffer_dead_letters_total", "kind" => kind)
+                        .increment(1);
+                    return;
+                }
+                Err(e) => {
+                    error!(error = %e, path = %dead_letters.path.display(), "Failed to dead letter write");
+                }
+            }
+        }
+        metrics::counter!("kuma_db_write_buffer_dropped_total", "kind" => kind).increment(1);
+    }
+
+    /// Writes spilled writes back, up to the buffer's capacity per pass. They're only removed
+    /// from the spill file once written, so a crash mid-flush writes them again rather than losing
+    /// them.
+    async fn unspill(&self) {
+        let Some(spill) = self.spill.as_ref() else {
+            return;
+        };
+        let (writes, len) = match spill.peek(self.capacity).await {
+            Ok(peeked) => peeked,
+            Err(e) => {
+                error!(error = %e, path = %spill.path.display(), "Failed to read spilled writes");
+                return;
+            }
+        };
+        if len == 0 {
+            return;
+        }
+
+        info!(count = writes.len(), "Flushing spilled database writes");
+        for write in writes {
+            self.write_with_retry(write).await;
+        }
+
+        if let Err(e) = spill.remove_front(len).await {
+            error!(error = %e, path = %spill.path.display(), "Failed to remove flushed spilled writes");
+        }
+    }
+}
+
+/// Why a write failed, which decides whether it's retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    /// The database couldn't be reached, retried until it can
+    Unreachable,
+    /// The database refused the write itself, which won't succeed later either
+    Rejected,
+    /// Anything else, like a deadlock or serialization failure, retried up to `max_attempts`
+    Other,
+}
+
+impl Failure {
+    fn of(e: &eyre::Report) -> Self {
+        let Some(e) = e.chain().find_map(|e| e.downcast_ref::<sqlx::Error>()) else {
+            // failed before reaching the database, e.g. spot prices of another block
+            return Self::Rejected;
+        };
+
+        match e {
+            sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed => Self::Unreachable,
+            sqlx::Error::Database(e) => Self::of_sqlstate(e.code().as_deref().unwrap_or_default()),
+            sqlx::Error::RowNotFound
+            | sqlx::Error::TypeNotFound { .. }
+            | sqlx::Error::ColumnNotFound(_)
+            | sqlx::Error::ColumnIndexOutOfBounds { .. }
+            | sqlx::Error::ColumnDecode { .. }
+            | sqlx::Error::Decode(_) => Self::Rejected,
+            _ => Self::Other,
+        }
+    }
+
+    /// Classifies a postgres error by its SQLSTATE class
+    fn of_sqlstate(code: &str) -> Self {
+        match code.get(..2) {
+            // connection exception, insufficient resources, operator intervention
+            Some("08" | "53" | "57") => Self::Unreachable,
+            // data exception, integrity constraint violation, syntax error or access rule
+            // violation
+            Some("22" | "23" | "42") => Self::Rejected,
+            _ => Self::Other,
+        }
+    }
+}
+
+fn record_depth(tx: &mpsc::Sender<Write>) {
+    metrics::gauge!("kuma_db_write_buffer_depth").set((tx.max_capacity() - tx.capacity()) as f64);
+}
+
+/// A file of writes, one JSON line each, whose reads and rewrites don't interleave with appends.
+#[derive(Debug)]
+struct JsonLines {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl JsonLines {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Appends a write to the file.
+    async fn append(&self, write: &Write) -> eyre::Result<()> {
+        let mut line = serde_json::to_string(write).wrap_err("failed to serialize write")?;
+        line.push('\n');
+
+        let _lock = self.lock.lock().await;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .wrap_err_with(|| format!("failed to open {}", self.path.display()))?;
+        file.write_all(line.as_bytes())
+            .await
+            .wrap_err_with(|| format!("failed to append write to {}", self.path.display()))
+    }
+
+    /// Reads up to `limit` writes from the front of the file, along with the length in bytes of
+    /// the lines they were read from, unreadable ones included.
+    async fn peek(&self, limit: usize) -> eyre::Result<(Vec<Write>, usize)> {
+        let contents = {
+            let _lock = self.lock.lock().await;
+            match fs::read_to_string(&self.path).await {
+                Ok(contents) => contents,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    return Ok((Vec::new(), 0));
+                }
+                Err(e) => return Err(e.into()),
+            }
+        };
+
+        let mut writes = Vec::new();
+        let mut len = 0;
+        for line in contents.split_inclusive('\n').take(limit) {
+            // a line still being appended is left for the next pass
+            if !line.ends_with('\n') {
+                break;
+            }
+            len += line.len();
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(line) {
+                Ok(write) => writes.push(write),
+                Err(e) => {
+                    metrics::counter!("kuma_db_write_buffer_dropped_total", "kind" => "corrupt")
+                        .increment(1);
+                    warn!(error = %e, "Dropping unreadable spilled write");
+                }
+            }
+        }
+
+        Ok((writes, len))
+    }
+
+    /// Removes the first `len` bytes of the file, which [`Self::peek`] returned, keeping whatever
+    /// was appended since. The file is removed once empty.
+    async fn remove_front(&self, len: usize) -> eyre::Result<()> {
+        let _lock = self.lock.lock().await;
+        let contents = fs::read(&self.path).await?;
+        let rest = contents.get(len..).unwrap_or_default();
+        if rest.is_empty() {
+            fs::remove_file(&self.path).await?;
+            return Ok(());
+        }
+
+        // replaced atomically, a crash leaves either the old or the new file
+        let tmp_path = tmp_path(&self.path);
+        fs::write(&tmp_path, rest).await?;
+        fs::rename(&tmp_path, &self.path).await?;
+        Ok(())
+    }
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    PathBuf::from(tmp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spill(name: &str) -> JsonLines {
+        let path = std::env::temp_dir().join(format!(
+            "kuma-write-buffer-{name}-{}.jsonl",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        JsonLines::new(path)
+    }
+
+    #[tokio::test]
+    async fn spilled_writes_are_removed_from_the_front_once_flushed() {
+        let spill = spill("front");
+        for _ in 0..3 {
+            spill.append(&Write::SpotPrices(Vec::new())).await.unwrap();
+        }
+
+        let (writes, len) = spill.peek(2).await.unwrap();
+        assert_eq!(writes.len(), 2);
+        // still there until flushed
+        assert_eq!(spill.peek(2).await.unwrap().0.len(), 2);
+
+        spill.remove_front(len).await.unwrap();
+        let (writes, len) = spill.peek(2).await.unwrap();
+        assert_eq!(writes.len(), 1);
+
+        spill.remove_front(len).await.unwrap();
+        assert!(!spill.path.exists());
+        assert_eq!(spill.peek(2).await.unwrap().1, 0);
+    }
+
+    #[tokio::test]
+    async fn writes_spilled_during_a_flush_are_kept() {
+        let spill = spill("during");
+        spill.append(&Write::SpotPrices(Vec::new())).await.unwrap();
+
+        let (writes, len) = spill.peek(10).await.unwrap();
+        assert_eq!(writes.len(), 1);
+        spill.append(&Write::SpotPrices(Vec::new())).await.unwrap();
+        spill.remove_front(len).await.unwrap();
+
+        let (writes, len) = spill.peek(10).await.unwrap();
+        assert_eq!(writes.len(), 1);
+        spill.remove_front(len).await.unwrap();
+        assert!(!spill.path.exists());
+    }
+
+    #[test]
+    fn failures_are_told_apart_by_sqlstate() {
+        assert_eq!(Failure::of_sqlstate("08006"), Failure::Unreachable);
+        assert_eq!(Failure::of_sqlstate("57P01"), Failure::Unreachable);
+        assert_eq!(Failure::of_sqlstate("23505"), Failure::Rejected);
+        assert_eq!(Failure::of_sqlstate("22003"), Failure::Rejected);
+        assert_eq!(Failure::of_sqlstate("40P01"), Failure::Other);
+        assert_eq!(Failure::of_sqlstate(""), Failure::Other);
+    }
+
+    #[test]
+    fn failures_outside_the_database_are_rejected() {
+        assert_eq!(
+            Failure::of(&sqlx::Error::PoolTimedOut.into()),
+            Failure::Unreachable
+        );
+        assert_eq!(
+            Failure::of(&eyre::Report::from(sqlx::Error::PoolClosed).wrap_err("insert failed")),
+            Failure::Unreachable
+        );
+        assert_eq!(
+            Failure::of(&sqlx::Error::RowNotFound.into()),
+            Failure::Rejected
+        );
+        assert_eq!(
+            Failure::of(&eyre::eyre!("spot prices of another block")),
+            Failure::Rejected
+        );
+    }
+}
@@ -0,0 +1,356 @@
+//! Turning a signal's simulated swaps into Tycho router transactions.
+//!
+//! Each leg is encoded with the pool's [`ProtocolComponent`] into a single swap through the Tycho
+//! router, pulling the sold token through Permit2, and can be simulated with `eth_call` before
+//! anything is broadcast. Submitted legs are signed locally and sent as raw transactions, so any
+//! of the chain's RPC endpoints can relay them.
+use std::time::Duration;
+
+use alloy::{
+    eips::Encodable2718 as _,
+    network::{EthereumWallet, TransactionBuilder as _},
+    primitives::{Address, B256, U256},
+    providers::Provider as _,
+    rpc::types::{TransactionReceipt, TransactionRequest},
+    signers::local::PrivateKeySigner,
+    sol,
+    sol_types::SolValue as _,
+};
+use color_eyre::eyre::{self, Context as _, OptionExt as _, eyre};
+use num_bigint::BigUint;
+use tokio::time::Instant;
+use tycho_execution::encoding::{
+    evm::encoder_builders::TychoRouterEncoderBuilder,
+    models::{Solution, Swap as EncodedSwap, UserTransferType},
+};
+use tycho_simulation::protocol::models::ProtocolComponent;
+
+use crate::{
+    chain::Chain,
+    rpc::RpcEndpoints,
+    signals::{self, bps_discount},
+    state::balances::{Inventory, u256_to_biguint},
+    strategy::Swap,
+};
+
+sol! {
+    event Transfer(address indexed from, address indexed to, uint256 value);
+}
+
+/// Extra gas allowed on top of the estimate when submitting, in percent
+const GAS_LIMIT_HEADROOM_PCT: u64 = 20;
+
+/// One side of a signal, encoded as a router call from the signing account.
+#[derive(Debug, Clone)]
+pub struct Leg {
+    pub chain: Chain,
+    /// The swap as simulated when the signal was generated
+    pub swap: Swap,
+    /// Least amount out the router accepts, the simulated amount less the signal's slippage
+    pub min_amount_out: BigUint,
+    pub transaction: TransactionRequest,
+}
+
+/// Outcome of a leg's transaction as executed by `eth_call` on the latest block.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    pub amount_out: BigUint,
+    pub gas: u64,
+    /// Gas price at the time of the simulation, in wei
+    pub gas_price: u128,
+}
+
+impl Simulation {
+    /// Gas the transaction would cost at the simulated gas price, in wei
+    pub fn gas_cost(&self) -> BigUint {
+        BigUint::from(self.gas) * BigUint::from(self.gas_price)
+    }
+}
+
+/// Outcome of a leg's transaction once mined.
+#[derive(Debug, Clone)]
+pub struct Execution {
+    pub tx_hash: B256,
+    pub block: Option<u64>,
+    pub success: bool,
+    /// Amount of the bought token transferred to the account, per the receipt's `Transfer` logs
+    pub amount_out: BigUint,
+    pub gas_used: u64,
+    /// Effective gas price paid, in wei
+    pub gas_price: u128,
+}
+
+impl Execution {
+    /// Gas the transaction cost, in wei
+    pub fn gas_cost(&self) -> BigUint {
+        BigUint::from(self.gas_used) * BigUint::from(self.gas_price)
+    }
+}
+
+/// Encodes the slow and fast legs of `signal`, each signed for by its chain's signer.
+///
+/// # Errors
+/// Returns an error if the signal was deserialized rather than generated, so it lost its pools'
+/// components, or if either swap can't be encoded.
+pub fn encode_signal(
+    signal: &signals::CrossChainSingleHop,
+    slow_signer: &PrivateKeySigner,
+    fast_signer: &PrivateKeySigner,
+) -> eyre::Result<(Leg, Leg)> {
+    let slow_component = signal
+        .slow_protocol_component
+        .as_deref()
+        .ok_or_eyre("signal has no slow pool component")?;
+    let fast_component = signal
+        .fast_protocol_component
+        .as_deref()
+        .ok_or_eyre("signal has no fast pool component")?;
+
+    let slow = encode_leg(
+        &signal.slow_chain,
+        slow_component,
+        &signal.slow_swap_sim,
+        signal.max_slippage_bps,
+        slow_signer,
+    )
+    .wrap_err("failed to encode slow leg")?;
+    let fast = encode_leg(
+        &signal.fast_chain,
+        fast_component,
+        &signal.fast_swap_sim,
+        signal.max_slippage_bps,
+        fast_signer,
+    )
+    .wrap_err("failed to encode fast leg")?;
+
+    Ok((slow, fast))
+}
+
+/// Encodes `swap` on the pool `component` as a router call from `signer`'s account, accepting
+/// `max_slippage_bps` less than the simulated amount out.
+pub fn encode_leg(
+    chain: &Chain,
+    component: &ProtocolComponent,
+    swap: &Swap,
+    max_slippage_bps: u64,
+    signer: &PrivateKeySigner,
+) -> eyre::Result<Leg> {
+    let encoder = TychoRouterEncoderBuilder::new()
+        .chain(chain.name)
+        .user_transfer_type(UserTransferType::TransferFromPermit2)
+        .signer(signer.clone())
+        .build()
+        .map_err(|e| eyre!("failed to build encoder: {e}"))?;
+
+    let sender = tycho_common::Bytes::from(signer.address().to_vec());
+    let min_amount_out = bps_discount(&swap.amount_out, max_slippage_bps);
+    let solution = Solution {
+        sender: sender.clone(),
+        receiver: sender,
+        given_token: swap.token_in.address.clone(),
+        given_amount: swap.amount_in.clone(),
+        checked_token: swap.token_out.address.clone(),
+        checked_amount: min_amount_out.clone(),
+        exact_out: false,
+        swaps: vec![EncodedSwap::new(
+            component.clone(),
+            swap.token_in.address.clone(),
+            swap.token_out.address.clone(),
+            0f64,
+            None,
+        )],
+        ..Default::default()
+    };
+    let encoded = encoder
+        .encode_full_calldata(vec![solution])
+        .map_err(|e| eyre!("failed to encode swap: {e}"))?
+        .pop()
+        .ok_or_eyre("encoder returned no transaction")?;
+
+    let transaction = TransactionRequest::default()
+        .with_chain_id(chain.chain_id())
+        .with_from(signer.address())
+        .with_to(Address::try_from(encoded.to.as_ref()).wrap_err("invalid router address")?)
+        .with_value(U256::from_be_slice(&encoded.value.to_bytes_be()))
+        .with_input(encoded.data);
+
+    Ok(Leg {
+        chain: chain.clone(),
+        swap: swap.clone(),
+        min_amount_out,
+        transaction,
+    })
+}
+
+impl Leg {
+    /// Checks that the account has the leg's amount in available in `inventory`, before anything
+    /// is simulated or broadcast.
+    pub fn check_inventory(&self, inventory: &Inventory) -> eyre::Result<()> {
+        inventory
+            .check(&self.swap.token_in, &self.swap.amount_in)
+            .wrap_err_with(|| format!("not enough inventory on {}", self.chain))
+    }
+
+    /// Executes the leg's transaction with `eth_call` and estimates its gas, without
+    /// broadcasting it.
+    ///
+    /// # Errors
+    /// Returns an error if the call reverts, e.g. because the account lacks the balance or the
+    /// Permit2 approval, or the pool moved past the slippage bound.
+    pub async fn simulate(&self, rpc: &RpcEndpoints) -> eyre::Result<Simulation> {
+        let output = rpc
+            .call(|provider| {
+                let transaction = self.transaction.clone();
+                async move { provider.call(transaction).await.wrap_err("eth_call failed") }
+            })
+            .await?;
+        let amount_out =
+            U256::abi_decode(&output).wrap_err("failed to decode the router's amount out")?;
+
+        let gas = rpc
+            .call(|provider| {
+                let transaction = self.transaction.clone();
+                async move {
+                    provider
+                        .estimate_gas(transaction)
+                        .await
+                        .wrap_err("eth_estimateGas failed")
+                }
+            })
+            .await?;
+        let gas_price = rpc
+            .call(|provider| async move {
+                provider
+                    .get_gas_price()
+                    .await
+                    .wrap_err("eth_gasPrice failed")
+            })
+            .await?;
+
+        Ok(Simulation {
+            amount_out: u256_to_biguint(amount_out),
+            gas,
+            gas_price,
+        })
+    }
+
+    /// Signs the leg's transaction with `signer` and broadcasts it, returning its hash.
+    ///
+    /// The nonce, gas limit and EIP-1559 fees are filled in from the chain right before signing.
+    pub async fn submit(
+        &self,
+        rpc: &RpcEndpoints,
+        signer: &PrivateKeySigner,
+    ) -> eyre::Result<B256> {
+        let from = signer.address();
+        let nonce = rpc
+            .call(|provider| async move {
+                provider
+                    .get_transaction_count(from)
+                    .pending()
+                    .await
+                    .wrap_err("eth_getTransactionCount failed")
+            })
+            .await?;
+        let gas = rpc
+            .call(|provider| {
+                let transaction = self.transaction.clone();
+                async move {
+                    provider
+                        .estimate_gas(transaction)
+                        .await
+                        .wrap_err("eth_estimateGas failed")
+                }
+            })
+            .await?;
+        let fees = rpc
+            .call(|provider| async move {
+                provider
+                    .estimate_eip1559_fees()
+                    .await
+                    .wrap_err("failed to estimate fees")
+            })
+            .await?;
+
+        let envelope = self
+            .transaction
+            .clone()
+            .with_nonce(nonce)
+            .with_gas_limit(gas + gas * GAS_LIMIT_HEADROOM_PCT / 100)
+            .with_max_fee_per_gas(fees.max_fee_per_gas)
+            .with_max_priority_fee_per_gas(fees.max_priority_fee_per_gas)
+            .build(&EthereumWallet::new(signer.clone()))
+            .await
+            .wrap_err("failed to sign transaction")?;
+        let tx_hash = *envelope.tx_hash();
+        let raw = envelope.encoded_2718();
+
+        rpc.call(|provider| {
+            let raw = raw.clone();
+            async move {
+                provider
+                    .send_raw_transaction(&raw)
+                    .await
+                    .wrap_err("eth_sendRawTransaction failed")
+            }
+        })
+        .await?;
+
+        Ok(tx_hash)
+    }
+
+    /// Waits up to `timeout` for `tx_hash` to be mined, polling once per block, and reads what
+    /// it transferred to `account` from its receipt.
+    pub async fn confirm(
+        &self,
+        rpc: &RpcEndpoints,
+        tx_hash: B256,
+        account: Address,
+        timeout: Duration,
+    ) -> eyre::Result<Execution> {
+        let deadline = Instant::now() + timeout;
+        let receipt = loop {
+            let receipt = rpc
+                .call(|provider| async move {
+                    provider
+                        .get_transaction_receipt(tx_hash)
+                        .await
+                        .wrap_err("eth_getTransactionReceipt failed")
+                })
+                .await?;
+            if let Some(receipt) = receipt {
+                break receipt;
+            }
+            if Instant::now() >= deadline {
+                return Err(eyre!(
+                    "{tx_hash} wasn't mined on {} within {timeout:?}",
+                    self.chain
+                ));
+            }
+            tokio::time::sleep(self.chain.block_time()).await;
+        };
+
+        Ok(Execution {
+            tx_hash,
+            block: receipt.block_number,
+            success: receipt.status(),
+            amount_out: self.received(&receipt, account),
+            gas_used: receipt.gas_used,
+            gas_price: receipt.effective_gas_price,
+        })
+    }
+
+    /// Sum of the bought token's `Transfer` logs to `account` in `receipt`.
+    fn received(&self, receipt: &TransactionReceipt, account: Address) -> BigUint {
+        let token = Address::try_from(self.swap.token_out.address.as_ref()).ok();
+        receipt
+            .inner
+            .logs()
+            .iter()
+            .filter(|log| Some(log.address()) == token)
+            .filter_map(|log| log.log_decode::<Transfer>().ok())
+            .filter(|transfer| transfer.inner.data.to == account)
+            .map(|transfer| u256_to_biguint(transfer.inner.data.value))
+            .sum()
+    }
+}
@@ -1,8 +1,14 @@
+pub mod admin;
 pub mod chain;
 pub mod collector;
 pub mod config;
 pub mod database;
+pub mod execution;
+pub mod rpc;
 pub mod signals;
 pub mod spot_prices;
 pub mod state;
+pub mod store;
 pub mod strategy;
+pub mod telemetry;
+pub mod verifier;
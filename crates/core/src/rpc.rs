@@ -0,0 +1,272 @@
+//! A chain's RPC endpoints in order of preference, failing over between them.
+//!
+//! Calls go to the most preferred healthy endpoint. A connection error, or no response within
+//! [`CALL_TIMEOUT`], marks the endpoint unhealthy for [`RETRY_AFTER`] and retries the call on the
+//! next one, so a flaky or hung primary doesn't stall the collector. Websocket endpoints are
+//! reconnected on their next use.
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    providers::{DynProvider, Provider as _, ProviderBuilder},
+    transports::{RpcError, TransportError},
+};
+use color_eyre::eyre::{self, Context as _, eyre};
+use tracing::{debug, warn};
+
+use crate::chain::Chain;
+
+/// How long an endpoint is skipped for after a connection error
+pub const RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// How long connecting to an endpoint, or a call to it, may take before failing over
+pub const CALL_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct RpcEndpoints {
+    chain: Chain,
+    endpoints: Arc<[Endpoint]>,
+    /// [`CALL_TIMEOUT`], shortened in tests
+    timeout: Duration,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    provider: tokio::sync::Mutex<Option<DynProvider>>,
+    health: Mutex<Health>,
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    consecutive_failures: u32,
+    unhealthy_until: Option<Instant>,
+}
+
+impl RpcEndpoints {
+    /// Endpoints of `chain`, its `rpc_url` first followed by the fallbacks. Connections are opened
+    /// on first use.
+    pub fn new(chain: &Chain) -> eyre::Result<Self> {
+        let endpoints = chain
+            .rpc_urls()
+            .enumerate()
+            .map(|(i, url)| {
+                if !is_supported_url(url) {
+                    return Err(eyre!(
+                        "rpc endpoint {i} for {chain} must be an http(s) or ws(s) url"
+                    ));
+                }
+                metrics::gauge!("kuma_rpc_endpoint_up", "chain" => chain.to_string(), "endpoint" => i.to_string())
+                    .set(1.0);
+
+                Ok(Endpoint {
+                    url: url.to_string(),
+                    provider: tokio::sync::Mutex::new(None),
+                    health: Mutex::new(Health::default()),
+                })
+            })
+            .collect::<eyre::Result<Arc<[_]>>>()?;
+
+        Ok(Self {
+            chain: chain.clone(),
+            endpoints,
+            timeout: CALL_TIMEOUT,
+        })
+    }
+
+    /// Shortens how long calls may take, to test hung endpoints quickly.
+    #[cfg(test)]
+    pub(crate) fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Runs `f` against the most preferred healthy endpoint, moving on to the next one when it
+    /// fails with a connection error or times out. Other errors, like reverts, are returned as is.
+    ///
+    /// # Errors
+    /// Returns the error of `f`, or the last connection error once every endpoint was tried.
+    pub async fn call<T, F, Fut>(&self, f: F) -> eyre::Result<T>
+    where
+        F: Fn(DynProvider) -> Fut,
+        Fut: Future<Output = eyre::Result<T>>,
+    {
+        let mut last_err = None;
+        for i in self.order() {
+            let connected = tokio::time::timeout(self.timeout, self.endpoints[i].connect())
+                .await
+                .unwrap_or_else(|_| Err(eyre!("no connection within {:?}", self.timeout)));
+            let provider = match connected {
+                Ok(provider) => provider,
+                Err(e) => {
+                    self.failed(i, &e).await;
+                    last_err = Some(e);
+                    continue;
+                }
+            };
+
+            let err = match tokio::time::timeout(self.timeout, f(provider)).await {
+                Ok(Err(e)) if is_connection_error(&e) => e,
+                Ok(result) => {
+                    self.succeeded(i);
+                    return result;
+                }
+                // a hung endpoint is failed over like an unreachable one
+                Err(_) => eyre!("no response within {:?}", self.timeout),
+            };
+            self.failed(i, &err).await;
+            last_err = Some(err);
+        }
+
+        Err(last_err
+            .unwrap_or_else(|| eyre!("no endpoints configured"))
+            .wrap_err(format!("every rpc endpoint for {} failed", self.chain)))
+    }
+
+    /// Endpoint indices to try, healthy ones first, each group in order of preference.
+    fn order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let (mut healthy, unhealthy): (Vec<_>, Vec<_>) =
+            (0..self.endpoints.len()).partition(|&i| {
+                let health = self.endpoints[i].health.lock().unwrap();
+                health.unhealthy_until.is_none_or(|until| until <= now)
+            });
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    async fn failed(&self, i: usize, err: &eyre::Report) {
+        let endpoint = &self.endpoints[i];
+        // websocket connections don't recover on their own, reconnect on the next call
+        endpoint.provider.lock().await.take();
+
+        let consecutive_failures = {
+            let mut health = endpoint.health.lock().unwrap();
+            health.consecutive_failures += 1;
+            health.unhealthy_until = Some(Instant::now() + RETRY_AFTER);
+            health.consecutive_failures
+        };
+        warn!(
+            chain = %self.chain,
+            endpoint = i,
+            consecutive_failures,
+            err = %err,
+            "RPC endpoint failed, failing over"
+        );
+        metrics::counter!("kuma_rpc_endpoint_failures_total", "chain" => self.chain.to_string(), "endpoint" => i.to_string())
+            .increment(1);
+        metrics::gauge!("kuma_rpc_endpoint_up", "chain" => self.chain.to_string(), "endpoint" => i.to_string())
+            .set(0.0);
+    }
+
+    fn succeeded(&self, i: usize) {
+        let mut health = self.endpoints[i].health.lock().unwrap();
+        if health.consecutive_failures > 0 {
+            debug!(chain = %self.chain, endpoint = i, "RPC endpoint recovered");
+            metrics::gauge!("kuma_rpc_endpoint_up", "chain" => self.chain.to_string(), "endpoint" => i.to_string())
+                .set(1.0);
+        }
+        *health = Health::default();
+    }
+}
+
+impl Endpoint {
+    async fn connect(&self) -> eyre::Result<DynProvider> {
+        let mut provider = self.provider.lock().await;
+        if let Some(provider) = provider.as_ref() {
+            return Ok(provider.clone());
+        }
+
+        let connected = ProviderBuilder::new()
+            .connect(&self.url)
+            .await
+            .wrap_err("failed to connect")?
+            .erased();
+        *provider = Some(connected.clone());
+        Ok(connected)
+    }
+}
+
+/// Whether `url` is an http(s) or ws(s) endpoint.
+pub fn is_supported_url(url: &str) -> bool {
+    ["http://", "https://", "ws://", "wss://"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+}
+
+/// Whether `err` was caused by the endpoint being unreachable or failing, rather than by the call.
+fn is_connection_error(err: &eyre::Report) -> bool {
+    err.chain().any(|cause| {
+        let transport = cause.downcast_ref::<TransportError>().or_else(|| {
+            match cause.downcast_ref::<alloy::contract::Error>() {
+                Some(alloy::contract::Error::TransportError(e)) => Some(e),
+                _ => None,
+            }
+        });
+        matches!(transport, Some(RpcError::Transport(_)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::transports::TransportErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn test_is_connection_error() {
+        let unreachable: eyre::Result<()> =
+            Err(TransportErrorKind::backend_gone()).wrap_err("eth_getBalance failed");
+        assert!(is_connection_error(&unreachable.unwrap_err()));
+
+        let reverted: eyre::Result<()> =
+            Err(TransportError::local_usage_str("reverted")).wrap_err("balanceOf failed");
+        assert!(!is_connection_error(&reverted.unwrap_err()));
+
+        assert!(!is_connection_error(&eyre!("block not found")));
+    }
+
+    #[test]
+    fn test_prefers_healthy_endpoints_in_order() {
+        let chain = Chain::eth_mainnet().with_rpc_fallbacks(vec![
+            "wss://secondary.example.com".to_string(),
+            "https://tertiary.example.com".to_string(),
+        ]);
+        let rpc = RpcEndpoints::new(&chain).unwrap();
+        assert_eq!(rpc.order(), [0, 1, 2]);
+
+        rpc.endpoints[0].health.lock().unwrap().unhealthy_until =
+            Some(Instant::now() + RETRY_AFTER);
+        assert_eq!(rpc.order(), [1, 2, 0]);
+
+        rpc.succeeded(0);
+        assert_eq!(rpc.order(), [0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_from_a_hung_endpoint() {
+        let chain = Chain::eth_mainnet()
+            .with_rpc_fallbacks(vec!["https://secondary.example.com".to_string()]);
+        let mut rpc = RpcEndpoints::new(&chain).unwrap();
+        rpc.timeout = Duration::from_millis(10);
+
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let result = rpc
+            .call(|_| {
+                let call = calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async move {
+                    if call == 0 {
+                        futures::future::pending::<()>().await;
+                    }
+                    eyre::Ok(call)
+                }
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(rpc.order(), [1, 0]);
+    }
+}
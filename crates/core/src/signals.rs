@@ -1,4 +1,4 @@
-use num_traits::CheckedSub;
+use num_traits::{CheckedSub, ToPrimitive as _};
 use serde::{Deserialize, Serialize};
 use std::{fmt::Display, sync::Arc};
 use tycho_simulation::protocol::models::ProtocolComponent;
@@ -48,6 +48,9 @@ pub struct CrossChainSingleHop {
     pub congestion_risk_discount_bps: u64,
     pub surplus: (BigUint, BigUint),
     pub expected_profit: (BigUint, BigUint),
+    /// How far apart the slow and fast blocks arrived, in milliseconds, when it was measured
+    #[serde(default)]
+    pub block_skew_ms: Option<u64>,
 }
 
 impl CrossChainSingleHop {
@@ -100,8 +103,50 @@ impl CrossChainSingleHop {
             expected_profit: expected_profits,
             max_slippage_bps,
             congestion_risk_discount_bps,
+            block_skew_ms: None,
         })
     }
+
+    /// Expected profits in both tokens valued in the pair's token A, token B priced at the rate
+    /// the fast swap trades them at.
+    ///
+    /// Comparable across directions, unlike `expected_profit` whose first amount is in the slow
+    /// swap's input token.
+    pub fn expected_profit_in_a(&self) -> BigUint {
+        let fast = &self.fast_swap_sim;
+        // token A per token B, as (numerator, denominator)
+        let (profit_a, profit_b, (a, b)) =
+            if self.slow_swap_sim.token_in == *self.slow_pair.token_a() {
+                // the fast swap sells B for A
+                (
+                    &self.expected_profit.0,
+                    &self.expected_profit.1,
+                    (&fast.amount_out, &fast.amount_in),
+                )
+            } else {
+                // the fast swap sells A for B
+                (
+                    &self.expected_profit.1,
+                    &self.expected_profit.0,
+                    (&fast.amount_in, &fast.amount_out),
+                )
+            };
+
+        if *b == BigUint::ZERO {
+            return profit_a.clone();
+        }
+        profit_a + profit_b * a / b
+    }
+
+    /// Expected profit in token A, in bps of the slow swap's amount in
+    pub fn expected_profit_bps(&self) -> u64 {
+        if self.slow_swap_sim.amount_in == BigUint::ZERO {
+            return 0;
+        }
+        (&self.expected_profit.0 * 10_000u32 / &self.slow_swap_sim.amount_in)
+            .to_u64()
+            .unwrap_or(u64::MAX)
+    }
 }
 
 impl Display for CrossChainSingleHop {
@@ -2,7 +2,11 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     chain::Chain,
-    state::{PoolId, pair::Pair},
+    state::{
+        PoolId,
+        pair::{Pair, PairState},
+        pool_meta::PoolMeta,
+    },
     strategy::Precomputes,
 };
 
@@ -15,13 +19,26 @@ pub struct SpotPrices {
     pub min_pool_id: PoolId,
     pub max_pool_id: PoolId,
     pub chain: Chain,
+    /// Protocol and fee of the min and max pools, when known
+    #[serde(default)]
+    pub min_pool_meta: Option<PoolMeta>,
+    #[serde(default)]
+    pub max_pool_meta: Option<PoolMeta>,
 }
 
 impl SpotPrices {
     pub fn from_precompute(precompute: &Precomputes, chain: Chain, pair: Pair) -> Self {
         let min = precompute.sorted_spot_prices[0].clone();
         let max = precompute.sorted_spot_prices[precompute.sorted_spot_prices.len() - 1].clone();
+        let pool_meta = |id: &PoolId| {
+            precompute
+                .pool_metadata
+                .get(id)
+                .map(|component| PoolMeta::from_component(component))
+        };
         SpotPrices {
+            min_pool_meta: pool_meta(&min.0),
+            max_pool_meta: pool_meta(&max.0),
             pair,
             block_height: precompute.block_height,
             min_pool_id: min.0,
@@ -31,4 +48,26 @@ impl SpotPrices {
             chain,
         }
     }
+
+    /// Spot price range across the pools of a block's pair state, given their `sorted` spot
+    /// prices, `None` if no pool quotes one
+    pub fn from_pair_state(
+        state: &PairState,
+        sorted: &[(PoolId, f64)],
+        chain: Chain,
+        pair: Pair,
+    ) -> Option<Self> {
+        let (min, max) = (sorted.first()?.clone(), sorted.last()?.clone());
+        Some(SpotPrices {
+            min_pool_meta: state.pool_meta.get(&min.0).cloned(),
+            max_pool_meta: state.pool_meta.get(&max.0).cloned(),
+            pair,
+            block_height: state.block_height,
+            min_pool_id: min.0,
+            min_price: min.1,
+            max_pool_id: max.0,
+            max_price: max.1,
+            chain,
+        })
+    }
 }
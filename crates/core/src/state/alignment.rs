@@ -0,0 +1,137 @@
+//! How far apart in time a signal's slow and fast blocks reached the bot.
+//!
+//! A signal pairs the precompute of one slow block with the state of one fast block, and their
+//! heights alone don't say whether those are of the same moment: while the slow chain's collector
+//! stalls, fresh fast blocks keep pairing with an old slow one. [`Alignment`] records when each
+//! chain's latest state arrived so every signal carries that skew, and rejects the pairing when it
+//! exceeds a bound.
+use std::time::{Duration, Instant};
+
+use color_eyre::eyre::{self, eyre};
+
+use super::pair::PairState;
+use crate::signals;
+
+/// When a chain's state at `height` was received
+#[derive(Debug, Clone, Copy)]
+struct Arrival {
+    height: u64,
+    received_at: Instant,
+}
+
+impl Arrival {
+    fn of(state: &PairState) -> Self {
+        Self {
+            height: state.block_height,
+            received_at: state.received_at,
+        }
+    }
+}
+
+/// Arrival times of the latest slow and fast states a strategy acts on.
+#[derive(Debug, Clone, Default)]
+pub struct Alignment {
+    /// Pairings whose blocks arrived further apart are rejected, none are when unset
+    max_skew: Option<Duration>,
+    slow: Option<Arrival>,
+    fast: Option<Arrival>,
+}
+
+impl Alignment {
+    pub fn new(max_skew: Option<Duration>) -> Self {
+        Self {
+            max_skew,
+            ..Default::default()
+        }
+    }
+
+    /// Records the arrival of the slow state the next precompute is made from.
+    pub fn record_slow(&mut self, state: &PairState) {
+        self.slow = Some(Arrival::of(state));
+    }
+
+    /// Records the arrival of the fast state the next signal is searched on.
+    pub fn record_fast(&mut self, state: &PairState) {
+        self.fast = Some(Arrival::of(state));
+    }
+
+    /// Time between the arrivals of the slow block at `slow_height` and the fast block at
+    /// `fast_height`, `None` unless those are the latest recorded of each chain.
+    pub fn skew(&self, slow_height: u64, fast_height: u64) -> Option<Duration> {
+        let (slow, fast) = (self.slow?, self.fast?);
+        if slow.height != slow_height || fast.height != fast_height {
+            return None;
+        }
+        let (earlier, later) = if slow.received_at <= fast.received_at {
+            (slow.received_at, fast.received_at)
+        } else {
+            (fast.received_at, slow.received_at)
+        };
+        Some(later - earlier)
+    }
+
+    /// Sets the block skew of `signal`.
+    ///
+    /// # Errors
+    /// If the skew exceeds the bound, in which case the signal shouldn't be acted on.
+    pub fn annotate(&self, signal: &mut signals::CrossChainSingleHop) -> eyre::Result<()> {
+        let skew = self.skew(signal.slow_height, signal.fast_height);
+        signal.block_skew_ms = skew.map(|skew| u64::try_from(skew.as_millis()).unwrap_or(u64::MAX));
+
+        match (skew, self.max_skew) {
+            (Some(skew), Some(max_skew)) if skew > max_skew => Err(eyre!(
+                "slow block {} and fast block {} arrived {}ms apart, more than {}ms",
+                signal.slow_height,
+                signal.fast_height,
+                skew.as_millis(),
+                max_skew.as_millis()
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alignment(max_skew_ms: u64, slow_offset_ms: u64) -> Alignment {
+        let fast_received_at = Instant::now();
+        Alignment {
+            max_skew: Some(Duration::from_millis(max_skew_ms)),
+            slow: Some(Arrival {
+                height: 10,
+                received_at: fast_received_at - Duration::from_millis(slow_offset_ms),
+            }),
+            fast: Some(Arrival {
+                height: 20,
+                received_at: fast_received_at,
+            }),
+        }
+    }
+
+    #[test]
+    fn skew_is_measured_between_the_latest_arrivals() {
+        let alignment = alignment(1_000, 300);
+
+        assert_eq!(alignment.skew(10, 20), Some(Duration::from_millis(300)));
+    }
+
+    #[test]
+    fn skew_of_other_blocks_is_unknown() {
+        let alignment = alignment(1_000, 300);
+
+        assert_eq!(alignment.skew(9, 20), None);
+        assert_eq!(alignment.skew(10, 21), None);
+        assert_eq!(Alignment::new(None).skew(10, 20), None);
+    }
+
+    #[test]
+    fn skew_is_symmetric() {
+        let mut alignment = alignment(1_000, 0);
+        let slow = alignment.slow.as_mut().unwrap();
+        slow.received_at += Duration::from_millis(250);
+
+        assert_eq!(alignment.skew(10, 20), Some(Duration::from_millis(250)));
+    }
+}
@@ -0,0 +1,566 @@
+//! Token balance accounting for the bot's account, driven by ERC20 `Transfer` logs.
+//!
+//! [`Inventory`] is what strategies and execution size against: the balances a chain's collector
+//! reads on every block, less what's committed to trades that haven't settled yet.
+use std::{cmp::Ordering, collections::HashMap, sync::Arc};
+
+use alloy::{
+    primitives::{Address, U256},
+    rpc::types::{Filter, Log},
+    sol,
+    sol_types::SolEvent as _,
+};
+use color_eyre::eyre::{self, WrapErr as _, eyre};
+use num_bigint::BigUint;
+use tokio::sync::watch;
+use tracing::{trace, warn};
+use tycho_common::{Bytes, models::token::Token};
+
+sol! {
+    event Transfer(address indexed from, address indexed to, uint256 value);
+}
+
+/// Balances of the tracked account, keyed by token address.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenBalances(HashMap<Address, BigUint>);
+
+/// A decoded transfer touching the tracked account.
+#[derive(Debug)]
+struct BalanceChange {
+    token: Address,
+    block_number: u64,
+    log_index: u64,
+    amount: BigUint,
+    incoming: bool,
+}
+
+impl TokenBalances {
+    pub fn new(balances: HashMap<Address, BigUint>) -> Self {
+        Self(balances)
+    }
+
+    pub fn get(&self, token: &Address) -> Option<&BigUint> {
+        self.0.get(token)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Address, &BigUint)> {
+        self.0.iter()
+    }
+
+    /// Applies ERC20 `Transfer` logs to the tracked balances of `account`.
+    ///
+    /// `to_logs` are transfers into `account` and `from_logs` are transfers out of it. Both sets
+    /// are merged and applied in `(block_number, log_index)` order, so a block that both receives
+    /// and spends a token is replayed in the order it happened on chain. When the same log shows
+    /// up in both sets (a self-transfer), the credit is applied before the debit.
+    ///
+    /// Logs flagged as `removed` (reorged out) are skipped.
+    ///
+    /// The update is all-or-nothing: if any log fails to decode, doesn't touch `account`, or
+    /// would drive a balance negative, an error is returned and the balances are left untouched.
+    pub fn update_token_balances(
+        &mut self,
+        account: Address,
+        to_logs: &[Log],
+        from_logs: &[Log],
+    ) -> eyre::Result<()> {
+        let mut changes = Vec::with_capacity(to_logs.len() + from_logs.len());
+        for log in to_logs {
+            if let Some(change) = decode_change(account, log, true)
+                .wrap_err("failed to decode incoming transfer log")?
+            {
+                changes.push(change);
+            }
+        }
+        for log in from_logs {
+            if let Some(change) = decode_change(account, log, false)
+                .wrap_err("failed to decode outgoing transfer log")?
+            {
+                changes.push(change);
+            }
+        }
+
+        // stable sort keeps credits ahead of debits for identical positions
+        changes.sort_by(|a, b| match a.block_number.cmp(&b.block_number) {
+            Ordering::Equal => a.log_index.cmp(&b.log_index),
+            ord => ord,
+        });
+
+        let mut updated = self.0.clone();
+        for change in changes {
+            let balance = updated.entry(change.token).or_default();
+            if change.incoming {
+                *balance += &change.amount;
+            } else {
+                if *balance < change.amount {
+                    return Err(eyre!(
+                        "outgoing transfer of {} for token {} at block {} (log {}) exceeds tracked balance {}",
+                        change.amount,
+                        change.token,
+                        change.block_number,
+                        change.log_index,
+                        balance
+                    ));
+                }
+                *balance -= &change.amount;
+            }
+
+            trace!(
+                token = %change.token,
+                block.number = change.block_number,
+                log.index = change.log_index,
+                incoming = change.incoming,
+                amount = %change.amount,
+                balance = %balance,
+                "Applied transfer to token balance"
+            );
+        }
+
+        self.0 = updated;
+        Ok(())
+    }
+}
+
+/// Filters for the `Transfer` logs of `tokens` into and out of `account` over blocks
+/// `from..=to`, in the order [`TokenBalances::update_token_balances`] takes them.
+pub fn transfer_filters(
+    account: Address,
+    tokens: &[Address],
+    from: u64,
+    to: u64,
+) -> (Filter, Filter) {
+    let transfers = Filter::new()
+        .address(tokens.to_vec())
+        .event_signature(Transfer::SIGNATURE_HASH)
+        .from_block(from)
+        .to_block(to);
+    let incoming = transfers.clone().topic2(account.into_word());
+    let outgoing = transfers.topic1(account.into_word());
+    (incoming, outgoing)
+}
+
+/// The account's holdings of one token on a chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenInventory {
+    pub token: Token,
+    /// Balance as of the inventory's block
+    pub held: BigUint,
+    /// Amount reserved for trades that haven't settled yet
+    pub pending: BigUint,
+}
+
+impl TokenInventory {
+    /// What's left to trade once pending trades settle.
+    pub fn available(&self) -> BigUint {
+        if self.held > self.pending {
+            &self.held - &self.pending
+        } else {
+            BigUint::default()
+        }
+    }
+}
+
+/// The account's holdings of the tracked tokens of one chain, keyed by token address.
+///
+/// Balances are unknown until the first [`update_held`](Self::update_held), e.g. when the
+/// collector tracks no account, and sizing falls back to the configured inventory until then.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Inventory {
+    /// Block the held balances were read at
+    height: Option<u64>,
+    tokens: HashMap<Bytes, TokenInventory>,
+}
+
+impl Inventory {
+    /// An inventory of `tokens` whose balances aren't known yet.
+    pub fn new(tokens: impl IntoIterator<Item = Token>) -> Self {
+        let tokens = tokens
+            .into_iter()
+            .map(|token| {
+                let inventory = TokenInventory {
+                    token: token.clone(),
+                    held: BigUint::default(),
+                    pending: BigUint::default(),
+                };
+                (token.address, inventory)
+            })
+            .collect();
+        Self {
+            height: None,
+            tokens,
+        }
+    }
+
+    /// Block the held balances were read at, `None` while they're unknown
+    pub fn height(&self) -> Option<u64> {
+        self.height
+    }
+
+    /// The holdings of `token`, `None` while balances are unknown or if it isn't tracked.
+    pub fn get(&self, token: &Token) -> Option<&TokenInventory> {
+        self.height?;
+        self.tokens.get(&token.address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TokenInventory> {
+        self.tokens.values()
+    }
+
+    /// Sets the held balances to those read at `height`, keeping what's pending. Tracked tokens
+    /// missing from `balances` are held at zero.
+    pub fn update_held(&mut self, height: u64, balances: &TokenBalances) {
+        for (address, inventory) in &mut self.tokens {
+            inventory.held = Address::try_from(address.as_ref())
+                .ok()
+                .and_then(|address| balances.get(&address))
+                .cloned()
+                .unwrap_or_default();
+        }
+        self.height = Some(height);
+    }
+
+    /// The most of `token` a trade may spend: `limit`, or what's available if that's less. Just
+    /// `limit` while balances are unknown.
+    pub fn cap(&self, token: &Token, limit: &BigUint) -> BigUint {
+        match self.get(token) {
+            Some(inventory) => inventory.available().min(limit.clone()),
+            None if self.height.is_some() => BigUint::default(),
+            None => limit.clone(),
+        }
+    }
+
+    /// Checks that `amount` of `token` is available to trade.
+    ///
+    /// # Errors
+    /// If balances are unknown, `token` isn't tracked or less than `amount` is available.
+    pub fn check(&self, token: &Token, amount: &BigUint) -> eyre::Result<()> {
+        if self.height.is_none() {
+            return Err(eyre!("balances aren't known yet"));
+        }
+        let available = self
+            .get(token)
+            .ok_or_else(|| eyre!("{} isn't tracked", token.symbol))?
+            .available();
+        if available < *amount {
+            return Err(eyre!(
+                "{amount} {} needed but only {available} is available",
+                token.symbol
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sets aside `amount` of `token` for a trade until it's [`release`](Self::release)d.
+    ///
+    /// # Errors
+    /// Like [`check`](Self::check), leaving the inventory untouched.
+    pub fn reserve(&mut self, token: &Token, amount: &BigUint) -> eyre::Result<()> {
+        self.check(token, amount)?;
+        let inventory = self
+            .tokens
+            .get_mut(&token.address)
+            .expect("checked to be tracked");
+        inventory.pending += amount;
+        Ok(())
+    }
+
+    /// Returns `amount` of `token` set aside by [`reserve`](Self::reserve), once its trade
+    /// settled or failed.
+    pub fn release(&mut self, token: &Token, amount: &BigUint) {
+        if let Some(inventory) = self.tokens.get_mut(&token.address) {
+            inventory.pending = if inventory.pending > *amount {
+                &inventory.pending - amount
+            } else {
+                BigUint::default()
+            };
+        }
+    }
+}
+
+/// An [`Inventory`] shared between the collector updating its balances and the workers sizing
+/// and reserving trades against it.
+#[derive(Debug, Clone)]
+pub struct SharedInventory(Arc<watch::Sender<Inventory>>);
+
+impl SharedInventory {
+    pub fn new(inventory: Inventory) -> Self {
+        Self(Arc::new(watch::Sender::new(inventory)))
+    }
+
+    /// A copy of the current inventory
+    pub fn get(&self) -> Inventory {
+        self.0.borrow().clone()
+    }
+
+    /// Receiver notified whenever the inventory changes
+    pub fn subscribe(&self) -> watch::Receiver<Inventory> {
+        self.0.subscribe()
+    }
+
+    /// See [`Inventory::update_held`].
+    pub fn update_held(&self, height: u64, balances: &TokenBalances) {
+        self.0
+            .send_modify(|inventory| inventory.update_held(height, balances));
+    }
+
+    /// See [`Inventory::reserve`].
+    pub fn reserve(&self, token: &Token, amount: &BigUint) -> eyre::Result<()> {
+        let mut result = Ok(());
+        self.0.send_if_modified(|inventory| {
+            result = inventory.reserve(token, amount);
+            result.is_ok()
+        });
+        result
+    }
+
+    /// See [`Inventory::release`].
+    pub fn release(&self, token: &Token, amount: &BigUint) {
+        self.0
+            .send_modify(|inventory| inventory.release(token, amount));
+    }
+}
+
+fn decode_change(
+    account: Address,
+    log: &Log,
+    incoming: bool,
+) -> eyre::Result<Option<BalanceChange>> {
+    if log.removed {
+        warn!(
+            token = %log.address(),
+            block.number = ?log.block_number,
+            log.index = ?log.log_index,
+            "Skipping removed transfer log"
+        );
+        return Ok(None);
+    }
+
+    let decoded = log
+        .log_decode::<Transfer>()
+        .wrap_err("log is not an ERC20 Transfer event")?;
+    let Transfer { from, to, value } = decoded.inner.data;
+
+    let counterparty = if incoming { to } else { from };
+    if counterparty != account {
+        return Err(eyre!(
+            "transfer {} -> {} does not {} account {}",
+            from,
+            to,
+            if incoming { "credit" } else { "debit" },
+            account
+        ));
+    }
+
+    let block_number = log
+        .block_number
+        .ok_or_else(|| eyre!("transfer log is missing its block number"))?;
+    let log_index = log
+        .log_index
+        .ok_or_else(|| eyre!("transfer log is missing its log index"))?;
+
+    Ok(Some(BalanceChange {
+        token: log.address(),
+        block_number,
+        log_index,
+        amount: u256_to_biguint(value),
+        incoming,
+    }))
+}
+
+pub(crate) fn u256_to_biguint(value: U256) -> BigUint {
+    BigUint::from_bytes_be(&value.to_be_bytes::<32>())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::address;
+
+    use super::*;
+
+    const ACCOUNT: Address = address!("0x00000000000000000000000000000000000000aa");
+    const OTHER: Address = address!("0x00000000000000000000000000000000000000bb");
+    const TOKEN: Address = address!("0x0000000000000000000000000000000000000001");
+
+    fn transfer_log(from: Address, to: Address, value: u64, block: u64, index: u64) -> Log {
+        let event = Transfer {
+            from,
+            to,
+            value: U256::from(value),
+        };
+        Log {
+            inner: alloy::primitives::Log {
+                address: TOKEN,
+                data: event.encode_log_data(),
+            },
+            block_number: Some(block),
+            log_index: Some(index),
+            ..Default::default()
+        }
+    }
+
+    fn balance(balances: &TokenBalances) -> BigUint {
+        balances.get(&TOKEN).cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn outgoing_transfers_decrement_balance() {
+        let mut balances = TokenBalances::new(HashMap::from([(TOKEN, BigUint::from(100u64))]));
+
+        balances
+            .update_token_balances(
+                ACCOUNT,
+                &[transfer_log(OTHER, ACCOUNT, 50, 1, 0)],
+                &[transfer_log(ACCOUNT, OTHER, 30, 2, 0)],
+            )
+            .unwrap();
+
+        assert_eq!(balance(&balances), BigUint::from(120u64));
+    }
+
+    #[test]
+    fn same_block_transfers_apply_in_log_order() {
+        // starting from zero, spending the received amount is only valid if the incoming
+        // transfer is applied first, regardless of which slice it was passed in
+        let mut balances = TokenBalances::default();
+
+        balances
+            .update_token_balances(
+                ACCOUNT,
+                &[transfer_log(OTHER, ACCOUNT, 40, 7, 1)],
+                &[transfer_log(ACCOUNT, OTHER, 40, 7, 3)],
+            )
+            .unwrap();
+
+        assert_eq!(balance(&balances), BigUint::default());
+    }
+
+    #[test]
+    fn overdraft_leaves_balances_untouched() {
+        let mut balances = TokenBalances::new(HashMap::from([(TOKEN, BigUint::from(10u64))]));
+
+        // the debit comes before the credit in the block, so it overdraws
+        let res = balances.update_token_balances(
+            ACCOUNT,
+            &[transfer_log(OTHER, ACCOUNT, 40, 7, 5)],
+            &[transfer_log(ACCOUNT, OTHER, 40, 7, 2)],
+        );
+
+        assert!(res.is_err());
+        assert_eq!(balance(&balances), BigUint::from(10u64));
+    }
+
+    #[test]
+    fn self_transfer_nets_to_zero() {
+        let mut balances = TokenBalances::new(HashMap::from([(TOKEN, BigUint::from(5u64))]));
+        let log = transfer_log(ACCOUNT, ACCOUNT, 5, 3, 0);
+
+        balances
+            .update_token_balances(ACCOUNT, &[log.clone()], &[log])
+            .unwrap();
+
+        assert_eq!(balance(&balances), BigUint::from(5u64));
+    }
+
+    #[test]
+    fn rejects_logs_for_other_accounts() {
+        let mut balances = TokenBalances::default();
+
+        let res =
+            balances.update_token_balances(ACCOUNT, &[], &[transfer_log(OTHER, ACCOUNT, 1, 1, 0)]);
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn skips_removed_logs() {
+        let mut balances = TokenBalances::new(HashMap::from([(TOKEN, BigUint::from(5u64))]));
+        let mut log = transfer_log(ACCOUNT, OTHER, 5, 3, 0);
+        log.removed = true;
+
+        balances
+            .update_token_balances(ACCOUNT, &[], &[log])
+            .unwrap();
+
+        assert_eq!(balance(&balances), BigUint::from(5u64));
+    }
+
+    fn token() -> Token {
+        Token::new(
+            &Bytes::from(TOKEN.to_vec()),
+            "TKN",
+            18,
+            0,
+            &[Some(0)],
+            tycho_common::models::Chain::Ethereum,
+            100,
+        )
+    }
+
+    fn inventory(held: u64) -> Inventory {
+        let mut inventory = Inventory::new([token()]);
+        inventory.update_held(
+            1,
+            &TokenBalances::new(HashMap::from([(TOKEN, BigUint::from(held))])),
+        );
+        inventory
+    }
+
+    #[test]
+    fn unknown_balances_fall_back_to_the_limit() {
+        let inventory = Inventory::new([token()]);
+
+        assert_eq!(
+            inventory.cap(&token(), &BigUint::from(50u64)),
+            BigUint::from(50u64)
+        );
+        assert!(inventory.check(&token(), &BigUint::from(1u64)).is_err());
+    }
+
+    #[test]
+    fn cap_is_the_lesser_of_limit_and_available() {
+        let mut inventory = inventory(100);
+
+        assert_eq!(
+            inventory.cap(&token(), &BigUint::from(50u64)),
+            BigUint::from(50u64)
+        );
+        inventory.reserve(&token(), &BigUint::from(70u64)).unwrap();
+        assert_eq!(
+            inventory.cap(&token(), &BigUint::from(50u64)),
+            BigUint::from(30u64)
+        );
+    }
+
+    #[test]
+    fn reserve_beyond_available_fails() {
+        let mut inventory = inventory(100);
+        inventory.reserve(&token(), &BigUint::from(60u64)).unwrap();
+
+        assert!(inventory.reserve(&token(), &BigUint::from(60u64)).is_err());
+        assert_eq!(
+            inventory.get(&token()).unwrap().pending,
+            BigUint::from(60u64)
+        );
+    }
+
+    #[test]
+    fn balance_updates_keep_pending_amounts() {
+        let mut inventory = inventory(100);
+        inventory.reserve(&token(), &BigUint::from(40u64)).unwrap();
+
+        inventory.update_held(
+            2,
+            &TokenBalances::new(HashMap::from([(TOKEN, BigUint::from(80u64))])),
+        );
+        assert_eq!(
+            inventory.get(&token()).unwrap().available(),
+            BigUint::from(40u64)
+        );
+
+        inventory.release(&token(), &BigUint::from(40u64));
+        assert_eq!(
+            inventory.get(&token()).unwrap().available(),
+            BigUint::from(80u64)
+        );
+    }
+}
@@ -1,13 +1,20 @@
 use std::{
     collections::{HashMap, HashSet},
     sync::Arc,
+    time::Instant,
 };
 
+use num_bigint::BigUint;
 use tracing::{debug, instrument, trace};
 use tycho_common::simulation::protocol_sim::ProtocolSim;
 use tycho_simulation::protocol::models::{ProtocolComponent, Update};
 
-use super::pair::{Pair, PairState};
+use super::{
+    balances::TokenBalances,
+    header::FinalityHeads,
+    pair::{Pair, PairState},
+    pool_meta::PoolMeta,
+};
 use crate::state;
 
 #[derive(Clone, Debug)]
@@ -19,7 +26,27 @@ pub struct Block {
     pub modified_pools: Arc<HashSet<state::PoolId>>,
     /// The pools that have not been modified in the latest block update
     pub unmodified_pools: Arc<HashSet<state::PoolId>>,
+    /// The pools Tycho removed in the latest block update, e.g. for falling below the TVL
+    /// threshold, with their last metadata to tell which pairs they belonged to
+    pub removed_pools: Arc<HashMap<state::PoolId, Arc<ProtocolComponent>>>,
+    /// The pools Tycho started tracking in the latest block update
+    pub added_pools: Arc<HashSet<state::PoolId>>,
+    /// The already tracked pools whose metadata, e.g. fee or hooks, changed in the latest block
+    /// update
+    pub updated_metadata: Arc<HashSet<state::PoolId>>,
     pub metadata: HashMap<state::PoolId, Arc<ProtocolComponent>>,
+    /// Native gas token balance of the tracked account, if one is configured
+    pub native_balance: Option<BigUint>,
+    /// ERC20 balances of the tracked account for the collected tokens, if one is configured
+    pub token_balances: Option<TokenBalances>,
+    /// Chain heads as of this block, refreshed by the collector
+    pub finality: FinalityHeads,
+    /// Header timestamp, in seconds since the unix epoch, once the collector fetched the header
+    pub timestamp: Option<u64>,
+    /// EIP-1559 base fee, once the collector fetched the header of a chain with a fee market
+    pub base_fee_per_gas: Option<u64>,
+    /// When the collector received this block's update
+    pub received_at: Instant,
 }
 
 impl Block {
@@ -46,18 +73,35 @@ impl Block {
             states,
             modified_pools: Arc::new(metadata.keys().cloned().collect()),
             unmodified_pools: Arc::new(HashSet::new()),
+            removed_pools: Arc::new(HashMap::new()),
+            added_pools: Arc::new(metadata.keys().cloned().collect()),
+            updated_metadata: Arc::new(HashSet::new()),
             metadata,
+            native_balance: None,
+            token_balances: None,
+            finality: FinalityHeads {
+                latest: block_number_or_timestamp,
+                ..Default::default()
+            },
+            timestamp: None,
+            base_fee_per_gas: None,
+            received_at: Instant::now(),
         }
     }
 
     /// Consume this `Block` and return a new snapshot with `block_update` applied.
     ///
-    /// - Evicts `removed_pairs` from `states`, `metadata`, `modified_pools` and `unmodified_pools`
-    /// - Inserts `new_pairs` to `states`, `metadata`, and `modified_pools`.
+    /// - Evicts `removed_pairs` from `states`, `metadata`, `modified_pools` and `unmodified_pools`,
+    ///   keeping their metadata in `removed_pools` for this block only.
+    /// - Inserts `new_pairs` to `states`, `metadata`, and `modified_pools`, noting them in
+    ///   `added_pools`. `new_pairs` of pools already tracked replace their metadata and are noted
+    ///   in `updated_metadata` if it changed.
     /// - Replaces states for `updated_states`, moves their IDs into `modified_pools`.
-    ///   - Note: Metadata (i.e. `ProtocolComponent`) are immutable data so they are not modified.
     ///
-    /// The returned `Block` has `block_number = block_update.block_number`.
+    /// The returned `Block` has `block_number = block_update.block_number`. The account balances
+    /// and safe/finalized heads are carried over from the previous block until the collector
+    /// refreshes them, while its timestamp and base fee are unset until the collector fetches its
+    /// header.
     ///
     /// Any `PairState` derived from the old `Block` keeps its own `Arc` handles:
     /// - `modified_pools` and `unmodified_pools` are cloned, leaving old snapshots unchanged
@@ -65,9 +109,10 @@ impl Block {
     ///
     /// New `PairState`s built after this call will reflect the updated contents.
     ///
-    /// # Panics
-    /// - if `removed_pairs` contains an ID not present in the original maps
-    /// - if `new_pairs` refers to a state missing from `updated_states`
+    /// Removals of pools the block doesn't hold, e.g. ones dropped before a resumed snapshot was
+    /// taken, are skipped.
+    ///
+    /// New pools without a state in `updated_states` are skipped until Tycho sends one.
     #[instrument(skip_all)]
     pub fn apply_update(self, block_update: Update) -> Self {
         let Self {
@@ -75,6 +120,9 @@ impl Block {
             unmodified_pools,
             mut states,
             mut metadata,
+            native_balance,
+            token_balances,
+            finality,
             ..
         } = self;
 
@@ -89,49 +137,63 @@ impl Block {
         let mut unmodified_pools = unmodified_pools.as_ref().clone();
 
         // remove pools that are no longer active
+        let mut removed_pools = HashMap::with_capacity(removed_pairs.len());
         for (id, _) in removed_pairs {
             // update block state map
             let id = state::PoolId(id);
-            let _removed_state = states
-                .remove(&id)
-                .expect("BlockUpdate.removed_pairs should only contain existing pairs");
+            states.remove(&id);
 
             // update metadata map
-            let _removed_metadata = metadata
-                .remove(&id)
-                .expect("BlockUpdate.removed_pairs should only contain existing pairs");
+            let Some(removed_metadata) = metadata.remove(&id) else {
+                debug!(block.number = %height, pair.id = %id, "Skipped removal of unknown pair");
+                continue;
+            };
 
             // update modified/unmodified maps
             if modified_pools.remove(&id) {
                 trace!(block.number = %height, pair.id = %id, "Removed pair from modified pairs");
             } else if unmodified_pools.remove(&id) {
                 trace!(block.number = %height, pair.id = %id, "Removed pair from unmodified pairs");
-            } else {
-                // TODO: maybe fail more gracefully from bad block updates, altho this should never happen if tycho_simulation is well written
-                panic!("BlockUpdate.removed_pairs should only contain existing pairs");
             }
 
             debug!(block.number = %height, pair.id = %id, "Removed pair");
+            removed_pools.insert(id, removed_metadata);
         }
 
-        // add new pools
+        // add new pools, or new metadata of tracked ones
+        let mut added_pools = HashSet::new();
+        let mut updated_metadata = HashSet::new();
         for (id, new_pair) in new_pairs {
+            let pair_id = state::PoolId(id.clone());
+
             // update block state map
-            let pair_state = updated_states
-                .remove(&id)
-                .expect("BlockUpdate.state should contain every new pool's state");
-            let pair_id = state::PoolId(id);
-            states.insert(pair_id.clone(), Arc::from(pair_state));
+            match updated_states.remove(&id) {
+                Some(pair_state) => {
+                    states.insert(pair_id.clone(), Arc::from(pair_state));
+                }
+                None if states.contains_key(&pair_id) => {}
+                None => {
+                    debug!(block.number = %height, pair.id = %pair_id, "Skipped new pair without a state");
+                    continue;
+                }
+            }
 
             // update metadata map
-            if let Some(metadata) = metadata.insert(pair_id.clone(), Arc::new(new_pair)) {
-                debug!(block.number = %height, pair.id = %metadata.id, "Updated metadata for pair");
+            match metadata.insert(pair_id.clone(), Arc::new(new_pair)) {
+                Some(previous) if metadata_changed(&previous, &metadata[&pair_id]) => {
+                    debug!(block.number = %height, pair.id = %pair_id, "Updated metadata for pair");
+                    updated_metadata.insert(pair_id.clone());
+                }
+                Some(_) => {}
+                None => {
+                    debug!(block.number = %height, pair.id = %pair_id, "Added pair");
+                    added_pools.insert(pair_id.clone());
+                }
             }
 
-            // Update modified pairs
+            // precompute simulates new pools and ones with new metadata again
             modified_pools.insert(pair_id.clone());
-
-            debug!(block.number = %height, pair.id = %pair_id, "Added pair to ");
+            unmodified_pools.remove(&pair_id);
         }
 
         // update existing pools
@@ -153,32 +215,305 @@ impl Block {
             height: block_update.block_number_or_timestamp,
             modified_pools: Arc::new(modified_pools),
             unmodified_pools: Arc::new(unmodified_pools),
+            removed_pools: Arc::new(removed_pools),
+            added_pools: Arc::new(added_pools),
+            updated_metadata: Arc::new(updated_metadata),
             metadata,
             states,
+            native_balance,
+            token_balances,
+            finality: FinalityHeads {
+                latest: height,
+                ..finality
+            },
+            timestamp: None,
+            base_fee_per_gas: None,
+            received_at: Instant::now(),
         }
     }
 
-    pub fn get_pair_state(&self, pair: &Pair) -> PairState {
+    /// Builds the `PairState` for `pair`, leaving out pools rejected by `pool_filter`.
+    pub fn get_pair_state(&self, pair: &Pair, pool_filter: &state::PoolFilter) -> PairState {
         let pair_metadata: HashMap<state::PoolId, Arc<ProtocolComponent>> = self
             .metadata
             .iter()
-            .filter(|(_id, metadata)| pair.in_token_vec(&metadata.tokens))
+            .filter(|(id, metadata)| pair.in_token_vec(&metadata.tokens) && pool_filter.allows(id))
             .map(|(id, metadata)| (id.clone(), Arc::clone(metadata)))
             .collect();
+        let removed_pools = self
+            .removed_pools
+            .iter()
+            .filter(|(_, metadata)| pair.in_token_vec(&metadata.tokens))
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        self.pair_state(pair_metadata, removed_pools)
+    }
+
+    /// Builds the `PairState` of each of `pairs` in a single pass over the block's pools, leaving
+    /// out pools rejected by `pool_filter`.
+    pub fn get_pair_states<'a>(
+        &self,
+        pairs: impl IntoIterator<Item = &'a Pair>,
+        pool_filter: &state::PoolFilter,
+    ) -> HashMap<Pair, PairState> {
+        // a pool holds both of a pair's tokens, so looking pairs up by their first one finds them
+        let mut by_token_a: HashMap<&tycho_common::Bytes, Vec<&Pair>> = HashMap::new();
+        for pair in pairs {
+            by_token_a
+                .entry(&pair.token_a().address)
+                .or_default()
+                .push(pair);
+        }
+
+        let mut pair_metadata: HashMap<&Pair, HashMap<state::PoolId, Arc<ProtocolComponent>>> =
+            by_token_a
+                .values()
+                .flatten()
+                .map(|pair| (*pair, HashMap::new()))
+                .collect();
+        for (id, metadata) in &self.metadata {
+            if !pool_filter.allows(id) {
+                continue;
+            }
+            for token in &metadata.tokens {
+                let Some(pairs) = by_token_a.get(&token.address) else {
+                    continue;
+                };
+                for pair in pairs
+                    .iter()
+                    .filter(|pair| pair.in_token_vec(&metadata.tokens))
+                {
+                    pair_metadata
+                        .get_mut(pair)
+                        .expect("every pair has an entry")
+                        .insert(id.clone(), Arc::clone(metadata));
+                }
+            }
+        }
 
-        let pair_states = self
-            .states
+        pair_metadata
+            .into_iter()
+            .map(|(pair, metadata)| {
+                let removed_pools = self
+                    .removed_pools
+                    .iter()
+                    .filter(|(_, metadata)| pair.in_token_vec(&metadata.tokens))
+                    .map(|(id, _)| id.clone())
+                    .collect();
+                (pair.clone(), self.pair_state(metadata, removed_pools))
+            })
+            .collect()
+    }
+
+    /// The `PairState` of the pools in `pair_metadata`, `removed_pools` being the pair's pools
+    /// removed in this block
+    fn pair_state(
+        &self,
+        pair_metadata: HashMap<state::PoolId, Arc<ProtocolComponent>>,
+        removed_pools: HashSet<state::PoolId>,
+    ) -> PairState {
+        let pair_states = pair_metadata
+            .keys()
+            .filter_map(|id| Some((id.clone(), Arc::clone(self.states.get(id)?))))
+            .collect();
+
+        let pool_meta = pair_metadata
             .iter()
-            .filter(|(id, _)| pair_metadata.contains_key(id))
-            .map(|(id, state)| (id.clone(), Arc::clone(state)))
+            .map(|(id, component)| (id.clone(), PoolMeta::from_component(component)))
             .collect();
 
         PairState {
             block_height: self.height,
             modified_pools: Arc::clone(&self.modified_pools),
             unmodified_pools: Arc::clone(&self.unmodified_pools),
+            removed_pools: Arc::new(removed_pools),
             states: pair_states,
             metadata: pair_metadata,
+            pool_meta,
+            native_balance: self.native_balance.clone(),
+            finality: self.finality,
+            timestamp: self.timestamp,
+            base_fee_per_gas: self.base_fee_per_gas,
+            received_at: self.received_at,
+        }
+    }
+}
+
+/// Whether a pool's metadata changed in a way that affects how it's simulated or routed through
+fn metadata_changed(previous: &ProtocolComponent, current: &ProtocolComponent) -> bool {
+    previous.static_attributes != current.static_attributes
+        || previous.tokens != current.tokens
+        || previous.contract_ids != current.contract_ids
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr as _;
+
+    use alloy::primitives::U256;
+    use tycho_common::{Bytes, models::token::Token};
+    use tycho_simulation::evm::protocol::uniswap_v2::state::UniswapV2State;
+
+    use super::*;
+
+    fn token(address: &str, symbol: &str) -> Token {
+        Token::new(
+            &Bytes::from_str(address).unwrap(),
+            symbol,
+            18,
+            0,
+            &[Some(0)],
+            tycho_common::models::Chain::Ethereum,
+            100,
+        )
+    }
+
+    fn pair() -> Pair {
+        Pair::new(
+            token("0x0000000000000000000000000000000000000001", "AAA"),
+            token("0x0000000000000000000000000000000000000002", "BBB"),
+        )
+    }
+
+    fn component(id: &str, pair: &Pair) -> ProtocolComponent {
+        ProtocolComponent::new(
+            Bytes::from_str(id).unwrap(),
+            "uniswap_v2".to_string(),
+            "uniswap_v2_pool".to_string(),
+            tycho_common::models::Chain::Ethereum,
+            vec![pair.token_a().clone(), pair.token_b().clone()],
+            vec![],
+            HashMap::new(),
+            Bytes::default(),
+            Default::default(),
+        )
+    }
+
+    fn update(height: u64, new: &[&str], removed: &[&str]) -> Update {
+        let pair = pair();
+        Update {
+            block_number_or_timestamp: height,
+            states: new
+                .iter()
+                .map(|id| {
+                    let state: Box<dyn ProtocolSim> = Box::new(UniswapV2State::new(
+                        U256::from(1_000u64),
+                        U256::from(1_000u64),
+                    ));
+                    (id.to_string(), state)
+                })
+                .collect(),
+            new_pairs: new
+                .iter()
+                .map(|id| (id.to_string(), component(id, &pair)))
+                .collect(),
+            removed_pairs: removed
+                .iter()
+                .map(|id| (id.to_string(), component(id, &pair)))
+                .collect(),
         }
     }
+
+    const POOL_A: &str = "0x0000000000000000000000000000000000001000";
+    const POOL_B: &str = "0x0000000000000000000000000000000000001001";
+
+    #[test]
+    fn removed_pools_are_pruned_and_surfaced() {
+        let block = Block::new(update(1, &[POOL_A, POOL_B], &[]));
+
+        let block = block.apply_update(update(2, &[], &[POOL_A]));
+        let state = block.get_pair_state(&pair(), &state::PoolFilter::default());
+
+        let pool_a = state::PoolId::from(POOL_A);
+        assert!(!block.states.contains_key(&pool_a));
+        assert!(!block.metadata.contains_key(&pool_a));
+        assert!(!state.states.contains_key(&pool_a));
+        assert_eq!(*state.removed_pools, HashSet::from([pool_a]));
+        assert!(state.states.contains_key(&state::PoolId::from(POOL_B)));
+    }
+
+    #[test]
+    fn removed_pools_are_only_surfaced_for_one_block() {
+        let block = Block::new(update(1, &[POOL_A], &[]))
+            .apply_update(update(2, &[], &[POOL_A]))
+            .apply_update(update(3, &[], &[]));
+
+        let state = block.get_pair_state(&pair(), &state::PoolFilter::default());
+        assert!(state.removed_pools.is_empty());
+    }
+
+    #[test]
+    fn pools_added_mid_stream_are_tracked_and_modified() {
+        let block = Block::new(update(1, &[POOL_A], &[])).apply_update(update(2, &[POOL_B], &[]));
+        let state = block.get_pair_state(&pair(), &state::PoolFilter::default());
+
+        let pool_b = state::PoolId::from(POOL_B);
+        assert_eq!(*block.added_pools, HashSet::from([pool_b.clone()]));
+        assert!(state.metadata.contains_key(&pool_b));
+        assert!(state.states.contains_key(&pool_b));
+        assert!(state.modified_pools.contains(&pool_b));
+        assert!(!state.unmodified_pools.contains(&pool_b));
+    }
+
+    #[test]
+    fn metadata_changes_replace_the_metadata_and_modify_the_pool() {
+        let block = Block::new(update(1, &[POOL_A], &[]));
+
+        let mut changed = update(2, &[POOL_A], &[]);
+        changed.states.clear();
+        changed
+            .new_pairs
+            .get_mut(POOL_A)
+            .unwrap()
+            .static_attributes
+            .insert("fee".to_string(), Bytes::from(vec![0x0b, 0xb8]));
+        let block = block.apply_update(changed);
+
+        let pool_a = state::PoolId::from(POOL_A);
+        assert!(block.added_pools.is_empty());
+        assert_eq!(*block.updated_metadata, HashSet::from([pool_a.clone()]));
+        assert!(block.states.contains_key(&pool_a));
+        assert!(block.modified_pools.contains(&pool_a));
+        assert!(
+            block.metadata[&pool_a]
+                .static_attributes
+                .contains_key("fee")
+        );
+    }
+
+    #[test]
+    fn resent_metadata_is_not_a_change() {
+        let block = Block::new(update(1, &[POOL_A], &[])).apply_update(update(2, &[POOL_A], &[]));
+
+        assert!(block.added_pools.is_empty());
+        assert!(block.updated_metadata.is_empty());
+    }
+
+    #[test]
+    fn removing_an_unknown_pool_is_skipped() {
+        let block = Block::new(update(1, &[POOL_A], &[])).apply_update(update(2, &[], &[POOL_B]));
+
+        assert!(block.removed_pools.is_empty());
+        assert!(block.states.contains_key(&state::PoolId::from(POOL_A)));
+    }
+
+    #[test]
+    fn gas_funds_need_a_known_balance_of_at_least_the_minimum() {
+        let mut block = Block::new(update(1, &[POOL_A], &[]));
+        let min_balance = BigUint::from(100u64);
+        let has_gas_funds = |block: &Block| {
+            block
+                .get_pair_state(&pair(), &state::PoolFilter::default())
+                .has_gas_funds(&min_balance)
+        };
+
+        assert!(!has_gas_funds(&block));
+
+        block.native_balance = Some(BigUint::from(99u64));
+        assert!(!has_gas_funds(&block));
+
+        block.native_balance = Some(BigUint::from(100u64));
+        assert!(has_gas_funds(&block));
+    }
 }
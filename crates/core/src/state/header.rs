@@ -0,0 +1,78 @@
+use alloy::primitives::B256;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Chain conditions read from the header of a collected block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockHeader {
+    pub height: u64,
+    pub hash: B256,
+    /// Header timestamp, in seconds since the unix epoch
+    pub timestamp: u64,
+    /// EIP-1559 base fee, `None` on chains without a fee market
+    pub base_fee_per_gas: Option<u64>,
+    /// EIP-4844 blob base fee, `None` on chains without blobs
+    pub blob_base_fee: Option<u128>,
+}
+
+/// Latest, safe and finalized heads of a chain, as reported by its RPC.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FinalityHeads {
+    pub latest: u64,
+    /// `None` if the RPC doesn't serve the `safe` tag
+    pub safe: Option<u64>,
+    /// `None` if the RPC doesn't serve the `finalized` tag
+    pub finalized: Option<u64>,
+}
+
+impl FinalityHeads {
+    /// Number of blocks built on top of `height`
+    pub fn confirmations(&self, height: u64) -> u64 {
+        self.latest.saturating_sub(height)
+    }
+
+    /// Whether the block at `height` meets `confirmation`.
+    ///
+    /// Heads the RPC doesn't report never confirm a block.
+    pub fn is_confirmed(&self, height: u64, confirmation: Confirmation) -> bool {
+        match confirmation {
+            Confirmation::Latest => true,
+            Confirmation::Depth(depth) => self.confirmations(height) >= depth,
+            Confirmation::Safe => self.safe.is_some_and(|safe| safe >= height),
+            Confirmation::Finalized => self.finalized.is_some_and(|finalized| finalized >= height),
+        }
+    }
+}
+
+/// How settled a block must be before it's acted on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Confirmation {
+    #[default]
+    Latest,
+    /// At least this many blocks on top
+    Depth(u64),
+    Safe,
+    Finalized,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confirmation_requirements() {
+        let heads = FinalityHeads {
+            latest: 110,
+            safe: Some(100),
+            finalized: None,
+        };
+
+        assert!(heads.is_confirmed(110, Confirmation::Latest));
+        assert!(heads.is_confirmed(105, Confirmation::Depth(5)));
+        assert!(!heads.is_confirmed(106, Confirmation::Depth(5)));
+        assert!(heads.is_confirmed(100, Confirmation::Safe));
+        assert!(!heads.is_confirmed(101, Confirmation::Safe));
+        assert!(!heads.is_confirmed(50, Confirmation::Finalized));
+    }
+}
@@ -0,0 +1,78 @@
+//! How many of a chain's blocks a consumer of its pair states never saw.
+//!
+//! Unless a pair's states are queued, a strategy busy with one block only reads the latest state
+//! once it's done, and the blocks in between are silently replaced. [`BlockLag`] counts those from
+//! the heights read, and tells when most recent reads skipped some, i.e. the consumer can't keep
+//! up with the chain.
+use std::collections::VecDeque;
+
+/// Reads looked back on to tell whether a consumer is behind
+const WINDOW: usize = 20;
+
+/// Heights of the pair states read from one chain.
+#[derive(Debug, Clone, Default)]
+pub struct BlockLag {
+    last_height: Option<u64>,
+    /// Whether each of the latest reads skipped blocks, oldest first
+    reads: VecDeque<bool>,
+}
+
+impl BlockLag {
+    /// Records a read of the state at `height`, returning how many blocks were skipped since the
+    /// previous read.
+    ///
+    /// A height at or below the previous one, as after a reorg, skipped none.
+    pub fn record(&mut self, height: u64) -> u64 {
+        let skipped = self
+            .last_height
+            .map_or(0, |last| height.saturating_sub(last).saturating_sub(1));
+        self.last_height = Some(height);
+
+        if self.reads.len() == WINDOW {
+            self.reads.pop_front();
+        }
+        self.reads.push_back(skipped > 0);
+
+        skipped
+    }
+
+    /// Whether most of the last reads, once there are enough of them, skipped blocks
+    pub fn is_behind(&self) -> bool {
+        self.reads.len() == WINDOW
+            && self.reads.iter().filter(|&&skipped| skipped).count() > WINDOW / 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_blocks_between_reads() {
+        let mut lag = BlockLag::default();
+
+        assert_eq!(lag.record(10), 0);
+        assert_eq!(lag.record(11), 0);
+        assert_eq!(lag.record(14), 2);
+        assert_eq!(lag.record(12), 0);
+        assert_eq!(lag.record(12), 0);
+    }
+
+    #[test]
+    fn behind_once_most_reads_skip_blocks() {
+        let mut lag = BlockLag::default();
+
+        let mut height = 0;
+        for _ in 0..WINDOW {
+            height += 2;
+            lag.record(height);
+            assert_eq!(lag.is_behind(), lag.reads.len() == WINDOW);
+        }
+
+        for _ in 0..WINDOW / 2 {
+            height += 1;
+            lag.record(height);
+        }
+        assert!(!lag.is_behind());
+    }
+}
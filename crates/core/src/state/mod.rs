@@ -1,9 +1,16 @@
-use std::fmt::Display;
+use std::{collections::HashSet, fmt::Display};
 
 use serde::{Deserialize, Serialize};
 
+pub mod alignment;
+pub mod balances;
 pub mod block;
+pub mod header;
+pub mod lag;
 pub mod pair;
+pub mod pair_snapshot;
+pub mod pool_meta;
+pub mod snapshot;
 
 // TODO: maybe some address sanitization?
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -26,3 +33,69 @@ impl From<&str> for PoolId {
         Self(id.to_string())
     }
 }
+
+impl AsRef<str> for PoolId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Per-chain pool allowlist/denylist applied before pools reach precompute.
+///
+/// Pool ids are compared case-insensitively. An empty allowlist allows every pool that isn't
+/// denied.
+#[derive(Debug, Clone, Default)]
+pub struct PoolFilter {
+    allowlist: HashSet<String>,
+    denylist: HashSet<String>,
+}
+
+impl PoolFilter {
+    pub fn new<A, D>(allowlist: A, denylist: D) -> Self
+    where
+        A: IntoIterator,
+        A::Item: AsRef<str>,
+        D: IntoIterator,
+        D::Item: AsRef<str>,
+    {
+        Self {
+            allowlist: allowlist
+                .into_iter()
+                .map(|id| id.as_ref().to_ascii_lowercase())
+                .collect(),
+            denylist: denylist
+                .into_iter()
+                .map(|id| id.as_ref().to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    pub fn allows(&self, id: &PoolId) -> bool {
+        if self.allowlist.is_empty() && self.denylist.is_empty() {
+            return true;
+        }
+
+        let id = id.as_ref().to_ascii_lowercase();
+        !self.denylist.contains(&id) && (self.allowlist.is_empty() || self.allowlist.contains(&id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pool_filter_applies_allowlist_and_denylist() {
+        let permissive = PoolFilter::default();
+        assert!(permissive.allows(&PoolId::from("0xabc")));
+
+        let denied = PoolFilter::new(Vec::<String>::new(), ["0xABC"]);
+        assert!(!denied.allows(&PoolId::from("0xabc")));
+        assert!(denied.allows(&PoolId::from("0xdef")));
+
+        let allowed = PoolFilter::new(["0xabc", "0xdef"], ["0xdef"]);
+        assert!(allowed.allows(&PoolId::from("0xAbC")));
+        assert!(!allowed.allows(&PoolId::from("0xdef")));
+        assert!(!allowed.allows(&PoolId::from("0x123")));
+    }
+}
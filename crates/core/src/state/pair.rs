@@ -2,19 +2,33 @@ use std::{
     collections::{HashMap, HashSet},
     fmt::Display,
     pin::Pin,
-    sync::Arc,
+    sync::{Arc, Mutex},
     task::{self, Poll},
+    time::Instant,
 };
 
 use futures::{Stream, StreamExt};
+use num_bigint::BigUint;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tokio::sync::watch;
-use tokio_stream::wrappers::WatchStream;
+use tokio::{
+    select,
+    sync::{broadcast, watch},
+    task::JoinHandle,
+};
+use tokio_stream::wrappers::{BroadcastStream, WatchStream, errors::BroadcastStreamRecvError};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, instrument, warn};
 use tycho_common::{models::token::Token, simulation::protocol_sim::ProtocolSim};
 use tycho_simulation::protocol::models::ProtocolComponent;
 
-use super::block::Block;
-use crate::state;
+use super::{
+    block::Block,
+    header::{Confirmation, FinalityHeads},
+    lag::BlockLag,
+    pool_meta::PoolMeta,
+};
+use crate::{chain::Chain, state};
 
 /// Represents a pair of tokens, normalized to Uniswap's zero2one direction.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -62,22 +76,92 @@ pub struct PairState {
 
     pub unmodified_pools: Arc<HashSet<state::PoolId>>,
 
+    /// The pair's pools removed in this block, whose precomputes from earlier blocks are stale
+    pub removed_pools: Arc<HashSet<state::PoolId>>,
+
     #[allow(dead_code)]
     pub metadata: HashMap<state::PoolId, Arc<ProtocolComponent>>,
+
+    /// Protocol and fee of each pool, read from its metadata
+    pub pool_meta: HashMap<state::PoolId, PoolMeta>,
+
+    /// Native gas token balance of the tracked account at `block_height`, if tracked
+    pub native_balance: Option<BigUint>,
+
+    /// Chain heads when the block was collected, to check how settled `block_height` is
+    pub finality: FinalityHeads,
+
+    /// Header timestamp, in seconds since the unix epoch, `None` if the collector failed to fetch
+    /// the header
+    pub timestamp: Option<u64>,
+
+    /// EIP-1559 base fee, `None` on chains without a fee market or if the collector failed to
+    /// fetch the header
+    pub base_fee_per_gas: Option<u64>,
+
+    /// When the collector received the block, to measure how long it took to act on it
+    pub received_at: Instant,
+}
+
+impl PairState {
+    /// Whether the tracked account holds at least `min_balance` of the chain's gas token.
+    ///
+    /// Returns `false` when no native balance is being tracked, so callers refuse to submit
+    /// transactions without knowing they can pay for gas.
+    pub fn has_gas_funds(&self, min_balance: &BigUint) -> bool {
+        self.native_balance
+            .as_ref()
+            .is_some_and(|balance| balance >= min_balance)
+    }
+
+    /// Whether `block_height` meets `confirmation`, as of when the block was collected.
+    pub fn is_confirmed(&self, confirmation: Confirmation) -> bool {
+        self.finality.is_confirmed(self.block_height, confirmation)
+    }
+}
+
+/// How a pair's states reach a consumer still busy with an earlier one when the next arrives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PairStateDelivery {
+    /// Only the latest state, replacing any not read yet
+    #[default]
+    Latest,
+    /// Every state in order, up to this many unread before the oldest are dropped
+    Queue(usize),
 }
 
 #[derive(Debug)]
-pub struct PairStateStream {
-    pair: Pair,
-    block_rx: WatchStream<Arc<Option<Block>>>,
+pub struct PairStateStream(Source);
+
+#[derive(Debug)]
+enum Source {
+    /// Builds its pair's state from every block itself
+    Block {
+        pair: Pair,
+        pool_filter: Arc<state::PoolFilter>,
+        block_rx: WatchStream<Arc<Option<Block>>>,
+    },
+    /// Receives its pair's latest state from a [`PairStateRouter`]
+    Routed(WatchStream<Arc<Option<PairState>>>),
+    /// Receives every one of its pair's states from a [`PairStateRouter`]
+    Queued {
+        chain: Chain,
+        state_rx: BroadcastStream<PairState>,
+    },
 }
 
 impl PairStateStream {
-    pub fn from_block_rx(pair: Pair, block_rx: watch::Receiver<Arc<Option<Block>>>) -> Self {
-        Self {
+    pub fn from_block_rx(
+        pair: Pair,
+        pool_filter: Arc<state::PoolFilter>,
+        block_rx: watch::Receiver<Arc<Option<Block>>>,
+    ) -> Self {
+        Self(Source::Block {
             pair,
+            pool_filter,
             block_rx: WatchStream::from_changes(block_rx),
-        }
+        })
     }
 }
 
@@ -88,22 +172,194 @@ impl Stream for PairStateStream {
         mut self: Pin<&mut Self>,
         cx: &mut task::Context<'_>,
     ) -> task::Poll<Option<Self::Item>> {
-        // check watch receiver for new block
-        let block_poll = self.block_rx.poll_next_unpin(cx);
-
-        match block_poll {
-            // Stream itself isn't ready, propagate pending state
-            Poll::Pending => Poll::Pending,
-            // Stream has ended, end our stream too
-            Poll::Ready(None) => Poll::Ready(None),
-            Poll::Ready(Some(block)) => match block.as_ref() {
-                Some(block) => {
-                    let state = block.get_pair_state(&self.pair);
-                    Poll::Ready(Some(state))
+        match &mut self.0 {
+            Source::Block {
+                pair,
+                pool_filter,
+                block_rx,
+            } => {
+                // check watch receiver for new block
+                let block_poll = block_rx.poll_next_unpin(cx);
+
+                match block_poll {
+                    // Stream itself isn't ready, propagate pending state
+                    Poll::Pending => Poll::Pending,
+                    // Stream has ended, end our stream too
+                    Poll::Ready(None) => Poll::Ready(None),
+                    Poll::Ready(Some(block)) => match block.as_ref() {
+                        Some(block) => Poll::Ready(Some(block.get_pair_state(pair, pool_filter))),
+                        // Only start yielding values after the initial block is received
+                        None => Poll::Pending,
+                    },
                 }
-                // Only start yielding values after the initial block is received
-                None => Poll::Pending,
+            }
+            Source::Routed(state_rx) => match state_rx.poll_next_unpin(cx) {
+                Poll::Pending => Poll::Pending,
+                Poll::Ready(None) => Poll::Ready(None),
+                Poll::Ready(Some(state)) => match state.as_ref() {
+                    Some(state) => Poll::Ready(Some(state.clone())),
+                    None => Poll::Pending,
+                },
+            },
+            Source::Queued { chain, state_rx } => loop {
+                match state_rx.poll_next_unpin(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(None) => return Poll::Ready(None),
+                    Poll::Ready(Some(Ok(state))) => return Poll::Ready(Some(state)),
+                    // the oldest states were dropped, the next one is the oldest left
+                    Poll::Ready(Some(Err(BroadcastStreamRecvError::Lagged(dropped)))) => {
+                        warn!(
+                            %chain,
+                            dropped,
+                            "Pair state queue is full, dropped the oldest states"
+                        );
+                        metrics::counter!("kuma_pair_state_queue_dropped_total", "chain" => chain.to_string())
+                            .increment(dropped);
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Builds the state of every subscribed pair once per block and fans it out to the pair's
+/// streams, instead of each [`PairStateStream`] filtering the same block for its own pair.
+#[derive(Debug, Clone)]
+pub struct PairStateRouter {
+    chain: Chain,
+    routes: Arc<Mutex<HashMap<Pair, Route>>>,
+    delivery: PairStateDelivery,
+}
+
+/// Where a pair's states are sent to its streams
+#[derive(Debug)]
+enum Route {
+    Latest(watch::Sender<Arc<Option<PairState>>>),
+    Queue(broadcast::Sender<PairState>),
+}
+
+impl Route {
+    fn new(delivery: PairStateDelivery) -> Self {
+        match delivery {
+            PairStateDelivery::Latest => Self::Latest(watch::channel(Arc::new(None)).0),
+            PairStateDelivery::Queue(capacity) => Self::Queue(broadcast::channel(capacity).0),
+        }
+    }
+
+    fn subscribe(&self, chain: &Chain) -> PairStateStream {
+        PairStateStream(match self {
+            Self::Latest(state_tx) => {
+                Source::Routed(WatchStream::from_changes(state_tx.subscribe()))
+            }
+            Self::Queue(state_tx) => Source::Queued {
+                chain: chain.clone(),
+                state_rx: BroadcastStream::new(state_tx.subscribe()),
             },
+        })
+    }
+
+    /// Whether every stream of the pair was dropped
+    fn is_closed(&self) -> bool {
+        match self {
+            Self::Latest(state_tx) => state_tx.is_closed(),
+            Self::Queue(state_tx) => state_tx.receiver_count() == 0,
+        }
+    }
+
+    fn send(&self, state: PairState) {
+        match self {
+            Self::Latest(state_tx) => {
+                state_tx.send_replace(Arc::new(Some(state)));
+            }
+            // only fails without streams, which the router drops the route of next block
+            Self::Queue(state_tx) => {
+                let _ = state_tx.send(state);
+            }
+        }
+    }
+}
+
+impl PairStateRouter {
+    /// Spawns the task routing the blocks of `chain`'s `block_rx` until shutdown or the collector
+    /// stops, leaving out pools rejected by `pool_filter` and sending states to streams per
+    /// `delivery`.
+    ///
+    /// # Panics
+    /// If `delivery` queues no states.
+    pub fn spawn(
+        chain: Chain,
+        block_rx: watch::Receiver<Arc<Option<Block>>>,
+        pool_filter: Arc<state::PoolFilter>,
+        delivery: PairStateDelivery,
+        shutdown_token: CancellationToken,
+    ) -> (Self, JoinHandle<()>) {
+        assert_ne!(
+            delivery,
+            PairStateDelivery::Queue(0),
+            "pair state queues must hold at least one state"
+        );
+        let router = Self {
+            chain,
+            routes: Arc::default(),
+            delivery,
+        };
+        let task = tokio::spawn(router.clone().run(block_rx, pool_filter, shutdown_token));
+        (router, task)
+    }
+
+    /// A stream of `pair`'s states, from the next block on like
+    /// [`PairStateStream::from_block_rx`].
+    pub fn subscribe(&self, pair: &Pair) -> PairStateStream {
+        self.routes
+            .lock()
+            .unwrap()
+            .entry(pair.clone())
+            .or_insert_with(|| Route::new(self.delivery))
+            .subscribe(&self.chain)
+    }
+
+    #[instrument(name = "pair_state_router", skip_all)]
+    async fn run(
+        self,
+        mut block_rx: watch::Receiver<Arc<Option<Block>>>,
+        pool_filter: Arc<state::PoolFilter>,
+        shutdown_token: CancellationToken,
+    ) {
+        // the collector only keeps its latest block, so any it replaced while the router was busy
+        // never reach the streams, whatever their delivery
+        let mut lag = BlockLag::default();
+        loop {
+            select! {
+                () = shutdown_token.cancelled() => break,
+                changed = block_rx.changed() => if changed.is_err() {
+                    break;
+                },
+            }
+
+            let block = Arc::clone(&block_rx.borrow_and_update());
+            let Some(block) = block.as_ref() else {
+                continue;
+            };
+
+            let skipped = lag.record(block.height);
+            if skipped > 0 {
+                warn!(
+                    chain = %self.chain,
+                    block.height = block.height,
+                    skipped,
+                    "Pair state router missed blocks the collector replaced"
+                );
+                metrics::counter!("kuma_pair_state_router_skipped_blocks_total", "chain" => self.chain.to_string())
+                    .increment(skipped);
+            }
+
+            let mut routes = self.routes.lock().unwrap();
+            // pairs whose streams were all dropped aren't built anymore
+            routes.retain(|_, route| !route.is_closed());
+            for (pair, state) in block.get_pair_states(routes.keys(), &pool_filter) {
+                routes[&pair].send(state);
+            }
         }
+        debug!("Pair state router stopped");
     }
 }
@@ -0,0 +1,208 @@
+//! Serializable snapshots of a [`PairState`], for replays, backtests and bug reports.
+//!
+//! Simulation states are `dyn ProtocolSim` trait objects and can't be serialized, so a snapshot
+//! holds each pool's state the way Tycho sends it: its component with the protocol's attributes and
+//! balances, which the collector's decoders turn back into the same simulation state.
+//! [`TychoStates`] follows a chain's Tycho messages, e.g. a recording's, to keep the latest of those
+//! per component for [`PairState::to_snapshot`].
+use std::collections::{HashMap, HashSet};
+
+use color_eyre::eyre::{self, OptionExt as _, eyre};
+use serde::{Deserialize, Serialize};
+use tycho_simulation::{
+    evm::decoder::TychoStreamDecoder,
+    tycho_client::feed::{
+        BlockHeader as TychoBlockHeader, FeedMessage,
+        synchronizer::{ComponentWithState, Snapshot, StateSyncMessage},
+    },
+};
+
+use super::{
+    PoolFilter, PoolId,
+    block::Block,
+    pair::{Pair, PairState},
+};
+
+/// A pool's state as Tycho sent it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolSnapshot {
+    /// Protocol the pool is decoded as, e.g. `uniswap_v3`
+    pub protocol_system: String,
+    pub state: ComponentWithState,
+}
+
+/// A [`PairState`] in a form that can be written to disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairStateSnapshot {
+    /// Header of the block the state is at
+    pub header: TychoBlockHeader,
+    pub pools: HashMap<PoolId, PoolSnapshot>,
+    /// The pools modified in that block, which precompute simulates again
+    pub modified_pools: HashSet<PoolId>,
+}
+
+/// The latest state Tycho sent of every component of a chain.
+#[derive(Debug, Default)]
+pub struct TychoStates {
+    pub(super) header: Option<TychoBlockHeader>,
+    pub(super) pools: HashMap<PoolId, PoolSnapshot>,
+}
+
+impl TychoStates {
+    /// Applies the snapshots, deltas and removals of `message`, like the decoder does.
+    pub fn apply(&mut self, message: &FeedMessage<TychoBlockHeader>) {
+        for (protocol_system, state_msg) in &message.state_msgs {
+            if self
+                .header
+                .as_ref()
+                .is_none_or(|header| header.number <= state_msg.header.number)
+            {
+                self.header = Some(state_msg.header.clone());
+            }
+
+            for (id, state) in &state_msg.snapshots.states {
+                self.pools.insert(
+                    PoolId::from(id.as_str()),
+                    PoolSnapshot {
+                        protocol_system: protocol_system.clone(),
+                        state: state.clone(),
+                    },
+                );
+            }
+
+            if let Some(deltas) = &state_msg.deltas {
+                for (id, delta) in &deltas.state_updates {
+                    let Some(pool) = self.pools.get_mut(&PoolId::from(id.as_str())) else {
+                        continue;
+                    };
+                    let attributes = &mut pool.state.state.attributes;
+                    attributes.extend(delta.updated_attributes.clone());
+                    attributes.retain(|name, _| !delta.deleted_attributes.contains(name));
+                }
+                for (id, balances) in &deltas.component_balances {
+                    let Some(pool) = self.pools.get_mut(&PoolId::from(id.as_str())) else {
+                        continue;
+                    };
+                    for (token, balance) in &balances.0 {
+                        pool.state
+                            .state
+                            .balances
+                            .insert(token.clone(), balance.balance.clone());
+                    }
+                }
+            }
+
+            for id in state_msg.removed_components.keys() {
+                self.pools.remove(&PoolId::from(id.as_str()));
+            }
+        }
+    }
+
+    /// Height of the latest block applied
+    pub fn block_height(&self) -> Option<u64> {
+        self.header.as_ref().map(|header| header.number)
+    }
+}
+
+impl PairState {
+    /// The snapshot of this state's pools, taken from `tycho_states` at the same block.
+    ///
+    /// # Errors
+    /// If `tycho_states` is at another block or misses one of the pools.
+    pub fn to_snapshot(&self, tycho_states: &TychoStates) -> eyre::Result<PairStateSnapshot> {
+        let header = tycho_states
+            .header
+            .clone()
+            .ok_or_eyre("no tycho message was applied")?;
+        if header.number != self.block_height {
+            return Err(eyre!(
+                "tycho states are at block {}, not {}",
+                header.number,
+                self.block_height
+            ));
+        }
+
+        let pools = self
+            .states
+            .keys()
+            .map(|id| {
+                let pool = tycho_states
+                    .pools
+                    .get(id)
+                    .ok_or_else(|| eyre!("no tycho state of pool {id}"))?;
+                Ok((id.clone(), pool.clone()))
+            })
+            .collect::<eyre::Result<_>>()?;
+
+        Ok(PairStateSnapshot {
+            header,
+            pools,
+            modified_pools: self
+                .modified_pools
+                .iter()
+                .filter(|id| self.states.contains_key(id))
+                .cloned()
+                .collect(),
+        })
+    }
+
+    /// Decodes `snapshot` with `decoder`, which must have the snapshot's protocols registered and
+    /// its tokens set, back into the state of `pair`.
+    ///
+    /// The state has no native balance or base fee and its finality is only the block itself, as
+    /// the collector doesn't get those from Tycho.
+    pub async fn from_snapshot(
+        snapshot: PairStateSnapshot,
+        decoder: &TychoStreamDecoder<TychoBlockHeader>,
+        pair: &Pair,
+        pool_filter: &PoolFilter,
+    ) -> eyre::Result<Self> {
+        let message = snapshot_message(&snapshot.header, snapshot.pools);
+        let update = decoder
+            .decode(message)
+            .await
+            .map_err(|e| eyre!("failed to decode pair state snapshot: {e}"))?;
+        let mut state = Block::new(update).get_pair_state(pair, pool_filter);
+        state.timestamp = Some(snapshot.header.timestamp);
+
+        let (modified, unmodified): (HashSet<_>, HashSet<_>) = state
+            .states
+            .keys()
+            .cloned()
+            .partition(|id| snapshot.modified_pools.contains(id));
+        state.modified_pools = modified.into();
+        state.unmodified_pools = unmodified.into();
+
+        Ok(state)
+    }
+}
+
+/// A Tycho message holding the full snapshot of `pools` at `header`, like the first message of a
+/// stream.
+pub(super) fn snapshot_message(
+    header: &TychoBlockHeader,
+    pools: impl IntoIterator<Item = (PoolId, PoolSnapshot)>,
+) -> FeedMessage<TychoBlockHeader> {
+    let mut state_msgs: HashMap<String, StateSyncMessage<TychoBlockHeader>> = HashMap::new();
+    for (id, pool) in pools {
+        state_msgs
+            .entry(pool.protocol_system)
+            .or_insert_with(|| StateSyncMessage {
+                header: header.clone(),
+                snapshots: Snapshot {
+                    states: HashMap::new(),
+                    vm_storage: HashMap::new(),
+                },
+                deltas: None,
+                removed_components: HashMap::new(),
+            })
+            .snapshots
+            .states
+            .insert(id.as_ref().to_string(), pool.state);
+    }
+
+    FeedMessage {
+        state_msgs,
+        sync_states: HashMap::new(),
+    }
+}
@@ -0,0 +1,162 @@
+//! What kind of pool a component is and what it charges, read from Tycho's component metadata.
+use serde::{Deserialize, Serialize};
+use tycho_simulation::protocol::models::ProtocolComponent;
+
+/// The AMM design a pool implements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolVersion {
+    /// Constant product over the whole price range
+    V2,
+    /// Concentrated liquidity
+    V3,
+    /// Concentrated liquidity in a singleton, with hooks
+    V4,
+    Other,
+}
+
+impl PoolVersion {
+    /// The version of a Tycho protocol system, e.g. `V3` for `pancakeswap_v3`
+    pub fn of_protocol_system(protocol_system: &str) -> Self {
+        match protocol_system.rsplit('_').next() {
+            Some("v2") => Self::V2,
+            Some("v3") => Self::V3,
+            Some("v4") => Self::V4,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// A pool's protocol and swap fee.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolMeta {
+    /// Tycho protocol system, e.g. `uniswap_v3`
+    pub protocol_system: String,
+    pub version: PoolVersion,
+    /// Swap fee in hundredths of a bip, as v3 pools encode it, e.g. 3000 for 0.3%. `None` when
+    /// the fee is dynamic or unknown.
+    pub fee_pips: Option<u32>,
+}
+
+impl PoolMeta {
+    pub fn new(protocol_system: String, fee_pips: Option<u32>) -> Self {
+        Self {
+            version: PoolVersion::of_protocol_system(&protocol_system),
+            protocol_system,
+            fee_pips,
+        }
+    }
+
+    /// Reads the fee from the component's static attributes: `fee` on v3 pools, `key_lp_fee` on
+    /// v4 ones. V2 forks charge the fixed fee of their protocol.
+    pub fn from_component(component: &ProtocolComponent) -> Self {
+        let attribute = |name: &str| {
+            component
+                .static_attributes
+                .get(name)
+                .and_then(|value| be_u32(value))
+        };
+        let fee_pips = match component.protocol_system.as_str() {
+            "uniswap_v2" | "sushiswap_v2" => Some(3_000),
+            "pancakeswap_v2" => Some(2_500),
+            "uniswap_v4" => attribute("key_lp_fee").filter(|fee| *fee & DYNAMIC_FEE_FLAG == 0),
+            _ => attribute("fee"),
+        };
+
+        Self::new(component.protocol_system.clone(), fee_pips)
+    }
+
+    /// The swap fee in basis points
+    pub fn fee_bps(&self) -> Option<f64> {
+        self.fee_pips.map(|fee| f64::from(fee) / 100.0)
+    }
+}
+
+/// Set in a v4 pool key's fee when its hook sets the fee of every swap
+const DYNAMIC_FEE_FLAG: u32 = 0x80_0000;
+
+/// A big-endian unsigned integer, `None` if it doesn't fit in a `u32`
+fn be_u32(bytes: &[u8]) -> Option<u32> {
+    let start = bytes
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bytes.len());
+    let significant = &bytes[start..];
+    if significant.len() > 4 {
+        return None;
+    }
+    Some(
+        significant
+            .iter()
+            .fold(0, |value, byte| (value << 8) | u32::from(*byte)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tycho_common::Bytes;
+
+    use super::*;
+
+    fn component(protocol_system: &str, attributes: &[(&str, &[u8])]) -> ProtocolComponent {
+        ProtocolComponent::new(
+            Bytes::from(vec![0x01]),
+            protocol_system.to_string(),
+            "pool".to_string(),
+            tycho_common::models::Chain::Ethereum,
+            vec![],
+            vec![],
+            attributes
+                .iter()
+                .map(|(name, value)| (name.to_string(), Bytes::from(value.to_vec())))
+                .collect::<HashMap<_, _>>(),
+            Bytes::default(),
+            Default::default(),
+        )
+    }
+
+    #[test]
+    fn reads_v3_fee_attribute() {
+        let meta = PoolMeta::from_component(&component("uniswap_v3", &[("fee", &[0x0b, 0xb8])]));
+
+        assert_eq!(meta.version, PoolVersion::V3);
+        assert_eq!(meta.fee_pips, Some(3_000));
+        assert_eq!(meta.fee_bps(), Some(30.0));
+    }
+
+    #[test]
+    fn v2_forks_charge_their_fixed_fee() {
+        let meta = PoolMeta::from_component(&component("pancakeswap_v2", &[]));
+
+        assert_eq!(meta.version, PoolVersion::V2);
+        assert_eq!(meta.fee_pips, Some(2_500));
+    }
+
+    #[test]
+    fn dynamic_v4_fees_are_unknown() {
+        let fixed = PoolMeta::from_component(&component(
+            "uniswap_v4",
+            &[("key_lp_fee", &[0x00, 0x01, 0xf4])],
+        ));
+        let dynamic = PoolMeta::from_component(&component(
+            "uniswap_v4",
+            &[("key_lp_fee", &[0x80, 0x00, 0x00])],
+        ));
+
+        assert_eq!(fixed.version, PoolVersion::V4);
+        assert_eq!(fixed.fee_pips, Some(500));
+        assert_eq!(dynamic.fee_pips, None);
+    }
+
+    #[test]
+    fn oversized_fee_attributes_are_ignored() {
+        let meta = PoolMeta::from_component(&component(
+            "uniswap_v3",
+            &[("fee", &[0x01, 0x00, 0x00, 0x00, 0x00])],
+        ));
+
+        assert_eq!(meta.fee_pips, None);
+    }
+}
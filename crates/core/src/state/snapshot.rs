@@ -0,0 +1,282 @@
+//! On-disk snapshots of a collector's latest block, to restart warm.
+//!
+//! Simulation states are `dyn ProtocolSim` trait objects and can't be serialized, so like a
+//! [`PairStateSnapshot`](super::pair_snapshot::PairStateSnapshot) a snapshot holds every tracked
+//! component's state the way Tycho last sent it, at the height of the collector's latest block. On
+//! restart the collector decodes it into its first block and seeds its stream's decoder with it,
+//! then decodes only the Tycho messages past the snapshot's height. Tycho's stream still opens
+//! with a full snapshot, but strategies get states right away instead of waiting for it.
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use color_eyre::eyre::{self, WrapErr as _};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tracing::{debug, warn};
+use tycho_common::models as tycho_models;
+use tycho_simulation::tycho_client::feed::{BlockHeader as TychoBlockHeader, FeedMessage};
+
+use super::{
+    PoolId,
+    pair_snapshot::{PoolSnapshot, TychoStates, snapshot_message},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockSnapshot {
+    pub chain: tycho_models::Chain,
+    /// Header of the block the states are at
+    pub header: TychoBlockHeader,
+    pub pools: HashMap<PoolId, PoolSnapshot>,
+}
+
+impl BlockSnapshot {
+    /// Snapshot of the states `tycho_states` followed, `None` before any message was applied.
+    pub fn from_tycho_states(
+        chain: tycho_models::Chain,
+        tycho_states: &TychoStates,
+    ) -> Option<Self> {
+        Some(Self {
+            chain,
+            header: tycho_states.header.clone()?,
+            pools: tycho_states.pools.clone(),
+        })
+    }
+
+    pub fn height(&self) -> u64 {
+        self.header.number
+    }
+
+    /// Default location of a chain's snapshot inside `dir`.
+    pub fn path_in(dir: &Path, chain: tycho_models::Chain) -> PathBuf {
+        dir.join(format!("{chain}.snapshot.json"))
+    }
+
+    /// The snapshot as a single Tycho message, which decodes into a block holding every pool.
+    pub fn to_message(&self) -> FeedMessage<TychoBlockHeader> {
+        snapshot_message(&self.header, self.pools.clone())
+    }
+
+    /// Tycho states to keep following from this snapshot on.
+    pub fn to_tycho_states(&self) -> TychoStates {
+        TychoStates {
+            header: Some(self.header.clone()),
+            pools: self.pools.clone(),
+        }
+    }
+
+    /// Writes the snapshot to `path`, replacing any previous one atomically.
+    ///
+    /// Saves to the same `path` must not overlap, as they share a temporary file.
+    pub async fn save(&self, path: &Path) -> eyre::Result<()> {
+        let json = serde_json::to_vec(self).wrap_err("failed to serialize block snapshot")?;
+
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .wrap_err_with(|| format!("failed to create snapshot dir {}", dir.display()))?;
+        }
+        let tmp_path = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, json)
+            .await
+            .wrap_err_with(|| format!("failed to write {}", tmp_path.display()))?;
+        tokio::fs::rename(&tmp_path, path)
+            .await
+            .wrap_err_with(|| format!("failed to move snapshot into {}", path.display()))?;
+
+        Ok(())
+    }
+
+    /// Reads the snapshot at `path`, `None` if there isn't one yet.
+    pub async fn load(path: &Path) -> eyre::Result<Option<Self>> {
+        let json = match tokio::fs::read(path).await {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).wrap_err_with(|| format!("failed to read {}", path.display()));
+            }
+        };
+
+        serde_json::from_slice(&json)
+            .map(Some)
+            .wrap_err_with(|| format!("failed to parse block snapshot {}", path.display()))
+    }
+}
+
+/// Saves a collector's snapshots one at a time from a single task, skipping to the latest one
+/// when several were taken during a save.
+#[derive(Debug)]
+pub struct SnapshotWriter {
+    snapshot_tx: watch::Sender<Option<Arc<BlockSnapshot>>>,
+}
+
+impl SnapshotWriter {
+    /// Spawns the task saving to `path`, which stops once the writer is dropped.
+    pub fn spawn(path: PathBuf) -> Self {
+        let (snapshot_tx, mut snapshot_rx) = watch::channel(None);
+        tokio::spawn(async move {
+            while snapshot_rx.changed().await.is_ok() {
+                let Some(snapshot) = snapshot_rx.borrow_and_update().clone() else {
+                    continue;
+                };
+                match snapshot.save(&path).await {
+                    Ok(()) => debug!(block.height = snapshot.height(), "Saved block snapshot"),
+                    Err(e) => {
+                        warn!(path = %path.display(), err = %e, "Failed to save block snapshot");
+                    }
+                }
+            }
+        });
+
+        Self { snapshot_tx }
+    }
+
+    /// Queues `snapshot` to be saved, replacing any queued one not being saved yet.
+    pub fn write(&self, snapshot: BlockSnapshot) {
+        self.snapshot_tx.send_replace(Some(Arc::new(snapshot)));
+    }
+}
+
+/// Picks the Tycho messages to decode on a stream resumed from a snapshot its decoder was seeded
+/// with: only those past the snapshot's height.
+#[derive(Debug)]
+pub struct Resume {
+    snapshot: Arc<BlockSnapshot>,
+    first: bool,
+    caught_up: bool,
+}
+
+impl Resume {
+    pub fn new(snapshot: Arc<BlockSnapshot>) -> Self {
+        Self {
+            snapshot,
+            first: true,
+            caught_up: false,
+        }
+    }
+
+    /// Whether `message` is past the snapshot and must be decoded.
+    ///
+    /// The stream's first message is Tycho's full snapshot. When it's past the snapshot, the
+    /// components it doesn't hold anymore are added to its removals: Tycho stopped tracking them
+    /// while the collector was down.
+    pub fn admit(&mut self, message: &mut FeedMessage<TychoBlockHeader>) -> bool {
+        if self.caught_up {
+            return true;
+        }
+        let first = std::mem::replace(&mut self.first, false);
+
+        let height = message
+            .state_msgs
+            .values()
+            .map(|state_msg| state_msg.header.number)
+            .max();
+        if height.is_some_and(|height| height <= self.snapshot.height()) {
+            debug!(block.height = ?height, "Skipped tycho message already in the block snapshot");
+            return false;
+        }
+
+        if first {
+            let removed = self.remove_untracked(message);
+            debug!(removed, "Removed snapshot pools tycho no longer tracks");
+        }
+        self.caught_up = true;
+        true
+    }
+
+    /// Adds the snapshot's pools missing from `message`'s full snapshot to its removals,
+    /// returning how many there were.
+    fn remove_untracked(&self, message: &mut FeedMessage<TychoBlockHeader>) -> usize {
+        let mut removed = 0;
+        for (id, pool) in &self.snapshot.pools {
+            // pools of protocols missing from the message aren't known to be removed
+            let Some(state_msg) = message.state_msgs.get_mut(&pool.protocol_system) else {
+                continue;
+            };
+            if !state_msg.snapshots.states.contains_key(id.as_ref()) {
+                state_msg
+                    .removed_components
+                    .insert(id.as_ref().to_string(), pool.state.component.clone());
+                removed += 1;
+            }
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tycho_simulation::tycho_client::feed::synchronizer::{Snapshot, StateSyncMessage};
+
+    use super::*;
+
+    fn header(number: u64) -> TychoBlockHeader {
+        TychoBlockHeader {
+            number,
+            ..Default::default()
+        }
+    }
+
+    fn message(number: u64) -> FeedMessage<TychoBlockHeader> {
+        FeedMessage {
+            state_msgs: HashMap::from([(
+                "uniswap_v2".to_string(),
+                StateSyncMessage {
+                    header: header(number),
+                    snapshots: Snapshot {
+                        states: HashMap::new(),
+                        vm_storage: HashMap::new(),
+                    },
+                    deltas: None,
+                    removed_components: HashMap::new(),
+                },
+            )]),
+            sync_states: HashMap::new(),
+        }
+    }
+
+    fn snapshot(number: u64) -> BlockSnapshot {
+        BlockSnapshot {
+            chain: tycho_models::Chain::Ethereum,
+            header: header(number),
+            pools: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn snapshot_roundtrips_through_disk() {
+        let dir = std::env::temp_dir().join(format!("kuma-snapshot-{}", std::process::id()));
+        let path = BlockSnapshot::path_in(&dir, tycho_models::Chain::Ethereum);
+
+        assert!(BlockSnapshot::load(&path).await.unwrap().is_none());
+        snapshot(42).save(&path).await.unwrap();
+        let loaded = BlockSnapshot::load(&path).await.unwrap().unwrap();
+        assert_eq!(loaded.chain, tycho_models::Chain::Ethereum);
+        assert_eq!(loaded.height(), 42);
+        assert!(loaded.pools.is_empty());
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[test]
+    fn resume_decodes_only_messages_past_the_snapshot() {
+        let mut resume = Resume::new(Arc::new(snapshot(100)));
+
+        assert!(!resume.admit(&mut message(98)));
+        assert!(!resume.admit(&mut message(100)));
+        assert!(resume.admit(&mut message(101)));
+        // a reorg below the snapshot once caught up is Tycho's to revert
+        assert!(resume.admit(&mut message(99)));
+    }
+
+    #[test]
+    fn tycho_states_resume_at_the_snapshot() {
+        let states = snapshot(7).to_tycho_states();
+        assert_eq!(states.block_height(), Some(7));
+
+        let again = BlockSnapshot::from_tycho_states(tycho_models::Chain::Ethereum, &states);
+        assert_eq!(again.map(|snapshot| snapshot.height()), Some(7));
+    }
+}
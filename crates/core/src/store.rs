@@ -0,0 +1,153 @@
+//! Sinks for the spot prices and signals produced by strategies.
+//!
+//! The daemon only needs to hand off what it observed, so it writes through these traits instead
+//! of the Postgres repositories. Light deployments can swap the database for JSON lines files or
+//! drop the data altogether.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::{self, WrapErr as _};
+use futures::{FutureExt as _, future::BoxFuture};
+use serde::Serialize;
+use tokio::{fs::OpenOptions, io::AsyncWriteExt as _, sync::Mutex};
+
+use crate::{
+    database::{self, Write},
+    signals::CrossChainSingleHop,
+    spot_prices::SpotPrices,
+};
+
+pub trait SpotPriceStore: Send + Sync {
+    fn insert_spot_prices(&self, spot_prices: Vec<SpotPrices>) -> BoxFuture<'_, eyre::Result<()>>;
+}
+
+pub trait SignalStore: Send + Sync {
+    /// Stores a signal along with the spot prices of its blocks, which may be empty. Stores that
+    /// support it write both atomically.
+    fn insert_signal(
+        &self,
+        signal: CrossChainSingleHop,
+        spot_prices: Vec<SpotPrices>,
+    ) -> BoxFuture<'_, eyre::Result<()>>;
+}
+
+/// Everything a strategy worker writes to.
+pub trait Store: SpotPriceStore + SignalStore {}
+
+impl<T: SpotPriceStore + SignalStore> Store for T {}
+
+/// Writes to Postgres, through the write buffer if one is configured.
+impl SpotPriceStore for database::Handle {
+    fn insert_spot_prices(&self, spot_prices: Vec<SpotPrices>) -> BoxFuture<'_, eyre::Result<()>> {
+        self.write(Write::SpotPrices(spot_prices)).boxed()
+    }
+}
+
+impl SignalStore for database::Handle {
+    fn insert_signal(
+        &self,
+        signal: CrossChainSingleHop,
+        spot_prices: Vec<SpotPrices>,
+    ) -> BoxFuture<'_, eyre::Result<()>> {
+        let write = if spot_prices.is_empty() {
+            Write::Signal(signal)
+        } else {
+            Write::SignalWithSpotPrices {
+                signal,
+                spot_prices,
+            }
+        };
+        self.write(write).boxed()
+    }
+}
+
+/// Discards everything written to it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopStore;
+
+impl SpotPriceStore for NoopStore {
+    fn insert_spot_prices(&self, _: Vec<SpotPrices>) -> BoxFuture<'_, eyre::Result<()>> {
+        futures::future::ok(()).boxed()
+    }
+}
+
+impl SignalStore for NoopStore {
+    fn insert_signal(
+        &self,
+        _: CrossChainSingleHop,
+        _: Vec<SpotPrices>,
+    ) -> BoxFuture<'_, eyre::Result<()>> {
+        futures::future::ok(()).boxed()
+    }
+}
+
+/// Appends spot prices and signals as JSON lines to `spot_prices.jsonl` and `signals.jsonl`.
+#[derive(Debug)]
+pub struct FileStore {
+    dir: PathBuf,
+    /// Keeps concurrent writes from interleaving
+    lock: Mutex<()>,
+}
+
+impl FileStore {
+    /// Creates `dir` if it doesn't exist yet.
+    pub fn new(dir: PathBuf) -> eyre::Result<Self> {
+        fs::create_dir_all(&dir)
+            .wrap_err_with(|| format!("failed to create store directory {}", dir.display()))?;
+
+        Ok(Self {
+            dir,
+            lock: Mutex::new(()),
+        })
+    }
+
+    async fn append<T: Serialize>(&self, file: &str, records: &[T]) -> eyre::Result<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let mut lines = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut lines, record).wrap_err("failed to serialize record")?;
+            lines.push(b'\n');
+        }
+
+        let path = self.dir.join(file);
+        let _guard = self.lock.lock().await;
+        append_to(&path, &lines)
+            .await
+            .wrap_err_with(|| format!("failed to append to {}", path.display()))
+    }
+}
+
+async fn append_to(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(bytes).await?;
+    file.flush().await
+}
+
+impl SpotPriceStore for FileStore {
+    fn insert_spot_prices(&self, spot_prices: Vec<SpotPrices>) -> BoxFuture<'_, eyre::Result<()>> {
+        async move { self.append("spot_prices.jsonl", &spot_prices).await }.boxed()
+    }
+}
+
+impl SignalStore for FileStore {
+    fn insert_signal(
+        &self,
+        signal: CrossChainSingleHop,
+        spot_prices: Vec<SpotPrices>,
+    ) -> BoxFuture<'_, eyre::Result<()>> {
+        async move {
+            self.append("spot_prices.jsonl", &spot_prices).await?;
+            self.append("signals.jsonl", &[signal]).await
+        }
+        .boxed()
+    }
+}
@@ -1,9 +1,10 @@
 use std::str::FromStr as _;
 
-use color_eyre::eyre::{self, OptionExt};
+use color_eyre::eyre::{self, OptionExt as _, WrapErr as _, eyre};
 
 use crate::{
-    config::{Config, InventoriesForChain},
+    chain::Chain,
+    config::{Config, InventoriesForChain, PairForChain},
     strategy::CrossChainSingleHop,
 };
 
@@ -34,24 +35,15 @@ impl Builder {
         //  get the pairs for the chains from strategy config
         let chain_pairs = Config::get_chain_pairs(&token_a, &token_b, &inventory);
         //  initialize pair and chain info
-        let (slow_chain, fast_chain) = (
-            chain_pairs
-                .keys()
-                .find(|chain| {
-                    chain.name
-                        == tycho_common::models::Chain::from_str(&slow_chain_name)
-                            .expect("invalid slow chain name: {slow_chain_name}")
-                })
-                .ok_or_eyre("invalid slow chain name")?,
-            chain_pairs
-                .keys()
-                .find(|chain| {
-                    chain.name
-                        == tycho_common::models::Chain::from_str(&fast_chain_name)
-                            .expect("invalid fast chain name: {fast_chain_name}")
-                })
-                .ok_or_eyre("invalid fast chain name")?,
-        );
+        let slow_chain = find_chain(&chain_pairs, &slow_chain_name)
+            .wrap_err_with(|| format!("invalid slow chain {slow_chain_name}"))?;
+        let fast_chain = find_chain(&chain_pairs, &fast_chain_name)
+            .wrap_err_with(|| format!("invalid fast chain {fast_chain_name}"))?;
+        if slow_chain == fast_chain {
+            return Err(eyre!(
+                "slow and fast chain must differ, both are {slow_chain_name}"
+            ));
+        }
         let (slow_pair, fast_pair) = (&chain_pairs[&slow_chain], &chain_pairs[&fast_chain]);
 
         // get inventory
@@ -74,6 +66,18 @@ impl Builder {
             binary_search_steps,
             max_slippage_bps,
             congestion_risk_discount_bps,
+            slow_spot_prices: Default::default(),
+            fast_spot_prices: Default::default(),
         })
     }
 }
+
+/// Looks up a configured chain by its Tycho name.
+fn find_chain<'a>(chain_pairs: &'a PairForChain, name: &str) -> eyre::Result<&'a Chain> {
+    let name = tycho_common::models::Chain::from_str(name)
+        .map_err(|e| eyre!("unknown chain name {name}: {e}"))?;
+    chain_pairs
+        .keys()
+        .find(|chain| chain.name == name)
+        .ok_or_eyre("chain is not configured or doesn't list both tokens of the pair")
+}
@@ -2,7 +2,8 @@ use std::sync::Arc;
 
 use color_eyre::eyre::{self, Context, eyre};
 use num_bigint::BigUint;
-use tracing::{debug, instrument, trace};
+use rayon::iter::{IntoParallelRefIterator as _, ParallelIterator as _};
+use tracing::{Span, debug, instrument, trace};
 use tycho_common::simulation::protocol_sim::ProtocolSim;
 use tycho_simulation::protocol::models::ProtocolComponent;
 
@@ -13,15 +14,17 @@ use crate::{
         self, PoolId,
         pair::{Pair, PairState},
     },
-    strategy::simulation::make_sorted_spot_prices,
 };
 
 mod builder;
 mod precompute;
 mod simulation;
 pub use builder::Builder;
-pub use precompute::Precomputes;
-pub use simulation::Swap;
+pub use precompute::{PrecomputeCurve, Precomputes};
+pub use simulation::{SpotPriceCache, Swap};
+
+/// Most pairs of crossed pools a signal search evaluates, in parallel on rayon's pool
+const MAX_CROSSED_CANDIDATES: usize = 4;
 
 // Implementation of the arbitrage strategy
 // TODO: should this and precompute be different types or should this just populate
@@ -37,14 +40,19 @@ pub struct CrossChainSingleHop {
     pub binary_search_steps: usize,
     pub max_slippage_bps: u64,
     pub congestion_risk_discount_bps: u64,
+    /// Spot prices of the latest slow and fast states, reused for the pools the next block leaves
+    /// unmodified
+    pub slow_spot_prices: SpotPriceCache,
+    pub fast_spot_prices: SpotPriceCache,
 }
 
 impl CrossChainSingleHop {
     #[instrument(skip_all)]
-    pub fn precompute(&self, slow_state: PairState) -> Precomputes {
+    pub fn precompute(&self, slow_state: &PairState) -> Precomputes {
         Precomputes::from_pair_state(
-            &slow_state,
+            slow_state,
             &self.slow_pair,
+            self.slow_spot_prices.sorted(slow_state, &self.slow_pair),
             &self.slow_inventory,
             None,
             self.binary_search_steps,
@@ -62,10 +70,10 @@ impl CrossChainSingleHop {
     pub fn generate_signal(
         &self,
         precompute: &Precomputes,
-        fast_state: PairState,
+        fast_state: &PairState,
     ) -> eyre::Result<signals::CrossChainSingleHop> {
         // 1. find the first pair of crossing pools from precompute & fast_state
-        let fast_sorted_spot_prices = make_sorted_spot_prices(&fast_state, &self.fast_pair);
+        let fast_sorted_spot_prices = self.fast_spot_prices.sorted(fast_state, &self.fast_pair);
         if fast_sorted_spot_prices.is_empty() {
             return Err(eyre::eyre!("No spot prices found for fast chain"));
         } else {
@@ -81,81 +89,122 @@ impl CrossChainSingleHop {
         // db.write(precompute.spot_prices[0])
         // db.write(precompute.spot_prices[precompute.spot_prices.len() - 1])
 
-        if let Some((slow_id, fast_id, direction)) =
-            find_first_crossed_pools(&precompute.sorted_spot_prices, &fast_sorted_spot_prices).map(
-                |(slow_id, slow_price, fast_id, fast_price)| {
-                    let spread = slow_price - fast_price;
-                    let slow_direction = if spread > 0.0 {
-                        Direction::AtoB
-                    } else {
-                        Direction::BtoA
-                    };
-                    debug!(
-                        %slow_direction,
-                        %spread,
-                        %slow_price,
-                        %fast_price,
-                        %slow_id,
-                        %fast_id,
-                        "found crossed pools"
-                    );
+        let candidates: Vec<_> = self
+            .find_crossed_pools(&precompute.sorted_spot_prices, &fast_sorted_spot_prices)
+            .into_iter()
+            .map(|(slow_id, slow_price, fast_id, fast_price)| {
+                let spread = slow_price - fast_price;
+                let slow_direction = if spread > 0.0 {
+                    Direction::AtoB
+                } else {
+                    Direction::BtoA
+                };
+                debug!(
+                    %slow_direction,
+                    %spread,
+                    %slow_price,
+                    %fast_price,
+                    %slow_id,
+                    %fast_id,
+                    "found crossed pools"
+                );
+
+                (slow_id, fast_id, slow_direction)
+            })
+            .collect();
+        if candidates.is_empty() {
+            return Err(eyre!(
+                "no crossing pools found for A->B (slow) and B->A (fast)"
+            ));
+        }
+
+        // each candidate's search simulates on its own fast pool, so they run in parallel and the
+        // fast chain's block time isn't spent waiting on one core
+        let signals: Vec<_> = if let [candidate] = candidates.as_slice() {
+            vec![self.evaluate_crossed_pools(precompute, fast_state, candidate)]
+        } else {
+            let span = Span::current();
+            candidates
+                .par_iter()
+                .map(|candidate| {
+                    span.in_scope(|| self.evaluate_crossed_pools(precompute, fast_state, candidate))
+                })
+                .collect()
+        };
 
-                    (slow_id, fast_id, slow_direction)
-                },
+        most_profitable(signals.into_iter().flatten()).ok_or_else(|| {
+            eyre!(
+                "no optimal signal found for any of {} crossed pools",
+                candidates.len()
             )
-        {
-            match direction {
-                Direction::AtoB => {
-                    if let Some(signal) = self.find_optimal_signal(
-                        &precompute.pool_sims[&slow_id].a_to_b,
-                        precompute.pool_metadata[&slow_id].clone(),
-                        &slow_id,
-                        precompute.block_height,
-                        fast_state.states[&fast_id].as_ref(),
-                        fast_state.metadata[&fast_id].clone(),
-                        &fast_id,
-                        fast_state.block_height,
-                        &self.fast_inventory.1,
-                    ) {
-                        trace!(
-                            slow_sim = %signal.slow_swap_sim,
-                            fast_sim = %signal.fast_swap_sim,
-                            signal.surplus = ?signal.surplus,
-                            signal.expected_profit = ?signal.expected_profit,
-                            "found optimal swap for A->B (slow) and B->A (fast)"
-                        );
-                        Ok(signal)
-                    } else {
-                        Err(eyre!(
-                            "no optimal signal found for A->B (slow) and B->A (fast)"
-                        ))
-                    }
+        })
+    }
+
+    /// Searches the optimal signal of one pair of crossed pools, trading `direction` on the slow
+    /// chain.
+    fn evaluate_crossed_pools(
+        &self,
+        precompute: &Precomputes,
+        fast_state: &PairState,
+        (slow_id, fast_id, direction): &(PoolId, PoolId, Direction),
+    ) -> Option<signals::CrossChainSingleHop> {
+        match direction {
+            Direction::AtoB => {
+                let signal = self.find_optimal_signal(
+                    &precompute.pool_sims.get(slow_id)?.a_to_b,
+                    precompute.pool_metadata[slow_id].clone(),
+                    slow_id,
+                    precompute.block_height,
+                    fast_state.states[fast_id].as_ref(),
+                    fast_state.metadata[fast_id].clone(),
+                    fast_id,
+                    fast_state.block_height,
+                    &self.fast_inventory.1,
+                );
+                match &signal {
+                    Some(signal) => trace!(
+                        slow_sim = %signal.slow_swap_sim,
+                        fast_sim = %signal.fast_swap_sim,
+                        signal.surplus = ?signal.surplus,
+                        signal.expected_profit = ?signal.expected_profit,
+                        "found optimal swap for A->B (slow) and B->A (fast)"
+                    ),
+                    None => debug!(
+                        %slow_id,
+                        %fast_id,
+                        "no optimal signal found for A->B (slow) and B->A (fast)"
+                    ),
                 }
-                Direction::BtoA => {
-                    if let Some(signal) = self.find_optimal_signal(
-                        &precompute.pool_sims[&slow_id].b_to_a,
-                        precompute.pool_metadata[&slow_id].clone(),
-                        &slow_id,
-                        precompute.block_height,
-                        fast_state.states[&fast_id].as_ref(),
-                        fast_state.metadata[&fast_id].clone(),
-                        &fast_id,
-                        fast_state.block_height,
-                        &self.fast_inventory.0,
-                    ) {
-                        trace!(slow_sim = %signal.slow_swap_sim, fast_sim = %signal.fast_swap_sim, signal.surplus = ?signal.surplus, signal.expected_profit = ?signal.expected_profit, "found optimal swap for B->A (slow) and A->B (fast)");
-                        Ok(signal)
-                    } else {
-                        Err(eyre!(
-                            "no optimal signal found for B->A (slow) and A->B (fast)"
-                        ))
-                    }
+                signal
+            }
+            Direction::BtoA => {
+                let signal = self.find_optimal_signal(
+                    &precompute.pool_sims.get(slow_id)?.b_to_a,
+                    precompute.pool_metadata[slow_id].clone(),
+                    slow_id,
+                    precompute.block_height,
+                    fast_state.states[fast_id].as_ref(),
+                    fast_state.metadata[fast_id].clone(),
+                    fast_id,
+                    fast_state.block_height,
+                    &self.fast_inventory.0,
+                );
+                match &signal {
+                    Some(signal) => trace!(
+                        slow_sim = %signal.slow_swap_sim,
+                        fast_sim = %signal.fast_swap_sim,
+                        signal.surplus = ?signal.surplus,
+                        signal.expected_profit = ?signal.expected_profit,
+                        "found optimal swap for B->A (slow) and A->B (fast)"
+                    ),
+                    None => debug!(
+                        %slow_id,
+                        %fast_id,
+                        "no optimal signal found for B->A (slow) and A->B (fast)"
+                    ),
                 }
+                signal
             }
-        } else {
-            Err(eyre!(
-                "no crossing pools found for A->B (slow) and B->A (fast)"
-            ))
         }
     }
 
@@ -191,8 +240,10 @@ impl CrossChainSingleHop {
         let (mut left, mut right) = (0, slow_sims.len() - 1);
 
         let mut best_signal: Option<signals::CrossChainSingleHop> = None;
+        let mut iterations = 0u32;
 
         while left < right {
+            iterations += 1;
             let mid = (right + left) / 2;
 
             // make sims for mid
@@ -267,7 +318,13 @@ impl CrossChainSingleHop {
             }
         }
 
-        trace!(index = %left, found_signal = %best_signal.is_some(), "search complete");
+        trace!(index = %left, iterations, found_signal = %best_signal.is_some(), "search complete");
+        metrics::histogram!(
+            "kuma_strategy_search_iterations",
+            "slow_chain" => self.slow_chain.to_string(),
+            "fast_chain" => self.fast_chain.to_string()
+        )
+        .record(iterations);
 
         best_signal
     }
@@ -277,12 +334,14 @@ impl CrossChainSingleHop {
         &self,
         precompute: simulation::Swap,
         fast_state: &dyn ProtocolSim,
+        fast_pool_id: &PoolId,
         fast_inventory: &BigUint,
         max_slippage_bps: u64,
     ) -> eyre::Result<simulation::Swap> {
         let amount_in = bps_discount(&precompute.amount_out, max_slippage_bps);
 
         if fast_inventory < &amount_in {
+            record_simulation_failure(fast_pool_id, "insufficient_inventory");
             return Err(eyre::eyre!("fast inventory is insufficient"));
         }
 
@@ -297,6 +356,7 @@ impl CrossChainSingleHop {
         };
 
         Swap::from_protocol_sim(&amount_in, &token_in, &token_out, fast_state)
+            .inspect_err(|_| record_simulation_failure(fast_pool_id, "simulation"))
             .wrap_err("swap simulation failed")
     }
 
@@ -315,6 +375,7 @@ impl CrossChainSingleHop {
         let fast_sim = match self.swap_from_precompute(
             slow_sim.clone(),
             fast_state,
+            fast_pool_id,
             fast_inventory,
             self.max_slippage_bps,
         ) {
@@ -348,45 +409,92 @@ impl CrossChainSingleHop {
             err
         })
     }
-}
 
-/// Finds the pair of pools with the biggest difference in spot prices based
-/// on the provided direction. The direction denotes the trade direction on the
-/// slow chain.
-///
-/// slow_prices contain the A -> B prices on the slow chain, sorted from lowest to highest.
-/// fast_prices contain the A -> B prices on the fast chain, sorted from lowest to highest.
-///
-/// # Returns
-/// A tuple of pool IDs (slow_id, fast_id, spread) denoting the pool IDs corresponding to the
-/// slow and fast chains respectively, and the spread between the two prices.
-#[instrument]
-fn find_first_crossed_pools(
-    sorted_slow_prices: &[(state::PoolId, f64)],
-    sorted_fast_prices: &[(state::PoolId, f64)],
-) -> Option<(state::PoolId, f64, state::PoolId, f64)> {
-    if sorted_slow_prices.is_empty() || sorted_fast_prices.is_empty() {
-        return None;
-    }
-    // need to find the max spread
-    // because the spot prices are sorted, we can start from the highest slow price
-    // and the lowest fast price, iterating backwards over slow prices and forwards over fast prices:
-    // slow:   [1, 2, 3]
-    // spread:  ↱ =2  ↲  <- highest spread
-    // fast:   [1, 2, 3]
-    sorted_slow_prices
-        .iter()
-        .rev()
-        .find_map(|(slow_id, slow_price)| {
-            sorted_fast_prices.iter().find_map(|(fast_id, fast_price)| {
-                let spread = slow_price - fast_price;
-                if spread.abs() > 0.0 {
-                    Some((slow_id.clone(), *slow_price, fast_id.clone(), *fast_price))
-                } else {
-                    None
+    /// Finds the pairs of pools with the biggest differences in spot prices, at most
+    /// [`MAX_CROSSED_CANDIDATES`] of them, widest spread first. The sign of a spread denotes the
+    /// trade direction on the slow chain.
+    ///
+    /// slow_prices contain the A -> B prices on the slow chain, sorted from lowest to highest.
+    /// fast_prices contain the A -> B prices on the fast chain, sorted from lowest to highest.
+    ///
+    /// # Returns
+    /// Tuples of pool IDs and prices (slow_id, slow_price, fast_id, fast_price) of the slow and
+    /// fast chains respectively.
+    #[instrument(skip(self))]
+    fn find_crossed_pools(
+        &self,
+        sorted_slow_prices: &[(state::PoolId, f64)],
+        sorted_fast_prices: &[(state::PoolId, f64)],
+    ) -> Vec<(state::PoolId, f64, state::PoolId, f64)> {
+        // because the spot prices are sorted, the widest spreads are between the ends of both:
+        // slow:   [1, 2, 3]
+        // spread:  ↱ =2  ↲  <- highest spread
+        // fast:   [1, 2, 3]
+        // so only the pools within the candidate limit of either end are paired
+        let ends = |prices: &[(state::PoolId, f64)]| {
+            let len = prices.len();
+            let mut idxs: Vec<usize> = (0..len.min(MAX_CROSSED_CANDIDATES))
+                .chain(len.saturating_sub(MAX_CROSSED_CANDIDATES)..len)
+                .collect();
+            idxs.sort_unstable();
+            idxs.dedup();
+            idxs
+        };
+
+        let mut candidates = 0u32;
+        let mut crossed = Vec::new();
+        for slow_idx in ends(sorted_slow_prices) {
+            let (slow_id, slow_price) = &sorted_slow_prices[slow_idx];
+            for fast_idx in ends(sorted_fast_prices) {
+                let (fast_id, fast_price) = &sorted_fast_prices[fast_idx];
+                candidates += 1;
+                if (slow_price - fast_price).abs() > 0.0 {
+                    crossed.push((slow_id.clone(), *slow_price, fast_id.clone(), *fast_price));
                 }
-            })
+            }
+        }
+        crossed.sort_by(|(_, slow_a, _, fast_a), (_, slow_b, _, fast_b)| {
+            (slow_b - fast_b).abs().total_cmp(&(slow_a - fast_a).abs())
+        });
+        crossed.truncate(MAX_CROSSED_CANDIDATES);
+
+        metrics::histogram!(
+            "kuma_strategy_crossed_pool_candidates",
+            "slow_chain" => self.slow_chain.to_string(),
+            "fast_chain" => self.fast_chain.to_string()
+        )
+        .record(candidates);
+
+        crossed
+    }
+}
+
+/// The signal with the highest expected profit valued in token A, whichever direction it trades.
+/// On equal profits keeps the earliest, i.e. the candidate with the wider spread.
+fn most_profitable(
+    signals: impl IntoIterator<Item = signals::CrossChainSingleHop>,
+) -> Option<signals::CrossChainSingleHop> {
+    signals
+        .into_iter()
+        .map(|signal| (signal.expected_profit_in_a(), signal))
+        .reduce(|best, candidate| {
+            if candidate.0 > best.0 {
+                candidate
+            } else {
+                best
+            }
         })
+        .map(|(_, signal)| signal)
+}
+
+/// Counts a fast chain swap the search couldn't simulate, by pool and why.
+fn record_simulation_failure(pool_id: &PoolId, reason: &'static str) {
+    metrics::counter!(
+        "kuma_strategy_simulation_failures_total",
+        "pool" => pool_id.to_string(),
+        "reason" => reason
+    )
+    .increment(1);
 }
 
 #[cfg(test)]
@@ -554,6 +662,7 @@ mod tests {
             block_height,
             modified_pools: Arc::new(HashSet::from([state::PoolId::from(pool_id)])),
             unmodified_pools: Arc::new(HashSet::new()),
+            removed_pools: Arc::new(HashSet::new()),
             metadata: HashMap::from([(
                 state::PoolId::from(pool_id),
                 Arc::new(ProtocolComponent::new(
@@ -568,6 +677,12 @@ mod tests {
                     NaiveDateTime::default(),
                 )),
             )]),
+            pool_meta: HashMap::new(),
+            native_balance: None,
+            finality: Default::default(),
+            timestamp: None,
+            base_fee_per_gas: None,
+            received_at: std::time::Instant::now(),
         }
     }
 
@@ -612,6 +727,8 @@ mod tests {
             fast_inventory: available_inventory_fast,
             max_slippage_bps: 25, // 0.25%
             congestion_risk_discount_bps: 25,
+            slow_spot_prices: Default::default(),
+            fast_spot_prices: Default::default(),
             // min_profit_threshold: 0.5, // 0.5%
             binary_search_steps: 16,
         })
@@ -646,6 +763,8 @@ mod tests {
             fast_inventory: available_inventory_fast,
             max_slippage_bps: 25, // 0.25%
             congestion_risk_discount_bps: 25,
+            slow_spot_prices: Default::default(),
+            fast_spot_prices: Default::default(),
             // min_profit_threshold: 0.5, // 0.5%
             binary_search_steps: 16,
         })
@@ -669,7 +788,7 @@ mod tests {
         );
 
         // Act
-        let precompute = strategy.precompute(slow_state.clone());
+        let precompute = strategy.precompute(&slow_state);
         assert_eq!(precompute.block_height, 0);
 
         // Assert
@@ -740,7 +859,7 @@ mod tests {
         );
 
         // Act
-        let precompute = strategy.precompute(slow_state.clone());
+        let precompute = strategy.precompute(&slow_state);
         assert_eq!(precompute.block_height, 0);
 
         // Assert
@@ -815,10 +934,8 @@ mod tests {
             tycho_common::models::Chain::Base,
         );
 
-        let precompute = strategy.precompute(slow_state);
-        let signal = strategy
-            .generate_signal(&precompute, fast_state.clone())
-            .unwrap();
+        let precompute = strategy.precompute(&slow_state);
+        let signal = strategy.generate_signal(&precompute, &fast_state).unwrap();
 
         assert_eq!(signal.slow_pool_id, state::PoolId::from("0x123"));
         assert_eq!(signal.fast_pool_id, state::PoolId::from("0x456"));
@@ -876,6 +993,196 @@ mod tests {
         )
     }
 
+    #[test]
+    fn generate_signal_picks_most_profitable_crossed_pools() {
+        let strategy = make_same_decimals_strategy();
+
+        let slow_state = make_single_univ2_pair_state(
+            &strategy.slow_pair,
+            2000,
+            "0x123",
+            10_000,
+            5_000,
+            tycho_common::models::Chain::Ethereum,
+        );
+
+        let mut fast_state = make_single_univ2_pair_state(
+            &strategy.fast_pair,
+            100,
+            "0x456",
+            10_000,
+            2_000,
+            tycho_common::models::Chain::Base,
+        );
+        let narrower = make_single_univ2_pair_state(
+            &strategy.fast_pair,
+            100,
+            "0x789",
+            10_000,
+            4_000,
+            tycho_common::models::Chain::Base,
+        );
+        fast_state.states.extend(narrower.states);
+        fast_state.metadata.extend(narrower.metadata);
+
+        let precompute = strategy.precompute(&slow_state);
+        let signal = strategy.generate_signal(&precompute, &fast_state).unwrap();
+
+        assert_eq!(signal.slow_pool_id, state::PoolId::from("0x123"));
+        assert_eq!(signal.fast_pool_id, state::PoolId::from("0x456"));
+
+        let narrower_signal = strategy
+            .evaluate_crossed_pools(
+                &precompute,
+                &fast_state,
+                &(
+                    state::PoolId::from("0x123"),
+                    state::PoolId::from("0x789"),
+                    Direction::AtoB,
+                ),
+            )
+            .unwrap();
+        assert!(narrower_signal.expected_profit < signal.expected_profit);
+    }
+
+    #[test]
+    fn crossed_pools_are_ordered_by_spread() {
+        let strategy = make_same_decimals_strategy();
+        let prices = |prices: &[(&str, f64)]| -> Vec<(state::PoolId, f64)> {
+            prices
+                .iter()
+                .map(|(id, price)| (state::PoolId::from(*id), *price))
+                .collect()
+        };
+
+        let crossed = strategy.find_crossed_pools(
+            &prices(&[("0x1", 1.0), ("0x2", 2.0), ("0x3", 3.0)]),
+            &prices(&[("0xa", 1.5), ("0xb", 2.0), ("0xc", 5.0)]),
+        );
+
+        let spreads: Vec<_> = crossed
+            .iter()
+            .map(|(slow_id, slow_price, fast_id, fast_price)| {
+                (
+                    slow_id.to_string(),
+                    fast_id.to_string(),
+                    slow_price - fast_price,
+                )
+            })
+            .collect();
+        assert_eq!(spreads.len(), MAX_CROSSED_CANDIDATES);
+        assert_eq!(spreads[0], ("0x1".to_string(), "0xc".to_string(), -4.0));
+        assert_eq!(spreads[1], ("0x2".to_string(), "0xc".to_string(), -3.0));
+        assert_eq!(spreads[2], ("0x3".to_string(), "0xc".to_string(), -2.0));
+        assert_eq!(spreads[3], ("0x3".to_string(), "0xa".to_string(), 1.5));
+    }
+
+    /// A state of `pair` at `block_height` with a Uniswap V2 pool per `(pool_id, reserve_a,
+    /// reserve_b)` of `pools`, only `modified` of which the block modified
+    fn make_univ2_pair_state(
+        pair: &Pair,
+        block_height: u64,
+        pools: &[(&str, u64, u64)],
+        modified: &[&str],
+    ) -> PairState {
+        let mut state = make_single_univ2_pair_state(
+            pair,
+            block_height,
+            pools[0].0,
+            pools[0].1,
+            pools[0].2,
+            tycho_common::models::Chain::Ethereum,
+        );
+        for (pool_id, reserve_a, reserve_b) in &pools[1..] {
+            let pool = make_single_univ2_pair_state(
+                pair,
+                block_height,
+                pool_id,
+                *reserve_a,
+                *reserve_b,
+                tycho_common::models::Chain::Ethereum,
+            );
+            state.states.extend(pool.states);
+            state.metadata.extend(pool.metadata);
+        }
+        let modified: HashSet<_> = modified.iter().map(|id| state::PoolId::from(*id)).collect();
+        state.unmodified_pools = Arc::new(
+            state
+                .states
+                .keys()
+                .filter(|id| !modified.contains(*id))
+                .cloned()
+                .collect(),
+        );
+        state.modified_pools = Arc::new(modified);
+        state
+    }
+
+    fn pool_ids(sorted: &[(PoolId, f64)]) -> Vec<String> {
+        sorted.iter().map(|(id, _)| id.to_string()).collect()
+    }
+
+    #[test]
+    fn spot_prices_of_unmodified_pools_are_reused() {
+        let pair = Pair::new(make_mainnet_pepe(), make_mainnet_weth());
+        let cache = SpotPriceCache::default();
+
+        let first = make_univ2_pair_state(
+            &pair,
+            1,
+            &[("0x1", 10_000, 5_000), ("0x2", 10_000, 2_000)],
+            &["0x1", "0x2"],
+        );
+        let first_prices = cache.sorted(&first, &pair);
+        assert_eq!(pool_ids(&first_prices), ["0x2", "0x1"]);
+
+        // 0x2 changed without the block marking it modified, so its old price is kept
+        let second = make_univ2_pair_state(
+            &pair,
+            2,
+            &[
+                ("0x1", 10_000, 1_000),
+                ("0x2", 10_000, 9_000),
+                ("0x3", 10_000, 3_000),
+            ],
+            &["0x1", "0x3"],
+        );
+        let second_prices = cache.sorted(&second, &pair);
+        assert_eq!(pool_ids(&second_prices), ["0x1", "0x2", "0x3"]);
+        assert_eq!(second_prices[1], first_prices[0]);
+        assert!(Arc::ptr_eq(&second_prices, &cache.sorted(&second, &pair)));
+
+        // after a gap every pool is priced again
+        let fourth = make_univ2_pair_state(
+            &pair,
+            4,
+            &[("0x1", 10_000, 1_000), ("0x2", 10_000, 9_000)],
+            &[],
+        );
+        assert_eq!(pool_ids(&cache.sorted(&fourth, &pair)), ["0x1", "0x2"]);
+        assert_eq!(
+            *cache.sorted(&fourth, &pair),
+            simulation::make_sorted_spot_prices(&fourth, &pair)
+        );
+    }
+
+    #[test]
+    fn spot_prices_of_removed_pools_are_dropped() {
+        let pair = Pair::new(make_mainnet_pepe(), make_mainnet_weth());
+        let cache = SpotPriceCache::default();
+
+        let first = make_univ2_pair_state(
+            &pair,
+            1,
+            &[("0x1", 10_000, 5_000), ("0x2", 10_000, 2_000)],
+            &["0x1", "0x2"],
+        );
+        cache.sorted(&first, &pair);
+
+        let second = make_univ2_pair_state(&pair, 2, &[("0x1", 10_000, 5_000)], &[]);
+        assert_eq!(pool_ids(&cache.sorted(&second, &pair)), ["0x1"]);
+    }
+
     #[test]
     fn generate_signal_same_decimals_bab() {
         let strategy = make_same_decimals_strategy();
@@ -898,10 +1205,8 @@ mod tests {
             tycho_common::models::Chain::Ethereum,
         );
 
-        let precompute = strategy.precompute(slow_state);
-        let signal = strategy
-            .generate_signal(&precompute, fast_state.clone())
-            .unwrap();
+        let precompute = strategy.precompute(&slow_state);
+        let signal = strategy.generate_signal(&precompute, &fast_state).unwrap();
 
         assert_eq!(signal.slow_pool_id, state::PoolId::from("0x123"));
         assert_eq!(signal.fast_pool_id, state::PoolId::from("0x456"));
@@ -980,10 +1285,8 @@ mod tests {
             tycho_common::models::Chain::Base,
         );
 
-        let precompute = strategy.precompute(slow_state);
-        let signal = strategy
-            .generate_signal(&precompute, fast_state.clone())
-            .unwrap();
+        let precompute = strategy.precompute(&slow_state);
+        let signal = strategy.generate_signal(&precompute, &fast_state).unwrap();
 
         assert_eq!(signal.slow_pool_id, state::PoolId::from("0x123"));
         assert_eq!(signal.fast_pool_id, state::PoolId::from("0x456"));
@@ -1063,10 +1366,8 @@ mod tests {
             tycho_common::models::Chain::Base,
         );
 
-        let precompute = strategy.precompute(slow_state);
-        let signal = strategy
-            .generate_signal(&precompute, fast_state.clone())
-            .unwrap();
+        let precompute = strategy.precompute(&slow_state);
+        let signal = strategy.generate_signal(&precompute, &fast_state).unwrap();
 
         assert_eq!(signal.slow_pool_id, state::PoolId::from("0x123"));
         assert_eq!(signal.fast_pool_id, state::PoolId::from("0x456"));
@@ -1123,4 +1424,61 @@ mod tests {
             .unwrap()
         )
     }
+
+    fn swap(token_in: Token, amount_in: u64, token_out: Token, amount_out: u64) -> Swap {
+        Swap {
+            token_in,
+            amount_in: BigUint::from(amount_in),
+            token_out,
+            amount_out: BigUint::from(amount_out),
+            gas_cost: BigUint::default(),
+        }
+    }
+
+    /// A signal of the same decimals strategy, expecting the swaps' whole surpluses as profit
+    fn signal_from_swaps(slow: Swap, fast: Swap) -> signals::CrossChainSingleHop {
+        let strategy = make_same_decimals_strategy();
+        let surplus = calculate_surplus(&slow, &fast).unwrap();
+        signals::CrossChainSingleHop {
+            slow_chain: strategy.slow_chain.clone(),
+            slow_pair: strategy.slow_pair.clone(),
+            slow_protocol_component: None,
+            slow_pool_id: PoolId::from("0x123"),
+            slow_swap_sim: slow,
+            slow_height: 2000,
+            fast_chain: strategy.fast_chain.clone(),
+            fast_pair: strategy.fast_pair.clone(),
+            fast_protocol_component: None,
+            fast_pool_id: PoolId::from("0x456"),
+            fast_swap_sim: fast,
+            fast_height: 100,
+            max_slippage_bps: 0,
+            congestion_risk_discount_bps: 0,
+            expected_profit: surplus.clone(),
+            surplus,
+            block_skew_ms: None,
+        }
+    }
+
+    #[test]
+    fn most_profitable_signal_is_compared_in_token_a() {
+        // WETH (B) trades at about a tenth of a PEPE (A)
+        // 2 PEPE and 10 WETH of profit, worth 3 PEPE
+        let a_to_b = signal_from_swaps(
+            swap(make_mainnet_pepe(), 20, make_mainnet_weth(), 210),
+            swap(make_base_weth(), 200, make_base_pepe(), 22),
+        );
+        // 12 WETH and 1 PEPE of profit, worth 2 PEPE
+        let b_to_a = signal_from_swaps(
+            swap(make_mainnet_weth(), 100, make_mainnet_pepe(), 11),
+            swap(make_base_pepe(), 10, make_base_weth(), 112),
+        );
+        // in each slow swap's input token B->A looks more profitable
+        assert!(b_to_a.expected_profit.0 > a_to_b.expected_profit.0);
+
+        assert_eq!(a_to_b.expected_profit_in_a(), BigUint::from(3u64));
+        assert_eq!(b_to_a.expected_profit_in_a(), BigUint::from(2u64));
+        let best = most_profitable([b_to_a, a_to_b]).unwrap();
+        assert_eq!(best.slow_swap_sim.token_in, make_mainnet_pepe());
+    }
 }
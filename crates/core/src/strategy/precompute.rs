@@ -1,7 +1,10 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use num_bigint::BigUint;
-use tracing::{error, instrument, trace};
+use tracing::{debug, error, instrument, trace};
 use tycho_simulation::protocol::models::ProtocolComponent;
 
 use crate::{
@@ -9,14 +12,30 @@ use crate::{
         self, PoolId,
         pair::{Pair, PairState},
     },
-    strategy::simulation::{self, make_sorted_spot_prices},
+    strategy::simulation,
 };
 
+/// A pool's simulated swaps in one direction at every step of a precompute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrecomputeCurve {
+    pub block_height: u64,
+    pub pool_id: PoolId,
+    /// Symbol of the sold token
+    pub token_in: String,
+    /// Symbol of the bought token
+    pub token_out: String,
+    pub amounts_in: Vec<BigUint>,
+    /// Simulated amount out for each of `amounts_in`
+    pub amounts_out: Vec<BigUint>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Precomputes {
     pub block_height: u64,
-    pub sorted_spot_prices: Vec<(PoolId, f64)>,
-    pub pool_sims: HashMap<state::PoolId, simulation::PoolSteps>,
+    pub sorted_spot_prices: Arc<Vec<(PoolId, f64)>>,
+    /// Simulated swaps of each pool, shared with the precomputes of later blocks the pool isn't
+    /// modified in
+    pub pool_sims: HashMap<state::PoolId, Arc<simulation::PoolSteps>>,
     #[allow(dead_code)]
     pub pool_metadata: HashMap<state::PoolId, Arc<ProtocolComponent>>,
 }
@@ -32,8 +51,9 @@ impl Precomputes {
     pub fn from_pair_state(
         state: &PairState,
         pair: &Pair,
+        sorted_spot_prices: Arc<Vec<(PoolId, f64)>>,
         inventory: &(BigUint, BigUint),
-        unmodified_precomputes: Option<Precomputes>,
+        unmodified_precomputes: Option<&Precomputes>,
         steps: usize,
     ) -> Self {
         let block_height = state.block_height;
@@ -41,15 +61,21 @@ impl Precomputes {
         let mut pool_sims = HashMap::new();
 
         // reuse precomputes for unmodified pools
-        if let Some(mut precomputes) = unmodified_precomputes {
+        if let Some(precomputes) = unmodified_precomputes {
+            for pool_id in state.removed_pools.iter() {
+                if precomputes.pool_sims.contains_key(pool_id) {
+                    debug!(pool.id = %pool_id, pair = %pair, "Evicted precompute of removed pool");
+                }
+            }
             // TODO: maybe take this out and just keep the previous signals around in the run function and then feed them into generate_signal
 
-            let unmodified_sims: HashMap<PoolId, simulation::PoolSteps> = state
+            let unmodified_sims: HashMap<PoolId, Arc<simulation::PoolSteps>> = state
                 .unmodified_pools
                 .iter()
+                .filter(|pool_id| !state.removed_pools.contains(*pool_id))
                 .filter_map(|pool_id| {
-                    let pool_sims = precomputes.pool_sims.remove(pool_id)?;
-                    Some((pool_id.clone(), pool_sims))
+                    let pool_sims = precomputes.pool_sims.get(pool_id)?;
+                    Some((pool_id.clone(), Arc::clone(pool_sims)))
                 })
                 .collect();
 
@@ -64,9 +90,11 @@ impl Precomputes {
             .filter_map(|pool_id| state.states.get(pool_id).map(|pool| (pool_id, pool)))
             .filter_map(|(pool_id, state)| {
                 match simulation::PoolSteps::from_protocol_sim(&pair, steps, inventory, state.as_ref()) {
-                    Ok(pool_sim) => Some((pool_id.clone(), pool_sim)),
+                    Ok(pool_sim) => Some((pool_id.clone(), Arc::new(pool_sim))),
                     Err(e) => {
                         error!(error = %e, pool.id = %pool_id, pair = %pair, "precompute failed, skipping pool");
+                        metrics::counter!("kuma_strategy_skipped_pools_total", "pool" => pool_id.to_string(), "reason" => "simulation")
+                            .increment(1);
                         None
                     }
                 }
@@ -74,7 +102,11 @@ impl Precomputes {
 
         pool_sims.extend(precomputes);
 
-        let sorted_spot_prices: Vec<(state::PoolId, f64)> = make_sorted_spot_prices(&state, &pair);
+        let priced: HashSet<&PoolId> = sorted_spot_prices.iter().map(|(id, _)| id).collect();
+        for pool_id in state.states.keys().filter(|id| !priced.contains(id)) {
+            metrics::counter!("kuma_strategy_skipped_pools_total", "pool" => pool_id.to_string(), "reason" => "spot_price")
+                .increment(1);
+        }
 
         if sorted_spot_prices.is_empty() {
             trace!(pair= %pair, "No spot prices found");
@@ -98,4 +130,30 @@ impl Precomputes {
             // pair: todo!(),
         }
     }
+
+    /// Both directions' curve of every simulated pool, ordered by pool id
+    pub fn curves(&self) -> Vec<PrecomputeCurve> {
+        let mut pool_ids: Vec<&PoolId> = self.pool_sims.keys().collect();
+        pool_ids.sort_by_key(|pool_id| pool_id.to_string());
+
+        pool_ids
+            .into_iter()
+            .flat_map(|pool_id| {
+                let pool_sim = &self.pool_sims[pool_id];
+                [&pool_sim.a_to_b, &pool_sim.b_to_a]
+                    .into_iter()
+                    .filter_map(move |swaps| {
+                        let first = swaps.first()?;
+                        Some(PrecomputeCurve {
+                            block_height: self.block_height,
+                            pool_id: pool_id.clone(),
+                            token_in: first.token_in.symbol.clone(),
+                            token_out: first.token_out.symbol.clone(),
+                            amounts_in: swaps.iter().map(|swap| swap.amount_in.clone()).collect(),
+                            amounts_out: swaps.iter().map(|swap| swap.amount_out.clone()).collect(),
+                        })
+                    })
+            })
+            .collect()
+    }
 }
@@ -1,4 +1,9 @@
-use std::fmt::Display;
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
 
 use color_eyre::eyre::{self, Context as _, eyre};
 use num_bigint::BigUint;
@@ -133,22 +138,118 @@ pub fn make_sorted_spot_prices(state: &PairState, pair: &Pair) -> Vec<(PoolId, f
     let mut spots: Vec<(PoolId, f64)> = state
         .states
         .iter()
-        .filter_map(|(id, pool)| {
-            let spot_price = pool.spot_price(pair.token_a(), pair.token_b());
-            match spot_price {
-                Ok(price) => Some((id.clone(), price)),
-                Err(err) => {
-                    debug!(
-                        error = %err,
-                        pair = %pair,
-                        "failed to get spot price, skipping pool"
-                    );
-                    None
-                }
-            }
-        })
+        .filter_map(|(id, pool)| spot_price(id, pool.as_ref(), pair))
         .collect();
 
     spots.sort_by(|(_, spot_price), (_, other_spot_price)| spot_price.total_cmp(other_spot_price));
     spots
 }
+
+/// The A -> B spot price of pool `id`, `None` if the pool can't quote one
+fn spot_price(id: &PoolId, pool: &dyn ProtocolSim, pair: &Pair) -> Option<(PoolId, f64)> {
+    match pool.spot_price(pair.token_a(), pair.token_b()) {
+        Ok(price) => Some((id.clone(), price)),
+        Err(err) => {
+            debug!(
+                error = %err,
+                pair = %pair,
+                "failed to get spot price, skipping pool"
+            );
+            None
+        }
+    }
+}
+
+/// `previous`, the sorted spot prices of the block before `state`, brought up to `state`.
+///
+/// Only the pools `state` modified, and those `previous` has no price of, are priced again. The
+/// rest keep their order, so the new prices are merged into them instead of sorting everything.
+fn update_sorted_spot_prices(
+    previous: &[(PoolId, f64)],
+    state: &PairState,
+    pair: &Pair,
+) -> Vec<(PoolId, f64)> {
+    let previous_ids: HashSet<&PoolId> = previous.iter().map(|(id, _)| id).collect();
+    let mut repriced: Vec<(PoolId, f64)> = state
+        .states
+        .iter()
+        .filter(|(id, _)| state.modified_pools.contains(*id) || !previous_ids.contains(id))
+        .filter_map(|(id, pool)| spot_price(id, pool.as_ref(), pair))
+        .collect();
+    repriced
+        .sort_by(|(_, spot_price), (_, other_spot_price)| spot_price.total_cmp(other_spot_price));
+
+    let mut kept = previous
+        .iter()
+        .filter(|(id, _)| state.states.contains_key(id) && !state.modified_pools.contains(id))
+        .peekable();
+    let mut repriced = repriced.into_iter().peekable();
+    let mut spots = Vec::with_capacity(state.states.len());
+    loop {
+        let take_kept = match (kept.peek(), repriced.peek()) {
+            (Some((_, kept_price)), Some((_, repriced_price))) => {
+                kept_price.total_cmp(repriced_price).is_le()
+            }
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => break,
+        };
+        let spot = if take_kept {
+            kept.next().cloned()
+        } else {
+            repriced.next()
+        };
+        spots.extend(spot);
+    }
+
+    spots
+}
+
+/// A pair's pools sorted by spot price as of the latest block asked for, so that the next block
+/// only prices the pools it modified.
+#[derive(Debug, Default)]
+pub struct SpotPriceCache(Mutex<Option<CachedSpotPrices>>);
+
+#[derive(Debug)]
+struct CachedSpotPrices {
+    block_height: u64,
+    /// Tells apart states of the same height, e.g. before and after a reorg
+    received_at: Instant,
+    sorted: Arc<Vec<(PoolId, f64)>>,
+}
+
+impl SpotPriceCache {
+    /// The A -> B spot prices of the pools of `state`, sorted from lowest to highest.
+    ///
+    /// The previous block's prices are only reused when `state` is the block right after it:
+    /// after a gap, a pool may have changed in a block that was never seen.
+    pub fn sorted(&self, state: &PairState, pair: &Pair) -> Arc<Vec<(PoolId, f64)>> {
+        let mut cached = self.0.lock().expect("spot price cache lock poisoned");
+        let sorted = match cached.as_ref() {
+            Some(cached)
+                if cached.block_height == state.block_height
+                    && cached.received_at == state.received_at =>
+            {
+                return Arc::clone(&cached.sorted);
+            }
+            Some(cached) if cached.block_height + 1 == state.block_height => {
+                metrics::counter!("kuma_strategy_spot_price_cache_total", "result" => "incremental")
+                    .increment(1);
+                update_sorted_spot_prices(&cached.sorted, state, pair)
+            }
+            _ => {
+                metrics::counter!("kuma_strategy_spot_price_cache_total", "result" => "full")
+                    .increment(1);
+                make_sorted_spot_prices(state, pair)
+            }
+        };
+
+        let sorted = Arc::new(sorted);
+        *cached = Some(CachedSpotPrices {
+            block_height: state.block_height,
+            received_at: state.received_at,
+            sorted: Arc::clone(&sorted),
+        });
+        sorted
+    }
+}
@@ -0,0 +1,408 @@
+//! Prometheus, logging and Sentry setup shared by the binaries, so they export the same metrics
+//! with the same histogram buckets and write logs in the same format.
+use std::{fmt::Write as _, io, net::SocketAddr, time::Duration};
+
+use color_eyre::eyre::{self, WrapErr as _};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::WithExportConfig as _;
+use opentelemetry_sdk::{
+    Resource,
+    trace::{SdkTracer, SdkTracerProvider},
+};
+use sentry::integrations::tracing::EventFilter;
+use serde_json::{Map, Value};
+use tracing::{
+    Event, Level, Subscriber,
+    field::{Field, Visit},
+};
+use tracing_subscriber::{
+    EnvFilter, Layer,
+    fmt::{
+        self, FmtContext, FormatEvent, FormatFields, FormattedFields, MakeWriter,
+        format::JsonFields, format::Writer, time::FormatTime as _, time::SystemTime,
+    },
+    layer::SubscriberExt as _,
+    registry::LookupSpan,
+    util::SubscriberInitExt as _,
+};
+
+use crate::config::{Config, LogFormat, SentryConfig};
+
+/// Histogram buckets for `*_seconds` metrics, from a fast query to a slow acquire
+const SECONDS_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Histogram buckets for `kuma_strategy_search_iterations`, a binary search over up to a few
+/// thousand precomputed trade sizes
+const SEARCH_ITERATION_BUCKETS: &[f64] = &[1.0, 2.0, 4.0, 6.0, 8.0, 10.0, 12.0, 16.0, 24.0, 32.0];
+
+/// Histogram buckets for `kuma_strategy_crossed_pool_candidates`, slow and fast pool pairs compared
+/// before finding a crossed one
+const CANDIDATE_BUCKETS: &[f64] = &[1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0, 256.0];
+
+/// Builder for the recorder of everything recorded through the `metrics` facade, such as the
+/// `kuma_db_*` metrics.
+pub fn prometheus_builder() -> eyre::Result<PrometheusBuilder> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".to_owned()), SECONDS_BUCKETS)
+        .and_then(|builder| {
+            builder.set_buckets_for_metric(
+                Matcher::Full("kuma_strategy_search_iterations".to_owned()),
+                SEARCH_ITERATION_BUCKETS,
+            )
+        })
+        .and_then(|builder| {
+            builder.set_buckets_for_metric(
+                Matcher::Full("kuma_strategy_crossed_pool_candidates".to_owned()),
+                CANDIDATE_BUCKETS,
+            )
+        })
+        .wrap_err("failed to set histogram buckets")
+}
+
+/// Interval at which histograms of a recorder without its own exporter are drained
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Installs the recorder globally, returning a handle that renders the metrics for scraping.
+/// Must be called from within the tokio runtime.
+pub fn install_prometheus_recorder() -> eyre::Result<PrometheusHandle> {
+    let handle = prometheus_builder()?
+        .install_recorder()
+        .wrap_err("failed to install prometheus recorder")?;
+
+    let upkeep = handle.clone();
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(UPKEEP_INTERVAL);
+        loop {
+            ticks.tick().await;
+            upkeep.run_upkeep();
+        }
+    });
+
+    Ok(handle)
+}
+
+/// Dependencies turned down to warnings unless a directive says otherwise
+const QUIET_TARGETS: &[&str] = &["h2", "hyper_util", "tycho_client", "tycho_simulation"];
+
+/// Logging, tracing and metrics settings of a binary, taken from its config.
+#[derive(Debug, Clone)]
+pub struct TelemetrySettings {
+    /// Name spans are exported under, e.g. `kumad`
+    pub service: &'static str,
+    /// Level of events not matched by `directives`
+    pub level: String,
+    pub format: LogFormat,
+    /// Write logs to stderr rather than stdout, keeping stdout for a command's output
+    pub stderr: bool,
+    /// Levels for specific targets, e.g. `kuma_core::collector=debug`
+    pub directives: Vec<String>,
+    /// OTLP/HTTP endpoint spans are exported to, if any
+    pub otlp_endpoint: Option<String>,
+    /// Where `/metrics` is served for Prometheus, for binaries without their own route
+    pub metrics_addr: Option<SocketAddr>,
+    pub sentry: Option<SentryConfig>,
+}
+
+impl TelemetrySettings {
+    pub fn from_config(service: &'static str, config: &Config) -> Self {
+        Self {
+            service,
+            level: config.log.level.clone(),
+            format: config.log.format,
+            stderr: false,
+            directives: config.log.directives.clone(),
+            otlp_endpoint: config.log.otlp_endpoint.clone(),
+            metrics_addr: config.metrics.as_ref().map(|metrics| metrics.listen_addr),
+            sentry: config.sentry.clone(),
+        }
+    }
+
+    /// The configured level and directives, or those of `RUST_LOG` when set, after turning chatty
+    /// dependencies down to warnings.
+    pub fn env_filter(&self) -> eyre::Result<EnvFilter> {
+        let configured = match std::env::var(EnvFilter::DEFAULT_ENV) {
+            Ok(directives) => directives,
+            Err(_) => std::iter::once(&self.level)
+                .chain(&self.directives)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(","),
+        };
+        let directives = QUIET_TARGETS
+            .iter()
+            .map(|target| format!("{target}=warn"))
+            .chain(std::iter::once(configured))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        EnvFilter::try_new(directives).wrap_err("invalid log level or directives")
+    }
+}
+
+/// Formats events as configured by `log.format`, to stdout.
+pub fn fmt_layer<S>(format: LogFormat) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fmt_layer_with_writer(format, io::stdout)
+}
+
+/// Like [`fmt_layer`], writing to `writer`.
+pub fn fmt_layer_with_writer<S, W>(format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    match format {
+        LogFormat::Pretty => fmt::layer()
+            .with_file(true)
+            .with_line_number(true)
+            .with_writer(writer)
+            .boxed(),
+        LogFormat::Json => fmt::layer()
+            .fmt_fields(JsonFields::new())
+            .event_format(FlatJson)
+            .with_writer(writer)
+            .boxed(),
+    }
+}
+
+/// Installs the configured log filter, [`fmt_layer`] and span export as the global subscriber.
+/// Keep the returned exporter alive until exiting, so it flushes the last spans.
+pub fn init_logging(settings: &TelemetrySettings) -> eyre::Result<OtlpExporter> {
+    let otlp = OtlpExporter::new(settings)?;
+    tracing_subscriber::registry()
+        .with(settings.env_filter()?)
+        .with(if settings.stderr {
+            fmt_layer_with_writer(settings.format, io::stderr)
+        } else {
+            fmt_layer(settings.format)
+        })
+        .with(otlp.layer())
+        .try_init()
+        .wrap_err("failed to install tracing subscriber")?;
+
+    Ok(otlp)
+}
+
+/// Exports spans to `log.otlp_endpoint` in batches, if it's set. Flushes the pending ones when
+/// dropped.
+#[derive(Debug)]
+pub struct OtlpExporter {
+    provider: Option<SdkTracerProvider>,
+    service: &'static str,
+}
+
+impl OtlpExporter {
+    pub fn new(settings: &TelemetrySettings) -> eyre::Result<Self> {
+        let Some(endpoint) = &settings.otlp_endpoint else {
+            return Ok(Self {
+                provider: None,
+                service: settings.service,
+            });
+        };
+
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()
+            .wrap_err("failed to build otlp span exporter")?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(settings.service)
+                    .build(),
+            )
+            .build();
+
+        Ok(Self {
+            provider: Some(provider),
+            service: settings.service,
+        })
+    }
+
+    /// Layer turning spans into OpenTelemetry ones, if export is enabled
+    pub fn layer<S>(&self) -> Option<tracing_opentelemetry::OpenTelemetryLayer<S, SdkTracer>>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let provider = self.provider.as_ref()?;
+        Some(tracing_opentelemetry::layer().with_tracer(provider.tracer(self.service)))
+    }
+}
+
+impl Drop for OtlpExporter {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("failed to flush spans to the otlp endpoint: {e}");
+            }
+        }
+    }
+}
+
+/// Starts reporting to Sentry, including panics on any thread such as those of spawned tasks.
+/// Events are flushed when the returned guard is dropped, so keep it alive until exiting.
+pub fn init_sentry(config: &SentryConfig) -> eyre::Result<sentry::ClientInitGuard> {
+    let dsn = config.dsn.parse().wrap_err("invalid sentry dsn")?;
+    Ok(sentry::init(sentry::ClientOptions {
+        dsn: Some(dsn),
+        environment: config.environment.clone().map(Into::into),
+        release: sentry::release_name!(),
+        attach_stacktrace: true,
+        ..Default::default()
+    }))
+}
+
+/// Sends `error!` events to Sentry, along with the fields of the spans they're in such as
+/// `strategy` or `chain.name`. Warnings and info events are kept as breadcrumbs leading up to
+/// them. Does nothing until [`init_sentry`] is called.
+pub fn sentry_layer<S>() -> impl Layer<S> + Send + Sync
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    sentry::integrations::tracing::layer()
+        .event_filter(|metadata| match *metadata.level() {
+            Level::ERROR => EventFilter::Event,
+            Level::WARN | Level::INFO => EventFilter::Breadcrumb,
+            _ => EventFilter::Ignore,
+        })
+        .enable_span_attributes()
+}
+
+/// Writes every event as a single JSON object, with the fields of the spans it's in merged into
+/// the event's own, so log aggregators can filter on e.g. `strategy` or `chain.name` directly.
+/// Inner spans and the event win when names clash.
+struct FlatJson;
+
+impl<S, N> FormatEvent<S, N> for FlatJson
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        let mut timestamp = String::new();
+        SystemTime.format_time(&mut Writer::new(&mut timestamp))?;
+
+        let mut record = Map::new();
+        record.insert("timestamp".to_owned(), timestamp.into());
+        record.insert("level".to_owned(), event.metadata().level().as_str().into());
+        record.insert("target".to_owned(), event.metadata().target().into());
+
+        let spans = ctx
+            .event_scope()
+            .into_iter()
+            .flat_map(|scope| scope.from_root());
+        let mut span_names = Vec::new();
+        for span in spans {
+            span_names.push(Value::from(span.name()));
+            // `JsonFields` stores each span's fields as a JSON object
+            if let Some(fields) = span.extensions().get::<FormattedFields<N>>() {
+                if let Ok(Value::Object(fields)) = serde_json::from_str(fields) {
+                    record.extend(fields);
+                }
+            }
+        }
+        if !span_names.is_empty() {
+            record.insert("spans".to_owned(), Value::Array(span_names));
+        }
+
+        event.record(&mut JsonVisitor(&mut record));
+
+        writeln!(writer, "{}", Value::Object(record))
+    }
+}
+
+/// Collects an event's fields into a JSON object.
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_owned(), format!("{value:?}").into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use tracing::info_span;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            io::Write::write(&mut *self.0.lock().unwrap(), buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_logs_flatten_span_fields() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            fmt::layer()
+                .fmt_fields(JsonFields::new())
+                .event_format(FlatJson)
+                .with_writer(move || writer.clone()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _strategy = info_span!("strategy_worker", strategy = "usdc-weth").entered();
+            let _chain = info_span!("collector", chain.name = "base", block = 7).entered();
+            tracing::warn!(block = 8, "Chain state is stale");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let record: Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(record["level"], "WARN");
+        assert_eq!(record["message"], "Chain state is stale");
+        assert_eq!(record["strategy"], "usdc-weth");
+        assert_eq!(record["chain.name"], "base");
+        assert_eq!(record["block"], 8);
+        assert_eq!(
+            record["spans"],
+            serde_json::json!(["strategy_worker", "collector"])
+        );
+    }
+}
@@ -0,0 +1,365 @@
+//! On-chain sanity checks for simulated pool state.
+//!
+//! Tycho's simulated state can occasionally drift from what's actually on chain. The
+//! [`PoolVerifier`] reads a pool's reserves (v2) or `slot0` (v3) directly over RPC, at the block
+//! the simulated state is at, and compares the implied spot price to the simulated one.
+use std::str::FromStr as _;
+
+use alloy::{
+    eips::BlockId,
+    primitives::{Address, U256},
+    sol,
+};
+use color_eyre::eyre::{self, Context as _, eyre};
+use num_traits::ToPrimitive as _;
+use tracing::{debug, instrument};
+use tycho_simulation::protocol::models::ProtocolComponent;
+
+use crate::{chain::Chain, rpc::RpcEndpoints, state::balances::u256_to_biguint, state::pair::Pair};
+
+sol! {
+    #[sol(rpc)]
+    interface IUniswapV2Pair {
+        function getReserves() external view returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast);
+    }
+
+    #[sol(rpc)]
+    interface IUniswapV3Pool {
+        function slot0() external view returns (uint160 sqrtPriceX96, int24 tick, uint16 observationIndex, uint16 observationCardinality, uint16 observationCardinalityNext, uint8 feeProtocol, bool unlocked);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PoolVerifier {
+    chain: Chain,
+    rpc: RpcEndpoints,
+    max_deviation_bps: u64,
+}
+
+impl PoolVerifier {
+    pub fn new(chain: &Chain, max_deviation_bps: u64) -> eyre::Result<Self> {
+        Ok(Self {
+            chain: chain.clone(),
+            rpc: RpcEndpoints::new(chain)?,
+            max_deviation_bps,
+        })
+    }
+
+    /// Checks that the on-chain spot price of `component` at block `height` is within
+    /// `max_deviation_bps` of `simulated_price`, the price simulated at that block.
+    ///
+    /// Prices are token A denominated in token B of `pair`, like `ProtocolSim::spot_price`. The
+    /// read goes through [`RpcEndpoints::call`], so a hung endpoint fails it over after
+    /// [`CALL_TIMEOUT`](crate::rpc::CALL_TIMEOUT) rather than stalling the strategy.
+    ///
+    /// # Errors
+    /// Returns an error if the pool's protocol isn't supported, the RPC read fails or times out on
+    /// every endpoint, or the deviation exceeds the configured bound.
+    #[instrument(
+        skip_all,
+        fields(chain = %self.chain, pool.id = %component.id, block.height = height, simulated_price)
+    )]
+    pub async fn verify(
+        &self,
+        component: &ProtocolComponent,
+        pair: &Pair,
+        simulated_price: f64,
+        height: u64,
+    ) -> eyre::Result<()> {
+        let onchain_price = self
+            .onchain_spot_price(component, pair, height)
+            .await
+            .wrap_err("failed to read on-chain spot price")?;
+
+        let deviation_bps = deviation_bps(simulated_price, onchain_price);
+        debug!(
+            onchain_price,
+            deviation_bps, "Compared simulated spot price to on-chain state"
+        );
+
+        if deviation_bps > self.max_deviation_bps as f64 {
+            return Err(eyre!(
+                "simulated spot price {simulated_price} deviates {deviation_bps:.1} bps from on-chain price {onchain_price} (max {} bps)",
+                self.max_deviation_bps
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn onchain_spot_price(
+        &self,
+        component: &ProtocolComponent,
+        pair: &Pair,
+        height: u64,
+    ) -> eyre::Result<f64> {
+        let address = Address::from_str(&component.id)
+            .wrap_err_with(|| format!("pool id {} is not an address", component.id))?;
+        // `Pair` is normalized so that token A is the pool's token0
+        let decimals_a = pair.token_a().decimals as i32;
+        let decimals_b = pair.token_b().decimals as i32;
+        // reading at the latest block would compare against a state the simulation hasn't seen
+        let block = BlockId::number(height);
+
+        match component.protocol_system.as_str() {
+            "uniswap_v2" | "sushiswap_v2" | "pancakeswap_v2" => {
+                let reserves = self
+                    .rpc
+                    .call(|provider| async move {
+                        IUniswapV2Pair::new(address, provider)
+                            .getReserves()
+                            .block(block)
+                            .call()
+                            .await
+                            .wrap_err("getReserves call failed")
+                    })
+                    .await?;
+
+                v2_spot_price(
+                    U256::from(reserves.reserve0),
+                    U256::from(reserves.reserve1),
+                    decimals_a,
+                    decimals_b,
+                )
+            }
+            "uniswap_v3" | "pancakeswap_v3" => {
+                let slot0 = self
+                    .rpc
+                    .call(|provider| async move {
+                        IUniswapV3Pool::new(address, provider)
+                            .slot0()
+                            .block(block)
+                            .call()
+                            .await
+                            .wrap_err("slot0 call failed")
+                    })
+                    .await?;
+
+                v3_spot_price(U256::from(slot0.sqrtPriceX96), decimals_a, decimals_b)
+            }
+            protocol => Err(eyre!("on-chain verification not supported for {protocol}")),
+        }
+    }
+}
+
+/// Spot price of token0 in token1 implied by a v2 pool's reserves.
+fn v2_spot_price(
+    reserve0: U256,
+    reserve1: U256,
+    decimals0: i32,
+    decimals1: i32,
+) -> eyre::Result<f64> {
+    let reserve0 = to_f64(reserve0)?;
+    let reserve1 = to_f64(reserve1)?;
+    if reserve0 == 0.0 {
+        return Err(eyre!("pool has no token A reserves"));
+    }
+
+    Ok(reserve1 / reserve0 * 10f64.powi(decimals0 - decimals1))
+}
+
+/// Spot price of token0 in token1 implied by a v3 pool's `sqrtPriceX96`.
+fn v3_spot_price(sqrt_price_x96: U256, decimals0: i32, decimals1: i32) -> eyre::Result<f64> {
+    let sqrt_price = to_f64(sqrt_price_x96)? / 2f64.powi(96);
+
+    Ok(sqrt_price * sqrt_price * 10f64.powi(decimals0 - decimals1))
+}
+
+fn to_f64(value: U256) -> eyre::Result<f64> {
+    u256_to_biguint(value)
+        .to_f64()
+        .ok_or_else(|| eyre!("{value} does not fit in an f64"))
+}
+
+/// Relative difference between `simulated` and `onchain`, in bps of the on-chain price.
+pub fn deviation_bps(simulated: f64, onchain: f64) -> f64 {
+    if onchain == 0.0 {
+        return f64::INFINITY;
+    }
+    ((simulated - onchain) / onchain).abs() * 10_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use tokio::{
+        io::{AsyncReadExt as _, AsyncWriteExt as _},
+        net::{TcpListener, TcpStream},
+    };
+    use tycho_common::{Bytes, models::token::Token};
+
+    use super::*;
+
+    /// USDC/WETH at 2500 USDC per WETH, USDC being token0
+    const USDC_RESERVE: u128 = 2_500_000_000_000;
+    const WETH_RESERVE: u128 = 1_000_000_000_000_000_000_000;
+    const USDC_IN_WETH: f64 = 0.0004;
+
+    fn token(address: &str, symbol: &str, decimals: u32) -> Token {
+        Token::new(
+            &Bytes::from_str(address).unwrap(),
+            symbol,
+            decimals,
+            0,
+            &[Some(0)],
+            tycho_common::models::Chain::Ethereum,
+            100,
+        )
+    }
+
+    fn usdc_weth() -> Pair {
+        Pair::new(
+            token("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48", "USDC", 6),
+            token("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", "WETH", 18),
+        )
+    }
+
+    fn component(pair: &Pair) -> ProtocolComponent {
+        ProtocolComponent::new(
+            Bytes::from_str("0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc").unwrap(),
+            "uniswap_v2".to_string(),
+            "uniswap_v2_pool".to_string(),
+            tycho_common::models::Chain::Ethereum,
+            vec![pair.token_a().clone(), pair.token_b().clone()],
+            vec![],
+            HashMap::new(),
+            Bytes::default(),
+            Default::default(),
+        )
+    }
+
+    fn verifier(rpc_url: String) -> PoolVerifier {
+        let mut chain = Chain::eth_mainnet();
+        chain.rpc_url = rpc_url;
+        let mut verifier = PoolVerifier::new(&chain, 10).unwrap();
+        verifier.rpc = verifier.rpc.with_timeout(Duration::from_millis(50));
+        verifier
+    }
+
+    /// Answers every JSON-RPC request with `result`, recording the requests.
+    async fn rpc_server(result: String) -> (String, Arc<Mutex<Vec<serde_json::Value>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let Some(request) = read_request(&mut socket).await else {
+                    continue;
+                };
+                let body = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": result,
+                })
+                .to_string();
+                recorded.lock().unwrap().push(request);
+
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                     content-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        (url, requests)
+    }
+
+    async fn read_request(socket: &mut TcpStream) -> Option<serde_json::Value> {
+        let mut buf = Vec::new();
+        loop {
+            let mut chunk = [0; 4096];
+            let n = socket.read(&mut chunk).await.ok()?;
+            if n == 0 {
+                return None;
+            }
+            buf.extend_from_slice(&chunk[..n]);
+
+            let text = String::from_utf8_lossy(&buf);
+            let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                continue;
+            };
+            let content_length = head
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("content-length")
+                        .then_some(value)?
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                })
+                .unwrap_or(0);
+            if body.len() >= content_length {
+                return serde_json::from_str(&body[..content_length]).ok();
+            }
+        }
+    }
+
+    #[test]
+    fn v2_and_v3_prices_agree() {
+        let v2 = v2_spot_price(U256::from(USDC_RESERVE), U256::from(WETH_RESERVE), 6, 18).unwrap();
+        // sqrt(reserve1 / reserve0) = 20_000, as a Q64.96
+        let v3 = v3_spot_price(U256::from(20_000u64) << 96, 6, 18).unwrap();
+
+        assert!(deviation_bps(v2, USDC_IN_WETH) < 0.01, "{v2}");
+        assert!(deviation_bps(v3, v2) < 0.01, "{v3} vs {v2}");
+        assert!(v2_spot_price(U256::ZERO, U256::from(WETH_RESERVE), 6, 18).is_err());
+    }
+
+    #[tokio::test]
+    async fn verify_reads_reserves_at_the_given_block() {
+        let reserves = format!("0x{USDC_RESERVE:064x}{WETH_RESERVE:064x}{:064x}", 0);
+        let (url, requests) = rpc_server(reserves).await;
+        let verifier = verifier(url);
+        let pair = usdc_weth();
+
+        verifier
+            .verify(&component(&pair), &pair, USDC_IN_WETH, 19_000_000)
+            .await
+            .unwrap();
+        let err = verifier
+            .verify(&component(&pair), &pair, USDC_IN_WETH * 1.01, 19_000_000)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("deviates"), "{err}");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        for request in requests.iter() {
+            assert_eq!(request["method"], "eth_call");
+            assert_eq!(request["params"][1], "0x121eac0");
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_times_out_on_a_hung_endpoint() {
+        // accepts connections through its backlog but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let verifier = verifier(format!("http://{}", listener.local_addr().unwrap()));
+        let pair = usdc_weth();
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            verifier.verify(&component(&pair), &pair, USDC_IN_WETH, 19_000_000),
+        )
+        .await
+        .expect("verify is bounded by the rpc timeout");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn deviation_is_relative_to_onchain_price() {
+        assert_eq!(deviation_bps(1.01, 1.0).round(), 100.0);
+        assert_eq!(deviation_bps(0.99, 1.0).round(), 100.0);
+        assert!(deviation_bps(1.0, 0.0).is_infinite());
+    }
+}
@@ -0,0 +1,275 @@
+//! Serves kumad's admin socket, see [`kuma_core::admin`].
+//!
+//! Pausing goes through the database when kumad stores to Postgres, so it lasts across restarts
+//! and shows in the backend's `/admin/controls`. Without the database, and for the min-profit
+//! thresholds, the controls are only applied in memory.
+use std::{
+    fs::Permissions,
+    io,
+    os::unix::fs::PermissionsExt as _,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use color_eyre::eyre::{self, Context as _};
+use kuma_core::{
+    admin::{DaemonStatus, Request, Response, StrategyStatus, WorkerStatus},
+    database::{self, ControlTarget},
+};
+use tokio::{
+    io::{AsyncBufReadExt as _, AsyncReadExt as _, AsyncWriteExt as _, BufReader},
+    net::{UnixListener, UnixStream},
+    select,
+    task::JoinHandle,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, instrument, warn};
+
+use crate::{control::Controls, heartbeat};
+
+/// Longest a client may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest request line read, requests are a few hundred bytes of JSON
+const MAX_REQUEST_BYTES: u64 = 16 * 1024;
+
+/// What the admin socket reads and controls.
+pub(crate) struct Server {
+    pub(crate) controls: Arc<Controls>,
+    pub(crate) workers: heartbeat::Workers,
+    /// Where pause switches are stored, when kumad stores to Postgres
+    pub(crate) db: Option<database::Handle>,
+}
+
+impl Server {
+    /// Spawns the task serving the admin socket at `path` until shutdown, replacing a socket left
+    /// behind by a previous kumad.
+    pub(crate) fn spawn(
+        self,
+        path: PathBuf,
+        shutdown_token: CancellationToken,
+    ) -> eyre::Result<JoinHandle<()>> {
+        match std::fs::remove_file(&path) {
+            Ok(()) => debug!(socket = %path.display(), "Removed stale admin socket"),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e)
+                    .wrap_err_with(|| format!("failed to remove stale {}", path.display()));
+            }
+        }
+        let listener = UnixListener::bind(&path)
+            .wrap_err_with(|| format!("failed to bind admin socket {}", path.display()))?;
+        // anyone who can connect can pause kumad, so only its own user may
+        std::fs::set_permissions(&path, Permissions::from_mode(0o600))
+            .wrap_err_with(|| format!("failed to restrict admin socket {}", path.display()))?;
+        info!(socket = %path.display(), "Serving admin socket");
+
+        Ok(tokio::spawn(self.run(listener, path, shutdown_token)))
+    }
+
+    #[instrument(name = "admin", skip_all)]
+    async fn run(self, listener: UnixListener, path: PathBuf, shutdown_token: CancellationToken) {
+        let server = Arc::new(self);
+        loop {
+            select! {
+                () = shutdown_token.cancelled() => break,
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        let server = Arc::clone(&server);
+                        tokio::spawn(async move {
+                            if let Err(e) = server.serve(stream).await {
+                                debug!(error = %e, "Admin connection failed");
+                            }
+                        });
+                    }
+                    Err(e) => warn!(error = %e, "Failed to accept admin connection"),
+                },
+            }
+        }
+
+        remove_socket(&path);
+        info!("Admin socket closed");
+    }
+
+    /// Answers the single request of a connection.
+    async fn serve(&self, stream: UnixStream) -> eyre::Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut line = String::new();
+        let read = tokio::time::timeout(
+            REQUEST_TIMEOUT,
+            BufReader::new(reader.take(MAX_REQUEST_BYTES)).read_line(&mut line),
+        )
+        .await
+        .wrap_err("timed out reading request")?
+        .wrap_err("failed to read request")?;
+
+        let response = if read as u64 == MAX_REQUEST_BYTES && !line.ends_with('\n') {
+            Response::Error {
+                message: format!("request is longer than {MAX_REQUEST_BYTES} bytes"),
+            }
+        } else {
+            match serde_json::from_str(&line) {
+                Ok(request) => self.handle(request).await,
+                Err(e) => Response::Error {
+                    message: format!("invalid request: {e}"),
+                },
+            }
+        };
+
+        let mut line = serde_json::to_string(&response).wrap_err("failed to serialize response")?;
+        line.push('\n');
+        writer
+            .write_all(line.as_bytes())
+            .await
+            .wrap_err("failed to send response")
+    }
+
+    async fn handle(&self, request: Request) -> Response {
+        info!(?request, "Handling admin request");
+        match self.apply(request).await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!(error = %e, "Admin request failed");
+                Response::Error {
+                    message: format!("{e:#}"),
+                }
+            }
+        }
+    }
+
+    async fn apply(&self, request: Request) -> eyre::Result<Response> {
+        match request {
+            Request::Status => return Ok(Response::Status(self.status())),
+            Request::SetStrategyPaused { strategy, paused } => {
+                self.ensure_strategy(&strategy)?;
+                self.set_paused(ControlTarget::Strategy(strategy), paused)
+                    .await?;
+            }
+            Request::SetExecutionPaused { paused } => {
+                self.set_paused(ControlTarget::Execution, paused).await?;
+            }
+            Request::SetMinProfit {
+                strategy,
+                min_profit_bps,
+            } => {
+                self.ensure_strategy(&strategy)?;
+                self.controls.set_min_profit_bps(&strategy, min_profit_bps);
+            }
+        }
+
+        Ok(Response::Done)
+    }
+
+    fn ensure_strategy(&self, id: &str) -> eyre::Result<()> {
+        if !self.controls.contains(id) {
+            eyre::bail!("no strategy '{id}' is running");
+        }
+        Ok(())
+    }
+
+    async fn set_paused(&self, target: ControlTarget, paused: bool) -> eyre::Result<()> {
+        let control = match &self.db {
+            Some(db) => db
+                .control_repository()
+                .set_paused(&target, paused)
+                .await
+                .wrap_err_with(|| format!("failed to store control {target}"))?,
+            None => database::Control {
+                target,
+                paused,
+                updated_at: 0,
+            },
+        };
+        // the database announces the change too, applying it now makes it visible right away
+        self.controls.apply(&control);
+
+        Ok(())
+    }
+
+    fn status(&self) -> DaemonStatus {
+        DaemonStatus {
+            execution_paused: self.controls.paused(&ControlTarget::Execution),
+            strategies: self
+                .controls
+                .strategy_ids()
+                .into_iter()
+                .map(|id| StrategyStatus {
+                    id: id.to_owned(),
+                    paused: self
+                        .controls
+                        .paused(&ControlTarget::Strategy(id.to_owned())),
+                    min_profit_bps: self.controls.min_profit_bps(id),
+                })
+                .collect(),
+            workers: self
+                .workers
+                .last_progress()
+                .into_iter()
+                .map(|(worker, last_progress_at)| WorkerStatus {
+                    worker,
+                    last_progress_at,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn remove_socket(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        warn!(socket = %path.display(), error = %e, "Failed to remove admin socket");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serves an admin socket without strategies or database under a path unique to the test
+    fn spawn(name: &str) -> (PathBuf, CancellationToken) {
+        let path =
+            std::env::temp_dir().join(format!("kumad-admin-{name}-{}.sock", std::process::id()));
+        let shutdown_token = CancellationToken::new();
+        Server {
+            controls: Arc::new(Controls::new(Vec::new(), None)),
+            workers: heartbeat::Workers::default(),
+            db: None,
+        }
+        .spawn(path.clone(), shutdown_token.clone())
+        .unwrap();
+        (path, shutdown_token)
+    }
+
+    #[tokio::test]
+    async fn socket_is_only_accessible_to_its_owner() {
+        let (path, shutdown_token) = spawn("mode");
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        shutdown_token.cancel();
+    }
+
+    #[tokio::test]
+    async fn oversized_request_is_rejected() {
+        let (path, shutdown_token) = spawn("oversized");
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(&vec![b' '; MAX_REQUEST_BYTES as usize + 1])
+            .await
+            .unwrap();
+        let mut response = String::new();
+        BufReader::new(stream)
+            .read_line(&mut response)
+            .await
+            .unwrap();
+
+        match serde_json::from_str::<Response>(&response).unwrap() {
+            Response::Error { message } => assert!(message.contains("longer than")),
+            response => panic!("unexpected response {response:?}"),
+        }
+
+        shutdown_token.cancel();
+    }
+}
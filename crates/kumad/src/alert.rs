@@ -0,0 +1,308 @@
+//! Slack and Telegram alerts about signals and failures.
+//!
+//! Alerts are queued to a background task that posts them to every configured webhook. At most one
+//! alert of each kind is sent every `alerts.min_interval_secs`, the ones dropped in between are
+//! counted in the next alert of that kind.
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::{self, WrapErr as _};
+use num_traits::ToPrimitive as _;
+use serde_json::json;
+use tokio::{select, sync::mpsc, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, instrument, warn};
+
+use kuma_core::{
+    config::{AlertsConfig, TokenAmount},
+    signals,
+};
+
+/// Alerts queued while the webhooks are slow to answer, later ones are dropped
+const QUEUE_CAPACITY: usize = 64;
+
+/// How long a webhook gets to accept an alert
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum AlertKind {
+    /// A signal expected to profit at least `alerts.min_signal_profit`
+    Signal,
+    /// A chain without block updates for longer than `max_block_staleness_secs`
+    StaleChain,
+    /// A failed database health check
+    Database,
+    /// kumad stopping because a component failed
+    Shutdown,
+}
+
+impl fmt::Display for AlertKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Self::Signal => "signal",
+            Self::StaleChain => "stale chain",
+            Self::Database => "database",
+            Self::Shutdown => "shutdown",
+        };
+        f.write_str(kind)
+    }
+}
+
+#[derive(Debug)]
+struct Alert {
+    kind: AlertKind,
+    text: String,
+}
+
+/// Queues alerts for delivery, cloned into every component raising them.
+#[derive(Debug, Clone)]
+pub(crate) struct Alerts {
+    tx: Option<mpsc::Sender<Alert>>,
+    /// Smallest expected profit worth a signal alert, by upper-cased token symbol
+    min_signal_profit: Arc<HashMap<String, TokenAmount>>,
+}
+
+impl Alerts {
+    /// Alerts that go nowhere, for when `alerts` isn't configured
+    pub(crate) fn disabled() -> Self {
+        Self {
+            tx: None,
+            min_signal_profit: Arc::default(),
+        }
+    }
+
+    pub(crate) fn send(&self, kind: AlertKind, text: impl Into<String>) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+
+        let text = text.into();
+        if let Err(e) = tx.try_send(Alert { kind, text }) {
+            warn!(%kind, err = %e, "Failed to queue alert, dropping it");
+        }
+    }
+
+    /// Alerts about `signal` if it's expected to profit at least `alerts.min_signal_profit` of
+    /// either token.
+    pub(crate) fn signal(&self, strategy: &str, signal: &signals::CrossChainSingleHop) {
+        let profits = [
+            (signal.slow_pair.token_a(), &signal.expected_profit.0),
+            (signal.slow_pair.token_b(), &signal.expected_profit.1),
+        ];
+        let worth_an_alert = profits.iter().any(|(token, profit)| {
+            self.min_signal_profit
+                .get(&token.symbol.to_ascii_uppercase())
+                .and_then(|min_profit| min_profit.to_units(token.decimals).ok())
+                .is_some_and(|min_profit| **profit >= min_profit)
+        });
+        if !worth_an_alert {
+            return;
+        }
+
+        let [(token_a, profit_a), (token_b, profit_b)] = profits.map(|(token, profit)| {
+            let amount =
+                profit.to_f64().unwrap_or(f64::INFINITY) / 10f64.powi(token.decimals as i32);
+            (token.symbol.clone(), amount)
+        });
+        self.send(
+            AlertKind::Signal,
+            format!(
+                "📡 {strategy}: signal expected to profit {profit_a} {token_a} and {profit_b} \
+                 {token_b}, slow block {} on {}, fast block {} on {}",
+                signal.slow_height, signal.slow_chain, signal.fast_height, signal.fast_chain
+            ),
+        );
+    }
+}
+
+/// Where alerts are posted
+#[derive(Debug)]
+enum Webhook {
+    Slack { url: String },
+    Telegram { bot_token: String, chat_id: String },
+}
+
+impl Webhook {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Slack { .. } => "slack",
+            Self::Telegram { .. } => "telegram",
+        }
+    }
+
+    async fn send(&self, client: &reqwest::Client, text: &str) -> eyre::Result<()> {
+        let request = match self {
+            Self::Slack { url } => client.post(url).json(&json!({ "text": text })),
+            Self::Telegram { bot_token, chat_id } => client
+                .post(format!("{TELEGRAM_API_URL}/bot{bot_token}/sendMessage"))
+                .json(&json!({ "chat_id": chat_id, "text": text })),
+        };
+
+        request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            // the url holds the webhook's secret or the bot token
+            .map_err(reqwest::Error::without_url)?;
+        Ok(())
+    }
+}
+
+/// Lets through one alert of each kind per interval.
+#[derive(Debug)]
+struct RateLimiter {
+    min_interval: Duration,
+    /// When the last alert of a kind was sent, and how many were dropped since
+    sent: HashMap<AlertKind, (Instant, u64)>,
+}
+
+impl RateLimiter {
+    fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            sent: HashMap::new(),
+        }
+    }
+
+    /// Whether an alert of `kind` may be sent at `now`, with the number of alerts of that kind
+    /// dropped since the last one.
+    fn check(&mut self, kind: AlertKind, now: Instant) -> Option<u64> {
+        match self.sent.get_mut(&kind) {
+            Some((last_sent, dropped)) if now.duration_since(*last_sent) < self.min_interval => {
+                *dropped += 1;
+                None
+            }
+            Some((last_sent, dropped)) => {
+                *last_sent = now;
+                Some(std::mem::take(dropped))
+            }
+            None => {
+                self.sent.insert(kind, (now, 0));
+                Some(0)
+            }
+        }
+    }
+}
+
+/// Spawns the task posting alerts to the configured webhooks. On shutdown, it delivers what's still
+/// queued, such as why kumad is stopping, before returning.
+pub(crate) fn spawn(
+    config: &AlertsConfig,
+    shutdown_token: CancellationToken,
+) -> eyre::Result<(Alerts, JoinHandle<()>)> {
+    let webhooks: Vec<_> = config
+        .slack
+        .iter()
+        .map(|slack| Webhook::Slack {
+            url: slack.webhook_url.clone(),
+        })
+        .chain(config.telegram.iter().map(|telegram| Webhook::Telegram {
+            bot_token: telegram.bot_token.clone(),
+            chat_id: telegram.chat_id.clone(),
+        }))
+        .collect();
+    if webhooks.is_empty() {
+        warn!("Alerts are configured without slack or telegram, they won't be sent anywhere");
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(SEND_TIMEOUT)
+        .build()
+        .wrap_err("failed to build alert http client")?;
+    let rate_limiter = RateLimiter::new(Duration::from_secs(config.min_interval_secs));
+    let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+
+    let task = tokio::spawn(run(client, webhooks, rate_limiter, rx, shutdown_token));
+    let alerts = Alerts {
+        tx: Some(tx),
+        min_signal_profit: Arc::new(
+            config
+                .min_signal_profit
+                .iter()
+                .map(|(symbol, amount)| (symbol.to_ascii_uppercase(), amount.clone()))
+                .collect(),
+        ),
+    };
+
+    Ok((alerts, task))
+}
+
+#[instrument(name = "alerts", skip_all)]
+async fn run(
+    client: reqwest::Client,
+    webhooks: Vec<Webhook>,
+    mut rate_limiter: RateLimiter,
+    mut rx: mpsc::Receiver<Alert>,
+    shutdown_token: CancellationToken,
+) {
+    loop {
+        select! {
+            () = shutdown_token.cancelled() => break,
+
+            Some(alert) = rx.recv() => {
+                deliver(&client, &webhooks, &mut rate_limiter, alert).await;
+            }
+        }
+    }
+
+    rx.close();
+    while let Ok(alert) = rx.try_recv() {
+        deliver(&client, &webhooks, &mut rate_limiter, alert).await;
+    }
+    info!("Alert task stopped");
+}
+
+async fn deliver(
+    client: &reqwest::Client,
+    webhooks: &[Webhook],
+    rate_limiter: &mut RateLimiter,
+    Alert { kind, text }: Alert,
+) {
+    let Some(dropped) = rate_limiter.check(kind, Instant::now()) else {
+        return;
+    };
+    let text = match dropped {
+        0 => text,
+        dropped => format!("{text}\n({dropped} earlier {kind} alerts were dropped)"),
+    };
+
+    for webhook in webhooks {
+        if let Err(e) = webhook.send(client, &text).await {
+            warn!(webhook = webhook.name(), %kind, err = %e, "Failed to send alert");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limits_each_kind() {
+        let mut rate_limiter = RateLimiter::new(Duration::from_secs(60));
+        let start = Instant::now();
+
+        assert_eq!(rate_limiter.check(AlertKind::Signal, start), Some(0));
+        assert_eq!(rate_limiter.check(AlertKind::Signal, start), None);
+        assert_eq!(
+            rate_limiter.check(AlertKind::Signal, start + Duration::from_secs(59)),
+            None
+        );
+        assert_eq!(rate_limiter.check(AlertKind::Database, start), Some(0));
+
+        assert_eq!(
+            rate_limiter.check(AlertKind::Signal, start + Duration::from_secs(60)),
+            Some(2)
+        );
+        assert_eq!(
+            rate_limiter.check(AlertKind::Signal, start + Duration::from_secs(120)),
+            Some(0)
+        );
+    }
+}
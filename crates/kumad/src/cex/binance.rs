@@ -0,0 +1,205 @@
+//! Binance spot market data over the combined `bookTicker`/`trade` streams
+use color_eyre::eyre::{self, WrapErr as _};
+use serde::Deserialize;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_util::sync::CancellationToken;
+
+use super::{Connection, FeedSenders, Handle, Quote, Side, Trade, parse_decimal};
+
+pub const DEFAULT_WS_URL: &str = "wss://stream.binance.com:9443";
+
+pub struct Builder {
+    /// Base websocket url, e.g. [`DEFAULT_WS_URL`]
+    pub ws_url: String,
+    /// Markets to track, e.g. `ETHUSDC`
+    pub markets: Vec<String>,
+    pub shutdown_token: CancellationToken,
+}
+
+impl Builder {
+    pub fn build(self) -> eyre::Result<Handle> {
+        let Self {
+            ws_url,
+            markets,
+            shutdown_token,
+        } = self;
+
+        let streams = markets
+            .iter()
+            .flat_map(|market| {
+                let market = market.to_ascii_lowercase();
+                [format!("{market}@bookTicker"), format!("{market}@trade")]
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        let url = format!("{}/stream?streams={streams}", ws_url.trim_end_matches('/'));
+
+        super::spawn(
+            "binance",
+            &markets,
+            vec![Box::new(MarketData { url })],
+            vec![],
+            shutdown_token,
+        )
+    }
+}
+
+struct MarketData {
+    url: String,
+}
+
+/// Combined stream envelope, see `/stream?streams=...`.
+#[derive(Debug, Deserialize)]
+struct StreamMessage {
+    stream: String,
+    data: serde_json::Value,
+}
+
+/// `<symbol>@bookTicker` payload, prices and quantities are decimal strings.
+#[derive(Debug, Deserialize)]
+struct BookTicker {
+    #[serde(rename = "u")]
+    update_id: u64,
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "b")]
+    bid: String,
+    #[serde(rename = "B")]
+    bid_qty: String,
+    #[serde(rename = "a")]
+    ask: String,
+    #[serde(rename = "A")]
+    ask_qty: String,
+}
+
+/// `<symbol>@trade` payload
+#[derive(Debug, Deserialize)]
+struct TradeEvent {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "p")]
+    price: String,
+    #[serde(rename = "q")]
+    qty: String,
+    #[serde(rename = "T")]
+    trade_time: u64,
+    /// Whether the buyer was the maker, i.e. the taker sold
+    #[serde(rename = "m")]
+    buyer_is_maker: bool,
+}
+
+impl Connection for MarketData {
+    fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    fn on_connect(&self) -> eyre::Result<Vec<Message>> {
+        // streams are selected through the url
+        Ok(vec![])
+    }
+
+    fn handle_text(&self, text: &str, feeds: &FeedSenders) -> eyre::Result<Option<Message>> {
+        let StreamMessage { stream, data } =
+            serde_json::from_str(text).wrap_err("failed to parse stream message")?;
+
+        if stream.ends_with("@bookTicker") {
+            let ticker: BookTicker =
+                serde_json::from_value(data).wrap_err("failed to parse book ticker")?;
+            feeds.send_quote(Quote {
+                update_id: ticker.update_id,
+                bid: parse_decimal(&ticker.bid)?,
+                bid_qty: parse_decimal(&ticker.bid_qty)?,
+                ask: parse_decimal(&ticker.ask)?,
+                ask_qty: parse_decimal(&ticker.ask_qty)?,
+                symbol: ticker.symbol,
+            })?;
+        } else if stream.ends_with("@trade") {
+            let trade: TradeEvent =
+                serde_json::from_value(data).wrap_err("failed to parse trade")?;
+            feeds.send_trade(Trade {
+                price: parse_decimal(&trade.price)?,
+                qty: parse_decimal(&trade.qty)?,
+                side: if trade.buyer_is_maker {
+                    Side::Sell
+                } else {
+                    Side::Buy
+                },
+                timestamp_ms: trade.trade_time,
+                symbol: trade.symbol,
+            });
+        } else {
+            return Err(eyre::eyre!("unexpected stream {stream}"));
+        }
+
+        Ok(None)
+    }
+
+    fn on_disconnect(&self, _feeds: &FeedSenders) {
+        // binance update ids are global, so quotes stay comparable across connections
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn market_data() -> MarketData {
+        MarketData {
+            url: DEFAULT_WS_URL.to_string(),
+        }
+    }
+
+    #[test]
+    fn book_ticker_updates_the_quote() {
+        let feeds = FeedSenders::for_markets(&["ETHUSDC"]);
+        let text = r#"{"stream":"ethusdc@bookTicker","data":{"u":400900217,"s":"ETHUSDC","b":"3000.10","B":"31.21","a":"3000.20","A":"40.66"}}"#;
+
+        assert_eq!(market_data().handle_text(text, &feeds).unwrap(), None);
+        assert_eq!(
+            feeds.latest_quote("ETHUSDC"),
+            Some(Quote {
+                symbol: "ETHUSDC".to_string(),
+                update_id: 400_900_217,
+                bid: 3000.10,
+                bid_qty: 31.21,
+                ask: 3000.20,
+                ask_qty: 40.66,
+            })
+        );
+    }
+
+    #[test]
+    fn trade_takes_the_taker_side() {
+        let feeds = FeedSenders::for_markets(&["ETHUSDC"]);
+        let mut trades = feeds.subscribe_trades();
+        let text = r#"{"stream":"ethusdc@trade","data":{"e":"trade","E":1718000000001,"s":"ETHUSDC","t":12345,"p":"3000.15","q":"0.5","T":1718000000000,"m":true,"M":true}}"#;
+
+        market_data().handle_text(text, &feeds).unwrap();
+        assert_eq!(
+            trades.try_recv().unwrap(),
+            Trade {
+                symbol: "ETHUSDC".to_string(),
+                price: 3000.15,
+                qty: 0.5,
+                side: Side::Sell,
+                timestamp_ms: 1_718_000_000_000,
+            }
+        );
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected() {
+        let feeds = FeedSenders::for_markets(&["ETHUSDC"]);
+
+        for text in [
+            "not json",
+            r#"{"result":null,"id":1}"#,
+            r#"{"stream":"ethusdc@depth","data":{}}"#,
+            r#"{"stream":"ethusdc@bookTicker","data":{"u":1,"s":"ETHUSDC","b":"abc","B":"1","a":"1","A":"1"}}"#,
+            r#"{"stream":"btcusdc@bookTicker","data":{"u":1,"s":"BTCUSDC","b":"1","B":"1","a":"1","A":"1"}}"#,
+        ] {
+            assert!(market_data().handle_text(text, &feeds).is_err(), "{text}");
+        }
+        assert_eq!(feeds.latest_quote("ETHUSDC"), None);
+    }
+}
@@ -0,0 +1,587 @@
+//! Centralized exchange collectors.
+//!
+//! Every venue runs one or more websocket [`Connection`]s publishing quotes, trades and balances
+//! into shared channels, so strategies can reference any exchange through [`CexCollector`]. Account
+//! state venues don't push is read by [`Poller`]s instead.
+use std::{collections::HashMap, pin::Pin, sync::Arc, time::Duration};
+
+use color_eyre::eyre::{self, WrapErr as _, eyre};
+use futures::{Future, SinkExt as _, StreamExt as _, future::BoxFuture};
+use tokio::{
+    select,
+    sync::{broadcast, watch},
+};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, trace, warn};
+
+pub mod binance;
+pub mod coinbase;
+pub mod okx;
+
+/// Wait before reconnecting after a websocket drops, doubled on every failed attempt
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest wait between reconnection attempts
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// How often [`Connection::keepalive`] messages are sent
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(20);
+
+/// Top of book for a single market.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quote {
+    pub symbol: String,
+    /// Venue specific sequence number, used to drop out-of-order updates
+    pub update_id: u64,
+    pub bid: f64,
+    pub bid_qty: f64,
+    pub ask: f64,
+    pub ask_qty: f64,
+}
+
+impl Quote {
+    pub fn mid(&self) -> f64 {
+        (self.bid + self.ask) / 2.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// A public trade, `side` is the taker's side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub symbol: String,
+    pub price: f64,
+    pub qty: f64,
+    pub side: Side,
+    pub timestamp_ms: u64,
+}
+
+/// Available balance per asset symbol.
+pub type Balances = HashMap<String, f64>;
+
+pub(crate) fn parse_decimal(value: &str) -> eyre::Result<f64> {
+    value
+        .parse()
+        .wrap_err_with(|| format!("invalid decimal {value}"))
+}
+
+/// Market data and account state of a running CEX collector.
+pub trait CexCollector: Future<Output = eyre::Result<()>> + Unpin + Send {
+    /// Name of the venue, e.g. `binance`
+    fn venue(&self) -> &'static str;
+
+    /// Latest top of book of `market`, `None` if it isn't tracked or no quote was received yet
+    fn quote(&self, market: &str) -> Option<Quote>;
+
+    /// Receiver for the top of book of `market`, `None` if the market isn't tracked
+    fn get_quote_rx(&self, market: &str) -> Option<watch::Receiver<Option<Quote>>>;
+
+    fn subscribe_trades(&self) -> broadcast::Receiver<Trade>;
+
+    /// Latest account balances, `None` if the venue hasn't reported them (or isn't authenticated)
+    fn balances(&self) -> Option<Balances>;
+
+    fn shutdown(&mut self) -> BoxFuture<'_, eyre::Result<()>>;
+
+    fn best_bid(&self, market: &str) -> Option<f64> {
+        self.quote(market).map(|quote| quote.bid)
+    }
+
+    fn best_ask(&self, market: &str) -> Option<f64> {
+        self.quote(market).map(|quote| quote.ask)
+    }
+
+    fn mid(&self, market: &str) -> Option<f64> {
+        self.quote(market).map(|quote| quote.mid())
+    }
+}
+
+/// A websocket session with a venue, e.g. its public market data or private account stream.
+pub(crate) trait Connection: Send + Sync {
+    fn url(&self) -> String;
+
+    /// Messages sent right after connecting, e.g. login and subscriptions
+    fn on_connect(&self) -> eyre::Result<Vec<Message>>;
+
+    /// Application level keepalive, for venues that don't rely on websocket pings
+    fn keepalive(&self) -> Option<Message> {
+        None
+    }
+
+    /// Handles a text frame, optionally replying with another message. Replying with a close
+    /// frame drops the session and reconnects, e.g. after the venue rejected a login.
+    fn handle_text(&self, text: &str, feeds: &FeedSenders) -> eyre::Result<Option<Message>>;
+
+    /// Called once the connection dropped. Sequence numbers usually restart with the next
+    /// connection, so quotes are cleared by default.
+    fn on_disconnect(&self, feeds: &FeedSenders) {
+        feeds.reset_quotes();
+    }
+}
+
+/// Account state read over a venue's REST API, for venues that don't push it.
+pub(crate) trait Poller: Send + Sync {
+    /// Wait between polls
+    fn interval(&self) -> Duration;
+
+    fn poll<'a>(&'a self, feeds: &'a FeedSenders) -> BoxFuture<'a, eyre::Result<()>>;
+}
+
+/// Sending side of a collector's feeds, shared by its connections.
+pub(crate) struct FeedSenders {
+    quote_txs: HashMap<String, watch::Sender<Option<Quote>>>,
+    trade_tx: broadcast::Sender<Trade>,
+    balances_tx: watch::Sender<Option<Balances>>,
+}
+
+impl FeedSenders {
+    /// Publishes `quote` unless an update with a higher sequence number was already seen.
+    pub(crate) fn send_quote(&self, quote: Quote) -> eyre::Result<()> {
+        let tx = self
+            .quote_txs
+            .get(&quote.symbol)
+            .ok_or_else(|| eyre!("received quote for untracked market {}", quote.symbol))?;
+
+        // venues can replay older updates right after a reconnect
+        let is_newer = tx
+            .borrow()
+            .as_ref()
+            .is_none_or(|prev| quote.update_id > prev.update_id);
+        if is_newer {
+            trace!(
+                market = %quote.symbol,
+                bid = quote.bid,
+                ask = quote.ask,
+                "Received top of book"
+            );
+            tx.send_replace(Some(quote));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn send_trade(&self, trade: Trade) {
+        trace!(
+            market = %trade.symbol,
+            price = trade.price,
+            qty = trade.qty,
+            side = ?trade.side,
+            "Received trade"
+        );
+        // not having any subscribers is fine
+        let _ = self.trade_tx.send(trade);
+    }
+
+    /// Merges `balances` into the known balances, venues may only push the assets that changed.
+    pub(crate) fn update_balances(&self, balances: Balances) {
+        self.balances_tx.send_modify(|current| {
+            current.get_or_insert_with(Balances::new).extend(balances);
+        });
+    }
+
+    fn reset_quotes(&self) {
+        for tx in self.quote_txs.values() {
+            tx.send_replace(None);
+        }
+    }
+}
+
+#[cfg(test)]
+impl FeedSenders {
+    /// Feeds for `markets` without a collector, for venues' tests to handle messages against.
+    pub(crate) fn for_markets(markets: &[&str]) -> Self {
+        Self {
+            quote_txs: markets
+                .iter()
+                .map(|market| (market.to_string(), watch::channel(None).0))
+                .collect(),
+            trade_tx: broadcast::channel(16).0,
+            balances_tx: watch::channel(None).0,
+        }
+    }
+
+    pub(crate) fn latest_quote(&self, market: &str) -> Option<Quote> {
+        self.quote_txs[market].borrow().clone()
+    }
+
+    pub(crate) fn subscribe_trades(&self) -> broadcast::Receiver<Trade> {
+        self.trade_tx.subscribe()
+    }
+
+    pub(crate) fn latest_balances(&self) -> Option<Balances> {
+        self.balances_tx.borrow().clone()
+    }
+}
+
+pub struct Handle {
+    venue: &'static str,
+    shutdown_token: CancellationToken,
+    worker_handle: Option<tokio::task::JoinHandle<eyre::Result<()>>>,
+    quote_rxs: HashMap<String, watch::Receiver<Option<Quote>>>,
+    trade_tx: broadcast::Sender<Trade>,
+    balances_rx: watch::Receiver<Option<Balances>>,
+}
+
+impl CexCollector for Handle {
+    fn venue(&self) -> &'static str {
+        self.venue
+    }
+
+    fn quote(&self, market: &str) -> Option<Quote> {
+        self.quote_rxs
+            .get(&market.to_ascii_uppercase())
+            .and_then(|rx| rx.borrow().clone())
+    }
+
+    fn get_quote_rx(&self, market: &str) -> Option<watch::Receiver<Option<Quote>>> {
+        self.quote_rxs.get(&market.to_ascii_uppercase()).cloned()
+    }
+
+    fn subscribe_trades(&self) -> broadcast::Receiver<Trade> {
+        self.trade_tx.subscribe()
+    }
+
+    fn balances(&self) -> Option<Balances> {
+        self.balances_rx.borrow().clone()
+    }
+
+    fn shutdown(&mut self) -> BoxFuture<'_, eyre::Result<()>> {
+        Box::pin(async move {
+            self.shutdown_token.cancel();
+            if let Err(e) = self
+                .worker_handle
+                .take()
+                .expect("shutdown must not be called twice")
+                .await
+            {
+                error!(venue = self.venue, "CEX worker failed: {}", e);
+                return Err(e.into());
+            }
+            Ok(())
+        })
+    }
+}
+
+// Awaiting the handle deals with the Worker's result
+impl Future for Handle {
+    type Output = eyre::Result<()>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        use futures::future::FutureExt as _;
+
+        let venue = self.venue;
+        let task = self
+            .worker_handle
+            .as_mut()
+            .expect("cex handle must not be polled after shutdown");
+
+        task.poll_unpin(cx).map(|result| match result {
+            Ok(worker_res) => match worker_res {
+                Ok(()) => Ok(()),
+                Err(e) => Err(e).wrap_err_with(|| format!("{venue} task returned with err")),
+            },
+            Err(e) => Err(e).wrap_err_with(|| format!("{venue} task panicked")),
+        })
+    }
+}
+
+/// Spawns a worker running `connections` and `pollers` against the feeds for `markets`.
+///
+/// Markets are keyed by the venue's symbol, upper cased.
+pub(crate) fn spawn(
+    venue: &'static str,
+    markets: &[String],
+    connections: Vec<Box<dyn Connection>>,
+    pollers: Vec<Box<dyn Poller>>,
+    shutdown_token: CancellationToken,
+) -> eyre::Result<Handle> {
+    if markets.is_empty() {
+        return Err(eyre!("no {venue} markets configured"));
+    }
+
+    let (quote_txs, quote_rxs): (HashMap<_, _>, HashMap<_, _>) = markets
+        .iter()
+        .map(|market| {
+            let market = market.to_ascii_uppercase();
+            let (tx, rx) = watch::channel::<Option<Quote>>(None);
+            ((market.clone(), tx), (market, rx))
+        })
+        .unzip();
+    let (trade_tx, _) = broadcast::channel(1024);
+    let (balances_tx, balances_rx) = watch::channel(None);
+
+    let worker = Worker {
+        venue,
+        connections,
+        pollers,
+        feeds: Arc::new(FeedSenders {
+            quote_txs,
+            trade_tx: trade_tx.clone(),
+            balances_tx,
+        }),
+        shutdown_token: shutdown_token.clone(),
+    };
+    let worker_handle = tokio::task::spawn(async move { worker.run().await });
+
+    Ok(Handle {
+        venue,
+        shutdown_token,
+        worker_handle: Some(worker_handle),
+        quote_rxs,
+        trade_tx,
+        balances_rx,
+    })
+}
+
+struct Worker {
+    venue: &'static str,
+    connections: Vec<Box<dyn Connection>>,
+    pollers: Vec<Box<dyn Poller>>,
+    feeds: Arc<FeedSenders>,
+    shutdown_token: CancellationToken,
+}
+
+impl Worker {
+    #[instrument(name = "cex_worker", skip(self), fields(venue = self.venue))]
+    pub async fn run(self) -> eyre::Result<()> {
+        let Self {
+            connections,
+            pollers,
+            feeds,
+            shutdown_token,
+            ..
+        } = self;
+
+        futures::future::join(
+            futures::future::join_all(
+                connections
+                    .iter()
+                    .map(|connection| run_connection(connection.as_ref(), &feeds, &shutdown_token)),
+            ),
+            futures::future::join_all(
+                pollers
+                    .iter()
+                    .map(|poller| run_poller(poller.as_ref(), &feeds, &shutdown_token)),
+            ),
+        )
+        .await;
+
+        info!("CEX worker received shutdown signal");
+        Ok(())
+    }
+}
+
+/// Wait before the next reconnection attempt, backing off while attempts keep failing.
+#[derive(Debug)]
+struct Backoff {
+    delay: Duration,
+}
+
+impl Backoff {
+    fn new() -> Self {
+        Self {
+            delay: RECONNECT_DELAY,
+        }
+    }
+
+    /// Delay before the next attempt, doubling the one after it up to [`MAX_RECONNECT_DELAY`]
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        delay
+    }
+
+    /// Starts over once a connection received messages
+    fn reset(&mut self) {
+        self.delay = RECONNECT_DELAY;
+    }
+
+    /// Sleeps for the next delay, returning `false` if shut down in the meantime.
+    async fn wait(&mut self, shutdown_token: &CancellationToken) -> bool {
+        select! {
+            () = shutdown_token.cancelled() => false,
+            () = tokio::time::sleep(self.next_delay()) => true,
+        }
+    }
+}
+
+/// Keeps `connection` alive, reconnecting with a backoff whenever it drops or can't be set up,
+/// until shutdown.
+async fn run_connection(
+    connection: &dyn Connection,
+    feeds: &FeedSenders,
+    shutdown_token: &CancellationToken,
+) {
+    let mut backoff = Backoff::new();
+    loop {
+        let url = connection.url();
+        let (mut ws, _) = select! {
+            () = shutdown_token.cancelled() => return,
+
+            res = connect_async(url.as_str()) => match res {
+                Ok(ws) => ws,
+                Err(e) => {
+                    warn!(%url, err = %e, "Failed to connect, retrying");
+                    if !backoff.wait(shutdown_token).await {
+                        return;
+                    }
+                    continue;
+                }
+            },
+        };
+        info!(%url, "Connected to websocket");
+
+        let mut connected = true;
+        match connection.on_connect() {
+            Ok(messages) => {
+                for message in messages {
+                    if let Err(e) = ws.send(message).await {
+                        warn!(%url, err = %e, "Failed to subscribe, reconnecting");
+                        connected = false;
+                        break;
+                    }
+                }
+            }
+            Err(e) => {
+                // e.g. credentials that can't be signed with
+                warn!(%url, err = %e, "Failed to set up connection, reconnecting");
+                if let Err(e) = ws.close(None).await {
+                    debug!(%url, err = %e, "Failed to close websocket");
+                }
+                connected = false;
+            }
+        }
+
+        let mut keepalive = tokio::time::interval(KEEPALIVE_INTERVAL);
+        while connected {
+            select! {
+                () = shutdown_token.cancelled() => {
+                    if let Err(e) = ws.close(None).await {
+                        debug!(%url, err = %e, "Failed to close websocket");
+                    }
+                    return;
+                }
+
+                _ = keepalive.tick() => {
+                    if let Some(message) = connection.keepalive() {
+                        if let Err(e) = ws.send(message).await {
+                            warn!(%url, err = %e, "Failed to send keepalive, reconnecting");
+                            connected = false;
+                        }
+                    }
+                }
+
+                message = ws.next() => match message {
+                    Some(Ok(Message::Text(text))) => match connection.handle_text(text.as_str(), feeds) {
+                        Ok(Some(Message::Close(frame))) => {
+                            warn!(%url, ?frame, "Connection gave up on the session, reconnecting");
+                            if let Err(e) = ws.close(frame).await {
+                                debug!(%url, err = %e, "Failed to close websocket");
+                            }
+                            connected = false;
+                        }
+                        Ok(Some(reply)) => {
+                            backoff.reset();
+                            if let Err(e) = ws.send(reply).await {
+                                warn!(%url, err = %e, "Failed to reply, reconnecting");
+                                connected = false;
+                            }
+                        }
+                        Ok(None) => backoff.reset(),
+                        Err(e) => warn!(%url, err = %e, "Failed to handle message"),
+                    },
+                    Some(Ok(Message::Close(frame))) => {
+                        warn!(%url, ?frame, "Websocket closed by venue, reconnecting");
+                        connected = false;
+                    }
+                    // pings are answered by tungstenite
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => {
+                        warn!(%url, err = %e, "Websocket failed, reconnecting");
+                        connected = false;
+                    }
+                    None => {
+                        warn!(%url, "Websocket ended, reconnecting");
+                        connected = false;
+                    }
+                }
+            }
+        }
+
+        connection.on_disconnect(feeds);
+        if !backoff.wait(shutdown_token).await {
+            return;
+        }
+    }
+}
+
+/// Runs `poller` every [`Poller::interval`] until shutdown, failed polls are retried on the next.
+async fn run_poller(poller: &dyn Poller, feeds: &FeedSenders, shutdown_token: &CancellationToken) {
+    let mut interval = tokio::time::interval(poller.interval());
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        select! {
+            () = shutdown_token.cancelled() => return,
+
+            _ = interval.tick() => {
+                if let Err(e) = poller.poll(feeds).await {
+                    warn!(err = %e, "Failed to poll venue");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap_and_resets() {
+        let mut backoff = Backoff::new();
+        let delays: Vec<_> = (0..8).map(|_| backoff.next_delay().as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 32, 60, 60]);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), RECONNECT_DELAY);
+    }
+
+    #[test]
+    fn quotes_only_move_forward() {
+        let feeds = FeedSenders::for_markets(&["ETHUSDC"]);
+        let quote = |update_id| Quote {
+            symbol: "ETHUSDC".to_string(),
+            update_id,
+            bid: 3000.0,
+            bid_qty: 1.0,
+            ask: 3000.5,
+            ask_qty: 2.0,
+        };
+
+        feeds.send_quote(quote(2)).unwrap();
+        feeds.send_quote(quote(1)).unwrap();
+        assert_eq!(feeds.latest_quote("ETHUSDC").unwrap().update_id, 2);
+
+        feeds.reset_quotes();
+        feeds.send_quote(quote(1)).unwrap();
+        assert_eq!(feeds.latest_quote("ETHUSDC").unwrap().update_id, 1);
+
+        assert!(
+            feeds
+                .send_quote(Quote {
+                    symbol: "BTCUSDC".to_string(),
+                    ..quote(3)
+                })
+                .is_err()
+        );
+    }
+}
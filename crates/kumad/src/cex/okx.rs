@@ -0,0 +1,444 @@
+//! OKX market data over the public `bbo-tbt`/`trades` channels, and account balances over the
+//! private `account` channel when credentials are configured
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use color_eyre::eyre::{self, WrapErr as _, eyre};
+use hmac::{Hmac, Mac as _};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::Sha256;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use super::{Balances, Connection, FeedSenders, Handle, Quote, Side, Trade, parse_decimal};
+
+pub const DEFAULT_WS_URL: &str = "wss://ws.okx.com:8443";
+
+/// API credentials, needed for balances only
+#[derive(Clone)]
+pub struct Credentials {
+    pub api_key: String,
+    pub api_secret: String,
+    pub passphrase: String,
+}
+
+pub struct Builder {
+    /// Base websocket url, e.g. [`DEFAULT_WS_URL`]
+    pub ws_url: String,
+    /// Instruments to track, e.g. `ETH-USDC`
+    pub markets: Vec<String>,
+    pub credentials: Option<Credentials>,
+    pub shutdown_token: CancellationToken,
+}
+
+impl Builder {
+    pub fn build(self) -> eyre::Result<Handle> {
+        let Self {
+            ws_url,
+            markets,
+            credentials,
+            shutdown_token,
+        } = self;
+        let ws_url = ws_url.trim_end_matches('/');
+
+        let mut connections: Vec<Box<dyn super::Connection>> = vec![Box::new(MarketData {
+            url: format!("{ws_url}/ws/v5/public"),
+            markets: markets.iter().map(|m| m.to_ascii_uppercase()).collect(),
+        })];
+        if let Some(credentials) = credentials {
+            connections.push(Box::new(Account {
+                url: format!("{ws_url}/ws/v5/private"),
+                credentials,
+            }));
+        }
+
+        super::spawn("okx", &markets, connections, vec![], shutdown_token)
+    }
+}
+
+/// Push message on a subscribed channel
+#[derive(Debug, Deserialize)]
+struct Push<T> {
+    arg: Arg,
+    data: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Arg {
+    channel: String,
+    #[serde(rename = "instId")]
+    inst_id: Option<String>,
+}
+
+/// Response to an operation such as `subscribe` or `login`
+#[derive(Debug, Deserialize)]
+struct Event {
+    event: String,
+    code: Option<String>,
+    msg: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Bbo {
+    /// `[price, size, deprecated, order count]`
+    asks: Vec<Vec<String>>,
+    bids: Vec<Vec<String>>,
+    ts: String,
+    #[serde(rename = "seqId")]
+    seq_id: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TradeData {
+    #[serde(rename = "instId")]
+    inst_id: String,
+    px: String,
+    sz: String,
+    side: String,
+    ts: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountData {
+    details: Vec<BalanceDetail>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BalanceDetail {
+    ccy: String,
+    #[serde(rename = "availBal")]
+    avail_bal: String,
+}
+
+/// Parses an event response, failing on `error` events.
+fn parse_event(text: &str) -> Option<eyre::Result<Event>> {
+    let event: Event = serde_json::from_str(text).ok()?;
+    if event.event == "error" {
+        return Some(Err(eyre!(
+            "okx error {}: {}",
+            event.code.unwrap_or_default(),
+            event.msg.unwrap_or_default()
+        )));
+    }
+    Some(Ok(event))
+}
+
+struct MarketData {
+    url: String,
+    markets: Vec<String>,
+}
+
+impl Connection for MarketData {
+    fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    fn on_connect(&self) -> eyre::Result<Vec<Message>> {
+        let args: Vec<_> = self
+            .markets
+            .iter()
+            .flat_map(|market| {
+                [
+                    json!({ "channel": "bbo-tbt", "instId": market }),
+                    json!({ "channel": "trades", "instId": market }),
+                ]
+            })
+            .collect();
+
+        Ok(vec![Message::text(
+            json!({ "op": "subscribe", "args": args }).to_string(),
+        )])
+    }
+
+    fn keepalive(&self) -> Option<Message> {
+        Some(Message::text("ping"))
+    }
+
+    fn handle_text(&self, text: &str, feeds: &FeedSenders) -> eyre::Result<Option<Message>> {
+        if text == "pong" {
+            return Ok(None);
+        }
+        if let Some(event) = parse_event(text) {
+            let event = event?;
+            debug!(event = %event.event, "Received okx event");
+            return Ok(None);
+        }
+
+        let push: Push<serde_json::Value> =
+            serde_json::from_str(text).wrap_err("failed to parse push message")?;
+        let inst_id = push
+            .arg
+            .inst_id
+            .ok_or_else(|| eyre!("push on {} is missing its instrument", push.arg.channel))?;
+
+        match push.arg.channel.as_str() {
+            "bbo-tbt" => {
+                for bbo in push.data {
+                    let bbo: Bbo = serde_json::from_value(bbo).wrap_err("failed to parse bbo")?;
+                    let (Some(bid), Some(ask)) = (bbo.bids.first(), bbo.asks.first()) else {
+                        continue;
+                    };
+                    let ts: u64 = bbo.ts.parse().wrap_err("invalid bbo timestamp")?;
+
+                    feeds.send_quote(Quote {
+                        symbol: inst_id.clone(),
+                        update_id: bbo.seq_id.unwrap_or(ts),
+                        bid: parse_level(bid, 0)?,
+                        bid_qty: parse_level(bid, 1)?,
+                        ask: parse_level(ask, 0)?,
+                        ask_qty: parse_level(ask, 1)?,
+                    })?;
+                }
+            }
+            "trades" => {
+                for trade in push.data {
+                    let trade: TradeData =
+                        serde_json::from_value(trade).wrap_err("failed to parse trade")?;
+                    feeds.send_trade(Trade {
+                        price: parse_decimal(&trade.px)?,
+                        qty: parse_decimal(&trade.sz)?,
+                        side: parse_side(&trade.side)?,
+                        timestamp_ms: trade.ts.parse().wrap_err("invalid trade timestamp")?,
+                        symbol: trade.inst_id,
+                    });
+                }
+            }
+            channel => return Err(eyre!("unexpected channel {channel}")),
+        }
+
+        Ok(None)
+    }
+}
+
+struct Account {
+    url: String,
+    credentials: Credentials,
+}
+
+impl Account {
+    /// `login` op, signed as `base64(hmac_sha256(secret, timestamp + "GET/users/self/verify"))`
+    fn login(&self) -> eyre::Result<Message> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .wrap_err("system clock is before the unix epoch")?
+            .as_secs()
+            .to_string();
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.credentials.api_secret.as_bytes())
+            .wrap_err("invalid okx api secret")?;
+        mac.update(format!("{timestamp}GET/users/self/verify").as_bytes());
+        let sign = BASE64.encode(mac.finalize().into_bytes());
+
+        Ok(Message::text(
+            json!({
+                "op": "login",
+                "args": [{
+                    "apiKey": self.credentials.api_key,
+                    "passphrase": self.credentials.passphrase,
+                    "timestamp": timestamp,
+                    "sign": sign,
+                }]
+            })
+            .to_string(),
+        ))
+    }
+}
+
+impl Connection for Account {
+    fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    fn on_connect(&self) -> eyre::Result<Vec<Message>> {
+        // subscriptions are only accepted once the login succeeded
+        Ok(vec![self.login()?])
+    }
+
+    fn keepalive(&self) -> Option<Message> {
+        Some(Message::text("ping"))
+    }
+
+    fn handle_text(&self, text: &str, feeds: &FeedSenders) -> eyre::Result<Option<Message>> {
+        if text == "pong" {
+            return Ok(None);
+        }
+        if let Some(event) = parse_event(text) {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    // a rejected login leaves the session useless, it's retried on a new one
+                    warn!(err = %e, "Okx rejected the account session, reconnecting");
+                    return Ok(Some(Message::Close(None)));
+                }
+            };
+            if event.event == "login" {
+                info!("Logged in to okx, subscribing to account balances");
+                return Ok(Some(Message::text(
+                    json!({ "op": "subscribe", "args": [{ "channel": "account" }] }).to_string(),
+                )));
+            }
+            debug!(event = %event.event, "Received okx event");
+            return Ok(None);
+        }
+
+        let push: Push<AccountData> =
+            serde_json::from_str(text).wrap_err("failed to parse account push")?;
+        let balances = push
+            .data
+            .iter()
+            .flat_map(|account| &account.details)
+            .map(|detail| Ok((detail.ccy.clone(), parse_decimal(&detail.avail_bal)?)))
+            .collect::<eyre::Result<Balances>>()?;
+        feeds.update_balances(balances);
+
+        Ok(None)
+    }
+
+    fn on_disconnect(&self, _feeds: &FeedSenders) {
+        // market data lives on the public connection
+    }
+}
+
+fn parse_level(level: &[String], idx: usize) -> eyre::Result<f64> {
+    parse_decimal(
+        level
+            .get(idx)
+            .ok_or_else(|| eyre!("order book level is missing field {idx}"))?,
+    )
+}
+
+fn parse_side(side: &str) -> eyre::Result<Side> {
+    match side {
+        "buy" => Ok(Side::Buy),
+        "sell" => Ok(Side::Sell),
+        side => Err(eyre!("unknown trade side {side}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn market_data() -> MarketData {
+        MarketData {
+            url: DEFAULT_WS_URL.to_string(),
+            markets: vec!["ETH-USDC".to_string()],
+        }
+    }
+
+    fn account() -> Account {
+        Account {
+            url: DEFAULT_WS_URL.to_string(),
+            credentials: Credentials {
+                api_key: "key".to_string(),
+                api_secret: "secret".to_string(),
+                passphrase: "passphrase".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn bbo_updates_the_quote() {
+        let feeds = FeedSenders::for_markets(&["ETH-USDC"]);
+        let text = r#"{"arg":{"channel":"bbo-tbt","instId":"ETH-USDC"},"data":[{"asks":[["3000.2","4","0","2"]],"bids":[["3000.1","3","0","1"]],"ts":"1718000000000","seqId":123456}]}"#;
+
+        assert_eq!(market_data().handle_text(text, &feeds).unwrap(), None);
+        assert_eq!(
+            feeds.latest_quote("ETH-USDC"),
+            Some(Quote {
+                symbol: "ETH-USDC".to_string(),
+                update_id: 123_456,
+                bid: 3000.1,
+                bid_qty: 3.0,
+                ask: 3000.2,
+                ask_qty: 4.0,
+            })
+        );
+    }
+
+    #[test]
+    fn trades_and_control_messages_are_handled() {
+        let feeds = FeedSenders::for_markets(&["ETH-USDC"]);
+        let mut trades = feeds.subscribe_trades();
+
+        for text in [
+            "pong",
+            r#"{"event":"subscribe","arg":{"channel":"trades","instId":"ETH-USDC"},"connId":"a4d3ae55"}"#,
+            r#"{"arg":{"channel":"trades","instId":"ETH-USDC"},"data":[{"instId":"ETH-USDC","tradeId":"1","px":"3000.1","sz":"0.1","side":"sell","ts":"1718000000000"}]}"#,
+        ] {
+            assert_eq!(market_data().handle_text(text, &feeds).unwrap(), None);
+        }
+        let trade = trades.try_recv().unwrap();
+        assert_eq!(trade.side, Side::Sell);
+        assert_eq!(trade.timestamp_ms, 1_718_000_000_000);
+    }
+
+    #[test]
+    fn malformed_market_data_is_rejected() {
+        let feeds = FeedSenders::for_markets(&["ETH-USDC"]);
+
+        for text in [
+            "not json",
+            r#"{"event":"error","code":"60012","msg":"Invalid request"}"#,
+            r#"{"arg":{"channel":"bbo-tbt"},"data":[]}"#,
+            r#"{"arg":{"channel":"bbo-tbt","instId":"ETH-USDC"},"data":[{"asks":[["3000.2"]],"bids":[["3000.1","3"]],"ts":"1718000000000"}]}"#,
+            r#"{"arg":{"channel":"trades","instId":"ETH-USDC"},"data":[{"instId":"ETH-USDC","px":"1","sz":"1","side":"up","ts":"1"}]}"#,
+        ] {
+            assert!(market_data().handle_text(text, &feeds).is_err(), "{text}");
+        }
+        assert_eq!(feeds.latest_quote("ETH-USDC"), None);
+    }
+
+    #[test]
+    fn account_subscribes_once_logged_in_and_merges_balances() {
+        let feeds = FeedSenders::for_markets(&[]);
+        let account = account();
+
+        let reply = account
+            .handle_text(
+                r#"{"event":"login","code":"0","msg":"","connId":"a4d3ae55"}"#,
+                &feeds,
+            )
+            .unwrap();
+        assert!(
+            matches!(reply, Some(Message::Text(text)) if text.contains(r#""channel":"account""#))
+        );
+
+        let text = r#"{"arg":{"channel":"account","uid":"44705892343619584"},"data":[{"details":[{"ccy":"USDC","availBal":"1234.5"},{"ccy":"ETH","availBal":"0.75"}]}]}"#;
+        account.handle_text(text, &feeds).unwrap();
+        let text = r#"{"arg":{"channel":"account","uid":"44705892343619584"},"data":[{"details":[{"ccy":"ETH","availBal":"0.5"}]}]}"#;
+        account.handle_text(text, &feeds).unwrap();
+
+        assert_eq!(
+            feeds.latest_balances(),
+            Some(Balances::from([
+                ("USDC".to_string(), 1234.5),
+                ("ETH".to_string(), 0.5),
+            ]))
+        );
+    }
+
+    #[test]
+    fn rejected_login_drops_the_session() {
+        let feeds = FeedSenders::for_markets(&[]);
+
+        let reply = account()
+            .handle_text(
+                r#"{"event":"error","code":"60009","msg":"Login failed."}"#,
+                &feeds,
+            )
+            .unwrap();
+        assert_eq!(reply, Some(Message::Close(None)));
+        assert!(
+            account()
+                .handle_text(
+                    r#"{"arg":{"channel":"account"},"data":[{"details":[{"ccy":"ETH"}]}]}"#,
+                    &feeds
+                )
+                .is_err()
+        );
+        assert_eq!(feeds.latest_balances(), None);
+    }
+}
@@ -0,0 +1,183 @@
+//! Applies the operator controls set through the backend's `/admin` routes or kumad's admin
+//! socket.
+//!
+//! Pause switches live in Postgres when kumad stores to it. A background task loads them, follows
+//! changes announced by the database and reloads them all after reconnecting, since changes made
+//! while the listener was down are missed. Without the database they're only set through the
+//! admin socket, and reset on restart like the min-profit thresholds.
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use color_eyre::eyre;
+use kuma_core::database::{self, Control, ControlTarget};
+use tokio::{select, sync::watch};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, instrument, warn};
+
+/// Wait before reconnecting after the listener failed
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Operator switches of a strategy worker.
+pub(crate) struct Switches {
+    /// Whether the strategy's signal generation is paused
+    pub(crate) strategy: watch::Receiver<bool>,
+    /// Whether emitting signals is paused
+    pub(crate) execution: watch::Receiver<bool>,
+    /// Least expected profit, in bps of the slow swap's amount in, of an emitted signal
+    pub(crate) min_profit_bps: watch::Receiver<Option<u64>>,
+}
+
+struct StrategySenders {
+    paused: watch::Sender<bool>,
+    min_profit_bps: watch::Sender<Option<u64>>,
+}
+
+/// Controls of every strategy, set by the database follower and the admin socket.
+pub(crate) struct Controls {
+    execution: watch::Sender<bool>,
+    strategies: HashMap<String, StrategySenders>,
+}
+
+impl Controls {
+    /// Unpaused controls of the strategies `ids`, starting at `min_profit_bps`
+    pub(crate) fn new(ids: impl IntoIterator<Item = String>, min_profit_bps: Option<u64>) -> Self {
+        Self {
+            execution: watch::channel(false).0,
+            strategies: ids
+                .into_iter()
+                .map(|id| {
+                    let senders = StrategySenders {
+                        paused: watch::channel(false).0,
+                        min_profit_bps: watch::channel(min_profit_bps).0,
+                    };
+                    (id, senders)
+                })
+                .collect(),
+        }
+    }
+
+    /// # Panics
+    /// Panics if `id` isn't one of the strategies the controls were created with
+    pub(crate) fn switches(&self, id: &str) -> Switches {
+        let senders = &self.strategies[id];
+        Switches {
+            strategy: senders.paused.subscribe(),
+            execution: self.execution.subscribe(),
+            min_profit_bps: senders.min_profit_bps.subscribe(),
+        }
+    }
+
+    /// Ids of the controlled strategies, sorted
+    pub(crate) fn strategy_ids(&self) -> Vec<&str> {
+        let mut ids: Vec<_> = self.strategies.keys().map(String::as_str).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    pub(crate) fn contains(&self, id: &str) -> bool {
+        self.strategies.contains_key(id)
+    }
+
+    pub(crate) fn paused(&self, target: &ControlTarget) -> bool {
+        match target {
+            ControlTarget::Strategy(id) => self
+                .strategies
+                .get(id)
+                .is_some_and(|senders| *senders.paused.borrow()),
+            ControlTarget::Execution => *self.execution.borrow(),
+        }
+    }
+
+    pub(crate) fn min_profit_bps(&self, id: &str) -> Option<u64> {
+        self.strategies
+            .get(id)
+            .and_then(|senders| *senders.min_profit_bps.borrow())
+    }
+
+    pub(crate) fn apply(&self, control: &Control) {
+        let tx = match &control.target {
+            ControlTarget::Strategy(id) => match self.strategies.get(id) {
+                Some(senders) => &senders.paused,
+                None => return,
+            },
+            ControlTarget::Execution => &self.execution,
+        };
+
+        if tx.send_replace(control.paused) != control.paused {
+            info!(
+                control = %control.target,
+                paused = control.paused,
+                "Applied operator control"
+            );
+        }
+    }
+
+    /// # Panics
+    /// Panics if `id` isn't one of the strategies the controls were created with
+    pub(crate) fn set_min_profit_bps(&self, id: &str, min_profit_bps: Option<u64>) {
+        if self.strategies[id]
+            .min_profit_bps
+            .send_replace(min_profit_bps)
+            != min_profit_bps
+        {
+            info!(
+                strategy = id,
+                ?min_profit_bps,
+                "Applied min profit threshold"
+            );
+        }
+    }
+
+    /// Applies every stored control, resuming targets that aren't stored.
+    fn reload(&self, controls: &[Control]) {
+        let targets = self
+            .strategies
+            .keys()
+            .map(|id| ControlTarget::Strategy(id.clone()))
+            .chain([ControlTarget::Execution]);
+        for target in targets {
+            let control = controls
+                .iter()
+                .find(|control| control.target == target)
+                .cloned()
+                .unwrap_or(Control {
+                    target,
+                    paused: false,
+                    updated_at: 0,
+                });
+            self.apply(&control);
+        }
+    }
+}
+
+/// Spawns the task applying the controls stored in `db` to `controls`, which stops on shutdown.
+pub(crate) fn spawn(
+    db: database::Handle,
+    controls: Arc<Controls>,
+    shutdown_token: CancellationToken,
+) {
+    tokio::spawn(async move {
+        loop {
+            select! {
+                () = shutdown_token.cancelled() => break,
+                Err(e) = follow(&db, &controls) => {
+                    warn!(error = %e, "Lost operator controls, reconnecting");
+                }
+            }
+
+            select! {
+                () = shutdown_token.cancelled() => break,
+                () = tokio::time::sleep(RECONNECT_DELAY) => {}
+            }
+        }
+    });
+}
+
+#[instrument(name = "controls", skip_all)]
+async fn follow(db: &database::Handle, controls: &Controls) -> eyre::Result<()> {
+    let mut listener = db.listen_controls().await?;
+    controls.reload(&db.control_repository().get_all().await?);
+
+    loop {
+        controls.apply(&listener.recv().await?);
+    }
+}
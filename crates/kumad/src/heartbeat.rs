@@ -0,0 +1,140 @@
+//! Liveness of kumad's workers.
+//!
+//! Each worker records when it last made progress: a collector when it receives a block, a strategy
+//! when it handles one. Every [`REPORT_INTERVAL`], a background task reports how long ago that was
+//! as the `kuma_worker_seconds_since_progress` gauge and, when kumad stores to Postgres, in the
+//! `worker_heartbeats` table behind the backend's `/admin/status`. The admin socket reads the
+//! progress itself, without waiting for a report, and so does the systemd watchdog.
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use kuma_core::{collector, database::HeartbeatRepository};
+use tokio::{select, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, instrument, warn};
+
+/// Interval between two reports of every worker's progress
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Records a worker's progress, cloned into the worker.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Heartbeat(Arc<Mutex<Option<SystemTime>>>);
+
+impl Heartbeat {
+    pub(crate) fn beat(&self) {
+        *self.0.lock().unwrap() = Some(SystemTime::now());
+    }
+}
+
+#[derive(Debug)]
+enum Progress {
+    Heartbeat(Heartbeat),
+    /// Collectors already track their last block update
+    Collector(collector::Staleness),
+}
+
+impl Progress {
+    fn last(&self) -> Option<SystemTime> {
+        match self {
+            Self::Heartbeat(heartbeat) => *heartbeat.0.lock().unwrap(),
+            Self::Collector(staleness) => staleness
+                .elapsed()
+                .and_then(|elapsed| SystemTime::now().checked_sub(elapsed)),
+        }
+    }
+}
+
+/// The workers to report, registered while kumad starts them.
+#[derive(Debug, Default)]
+pub(crate) struct Registry {
+    workers: Vec<(String, Progress)>,
+}
+
+impl Registry {
+    pub(crate) fn register(&mut self, worker: String) -> Heartbeat {
+        let heartbeat = Heartbeat::default();
+        self.workers
+            .push((worker, Progress::Heartbeat(heartbeat.clone())));
+        heartbeat
+    }
+
+    pub(crate) fn register_collector(&mut self, handle: &collector::Handle) {
+        let staleness = handle.staleness_monitor();
+        self.workers.push((
+            format!("collector:{}", staleness.chain().name),
+            Progress::Collector(staleness),
+        ));
+    }
+
+    /// Spawns the task reporting every worker's progress until shutdown, storing it with
+    /// `heartbeats` if given. Returns the task along with the registered workers.
+    pub(crate) fn spawn(
+        self,
+        heartbeats: Option<HeartbeatRepository>,
+        shutdown_token: CancellationToken,
+    ) -> (JoinHandle<()>, Workers) {
+        let workers = Workers(Arc::new(self.workers));
+        let task = tokio::spawn(workers.clone().run(heartbeats, shutdown_token));
+        (task, workers)
+    }
+}
+
+/// The registered workers, shared by the heartbeat task and the admin socket.
+#[derive(Debug, Clone)]
+pub(crate) struct Workers(Arc<Vec<(String, Progress)>>);
+
+impl Workers {
+    /// When each worker last made progress, in seconds since the unix epoch
+    pub(crate) fn last_progress(&self) -> Vec<(String, Option<u64>)> {
+        self.last_progress_at()
+            .into_iter()
+            .map(|(worker, last)| {
+                let last = last
+                    .and_then(|last| last.duration_since(UNIX_EPOCH).ok())
+                    .map(|last| last.as_secs());
+                (worker, last)
+            })
+            .collect()
+    }
+
+    /// When each worker last made progress, `None` if it hasn't yet
+    pub(crate) fn last_progress_at(&self) -> Vec<(String, Option<SystemTime>)> {
+        self.0
+            .iter()
+            .map(|(worker, progress)| (worker.clone(), progress.last()))
+            .collect()
+    }
+
+    #[instrument(name = "heartbeats", skip_all)]
+    async fn run(self, heartbeats: Option<HeartbeatRepository>, shutdown_token: CancellationToken) {
+        let mut ticks = tokio::time::interval(REPORT_INTERVAL);
+        loop {
+            select! {
+                () = shutdown_token.cancelled() => break,
+                _ = ticks.tick() => self.report(heartbeats.as_ref()).await,
+            }
+        }
+        info!("Heartbeat task stopped");
+    }
+
+    async fn report(&self, heartbeats: Option<&HeartbeatRepository>) {
+        let now = SystemTime::now();
+        for (worker, progress) in self.0.iter() {
+            if let Some(since) = progress
+                .last()
+                .and_then(|last| now.duration_since(last).ok())
+            {
+                metrics::gauge!("kuma_worker_seconds_since_progress", "worker" => worker.clone())
+                    .set(since.as_secs_f64());
+            }
+        }
+
+        if let Some(heartbeats) = heartbeats {
+            if let Err(e) = heartbeats.upsert_all(&self.last_progress()).await {
+                warn!(err = %e, "Failed to store worker heartbeats");
+            }
+        }
+    }
+}
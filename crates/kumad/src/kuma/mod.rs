@@ -1,28 +1,54 @@
 use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use color_eyre::eyre::{self, Context, eyre};
-use tokio::select;
+use futures::{StreamExt as _, future::OptionFuture, stream::FuturesUnordered};
+use tokio::{select, sync::watch, task::JoinHandle};
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info, instrument, warn};
 
-use crate::strategy;
+use crate::{
+    admin,
+    alert::{self, AlertKind},
+    cex::{self, CexCollector as _},
+    control, heartbeat, mempool, reload, service, strategy,
+};
 use kuma_core::{
     chain::Chain,
     collector,
-    config::{Config, StrategyConfig},
+    config::{Config, StoreConfig, StrategyConfig},
     database,
+    store::{FileStore, NoopStore, Store},
 };
 
 pub(super) struct Kuma {
     shutdown_token: CancellationToken,
     #[allow(dead_code)]
     collector_handles: HashMap<Chain, collector::Handle>,
-    strategy_handle: strategy::Handle,
+    /// Strategy workers by strategy id
+    strategy_handles: Vec<(String, strategy::Handle)>,
+    cex_handles: Vec<cex::Handle>,
+    /// Mempool watchers of the strategies whose fast chain has one configured
+    mempool_handles: Vec<mempool::Handle>,
+    /// Database health changes, unless spot prices and signals are stored elsewhere
+    db_health: Option<watch::Receiver<database::Health>>,
+    alerts: alert::Alerts,
+    /// Delivers alerts until shutdown, if they're configured
+    alert_task: Option<JoinHandle<()>>,
+    /// Reports the workers' progress until shutdown
+    heartbeat_task: JoinHandle<()>,
+    /// Serves the admin socket until shutdown, if it's configured
+    admin_task: Option<JoinHandle<()>>,
+    /// Pings systemd's watchdog until shutdown, if it watches kumad
+    watchdog_task: Option<JoinHandle<()>>,
+    /// Tells systemd kumad is stopping, if it's configured and kumad runs under it
+    notifier: Option<service::Notifier>,
+    /// Removed once every task has stopped
+    _pid_file: Option<service::PidFile>,
 }
 
 impl Kuma {
     #[instrument(skip_all)]
-    pub(super) fn new(cfg: Config, shutdown_token: CancellationToken) -> eyre::Result<Self> {
+    pub(super) async fn new(cfg: Config, shutdown_token: CancellationToken) -> eyre::Result<Self> {
         // 1. extract from config, for each chain:
         //  1. token addrs
         //  2. inventory
@@ -38,7 +64,30 @@ impl Kuma {
                         "🔗 Initialized chain info from config")
         }
 
-        let db = database::Handle::from_config(cfg.database, Arc::new(addrs_for_chain.clone()))?;
+        let account = cfg
+            .account_address()
+            .wrap_err("failed to derive account address")?;
+        info!(%account, "🔑 Tracking balances for account");
+
+        let (store, db): (Arc<dyn Store>, _) = match &cfg.store {
+            StoreConfig::Postgres => {
+                let db = database::Handle::from_config(
+                    cfg.database.clone(),
+                    Arc::new(addrs_for_chain.clone()),
+                )
+                .await?;
+                (Arc::new(db.clone()), Some(db))
+            }
+            StoreConfig::File { dir } => {
+                info!(dir = %dir.display(), "Storing spot prices and signals in files");
+                (Arc::new(FileStore::new(dir.clone())?), None)
+            }
+            StoreConfig::Noop => {
+                warn!("Discarding spot prices and signals");
+                (Arc::new(NoopStore), None)
+            }
+        };
+        let db_health = db.as_ref().map(database::Handle::subscribe_health);
 
         // 2. set up collectors for each chain
         let collector_handles: HashMap<Chain, collector::Handle> = addrs_for_chain
@@ -46,11 +95,19 @@ impl Kuma {
             .map(|(chain, addrs)| {
                 let handle = collector::Builder {
                     chain: chain.clone(),
-                    tycho_url: chain.tycho_url.clone(),
-                    api_key: cfg.tycho_api_key.clone(),
+                    endpoints: cfg.tycho_endpoints(&chain),
+                    max_stream_failures: cfg.max_tycho_stream_failures,
                     tokens: addrs,
                     add_tvl_threshold: cfg.add_tvl_threshold,
                     remove_tvl_threshold: cfg.remove_tvl_threshold,
+                    account: Some(account),
+                    pool_filter: cfg.pool_filter(&chain),
+                    pair_state_delivery: cfg.pair_state_delivery,
+                    protocols: cfg.protocols(&chain),
+                    snapshot: cfg.snapshot_settings(&chain),
+                    record: cfg.record_path(&chain),
+                    blocks: db.as_ref().map(database::Handle::block_repository),
+                    gas_prices: db.as_ref().map(database::Handle::gas_price_repository),
                     shutdown_token: shutdown_token.clone(),
                 }
                 .build()
@@ -58,55 +115,197 @@ impl Kuma {
                 Ok((chain.clone(), handle))
             })
             .collect::<eyre::Result<HashMap<Chain, collector::Handle>>>()?;
+        let mut heartbeats = heartbeat::Registry::default();
+        for handle in collector_handles.values() {
+            heartbeats.register_collector(handle);
+        }
 
-        // TODO: this should run for each strategy config
-        let strategy_handle = {
+        let controls = Arc::new(control::Controls::new(
+            cfg.strategies.iter().map(StrategyConfig::id),
+            cfg.min_profit_bps,
+        ));
+        match &db {
+            Some(db) => control::spawn(db.clone(), Arc::clone(&controls), shutdown_token.clone()),
+            None if cfg.admin_socket.is_none() => {
+                info!(
+                    "Operator controls need the postgres store or the admin socket, strategies \
+                     can't be paused"
+                );
+            }
+            None => {}
+        }
+        if cfg.record_precompute_curves && db.is_none() {
+            warn!("Recording precompute curves needs the postgres store, they're discarded");
+        }
+        let mut settings = reload::spawn(cfg.clone(), shutdown_token.clone());
+        let (alerts, alert_task) = match &cfg.alerts {
+            Some(alerts_cfg) => {
+                let (alerts, task) = alert::spawn(alerts_cfg, shutdown_token.clone())
+                    .wrap_err("failed to start alerts")?;
+                (alerts, Some(task))
+            }
+            None => (alert::Alerts::disabled(), None),
+        };
+
+        // every strategy reads the state of its chains from the shared collectors
+        let mut mempool_handles = Vec::new();
+        let mut strategy_handles = Vec::with_capacity(cfg.strategies.len());
+        for strategy_cfg in &cfg.strategies {
+            let id = strategy_cfg.id();
             let StrategyConfig {
                 token_a,
                 token_b,
                 slow_chain,
                 fast_chain,
-            } = &cfg.strategies[0];
+                slow_confirmation,
+                ..
+            } = strategy_cfg;
+            let params = cfg.strategy_params(strategy_cfg);
 
             let strategy = kuma_core::strategy::Builder {
                 token_a: token_a.clone(),
                 token_b: token_b.clone(),
                 slow_chain_name: slow_chain.clone(),
                 fast_chain_name: fast_chain.clone(),
-                inventory,
-                binary_search_steps: cfg.binary_search_steps,
-                max_slippage_bps: cfg.max_slippage_bps,
-                congestion_risk_discount_bps: cfg.congestion_risk_discount_bps,
+                inventory: inventory.clone(),
+                binary_search_steps: params.binary_search_steps,
+                max_slippage_bps: params.max_slippage_bps,
+                congestion_risk_discount_bps: params.congestion_risk_discount_bps,
             }
             .build()
-            .wrap_err("failed to build strategy")?;
+            .wrap_err_with(|| format!("failed to build strategy {id}"))?;
 
             let slow_stream =
                 collector_handles[&strategy.slow_chain].get_pair_state_stream(&strategy.slow_pair);
             let fast_stream =
                 collector_handles[&strategy.fast_chain].get_pair_state_stream(&strategy.fast_pair);
+            let fast_header_rx = collector_handles[&strategy.fast_chain].get_header_rx();
+            let slow_finality_rx = collector_handles[&strategy.slow_chain].get_finality_rx();
+            let slow_staleness = collector_handles[&strategy.slow_chain].staleness_monitor();
+            let fast_staleness = collector_handles[&strategy.fast_chain].staleness_monitor();
+            let slow_inventory = collector_handles[&strategy.slow_chain].inventory();
+            let fast_inventory = collector_handles[&strategy.fast_chain].inventory();
+
+            let slow_block_time = strategy.slow_chain.block_time();
+            let fast_block_time = strategy.fast_chain.block_time();
 
-            let slow_block_time = strategy
-                .slow_chain
-                .metadata
-                .average_blocktime_hint()
-                .expect("chain metadata for average block time not found");
+            // each strategy watches its own fast pool, sized in its own token A
+            let mempool_handle = cfg
+                .mempool(&strategy.fast_chain)
+                .map(|mempool_cfg| {
+                    mempool::Builder {
+                        chain: strategy.fast_chain.clone(),
+                        ws_url: mempool_cfg.ws_url.clone(),
+                        min_swap_size: mempool_cfg.min_swap_size,
+                        shutdown_token: shutdown_token.clone(),
+                    }
+                    .build()
+                })
+                .transpose()
+                .wrap_err_with(|| format!("failed to start mempool watcher for strategy {id}"))?;
+            let mempool = mempool_handle.as_ref().map(mempool::Handle::subscribe);
+            mempool_handles.extend(mempool_handle);
 
-            strategy::Builder {
+            let handle = strategy::Builder {
+                id: id.clone(),
                 strategy,
                 slow_stream,
                 fast_stream,
+                fast_header_rx,
+                slow_finality_rx,
+                slow_confirmation: *slow_confirmation,
                 slow_block_time,
-                db,
+                fast_block_time,
+                max_block_latency_fraction: cfg.max_block_latency_fraction,
+                max_fast_state_age_blocks: cfg.max_fast_state_age_blocks,
+                slow_staleness,
+                fast_staleness,
+                settings: settings
+                    .remove(&id)
+                    .expect("settings are reloaded for every configured strategy"),
+                max_onchain_deviation_bps: cfg.max_onchain_deviation_bps,
+                max_block_skew: cfg.max_block_skew(),
+                mempool,
+                store: Arc::clone(&store),
+                curves: db
+                    .as_ref()
+                    .filter(|_| cfg.record_precompute_curves)
+                    .map(database::Handle::curve_repository),
+                controls: controls.switches(&id),
+                alerts: alerts.clone(),
+                heartbeat: heartbeats.register(format!("strategy:{id}")),
+                slow_inventory,
+                fast_inventory,
+                min_gas_balances: (
+                    cfg.min_gas_balance(&strategy.slow_chain)?,
+                    cfg.min_gas_balance(&strategy.fast_chain)?,
+                ),
             }
             .build()
-            .wrap_err("failed to build strategy worker")?
+            .wrap_err_with(|| format!("failed to build strategy worker {id}"))?;
+
+            info!(strategy = %id, "Started strategy worker");
+            strategy_handles.push((id, handle));
+        }
+
+        let cex_handles = spawn_cex_collectors(&cfg, &shutdown_token)
+            .wrap_err("failed to start cex collectors")?;
+        let (heartbeat_task, workers) = heartbeats.spawn(
+            db.as_ref().map(database::Handle::heartbeat_repository),
+            shutdown_token.clone(),
+        );
+        let admin_task = cfg
+            .admin_socket
+            .clone()
+            .map(|path| {
+                admin::Server {
+                    controls,
+                    workers: workers.clone(),
+                    db: db.clone(),
+                }
+                .spawn(path, shutdown_token.clone())
+            })
+            .transpose()
+            .wrap_err("failed to start admin socket")?;
+
+        let pid_file = cfg
+            .pid_file
+            .as_deref()
+            .map(service::PidFile::create)
+            .transpose()?;
+        let (notifier, watchdog_task) = match &cfg.systemd {
+            Some(systemd) => match service::Notifier::from_env()? {
+                Some(notifier) => {
+                    notifier.notify("READY=1");
+                    let watchdog_task = notifier.spawn_watchdog(
+                        workers,
+                        systemd.max_worker_idle(),
+                        shutdown_token.clone(),
+                    );
+                    (Some(notifier), watchdog_task)
+                }
+                None => {
+                    warn!("systemd is configured but NOTIFY_SOCKET is unset, not notifying it");
+                    (None, None)
+                }
+            },
+            None => (None, None),
         };
 
         Ok(Self {
             shutdown_token,
             collector_handles,
-            strategy_handle,
+            strategy_handles,
+            cex_handles,
+            mempool_handles,
+            db_health,
+            alerts,
+            alert_task,
+            heartbeat_task,
+            admin_task,
+            watchdog_task,
+            notifier,
+            _pid_file: pid_file,
         })
     }
 
@@ -124,6 +323,20 @@ impl Kuma {
                 })
             })
             .collect::<Vec<_>>();
+        let mut cex_futs = self
+            .cex_handles
+            .iter_mut()
+            .map(|handle| async move { (handle.venue(), handle.await) })
+            .collect::<FuturesUnordered<_>>();
+        let mut strategy_futs = self
+            .strategy_handles
+            .iter_mut()
+            .map(|(id, handle)| async move { (id.as_str(), handle.await) })
+            .collect::<FuturesUnordered<_>>();
+        let mut mempool_futs = self
+            .mempool_handles
+            .iter_mut()
+            .collect::<FuturesUnordered<_>>();
 
         let reason: eyre::Result<String> = {
             loop {
@@ -140,13 +353,45 @@ impl Kuma {
                         }
                     }
 
-                    // Handle strategy worker task completion
-                    result = &mut self.strategy_handle => {
+                    // Handle cex collector task completion
+                    Some((venue, result)) = cex_futs.next() => {
+                        match result {
+                            Ok(()) => break Ok(format!("{venue} collector task completed")),
+                            Err(e) => break Err(e),
+                        }
+                    }
+
+                    // Handle mempool worker task completion
+                    Some(result) = mempool_futs.next() => {
                         match result {
-                            Ok(()) => break Ok("strategy worker completed".to_owned()),
+                            Ok(()) => break Ok("mempool worker completed".to_owned()),
                             Err(e) => break Err(e),
                         }
                     }
+
+                    // Surface database health changes
+                    Some(Ok(())) = OptionFuture::from(self.db_health.as_mut().map(|rx| rx.changed())) => {
+                        let db_health = self.db_health.as_mut().expect("health changed so the receiver exists");
+                        match &*db_health.borrow_and_update() {
+                            database::Health::Healthy => info!("Database is reachable"),
+                            database::Health::Unhealthy(error) => {
+                                error!(%error, "Database health check failed");
+                                self.alerts.send(
+                                    AlertKind::Database,
+                                    format!("🛢️ Database health check failed: {error}"),
+                                );
+                            }
+                            database::Health::Unknown => {}
+                        }
+                    }
+
+                    // Handle strategy worker task completion
+                    Some((id, result)) = strategy_futs.next() => {
+                        match result {
+                            Ok(()) => break Ok(format!("strategy worker {id} completed")),
+                            Err(e) => break Err(e.wrap_err(format!("strategy worker {id} failed"))),
+                        }
+                    }
                 }
             }
         };
@@ -158,6 +403,18 @@ impl Kuma {
     async fn shutdown(mut self, reason: eyre::Result<String>) {
         const WAIT_BEFORE_ABORT: Duration = Duration::from_secs(25);
 
+        // queued first, the alert task delivers it before stopping
+        if let Err(reason) = &reason {
+            self.alerts.send(
+                AlertKind::Shutdown,
+                format!("🛑 kumad is shutting down: {reason:#}"),
+            );
+        }
+
+        if let Some(notifier) = &self.notifier {
+            notifier.notify("STOPPING=1");
+        }
+
         // trigger the shutdown token in case it wasn't triggered yet
         self.shutdown_token.cancel();
 
@@ -170,9 +427,22 @@ impl Kuma {
             Err(reason) => error!(%reason, message),
         };
 
-        // Shutdown strategy worker
-        if let Err(e) = self.strategy_handle.shutdown().await {
-            error!("Failed to shutdown strategy worker: {}", e);
+        for (id, mut handle) in self.strategy_handles {
+            if let Err(e) = handle.shutdown().await {
+                error!("Failed to shutdown strategy worker {}: {}", id, e);
+            }
+        }
+
+        for mut handle in self.mempool_handles {
+            if let Err(e) = handle.shutdown().await {
+                error!("Failed to shutdown mempool worker: {}", e);
+            }
+        }
+
+        for mut handle in self.cex_handles {
+            if let Err(e) = handle.shutdown().await {
+                error!("Failed to shutdown {} collector: {}", handle.venue(), e);
+            }
         }
 
         for (chain, mut handle) in self.collector_handles {
@@ -180,5 +450,108 @@ impl Kuma {
                 error!("Failed to shutdown collector for {}: {}", chain.name, e)
             }
         }
+
+        if let Err(e) = self.heartbeat_task.await {
+            error!("Heartbeat task panicked: {}", e);
+        }
+
+        if let Some(admin_task) = self.admin_task {
+            if let Err(e) = admin_task.await {
+                error!("Admin task panicked: {}", e);
+            }
+        }
+
+        if let Some(watchdog_task) = self.watchdog_task {
+            if let Err(e) = watchdog_task.await {
+                error!("Watchdog task panicked: {}", e);
+            }
+        }
+
+        if let Some(alert_task) = self.alert_task {
+            if let Err(e) = alert_task.await {
+                error!("Alert task panicked: {}", e);
+            }
+        }
     }
 }
+
+fn spawn_cex_collectors(
+    cfg: &Config,
+    shutdown_token: &CancellationToken,
+) -> eyre::Result<Vec<cex::Handle>> {
+    let mut handles = Vec::new();
+
+    if let Some(binance_cfg) = &cfg.binance {
+        handles.push(
+            cex::binance::Builder {
+                ws_url: binance_cfg
+                    .ws_url
+                    .clone()
+                    .unwrap_or_else(|| cex::binance::DEFAULT_WS_URL.to_string()),
+                markets: binance_cfg.markets.clone(),
+                shutdown_token: shutdown_token.clone(),
+            }
+            .build()
+            .wrap_err("failed to start binance collector")?,
+        );
+    }
+
+    if let Some(okx_cfg) = &cfg.okx {
+        let credentials = match (&okx_cfg.api_key, &okx_cfg.api_secret, &okx_cfg.passphrase) {
+            (Some(api_key), Some(api_secret), Some(passphrase)) => Some(cex::okx::Credentials {
+                api_key: api_key.clone(),
+                api_secret: api_secret.clone(),
+                passphrase: passphrase.clone(),
+            }),
+            (None, None, None) => None,
+            _ => {
+                warn!("Incomplete okx credentials, not tracking okx balances");
+                None
+            }
+        };
+        handles.push(
+            cex::okx::Builder {
+                ws_url: okx_cfg
+                    .cex
+                    .ws_url
+                    .clone()
+                    .unwrap_or_else(|| cex::okx::DEFAULT_WS_URL.to_string()),
+                markets: okx_cfg.cex.markets.clone(),
+                credentials,
+                shutdown_token: shutdown_token.clone(),
+            }
+            .build()
+            .wrap_err("failed to start okx collector")?,
+        );
+    }
+
+    if let Some(coinbase_cfg) = &cfg.coinbase {
+        let credentials = match (&coinbase_cfg.api_key, &coinbase_cfg.api_secret) {
+            (Some(api_key), Some(api_secret)) => Some(cex::coinbase::Credentials {
+                api_key: api_key.clone(),
+                api_secret: api_secret.clone(),
+            }),
+            (None, None) => None,
+            _ => {
+                warn!("Incomplete coinbase credentials, not tracking coinbase balances");
+                None
+            }
+        };
+        handles.push(
+            cex::coinbase::Builder {
+                ws_url: coinbase_cfg
+                    .cex
+                    .ws_url
+                    .clone()
+                    .unwrap_or_else(|| cex::coinbase::DEFAULT_WS_URL.to_string()),
+                markets: coinbase_cfg.cex.markets.clone(),
+                credentials,
+                shutdown_token: shutdown_token.clone(),
+            }
+            .build()
+            .wrap_err("failed to start coinbase collector")?,
+        );
+    }
+
+    Ok(handles)
+}
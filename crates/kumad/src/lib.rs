@@ -9,7 +9,15 @@ use kuma_core::config::Config;
 use tokio::task::{JoinError, JoinHandle};
 use tokio_util::sync::CancellationToken;
 
+mod admin;
+mod alert;
+pub mod cex;
+mod control;
+mod heartbeat;
 mod kuma;
+mod mempool;
+mod reload;
+mod service;
 mod strategy;
 pub mod telemetry;
 
@@ -24,9 +32,9 @@ impl Kuma {
     ///
     /// # Errors
     /// Returns an error if Kuma cannot be initialized.
-    pub fn spawn(cfg: Config) -> eyre::Result<Self> {
+    pub async fn spawn(cfg: Config) -> eyre::Result<Self> {
         let shutdown_token = CancellationToken::new();
-        let inner = kuma::Kuma::new(cfg, shutdown_token.child_token())?;
+        let inner = kuma::Kuma::new(cfg, shutdown_token.child_token()).await?;
         let task = tokio::spawn(inner.run());
 
         Ok(Self {
@@ -1,7 +1,10 @@
 use std::process::ExitCode;
 
 use color_eyre::eyre::{self, eyre};
-use kuma_core::config::Config;
+use kuma_core::{
+    config::Config,
+    telemetry::{OtlpExporter, TelemetrySettings, init_sentry},
+};
 use kumad::{
     Kuma,
     telemetry::{self, init_subscriber},
@@ -24,14 +27,60 @@ async fn main() -> ExitCode {
         }
         Ok(cfg) => cfg,
     };
-    eprintln!("starting with config:\n{cfg:?}");
+    if let Err(errors) = cfg.validate() {
+        eprintln!("{errors}");
+        return ExitCode::FAILURE;
+    }
+
+    let telemetry_settings = TelemetrySettings::from_config("kumad", &cfg);
+
+    // set up error reporting, kept alive to flush pending events on exit
+    let _sentry = match telemetry_settings
+        .sentry
+        .as_ref()
+        .map(init_sentry)
+        .transpose()
+    {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("failed initializing sentry: {e:?}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // set up tracing, the exporter is kept alive to flush pending spans on exit
+    let tracing_subscriber = OtlpExporter::new(&telemetry_settings).and_then(|otlp| {
+        let subscriber = telemetry::get_subscriber(&telemetry_settings, &otlp)?;
+        Ok((subscriber, otlp))
+    });
+    let _otlp = match tracing_subscriber {
+        Ok((subscriber, otlp)) => {
+            init_subscriber(subscriber);
+            otlp
+        }
+        Err(e) => {
+            eprintln!("failed initializing tracing: {e:?}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // the config holds keys and credentials, so only what it runs is logged
+    info!(
+        chains = ?cfg.chains.iter().map(|chain| &chain.name).collect::<Vec<_>>(),
+        strategies = cfg.strategies.len(),
+        "Loaded config"
+    );
 
-    // set up tracing
-    let tracing_subscriber = telemetry::get_subscriber();
-    init_subscriber(tracing_subscriber);
+    // set up metrics
+    if let Some(listen_addr) = telemetry_settings.metrics_addr {
+        if let Err(e) = telemetry::init_metrics(listen_addr) {
+            error!(%e, "failed initializing metrics");
+            return ExitCode::FAILURE;
+        }
+    }
 
     // spawn service
-    let mut kuma = match Kuma::spawn(cfg) {
+    let mut kuma = match Kuma::spawn(cfg).await {
         Ok(kuma) => kuma,
         Err(e) => {
             error!(%e, "failed initializing kuma");
@@ -0,0 +1,57 @@
+use std::sync::{Arc, atomic::AtomicBool};
+
+use color_eyre::eyre::{self, eyre};
+use tokio::sync::{broadcast, watch};
+use tokio_util::sync::CancellationToken;
+
+use kuma_core::chain::Chain;
+
+use super::{CompetingSwap, Handle, WatchedPool, Worker};
+
+pub struct Builder {
+    pub chain: Chain,
+    /// Websocket RPC endpoint streaming full pending transactions
+    pub ws_url: String,
+    /// Smallest swap that's flagged, in units of the watched pair's token A
+    pub min_swap_size: f64,
+    pub shutdown_token: CancellationToken,
+}
+
+impl Builder {
+    pub fn build(self) -> eyre::Result<Handle> {
+        let Self {
+            chain,
+            ws_url,
+            min_swap_size,
+            shutdown_token,
+        } = self;
+
+        if min_swap_size.is_nan() || min_swap_size < 0.0 {
+            return Err(eyre!(
+                "min swap size must be non-negative, got {min_swap_size}"
+            ));
+        }
+
+        let (watched_pool_tx, watched_pool_rx) = watch::channel::<Option<WatchedPool>>(None);
+        let (alert_tx, _) = broadcast::channel::<CompetingSwap>(256);
+
+        let worker = Worker {
+            chain,
+            ws_url,
+            min_swap_size,
+            watched_pool_rx,
+            alert_tx: alert_tx.clone(),
+            warned_hash_only: AtomicBool::new(false),
+            shutdown_token: shutdown_token.clone(),
+        };
+
+        let worker_handle = tokio::task::spawn(async move { worker.run().await });
+
+        Ok(Handle {
+            shutdown_token,
+            worker_handle: Some(worker_handle),
+            watched_pool_tx: Arc::new(watched_pool_tx),
+            alert_tx,
+        })
+    }
+}
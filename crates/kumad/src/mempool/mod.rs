@@ -0,0 +1,622 @@
+//! Pending transaction watcher for the fast chain.
+//!
+//! Subscribes to full pending transactions over a websocket RPC and flags large swaps that would
+//! trade through the pool a signal was generated for, before that signal is acted on. Swaps are
+//! recognized by decoding the common Uniswap V2/V3 router calls, so they're matched against the
+//! watched pool by token pair and protocol family rather than by the exact pool they route to.
+//! Direct calls to the pool's address are always flagged.
+use std::{
+    pin::Pin,
+    str::FromStr as _,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use alloy::{
+    primitives::{Address, B256, Bytes, U256},
+    sol,
+    sol_types::SolInterface as _,
+};
+use color_eyre::eyre::{self, WrapErr as _};
+use futures::{Future, SinkExt as _, StreamExt as _};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive as _;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::{
+    select,
+    sync::{broadcast, watch},
+};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, instrument, trace, warn};
+
+use kuma_core::{
+    chain::Chain,
+    state::{PoolId, pair::Pair},
+};
+
+pub use builder::Builder;
+mod builder;
+
+/// Wait before reconnecting after the websocket drops
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+sol! {
+    interface IUniswapV2Router {
+        function swapExactTokensForTokens(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline) external;
+        function swapTokensForExactTokens(uint256 amountOut, uint256 amountInMax, address[] path, address to, uint256 deadline) external;
+        function swapExactETHForTokens(uint256 amountOutMin, address[] path, address to, uint256 deadline) external payable;
+        function swapExactTokensForETH(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline) external;
+    }
+
+    interface ISwapRouter02 {
+        struct ExactInputSingleParams {
+            address tokenIn;
+            address tokenOut;
+            uint24 fee;
+            address recipient;
+            uint256 amountIn;
+            uint256 amountOutMinimum;
+            uint160 sqrtPriceLimitX96;
+        }
+
+        function exactInputSingle(ExactInputSingleParams params) external payable;
+    }
+}
+
+/// The pool a pending signal trades through on the fast chain.
+#[derive(Debug, Clone)]
+pub struct WatchedPool {
+    pub id: PoolId,
+    /// Pool contract, `None` for pools that aren't identified by an address (e.g. V4)
+    pub address: Option<Address>,
+    pub protocol_system: String,
+    pub pair: Pair,
+    /// Simulated price of token A in token B, used to size swaps selling token B
+    pub spot_price: f64,
+}
+
+impl WatchedPool {
+    pub fn new(id: PoolId, protocol_system: &str, pair: Pair, spot_price: f64) -> Self {
+        Self {
+            address: Address::from_str(id.as_ref()).ok(),
+            id,
+            protocol_system: protocol_system.to_string(),
+            pair,
+            spot_price,
+        }
+    }
+
+    fn token_addresses(&self) -> Option<(Address, Address)> {
+        Some((
+            Address::try_from(self.pair.token_a().address.as_ref()).ok()?,
+            Address::try_from(self.pair.token_b().address.as_ref()).ok()?,
+        ))
+    }
+
+    /// Size of `amount_in` of `token_in` in units of token A, `None` if it isn't a pair token
+    fn size_in_token_a(&self, token_in: Address, amount_in: U256) -> Option<f64> {
+        let (token_a, token_b) = self.token_addresses()?;
+        let token = if token_in == token_a {
+            self.pair.token_a()
+        } else if token_in == token_b {
+            self.pair.token_b()
+        } else {
+            return None;
+        };
+
+        let amount = BigUint::from_bytes_be(&amount_in.to_be_bytes::<32>()).to_f64()?
+            / 10f64.powi(token.decimals as i32);
+
+        if token_in == token_a {
+            Some(amount)
+        } else if self.spot_price > 0.0 {
+            Some(amount / self.spot_price)
+        } else {
+            None
+        }
+    }
+}
+
+/// A pending transaction expected to trade through the watched pool.
+#[derive(Debug, Clone)]
+pub struct CompetingSwap {
+    pub tx_hash: B256,
+    pub pool_id: PoolId,
+    /// Swap size in units of the pair's token A, `None` for direct pool calls that weren't decoded
+    pub size: Option<f64>,
+}
+
+/// Lets a strategy point the watcher at a pool and receive the swaps competing with it.
+pub struct Subscription {
+    watched_pool_tx: Arc<watch::Sender<Option<WatchedPool>>>,
+    alert_rx: broadcast::Receiver<CompetingSwap>,
+}
+
+impl Subscription {
+    /// Starts watching `pool`, or stops watching when `None`
+    pub fn watch(&self, pool: Option<WatchedPool>) {
+        self.watched_pool_tx.send_replace(pool);
+    }
+
+    pub async fn recv(&mut self) -> Result<CompetingSwap, broadcast::error::RecvError> {
+        self.alert_rx.recv().await
+    }
+
+    /// A subscription fed by the returned sender rather than a watcher, along with the pool it's
+    /// pointed at
+    #[cfg(test)]
+    pub(crate) fn channel() -> (
+        Self,
+        broadcast::Sender<CompetingSwap>,
+        watch::Receiver<Option<WatchedPool>>,
+    ) {
+        let (watched_pool_tx, watched_pool_rx) = watch::channel(None);
+        let (alert_tx, alert_rx) = broadcast::channel(16);
+        let subscription = Self {
+            watched_pool_tx: Arc::new(watched_pool_tx),
+            alert_rx,
+        };
+        (subscription, alert_tx, watched_pool_rx)
+    }
+}
+
+pub struct Handle {
+    shutdown_token: CancellationToken,
+    worker_handle: Option<tokio::task::JoinHandle<eyre::Result<()>>>,
+    watched_pool_tx: Arc<watch::Sender<Option<WatchedPool>>>,
+    alert_tx: broadcast::Sender<CompetingSwap>,
+}
+
+impl Handle {
+    pub async fn shutdown(&mut self) -> eyre::Result<()> {
+        self.shutdown_token.cancel();
+        if let Err(e) = self
+            .worker_handle
+            .take()
+            .expect("shutdown must not be called twice")
+            .await
+        {
+            error!("Mempool worker failed: {}", e);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    pub fn subscribe(&self) -> Subscription {
+        Subscription {
+            watched_pool_tx: self.watched_pool_tx.clone(),
+            alert_rx: self.alert_tx.subscribe(),
+        }
+    }
+}
+
+// Awaiting the handle deals with the Worker's result
+impl Future for Handle {
+    type Output = eyre::Result<()>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        use futures::future::FutureExt as _;
+
+        let task = self
+            .worker_handle
+            .as_mut()
+            .expect("mempool handle must not be polled after shutdown");
+
+        task.poll_unpin(cx).map(|result| match result {
+            Ok(worker_res) => match worker_res {
+                Ok(()) => Ok(()),
+                Err(e) => Err(e).wrap_err("mempool task returned with err"),
+            },
+            Err(e) => Err(e).wrap_err("mempool task panicked"),
+        })
+    }
+}
+
+/// `eth_subscription` notification carrying a pending transaction
+#[derive(Debug, Deserialize)]
+struct Notification {
+    params: NotificationParams,
+}
+
+#[derive(Debug, Deserialize)]
+struct NotificationParams {
+    result: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct PendingTransaction {
+    hash: B256,
+    to: Option<Address>,
+    input: Bytes,
+    value: U256,
+}
+
+/// A swap decoded from router calldata
+struct RouterSwap {
+    /// `(token in, token out)` of the first hop, the only one whose input amount is known
+    tokens: (Address, Address),
+    /// Input amount, an upper bound for exact output swaps
+    amount_in: U256,
+    /// Protocol family suffix of the pools the router trades through, e.g. `_v2`
+    protocol_family: &'static str,
+}
+
+impl RouterSwap {
+    fn decode(tx: &PendingTransaction) -> Option<Self> {
+        use ISwapRouter02::ISwapRouter02Calls as V3;
+        use IUniswapV2Router::IUniswapV2RouterCalls as V2;
+
+        if let Ok(call) = V2::abi_decode(&tx.input) {
+            let (path, amount_in) = match call {
+                V2::swapExactTokensForTokens(call) => (call.path, call.amountIn),
+                V2::swapTokensForExactTokens(call) => (call.path, call.amountInMax),
+                V2::swapExactETHForTokens(call) => (call.path, tx.value),
+                V2::swapExactTokensForETH(call) => (call.path, call.amountIn),
+            };
+            let (token_in, token_out) = (*path.first()?, *path.get(1)?);
+            return Some(Self {
+                tokens: (token_in, token_out),
+                amount_in,
+                protocol_family: "_v2",
+            });
+        }
+
+        if let Ok(V3::exactInputSingle(call)) = V3::abi_decode(&tx.input) {
+            return Some(Self {
+                tokens: (call.params.tokenIn, call.params.tokenOut),
+                amount_in: call.params.amountIn,
+                protocol_family: "_v3",
+            });
+        }
+
+        None
+    }
+}
+
+struct Worker {
+    chain: Chain,
+    ws_url: String,
+    min_swap_size: f64,
+    watched_pool_rx: watch::Receiver<Option<WatchedPool>>,
+    alert_tx: broadcast::Sender<CompetingSwap>,
+    /// Whether the node was already reported to stream only transaction hashes
+    warned_hash_only: AtomicBool,
+    shutdown_token: CancellationToken,
+}
+
+impl Worker {
+    #[instrument(name = "mempool_worker", skip(self), fields(chain = %self.chain.name))]
+    pub async fn run(self) -> eyre::Result<()> {
+        info!("Starting mempool worker");
+
+        loop {
+            let (mut ws, _) = select! {
+                () = self.shutdown_token.cancelled() => break,
+
+                res = connect_async(self.ws_url.as_str()) => match res {
+                    Ok(ws) => ws,
+                    Err(e) => {
+                        warn!(url = %self.ws_url, err = %e, "Failed to connect, retrying");
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                        continue;
+                    }
+                },
+            };
+
+            let subscribe = json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "eth_subscribe",
+                "params": ["newPendingTransactions", true],
+            });
+            if let Err(e) = ws.send(Message::text(subscribe.to_string())).await {
+                warn!(url = %self.ws_url, err = %e, "Failed to subscribe, reconnecting");
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+            info!(url = %self.ws_url, "Subscribed to pending transactions");
+
+            loop {
+                select! {
+                    () = self.shutdown_token.cancelled() => {
+                        if let Err(e) = ws.close(None).await {
+                            debug!(err = %e, "Failed to close websocket");
+                        }
+                        info!("Mempool worker received shutdown signal");
+                        return Ok(());
+                    }
+
+                    message = ws.next() => match message {
+                        Some(Ok(Message::Text(text))) => self.handle_text(text.as_str()),
+                        Some(Ok(Message::Close(frame))) => {
+                            warn!(?frame, "Websocket closed by node, reconnecting");
+                            break;
+                        }
+                        // pings are answered by tungstenite
+                        Some(Ok(_)) => {}
+                        Some(Err(e)) => {
+                            warn!(err = %e, "Websocket failed, reconnecting");
+                            break;
+                        }
+                        None => {
+                            warn!("Websocket ended, reconnecting");
+                            break;
+                        }
+                    }
+                }
+            }
+
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+
+        info!("Mempool worker received shutdown signal");
+        Ok(())
+    }
+
+    /// Alerts on a pending transaction competing with the watched pool. Notifications that aren't
+    /// full transactions are counted and skipped, one bad payload isn't worth the watcher.
+    fn handle_text(&self, text: &str) {
+        let Ok(notification) = serde_json::from_str::<Notification>(text) else {
+            // subscription confirmations and other responses
+            trace!(text, "Ignoring non-notification message");
+            return;
+        };
+        if notification.params.result.is_string() {
+            self.count_invalid("hash_only");
+            if !self.warned_hash_only.swap(true, Ordering::Relaxed) {
+                warn!(
+                    url = %self.ws_url,
+                    "Node only streams pending transaction hashes, full transactions are required"
+                );
+            }
+            return;
+        }
+
+        let watched_pool = self.watched_pool_rx.borrow();
+        let Some(pool) = watched_pool.as_ref() else {
+            return;
+        };
+
+        let tx = match serde_json::from_value::<PendingTransaction>(notification.params.result) {
+            Ok(tx) => tx,
+            Err(e) => {
+                self.count_invalid("malformed");
+                debug!(err = %e, "Skipped pending transaction that failed to parse");
+                return;
+            }
+        };
+        if let Some(swap) = self.competing_swap(pool, &tx) {
+            warn!(
+                tx.hash = %swap.tx_hash,
+                pool.id = %swap.pool_id,
+                size = ?swap.size,
+                "⚔️ Competing swap pending on watched pool"
+            );
+            // not having any subscribers is fine
+            let _ = self.alert_tx.send(swap);
+        }
+    }
+
+    fn count_invalid(&self, reason: &'static str) {
+        metrics::counter!("kuma_mempool_invalid_notifications_total", "chain" => self.chain.to_string(), "reason" => reason)
+            .increment(1);
+    }
+
+    fn competing_swap(&self, pool: &WatchedPool, tx: &PendingTransaction) -> Option<CompetingSwap> {
+        if pool.address.is_some() && tx.to == pool.address {
+            return Some(CompetingSwap {
+                tx_hash: tx.hash,
+                pool_id: pool.id.clone(),
+                size: None,
+            });
+        }
+
+        let swap = RouterSwap::decode(tx)?;
+        if !pool.protocol_system.ends_with(swap.protocol_family) {
+            return None;
+        }
+        let (token_a, token_b) = pool.token_addresses()?;
+        if swap.tokens != (token_a, token_b) && swap.tokens != (token_b, token_a) {
+            return None;
+        }
+
+        let size = pool.size_in_token_a(swap.tokens.0, swap.amount_in)?;
+        (size >= self.min_swap_size).then(|| CompetingSwap {
+            tx_hash: tx.hash,
+            pool_id: pool.id.clone(),
+            size: Some(size),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tycho_common::models::token::Token;
+
+    use super::*;
+
+    const USDC: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+    const WETH: &str = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+    /// The Uniswap V2 USDC/WETH pair
+    const V2_POOL: &str = "0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc";
+    const V2_ROUTER: &str = "0x7a250d5630b4cf539739df2c5dacb4c659f2488d";
+    const SWAP_ROUTER_02: &str = "0x68b3465833fb72a70ecdf485e0e4c7bd8665fc45";
+
+    /// `swapExactTokensForTokens` of 1000 USDC for WETH through the V2 router
+    const V2_EXACT_IN: &str = "0x38ed1739\
+        000000000000000000000000000000000000000000000000000000003b9aca00\
+        00000000000000000000000000000000000000000000000005698eef06670000\
+        00000000000000000000000000000000000000000000000000000000000000a0\
+        00000000000000000000000028c6c06298d514db089934071355e5743bf21d60\
+        0000000000000000000000000000000000000000000000000000000066669980\
+        0000000000000000000000000000000000000000000000000000000000000002\
+        000000000000000000000000a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48\
+        000000000000000000000000c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+
+    /// `exactInputSingle` of 1 WETH for USDC in the 0.05% pool through SwapRouter02
+    const V3_EXACT_IN_SINGLE: &str = "0x04e45aaf\
+        000000000000000000000000c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2\
+        000000000000000000000000a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48\
+        00000000000000000000000000000000000000000000000000000000000001f4\
+        00000000000000000000000028c6c06298d514db089934071355e5743bf21d60\
+        0000000000000000000000000000000000000000000000000de0b6b3a7640000\
+        000000000000000000000000000000000000000000000000000000008f0d1800\
+        0000000000000000000000000000000000000000000000000000000000000000";
+
+    fn address(address: &str) -> Address {
+        Address::from_str(address).unwrap()
+    }
+
+    fn token(address: &str, symbol: &str, decimals: u32) -> Token {
+        Token::new(
+            &tycho_common::Bytes::from_str(address).unwrap(),
+            symbol,
+            decimals,
+            0,
+            &[Some(0)],
+            tycho_common::models::Chain::Ethereum,
+            100,
+        )
+    }
+
+    /// A pool of the USDC/WETH pair, token A being USDC at 2500 USDC per WETH
+    fn pool(id: &str, protocol_system: &str) -> WatchedPool {
+        let pair = Pair::new(token(USDC, "USDC", 6), token(WETH, "WETH", 18));
+        WatchedPool::new(PoolId::from(id), protocol_system, pair, 1.0 / 2500.0)
+    }
+
+    fn tx(to: &str, input: &str) -> PendingTransaction {
+        PendingTransaction {
+            hash: B256::repeat_byte(0xab),
+            to: Some(address(to)),
+            input: Bytes::from_str(input).unwrap(),
+            value: U256::ZERO,
+        }
+    }
+
+    fn worker(min_swap_size: f64) -> (Worker, broadcast::Receiver<CompetingSwap>) {
+        let (alert_tx, alert_rx) = broadcast::channel(16);
+        let worker = Worker {
+            chain: Chain::new(
+                "ethereum",
+                "http://localhost:8545",
+                "localhost:4242",
+                "0x000000000022d473030f116ddee9f6b43ac78ba3",
+            )
+            .unwrap(),
+            ws_url: "ws://localhost:8546".to_string(),
+            min_swap_size,
+            watched_pool_rx: watch::channel(Some(pool(V2_POOL, "uniswap_v2"))).1,
+            alert_tx,
+            warned_hash_only: AtomicBool::new(false),
+            shutdown_token: CancellationToken::new(),
+        };
+        (worker, alert_rx)
+    }
+
+    #[test]
+    fn decodes_v2_router_swaps() {
+        let swap = RouterSwap::decode(&tx(V2_ROUTER, V2_EXACT_IN)).unwrap();
+
+        assert_eq!(swap.tokens, (address(USDC), address(WETH)));
+        assert_eq!(swap.amount_in, U256::from(1_000_000_000u64));
+        assert_eq!(swap.protocol_family, "_v2");
+    }
+
+    #[test]
+    fn decodes_v3_exact_input_single() {
+        let swap = RouterSwap::decode(&tx(SWAP_ROUTER_02, V3_EXACT_IN_SINGLE)).unwrap();
+
+        assert_eq!(swap.tokens, (address(WETH), address(USDC)));
+        assert_eq!(swap.amount_in, U256::from(10u64.pow(18)));
+        assert_eq!(swap.protocol_family, "_v3");
+    }
+
+    #[test]
+    fn ignores_calldata_of_other_calls() {
+        // an ERC20 `approve`, and a router selector with its arguments cut off
+        let approve = "0x095ea7b3\
+            00000000000000000000000068b3465833fb72a70ecdf485e0e4c7bd8665fc45\
+            ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff";
+        assert!(RouterSwap::decode(&tx(USDC, approve)).is_none());
+        assert!(RouterSwap::decode(&tx(V2_ROUTER, &V2_EXACT_IN[..74])).is_none());
+    }
+
+    #[test]
+    fn competing_swaps_match_the_pools_pair_and_family() {
+        let (worker, _) = worker(500.0);
+        let v2_pool = pool(V2_POOL, "uniswap_v2");
+
+        let swap = worker
+            .competing_swap(&v2_pool, &tx(V2_ROUTER, V2_EXACT_IN))
+            .unwrap();
+        assert_eq!(swap.pool_id, v2_pool.id);
+        assert_eq!(swap.size, Some(1000.0));
+
+        // a V3 router swap doesn't trade through a V2 pool
+        assert!(
+            worker
+                .competing_swap(&v2_pool, &tx(SWAP_ROUTER_02, V3_EXACT_IN_SINGLE))
+                .is_none()
+        );
+
+        // 1 WETH sold is 2500 of token A
+        let v3_pool = pool("0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640", "uniswap_v3");
+        let swap = worker
+            .competing_swap(&v3_pool, &tx(SWAP_ROUTER_02, V3_EXACT_IN_SINGLE))
+            .unwrap();
+        assert!((swap.size.unwrap() - 2500.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn small_swaps_and_direct_pool_calls() {
+        let (worker, _) = worker(2000.0);
+        let v2_pool = pool(V2_POOL, "uniswap_v2");
+
+        assert!(
+            worker
+                .competing_swap(&v2_pool, &tx(V2_ROUTER, V2_EXACT_IN))
+                .is_none()
+        );
+
+        let direct = worker
+            .competing_swap(&v2_pool, &tx(V2_POOL, "0x022c0d9f"))
+            .unwrap();
+        assert_eq!(direct.size, None);
+    }
+
+    #[test]
+    fn bad_notifications_are_skipped() {
+        let (worker, mut alert_rx) = worker(500.0);
+        let notification = |result: serde_json::Value| {
+            json!({
+                "jsonrpc": "2.0",
+                "method": "eth_subscription",
+                "params": {"subscription": "0x1", "result": result},
+            })
+            .to_string()
+        };
+
+        worker.handle_text(&notification(json!(format!("0x{}", "ab".repeat(32)))));
+        assert!(worker.warned_hash_only.load(Ordering::Relaxed));
+        worker.handle_text(&notification(json!({"hash": "0x12"})));
+        worker.handle_text("not json");
+        assert!(alert_rx.try_recv().is_err());
+
+        worker.handle_text(&notification(json!({
+            "hash": format!("0x{}", "cd".repeat(32)),
+            "to": V2_ROUTER,
+            "input": V2_EXACT_IN,
+            "value": "0x0",
+        })));
+        assert_eq!(alert_rx.try_recv().unwrap().size, Some(1000.0));
+    }
+}
@@ -0,0 +1,109 @@
+//! Reloads the config file while kumad runs.
+//!
+//! The file is polled for changes, since only a handful of settings can be applied to running
+//! workers: those in [`LiveSettings`]. Any other change is logged and ignored until the next
+//! restart.
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use kuma_core::config::{self, Config, LiveSettings};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// Interval between checks of the config file's modification time
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Spawns the task watching the config file, which stops on shutdown. `config` is the one kumad
+/// started with, changes are compared against it. Returns the settings of each strategy by id.
+pub(crate) fn spawn(
+    config: Config,
+    shutdown_token: CancellationToken,
+) -> HashMap<String, watch::Receiver<LiveSettings>> {
+    let (settings_txs, settings_rxs): (HashMap<_, _>, HashMap<_, _>) = config
+        .strategies
+        .iter()
+        .map(|strategy| {
+            let (tx, rx) = watch::channel(config.live_settings(strategy));
+            ((strategy.id(), tx), (strategy.id(), rx))
+        })
+        .unzip();
+
+    let path = config::config_path();
+    tokio::spawn(async move {
+        let mut modified = modified_at(&path).await;
+        if modified.is_none() {
+            info!(
+                file = %path.display(),
+                "Config file not found, not watching it for changes"
+            );
+        }
+
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            tokio::select! {
+                () = shutdown_token.cancelled() => break,
+                _ = interval.tick() => {}
+            }
+
+            let latest = modified_at(&path).await;
+            if latest == modified {
+                continue;
+            }
+            modified = latest;
+
+            debug!(file = %path.display(), "Config file changed, reloading it");
+            reload(&config, &path, &settings_txs);
+        }
+    });
+
+    settings_rxs
+}
+
+async fn modified_at(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path)
+        .await
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn reload(
+    config: &Config,
+    path: &Path,
+    settings_txs: &HashMap<String, watch::Sender<LiveSettings>>,
+) {
+    let new = match Config::load_from(path) {
+        Ok(new) => new,
+        Err(e) => {
+            warn!(error = %e, "Failed to reload config, keeping the current settings");
+            return;
+        }
+    };
+    if let Err(errors) = new.validate() {
+        warn!(%errors, "Reloaded config is invalid, keeping the current settings");
+        return;
+    }
+
+    match config.restart_required(&new) {
+        Ok(fields) if fields.is_empty() => {}
+        Ok(fields) => warn!(
+            ?fields,
+            "Config changes need a restart to take effect, ignoring them"
+        ),
+        Err(e) => warn!(error = %e, "Failed to compare the reloaded config"),
+    }
+
+    // strategies only change with a restart, so they're the ones kumad runs
+    for strategy in &new.strategies {
+        let Some(settings_tx) = settings_txs.get(&strategy.id()) else {
+            continue;
+        };
+        let settings = new.live_settings(strategy);
+        if settings_tx.send_replace(settings) != settings {
+            info!(strategy = %strategy.id(), ?settings, "Reloaded config");
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Integration with service managers beyond SIGTERM.
+//!
+//! `pid_file` is written once kumad has started and removed when it stops. Under systemd with
+//! `Type=notify`, kumad sends `READY=1` once every worker has started and `STOPPING=1` when it
+//! shuts down. With `WatchdogSec=` set as well, it pings the watchdog at half that interval for as
+//! long as every worker registered with [`heartbeat`](crate::heartbeat) made progress within
+//! `max_worker_idle_secs`, so systemd restarts a daemon that is running but wedged.
+use std::{
+    os::unix::net::{SocketAddr, UnixDatagram},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use color_eyre::eyre::{self, Context as _};
+use tokio::{select, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, instrument, warn};
+
+use crate::heartbeat::Workers;
+
+/// The pid file, removed when dropped.
+#[derive(Debug)]
+pub(crate) struct PidFile(PathBuf);
+
+impl PidFile {
+    /// Writes the process id to `path`, replacing what it held.
+    pub(crate) fn create(path: &Path) -> eyre::Result<Self> {
+        std::fs::write(path, format!("{}\n", std::process::id()))
+            .wrap_err_with(|| format!("failed to write pid file {}", path.display()))?;
+        Ok(Self(path.to_owned()))
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0) {
+            warn!(path = %self.0.display(), err = %e, "Failed to remove pid file");
+        }
+    }
+}
+
+/// Sends `sd_notify` messages to systemd's `NOTIFY_SOCKET`.
+#[derive(Debug, Clone)]
+pub(crate) struct Notifier {
+    socket: Arc<UnixDatagram>,
+    addr: SocketAddr,
+}
+
+impl Notifier {
+    /// The notifier of the socket systemd passes in `NOTIFY_SOCKET`, `None` when kumad isn't run
+    /// by systemd with `Type=notify`.
+    pub(crate) fn from_env() -> eyre::Result<Option<Self>> {
+        let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+            return Ok(None);
+        };
+        let path = path.to_string_lossy();
+        let addr = match path.strip_prefix('@') {
+            Some(name) => abstract_addr(name),
+            None => SocketAddr::from_pathname(path.as_ref()),
+        }
+        .wrap_err_with(|| format!("invalid NOTIFY_SOCKET {path}"))?;
+        let socket = UnixDatagram::unbound().wrap_err("failed to open a notify socket")?;
+
+        Ok(Some(Self {
+            socket: Arc::new(socket),
+            addr,
+        }))
+    }
+
+    /// Sends `state`, e.g. `READY=1`, logging instead of failing since kumad keeps running either
+    /// way.
+    pub(crate) fn notify(&self, state: &str) {
+        if let Err(e) = self.socket.send_to_addr(state.as_bytes(), &self.addr) {
+            warn!(state, err = %e, "Failed to notify systemd");
+        }
+    }
+
+    /// Spawns the task pinging systemd's watchdog while every one of `workers` made progress
+    /// within `max_idle`, until shutdown. `None` when systemd doesn't watch kumad.
+    pub(crate) fn spawn_watchdog(
+        &self,
+        workers: Workers,
+        max_idle: Duration,
+        shutdown_token: CancellationToken,
+    ) -> Option<JoinHandle<()>> {
+        let interval = watchdog_interval()? / 2;
+        Some(tokio::spawn(self.clone().watchdog(
+            workers,
+            interval,
+            max_idle,
+            shutdown_token,
+        )))
+    }
+
+    #[instrument(name = "watchdog", skip_all)]
+    async fn watchdog(
+        self,
+        workers: Workers,
+        interval: Duration,
+        max_idle: Duration,
+        shutdown_token: CancellationToken,
+    ) {
+        // workers that haven't made progress yet count from when the watchdog started
+        let started = SystemTime::now();
+        let mut ticks = tokio::time::interval(interval);
+        let mut idle_before = Vec::new();
+        loop {
+            select! {
+                () = shutdown_token.cancelled() => break,
+                _ = ticks.tick() => {}
+            }
+
+            let now = SystemTime::now();
+            let idle: Vec<_> = workers
+                .last_progress_at()
+                .into_iter()
+                .filter(|(_, last)| {
+                    now.duration_since(last.unwrap_or(started))
+                        .is_ok_and(|since| since > max_idle)
+                })
+                .map(|(worker, _)| worker)
+                .collect();
+
+            if idle.is_empty() {
+                self.notify("WATCHDOG=1");
+            } else if idle != idle_before {
+                warn!(
+                    workers = %idle.join(", "),
+                    max_idle = %humantime::format_duration(max_idle),
+                    "Workers stopped making progress, no longer pinging the systemd watchdog"
+                );
+            }
+            idle_before = idle;
+        }
+        info!("Watchdog task stopped");
+    }
+}
+
+/// Interval systemd expects watchdog pings at, from `WATCHDOG_USEC` when `WATCHDOG_PID` is unset
+/// or kumad's own pid
+fn watchdog_interval() -> Option<Duration> {
+    if let Some(pid) = std::env::var_os("WATCHDOG_PID") {
+        if pid.to_str()?.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+    let usec = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    Some(Duration::from_micros(usec)).filter(|interval| !interval.is_zero())
+}
+
+#[cfg(target_os = "linux")]
+fn abstract_addr(name: &str) -> std::io::Result<SocketAddr> {
+    use std::os::linux::net::SocketAddrExt as _;
+
+    SocketAddr::from_abstract_name(name)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn abstract_addr(_name: &str) -> std::io::Result<SocketAddr> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "abstract sockets are only supported on linux",
+    ))
+}
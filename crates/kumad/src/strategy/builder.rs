@@ -1,44 +1,153 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
-use color_eyre::eyre::{self};
-use tokio::sync::broadcast;
+use color_eyre::eyre::{self, WrapErr as _};
+use num_bigint::BigUint;
+use tokio::sync::{broadcast, watch};
 use tokio_util::sync::CancellationToken;
 
-use kuma_core::{database, signals, state::pair::PairStateStream, strategy};
+use kuma_core::{
+    collector,
+    config::LiveSettings,
+    database::CurveRepository,
+    signals,
+    state::{
+        alignment::Alignment,
+        balances::SharedInventory,
+        header::{BlockHeader, Confirmation, FinalityHeads},
+        pair::PairStateStream,
+    },
+    store::Store,
+    strategy,
+    verifier::PoolVerifier,
+};
 
 use super::{Handle, Worker};
+use crate::{alert, control, heartbeat, mempool};
 
 pub struct Builder {
+    /// Strategy id the worker logs under, see `StrategyConfig::id`
+    pub id: String,
     pub strategy: strategy::CrossChainSingleHop,
     pub slow_stream: PairStateStream,
     pub fast_stream: PairStateStream,
+    pub fast_header_rx: watch::Receiver<Option<BlockHeader>>,
+    pub slow_finality_rx: watch::Receiver<FinalityHeads>,
+    /// Signals are held until their slow block meets this
+    pub slow_confirmation: Confirmation,
     pub slow_block_time: Duration,
-    pub db: database::Handle,
+    pub fast_block_time: Duration,
+    /// Warn when acting on a block takes longer than this fraction of its chain's block time
+    pub max_block_latency_fraction: f64,
+    /// Skip fast states received more than this many fast block times ago
+    pub max_fast_state_age_blocks: u32,
+    pub slow_staleness: collector::Staleness,
+    pub fast_staleness: collector::Staleness,
+    /// Settings reloaded from the config file while the worker runs
+    pub settings: watch::Receiver<LiveSettings>,
+    /// Reject signals whose pools deviate more than this many bps from on-chain state
+    pub max_onchain_deviation_bps: Option<u64>,
+    /// Reject signals whose slow and fast blocks arrived further apart than this
+    pub max_block_skew: Option<Duration>,
+    /// Drop signals when a competing swap on the fast pool is pending
+    pub mempool: Option<mempool::Subscription>,
+    /// Where spot prices and signals are written
+    pub store: Arc<dyn Store>,
+    /// Where every slow block's precompute curves are written, if they're recorded
+    pub curves: Option<CurveRepository>,
+    /// Operator switches pausing signal generation or emission, and the min profit to emit
+    pub controls: control::Switches,
+    /// Alerts about profitable signals and stale chains
+    pub alerts: alert::Alerts,
+    /// Beaten on every block the worker handles
+    pub heartbeat: heartbeat::Heartbeat,
+    /// The account's inventories on the slow and fast chain, capping the strategy's configured
+    /// ones
+    pub slow_inventory: SharedInventory,
+    pub fast_inventory: SharedInventory,
+    /// Least gas token balance the account must hold on the slow and fast chain for signals to
+    /// be generated, unchecked when `None`
+    pub min_gas_balances: (Option<BigUint>, Option<BigUint>),
 }
 
 impl Builder {
     pub fn build(self) -> eyre::Result<Handle> {
         let Self {
+            id,
             strategy,
             slow_stream,
             fast_stream,
+            fast_header_rx,
+            slow_finality_rx,
+            slow_confirmation,
             slow_block_time: slow_block_time_ms,
-            db,
+            fast_block_time,
+            max_block_latency_fraction,
+            max_fast_state_age_blocks,
+            slow_staleness,
+            fast_staleness,
+            settings,
+            max_onchain_deviation_bps,
+            max_block_skew,
+            mempool,
+            store,
+            curves,
+            controls,
+            alerts,
+            heartbeat,
+            slow_inventory,
+            fast_inventory,
+            min_gas_balances,
         } = self;
 
+        let onchain_verifiers = max_onchain_deviation_bps
+            .map(|max_deviation_bps| {
+                eyre::Ok((
+                    PoolVerifier::new(&strategy.slow_chain, max_deviation_bps)?,
+                    PoolVerifier::new(&strategy.fast_chain, max_deviation_bps)?,
+                ))
+            })
+            .transpose()
+            .wrap_err("failed to set up on-chain verifiers")?;
+
         // Create broadcast channel for signals
         let (signal_tx, signal_rx) = broadcast::channel::<signals::CrossChainSingleHop>(256);
 
         let shutdown_token = CancellationToken::new();
+        let max_inventories = (
+            strategy.slow_inventory.clone(),
+            strategy.fast_inventory.clone(),
+        );
 
         let worker = Worker {
+            id,
             strategy,
             slow_stream,
             fast_stream,
+            fast_header_rx,
+            slow_finality_rx,
+            slow_confirmation,
             signal_tx,
             shutdown_token: shutdown_token.clone(),
             slow_block_time: slow_block_time_ms,
-            db,
+            fast_block_time,
+            max_block_latency_fraction,
+            max_fast_state_age_blocks,
+            slow_staleness,
+            fast_staleness,
+            settings,
+            onchain_verifiers,
+            alignment: Alignment::new(max_block_skew),
+            lags: Default::default(),
+            mempool,
+            store,
+            curves,
+            controls,
+            alerts,
+            heartbeat,
+            inventories: (slow_inventory, fast_inventory),
+            max_inventories,
+            min_gas_balances,
+            slow_gas_funded: false,
         };
 
         let worker_handle = tokio::task::spawn(async move { worker.run().await });
@@ -1,24 +1,50 @@
 //! Strategy module for managing cross-chain arbitrage signal generation
 
-use std::{pin::Pin, time::Duration};
+use std::{collections::HashMap, pin::Pin, sync::Arc, time::Duration};
 
-use color_eyre::eyre::{self, WrapErr as _, eyre};
+use color_eyre::eyre::{self, OptionExt as _, WrapErr as _, eyre};
 use futures::{Future, FutureExt as _, stream::FuturesUnordered};
-use tokio::{select, sync::broadcast, time::Instant};
+use num_bigint::BigUint;
+use tokio::{
+    select,
+    sync::{broadcast, watch},
+    time::Instant,
+};
 use tokio_stream::StreamExt;
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, info, instrument, trace};
+use tracing::{debug, error, info, instrument, trace, warn};
+use tycho_common::simulation::protocol_sim::ProtocolSim;
 
 use kuma_core::{
-    database, signals,
+    collector,
+    config::LiveSettings,
+    database::CurveRepository,
+    signals,
     spot_prices::SpotPrices,
-    state::pair::PairStateStream,
+    state::{
+        PoolId,
+        alignment::Alignment,
+        balances::{Inventory, SharedInventory},
+        header::{BlockHeader, Confirmation, FinalityHeads},
+        lag::BlockLag,
+        pair::{Pair, PairState, PairStateStream},
+    },
+    store::Store,
     strategy::{self, Precomputes},
+    verifier::PoolVerifier,
+};
+
+use crate::{
+    alert::{self, AlertKind},
+    control, heartbeat, mempool,
 };
 
 pub use builder::Builder;
 mod builder;
 
+/// Buffered spot prices that are flushed without waiting for the next slow block
+const MAX_PENDING_SPOT_PRICES: usize = 256;
+
 pub struct Handle {
     shutdown_token: CancellationToken,
     worker_handle: Option<tokio::task::JoinHandle<eyre::Result<()>>>,
@@ -73,39 +99,83 @@ impl Future for Handle {
 }
 
 struct Worker {
+    id: String,
     // TODO: set up strategy object from core
     strategy: strategy::CrossChainSingleHop,
     slow_stream: PairStateStream,
     fast_stream: PairStateStream,
+    fast_header_rx: watch::Receiver<Option<BlockHeader>>,
+    slow_finality_rx: watch::Receiver<FinalityHeads>,
+    slow_confirmation: Confirmation,
     signal_tx: broadcast::Sender<signals::CrossChainSingleHop>,
     shutdown_token: CancellationToken,
     slow_block_time: Duration,
-    db: database::Handle,
+    fast_block_time: Duration,
+    /// Warn when acting on a block takes longer than this fraction of its chain's block time
+    max_block_latency_fraction: f64,
+    /// Fast states received more than this many fast block times ago aren't acted on
+    max_fast_state_age_blocks: u32,
+    slow_staleness: collector::Staleness,
+    fast_staleness: collector::Staleness,
+    /// Settings reloaded from the config file, applied before each slow block's precompute
+    settings: watch::Receiver<LiveSettings>,
+    /// On-chain sanity checks for the slow and fast pools of a signal, if enabled
+    onchain_verifiers: Option<(PoolVerifier, PoolVerifier)>,
+    /// When the latest slow and fast states arrived, to bound how far apart a signal's blocks are
+    alignment: Alignment,
+    /// Blocks of the slow and fast chain skipped between the states read
+    lags: (BlockLag, BlockLag),
+    /// Competing swaps on the fast chain, if the mempool watcher is enabled
+    mempool: Option<mempool::Subscription>,
+    store: Arc<dyn Store>,
+    /// Where precompute curves are written, if they're recorded
+    curves: Option<CurveRepository>,
+    controls: control::Switches,
+    alerts: alert::Alerts,
+    heartbeat: heartbeat::Heartbeat,
+    /// The account's live inventories on the slow and fast chain
+    inventories: (SharedInventory, SharedInventory),
+    /// The configured inventories, the most the strategy trades on the slow and fast chain
+    max_inventories: ((BigUint, BigUint), (BigUint, BigUint)),
+    /// Least gas token balance required on the slow and fast chain, unchecked when `None`
+    min_gas_balances: (Option<BigUint>, Option<BigUint>),
+    /// Whether the latest slow state had the gas funds required
+    slow_gas_funded: bool,
 }
 
 impl Worker {
-    #[instrument(name = "strategy_worker", skip(self))]
+    #[instrument(name = "strategy_worker", skip(self), fields(strategy = %self.id))]
     pub async fn run(mut self) -> eyre::Result<()> {
         info!("Starting strategy worker");
 
         let submission_delay = self.slow_block_time.mul_f64(0.75);
         let mut submission_deadline = None;
         let mut precompute: Option<Precomputes> = None;
+        // the signal to emit, with when its fast block was received
         let mut curr_signal = None;
         let mut db_writes: FuturesUnordered<
             Pin<Box<dyn Future<Output = eyre::Result<()>> + Send>>,
         > = FuturesUnordered::new();
+        let mut mempool = self.mempool.take();
+        // spot prices written as one batch per slow block
+        let mut pending_spot_prices: Vec<SpotPrices> = Vec::new();
+        // the precomputed slow block's spot prices, kept to be stored along with its first signal
+        let mut slow_spot_prices: Option<SpotPrices> = None;
 
         // biased loop
         // 1. shutdown signal
+        // 2. competing swap pending on the signal's fast pool - drop the signal
         // 2. timer ended and there's a signal to emit - populate the signal emission
+        // 2. slow chain confirmed a held signal's block - emit it
         // 2. slow chain updates
         //  1. set up signal generation timer
         //  2. precompute
-        //  3. save spot prices to db
+        //  3. save the last block's spot prices to db with the fast blocks' since, keep this one's
+        //     for its signal
         // 3. fast chain updates
-        //  1. try to generate signal from precompute
-        //  2. overwrite current signal
+        //  1. buffer spot prices
+        //  2. try to generate signal from precompute
+        //  3. overwrite current signal
         // 4. db write
         // 5. emit signal
 
@@ -118,6 +188,28 @@ impl Worker {
                     break Ok(());
                 }
 
+                // drop the signal if a pending swap would move its fast pool first
+                Ok(swap) = async {
+                    match mempool.as_mut() {
+                        Some(mempool) => mempool.recv().await,
+                        None => futures::future::pending().await,
+                    }
+                }, if curr_signal.is_some() => {
+                    let (signal, _) = curr_signal.as_ref().expect("Signal checked to be Some");
+                    if swap.pool_id == signal.fast_pool_id {
+                        warn!(
+                            %signal,
+                            tx.hash = %swap.tx_hash,
+                            size = ?swap.size,
+                            "Competing swap pending on the fast pool, dropping signal"
+                        );
+                        curr_signal = None;
+                        if let Some(mempool) = mempool.as_ref() {
+                            mempool.watch(None);
+                        }
+                    }
+                }
+
                 // emit signal when timer ends if one exists
                 _ = async {
                     if let Some(deadline) = submission_deadline {
@@ -126,14 +218,35 @@ impl Worker {
                         futures::future::pending().await
                     }
                 }, if curr_signal.is_some() => {
-                    let signal = curr_signal.take().expect("Signal checked to be Some");
-                    debug!(%signal, "📡 Emitting signal");
+                    if !curr_signal.as_ref().is_some_and(|(signal, _)| self.slow_confirmed(signal)) {
+                        debug!(
+                            confirmation = ?self.slow_confirmation,
+                            "Slow block isn't confirmed yet, holding signal"
+                        );
+                        submission_deadline = None;
+                        continue;
+                    }
+                    let (signal, fast_received_at) = curr_signal.take().expect("Signal checked to be Some");
+                    self.emit_signal(signal, fast_received_at, mempool.as_ref())?;
+                }
 
-                    self.signal_tx.send(signal).wrap_err("Signal sent")?;
+                // emit a held signal once its slow block is confirmed
+                Ok(()) = self.slow_finality_rx.changed(), if curr_signal.is_some() && submission_deadline.is_none() => {
+                    if curr_signal.as_ref().is_some_and(|(signal, _)| self.slow_confirmed(signal)) {
+                        let (signal, fast_received_at) = curr_signal.take().expect("Signal checked to be Some");
+                        self.emit_signal(signal, fast_received_at, mempool.as_ref())?;
+                    }
                 }
 
                 // Handle slow chain updates
                 Some(slow_state) = self.slow_stream.next() => {
+                    self.heartbeat.beat();
+                    self.record_block_lag(Stage::Precompute, slow_state.block_height);
+                    self.slow_gas_funded = self.has_gas_funds(Stage::Precompute, &slow_state);
+                    if self.settings.has_changed().unwrap_or(false) {
+                        self.apply_settings();
+                    }
+
                     // Start timer for 75% of block time
                     submission_deadline = Some(Instant::now() + submission_delay);
 
@@ -143,58 +256,172 @@ impl Worker {
                     );
 
                     // Generate precomputes
-                    let new_precompute = self.strategy.precompute(slow_state);
+                    let started = Instant::now();
+                    let slow_received_at = slow_state.received_at;
+                    let (slow_timestamp, slow_base_fee) = (slow_state.timestamp, slow_state.base_fee_per_gas);
+                    self.size_inventories();
+                    self.alignment.record_slow(&slow_state);
+                    let new_precompute = self.strategy.precompute(&slow_state);
+                    metrics::histogram!("kuma_strategy_precompute_duration_seconds", "strategy" => self.id.clone())
+                        .record(started.elapsed().as_secs_f64());
+                    self.record_block_latency(Stage::Precompute, new_precompute.block_height, slow_received_at);
 
                     debug!(
                         block.height = new_precompute.block_height,
+                        block.timestamp = ?slow_timestamp,
+                        block.base_fee_per_gas = ?slow_base_fee,
                         "✅ Precomputed trade sizes for slow chain"
                     );
 
-                    // Write spot prices to db
-                    let spot_prices = SpotPrices::from_precompute(
+                    // Write the previous slow block's spot prices, unless a signal stored them, and
+                    // the fast blocks' since
+                    let previous = slow_spot_prices.replace(SpotPrices::from_precompute(
                         &new_precompute,
                         self.strategy.slow_chain.clone(),
                         self.strategy.slow_pair.clone()
-                    );
-
-                    let repo = self.db.spot_price_repository();
-                    db_writes.push(async move {
-                        repo.insert(spot_prices).await.map_err(|e| eyre!("failed to write spot prices to db: {e:}"))
-                    }.boxed());
+                    ));
+                    pending_spot_prices.extend(previous);
+                    if !pending_spot_prices.is_empty() {
+                        db_writes.push(self.write_spot_prices(std::mem::take(&mut pending_spot_prices)));
+                    }
+                    if let Some(write) = self.write_curves(&new_precompute) {
+                        db_writes.push(write);
+                    }
 
                     // Save precompute
                     precompute = Some(new_precompute);
                 }
 
-                // TODO: handle for processing fast blocks
-                // 1. update the fast current block
-                // 2. write to db
-                // 3. log a trace
-
                 // Handle timer expiration for signal generation
                 Some(fast_state) = self.fast_stream.next() => {
+                    self.heartbeat.beat();
+                    self.record_block_lag(Stage::SignalEmission, fast_state.block_height);
+                    let fast_spot_prices = self.strategy.fast_spot_prices.sorted(&fast_state, &self.strategy.fast_pair);
+                    if let Some(spot_prices) = SpotPrices::from_pair_state(
+                        &fast_state,
+                        &fast_spot_prices,
+                        self.strategy.fast_chain.clone(),
+                        self.strategy.fast_pair.clone(),
+                    ) {
+                        pending_spot_prices.push(spot_prices);
+                        // don't grow without bound while the slow chain stalls
+                        if pending_spot_prices.len() >= MAX_PENDING_SPOT_PRICES {
+                            db_writes.push(self.write_spot_prices(std::mem::take(&mut pending_spot_prices)));
+                        }
+                    }
+
+                    let max_block_staleness = self.settings.borrow().max_block_staleness;
+                    if let Some(stale) = [&self.slow_staleness, &self.fast_staleness]
+                        .into_iter()
+                        .find(|staleness| staleness.exceeds(max_block_staleness))
+                    {
+                        warn!(
+                            chain = %stale.chain(),
+                            staleness = ?stale.elapsed(),
+                            max_staleness = ?max_block_staleness,
+                            "Chain state is stale, skipping signal generation"
+                        );
+                        let since = match stale.elapsed() {
+                            Some(elapsed) => {
+                                let elapsed = Duration::from_secs(elapsed.as_secs());
+                                format!("for {}", humantime::format_duration(elapsed))
+                            }
+                            None => "yet".to_owned(),
+                        };
+                        self.alerts.send(
+                            AlertKind::StaleChain,
+                            format!(
+                                "⏳ {} hasn't had a block update {since}, {} isn't generating signals",
+                                stale.chain(),
+                                self.id
+                            ),
+                        );
+                        continue;
+                    }
+
+                    if *self.controls.strategy.borrow() {
+                        trace!(block.height = fast_state.block_height, "Strategy is paused, skipping signal generation");
+                        continue;
+                    }
+
+                    if self.fast_state_is_stale(fast_state.block_height, fast_state.received_at) {
+                        continue;
+                    }
+
+                    // the slow chain already warned when its state came in
+                    if !(self.slow_gas_funded && self.has_gas_funds(Stage::SignalEmission, &fast_state)) {
+                        continue;
+                    }
+
                     if let Some(precompute) = precompute.as_ref() {
                         // Step 3: Read latest fast chain state and generate signal
                         // TODO: fix this to use the curr fast state object
                         let (slow_height, fast_height) = (precompute.block_height, fast_state.block_height);
+                        let fast_received_at = fast_state.received_at;
+                        let (fast_timestamp, fast_base_fee) = (fast_state.timestamp, fast_state.base_fee_per_gas);
+
+                        self.size_inventories();
+                        self.alignment.record_fast(&fast_state);
+                        match self.strategy.generate_signal(precompute, &fast_state) {
+                            Ok(mut signal) => {
+                                let aligned = self.alignment.annotate(&mut signal);
+                                if let Some(skew_ms) = signal.block_skew_ms {
+                                    metrics::histogram!("kuma_strategy_block_skew_seconds", "strategy" => self.id.clone())
+                                        .record(skew_ms as f64 / 1000.0);
+                                }
+                                if let Err(e) = aligned {
+                                    warn!(%signal, error = %e, "Signal's blocks are too far apart, dropping it");
+                                    metrics::counter!("kuma_strategy_misaligned_signals_total", "strategy" => self.id.clone())
+                                        .increment(1);
+                                    continue;
+                                }
+
+                                if let Err(e) = self.verify_onchain(&signal, precompute, &fast_state.states).await {
+                                    warn!(
+                                        %signal,
+                                        error = %e,
+                                        "Signal failed on-chain sanity check, dropping it"
+                                    );
+                                    continue;
+                                }
 
-                        match self.strategy.generate_signal(precompute, fast_state) {
-                            Ok(signal) => {
                                 info!(
                                     %signal,
+                                    fast_block.timestamp = ?fast_timestamp,
+                                    fast_block.base_fee_per_gas = ?fast_base_fee,
+                                    block_skew_ms = ?signal.block_skew_ms,
                                     "📡 Generated cross-chain signal"
                                 );
+                                metrics::counter!("kuma_strategy_signals_total", "strategy" => self.id.clone())
+                                    .increment(1);
+                                self.alerts.signal(&self.id, &signal);
 
-                                curr_signal = Some(signal.clone());
+                                if let Some(mempool) = mempool.as_ref() {
+                                    mempool.watch(watched_pool(&signal, &fast_state.states));
+                                }
+                                curr_signal = Some((signal.clone(), fast_received_at));
 
-                                // Save generated signal to db and update it for emission
-                                let repo = self.db.signal_repository();
+                                // Save generated signal to db along with its blocks' spot prices, so
+                                // neither is stored without the other
+                                let (mut spot_prices, rest): (Vec<_>, Vec<_>) = std::mem::take(&mut pending_spot_prices)
+                                    .into_iter()
+                                    .partition(|spot_prices| {
+                                        spot_prices.chain == signal.fast_chain
+                                            && spot_prices.block_height == signal.fast_height
+                                    });
+                                pending_spot_prices = rest;
+                                if slow_spot_prices
+                                    .as_ref()
+                                    .is_some_and(|slow| slow.block_height == signal.slow_height)
+                                {
+                                    spot_prices.extend(slow_spot_prices.take());
+                                }
+                                let store = Arc::clone(&self.store);
                                 db_writes.push(async move {
-                                    repo.insert(signal.clone()).await.map_err(|e| {
-                                        eyre!("failed to write signal to db: {e:}")
-                                    })
+                                    store.insert_signal(signal, spot_prices)
+                                        .await
+                                        .map_err(|e| eyre!("failed to write signal to db: {e:}"))
                                 }.boxed());
-                                panic!("Signal generated")
                             }
                             Err(e) => {
                                 debug!(
@@ -218,4 +445,586 @@ impl Worker {
             }
         }
     }
+
+    /// Applies the latest reloaded settings to the strategy. Signals are generated from a slow
+    /// block's precompute, so this happens between precomputes to keep both on the same settings.
+    fn apply_settings(&mut self) {
+        let settings = *self.settings.borrow_and_update();
+        self.strategy.max_slippage_bps = settings.max_slippage_bps;
+        self.strategy.congestion_risk_discount_bps = settings.congestion_risk_discount_bps;
+        info!(?settings, "Applied reloaded strategy settings");
+    }
+
+    /// Sizes the strategy to the configured inventories, capped to what the account has available
+    /// once the collectors read its balances.
+    fn size_inventories(&mut self) {
+        let cap = |inventory: &Inventory, pair: &Pair, (max_a, max_b): &(BigUint, BigUint)| {
+            (
+                inventory.cap(pair.token_a(), max_a),
+                inventory.cap(pair.token_b(), max_b),
+            )
+        };
+        self.strategy.slow_inventory = cap(
+            &self.inventories.0.get(),
+            &self.strategy.slow_pair,
+            &self.max_inventories.0,
+        );
+        self.strategy.fast_inventory = cap(
+            &self.inventories.1.get(),
+            &self.strategy.fast_pair,
+            &self.max_inventories.1,
+        );
+    }
+
+    fn write_spot_prices(
+        &self,
+        spot_prices: Vec<SpotPrices>,
+    ) -> Pin<Box<dyn Future<Output = eyre::Result<()>> + Send>> {
+        let store = Arc::clone(&self.store);
+        async move {
+            let count = spot_prices.len();
+            store
+                .insert_spot_prices(spot_prices)
+                .await
+                .map_err(|e| eyre!("failed to write {count} spot prices to db: {e:}"))
+        }
+        .boxed()
+    }
+
+    /// Writes the precompute's curves if they're recorded
+    fn write_curves(
+        &self,
+        precompute: &Precomputes,
+    ) -> Option<Pin<Box<dyn Future<Output = eyre::Result<()>> + Send>>> {
+        let curves_repository = self.curves.clone()?;
+        let chain = self.strategy.slow_chain.clone();
+        let curves = precompute.curves();
+        Some(
+            async move {
+                curves_repository
+                    .insert_many(&chain, &curves)
+                    .await
+                    .map_err(|e| {
+                        eyre!(
+                            "failed to write {} precompute curves to db: {e:}",
+                            curves.len()
+                        )
+                    })
+            }
+            .boxed(),
+        )
+    }
+
+    /// Whether the signal's slow block meets the configured confirmation
+    fn slow_confirmed(&self, signal: &signals::CrossChainSingleHop) -> bool {
+        self.slow_finality_rx
+            .borrow()
+            .is_confirmed(signal.slow_height, self.slow_confirmation)
+    }
+
+    fn emit_signal(
+        &self,
+        signal: signals::CrossChainSingleHop,
+        fast_received_at: std::time::Instant,
+        mempool: Option<&mempool::Subscription>,
+    ) -> eyre::Result<()> {
+        let fast_base_fee = self
+            .fast_header_rx
+            .borrow()
+            .as_ref()
+            .and_then(|header| header.base_fee_per_gas);
+        if let Some(mempool) = mempool {
+            mempool.watch(None);
+        }
+        if *self.controls.strategy.borrow() || *self.controls.execution.borrow() {
+            info!(%signal, "Signal emission is paused, dropping signal");
+            return Ok(());
+        }
+        if let Some(min_profit_bps) = *self.controls.min_profit_bps.borrow() {
+            let profit_bps = signal.expected_profit_bps();
+            if profit_bps < min_profit_bps {
+                info!(
+                    %signal,
+                    profit_bps,
+                    min_profit_bps,
+                    "Signal is below the min profit threshold, dropping signal"
+                );
+                return Ok(());
+            }
+        }
+
+        debug!(%signal, fast.base_fee_per_gas = ?fast_base_fee, "📡 Emitting signal");
+        self.record_block_latency(Stage::SignalEmission, signal.fast_height, fast_received_at);
+
+        self.signal_tx.send(signal).wrap_err("Signal sent")?;
+        Ok(())
+    }
+
+    /// Whether the fast state at `height`, received at `received_at`, is older than
+    /// `max_fast_state_age_blocks` fast block times, counting the skipped signal when it is.
+    fn fast_state_is_stale(&self, height: u64, received_at: std::time::Instant) -> bool {
+        let age = received_at.elapsed();
+        let max_age = self.fast_block_time * self.max_fast_state_age_blocks;
+        if age <= max_age {
+            return false;
+        }
+
+        warn!(
+            block.height = height,
+            ?age,
+            ?max_age,
+            "Fast chain state is stale, skipping signal generation"
+        );
+        metrics::counter!("kuma_strategy_stale_fast_states_total", "strategy" => self.id.clone())
+            .increment(1);
+        true
+    }
+
+    /// Whether the account holds the configured minimum of gas on the chain of `stage`'s `state`,
+    /// counting the block when it doesn't.
+    fn has_gas_funds(&self, stage: Stage, state: &PairState) -> bool {
+        let (min_gas_balance, chain) = match stage {
+            Stage::Precompute => (&self.min_gas_balances.0, &self.strategy.slow_chain),
+            Stage::SignalEmission => (&self.min_gas_balances.1, &self.strategy.fast_chain),
+        };
+        let Some(min_gas_balance) = min_gas_balance else {
+            return true;
+        };
+        if state.has_gas_funds(min_gas_balance) {
+            return true;
+        }
+
+        warn!(
+            %chain,
+            block.height = state.block_height,
+            native_balance = ?state.native_balance,
+            %min_gas_balance,
+            "Account is short of gas, skipping signal generation"
+        );
+        metrics::counter!("kuma_strategy_gas_short_blocks_total", "strategy" => self.id.clone(), "chain" => chain.to_string())
+            .increment(1);
+        false
+    }
+
+    /// Records how long after its block was received `stage` finished, warning when that's more
+    /// than `max_block_latency_fraction` of the chain's block time.
+    fn record_block_latency(&self, stage: Stage, height: u64, received_at: std::time::Instant) {
+        let latency = received_at.elapsed();
+        let (chain, block_time) = match stage {
+            Stage::Precompute => (&self.strategy.slow_chain, self.slow_block_time),
+            Stage::SignalEmission => (&self.strategy.fast_chain, self.fast_block_time),
+        };
+        metrics::histogram!(stage.metric(), "chain" => chain.to_string())
+            .record(latency.as_secs_f64());
+
+        let max_latency = block_time.mul_f64(self.max_block_latency_fraction);
+        if latency > max_latency {
+            warn!(
+                %chain,
+                block.height = height,
+                ?stage,
+                ?latency,
+                ?max_latency,
+                "Acting on the block took a large share of its block time"
+            );
+        }
+    }
+
+    /// Counts the blocks skipped since the previous state read from the stage's chain, and warns
+    /// when most recent reads skipped some: the strategy can't keep up with the chain.
+    fn record_block_lag(&mut self, stage: Stage, height: u64) {
+        let (lag, chain) = match stage {
+            Stage::Precompute => (&mut self.lags.0, &self.strategy.slow_chain),
+            Stage::SignalEmission => (&mut self.lags.1, &self.strategy.fast_chain),
+        };
+        let was_behind = lag.is_behind();
+        let skipped = lag.record(height);
+        if skipped > 0 {
+            debug!(%chain, block.height = height, skipped, "Skipped blocks since the last state read");
+        }
+        metrics::counter!("kuma_strategy_skipped_blocks_total", "strategy" => self.id.clone(), "chain" => chain.to_string())
+            .increment(skipped);
+        metrics::gauge!("kuma_strategy_behind", "strategy" => self.id.clone(), "chain" => chain.to_string())
+            .set(f64::from(u8::from(lag.is_behind())));
+
+        match (was_behind, lag.is_behind()) {
+            (false, true) => warn!(
+                %chain,
+                block.height = height,
+                "Strategy keeps missing blocks, most recent states read skipped some"
+            ),
+            (true, false) => {
+                info!(%chain, block.height = height, "Strategy caught up with the chain")
+            }
+            _ => {}
+        }
+    }
+
+    /// Cross-checks the signal's pools against on-chain state, if verification is enabled.
+    async fn verify_onchain(
+        &self,
+        signal: &signals::CrossChainSingleHop,
+        precompute: &Precomputes,
+        fast_states: &HashMap<PoolId, Arc<dyn ProtocolSim>>,
+    ) -> eyre::Result<()> {
+        let Some((slow_verifier, fast_verifier)) = self.onchain_verifiers.as_ref() else {
+            return Ok(());
+        };
+
+        let slow_component = signal
+            .slow_protocol_component
+            .as_ref()
+            .ok_or_eyre("signal is missing the slow pool's component")?;
+        let fast_component = signal
+            .fast_protocol_component
+            .as_ref()
+            .ok_or_eyre("signal is missing the fast pool's component")?;
+
+        let slow_price = precompute
+            .sorted_spot_prices
+            .iter()
+            .find_map(|(id, price)| (id == &signal.slow_pool_id).then_some(*price))
+            .ok_or_eyre("no simulated spot price for the slow pool")?;
+        let fast_price = fast_states
+            .get(&signal.fast_pool_id)
+            .ok_or_eyre("no simulated state for the fast pool")?
+            .spot_price(signal.fast_pair.token_a(), signal.fast_pair.token_b())
+            .wrap_err("failed to compute simulated spot price for the fast pool")?;
+
+        tokio::try_join!(
+            slow_verifier.verify(
+                slow_component,
+                &signal.slow_pair,
+                slow_price,
+                signal.slow_height
+            ),
+            fast_verifier.verify(
+                fast_component,
+                &signal.fast_pair,
+                fast_price,
+                signal.fast_height
+            ),
+        )?;
+
+        Ok(())
+    }
+}
+
+/// What a block's latency is measured up to
+#[derive(Debug, Clone, Copy)]
+enum Stage {
+    /// Precompute finished on a slow block
+    Precompute,
+    /// A signal generated on a fast block was emitted
+    SignalEmission,
+}
+
+impl Stage {
+    fn metric(self) -> &'static str {
+        match self {
+            Self::Precompute => "kuma_strategy_slow_block_to_precompute_seconds",
+            Self::SignalEmission => "kuma_strategy_fast_block_to_signal_seconds",
+        }
+    }
+}
+
+/// The signal's fast pool, as watched for competing swaps
+fn watched_pool(
+    signal: &signals::CrossChainSingleHop,
+    fast_states: &HashMap<PoolId, Arc<dyn ProtocolSim>>,
+) -> Option<mempool::WatchedPool> {
+    let component = signal.fast_protocol_component.as_ref()?;
+    let spot_price = fast_states
+        .get(&signal.fast_pool_id)?
+        .spot_price(signal.fast_pair.token_a(), signal.fast_pair.token_b())
+        .ok()?;
+
+    Some(mempool::WatchedPool::new(
+        signal.fast_pool_id.clone(),
+        &component.protocol_system,
+        signal.fast_pair.clone(),
+        spot_price,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr as _;
+
+    use alloy::primitives::{B256, U256};
+    use kuma_core::{
+        chain::Chain,
+        state::{PoolFilter, block::Block},
+        store::NoopStore,
+    };
+    use tycho_common::{Bytes, models::token::Token};
+    use tycho_simulation::{
+        evm::protocol::uniswap_v2::state::UniswapV2State,
+        protocol::models::{ProtocolComponent, Update},
+    };
+
+    use super::*;
+
+    const SLOW_POOL: &str = "0x0000000000000000000000000000000000001000";
+    const FAST_POOL: &str = "0x0000000000000000000000000000000000002000";
+    const SLOW_HEIGHT: u64 = 2000;
+    /// Long enough for the worker to have emitted a signal it wasn't holding back
+    const QUIET_PERIOD: Duration = Duration::from_millis(200);
+
+    fn token(address: &str, symbol: &str, chain: tycho_common::models::Chain) -> Token {
+        Token::new(
+            &Bytes::from_str(address).unwrap(),
+            symbol,
+            18,
+            0,
+            &[Some(0)],
+            chain,
+            100,
+        )
+    }
+
+    fn pair(chain: tycho_common::models::Chain) -> Pair {
+        Pair::new(
+            token("0x0000000000000000000000000000000000000001", "PEPE", chain),
+            token("0x0000000000000000000000000000000000000002", "WETH", chain),
+        )
+    }
+
+    fn whole(amount: u64) -> BigUint {
+        BigUint::from(amount) * BigUint::from(10u64).pow(18)
+    }
+
+    /// A block of `chain` at `height` with a single Uniswap V2 pool of `pair` holding the
+    /// reserves, in whole tokens
+    fn block(
+        chain: tycho_common::models::Chain,
+        pair: &Pair,
+        height: u64,
+        pool: &str,
+        reserves: (u64, u64),
+    ) -> Block {
+        let reserve = |amount| U256::from_str(&whole(amount).to_string()).unwrap();
+        let state: Box<dyn ProtocolSim> = Box::new(UniswapV2State::new(
+            reserve(reserves.0),
+            reserve(reserves.1),
+        ));
+        let component = ProtocolComponent::new(
+            Bytes::from_str(pool).unwrap(),
+            "uniswap_v2".to_string(),
+            "uniswap_v2_pool".to_string(),
+            chain,
+            vec![pair.token_a().clone(), pair.token_b().clone()],
+            vec![],
+            HashMap::new(),
+            Bytes::default(),
+            Default::default(),
+        );
+
+        Block::new(Update {
+            block_number_or_timestamp: height,
+            states: HashMap::from([(pool.to_string(), state)]),
+            new_pairs: HashMap::from([(pool.to_string(), component)]),
+            removed_pairs: HashMap::new(),
+        })
+    }
+
+    /// A running worker whose chains are fed through the `*_block_tx` channels
+    struct Harness {
+        handle: Handle,
+        signal_rx: broadcast::Receiver<signals::CrossChainSingleHop>,
+        slow_block_tx: watch::Sender<Arc<Option<Block>>>,
+        fast_block_tx: watch::Sender<Arc<Option<Block>>>,
+        slow_finality_tx: watch::Sender<FinalityHeads>,
+        slow_pair: Pair,
+        fast_pair: Pair,
+        _settings_tx: watch::Sender<LiveSettings>,
+        _controls: control::Controls,
+    }
+
+    impl Harness {
+        fn spawn(slow_confirmation: Confirmation, mempool: Option<mempool::Subscription>) -> Self {
+            let slow_chain = Chain::new(
+                "ethereum",
+                "http://localhost:8545",
+                "localhost:4242",
+                "0x000000000022d473030f116ddee9f6b43ac78ba3",
+            )
+            .unwrap();
+            let fast_chain = Chain::new(
+                "base",
+                "http://localhost:8546",
+                "localhost:4243",
+                "0x000000000022d473030f116ddee9f6b43ac78ba3",
+            )
+            .unwrap();
+            let slow_pair = pair(tycho_common::models::Chain::Ethereum);
+            let fast_pair = pair(tycho_common::models::Chain::Base);
+
+            let (slow_block_tx, slow_block_rx) = watch::channel(Arc::new(None));
+            let (fast_block_tx, fast_block_rx) = watch::channel(Arc::new(None));
+            let (slow_finality_tx, slow_finality_rx) = watch::channel(FinalityHeads::default());
+            let (settings_tx, settings) = watch::channel(LiveSettings {
+                max_slippage_bps: 25,
+                congestion_risk_discount_bps: 25,
+                max_block_staleness: Duration::from_secs(60),
+            });
+            let controls = control::Controls::new(["test".to_string()], None);
+            let staleness =
+                |chain: &Chain| collector::Staleness::new(chain.clone(), watch::channel(None).1);
+
+            let handle = Builder {
+                id: "test".to_string(),
+                strategy: strategy::CrossChainSingleHop {
+                    slow_pair: slow_pair.clone(),
+                    slow_chain: slow_chain.clone(),
+                    fast_pair: fast_pair.clone(),
+                    fast_chain: fast_chain.clone(),
+                    slow_inventory: (whole(50), whole(100)),
+                    fast_inventory: (whole(200), whole(150)),
+                    binary_search_steps: 16,
+                    max_slippage_bps: 25,
+                    congestion_risk_discount_bps: 25,
+                    slow_spot_prices: Default::default(),
+                    fast_spot_prices: Default::default(),
+                },
+                slow_stream: PairStateStream::from_block_rx(
+                    slow_pair.clone(),
+                    Arc::new(PoolFilter::default()),
+                    slow_block_rx,
+                ),
+                fast_stream: PairStateStream::from_block_rx(
+                    fast_pair.clone(),
+                    Arc::new(PoolFilter::default()),
+                    fast_block_rx,
+                ),
+                fast_header_rx: watch::channel(None).1,
+                slow_finality_rx,
+                slow_confirmation,
+                slow_block_time: Duration::from_millis(20),
+                fast_block_time: Duration::from_secs(2),
+                max_block_latency_fraction: 1.0,
+                max_fast_state_age_blocks: 3,
+                slow_staleness: staleness(&slow_chain),
+                fast_staleness: staleness(&fast_chain),
+                settings,
+                max_onchain_deviation_bps: None,
+                max_block_skew: None,
+                mempool,
+                store: Arc::new(NoopStore),
+                curves: None,
+                controls: controls.switches("test"),
+                alerts: alert::Alerts::disabled(),
+                heartbeat: heartbeat::Heartbeat::default(),
+                slow_inventory: SharedInventory::new(Inventory::new([])),
+                fast_inventory: SharedInventory::new(Inventory::new([])),
+                min_gas_balances: (None, None),
+            }
+            .build()
+            .unwrap();
+            let signal_rx = handle.get_signal_rx();
+
+            Self {
+                handle,
+                signal_rx,
+                slow_block_tx,
+                fast_block_tx,
+                slow_finality_tx,
+                slow_pair,
+                fast_pair,
+                _settings_tx: settings_tx,
+                _controls: controls,
+            }
+        }
+
+        /// Sends a slow and a fast block whose pools are crossed, generating a signal
+        fn send_crossed_blocks(&self) {
+            self.slow_block_tx.send_replace(Arc::new(Some(block(
+                tycho_common::models::Chain::Ethereum,
+                &self.slow_pair,
+                SLOW_HEIGHT,
+                SLOW_POOL,
+                (10_000, 5_000),
+            ))));
+            self.fast_block_tx.send_replace(Arc::new(Some(block(
+                tycho_common::models::Chain::Base,
+                &self.fast_pair,
+                100,
+                FAST_POOL,
+                (10_000, 2_000),
+            ))));
+        }
+
+        async fn next_signal(&mut self, within: Duration) -> Option<signals::CrossChainSingleHop> {
+            tokio::time::timeout(within, self.signal_rx.recv())
+                .await
+                .ok()
+                .map(|signal| signal.unwrap())
+        }
+
+        async fn shutdown(mut self) {
+            self.handle.shutdown().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn generated_signal_is_emitted() {
+        let mut harness = Harness::spawn(Confirmation::Latest, None);
+
+        harness.send_crossed_blocks();
+
+        let signal = harness.next_signal(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(signal.slow_pool_id, PoolId::from(SLOW_POOL));
+        assert_eq!(signal.fast_pool_id, PoolId::from(FAST_POOL));
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn signal_is_held_until_its_slow_block_is_confirmed() {
+        let mut harness = Harness::spawn(Confirmation::Depth(2), None);
+
+        harness.send_crossed_blocks();
+        assert!(harness.next_signal(QUIET_PERIOD).await.is_none());
+
+        harness.slow_finality_tx.send_replace(FinalityHeads {
+            latest: SLOW_HEIGHT + 2,
+            safe: None,
+            finalized: None,
+        });
+        let signal = harness.next_signal(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(signal.slow_height, SLOW_HEIGHT);
+        harness.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn signal_is_dropped_on_a_competing_swap() {
+        let (subscription, swap_tx, mut watched_pool_rx) = mempool::Subscription::channel();
+        let mut harness = Harness::spawn(Confirmation::Depth(2), Some(subscription));
+
+        harness.send_crossed_blocks();
+        // the worker watches the fast pool once it holds a signal
+        watched_pool_rx
+            .wait_for(|pool| {
+                pool.as_ref()
+                    .is_some_and(|pool| pool.id == PoolId::from(FAST_POOL))
+            })
+            .await
+            .unwrap();
+
+        swap_tx
+            .send(mempool::CompetingSwap {
+                tx_hash: B256::ZERO,
+                pool_id: PoolId::from(FAST_POOL),
+                size: Some(1.0),
+            })
+            .unwrap();
+        watched_pool_rx.wait_for(Option::is_none).await.unwrap();
+
+        harness.slow_finality_tx.send_replace(FinalityHeads {
+            latest: SLOW_HEIGHT + 2,
+            safe: None,
+            finalized: None,
+        });
+        assert!(harness.next_signal(QUIET_PERIOD).await.is_none());
+        harness.shutdown().await;
+    }
 }
@@ -1,23 +1,22 @@
-use std::sync::OnceLock;
+use std::{net::SocketAddr, sync::OnceLock};
 
-use tracing::Subscriber;
-use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt as _};
+use color_eyre::eyre::{self, WrapErr as _};
+use kuma_core::telemetry::{self, OtlpExporter, TelemetrySettings};
+use metrics::Unit;
+use tracing::{Subscriber, info};
+use tracing_subscriber::layer::SubscriberExt as _;
 
 static TELEMETRY_INIT: OnceLock<()> = OnceLock::new();
 
-pub fn get_subscriber() -> impl Subscriber + Send + Sync {
-    // use the passed log level or default to RUST_LOG value
-    let filter = EnvFilter::from_default_env()
-        .add_directive("h2=warn".parse().expect("well-formed"))
-        .add_directive("hyper_util=warn".parse().expect("well-formed"))
-        .add_directive("tycho_client=warn".parse().expect("well-formed"))
-        .add_directive("tycho_simulation=warn".parse().expect("well-formed"));
-
-    let fmt_layer = fmt::layer().with_file(true).with_line_number(true);
-
-    tracing_subscriber::Registry::default()
-        .with(filter)
-        .with(fmt_layer)
+pub fn get_subscriber(
+    settings: &TelemetrySettings,
+    otlp: &OtlpExporter,
+) -> eyre::Result<impl Subscriber + Send + Sync> {
+    Ok(tracing_subscriber::Registry::default()
+        .with(settings.env_filter()?)
+        .with(telemetry::fmt_layer(settings.format))
+        .with(otlp.layer())
+        .with(settings.sentry.is_some().then(telemetry::sentry_layer)))
 }
 
 pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
@@ -26,3 +25,93 @@ pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
         .expect("global tracing subscriber already set");
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 }
+
+/// Serves everything recorded through the `metrics` facade, such as the `kuma_db_*` metrics,
+/// on `/metrics` for Prometheus to scrape. Must be called from within the tokio runtime.
+pub fn init_metrics(listen_addr: SocketAddr) -> eyre::Result<()> {
+    telemetry::prometheus_builder()?
+        .with_http_listener(listen_addr)
+        .install()
+        .wrap_err("failed to install prometheus exporter")?;
+    describe_metrics();
+    info!(addr = %listen_addr, "Serving prometheus metrics");
+
+    Ok(())
+}
+
+/// Help texts for the metrics recorded by the collectors and strategy workers.
+fn describe_metrics() {
+    metrics::describe_counter!(
+        "kuma_collector_blocks_total",
+        "Block updates received from Tycho, by chain"
+    );
+    metrics::describe_counter!(
+        "kuma_collector_stream_failures_total",
+        "Errors received on the Tycho stream, by chain"
+    );
+    metrics::describe_gauge!(
+        "kuma_collector_stream_lag_seconds",
+        Unit::Seconds,
+        "How far the latest block's timestamp is behind the wall clock, by chain"
+    );
+    metrics::describe_gauge!(
+        "kuma_collector_block_height",
+        "Height of the latest block update received from Tycho, by chain"
+    );
+    metrics::describe_gauge!(
+        "kuma_collector_rpc_head_lag_blocks",
+        "Blocks the RPC head is ahead of the latest Tycho block update, by chain"
+    );
+    metrics::describe_gauge!(
+        "kuma_collector_stream_messages_per_second",
+        "Tycho stream messages received per second over the last minute, by chain"
+    );
+    metrics::describe_histogram!(
+        "kuma_strategy_precompute_duration_seconds",
+        Unit::Seconds,
+        "Time spent precomputing trade sizes on a slow block, by strategy"
+    );
+    metrics::describe_counter!(
+        "kuma_strategy_signals_total",
+        "Signals generated, by strategy"
+    );
+    metrics::describe_histogram!(
+        "kuma_strategy_slow_block_to_precompute_seconds",
+        Unit::Seconds,
+        "Time from receiving a slow block to finishing its precompute, by chain"
+    );
+    metrics::describe_histogram!(
+        "kuma_strategy_fast_block_to_signal_seconds",
+        Unit::Seconds,
+        "Time from receiving a fast block to emitting the signal generated on it, by chain"
+    );
+    metrics::describe_histogram!(
+        "kuma_strategy_search_iterations",
+        "Binary search steps taken to find a signal's optimal trade size, by slow and fast chain"
+    );
+    metrics::describe_histogram!(
+        "kuma_strategy_crossed_pool_candidates",
+        "Slow and fast pool pairs compared before finding crossed ones, by slow and fast chain"
+    );
+    metrics::describe_counter!(
+        "kuma_strategy_simulation_failures_total",
+        "Fast chain swaps the signal search couldn't simulate, by pool and reason"
+    );
+    metrics::describe_counter!(
+        "kuma_strategy_skipped_pools_total",
+        "Slow chain pools left out of a precompute, by pool and reason"
+    );
+    metrics::describe_gauge!(
+        "kuma_worker_seconds_since_progress",
+        Unit::Seconds,
+        "Time since a worker last made progress, by worker such as collector:base or strategy:<id>"
+    );
+    metrics::describe_gauge!(
+        "kuma_rpc_endpoint_up",
+        "Whether an RPC endpoint answered its last call, by chain and position in the list"
+    );
+    metrics::describe_counter!(
+        "kuma_rpc_endpoint_failures_total",
+        "Connection errors of an RPC endpoint, by chain and position in the list"
+    );
+}
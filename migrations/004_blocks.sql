@@ -0,0 +1,17 @@
+-- Chain conditions of every collected block, to join signals and prices against
+
+CREATE TABLE IF NOT EXISTS blocks (
+    chain VARCHAR(50) NOT NULL,
+    height BIGINT NOT NULL,
+    hash VARCHAR(66) NOT NULL,
+    timestamp TIMESTAMP WITH TIME ZONE NOT NULL,
+    base_fee_per_gas BIGINT,
+    blob_base_fee TEXT,
+    native_balance TEXT,
+    -- token address -> balance of the tracked account
+    token_balances JSONB,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (chain, height)
+);
+
+CREATE INDEX IF NOT EXISTS idx_blocks_timestamp ON blocks(timestamp DESC);
@@ -0,0 +1,13 @@
+-- Fee history per chain, to evaluate signal profitability after the fact
+
+CREATE TABLE IF NOT EXISTS gas_prices (
+    chain VARCHAR(50) NOT NULL,
+    height BIGINT NOT NULL,
+    timestamp TIMESTAMP WITH TIME ZONE NOT NULL,
+    base_fee_per_gas BIGINT,
+    priority_fee_per_gas BIGINT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (chain, height)
+);
+
+CREATE INDEX IF NOT EXISTS idx_gas_prices_chain_timestamp ON gas_prices(chain, timestamp DESC);
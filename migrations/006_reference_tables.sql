@@ -0,0 +1,96 @@
+-- Normalize chains and tokens into reference tables shared by spot prices and signals
+
+CREATE TABLE IF NOT EXISTS chains (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(50) NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS tokens (
+    id SERIAL PRIMARY KEY,
+    chain_id INTEGER NOT NULL REFERENCES chains(id),
+    symbol VARCHAR(50) NOT NULL,
+    -- unknown for tokens backfilled from existing rows until they're written again
+    address VARCHAR(42),
+    decimals INTEGER,
+    UNIQUE (chain_id, symbol)
+);
+
+-- Id of a chain, inserting it if it's new
+CREATE OR REPLACE FUNCTION kuma_chain_id(chain_name TEXT) RETURNS INTEGER AS $$
+    INSERT INTO chains (name) VALUES (chain_name)
+    ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+    RETURNING id;
+$$ LANGUAGE SQL;
+
+-- Id of a token on a chain, inserting it or filling in its address and decimals if they're known
+CREATE OR REPLACE FUNCTION kuma_token_id(
+    chain_name TEXT,
+    token_symbol TEXT,
+    token_address TEXT DEFAULT NULL,
+    token_decimals INTEGER DEFAULT NULL
+) RETURNS INTEGER AS $$
+    INSERT INTO tokens (chain_id, symbol, address, decimals)
+    VALUES (kuma_chain_id(chain_name), token_symbol, token_address, token_decimals)
+    ON CONFLICT (chain_id, symbol) DO UPDATE SET
+        address = COALESCE(EXCLUDED.address, tokens.address),
+        decimals = COALESCE(EXCLUDED.decimals, tokens.decimals)
+    RETURNING id;
+$$ LANGUAGE SQL;
+
+-- Spot prices
+ALTER TABLE spot_prices
+    ADD COLUMN chain_id INTEGER REFERENCES chains(id),
+    ADD COLUMN token_a_id INTEGER REFERENCES tokens(id),
+    ADD COLUMN token_b_id INTEGER REFERENCES tokens(id);
+
+UPDATE spot_prices SET
+    chain_id = kuma_chain_id(chain),
+    token_a_id = kuma_token_id(chain, token_a_symbol),
+    token_b_id = kuma_token_id(chain, token_b_symbol);
+
+ALTER TABLE spot_prices
+    ALTER COLUMN chain_id SET NOT NULL,
+    ALTER COLUMN token_a_id SET NOT NULL,
+    ALTER COLUMN token_b_id SET NOT NULL,
+    DROP COLUMN chain,
+    DROP COLUMN token_a_symbol,
+    DROP COLUMN token_b_symbol;
+
+CREATE INDEX IF NOT EXISTS idx_spot_prices_chain_block ON spot_prices(chain_id, block_height DESC);
+CREATE INDEX IF NOT EXISTS idx_spot_prices_tokens ON spot_prices(token_a_id, token_b_id, created_at DESC);
+
+-- Signals
+ALTER TABLE signals
+    ADD COLUMN slow_chain_id INTEGER REFERENCES chains(id),
+    ADD COLUMN fast_chain_id INTEGER REFERENCES chains(id),
+    ADD COLUMN slow_swap_token_in_id INTEGER REFERENCES tokens(id),
+    ADD COLUMN slow_swap_token_out_id INTEGER REFERENCES tokens(id),
+    ADD COLUMN fast_swap_token_in_id INTEGER REFERENCES tokens(id),
+    ADD COLUMN fast_swap_token_out_id INTEGER REFERENCES tokens(id);
+
+UPDATE signals SET
+    slow_chain_id = kuma_chain_id(slow_chain),
+    fast_chain_id = kuma_chain_id(fast_chain),
+    slow_swap_token_in_id = kuma_token_id(slow_chain, slow_swap_token_in_symbol),
+    slow_swap_token_out_id = kuma_token_id(slow_chain, slow_swap_token_out_symbol),
+    fast_swap_token_in_id = kuma_token_id(fast_chain, fast_swap_token_in_symbol),
+    fast_swap_token_out_id = kuma_token_id(fast_chain, fast_swap_token_out_symbol);
+
+ALTER TABLE signals
+    ALTER COLUMN slow_chain_id SET NOT NULL,
+    ALTER COLUMN fast_chain_id SET NOT NULL,
+    ALTER COLUMN slow_swap_token_in_id SET NOT NULL,
+    ALTER COLUMN slow_swap_token_out_id SET NOT NULL,
+    ALTER COLUMN fast_swap_token_in_id SET NOT NULL,
+    ALTER COLUMN fast_swap_token_out_id SET NOT NULL,
+    DROP COLUMN slow_chain,
+    DROP COLUMN fast_chain,
+    DROP COLUMN slow_swap_token_in_symbol,
+    DROP COLUMN slow_swap_token_out_symbol,
+    DROP COLUMN fast_swap_token_in_symbol,
+    DROP COLUMN fast_swap_token_out_symbol;
+
+CREATE INDEX IF NOT EXISTS idx_signals_slow_chain ON signals(slow_chain_id);
+CREATE INDEX IF NOT EXISTS idx_signals_fast_chain ON signals(fast_chain_id);
+CREATE INDEX IF NOT EXISTS idx_signals_slow_tokens ON signals(slow_swap_token_in_id, slow_swap_token_out_id);
+CREATE INDEX IF NOT EXISTS idx_signals_fast_tokens ON signals(fast_swap_token_in_id, fast_swap_token_out_id);
@@ -0,0 +1,21 @@
+-- Compound indexes for filtering signals by route, pair, expected profit and time
+
+DROP INDEX IF EXISTS idx_signals_slow_chain;
+DROP INDEX IF EXISTS idx_signals_fast_chain;
+DROP INDEX IF EXISTS idx_signals_slow_tokens;
+DROP INDEX IF EXISTS idx_signals_fast_tokens;
+
+CREATE INDEX IF NOT EXISTS idx_signals_route_created_at
+    ON signals(slow_chain_id, fast_chain_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_signals_fast_chain_created_at
+    ON signals(fast_chain_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_signals_slow_tokens_created_at
+    ON signals(slow_swap_token_in_id, slow_swap_token_out_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_signals_fast_tokens_created_at
+    ON signals(fast_swap_token_in_id, fast_swap_token_out_id, created_at DESC);
+
+-- profits are stored as text, so range filters compare them as numerics
+CREATE INDEX IF NOT EXISTS idx_signals_expected_profit_a ON signals((expected_profit_a::NUMERIC));
+CREATE INDEX IF NOT EXISTS idx_signals_expected_profit_b ON signals((expected_profit_b::NUMERIC));
+
+CREATE INDEX IF NOT EXISTS idx_tokens_symbol ON tokens(symbol);
@@ -0,0 +1,38 @@
+-- Materialized views backing the dashboard, refreshed periodically by `database.materialized_views`
+
+-- Latest price quoted by each pool, whether it was the cheapest or the most expensive of its block
+CREATE MATERIALIZED VIEW IF NOT EXISTS latest_pool_spot_prices AS
+SELECT DISTINCT ON (chain_id, pool_id)
+    chain_id, token_a_id, token_b_id, pool_id, price, block_height, created_at
+FROM (
+    SELECT chain_id, token_a_id, token_b_id, min_pool_id AS pool_id, min_price AS price,
+        block_height, created_at
+    FROM spot_prices
+    UNION ALL
+    SELECT chain_id, token_a_id, token_b_id, max_pool_id AS pool_id, max_price AS price,
+        block_height, created_at
+    FROM spot_prices
+) pool_prices
+ORDER BY chain_id, pool_id, block_height DESC, created_at DESC;
+
+-- unique indexes let the views be refreshed concurrently
+CREATE UNIQUE INDEX IF NOT EXISTS idx_latest_pool_spot_prices_pool
+    ON latest_pool_spot_prices(chain_id, pool_id);
+CREATE INDEX IF NOT EXISTS idx_latest_pool_spot_prices_tokens
+    ON latest_pool_spot_prices(token_a_id, token_b_id);
+
+-- Signals per hour, chain route and slow swap direction
+CREATE MATERIALIZED VIEW IF NOT EXISTS hourly_signal_stats AS
+SELECT
+    date_trunc('hour', created_at) AS hour,
+    slow_chain_id, fast_chain_id,
+    slow_swap_token_in_id, slow_swap_token_out_id,
+    COUNT(*) AS signals
+FROM signals
+WHERE created_at IS NOT NULL
+GROUP BY hour, slow_chain_id, fast_chain_id, slow_swap_token_in_id, slow_swap_token_out_id;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_hourly_signal_stats_key ON hourly_signal_stats(
+    hour, slow_chain_id, fast_chain_id, slow_swap_token_in_id, slow_swap_token_out_id
+);
+CREATE INDEX IF NOT EXISTS idx_hourly_signal_stats_hour ON hourly_signal_stats(hour DESC);
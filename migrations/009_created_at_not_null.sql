@@ -0,0 +1,9 @@
+-- Every spot price and signal records when it was inserted, for querying by wall-clock time
+
+UPDATE spot_prices SET created_at = NOW() WHERE created_at IS NULL;
+ALTER TABLE spot_prices ALTER COLUMN created_at SET NOT NULL;
+
+UPDATE signals SET created_at = NOW() WHERE created_at IS NULL;
+ALTER TABLE signals ALTER COLUMN created_at SET NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_spot_prices_created_at ON spot_prices(created_at DESC);
@@ -0,0 +1,19 @@
+-- OHLC candles downsampled from per-block spot prices, see `database.candles`
+
+CREATE TABLE IF NOT EXISTS spot_price_candles (
+    chain_id INTEGER NOT NULL REFERENCES chains(id),
+    token_a_id INTEGER NOT NULL REFERENCES tokens(id),
+    token_b_id INTEGER NOT NULL REFERENCES tokens(id),
+    -- 60, 300 or 3600
+    interval_secs INTEGER NOT NULL,
+    bucket_start TIMESTAMP WITH TIME ZONE NOT NULL,
+    open DOUBLE PRECISION NOT NULL,
+    high DOUBLE PRECISION NOT NULL,
+    low DOUBLE PRECISION NOT NULL,
+    close DOUBLE PRECISION NOT NULL,
+    blocks BIGINT NOT NULL,
+    PRIMARY KEY (chain_id, token_a_id, token_b_id, interval_secs, bucket_start)
+);
+
+CREATE INDEX IF NOT EXISTS idx_spot_price_candles_interval_bucket
+    ON spot_price_candles(interval_secs, bucket_start DESC);
@@ -0,0 +1,14 @@
+-- Announce new signals on the `kuma_signals` channel with the row's id as payload, so the backend
+-- can stream them to clients instead of having them poll
+
+CREATE OR REPLACE FUNCTION kuma_notify_signal() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('kuma_signals', NEW.id::TEXT);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS signals_notify ON signals;
+CREATE TRIGGER signals_notify
+    AFTER INSERT ON signals
+    FOR EACH ROW EXECUTE FUNCTION kuma_notify_signal();
@@ -0,0 +1,21 @@
+-- Operator switches pausing a strategy (`strategy:<id>`) or signal emission altogether
+-- (`execution`). Changes are announced on the `kuma_controls` channel with the target as payload,
+-- so kumad can pick them up without a restart.
+
+CREATE TABLE IF NOT EXISTS controls (
+    target TEXT PRIMARY KEY,
+    paused BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE OR REPLACE FUNCTION kuma_notify_control() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('kuma_controls', NEW.target);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS controls_notify ON controls;
+CREATE TRIGGER controls_notify
+    AFTER INSERT OR UPDATE ON controls
+    FOR EACH ROW EXECUTE FUNCTION kuma_notify_control();
@@ -0,0 +1,9 @@
+-- Last progress of each kumad worker (`collector:<chain>`, `strategy:<id>`), reported every few
+-- seconds so the backend's `/admin/status` can show which ones are stuck. `last_progress_at` is
+-- NULL until the worker made progress, `reported_at` goes stale when kumad itself stops.
+
+CREATE TABLE IF NOT EXISTS worker_heartbeats (
+    worker TEXT PRIMARY KEY,
+    last_progress_at TIMESTAMP WITH TIME ZONE,
+    reported_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,17 @@
+-- The amount_in -> amount_out curves strategies precompute on every slow block, one row per pool
+-- and direction, recorded with `record_precompute_curves` for research. Amounts are decimal strings
+-- since they overflow BIGINT, `amounts_out[i]` is what selling `amounts_in[i]` would have bought.
+
+CREATE TABLE IF NOT EXISTS precompute_curves (
+    chain VARCHAR(50) NOT NULL,
+    block_height BIGINT NOT NULL,
+    pool_id TEXT NOT NULL,
+    token_in TEXT NOT NULL,
+    token_out TEXT NOT NULL,
+    amounts_in TEXT[] NOT NULL,
+    amounts_out TEXT[] NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (chain, block_height, pool_id, token_in)
+);
+
+CREATE INDEX IF NOT EXISTS idx_precompute_curves_created_at ON precompute_curves(created_at);
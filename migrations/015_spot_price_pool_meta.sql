@@ -0,0 +1,9 @@
+-- Protocol system and swap fee, in hundredths of a bip, of the pools quoting each spot price range's
+-- min and max, so prices can be told apart by pool kind. NULL for rows written before, and fees
+-- NULL where the pool's fee is dynamic or unknown.
+
+ALTER TABLE spot_prices
+    ADD COLUMN IF NOT EXISTS min_pool_protocol TEXT,
+    ADD COLUMN IF NOT EXISTS min_pool_fee_pips INTEGER,
+    ADD COLUMN IF NOT EXISTS max_pool_protocol TEXT,
+    ADD COLUMN IF NOT EXISTS max_pool_fee_pips INTEGER;
@@ -0,0 +1,23 @@
+-- Transactions `kuma execute` broadcast for a signal's legs, one row per leg. `pending` legs
+-- weren't mined before it stopped waiting, so what they realized is unknown. Amounts and the gas
+-- price are decimal strings since they overflow BIGINT. `signal_id` can't reference signals(id)
+-- since the hypertable's primary key also covers `created_at`.
+
+CREATE TABLE IF NOT EXISTS executions (
+    id BIGSERIAL PRIMARY KEY,
+    signal_id BIGINT NOT NULL,
+    leg TEXT NOT NULL CHECK (leg IN ('slow', 'fast')),
+    chain VARCHAR(50) NOT NULL,
+    tx_hash VARCHAR(66) NOT NULL UNIQUE,
+    status TEXT NOT NULL CHECK (status IN ('pending', 'mined', 'reverted')),
+    block_height BIGINT,
+    token_out_symbol VARCHAR(50) NOT NULL,
+    expected_amount_out TEXT NOT NULL,
+    amount_out TEXT,
+    gas_used BIGINT,
+    gas_price TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_executions_signal_id ON executions(signal_id);
+CREATE INDEX IF NOT EXISTS idx_executions_created_at ON executions(created_at DESC);
@@ -1,9 +1,13 @@
 -- Insert mock data for spot prices for testing purposes
 
 INSERT INTO spot_prices (
-    token_a_symbol, token_b_symbol,
-    block_height, min_price, max_price, min_pool_id, max_pool_id, chain
-) VALUES
+    chain_id, token_a_id, token_b_id,
+    block_height, min_price, max_price, min_pool_id, max_pool_id
+)
+SELECT
+    kuma_chain_id(chain), kuma_token_id(chain, token_a_symbol), kuma_token_id(chain, token_b_symbol),
+    block_height, min_price, max_price, min_pool_id, max_pool_id
+FROM (VALUES
     -- WETH/USDC pair on Ethereum
     ('WETH', 'USDC',
      19500000, 3700, 3703, '0x123', '0x456', 'ethereum'),
@@ -18,4 +22,8 @@ INSERT INTO spot_prices (
     ('PEPE', 'WETH',
      19500001, 0.0012, 0.0015, '0x456', '0x123', 'base'),
     ('PEPE', 'WETH',
-     19500002, 0.0009, 0.0012, '0x456', '0x234', 'base');
+     19500002, 0.0009, 0.0012, '0x456', '0x234', 'base')
+) AS v (
+    token_a_symbol, token_b_symbol,
+    block_height, min_price, max_price, min_pool_id, max_pool_id, chain
+);
@@ -1,15 +1,27 @@
 -- Insert mock signals for testing purposes
 
 INSERT INTO signals (
-    slow_chain, slow_height, slow_pool_id,
-    fast_chain, fast_height, fast_pool_id,
-    slow_swap_token_in_symbol, slow_swap_token_out_symbol,
+    slow_chain_id, slow_height, slow_pool_id,
+    fast_chain_id, fast_height, fast_pool_id,
+    slow_swap_token_in_id, slow_swap_token_out_id,
     slow_swap_amount_in, slow_swap_amount_out, slow_swap_gas_cost,
-    fast_swap_token_in_symbol, fast_swap_token_out_symbol,
+    fast_swap_token_in_id, fast_swap_token_out_id,
+    fast_swap_amount_in, fast_swap_amount_out, fast_swap_gas_cost,
+    surplus_a, surplus_b, expected_profit_a, expected_profit_b,
+    max_slippage_bps, congestion_risk_discount_bps
+)
+SELECT
+    kuma_chain_id(slow_chain), slow_height, slow_pool_id,
+    kuma_chain_id(fast_chain), fast_height, fast_pool_id,
+    kuma_token_id(slow_chain, slow_swap_token_in_symbol),
+    kuma_token_id(slow_chain, slow_swap_token_out_symbol),
+    slow_swap_amount_in, slow_swap_amount_out, slow_swap_gas_cost,
+    kuma_token_id(fast_chain, fast_swap_token_in_symbol),
+    kuma_token_id(fast_chain, fast_swap_token_out_symbol),
     fast_swap_amount_in, fast_swap_amount_out, fast_swap_gas_cost,
     surplus_a, surplus_b, expected_profit_a, expected_profit_b,
     max_slippage_bps, congestion_risk_discount_bps
-) VALUES
+FROM (VALUES
     (
         'ethereum', 18765432, '0x1f9840a85d5aF5bf1D1762F925BDADdC4201F984',
         'base', 9876543, '0x2f9840a85d5aF5bf1D1762F925BDADdC4201F985',
@@ -39,4 +51,14 @@ INSERT INTO signals (
         '47', '5100', '30',
         '400', '400', '350', '350',
         100, 40
-    );
+    )
+) AS v (
+    slow_chain, slow_height, slow_pool_id,
+    fast_chain, fast_height, fast_pool_id,
+    slow_swap_token_in_symbol, slow_swap_token_out_symbol,
+    slow_swap_amount_in, slow_swap_amount_out, slow_swap_gas_cost,
+    fast_swap_token_in_symbol, fast_swap_token_out_symbol,
+    fast_swap_amount_in, fast_swap_amount_out, fast_swap_gas_cost,
+    surplus_a, surplus_b, expected_profit_a, expected_profit_b,
+    max_slippage_bps, congestion_risk_discount_bps
+);